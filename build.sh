@@ -37,6 +37,11 @@ fi
 info "Copying web assets..."
 cp web/index.html "$DIST/index.html"
 cp web/worker.js "$DIST/worker.js"
+cp web/embeddings_worker.js "$DIST/embeddings_worker.js"
+cp web/llm_worker.js "$DIST/llm_worker.js"
+cp web/js_worker.js "$DIST/js_worker.js"
+cp web/sql_worker.js "$DIST/sql_worker.js"
+cp web/api_sw.js "$DIST/api_sw.js"
 cp -r web/fonts/* "$DIST/" 2>/dev/null || true
 
 ok "Build complete → $DIST/"
@@ -1,23 +1,140 @@
 //! Main egui application — composes all panels and manages agent runtime.
 
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use egui::{self, CentralPanel, SidePanel, TopBottomPanel, RichText, Vec2};
+use futures::future::LocalBoxFuture;
+use wasm_bindgen::JsCast;
 
-use agent_core::event_bus::EventBus;
-use agent_core::ports::{LlmPort, ShellPort, VfsPort};
+use agent_core::config_store::{CONFIG_KEY, ConfigStore};
+use agent_core::demo::DemoLlm;
+use agent_core::event_bus::{DEFAULT_DELTA_COALESCE_CHARS, EventBus};
+use agent_core::cache::CachedLlm;
+use agent_core::fallback::{FallbackEntry, FallbackLlm};
+use agent_core::memory::{MemoryStore, SESSION_SUMMARY_TAG, summarize_conversation};
+use agent_core::models::ModelCatalog;
+use agent_core::prompts::PromptLibrary;
+use agent_core::replay::{RecordingLlm, RecordingShell, RecordingStore, replay_session};
+use agent_core::ports::{LlmPort, ShellPort, StoragePort, VfsPort};
+use agent_core::retrieval::WorkspaceRetriever;
+use agent_core::tools::script_command;
 use agent_core::runtime::AgentRuntime;
-use agent_platform::llm::OpenAiCompatProvider;
+use agent_core::snapshots::SnapshotStore;
+use agent_core::templates::{TEMPLATES, WorkspaceTemplate};
+use agent_core::undo::{UndoJournal, UndoScope, format_undone};
+use agent_core::activity::{ActivityLog, TurnRecorder};
+use agent_core::eval;
+use agent_core::batch;
+use agent_core::orchestrator;
+use agent_core::web::WebReader;
+use agent_core::endpoint::{self, CHAT_COMPLETIONS_PATH};
+use agent_core::js::JsRunner;
+use agent_core::sql::SqlDatabases;
+use agent_core::usage::UsageLedger;
+use agent_core::perf::{FrameSample, FrameStats, StorageMeter};
+use agent_platform::crash;
+use agent_platform::diagram;
+use agent_platform::endpoint::{EndpointHandler, LocalEndpoint};
+use agent_platform::embeddings::{DEFAULT_EMBEDDING_MODEL, TransformersEmbeddings};
+use agent_platform::fetch::BrowserFetch;
+use agent_platform::js::WorkerJs;
+use agent_platform::sql::SqlJs;
+use agent_platform::llm::{LlmWorker, OpenAiCompatProvider, WebLlmProvider};
+use agent_platform::session::SessionManager;
 use agent_platform::shell::WasmerShellAdapter;
-use agent_platform::storage::MemoryStorage;
+use agent_platform::share;
+use agent_platform::vault::{KeyVault, VaultKey};
 use agent_platform::vfs::StorageVfs;
-use agent_types::config::AgentConfig;
-use agent_ui::panels::{chat, terminal, settings};
-use agent_ui::state::UiState;
+use agent_platform::workspace::WorkspaceManager;
+use agent_types::activity::ActivityStats;
+use agent_types::storage::StorageBreakdown;
+use agent_types::error::ErrorKind;
+use agent_types::config::{AgentConfig, LlmConfig, LlmProvider, ResponseCacheConfig, ShellConfig, ThemePreset};
+use agent_types::event::AgentEvent;
+use agent_types::message::Message;
+use agent_types::migration::{RestoredConfig, config_changes, export_config, keep_secrets, restore_config};
+use agent_types::pricing::{UsageTotals, format_usd};
+use agent_types::prompt::PromptSnippet;
+use agent_types::replay::{Recording, TapeEntry};
+use agent_types::session::{Session, SessionConfig, SessionRecovery, SessionSummary};
+use agent_types::share::{LONG_SHARE_LINK_CHARS, SharedTranscript};
+use agent_types::snapshot::Snapshot;
+use agent_types::tool::{DirEntry, ExecOptions, ExecResult};
+use agent_types::vault::VaultKeys;
+use agent_types::workspace::Workspace;
+use crate::file_transfer;
+use agent_ui::panels::{
+    chat, logs, stats, terminal,
+    diagnostics::{self, Cleanup, Retained},
+    perf::{self, PerfCounts},
+    batch as batch_panel,
+    eval as eval_panel,
+    editor::{self, SaveRequest},
+    explorer::{self, FileExplorer},
+    health::{self, ShellHealth},
+    plan,
+    prompts::PromptAction,
+    settings::{self, ImportPreview, ModelOptions},
+    vault::{self, VaultAction, VaultStatus},
+    viewer::{self, FileLoad},
+};
+use agent_ui::diagrams::{DiagramKind, DiagramRequest};
+use agent_ui::images::MAX_CHAT_IMAGE_BYTES;
+use agent_ui::state::{TerminalLine, UiState};
 use agent_ui::theme;
 
+use crate::{notify, router, tab_status};
+
 const WORKSPACE_ROOT: &str = "/workspace";
+/// Script run in the terminal when the shell becomes ready
+const RC_PATH: &str = "/workspace/.agentrc";
+
+/// An editor save and how it went
+type SaveOutcome = (SaveRequest, Result<(), String>);
+/// A large paste written to the workspace: path, text and outcome
+type PasteOutcome = (String, String, Result<(), String>);
+/// A finished directory listing for the file tree
+type ListingOutcome = (String, Result<Vec<DirEntry>, String>);
+/// A finished read of an image shown in the chat
+type ImageOutcome = (String, Result<Vec<u8>, String>);
+/// A finished save of a chat table, by path
+type TableOutcome = (String, Result<(), String>);
+/// A finished mermaid render: the pixels' size and RGBA
+type DiagramOutcome = (DiagramRequest, Result<([usize; 2], Vec<u8>), String>);
+/// Font files fetched, by URL, or why none loaded
+type FontOutcome = Result<Vec<(String, Vec<u8>)>, String>;
+
+/// How a turn ended: nothing, or the error shown to the user
+type TurnOutcome = Result<(), String>;
+
+type BatchLoad = Result<Vec<String>, String>;
+
+/// Token usage and estimated cost for the top bar
+#[derive(Default)]
+struct CostSummary {
+    /// The latest turn in this session, once one has run
+    turn: Option<UsageTotals>,
+    session: UsageTotals,
+    all_time: UsageTotals,
+}
+
+/// A finished session replay
+struct ReplayOutcome {
+    history: Vec<Message>,
+    /// Why the replay left the recording, if it did
+    divergence: Option<String>,
+}
+
+/// A finished key vault operation
+enum VaultOutcome {
+    /// Whether a vault exists, checked at startup
+    Found(bool),
+    Created(VaultKey),
+    Unlocked(VaultKey, VaultKeys),
+    Removed,
+    Failed(String),
+}
 
 /// The main application state
 pub struct AgentApp {
@@ -28,158 +145,2583 @@ pub struct AgentApp {
     runtime: Rc<RefCell<AgentRuntime>>,
     /// LLM provider — recreated when config changes
     llm: Rc<dyn LlmPort>,
+    /// Network worker shared by the HTTP-based providers
+    llm_worker: Option<Rc<LlmWorker>>,
+    /// Provider and settings the local endpoint answers with, kept in step
+    /// with `llm`
+    endpoint_llm: Rc<RefCell<(Rc<dyn LlmPort>, LlmConfig)>>,
+    /// The local endpoint while it is on
+    endpoint: Option<LocalEndpoint>,
+    /// The local endpoint is being started
+    endpoint_starting: bool,
+    /// Started local endpoint, or why it couldn't be
+    endpoint_start: Rc<RefCell<Option<Result<LocalEndpoint, String>>>>,
     /// Shell adapter
     shell: Rc<dyn ShellPort>,
-    /// Virtual filesystem
+    /// The worker-backed shell while it is in use, watched so a worker that
+    /// never starts can be swapped for the stub
+    shell_adapter: Option<Rc<WasmerShellAdapter>>,
+    /// Virtual filesystem of the active workspace
     vfs: Rc<dyn VfsPort>,
-    /// First frame flag for theme + font setup
-    first_frame: bool,
-    /// Whether CJK font has been loaded
-    font_loaded: Rc<RefCell<bool>>,
+    /// Root key-value storage: memories, model lists, workspace records
+    storage: Rc<dyn StoragePort>,
+    /// Storage namespace of the active workspace (VFS, retrieval index)
+    workspace_storage: Rc<dyn StoragePort>,
+    /// Workspace persistence
+    workspaces: Rc<WorkspaceManager>,
+    /// The active workspace
+    workspace: Workspace,
+    /// Cached workspace list for the top bar selector
+    workspace_list: Rc<RefCell<Vec<Workspace>>>,
+    /// Workspace created in the background, to switch to on the next frame
+    workspace_request: Rc<RefCell<Option<Workspace>>>,
+    /// Open create/rename/delete workspace dialog
+    workspace_dialog: Option<WorkspaceDialog>,
+    /// Saved settings
+    config_store: Rc<ConfigStore>,
+    /// Finished startup load of the saved settings; the inner `None` means
+    /// nothing was saved yet
+    config_load: Rc<RefCell<Option<Option<RestoredConfig>>>>,
+    /// The saved settings have been applied, so changes may be saved
+    config_restored: bool,
+    /// Problems found restoring the saved settings, until dismissed
+    config_warnings: Vec<String>,
+    /// Contents of a settings file picked for import, or why it couldn't be read
+    config_import: Rc<RefCell<Option<Result<String, String>>>>,
+    /// Memory/retrieval settings changed while a turn held the runtime
+    runtime_settings_pending: bool,
+    /// The workspace's rc script runs once the shell is ready
+    rc_pending: bool,
+    /// Rc script read from the VFS, waiting for the terminal to be free
+    rc_script: Rc<RefCell<Option<String>>>,
+    /// Theme preset applied to the context, None before the first frame
+    applied_theme: Option<ThemePreset>,
+    /// Scale and screen reader settings last applied to the context; the
+    /// scale is only reapplied when the setting changes, so browser-style
+    /// keyboard zoom still works in between
+    applied_accessibility: Option<(f32, bool)>,
+    /// Fonts fetched for CJK text
+    font_load: Rc<RefCell<Option<FontOutcome>>>,
+    /// Mirrors `config.ui.notify_on_turn_end` for the EventBus listener
+    notify_enabled: Rc<Cell<bool>>,
+    /// Mirrors `config.shell` for the shell adapter
+    shell_limits: Rc<Cell<ShellConfig>>,
+    /// Session persistence (per workspace if it keeps sessions separate)
+    sessions: Rc<SessionManager>,
+    /// The active session (saved after every turn)
+    session: Rc<RefCell<Session>>,
+    /// Cached session list for the top bar selector
+    session_list: Rc<RefCell<Vec<SessionSummary>>>,
+    /// Session id requested by the router (hash change / back / forward)
+    route_request: Rc<RefCell<Option<String>>>,
+    /// Session loaded from storage, waiting to be applied on the next frame
+    loaded_session: Rc<RefCell<Option<Session>>>,
+    /// Share link payload from the URL, to open read-only
+    share_request: Rc<RefCell<Option<String>>>,
+    /// Conversation unpacked from a share link, or why it couldn't be
+    shared_load: Rc<RefCell<Option<Result<SharedTranscript, String>>>>,
+    /// The session packed into a share link, or why it couldn't be
+    share_link: Rc<RefCell<Option<Result<String, String>>>>,
+    /// "Archive this conversation?" dialog is open
+    archive_prompt: bool,
+    /// Open "New session" dialog with its overrides
+    new_session_dialog: Option<NewSessionDialog>,
+    /// Overrides for the next fresh session to load
+    pending_session_config: Option<SessionConfig>,
+    /// A summary is being generated before switching sessions
+    archiving: Rc<Cell<bool>>,
+    /// Cached provider model lists
+    model_catalog: Rc<ModelCatalog>,
+    /// Model list shown in settings
+    model_options: Rc<RefCell<ModelOptions>>,
+    /// Provider, base URL and API key the model list was loaded for
+    models_for: Option<(LlmProvider, Option<String>, String, Option<String>)>,
+    /// Bumped per model list load so a slow, superseded fetch is ignored
+    models_generation: Rc<Cell<u32>>,
+    /// File read for the viewer, waiting to be applied on the next frame
+    viewer_load: Rc<RefCell<Option<FileLoad>>>,
+    /// Finished editor save, waiting to be applied on the next frame
+    editor_save: Rc<RefCell<Option<SaveOutcome>>>,
+    /// Large pastes saved as workspace files
+    paste_saves: Rc<RefCell<Vec<PasteOutcome>>>,
+    /// Message whose turn couldn't reach the provider, waiting to go
+    /// back into the outbox
+    unsent: Rc<RefCell<Option<chat::Outgoing>>>,
+    /// Recent frame times and events per frame, for the perf overlay
+    frame_stats: FrameStats,
+    /// Storage operations through the root storage
+    storage_meter: Rc<StorageMeter>,
+    /// Prompt of the turn in flight, which the session doesn't hold until
+    /// the turn ends
+    turn_prompt: Rc<RefCell<Option<String>>>,
+    /// Directory listings for the file tree, applied on the next frame
+    file_listings: Rc<RefCell<Vec<ListingOutcome>>>,
+    /// Images read for the chat, applied on the next frame
+    image_loads: Rc<RefCell<Vec<ImageOutcome>>>,
+    /// Chat tables saved as files, opened on the next frame
+    table_saves: Rc<RefCell<Vec<TableOutcome>>>,
+    /// Diagrams rendered for the chat, applied on the next frame
+    diagram_renders: Rc<RefCell<Vec<DiagramOutcome>>>,
+    /// Saved prompt snippets
+    prompt_library: Rc<PromptLibrary>,
+    /// Snippets loaded at startup, waiting to be handed to the picker
+    prompt_load: Rc<RefCell<Option<Vec<PromptSnippet>>>>,
+    /// Outcome of an undo or snapshot restore started from the top bar,
+    /// for the status line
+    undo_result: Rc<RefCell<Option<String>>>,
+    /// Per-turn snapshots of the active workspace
+    snapshots: Rc<SnapshotStore>,
+    /// Cached snapshot list for the Undo menu, newest first
+    snapshot_list: Rc<RefCell<Vec<Snapshot>>>,
+    /// Passphrase-protected API key storage
+    key_vault: Rc<KeyVault>,
+    /// Derived vault key while the vault is unlocked
+    vault_key: Option<Rc<VaultKey>>,
+    /// Decrypted keys while the vault is unlocked
+    vault_keys: VaultKeys,
+    /// Provider whose vault key is in `config.llm.api_key`
+    vault_provider: LlmProvider,
+    /// Finished vault operation, waiting to be applied on the next frame
+    vault_result: Rc<RefCell<Option<VaultOutcome>>>,
+    /// All-time usage, across sessions and workspaces
+    usage_ledger: Rc<UsageLedger>,
+    /// Usage shown in the top bar
+    costs: Rc<RefCell<CostSummary>>,
+    /// Per-turn activity, for the stats panel
+    activity_log: Rc<ActivityLog>,
+    /// Record of the turn in progress, fed from the event bus
+    turn_recorder: Rc<RefCell<TurnRecorder>>,
+    /// Stats loaded from the activity log, waiting to be shown
+    stats_load: Rc<RefCell<Option<ActivityStats>>>,
+    /// Storage measured for the diagnostics section
+    storage_breakdown: Rc<RefCell<Option<StorageBreakdown>>>,
+    /// Outcome of a storage cleanup from the diagnostics section
+    cleanup_done: Rc<RefCell<Option<Result<String, String>>>>,
+    /// Finished command typed into the terminal
+    terminal_result: Rc<RefCell<Option<Result<ExecResult, String>>>>,
+    /// Terminal line echoing the command in `terminal_result`
+    terminal_header: usize,
+    /// Finished eval run: summary line, or why it failed
+    eval_result: Rc<RefCell<Option<Result<String, String>>>>,
+    /// Prompts read from a batch file, or why they couldn't be
+    batch_load: Rc<RefCell<Option<BatchLoad>>>,
+    /// Finished turn of the running batch prompt
+    batch_turn: Rc<RefCell<Option<TurnOutcome>>>,
+    /// Recorded LLM and shell responses, per session
+    recordings: Rc<RecordingStore>,
+    /// Recording loaded for replay: the fresh session it will run in, once
+    /// that session is active
+    replay_pending: Option<(String, Recording)>,
+    replay_load: Rc<RefCell<Option<Result<Recording, String>>>>,
+    /// Finished replay, waiting to be shown on the next frame
+    replay_done: Rc<RefCell<Option<ReplayOutcome>>>,
+    /// A replay that found the runtime taken, to be started again
+    replay_requeue: Rc<RefCell<Option<Recording>>>,
+    /// A replay is running
+    replaying: Rc<Cell<bool>>,
 }
 
-impl AgentApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let config = AgentConfig::default();
-        let event_bus = EventBus::new();
+impl AgentApp {
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        storage: Rc<dyn StoragePort>,
+        storage_meter: Rc<StorageMeter>,
+        workspace: Workspace,
+    ) -> Self {
+        let config = AgentConfig::default();
+        let event_bus = EventBus::with_delta_coalescing(DEFAULT_DELTA_COALESCE_CHARS);
+
+        let notify_enabled = Rc::new(Cell::new(config.ui.notify_on_turn_end));
+        notify::install(&event_bus, notify_enabled.clone());
+        tab_status::install(&event_bus);
+        let turn_recorder = Rc::new(RefCell::new(TurnRecorder::default()));
+        let recorder = turn_recorder.clone();
+        event_bus.subscribe(move |event| recorder.borrow_mut().observe(event));
+
+        // Create the agent runtime
+        let mut runtime = AgentRuntime::new(config.clone(), event_bus.clone());
+
+        // Create platform adapters; LLM requests fall back to in-page
+        // fetch if the network worker can't be started
+        let llm_worker = match LlmWorker::new() {
+            Ok(w) => Some(Rc::new(w)),
+            Err(e) => {
+                log::warn!("LLM worker unavailable: {}. Using in-page fetch.", e);
+                None
+            }
+        };
+        let llm = with_cache(build_llm(&config.llm, &event_bus, &llm_worker), &config.llm, &config.cache, &storage);
+
+        // Try to create shell adapter, fall back to a stub if Worker creation fails
+        let shell_limits = Rc::new(Cell::new(config.shell));
+        let (shell, shell_adapter): (Rc<dyn ShellPort>, _) = match WasmerShellAdapter::new(shell_limits.clone()) {
+            Ok(s) => {
+                let adapter = Rc::new(s);
+                (adapter.clone(), Some(adapter))
+            }
+            Err(e) => {
+                log::warn!("Shell adapter unavailable: {}. Using stub.", e);
+                event_bus.emit(AgentEvent::ShellUnavailable { reason: STUB_SHELL_HINT.to_string() });
+                (Rc::new(StubShell::new(STUB_SHELL_HINT)), None)
+            }
+        };
+
+        let workspaces = Rc::new(WorkspaceManager::new(storage.clone()));
+        let workspace_storage = workspaces.storage_for(&workspace);
+        runtime.set_undo(Some(Rc::new(UndoJournal::new(workspace_storage.clone()))));
+        let snapshots = Rc::new(SnapshotStore::new(workspace_storage.clone(), WORKSPACE_ROOT));
+        runtime.set_snapshots(Some(snapshots.clone()));
+        let vfs = Rc::new(StorageVfs::new(workspace_storage.clone()));
+        let sessions = Rc::new(SessionManager::new(workspaces.session_storage_for(&workspace)));
+        let model_catalog = Rc::new(ModelCatalog::new(storage.clone()));
+        let storage_for_config = storage.clone();
+        let storage_for_prompts = storage.clone();
+        let storage_for_vault = storage.clone();
+        let storage_for_usage = storage.clone();
+        let storage_for_activity = storage.clone();
+        let workspace_storage_for_recordings = workspace_storage.clone();
+
+        // Restore the session named in the URL, or start a fresh one; a
+        // share link is shown over the fresh one
+        let route_request = Rc::new(RefCell::new(router::current_session()));
+        let share_request = Rc::new(RefCell::new(router::current_share()));
+        let session = Session::new(new_session_id());
+        if route_request.borrow().is_none() && share_request.borrow().is_none() {
+            router::replace_with(&session.id);
+        }
+        router::listen(route_request.clone(), share_request.clone(), cc.egui_ctx.clone());
+
+        let endpoint_llm = Rc::new(RefCell::new((llm.clone(), config.llm.clone())));
+
+        let mut app = Self {
+            ui_state: UiState::new(),
+            config,
+            event_bus,
+            runtime: Rc::new(RefCell::new(runtime)),
+            endpoint_llm,
+            endpoint: None,
+            endpoint_starting: false,
+            endpoint_start: Rc::new(RefCell::new(None)),
+            llm,
+            llm_worker,
+            shell,
+            shell_adapter,
+            vfs: vfs.clone(),
+            storage,
+            workspace_storage,
+            workspaces,
+            workspace,
+            workspace_list: Rc::new(RefCell::new(Vec::new())),
+            workspace_request: Rc::new(RefCell::new(None)),
+            workspace_dialog: None,
+            config_store: Rc::new(ConfigStore::new(storage_for_config)),
+            config_load: Rc::new(RefCell::new(None)),
+            config_restored: false,
+            config_warnings: Vec::new(),
+            config_import: Rc::new(RefCell::new(None)),
+            runtime_settings_pending: false,
+            rc_pending: true,
+            rc_script: Rc::new(RefCell::new(None)),
+            applied_theme: None,
+            applied_accessibility: None,
+            font_load: Rc::new(RefCell::new(None)),
+            notify_enabled,
+            shell_limits,
+            sessions,
+            session: Rc::new(RefCell::new(session)),
+            session_list: Rc::new(RefCell::new(Vec::new())),
+            route_request,
+            loaded_session: Rc::new(RefCell::new(None)),
+            share_request,
+            shared_load: Rc::new(RefCell::new(None)),
+            share_link: Rc::new(RefCell::new(None)),
+            archive_prompt: false,
+            new_session_dialog: None,
+            pending_session_config: None,
+            archiving: Rc::new(Cell::new(false)),
+            model_catalog,
+            model_options: Rc::new(RefCell::new(ModelOptions::default())),
+            models_for: None,
+            models_generation: Rc::new(Cell::new(0)),
+            viewer_load: Rc::new(RefCell::new(None)),
+            file_listings: Rc::new(RefCell::new(Vec::new())),
+            image_loads: Rc::new(RefCell::new(Vec::new())),
+            table_saves: Rc::new(RefCell::new(Vec::new())),
+            diagram_renders: Rc::new(RefCell::new(Vec::new())),
+            editor_save: Rc::new(RefCell::new(None)),
+            paste_saves: Rc::new(RefCell::new(Vec::new())),
+            unsent: Rc::new(RefCell::new(None)),
+            turn_prompt: Rc::new(RefCell::new(None)),
+            frame_stats: FrameStats::default(),
+            storage_meter,
+            prompt_library: Rc::new(PromptLibrary::new(storage_for_prompts)),
+            prompt_load: Rc::new(RefCell::new(None)),
+            undo_result: Rc::new(RefCell::new(None)),
+            snapshots,
+            snapshot_list: Rc::new(RefCell::new(Vec::new())),
+            key_vault: Rc::new(KeyVault::new(storage_for_vault)),
+            vault_key: None,
+            vault_keys: VaultKeys::default(),
+            vault_provider: LlmProvider::DeepSeek,
+            vault_result: Rc::new(RefCell::new(None)),
+            usage_ledger: Rc::new(UsageLedger::new(storage_for_usage)),
+            costs: Rc::new(RefCell::new(CostSummary::default())),
+            activity_log: Rc::new(ActivityLog::new(storage_for_activity)),
+            turn_recorder,
+            stats_load: Rc::new(RefCell::new(None)),
+            storage_breakdown: Rc::new(RefCell::new(None)),
+            cleanup_done: Rc::new(RefCell::new(None)),
+            terminal_result: Rc::new(RefCell::new(None)),
+            terminal_header: 0,
+            eval_result: Rc::new(RefCell::new(None)),
+            batch_load: Rc::new(RefCell::new(None)),
+            batch_turn: Rc::new(RefCell::new(None)),
+            recordings: Rc::new(RecordingStore::new(workspace_storage_for_recordings)),
+            replay_pending: None,
+            replay_load: Rc::new(RefCell::new(None)),
+            replay_done: Rc::new(RefCell::new(None)),
+            replay_requeue: Rc::new(RefCell::new(None)),
+            replaying: Rc::new(Cell::new(false)),
+        };
+
+        // Initialize default workspace
+        Self::init_workspace(vfs);
+        app.ui_state.explorer = FileExplorer::new(WORKSPACE_ROOT);
+        app.ui_state.terminal.cwd = WORKSPACE_ROOT.to_string();
+        app.refresh_session_list();
+        app.refresh_workspace_list();
+        app.refresh_snapshot_list();
+        app.load_config(&cc.egui_ctx);
+        app.load_prompts(&cc.egui_ctx);
+        app.check_vault(&cc.egui_ctx);
+        app.load_usage_total(&cc.egui_ctx);
+        app.apply_runtime_settings();
+        app.sync_model_list(&cc.egui_ctx);
+        app.set_crash_recovery();
+
+        app
+    }
+
+    /// What a panic writes out: the active session, with the prompt of a
+    /// turn still running (the turn holds the runtime, so its other new
+    /// messages can't be reached).
+    fn set_crash_recovery(&self) {
+        let session = self.session.clone();
+        let runtime = self.runtime.clone();
+        let turn_prompt = self.turn_prompt.clone();
+        let workspace_id = self.workspace.id.clone();
+        crash::set_recovery(move || {
+            let mut session = session.try_borrow().ok()?.clone();
+            if let Ok(runtime) = runtime.try_borrow() {
+                session.messages = runtime.history().to_vec();
+            } else if let Some(prompt) = turn_prompt.try_borrow().ok()?.clone() {
+                session.messages.push(Message::user(prompt));
+            }
+            Some(SessionRecovery { workspace_id: workspace_id.clone(), session })
+        });
+    }
+
+    /// Create default workspace directories in VFS
+    fn init_workspace(vfs: Rc<dyn VfsPort>) {
+        wasm_bindgen_futures::spawn_local(async move {
+            let dirs = [
+                WORKSPACE_ROOT,
+                &format!("{}/home", WORKSPACE_ROOT),
+                &format!("{}/tmp", WORKSPACE_ROOT),
+                &format!("{}/src", WORKSPACE_ROOT),
+            ];
+            for dir in &dirs {
+                let _ = vfs.mkdir(dir).await;
+            }
+            // Write a welcome README (storage may be persistent — don't clobber edits)
+            let readme_path = format!("{}/README.md", WORKSPACE_ROOT);
+            if !vfs.exists(&readme_path).await.unwrap_or(false) {
+                let readme = "# WASM Agent Workspace\n\n\
+                    This is your default workspace.\n\
+                    Files created by the agent will be stored here.\n";
+                let _ = vfs.write_file(&readme_path, readme.as_bytes()).await;
+            }
+            log::info!("Workspace initialised at {}", WORKSPACE_ROOT);
+        });
+    }
+
+    /// Fetch the configured font files. Files that fail are skipped; it is
+    /// an error only if none load.
+    fn fetch_fonts(&self, ctx: &egui::Context) {
+        let urls: Vec<String> = self
+            .config
+            .ui
+            .fonts
+            .urls
+            .iter()
+            .map(|u| u.trim())
+            .filter(|u| !u.is_empty())
+            .map(String::from)
+            .collect();
+        let slot = self.font_load.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let mut fonts = Vec::new();
+            let mut last_error = "No font URLs are configured".to_string();
+            for url in urls {
+                match file_transfer::fetch_bytes(&url).await {
+                    Ok(bytes) => fonts.push((url, bytes)),
+                    Err(e) => {
+                        log::warn!("Failed to fetch font {}: {}", url, e);
+                        last_error = format!("{}: {}", url, e);
+                    }
+                }
+            }
+            *slot.borrow_mut() = Some(if fonts.is_empty() { Err(last_error) } else { Ok(fonts) });
+            ctx.request_repaint();
+        });
+    }
+
+    /// Fetch fonts once text needs them, and install them when they arrive
+    fn poll_fonts(&mut self, ctx: &egui::Context) {
+        // The saved settings decide whether fonts are fetched at all
+        if !self.config_restored {
+            return;
+        }
+        let fonts = &mut self.ui_state.fonts;
+        fonts.observe_text(&self.ui_state.input_text);
+        fonts.observe_text(&self.ui_state.terminal.input);
+        if fonts.take_request() {
+            self.fetch_fonts(ctx);
+        }
+        let Some(result) = self.font_load.borrow_mut().take() else {
+            return;
+        };
+        let result = result.map(|loaded| {
+            let mut fonts = egui::FontDefinitions::default();
+            // Earlier URLs first, ahead of the built-in fonts for the
+            // glyphs they cover
+            for (i, (url, bytes)) in loaded.into_iter().enumerate() {
+                let name = format!("extra_font_{}", i);
+                fonts.font_data.insert(name.clone(), egui::FontData::from_owned(bytes).into());
+                fonts.families.entry(egui::FontFamily::Proportional).or_default().insert(i, name.clone());
+                fonts.families.entry(egui::FontFamily::Monospace).or_default().push(name);
+                log::info!("Font {} loaded", url);
+            }
+            ctx.set_fonts(fonts);
+        });
+        self.ui_state.fonts.finish(result);
+    }
+
+    fn load_config(&self, ctx: &egui::Context) {
+        let store = self.config_store.clone();
+        let slot = self.config_load.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let restored = store.load().await.unwrap_or_else(|e| {
+                log::warn!("Failed to load saved settings: {}", e);
+                None
+            });
+            *slot.borrow_mut() = Some(restored);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Apply the settings restored at startup. Until then nothing is saved,
+    /// so an early change can't overwrite them.
+    fn poll_config(&mut self, ctx: &egui::Context) {
+        let Some(loaded) = self.config_load.borrow_mut().take() else {
+            return;
+        };
+        self.config_restored = true;
+        let Some(restored) = loaded else {
+            return;
+        };
+        for warning in &restored.warnings {
+            log::warn!("Restoring settings: {}", warning);
+        }
+        if !restored.warnings.is_empty() {
+            self.config_warnings = restored.warnings;
+            self.ui_state.show_settings = true;
+        }
+        // Keys are never saved with the settings
+        let api_key = std::mem::take(&mut self.config.llm.api_key);
+        self.config = restored.config;
+        self.config.llm.api_key = api_key;
+
+        // No permission prompt here: browsers only allow it on a user gesture
+        self.notify_enabled.set(self.config.ui.notify_on_turn_end);
+        self.ui_state.fonts.configure(&self.config.ui.fonts);
+        self.shell_limits.set(self.config.shell);
+        self.storage.set_byte_cap(self.config.storage.memory_cap_bytes);
+        self.sync_vault_key();
+        self.apply_runtime_settings();
+        self.rebuild_llm();
+        self.sync_model_list(ctx);
+    }
+
+    /// Export settings, pick a file to import, preview it and apply it once
+    /// confirmed.
+    fn poll_config_transfer(&mut self, ctx: &egui::Context) {
+        let transfer = &mut self.ui_state.config_transfer;
+        if std::mem::take(&mut transfer.export_requested) {
+            let exported = export_config(&self.config, transfer.include_secrets)
+                .map_err(|e| e.user_message())
+                .and_then(|json| {
+                    file_transfer::download("agent-settings.json", "application/json", &json)
+                        .map_err(|e| format!("Export failed: {:?}", e))
+                });
+            transfer.status = Some(exported.map(|()| "Settings exported to agent-settings.json".to_string()));
+        }
+
+        if std::mem::take(&mut transfer.import_requested) {
+            transfer.status = None;
+            let slot = self.config_import.clone();
+            let ctx = ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let picked = match file_transfer::pick_text_file(".json,application/json").await {
+                    Ok(Some(text)) => Ok(text),
+                    Ok(None) => return,
+                    Err(e) => Err(format!("Could not read the file: {:?}", e)),
+                };
+                *slot.borrow_mut() = Some(picked);
+                ctx.request_repaint();
+            });
+        }
+
+        let picked = self.config_import.borrow_mut().take();
+        match picked.map(|text| text.and_then(|t| restore_config(t.as_bytes()).map_err(|e| e.user_message()))) {
+            Some(Ok(mut restored)) => {
+                keep_secrets(&mut restored.config, &self.config);
+                let changes = config_changes(&self.config, &restored.config);
+                if changes.is_empty() {
+                    transfer.status = Some(Ok("The file matches the current settings".to_string()));
+                } else {
+                    transfer.preview = Some(ImportPreview {
+                        config: restored.config,
+                        changes,
+                        warnings: restored.warnings,
+                    });
+                }
+            }
+            Some(Err(e)) => transfer.status = Some(Err(format!("Not a settings file: {}", e))),
+            None => {}
+        }
+
+        if !std::mem::take(&mut transfer.apply_requested) {
+            return;
+        }
+        let Some(preview) = transfer.preview.take() else {
+            return;
+        };
+        transfer.status = Some(Ok(format!("Imported {} setting(s)", preview.changes.len())));
+        self.config = preview.config;
+        self.save_config();
+        self.sync_vault_key();
+        self.apply_ui_settings();
+        self.apply_runtime_settings();
+        self.rebuild_llm();
+        self.sync_model_list(ctx);
+    }
+
+    fn save_config(&self) {
+        if !self.config_restored {
+            return;
+        }
+        let store = self.config_store.clone();
+        let config = self.config.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = store.save(&config).await {
+                log::warn!("Failed to save settings: {}", e);
+            }
+        });
+    }
+
+    /// Sync browser-side preferences and shell limits after the settings
+    /// panel changed them.
+    fn apply_ui_settings(&mut self) {
+        let notify = self.config.ui.notify_on_turn_end;
+        if notify && !self.notify_enabled.get() {
+            // The settings click is the user gesture browsers require
+            notify::request_permission();
+        }
+        self.notify_enabled.set(notify);
+        self.ui_state.fonts.configure(&self.config.ui.fonts);
+        self.shell_limits.set(self.config.shell);
+        self.storage.set_byte_cap(self.config.storage.memory_cap_bytes);
+    }
+
+    /// Push the active session's settings (the global config plus the
+    /// session's overrides) into the runtime, starting the embeddings
+    /// worker the first time retrieval is enabled. Retried on later frames
+    /// if a turn currently holds the runtime.
+    fn apply_runtime_settings(&mut self) {
+        let Ok(mut runtime) = self.runtime.try_borrow_mut() else {
+            self.runtime_settings_pending = true;
+            return;
+        };
+        self.runtime_settings_pending = false;
+        let config = self.session.borrow().config.apply(&self.config);
+        runtime.config.llm = config.llm;
+        runtime.config.allowed_tools = config.allowed_tools;
+        if runtime.config.system_prompt != config.system_prompt {
+            runtime.set_system_prompt(&config.system_prompt);
+        }
+        runtime.config.memory = self.config.memory.clone();
+        runtime.config.review = self.config.review.clone();
+        runtime.config.plan = self.config.plan.clone();
+        runtime.config.tool_output = self.config.tool_output.clone();
+        runtime.config.context = self.config.context.clone();
+        runtime.config.retrieval = self.config.retrieval.clone();
+        runtime.config.prices = self.config.prices.clone();
+
+        let memory = self
+            .config
+            .memory
+            .enabled
+            .then(|| Rc::new(MemoryStore::new(self.storage.clone())));
+        runtime.set_memory(memory);
+
+        runtime.config.web = self.config.web.clone();
+        let web = self.config.web.enabled.then(|| {
+            let proxy = self.config.llm.proxy().map(String::from);
+            Rc::new(WebReader::new(Rc::new(BrowserFetch::new(proxy))))
+        });
+        runtime.set_web_reader(web);
+
+        runtime.config.js = self.config.js;
+        let js = self.config.js.enabled.then(|| Rc::new(JsRunner::new(Rc::new(WorkerJs::new()))));
+        runtime.set_js_runner(js);
+
+        runtime.config.sql = self.config.sql;
+        let sql = self
+            .config
+            .sql
+            .enabled
+            .then(|| Rc::new(SqlDatabases::new(Rc::new(SqlJs::new()), self.workspace_storage.clone())));
+        runtime.set_sql(sql);
+
+        if !self.config.retrieval.enabled {
+            runtime.set_retriever(None);
+            return;
+        }
+        if runtime.tools.get("search_workspace").is_some() {
+            return;
+        }
+        match TransformersEmbeddings::new(DEFAULT_EMBEDDING_MODEL, self.event_bus.clone()) {
+            Ok(embeddings) => {
+                let retriever = WorkspaceRetriever::new(
+                    Rc::new(embeddings),
+                    self.workspace_storage.clone(),
+                    WORKSPACE_ROOT,
+                );
+                runtime.set_retriever(Some(Rc::new(retriever)));
+            }
+            Err(e) => log::warn!("Workspace search unavailable: {}", e),
+        }
+    }
+
+    fn rebuild_llm(&mut self) {
+        // Fallbacks without a key of their own use the vault's, if unlocked
+        let mut config = self.session.borrow().config.apply(&self.config).llm;
+        for target in &mut config.fallbacks {
+            if target.api_key.is_empty() {
+                if let Some(key) = self.vault_keys.get(&target.provider) {
+                    target.api_key = key.to_string();
+                }
+            }
+        }
+        self.llm = with_cache(build_llm(&config, &self.event_bus, &self.llm_worker), &config, &self.config.cache, &self.storage);
+        *self.endpoint_llm.borrow_mut() = (self.llm.clone(), config);
+    }
+
+    /// Turn the local endpoint on or off to match the settings. One that
+    /// can't start turns the setting back off.
+    fn poll_endpoint(&mut self, ctx: &egui::Context) {
+        let started = self.endpoint_start.borrow_mut().take();
+        if let Some(result) = started {
+            self.endpoint_starting = false;
+            match result {
+                // Dropped, and so stopped, if turned off meanwhile
+                Ok(endpoint) if self.config.local_endpoint => {
+                    self.endpoint = Some(endpoint);
+                    let location = web_sys::window().map(|w| w.location());
+                    let origin = location.as_ref().and_then(|l| l.origin().ok()).unwrap_or_default();
+                    let path = location.and_then(|l| l.pathname().ok()).unwrap_or_default();
+                    let base = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+                    self.ui_state.push_notice(format!(
+                        "Local endpoint on: pages on this site can call {}{}{} with your {} settings",
+                        origin,
+                        base,
+                        CHAT_COMPLETIONS_PATH,
+                        self.config.llm.provider.label()
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.ui_state.push_notice(format!("Couldn't start the local endpoint: {}", e));
+                    self.config.local_endpoint = false;
+                    self.save_config();
+                }
+            }
+        }
+
+        if !self.config.local_endpoint {
+            self.endpoint = None;
+        } else if self.endpoint.is_none() && !self.endpoint_starting {
+            self.endpoint_starting = true;
+            let target = self.endpoint_llm.clone();
+            let handler: EndpointHandler = Rc::new(move |method, path, body| {
+                let (llm, config) = target.borrow().clone();
+                Box::pin(async move { endpoint::handle(llm, &config, &method, &path, &body).await })
+            });
+            let slot = self.endpoint_start.clone();
+            let ctx = ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = LocalEndpoint::start(handler).await.map_err(|e| e.to_string());
+                *slot.borrow_mut() = Some(result);
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Reload the model list when the provider, base URL, API key or proxy
+    /// changed. A new provider uses its cached list if fresh; a new key or
+    /// proxy always refetches.
+    fn sync_model_list(&mut self, ctx: &egui::Context) {
+        let llm = &self.config.llm;
+        let target = (
+            llm.provider.clone(),
+            llm.api_base.clone(),
+            llm.api_key.clone(),
+            llm.proxy_url.clone(),
+        );
+        let Some(previous) = self.models_for.replace(target.clone()) else {
+            self.load_models(ctx, false);
+            return;
+        };
+        if previous == target {
+            return;
+        }
+        let same_endpoint = previous.0 == target.0 && previous.1 == target.1;
+        if !same_endpoint {
+            self.model_options.borrow_mut().list = None;
+        }
+        self.load_models(ctx, same_endpoint);
+    }
+
+    /// Load the model list in the background, refetching if `force` is set
+    /// or the cached list is stale.
+    fn load_models(&self, ctx: &egui::Context, force: bool) {
+        let generation = self.models_generation.get().wrapping_add(1);
+        self.models_generation.set(generation);
+        let options = self.model_options.clone();
+
+        if self.config.llm.provider.requires_api_key() && self.config.llm.api_key.is_empty() {
+            let mut options = options.borrow_mut();
+            options.loading = false;
+            options.error = None;
+            return;
+        }
+
+        let catalog = self.model_catalog.clone();
+        let llm = self.llm.clone();
+        let config = self.config.llm.clone();
+        let current = self.models_generation.clone();
+        let ctx = ctx.clone();
+        options.borrow_mut().loading = true;
+        wasm_bindgen_futures::spawn_local(async move {
+            // Show whatever is stored while the fetch runs
+            if let Ok(Some(cached)) = catalog.cached(&config).await {
+                if current.get() == generation {
+                    options.borrow_mut().list = Some(cached);
+                    ctx.request_repaint();
+                }
+            }
+            let result = if force {
+                catalog.refresh(llm.as_ref(), &config).await
+            } else {
+                catalog.get(llm.as_ref(), &config).await
+            };
+            if current.get() != generation {
+                return;
+            }
+            let mut options = options.borrow_mut();
+            options.loading = false;
+            match result {
+                Ok(list) => {
+                    options.list = Some(list);
+                    options.error = None;
+                }
+                Err(e) => {
+                    log::warn!("Failed to list models: {}", e);
+                    options.error = Some(e.user_message());
+                }
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    fn refresh_session_list(&self) {
+        let sessions = self.sessions.clone();
+        let list = self.session_list.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match sessions.list().await {
+                Ok(summaries) => *list.borrow_mut() = summaries,
+                Err(e) => log::warn!("Failed to list sessions: {}", e),
+            }
+        });
+    }
+
+    fn refresh_snapshot_list(&self) {
+        let snapshots = self.snapshots.clone();
+        let list = self.snapshot_list.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match snapshots.list().await {
+                Ok(found) => *list.borrow_mut() = found,
+                Err(e) => log::warn!("Failed to list snapshots: {}", e),
+            }
+        });
+    }
+
+    fn refresh_workspace_list(&self) {
+        let workspaces = self.workspaces.clone();
+        let list = self.workspace_list.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match workspaces.list().await {
+                Ok(all) => *list.borrow_mut() = all,
+                Err(e) => log::warn!("Failed to list workspaces: {}", e),
+            }
+        });
+    }
+
+    /// Make `workspace` active: point the VFS, sessions and search index at
+    /// its storage. Moving to or from a workspace with separate sessions
+    /// starts a new session, since the current one belongs elsewhere.
+    fn switch_workspace(&mut self, workspace: Workspace) {
+        if workspace.id == self.workspace.id {
+            return;
+        }
+        let new_session = workspace.separate_sessions || self.workspace.separate_sessions;
+
+        self.workspace_storage = self.workspaces.storage_for(&workspace);
+        let vfs = Rc::new(StorageVfs::new(self.workspace_storage.clone()));
+        self.vfs = vfs.clone();
+        self.sessions = Rc::new(SessionManager::new(self.workspaces.session_storage_for(&workspace)));
+        self.recordings = Rc::new(RecordingStore::new(self.workspace_storage.clone()));
+        Self::init_workspace(vfs);
+        self.ui_state.viewer.close();
+        self.ui_state.editor.close();
+        self.ui_state.files = Default::default();
+        self.ui_state.explorer.reset(WORKSPACE_ROOT);
+        self.rc_pending = true;
+
+        // The old index, undo journal and snapshots describe the other
+        // workspace's files
+        self.snapshots = Rc::new(SnapshotStore::new(self.workspace_storage.clone(), WORKSPACE_ROOT));
+        if let Ok(mut runtime) = self.runtime.try_borrow_mut() {
+            runtime.set_retriever(None);
+            runtime.set_undo(Some(Rc::new(UndoJournal::new(self.workspace_storage.clone()))));
+            runtime.set_snapshots(Some(self.snapshots.clone()));
+        }
+        self.refresh_snapshot_list();
+        self.apply_runtime_settings();
+
+        let workspaces = self.workspaces.clone();
+        let id = workspace.id.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = workspaces.set_current(&id).await {
+                log::warn!("Failed to remember workspace: {}", e);
+            }
+        });
+        log::info!("Switched to workspace {}", workspace.name);
+        self.workspace = workspace;
+        self.set_crash_recovery();
+
+        if new_session {
+            router::navigate_to(&new_session_id());
+        }
+        self.refresh_session_list();
+    }
+
+    fn workspace_selector(&mut self, ui: &mut egui::Ui) {
+        let busy = self.ui_state.is_busy() || self.archiving.get();
+        let mut selected = None;
+
+        ui.add_enabled_ui(!busy, |ui| {
+            egui::ComboBox::from_id_salt("workspace_selector")
+                .selected_text(RichText::new(&self.workspace.name).small())
+                .width(120.0)
+                .show_ui(ui, |ui| {
+                    for ws in self.workspace_list.borrow().iter() {
+                        if ui.selectable_label(ws.id == self.workspace.id, &ws.name).clicked() {
+                            selected = Some(ws.clone());
+                        }
+                    }
+                });
+            ui.menu_button("⋯", |ui| {
+                if ui.button("New workspace…").clicked() {
+                    self.workspace_dialog = Some(WorkspaceDialog::Create {
+                        name: String::new(),
+                        template: &TEMPLATES[0],
+                        separate_sessions: false,
+                    });
+                    ui.close();
+                }
+                if ui.button("Rename…").clicked() {
+                    self.workspace_dialog = Some(WorkspaceDialog::Rename {
+                        name: self.workspace.name.clone(),
+                    });
+                    ui.close();
+                }
+                let deletable = !self.workspace.is_default();
+                if ui.add_enabled(deletable, egui::Button::new("Delete…")).clicked() {
+                    self.workspace_dialog = Some(WorkspaceDialog::Delete);
+                    ui.close();
+                }
+            });
+        });
+
+        if let Some(ws) = selected {
+            self.switch_workspace(ws);
+        }
+    }
+
+    /// Create, rename or delete a workspace.
+    fn workspace_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = self.workspace_dialog.as_mut() else {
+            return;
+        };
+        let mut confirmed = false;
+        let mut cancelled = false;
+        let modal = egui::Modal::new(egui::Id::new("workspace_dialog")).show(ctx, |ui| {
+            ui.set_max_width(320.0);
+            let ready = match dialog {
+                WorkspaceDialog::Create { name, template, separate_sessions } => {
+                    ui.heading("New workspace");
+                    ui.add(egui::TextEdit::singleline(name).hint_text("Name"));
+                    egui::ComboBox::from_id_salt("workspace_template")
+                        .selected_text(template.name)
+                        .show_ui(ui, |ui| {
+                            for t in TEMPLATES {
+                                ui.selectable_value(template, t, t.name)
+                                    .on_hover_text(t.description);
+                            }
+                        });
+                    ui.label(RichText::new(template.description).small().color(theme::TEXT_SECONDARY));
+                    ui.checkbox(separate_sessions, "Keep its sessions separate");
+                    !name.trim().is_empty()
+                }
+                WorkspaceDialog::Rename { name } => {
+                    ui.heading("Rename workspace");
+                    ui.text_edit_singleline(name);
+                    !name.trim().is_empty()
+                }
+                WorkspaceDialog::Delete => {
+                    ui.heading("Delete workspace");
+                    ui.label(format!(
+                        "Delete \"{}\" and all of its files? This cannot be undone.",
+                        self.workspace.name
+                    ));
+                    true
+                }
+            };
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                let label = match dialog {
+                    WorkspaceDialog::Create { .. } => "Create",
+                    WorkspaceDialog::Rename { .. } => "Rename",
+                    WorkspaceDialog::Delete => "Delete",
+                };
+                if ui.add_enabled(ready, egui::Button::new(label)).clicked() {
+                    confirmed = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancelled = true;
+                }
+            });
+        });
+        if modal.should_close() {
+            cancelled = true;
+        }
+        if cancelled {
+            self.workspace_dialog = None;
+            return;
+        }
+        if !confirmed {
+            return;
+        }
+
+        let workspaces = self.workspaces.clone();
+        let list = self.workspace_list.clone();
+        let ctx = ctx.clone();
+        match self.workspace_dialog.take() {
+            Some(WorkspaceDialog::Create { name, template, separate_sessions }) => {
+                let request = self.workspace_request.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    match workspaces.create(&name, separate_sessions).await {
+                        Ok(ws) => {
+                            let vfs = StorageVfs::new(workspaces.storage_for(&ws));
+                            if let Err(e) = template.apply(&vfs, WORKSPACE_ROOT).await {
+                                log::warn!("Failed to apply template {}: {}", template.id, e);
+                            }
+                            *request.borrow_mut() = Some(ws);
+                        }
+                        Err(e) => log::warn!("Failed to create workspace: {}", e),
+                    }
+                    if let Ok(all) = workspaces.list().await {
+                        *list.borrow_mut() = all;
+                    }
+                    ctx.request_repaint();
+                });
+            }
+            Some(WorkspaceDialog::Rename { name }) => {
+                self.workspace.name = name.trim().to_string();
+                let id = self.workspace.id.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Err(e) = workspaces.rename(&id, &name).await {
+                        log::warn!("Failed to rename workspace: {}", e);
+                    }
+                    if let Ok(all) = workspaces.list().await {
+                        *list.borrow_mut() = all;
+                    }
+                    ctx.request_repaint();
+                });
+            }
+            Some(WorkspaceDialog::Delete) => {
+                let id = self.workspace.id.clone();
+                self.switch_workspace(Workspace::default_workspace());
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Err(e) = workspaces.delete(&id).await {
+                        log::warn!("Failed to delete workspace: {}", e);
+                    }
+                    if let Ok(all) = workspaces.list().await {
+                        *list.borrow_mut() = all;
+                    }
+                    ctx.request_repaint();
+                });
+            }
+            None => {}
+        }
+    }
+
+    /// Load files the viewer asked for and hand finished loads back to it.
+    fn poll_viewer(&mut self, ctx: &egui::Context) {
+        let finished = self.viewer_load.borrow_mut().take();
+        if let Some(load) = finished {
+            if let FileLoad::Loaded { data, .. } = &load {
+                self.ui_state.fonts.observe_text(&String::from_utf8_lossy(data));
+            }
+            self.ui_state.viewer.finish_load(load);
+        }
+
+        let viewer = &mut self.ui_state.viewer;
+        if std::mem::take(&mut viewer.download_requested) {
+            if let (Some(path), Some(data)) = (&viewer.path, viewer.data()) {
+                let name = path.rsplit('/').next().unwrap_or(path);
+                let mime = viewer.content_type.as_deref().unwrap_or("application/octet-stream");
+                if let Err(e) = file_transfer::download_bytes(name, mime, data) {
+                    log::warn!("Download of {} failed: {:?}", path, e);
+                }
+            }
+        }
+
+        let Some(request) = self.ui_state.viewer.take_request() else {
+            return;
+        };
+        let vfs = self.vfs.clone();
+        let slot = self.viewer_load.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let path = request.path;
+            let load = match vfs.stat(&path).await {
+                Ok(stat) if stat.is_dir => FileLoad::Failed {
+                    path,
+                    error: "This is a directory".to_string(),
+                },
+                Ok(stat) if stat.size > viewer::MAX_AUTO_LOAD_BYTES && !request.force => {
+                    FileLoad::TooLarge { path, size: stat.size }
+                }
+                Ok(stat) => match vfs.read_file(&path).await {
+                    Ok(data) => FileLoad::Loaded { path, data, content_type: stat.content_type },
+                    Err(e) => FileLoad::Failed { path, error: e.to_string() },
+                },
+                Err(e) => FileLoad::Failed { path, error: e.to_string() },
+            };
+            *slot.borrow_mut() = Some(load);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Write files the editor saved and report the outcome back to it.
+    fn poll_editor(&mut self, ctx: &egui::Context) {
+        let finished = self.editor_save.borrow_mut().take();
+        if let Some((request, result)) = finished {
+            if result.is_ok() {
+                self.ui_state.files.invalidate(&request.path);
+            }
+            self.ui_state.editor.finish_save(request, result);
+        }
+
+        let Some(request) = self.ui_state.editor.take_save_request() else {
+            return;
+        };
+        let vfs = self.vfs.clone();
+        let slot = self.editor_save.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = vfs
+                .write_file(&request.path, request.contents.as_bytes())
+                .await
+                .map_err(|e| e.to_string());
+            *slot.borrow_mut() = Some((request, result));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Write large pastes the chat saved as files and report failures
+    /// back to it.
+    fn poll_pastes(&mut self, ctx: &egui::Context) {
+        let finished = std::mem::take(&mut *self.paste_saves.borrow_mut());
+        for (path, text, result) in finished {
+            if result.is_ok() {
+                self.ui_state.files.invalidate(&path);
+            }
+            self.ui_state.paste.finish_save(&path, text, result);
+        }
+
+        for (path, text) in self.ui_state.paste.take_saves() {
+            let vfs = self.vfs.clone();
+            let slot = self.paste_saves.clone();
+            let ctx = ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = vfs.write_file(&path, text.as_bytes()).await.map_err(|e| e.to_string());
+                slot.borrow_mut().push((path, text, result));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Track the browser's connectivity, put messages that couldn't reach
+    /// the provider back in the outbox, and send the next queued message
+    /// when it is due.
+    fn poll_outbox(&mut self, ctx: &egui::Context) {
+        if let Some(window) = web_sys::window() {
+            self.ui_state.set_online(window.navigator().on_line());
+        }
+        let now = ctx.input(|i| i.time);
+        if let Some(message) = self.unsent.borrow_mut().take() {
+            self.ui_state.requeue(message, now);
+        }
+        // The runtime stays borrowed while a turn runs, including one
+        // dispatched last frame that hasn't reported its state yet
+        if self.runtime.try_borrow_mut().is_ok() {
+            if let Some(message) = self.ui_state.take_queued(now) {
+                self.dispatch_message(message, ctx);
+            }
+        }
+        if !self.ui_state.outbox.is_empty() {
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+    }
+
+    /// List the directories the file tree asked for and hand finished
+    /// listings back to it.
+    fn poll_file_tree(&mut self, ctx: &egui::Context) {
+        let finished = std::mem::take(&mut *self.file_listings.borrow_mut());
+        for (dir, result) in finished {
+            self.ui_state.files.finish_list(&dir, result);
+        }
+
+        for dir in self.ui_state.files.take_requests() {
+            let vfs = self.vfs.clone();
+            let slot = self.file_listings.clone();
+            let ctx = ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = vfs.list_dir(&dir).await.map_err(|e| e.to_string());
+                slot.borrow_mut().push((dir, result));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Read images the chat wants to show under tool results
+    fn poll_chat_images(&mut self, ctx: &egui::Context) {
+        let finished = std::mem::take(&mut *self.image_loads.borrow_mut());
+        for (path, result) in finished {
+            self.ui_state.images.finish(&path, result);
+        }
+
+        for path in self.ui_state.images.take_requests() {
+            // A shared conversation's files are in the sharer's browser
+            if self.ui_state.share.viewing.is_some() {
+                self.ui_state.images.finish(&path, Err("images aren't included in share links".to_string()));
+                continue;
+            }
+            let vfs = self.vfs.clone();
+            let slot = self.image_loads.clone();
+            let ctx = ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = match vfs.stat(&path).await {
+                    Ok(stat) if stat.size > MAX_CHAT_IMAGE_BYTES => Err("too large to show here".to_string()),
+                    Ok(_) => vfs.read_file(&path).await.map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
+                slot.borrow_mut().push((path, result));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Save chat tables the user asked to open as files, and open them
+    fn poll_table_exports(&mut self, ctx: &egui::Context) {
+        let finished = std::mem::take(&mut *self.table_saves.borrow_mut());
+        for (path, result) in finished {
+            match result {
+                Ok(()) => {
+                    self.ui_state.files.invalidate(&path);
+                    self.ui_state.viewer.open(&path);
+                }
+                Err(e) => self.ui_state.push_notice(format!("Couldn't save the table to {}: {}", path, e)),
+            }
+        }
+
+        for (path, csv) in self.ui_state.table_exports.take_saves() {
+            let vfs = self.vfs.clone();
+            let slot = self.table_saves.clone();
+            let ctx = ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = vfs.write_file(&path, csv.as_bytes()).await.map_err(|e| e.to_string());
+                slot.borrow_mut().push((path, result));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Render mermaid diagrams and formulas the chat wants to show
+    fn poll_diagrams(&mut self, ctx: &egui::Context) {
+        let finished = std::mem::take(&mut *self.diagram_renders.borrow_mut());
+        for (request, result) in finished {
+            self.ui_state.diagrams.finish(&request, result);
+        }
+
+        for request in self.ui_state.diagrams.take_requests() {
+            let scale = ctx.pixels_per_point();
+            let slot = self.diagram_renders.clone();
+            let ctx = ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let drawn = match request.kind {
+                    DiagramKind::Mermaid => diagram::render_mermaid(&request.source, scale, request.dark).await,
+                    DiagramKind::Math => diagram::render_math(&request.source, scale, request.dark).await,
+                };
+                let result = drawn.map(|pixels| ([pixels.width, pixels.height], pixels.rgba)).map_err(|e| e.to_string());
+                slot.borrow_mut().push((request, result));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Open share links from the URL read-only, and pack the session into
+    /// a link when the chat asks for one
+    fn poll_share(&mut self, ctx: &egui::Context) {
+        let requested = self.share_request.borrow_mut().take();
+        if let Some(payload) = requested {
+            self.ui_state.load_messages(&[]);
+            self.ui_state.share.viewing = Some("Shared conversation".to_string());
+            self.ui_state.status_text = "Opening link…".to_string();
+            let slot = self.shared_load.clone();
+            let ctx = ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = share::unpack(&payload).await.map_err(|e| e.to_string());
+                *slot.borrow_mut() = Some(result);
+                ctx.request_repaint();
+            });
+        }
+
+        let loaded = self.shared_load.borrow_mut().take();
+        match loaded {
+            Some(Ok(transcript)) => self.ui_state.load_shared(&transcript),
+            Some(Err(e)) => {
+                let session = self.session.borrow().clone();
+                self.ui_state.load_messages(&session.messages);
+                self.ui_state.push_notice(format!("Couldn't open the shared conversation: {}", e));
+                router::replace_with(&session.id);
+            }
+            None => {}
+        }
+        if self.ui_state.share.take_leave() {
+            router::navigate_to(&new_session_id());
+        }
+
+        if self.ui_state.share.take_request() {
+            self.ui_state.share.packing = true;
+            let transcript = SharedTranscript::from_session(&self.session.borrow());
+            let slot = self.share_link.clone();
+            let ctx = ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = share::pack(&transcript).await.map_err(|e| e.to_string());
+                *slot.borrow_mut() = Some(result);
+                ctx.request_repaint();
+            });
+        }
+
+        let packed = self.share_link.borrow_mut().take();
+        if let Some(result) = packed {
+            self.ui_state.share.packing = false;
+            let url = result.and_then(|payload| router::share_url(&payload).ok_or_else(|| "the page has no URL".to_string()));
+            match url {
+                Ok(url) => {
+                    let mut notice = format!(
+                        "Share link copied ({} characters). Anyone with it can read this conversation, tool results included.",
+                        url.len()
+                    );
+                    if url.len() > LONG_SHARE_LINK_CHARS {
+                        notice.push_str(" It is long enough that some apps may cut it off.");
+                    }
+                    ctx.copy_text(url);
+                    self.ui_state.push_notice(notice);
+                }
+                Err(e) => self.ui_state.push_notice(format!("Couldn't make a share link: {}", e)),
+            }
+        }
+    }
+
+    /// Load the prompt library for the chat input's picker.
+    fn load_prompts(&self, ctx: &egui::Context) {
+        let library = self.prompt_library.clone();
+        let loaded = self.prompt_load.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match library.list().await {
+                Ok(snippets) => {
+                    *loaded.borrow_mut() = Some(snippets);
+                    ctx.request_repaint();
+                }
+                Err(e) => log::warn!("Failed to load prompts: {}", e),
+            }
+        });
+    }
+
+    /// Persist prompt library changes made in the picker.
+    fn poll_prompts(&mut self) {
+        let loaded = self.prompt_load.borrow_mut().take();
+        if let Some(snippets) = loaded {
+            self.ui_state.prompts.snippets = snippets;
+        }
+
+        for action in self.ui_state.prompts.take_actions() {
+            let library = self.prompt_library.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = match &action {
+                    PromptAction::Save(snippet) => library.save(snippet).await,
+                    PromptAction::Delete(id) => library.delete(id).await,
+                };
+                if let Err(e) = result {
+                    log::warn!("Failed to update prompt library ({:?}): {}", action, e);
+                }
+            });
+        }
+    }
+
+    /// Apply router requests and finished session loads. Switching is
+    /// deferred while a turn is running since the runtime is borrowed.
+    fn poll_sessions(&mut self, ctx: &egui::Context) {
+        if self.ui_state.is_busy() {
+            return;
+        }
+
+        let created = self.workspace_request.borrow_mut().take();
+        if let Some(workspace) = created {
+            self.switch_workspace(workspace);
+        }
+
+        let requested = self.route_request.borrow_mut().take();
+        if let Some(id) = requested {
+            // Going back from a share link may return to the session it
+            // was shown over
+            if id != self.session.borrow().id || self.ui_state.share.viewing.is_some() {
+                let sessions = self.sessions.clone();
+                let loaded = self.loaded_session.clone();
+                let ctx = ctx.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let session = match sessions.load(&id).await {
+                        Ok(Some(s)) => s,
+                        Ok(None) => Session::new(id),
+                        Err(e) => {
+                            log::warn!("Failed to load session {}: {}", id, e);
+                            Session::new(id)
+                        }
+                    };
+                    *loaded.borrow_mut() = Some(session);
+                    ctx.request_repaint();
+                });
+            }
+        }
+
+        let loaded = self.loaded_session.borrow_mut().take();
+        if let Some(mut session) = loaded {
+            let Ok(mut runtime) = self.runtime.try_borrow_mut() else {
+                *self.loaded_session.borrow_mut() = Some(session);
+                return;
+            };
+            if session.messages.is_empty() {
+                if let Some(config) = self.pending_session_config.take() {
+                    session.config = config;
+                }
+            }
+            runtime.restore(session.messages.clone());
+            drop(runtime);
+            self.ui_state.load_messages(&session.messages);
+            *self.costs.borrow_mut() = CostSummary {
+                turn: None,
+                session: UsageTotals::from_messages(&session.messages),
+                all_time: self.costs.borrow().all_time.clone(),
+            };
+            *self.session.borrow_mut() = session;
+            // Model, temperature and prompt may differ per session
+            self.apply_runtime_settings();
+            self.rebuild_llm();
+        }
+    }
+
+    fn session_selector(&mut self, ui: &mut egui::Ui) {
+        let current_id = self.session.borrow().id.clone();
+        let current_title = self.session.borrow().title.clone();
+        let busy = self.ui_state.is_busy() || self.archiving.get() || self.replaying.get();
+
+        ui.add_enabled_ui(!busy, |ui| {
+            egui::ComboBox::from_id_salt("session_selector")
+                .selected_text(RichText::new(current_title).small())
+                .width(180.0)
+                .show_ui(ui, |ui| {
+                    for summary in self.session_list.borrow().iter() {
+                        let mut label = format!("{} ({})", summary.title, summary.message_count);
+                        if summary.cost_usd > 0.0 {
+                            label.push_str(&format!(" · {}", format_usd(summary.cost_usd)));
+                        }
+                        if ui
+                            .selectable_label(summary.id == current_id, label)
+                            .clicked()
+                        {
+                            router::navigate_to(&summary.id);
+                        }
+                    }
+                });
+            if ui.button("New").on_hover_text("Start a new session").clicked() {
+                self.start_new_session();
+            }
+            if ui
+                .button("New…")
+                .on_hover_text("Start a new session with its own system prompt, model or temperature")
+                .clicked()
+            {
+                let config = self.session.borrow().config.apply(&self.config);
+                self.new_session_dialog = Some(NewSessionDialog {
+                    system_prompt: config.system_prompt,
+                    model: config.llm.model,
+                    temperature: self.session.borrow().config.temperature,
+                });
+            }
+            let has_messages = !self.session.borrow().messages.is_empty();
+            if ui
+                .add_enabled(has_messages, egui::Button::new("Replay"))
+                .on_hover_text("Run this session again in a new one, from its recording")
+                .clicked()
+            {
+                self.load_recording(ui.ctx());
+            }
+            if self.archiving.get() {
+                ui.spinner();
+                ui.label(RichText::new("Summarizing…").small().color(theme::TEXT_SECONDARY));
+            }
+            if self.replaying.get() {
+                ui.spinner();
+                ui.label(RichText::new("Replaying…").small().color(theme::TEXT_SECONDARY));
+            }
+        });
+    }
+
+    /// Persona of the current session: its prompt, tools and model.
+    fn persona_selector(&mut self, ui: &mut egui::Ui) {
+        let current = self.session.borrow().config.persona.clone();
+        let mut selected = current.clone();
+        ui.add_enabled_ui(!self.ui_state.is_busy(), |ui| {
+            egui::ComboBox::from_id_salt("persona_selector")
+                .selected_text(RichText::new(current.as_deref().unwrap_or("Default")).small())
+                .width(120.0)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut selected, None, "Default");
+                    for persona in &self.config.personas {
+                        ui.selectable_value(&mut selected, Some(persona.name.clone()), &persona.name);
+                    }
+                })
+                .response
+                .on_hover_text("Persona for this session");
+        });
+        if selected == current {
+            return;
+        }
+        self.session.borrow_mut().config.persona = selected;
+        self.apply_runtime_settings();
+        self.rebuild_llm();
+        if self.session.borrow().messages.is_empty() {
+            return;
+        }
+        let sessions = self.sessions.clone();
+        let snapshot = self.session.borrow().clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = sessions.save(&snapshot).await {
+                log::warn!("Failed to save session: {}", e);
+            }
+        });
+    }
+
+    /// Switch to a fresh session, offering to archive this one first.
+    fn start_new_session(&mut self) {
+        if self.session.borrow().messages.is_empty() {
+            router::navigate_to(&new_session_id());
+        } else {
+            self.archive_prompt = true;
+        }
+    }
+
+    /// Choose the overrides for a new session. Values equal to the global
+    /// settings are not stored, so the session follows later changes to them.
+    fn new_session_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = self.new_session_dialog.as_mut() else {
+            return;
+        };
+        let mut confirmed = false;
+        let mut cancelled = false;
+        let modal = egui::Modal::new(egui::Id::new("new_session_dialog")).show(ctx, |ui| {
+            ui.set_max_width(420.0);
+            ui.heading("New session");
+            ui.label(RichText::new("System prompt").small().color(theme::TEXT_SECONDARY));
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut dialog.system_prompt)
+                        .desired_rows(6)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Model").small().color(theme::TEXT_SECONDARY));
+                ui.add(egui::TextEdit::singleline(&mut dialog.model).hint_text(&self.config.llm.model));
+            });
+            ui.horizontal(|ui| {
+                let mut custom = dialog.temperature.is_some();
+                if ui.checkbox(&mut custom, "Temperature").changed() {
+                    dialog.temperature = custom.then_some(self.config.llm.temperature);
+                }
+                match &mut dialog.temperature {
+                    Some(temperature) => {
+                        ui.add(egui::Slider::new(temperature, 0.0..=2.0).step_by(0.1));
+                    }
+                    None => {
+                        ui.label(
+                            RichText::new(format!("{:.1} (global)", self.config.llm.temperature))
+                                .small()
+                                .color(theme::TEXT_SECONDARY),
+                        );
+                    }
+                }
+            });
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if ui.button("Create").clicked() {
+                    confirmed = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancelled = true;
+                }
+            });
+        });
+        if modal.should_close() {
+            cancelled = true;
+        }
+        if cancelled {
+            self.new_session_dialog = None;
+            return;
+        }
+        if !confirmed {
+            return;
+        }
+        let Some(dialog) = self.new_session_dialog.take() else {
+            return;
+        };
+        let prompt = dialog.system_prompt.trim();
+        let model = dialog.model.trim();
+        // The new session keeps the persona; only changes from it are overrides
+        let persona = self.session.borrow().config.persona.clone();
+        let base = SessionConfig { persona: persona.clone(), ..Default::default() }.apply(&self.config);
+        self.pending_session_config = Some(SessionConfig {
+            system_prompt: (!prompt.is_empty() && prompt != base.system_prompt.trim())
+                .then(|| prompt.to_string()),
+            model: (!model.is_empty() && model != base.llm.model).then(|| model.to_string()),
+            temperature: dialog.temperature,
+            persona,
+        });
+        self.start_new_session();
+    }
+
+    /// Undo menu for file changes made by the agent's tools.
+    fn undo_menu(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let busy = self.ui_state.is_busy();
+        ui.add_enabled_ui(!busy, |ui| {
+            ui.menu_button("Undo", |ui| {
+                if ui.button("Undo last file change").clicked() {
+                    self.undo_file_changes(ctx, UndoScope::Last);
+                    ui.close();
+                }
+                if ui.button("Undo all changes from the last turn").clicked() {
+                    self.undo_file_changes(ctx, UndoScope::Turn);
+                    ui.close();
+                }
+                ui.separator();
+                ui.menu_button("Restore workspace to before…", |ui| {
+                    let list = self.snapshot_list.borrow().clone();
+                    if list.is_empty() {
+                        ui.label(RichText::new("No snapshots yet").small().color(theme::TEXT_SECONDARY));
+                    }
+                    egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                        for snapshot in &list {
+                            let text = format!("{}  {}", snapshot.time_label(), snapshot.label);
+                            if ui
+                                .button(text)
+                                .on_hover_text(format!("{} files", snapshot.files.len()))
+                                .clicked()
+                            {
+                                self.restore_snapshot(ctx, snapshot);
+                                ui.close();
+                            }
+                        }
+                    });
+                });
+            });
+        });
+    }
+
+    fn undo_file_changes(&self, ctx: &egui::Context, scope: UndoScope) {
+        let journal = UndoJournal::new(self.workspace_storage.clone());
+        let vfs = self.vfs.clone();
+        let result = self.undo_result.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let message = match journal.undo(vfs.as_ref(), scope).await {
+                Ok(undone) => format_undone(&undone).replace('\n', "; "),
+                Err(e) => format!("Undo failed: {}", e),
+            };
+            *result.borrow_mut() = Some(message);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Put the workspace back to `snapshot`. The current state is
+    /// snapshotted first, so the restore itself can be reverted.
+    fn restore_snapshot(&self, ctx: &egui::Context, snapshot: &Snapshot) {
+        let snapshots = self.snapshots.clone();
+        let vfs = self.vfs.clone();
+        let result = self.undo_result.clone();
+        let list = self.snapshot_list.clone();
+        let seq = snapshot.seq;
+        let label = format!("Restore of \"{}\"", snapshot.label);
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let restored = async {
+                snapshots.take(vfs.as_ref(), &label).await?;
+                snapshots.restore(vfs.as_ref(), seq).await
+            };
+            let message = match restored.await {
+                Ok(changed) => format!("Workspace restored ({} files changed)", changed),
+                Err(e) => format!("Restore failed: {}", e),
+            };
+            if let Ok(found) = snapshots.list().await {
+                *list.borrow_mut() = found;
+            }
+            *result.borrow_mut() = Some(message);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Show the outcome of a finished undo or restore. An open preview is
+    /// reloaded in case its file was among those restored.
+    fn poll_undo(&mut self) {
+        let Some(message) = self.undo_result.borrow_mut().take() else {
+            return;
+        };
+        log::info!("{}", message);
+        self.ui_state.status_text = message;
+        self.ui_state.files.invalidate_all();
+        if let Some(path) = self.ui_state.viewer.path.clone() {
+            self.ui_state.viewer.open(&path);
+        }
+    }
+
+    fn load_usage_total(&self, ctx: &egui::Context) {
+        let ledger = self.usage_ledger.clone();
+        let costs = self.costs.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match ledger.total().await {
+                Ok(total) => {
+                    costs.borrow_mut().all_time = total;
+                    ctx.request_repaint();
+                }
+                Err(e) => log::warn!("Failed to load usage totals: {}", e),
+            }
+        });
+    }
+
+    /// Load the activity log into the stats panel.
+    fn load_stats(&mut self, ctx: &egui::Context) {
+        self.ui_state.stats.loading = true;
+        let log = self.activity_log.clone();
+        let slot = self.stats_load.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let records = log.records().await.unwrap_or_else(|e| {
+                log::warn!("Failed to load activity log: {}", e);
+                Vec::new()
+            });
+            *slot.borrow_mut() = Some(ActivityStats::from_records(&records));
+            ctx.request_repaint();
+        });
+    }
+
+    fn poll_stats(&mut self, ctx: &egui::Context) {
+        if std::mem::take(&mut self.ui_state.stats.refresh_requested) {
+            self.load_stats(ctx);
+        }
+        if let Some(stats) = self.stats_load.borrow_mut().take() {
+            self.ui_state.stats.stats = Some(stats);
+            self.ui_state.stats.loading = false;
+        }
+    }
+
+    /// The log window, and a download of the log when asked for.
+    fn logs_window(&mut self, ctx: &egui::Context) {
+        if !self.ui_state.logs.open {
+            return;
+        }
+        let mut buffer = match agent_core::logs::global().lock() {
+            Ok(buffer) => buffer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        logs::log_window(ctx, &mut self.ui_state.logs, &mut buffer);
+        if !std::mem::take(&mut self.ui_state.logs.download_requested) {
+            return;
+        }
+        let now = String::from(js_sys::Date::new_0().to_iso_string());
+        let agent = web_sys::window().and_then(|w| w.navigator().user_agent().ok()).unwrap_or_default();
+        let text = format!("Agent {} log, saved {}
+{}
+
+{}", env!("CARGO_PKG_VERSION"), now, agent, buffer.to_text());
+        drop(buffer);
+        let name = format!("agent-log-{}.txt", now.get(..19).unwrap_or(&now).replace(':', "-"));
+        if let Err(e) = file_transfer::download(&name, "text/plain", &text) {
+            self.ui_state.push_notice(format!("Could not save the log: {:?}", e));
+        }
+    }
+
+    /// Measure storage for the diagnostics section, and run the cleanups
+    /// it asks for.
+    fn poll_diagnostics(&mut self, ctx: &egui::Context) {
+        if let Some(cleanup) = self.ui_state.diagnostics.cleanup.take() {
+            self.run_cleanup(cleanup, ctx);
+        }
+        if let Some(status) = self.cleanup_done.borrow_mut().take() {
+            let panel = &mut self.ui_state.diagnostics;
+            panel.running = false;
+            panel.status = Some(status);
+            panel.refresh_requested = true;
+            self.refresh_session_list();
+        }
+        if let Some(breakdown) = self.storage_breakdown.borrow_mut().take() {
+            self.ui_state.diagnostics.storage = Some(breakdown);
+            self.ui_state.diagnostics.loading = false;
+        }
+        if !std::mem::take(&mut self.ui_state.diagnostics.refresh_requested) {
+            return;
+        }
+        self.ui_state.diagnostics.loading = true;
+        let root = self.storage.clone();
+        let sessions = self.sessions.clone();
+        let vfs = StorageVfs::new(self.workspace_storage.clone());
+        let slot = self.storage_breakdown.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let measured = async {
+                agent_types::Result::Ok(StorageBreakdown {
+                    config: root.stored_bytes(CONFIG_KEY).await?,
+                    sessions: sessions.stored_bytes().await?,
+                    vfs: vfs.stored_bytes().await?,
+                    total: root.stored_bytes("").await?,
+                })
+            };
+            let breakdown = measured.await.unwrap_or_else(|e| {
+                log::warn!("Failed to measure storage: {}", e);
+                StorageBreakdown::default()
+            });
+            *slot.borrow_mut() = Some(breakdown);
+            ctx.request_repaint();
+        });
+    }
+
+    fn run_cleanup(&mut self, cleanup: Cleanup, ctx: &egui::Context) {
+        self.ui_state.diagnostics.status = None;
+        let work: LocalBoxFuture<'static, agent_types::Result<String>> = match cleanup {
+            Cleanup::ClearTerminal => {
+                let lines = self.ui_state.terminal_lines.len();
+                self.ui_state.terminal_lines.clear();
+                self.ui_state.diagnostics.status = Some(Ok(format!("Cleared {} terminal lines", lines)));
+                return;
+            }
+            Cleanup::VacuumVfs => {
+                let workspaces = self.workspaces.clone();
+                Box::pin(async move {
+                    let removed = workspaces.vacuum().await?;
+                    Ok(format!("Removed {} keys left by deleted workspaces", removed))
+                })
+            }
+            Cleanup::DeleteOldSessions { cutoff } => {
+                let sessions = self.sessions.clone();
+                let keep = self.session.borrow().id.clone();
+                Box::pin(async move {
+                    let deleted = sessions.delete_older_than(&cutoff, &keep).await?;
+                    Ok(format!("Deleted {} old sessions", deleted))
+                })
+            }
+        };
+        self.ui_state.diagnostics.running = true;
+        let slot = self.cleanup_done.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            *slot.borrow_mut() = Some(work.await.map_err(|e| e.user_message()));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Run a command typed into the terminal.
+    fn run_terminal_command(&mut self, command: String, ctx: &egui::Context) {
+        let expanded = terminal::expand_aliases(&command, &self.config.aliases);
+        let script = terminal::script_invocation(&expanded, &self.ui_state.terminal.cwd)
+            .map(|(path, args)| (path, args.to_string()));
+        self.execute_in_terminal(&command, expanded, script, ctx);
+    }
 
-        // Create the agent runtime
-        let runtime = AgentRuntime::new(config.clone(), event_bus.clone());
+    /// Run `command` in the shell, echoing `echo` as the prompt line. With
+    /// a script (VFS path and arguments), that script is run instead if it
+    /// exists.
+    fn execute_in_terminal(
+        &mut self,
+        echo: &str,
+        command: String,
+        script: Option<(String, String)>,
+        ctx: &egui::Context,
+    ) {
+        self.terminal_header = self.ui_state.echo_command(echo);
+        self.ui_state.terminal.running = true;
+        let opts = ExecOptions::in_dir(&self.ui_state.terminal.cwd);
+        let shell = self.shell.clone();
+        let vfs = self.vfs.clone();
+        let slot = self.terminal_result.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let command = match script {
+                Some((path, args)) => match vfs.read_file(&path).await {
+                    Ok(data) => script_command(&path, &String::from_utf8_lossy(&data), &args),
+                    Err(_) => command,
+                },
+                None => command,
+            };
+            let result = shell.execute(&command, &opts).await.map_err(|e| e.to_string());
+            *slot.borrow_mut() = Some(result);
+            ctx.request_repaint();
+        });
+    }
 
-        // Create platform adapters
-        let llm = Rc::new(OpenAiCompatProvider::new(config.llm.clone()));
+    /// Report the worker ready once it is, or swap in the stub shell if it
+    /// gave up and tell the user why.
+    fn poll_shell_health(&mut self) {
+        let Some(adapter) = self.shell_adapter.as_ref() else {
+            return;
+        };
+        if self.ui_state.health.shell == ShellHealth::Starting && adapter.is_ready() {
+            self.event_bus.emit(AgentEvent::ShellReady);
+        }
+        let Some(reason) = adapter.failure() else {
+            return;
+        };
+        self.shell_adapter = None;
+        self.shell = Rc::new(StubShell::new(&reason));
+        self.event_bus.emit(AgentEvent::ShellUnavailable { reason });
+    }
 
-        // Try to create shell adapter, fall back to a stub if Worker creation fails
-        let shell: Rc<dyn ShellPort> = match WasmerShellAdapter::new() {
-            Ok(s) => Rc::new(s),
-            Err(e) => {
-                log::warn!("Shell adapter unavailable: {}. Using stub.", e);
-                Rc::new(StubShell)
+    /// Read the workspace's rc script, if it has one.
+    fn load_rc_script(&self, ctx: &egui::Context) {
+        let vfs = self.vfs.clone();
+        let slot = self.rc_script.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if !vfs.exists(RC_PATH).await.unwrap_or(false) {
+                return;
             }
-        };
+            match vfs.read_file(RC_PATH).await {
+                Ok(data) => {
+                    let script = String::from_utf8_lossy(&data).into_owned();
+                    if !script.trim().is_empty() {
+                        *slot.borrow_mut() = Some(script);
+                        ctx.request_repaint();
+                    }
+                }
+                Err(e) => log::warn!("Failed to read {}: {}", RC_PATH, e),
+            }
+        });
+    }
+
+    /// Run the rc script once the shell is up, keep the jobs list current,
+    /// and show the output of a finished terminal command.
+    fn poll_terminal(&mut self, ctx: &egui::Context) {
+        for handle in self.ui_state.jobs.take_kills() {
+            let shell = self.shell.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(e) = shell.cancel(handle).await {
+                    log::warn!("Failed to cancel shell command {}: {}", handle.0, e);
+                }
+            });
+        }
+        self.ui_state.jobs.set_running(self.shell.list_running());
+        if !self.ui_state.jobs.running.is_empty() {
+            // Keep the elapsed times moving
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
 
-        // Use memory storage + VFS for now (IndexedDB will be initialized async)
-        let storage = Rc::new(MemoryStorage::new());
-        let vfs = Rc::new(StorageVfs::new(storage));
+        if self.rc_pending {
+            if self.shell.is_ready() {
+                self.rc_pending = false;
+                self.load_rc_script(ctx);
+            } else {
+                ctx.request_repaint_after(std::time::Duration::from_millis(250));
+            }
+        }
+        if !self.ui_state.terminal.running {
+            let script = self.rc_script.borrow_mut().take();
+            if let Some(script) = script {
+                self.execute_in_terminal(&format!("source {}", RC_PATH), script, None, ctx);
+            }
+        }
 
-        let app = Self {
-            ui_state: UiState::new(),
-            config,
-            event_bus,
-            runtime: Rc::new(RefCell::new(runtime)),
-            llm,
-            shell,
-            vfs: vfs.clone(),
-            first_frame: true,
-            font_loaded: Rc::new(RefCell::new(false)),
+        let Some(result) = self.terminal_result.borrow_mut().take() else {
+            return;
         };
+        self.ui_state.terminal.running = false;
+        // The command may have changed any file
+        self.ui_state.files.invalidate_all();
+        match result {
+            Ok(output) => {
+                if output.duration_ms > 0 {
+                    self.ui_state.time_command(self.terminal_header, output.duration_ms);
+                }
+                self.ui_state.fonts.observe_text(&output.stdout);
+                self.ui_state.fonts.observe_text(&output.stderr);
+                let lines = &mut self.ui_state.terminal_lines;
+                for (text, is_stderr) in [(output.stdout, false), (output.stderr, true)] {
+                    lines.extend(text.lines().map(|line| TerminalLine {
+                        text: line.to_string(),
+                        is_stderr,
+                    }));
+                }
+                if output.exit_code != 0 {
+                    lines.push(TerminalLine {
+                        text: format!("[exit {}]", output.exit_code),
+                        is_stderr: true,
+                    });
+                }
+            }
+            Err(e) => self.ui_state.terminal_lines.push(TerminalLine { text: e, is_stderr: true }),
+        }
+    }
 
-        // Initialize default workspace
-        Self::init_workspace(vfs);
+    /// Run the eval suite chosen in settings against the current config.
+    fn start_eval(&mut self, ctx: &egui::Context) {
+        let panel = &mut self.ui_state.eval;
+        panel.running = true;
+        panel.result = None;
+        let suite_path = panel.suite_path.trim().to_string();
+        let config = self.config.clone();
+        let llm = self.llm.clone();
+        let shell = self.shell.clone();
+        let vfs = self.vfs.clone();
+        let slot = self.eval_result.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = eval::run_suite_file(&config, &suite_path, llm.as_ref(), shell.as_ref(), vfs.as_ref())
+                .await
+                .map(|(report, _)| {
+                    format!(
+                        "{}/{} passed — report in {}",
+                        report.passed(),
+                        report.outcomes.len(),
+                        eval::report_path(&suite_path)
+                    )
+                })
+                .map_err(|e| e.user_message());
+            *slot.borrow_mut() = Some(result);
+            ctx.request_repaint();
+        });
+    }
 
-        app
+    fn poll_eval(&mut self, ctx: &egui::Context) {
+        if std::mem::take(&mut self.ui_state.eval.run_requested) {
+            self.start_eval(ctx);
+        }
+        let Some(result) = self.eval_result.borrow_mut().take() else {
+            return;
+        };
+        let panel = &mut self.ui_state.eval;
+        panel.running = false;
+        if result.is_ok() {
+            let report = eval::report_path(panel.suite_path.trim());
+            self.ui_state.viewer.open(&report);
+        }
+        self.ui_state.eval.result = Some(result);
     }
 
-    /// Create default workspace directories in VFS
-    fn init_workspace(vfs: Rc<dyn VfsPort>) {
+    fn load_batch(&mut self, ctx: &egui::Context) {
+        let panel = &mut self.ui_state.batch;
+        panel.loading = true;
+        panel.error = None;
+        let path = panel.file_path.trim().to_string();
+        let vfs = self.vfs.clone();
+        let slot = self.batch_load.clone();
+        let ctx = ctx.clone();
         wasm_bindgen_futures::spawn_local(async move {
-            let dirs = [
-                WORKSPACE_ROOT,
-                &format!("{}/home", WORKSPACE_ROOT),
-                &format!("{}/tmp", WORKSPACE_ROOT),
-                &format!("{}/src", WORKSPACE_ROOT),
-            ];
-            for dir in &dirs {
-                let _ = vfs.mkdir(dir).await;
-            }
-            // Write a welcome README
-            let readme = "# WASM Agent Workspace\n\n\
-                This is your default workspace.\n\
-                Files created by the agent will be stored here.\n";
-            let _ = vfs
-                .write_file(
-                    &format!("{}/README.md", WORKSPACE_ROOT),
-                    readme.as_bytes(),
-                )
-                .await;
-            log::info!("Workspace initialised at {}", WORKSPACE_ROOT);
+            let result = batch::load_batch(vfs.as_ref(), &path).await.map_err(|e| e.user_message());
+            *slot.borrow_mut() = Some(result);
+            ctx.request_repaint();
         });
     }
 
-    /// Fetch CJK font from server and install into egui
-    fn load_cjk_font(ctx: egui::Context, loaded_flag: Rc<RefCell<bool>>) {
+    /// Load prompts files for the batch window, record how each batch
+    /// prompt's turn ended, and start the next one once the agent is free.
+    fn poll_batch(&mut self, ctx: &egui::Context) {
+        if std::mem::take(&mut self.ui_state.batch.load_requested) {
+            self.load_batch(ctx);
+        }
+        if let Some(result) = self.batch_load.borrow_mut().take() {
+            let panel = &mut self.ui_state.batch;
+            panel.loading = false;
+            match result {
+                Ok(prompts) => panel.add(prompts),
+                Err(e) => panel.error = Some(e),
+            }
+        }
+        if let Some(outcome) = self.batch_turn.borrow_mut().take() {
+            if let Some(summary) = self.ui_state.batch.finish(outcome) {
+                self.ui_state.push_notice(summary);
+            }
+        }
+
+        // Queued chat messages go first; the runtime stays borrowed while
+        // a turn runs
+        let free = self.ui_state.online
+            && self.ui_state.outbox.is_empty()
+            && !self.ui_state.is_busy()
+            && self.runtime.try_borrow_mut().is_ok();
+        if !free {
+            return;
+        }
+        if let Some(prompt) = self.ui_state.batch.take_next() {
+            let attachments = chat::mentioned_files(&prompt);
+            self.ui_state.push_user_message(&prompt, attachments.clone());
+            let message = chat::Outgoing { text: prompt, attachments };
+            self.dispatch_turn(message, ctx, Some(self.batch_turn.clone()));
+        }
+    }
+
+    fn load_recording(&self, ctx: &egui::Context) {
+        let recordings = self.recordings.clone();
+        let id = self.session.borrow().id.clone();
+        let slot = self.replay_load.clone();
+        let ctx = ctx.clone();
         wasm_bindgen_futures::spawn_local(async move {
-            let window = match web_sys::window() {
-                Some(w) => w,
-                None => return,
+            let result = match recordings.load(&id).await {
+                Ok(Some(recording)) if recording.turns() > 0 => Ok(recording),
+                Ok(_) => Err("This session has no recording (turn on recording in Settings)".to_string()),
+                Err(e) => Err(format!("Failed to load the recording: {}", e.user_message())),
             };
-            let resp = match wasm_bindgen_futures::JsFuture::from(
-                window.fetch_with_str("NotoSansTC-Regular.otf"),
-            )
-            .await
-            {
-                Ok(r) => r,
-                Err(e) => {
-                    log::warn!("Failed to fetch CJK font: {:?}", e);
-                    return;
-                }
+            *slot.borrow_mut() = Some(result);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Switch to a fresh session for a loaded recording, start the replay
+    /// once it is active, and show the result when it is done.
+    fn poll_replay(&mut self, ctx: &egui::Context) {
+        let loaded = self.replay_load.borrow_mut().take();
+        match loaded {
+            Some(Ok(recording)) => {
+                let id = new_session_id();
+                router::navigate_to(&id);
+                self.replay_pending = Some((id, recording));
+            }
+            Some(Err(message)) => self.ui_state.status_text = message,
+            None => {}
+        }
+        let requeued = self.replay_requeue.borrow_mut().take();
+        if let Some(recording) = requeued {
+            self.replay_pending = Some((self.session.borrow().id.clone(), recording));
+        }
+
+        // A turn that hasn't reached TurnStart yet isn't busy but already
+        // holds the runtime
+        let ready = matches!(&self.replay_pending, Some((id, _)) if *id == self.session.borrow().id)
+            && !self.ui_state.is_busy()
+            && self.runtime.try_borrow_mut().is_ok();
+        if ready {
+            if let Some((_, recording)) = self.replay_pending.take() {
+                self.start_replay(recording, ctx);
+            }
+        }
+
+        let done = self.replay_done.borrow_mut().take();
+        if let Some(outcome) = done {
+            self.ui_state.load_messages(&outcome.history);
+            // Replayed responses cost nothing, so the all-time total stays put
+            self.costs.borrow_mut().session = UsageTotals::from_messages(&outcome.history);
+            self.ui_state.status_text = match outcome.divergence {
+                Some(message) => message,
+                None => "Replay finished".to_string(),
             };
-            let resp: web_sys::Response = resp.into();
-            let buf = match resp.array_buffer() {
-                Ok(p) => match wasm_bindgen_futures::JsFuture::from(p).await {
-                    Ok(b) => b,
-                    Err(_) => return,
-                },
-                Err(_) => return,
+        }
+    }
+
+    fn start_replay(&self, recording: Recording, ctx: &egui::Context) {
+        log::info!("Replaying {} turns of session {}", recording.turns(), recording.session_id);
+        let runtime = self.runtime.clone();
+        let requeue = self.replay_requeue.clone();
+        let vfs = self.vfs.clone();
+        let sessions = self.sessions.clone();
+        let session = self.session.clone();
+        let session_list = self.session_list.clone();
+        let done = self.replay_done.clone();
+        let replaying = self.replaying.clone();
+        let ctx = ctx.clone();
+        replaying.set(true);
+
+        // As in dispatch_turn, the runtime stays borrowed for the whole
+        // replay (clippy can't see that nothing else takes it with a plain
+        // borrow_mut). It is taken before anything is awaited; if a turn
+        // got it first, the replay waits for another try.
+        #[allow(clippy::await_holding_refcell_ref)]
+        wasm_bindgen_futures::spawn_local(async move {
+            let Ok(mut rt) = runtime.try_borrow_mut() else {
+                *requeue.borrow_mut() = Some(recording);
+                replaying.set(false);
+                ctx.request_repaint();
+                return;
             };
-            let uint8 = js_sys::Uint8Array::new(&buf);
-            let bytes = uint8.to_vec();
+            let result = replay_session(&mut rt, &recording, vfs.as_ref()).await;
+            drop(rt);
+            let history = runtime.borrow().history().to_vec();
+            session.borrow_mut().update_messages(history.clone());
+            let snapshot = session.borrow().clone();
+            if let Err(e) = sessions.save(&snapshot).await {
+                log::warn!("Failed to save session: {}", e);
+            }
+            if let Ok(list) = sessions.list().await {
+                *session_list.borrow_mut() = list;
+            }
+            *done.borrow_mut() = Some(ReplayOutcome {
+                history,
+                divergence: result.err().map(|e| e.user_message()),
+            });
+            replaying.set(false);
+            ctx.request_repaint();
+        });
+    }
 
-            let mut fonts = egui::FontDefinitions::default();
-            fonts.font_data.insert(
-                "noto_sans_tc".to_owned(),
-                egui::FontData::from_owned(bytes).into(),
-            );
-            // Prepend CJK font so it takes priority for CJK glyphs
-            fonts
-                .families
-                .entry(egui::FontFamily::Proportional)
-                .or_default()
-                .insert(0, "noto_sans_tc".to_owned());
-            fonts
-                .families
-                .entry(egui::FontFamily::Monospace)
-                .or_default()
-                .push("noto_sans_tc".to_owned());
+    /// Estimated cost of the last turn, this session and all time.
+    fn cost_label(&self, ui: &mut egui::Ui) {
+        let costs = self.costs.borrow();
+        let mut text = format!(
+            "{} session · {} total",
+            format_usd(costs.session.cost_usd),
+            format_usd(costs.all_time.cost_usd)
+        );
+        if let Some(turn) = &costs.turn {
+            text = format!("{} turn · {}", format_usd(turn.cost_usd), text);
+        }
+        let tokens = |u: &UsageTotals| format!("{} in / {} out tokens", u.prompt_tokens, u.completion_tokens);
+        ui.separator();
+        ui.label(RichText::new(text).color(theme::TEXT_SECONDARY).small())
+            .on_hover_text(format!(
+                "Estimated from list prices (override in Settings → Pricing)\n\
+                 Session: {}\nAll time: {} over {} turns",
+                tokens(&costs.session),
+                tokens(&costs.all_time),
+                costs.all_time.turns
+            ));
+    }
 
-            ctx.set_fonts(fonts);
-            *loaded_flag.borrow_mut() = true;
+    /// Look for a stored key vault; if there is one, the app starts locked.
+    fn check_vault(&self, ctx: &egui::Context) {
+        let key_vault = self.key_vault.clone();
+        let result = self.vault_result.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match key_vault.exists().await {
+                Ok(found) => {
+                    *result.borrow_mut() = Some(VaultOutcome::Found(found));
+                    ctx.request_repaint();
+                }
+                Err(e) => log::warn!("Failed to check for a key vault: {}", e),
+            }
+        });
+    }
+
+    /// Carry out queued vault actions and apply finished ones.
+    fn poll_vault(&mut self, ctx: &egui::Context) {
+        for action in self.ui_state.vault.take_actions() {
+            self.start_vault_action(action, ctx);
+        }
+
+        let Some(outcome) = self.vault_result.borrow_mut().take() else {
+            return;
+        };
+        let panel = &mut self.ui_state.vault;
+        panel.busy = false;
+        match outcome {
+            VaultOutcome::Found(false) => {}
+            VaultOutcome::Found(true) => {
+                panel.status = VaultStatus::Locked;
+                panel.show_unlock = true;
+            }
+            VaultOutcome::Created(key) => {
+                panel.status = VaultStatus::Unlocked;
+                self.vault_key = Some(Rc::new(key));
+                self.ui_state.status_text = "API keys saved to the key vault".to_string();
+            }
+            VaultOutcome::Unlocked(key, keys) => {
+                panel.status = VaultStatus::Unlocked;
+                panel.show_unlock = false;
+                self.vault_key = Some(Rc::new(key));
+                self.vault_provider = self.config.llm.provider.clone();
+                self.config.llm.api_key = keys.get(&self.vault_provider).unwrap_or_default().to_string();
+                self.vault_keys = keys;
+                self.rebuild_llm();
+                self.sync_model_list(ctx);
+            }
+            VaultOutcome::Removed => {
+                panel.status = VaultStatus::Off;
+                self.vault_key = None;
+                self.vault_keys = VaultKeys::default();
+            }
+            VaultOutcome::Failed(message) => panel.error = Some(message),
+        }
+    }
+
+    fn start_vault_action(&mut self, action: VaultAction, ctx: &egui::Context) {
+        let key_vault = self.key_vault.clone();
+        let result = self.vault_result.clone();
+        let ctx = ctx.clone();
+        match action {
+            VaultAction::Create(passphrase) => {
+                let mut keys = VaultKeys::default();
+                keys.set(&self.config.llm.provider, &self.config.llm.api_key);
+                self.vault_keys = keys.clone();
+                self.vault_provider = self.config.llm.provider.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let outcome = match key_vault.create(&passphrase, &keys).await {
+                        Ok(key) => VaultOutcome::Created(key),
+                        Err(e) => VaultOutcome::Failed(e.to_string()),
+                    };
+                    *result.borrow_mut() = Some(outcome);
+                    ctx.request_repaint();
+                });
+            }
+            VaultAction::Unlock(passphrase) => {
+                wasm_bindgen_futures::spawn_local(async move {
+                    let outcome = match key_vault.unlock(&passphrase).await {
+                        Ok((key, keys)) => VaultOutcome::Unlocked(key, keys),
+                        Err(e) => VaultOutcome::Failed(e.to_string()),
+                    };
+                    *result.borrow_mut() = Some(outcome);
+                    ctx.request_repaint();
+                });
+            }
+            VaultAction::Lock => {
+                self.vault_key = None;
+                self.vault_keys = VaultKeys::default();
+                self.config.llm.api_key.clear();
+                self.ui_state.vault.status = VaultStatus::Locked;
+                self.ui_state.vault.busy = false;
+                self.rebuild_llm();
+                self.sync_model_list(&ctx);
+            }
+            VaultAction::Remove => {
+                wasm_bindgen_futures::spawn_local(async move {
+                    let outcome = match key_vault.remove().await {
+                        Ok(()) => VaultOutcome::Removed,
+                        Err(e) => VaultOutcome::Failed(e.to_string()),
+                    };
+                    *result.borrow_mut() = Some(outcome);
+                    ctx.request_repaint();
+                });
+            }
+        }
+    }
+
+    /// Keep the unlocked vault and the settings in step: switching provider
+    /// loads that provider's stored key, editing the key re-encrypts the vault.
+    fn sync_vault_key(&mut self) {
+        let Some(key) = self.vault_key.clone() else {
+            return;
+        };
+        let provider = self.config.llm.provider.clone();
+        if provider != self.vault_provider {
+            self.config.llm.api_key = self.vault_keys.get(&provider).unwrap_or_default().to_string();
+            self.vault_provider = provider;
+            return;
+        }
+        if !self.vault_keys.set(&provider, &self.config.llm.api_key) {
+            return;
+        }
+        let key_vault = self.key_vault.clone();
+        let keys = self.vault_keys.clone();
+        let result = self.vault_result.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = key_vault.save(&key, &keys).await {
+                *result.borrow_mut() = Some(VaultOutcome::Failed(format!("Failed to update the key vault: {}", e)));
+            }
+        });
+    }
+
+    /// Offer to summarize the current conversation before starting a new one.
+    fn archive_dialog(&mut self, ctx: &egui::Context) {
+        if !self.archive_prompt {
+            return;
+        }
+        let mut choice = None;
+        let modal = egui::Modal::new(egui::Id::new("archive_dialog")).show(ctx, |ui| {
+            ui.set_max_width(320.0);
+            ui.heading("Start a new session");
+            ui.label("Summarize this conversation first? The summary is kept with the \
+                      session and, with memory enabled, can be recalled later.");
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if ui.button("Summarize & archive").clicked() {
+                    choice = Some(true);
+                }
+                if ui.button("Just start new").clicked() {
+                    choice = Some(false);
+                }
+                if ui.button("Cancel").clicked() {
+                    self.archive_prompt = false;
+                    self.pending_session_config = None;
+                }
+            });
+        });
+        if modal.should_close() {
+            self.archive_prompt = false;
+            self.pending_session_config = None;
+        }
+        match choice {
+            Some(true) => {
+                self.archive_prompt = false;
+                self.archive_session(ctx);
+            }
+            Some(false) => {
+                self.archive_prompt = false;
+                router::navigate_to(&new_session_id());
+            }
+            None => {}
+        }
+    }
+
+    /// Summarize the current session via the LLM, store the summary as the
+    /// session note (and a memory), then switch to a fresh session.
+    fn archive_session(&self, ctx: &egui::Context) {
+        let history = self.session.borrow().messages.clone();
+        let llm = self.llm.clone();
+        let llm_config = self.session.borrow().config.apply(&self.config).llm;
+        let sessions = self.sessions.clone();
+        let session = self.session.clone();
+        let memory = self
+            .config
+            .memory
+            .enabled
+            .then(|| MemoryStore::new(self.storage.clone()));
+        let archiving = self.archiving.clone();
+        let ctx = ctx.clone();
+
+        archiving.set(true);
+        wasm_bindgen_futures::spawn_local(async move {
+            match summarize_conversation(llm.as_ref(), &llm_config, &history).await {
+                Ok(note) if !note.is_empty() => {
+                    session.borrow_mut().note = Some(note.clone());
+                    let snapshot = session.borrow().clone();
+                    if let Err(e) = sessions.save(&snapshot).await {
+                        log::warn!("Failed to save session note: {}", e);
+                    }
+                    if let Some(memory) = memory {
+                        let points: Vec<&str> = note
+                            .lines()
+                            .map(|l| l.trim().trim_start_matches(['-', '*', '•']).trim())
+                            .filter(|l| !l.is_empty())
+                            .collect();
+                        let fact = format!("Earlier session \"{}\": {}", snapshot.title, points.join("; "));
+                        let tags = vec![SESSION_SUMMARY_TAG.to_string(), snapshot.id.clone()];
+                        if let Err(e) = memory.remember(&fact, tags).await {
+                            log::warn!("Failed to store session summary: {}", e);
+                        }
+                    }
+                }
+                Ok(_) => log::warn!("LLM returned an empty session summary"),
+                Err(e) => log::warn!("Failed to summarize session: {}", e),
+            }
+            archiving.set(false);
+            router::navigate_to(&new_session_id());
             ctx.request_repaint();
-            log::info!("CJK font loaded");
         });
     }
+}
 
-    fn rebuild_llm(&mut self) {
-        self.llm = Rc::new(OpenAiCompatProvider::new(self.config.llm.clone()));
+/// Workspace dialog being shown; Rename and Delete act on the active workspace
+enum WorkspaceDialog {
+    Create {
+        name: String,
+        template: &'static WorkspaceTemplate,
+        separate_sessions: bool,
+    },
+    Rename { name: String },
+    Delete,
+}
+
+/// Overrides being chosen for a new session, prefilled with the current
+/// session's settings
+struct NewSessionDialog {
+    system_prompt: String,
+    model: String,
+    /// `None` follows the global temperature
+    temperature: Option<f32>,
+}
+
+/// The configured provider, wrapped in a fallback chain if any are set
+fn build_llm(config: &LlmConfig, event_bus: &EventBus, worker: &Option<Rc<LlmWorker>>) -> Rc<dyn LlmPort> {
+    if config.fallbacks.is_empty() {
+        return build_provider(config, event_bus, worker);
+    }
+    let chain = std::iter::once(config.clone())
+        .chain(config.fallbacks.iter().map(|target| config.for_fallback(target)))
+        .map(|c| FallbackEntry {
+            provider: c.provider.label().to_string(),
+            model: c.model.clone(),
+            llm: build_provider(&c, event_bus, worker),
+        })
+        .collect();
+    Rc::new(FallbackLlm::new(chain, event_bus.clone()))
+}
+
+/// `llm`, built from `config`, behind the response cache when it is
+/// turned on
+fn with_cache(
+    llm: Rc<dyn LlmPort>,
+    config: &LlmConfig,
+    cache: &ResponseCacheConfig,
+    storage: &Rc<dyn StoragePort>,
+) -> Rc<dyn LlmPort> {
+    if !cache.enabled {
+        return llm;
+    }
+    let api_base = config.api_base.clone().unwrap_or_else(|| config.provider.default_base_url().to_string());
+    Rc::new(CachedLlm::new(llm, storage.clone(), cache.ttl_secs, config.provider.label(), api_base))
+}
+
+/// Pick the LLM adapter for the configured provider.
+fn build_provider(config: &LlmConfig, event_bus: &EventBus, worker: &Option<Rc<LlmWorker>>) -> Rc<dyn LlmPort> {
+    match config.provider {
+        LlmProvider::Local => Rc::new(WebLlmProvider::new(config.clone(), event_bus.clone())),
+        LlmProvider::Demo => Rc::new(DemoLlm::new()),
+        _ => Rc::new(OpenAiCompatProvider::new(config.clone(), worker.clone())),
     }
 }
 
+fn new_session_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Size of the WASM linear memory, which only grows
+fn wasm_heap_bytes() -> Option<u64> {
+    let memory = wasm_bindgen::memory().dyn_into::<js_sys::WebAssembly::Memory>().ok()?;
+    let buffer = memory.buffer().dyn_into::<js_sys::ArrayBuffer>().ok()?;
+    Some(u64::from(buffer.byte_length()))
+}
+
 impl eframe::App for AgentApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Apply theme + start font loading on first frame
-        if self.first_frame {
-            theme::apply_theme(ctx);
-            Self::load_cjk_font(ctx.clone(), self.font_loaded.clone());
-            self.first_frame = false;
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // Apply the theme on the first frame and whenever it is changed
+        let preset = self.config.ui.theme;
+        if self.applied_theme != Some(preset) {
+            theme::apply_theme(ctx, preset);
+            self.applied_theme = Some(preset);
+        }
+        let accessibility = (self.config.ui.scale, self.config.ui.screen_reader);
+        if self.applied_accessibility != Some(accessibility) {
+            theme::apply_accessibility(ctx, &self.config.ui);
+            self.applied_accessibility = Some(accessibility);
         }
 
         // Drain events from the agent runtime and update UI state
         let events = self.event_bus.drain();
+        self.frame_stats.push(FrameSample {
+            interval_ms: ctx.input(|i| i.unstable_dt) * 1000.0,
+            cpu_ms: frame.info().cpu_usage.map(|s| s * 1000.0),
+            events: events.len(),
+        });
         if !events.is_empty() {
             self.ui_state.process_events(events);
             ctx.request_repaint();
         }
 
+        self.poll_config(ctx);
+        self.poll_config_transfer(ctx);
+        self.poll_sessions(ctx);
+        self.archive_dialog(ctx);
+        self.new_session_dialog(ctx);
+        self.workspace_dialog(ctx);
+        self.poll_viewer(ctx);
+        self.poll_fonts(ctx);
+        self.poll_editor(ctx);
+        self.poll_pastes(ctx);
+        self.poll_outbox(ctx);
+        self.poll_file_tree(ctx);
+        self.poll_chat_images(ctx);
+        self.poll_table_exports(ctx);
+        self.poll_diagrams(ctx);
+        self.poll_share(ctx);
+        self.poll_endpoint(ctx);
+        self.poll_shell_health();
+        self.poll_terminal(ctx);
+        self.poll_prompts();
+        self.poll_undo();
+        self.poll_vault(ctx);
+        self.poll_stats(ctx);
+        self.poll_diagnostics(ctx);
+        self.poll_eval(ctx);
+        self.poll_batch(ctx);
+        self.poll_replay(ctx);
+        vault::unlock_dialog(ctx, &mut self.ui_state.vault);
+        settings::import_preview_dialog(ctx, &mut self.ui_state.config_transfer);
+        stats::stats_window(ctx, &mut self.ui_state.stats);
+        self.logs_window(ctx);
+        plan::plan_window(ctx, &mut self.ui_state.plan);
+        if let Some(summary) = batch_panel::batch_window(ctx, &mut self.ui_state.batch) {
+            self.ui_state.push_notice(summary);
+        }
+        if let Some(path) = explorer::explorer_window(ctx, &mut self.ui_state.explorer, &mut self.ui_state.files) {
+            self.ui_state.viewer.open(&path);
+        }
+        if self.runtime_settings_pending && !self.ui_state.is_busy() {
+            self.apply_runtime_settings();
+        }
+
         // Request repaint while agent is busy (to poll for events)
         if self.ui_state.is_busy() {
             ctx.request_repaint();
@@ -195,15 +2737,27 @@ impl eframe::App for AgentApp {
                         .size(16.0),
                 );
                 ui.separator();
+                self.workspace_selector(ui);
+                ui.separator();
+                self.session_selector(ui);
+                ui.separator();
+                self.undo_menu(ui, ctx);
+                ui.separator();
+                self.persona_selector(ui);
+                ui.separator();
+                let model = self.session.borrow().config.apply(&self.config).llm.model;
+                let own_model = model != self.config.llm.model;
                 ui.label(
                     RichText::new(format!(
-                        "Provider: {} | Model: {}",
+                        "Provider: {} | Model: {}{}",
                         self.config.llm.provider.label(),
-                        self.config.llm.model
+                        model,
+                        if own_model { " (session)" } else { "" }
                     ))
                     .color(theme::TEXT_SECONDARY)
                     .small(),
                 );
+                self.cost_label(ui);
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     if ui
                         .selectable_label(self.ui_state.show_settings, "Settings")
@@ -211,6 +2765,24 @@ impl eframe::App for AgentApp {
                     {
                         self.ui_state.show_settings = !self.ui_state.show_settings;
                     }
+                    if ui.selectable_label(self.ui_state.explorer.open, "Files").clicked() {
+                        self.ui_state.explorer.open = !self.ui_state.explorer.open;
+                    }
+                    if ui.selectable_label(self.ui_state.plan.open, "Plan").clicked() {
+                        self.ui_state.plan.open = !self.ui_state.plan.open;
+                    }
+                    if ui.selectable_label(self.ui_state.batch.open, "Batch").clicked() {
+                        self.ui_state.batch.open = !self.ui_state.batch.open;
+                    }
+                    if ui.selectable_label(self.ui_state.stats.open, "Stats").clicked() {
+                        self.ui_state.stats.toggle();
+                    }
+                    if ui.selectable_label(self.ui_state.logs.open, "Logs").clicked() {
+                        self.ui_state.logs.open = !self.ui_state.logs.open;
+                    }
+                    vault::vault_indicator(ui, &mut self.ui_state.vault);
+                    ui.separator();
+                    health::health_indicator(ui, &self.ui_state.health, self.ui_state.online);
                 });
             });
         });
@@ -221,8 +2793,59 @@ impl eframe::App for AgentApp {
                 .min_width(280.0)
                 .max_width(350.0)
                 .show(ctx, |ui| {
-                    if settings::settings_panel(ui, &mut self.config) {
+                    settings::config_warnings(ui, &mut self.config_warnings);
+                    let mut changed = settings::settings_panel(
+                        ui,
+                        &mut self.config,
+                        &mut self.model_options.borrow_mut(),
+                    );
+                    ui.separator();
+                    settings::transfer_section(ui, &mut self.ui_state.config_transfer);
+                    ui.separator();
+                    if let Some(usage) = self.storage.usage() {
+                        changed |= settings::storage_section(ui, &mut self.config, &usage);
+                        ui.separator();
+                    }
+                    let retained = Retained {
+                        heap_bytes: wasm_heap_bytes(),
+                        messages: self.ui_state.messages.len(),
+                        terminal_lines: self.ui_state.terminal_lines.len(),
+                    };
+                    diagnostics::diagnostics_section(ui, &mut self.ui_state.diagnostics, &retained);
+                    ui.separator();
+                    vault::vault_section(ui, &mut self.ui_state.vault);
+                    ui.separator();
+                    let busy = self.ui_state.is_busy();
+                    eval_panel::eval_section(ui, &mut self.ui_state.eval, busy);
+                    if changed {
+                        self.save_config();
+                        self.sync_vault_key();
+                        self.apply_ui_settings();
+                        self.apply_runtime_settings();
                         self.rebuild_llm();
+                        self.sync_model_list(ctx);
+                    }
+                    let refresh = std::mem::take(&mut self.model_options.borrow_mut().refresh_requested);
+                    if refresh {
+                        self.load_models(ctx, true);
+                    }
+                });
+        }
+
+        // ── File viewer / editor side panel (while a file is open) ──
+        if self.ui_state.viewer.is_open() || self.ui_state.editor.is_open() {
+            SidePanel::left("file_viewer")
+                .resizable(true)
+                .default_width(420.0)
+                .min_width(240.0)
+                .show(ctx, |ui| {
+                    // A file opened from chat is previewed over the editor
+                    if self.ui_state.viewer.is_open() {
+                        if viewer::viewer_panel(ui, &mut self.ui_state.viewer) {
+                            self.ui_state.edit_viewed_file();
+                        }
+                    } else {
+                        editor::editor_panel(ui, &mut self.ui_state.editor);
                     }
                 });
         }
@@ -235,7 +2858,7 @@ impl eframe::App for AgentApp {
             // Chat panel (top portion)
             let chat_height = available.y - terminal_height - 12.0;
             ui.allocate_ui(Vec2::new(available.x, chat_height), |ui| {
-                if let Some(user_msg) = chat::chat_panel(ui, &mut self.ui_state) {
+                if let Some(user_msg) = chat::chat_panel(ui, &mut self.ui_state, &self.config.ui.chat) {
                     self.dispatch_message(user_msg, ctx);
                 }
             });
@@ -244,29 +2867,138 @@ impl eframe::App for AgentApp {
 
             // Terminal panel (bottom portion)
             ui.allocate_ui(Vec2::new(available.x, terminal_height), |ui| {
-                terminal::terminal_panel(ui, &self.ui_state);
+                if let Some(command) = terminal::terminal_panel(ui, &mut self.ui_state) {
+                    self.run_terminal_command(command, ctx);
+                }
             });
         });
+
+        // Redrawn every frame while shown, so the graph shows the frame
+        // rate rather than how long the UI sat idle
+        if self.config.ui.perf_overlay {
+            let counts = PerfCounts {
+                pending_storage: self.storage_meter.pending(),
+                storage_ops: self.storage_meter.total(),
+                messages: self.ui_state.messages.len(),
+                terminal_lines: self.ui_state.terminal_lines.len(),
+            };
+            perf::perf_overlay(ctx, &self.frame_stats, counts);
+            ctx.request_repaint();
+        }
     }
 }
 
 impl AgentApp {
     /// Dispatch a user message to the agent runtime (async, non-blocking).
-    fn dispatch_message(&self, text: String, ctx: &egui::Context) {
+    fn dispatch_message(&self, message: chat::Outgoing, ctx: &egui::Context) {
+        self.dispatch_turn(message, ctx, None);
+    }
+
+    /// Run `message` as a turn. With `report`, how the turn ended goes
+    /// there, and a message that couldn't reach the provider isn't queued
+    /// again.
+    fn dispatch_turn(&self, message: chat::Outgoing, ctx: &egui::Context, report: Option<Rc<RefCell<Option<TurnOutcome>>>>) {
+        let chat::Outgoing { text, attachments } = message;
+        let unsent = self.unsent.clone();
+        let turn_prompt = self.turn_prompt.clone();
         let runtime = self.runtime.clone();
-        let llm = self.llm.clone();
-        let shell = self.shell.clone();
+        let mut llm = self.llm.clone();
+        let mut shell = self.shell.clone();
+        let recording = self.config.record_sessions.then(|| {
+            let id = self.session.borrow().id.clone();
+            llm = Rc::new(RecordingLlm::new(llm.clone(), self.recordings.clone(), &id));
+            shell = Rc::new(RecordingShell::new(shell.clone(), self.recordings.clone(), &id));
+            (self.recordings.clone(), id)
+        });
         let vfs = self.vfs.clone();
         let ctx = ctx.clone();
+        let sessions = self.sessions.clone();
+        let session = self.session.clone();
+        let session_list = self.session_list.clone();
+        let snapshots = self.snapshots.clone();
+        let snapshot_list = self.snapshot_list.clone();
+        let usage_ledger = self.usage_ledger.clone();
+        let costs = self.costs.clone();
+        let activity_log = self.activity_log.clone();
+        let turn_recorder = self.turn_recorder.clone();
 
+        // The runtime stays borrowed for the whole turn. It is taken before
+        // anything is awaited, so a message sent before this turn's
+        // TurnStart reaches the UI finds it taken and goes back to the
+        // outbox instead of starting a second turn.
+        #[allow(clippy::await_holding_refcell_ref)]
         wasm_bindgen_futures::spawn_local(async move {
-            let result = {
-                let mut rt = runtime.borrow_mut();
-                rt.run_turn(&text, llm.as_ref(), shell.as_ref(), vfs.as_ref())
+            let Ok(mut rt) = runtime.try_borrow_mut() else {
+                match report {
+                    Some(report) => *report.borrow_mut() = Some(Err("Another turn was still running".to_string())),
+                    None => *unsent.borrow_mut() = Some(chat::Outgoing { text, attachments }),
+                }
+                ctx.request_repaint();
+                return;
+            };
+            *turn_prompt.borrow_mut() = Some(text.clone());
+            if let Some((recordings, id)) = &recording {
+                let prompt = TapeEntry::Prompt { text: text.clone() };
+                if let Err(e) = recordings.append(id, prompt).await {
+                    log::warn!("Failed to record prompt: {}", e);
+                }
+            }
+            let result = if rt.config.plan.enabled {
+                orchestrator::run_planned_turn(&mut rt, &text, &attachments, llm.as_ref(), shell.as_ref(), vfs.as_ref())
+                    .await
+            } else {
+                rt.run_turn_with_attachments(&text, &attachments, llm.as_ref(), shell.as_ref(), vfs.as_ref())
                     .await
             };
-            if let Err(e) = result {
+            drop(rt);
+            let outcome = result.as_ref().map(|_| ()).map_err(|e| e.user_message());
+            if let Err(e) = &result {
                 log::error!("Agent turn error: {}", e);
+                // Nothing reached the provider: queue the message to be
+                // sent again instead of keeping it as a failed turn
+                if report.is_none() && e.kind() == ErrorKind::Network && runtime.borrow_mut().withdraw_unanswered() {
+                    *unsent.borrow_mut() = Some(chat::Outgoing { text, attachments });
+                }
+            }
+
+            // Persist the conversation so it can be reopened via its URL
+            let history = runtime.borrow().history().to_vec();
+            let turn = UsageTotals::last_turn(&history);
+            let session_usage = UsageTotals::from_messages(&history);
+            session.borrow_mut().update_messages(history);
+            turn_prompt.borrow_mut().take();
+            let snapshot = session.borrow().clone();
+            if let Err(e) = sessions.save(&snapshot).await {
+                log::warn!("Failed to save session: {}", e);
+            }
+            if let Ok(list) = sessions.list().await {
+                *session_list.borrow_mut() = list;
+            }
+            if let Ok(list) = snapshots.list().await {
+                *snapshot_list.borrow_mut() = list;
+            }
+
+            {
+                let mut costs = costs.borrow_mut();
+                costs.session = session_usage;
+                costs.turn = Some(turn.clone());
+            }
+            match usage_ledger.record(&turn).await {
+                Ok(total) => costs.borrow_mut().all_time = total,
+                Err(e) => log::warn!("Failed to record usage: {}", e),
+            }
+
+            let record = turn_recorder.borrow_mut().finish(turn);
+            if let Some(mut record) = record {
+                if let Err(e) = result {
+                    record.error.get_or_insert_with(|| e.to_string());
+                }
+                if let Err(e) = activity_log.append(&record).await {
+                    log::warn!("Failed to record activity: {}", e);
+                }
+            }
+            if let Some(report) = report {
+                *report.borrow_mut() = Some(outcome);
             }
             ctx.request_repaint();
         });
@@ -275,30 +3007,39 @@ impl AgentApp {
 
 // ─── Stub shell for when Worker is not available ─────────────
 
-struct StubShell;
+/// Why the shell is missing when the worker couldn't even be created
+const STUB_SHELL_HINT: &str = "Wasmer-JS Worker failed to initialize. Ensure worker.js is served correctly.";
+
+struct StubShell {
+    /// What went wrong, shown with every command
+    hint: String,
+}
+
+impl StubShell {
+    fn new(hint: &str) -> Self {
+        Self { hint: hint.to_string() }
+    }
+}
 
 #[async_trait::async_trait(?Send)]
 impl ShellPort for StubShell {
     async fn execute(
         &self,
         cmd: &str,
-        _timeout_ms: Option<u64>,
+        _opts: &agent_types::tool::ExecOptions,
     ) -> agent_types::Result<agent_types::tool::ExecResult> {
         Ok(agent_types::tool::ExecResult {
-            stdout: format!(
-                "[Shell not available] Would execute: {}\n\
-                 Hint: Wasmer-JS Worker failed to initialize. \
-                 Ensure worker.js is served correctly.",
-                cmd
-            ),
+            stdout: format!("[Shell not available] Would execute: {}\nHint: {}", cmd, self.hint),
             stderr: String::new(),
             exit_code: 127,
+            duration_ms: 0,
         })
     }
 
     fn execute_streaming(
         &self,
         _cmd: &str,
+        _opts: &agent_types::tool::ExecOptions,
     ) -> std::pin::Pin<Box<dyn futures::Stream<Item = agent_core::ports::ShellStreamEvent>>> {
         Box::pin(futures::stream::once(async {
             agent_core::ports::ShellStreamEvent::Error("Shell not available".to_string())
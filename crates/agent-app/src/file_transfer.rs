@@ -0,0 +1,79 @@
+//! Browser file download and upload, for settings export/import and
+//! workspace files, and fetching assets such as fonts.
+
+use futures::channel::oneshot;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, HtmlInputElement, Url};
+
+/// Save `contents` as a download named `filename`.
+pub fn download(filename: &str, mime: &str, contents: &str) -> Result<(), JsValue> {
+    download_part(filename, mime, &JsValue::from_str(contents))
+}
+
+/// Save binary `contents` as a download named `filename`.
+pub fn download_bytes(filename: &str, mime: &str, contents: &[u8]) -> Result<(), JsValue> {
+    download_part(filename, mime, &js_sys::Uint8Array::from(contents))
+}
+
+fn download_part(filename: &str, mime: &str, part: &JsValue) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(part);
+    let options = BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = Blob::new_with_buffer_source_sequence_and_options(&parts, &options)?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| JsValue::from_str("no document"))?;
+    let anchor: HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(&url);
+    anchor.set_download(filename);
+    anchor.click();
+    Url::revoke_object_url(&url)
+}
+
+/// Ask the user for a file and read it as text; `None` if they cancel.
+/// Must follow a user gesture closely or browsers won't open the picker.
+pub async fn pick_text_file(accept: &str) -> Result<Option<String>, JsValue> {
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| JsValue::from_str("no document"))?;
+    let input: HtmlInputElement = document.create_element("input")?.dyn_into()?;
+    input.set_type("file");
+    input.set_accept(accept);
+
+    let (done, picked) = oneshot::channel::<()>();
+    let mut done = Some(done);
+    let on_done = Closure::<dyn FnMut()>::new(move || {
+        if let Some(done) = done.take() {
+            let _ = done.send(());
+        }
+    });
+    input.add_event_listener_with_callback("change", on_done.as_ref().unchecked_ref())?;
+    input.add_event_listener_with_callback("cancel", on_done.as_ref().unchecked_ref())?;
+    input.click();
+    let _ = picked.await;
+    drop(on_done);
+
+    let Some(file) = input.files().and_then(|files| files.get(0)) else {
+        return Ok(None);
+    };
+    let text = wasm_bindgen_futures::JsFuture::from(file.text()).await?;
+    Ok(text.as_string())
+}
+
+/// Fetch `url` (relative to the page or absolute) as bytes.
+pub async fn fetch_bytes(url: &str) -> Result<Vec<u8>, String> {
+    let window = web_sys::window().ok_or("no window")?;
+    let response = wasm_bindgen_futures::JsFuture::from(window.fetch_with_str(url))
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    let response: web_sys::Response = response.dyn_into().map_err(|_| "not a response".to_string())?;
+    if !response.ok() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let buffer = response.array_buffer().map_err(|e| format!("{:?}", e))?;
+    let buffer = wasm_bindgen_futures::JsFuture::from(buffer).await.map_err(|e| format!("{:?}", e))?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
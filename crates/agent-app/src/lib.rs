@@ -4,15 +4,30 @@
 //! It assembles all platform adapters and hands them to the egui UI.
 
 mod app;
+mod file_transfer;
+mod notify;
+mod router;
+mod tab_status;
 
+use std::rc::Rc;
+
+use agent_core::perf::{MeteredStorage, StorageMeter};
+use agent_core::ports::StoragePort;
+use agent_platform::storage::{auto_detect_storage, MemoryStorage};
+use agent_platform::workspace::WorkspaceManager;
+use agent_types::config::StorageConfig;
+use agent_types::workspace::Workspace;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
 /// WASM entry point — called from index.html
 #[wasm_bindgen(start)]
 pub async fn main() {
+    // Show panics over the canvas instead of leaving it frozen
+    agent_platform::crash::install();
+
     // Initialize logging
-    wasm_logger::init(wasm_logger::Config::default());
+    agent_platform::logging::init(log::LevelFilter::Debug);
     log::info!("Agent WASM starting...");
 
     // Launch the egui application
@@ -29,12 +44,37 @@ pub async fn main() {
         .dyn_into::<web_sys::HtmlCanvasElement>()
         .expect("Element is not a canvas");
 
+    // Open persistent storage before the app starts so sessions named in
+    // the URL can be restored on load
+    let storage = match auto_detect_storage().await {
+        Ok(s) => s,
+        Err(e) => {
+            log::warn!("Storage detection failed ({}), using memory", e);
+            Rc::new(MemoryStorage::with_byte_cap(StorageConfig::default().memory_cap_bytes))
+        }
+    };
+    // Counted for the perf overlay
+    let storage_meter = Rc::new(StorageMeter::default());
+    let storage: Rc<dyn StoragePort> = Rc::new(MeteredStorage::new(storage, storage_meter.clone()));
+    let workspaces = WorkspaceManager::new(storage.clone());
+    // A session the last run wrote out as it crashed
+    if let Some(id) = agent_platform::crash::restore(&workspaces).await {
+        router::navigate_to(&id);
+    }
+    let workspace = workspaces
+        .current()
+        .await
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to load the last workspace ({}), using the default", e);
+            Workspace::default_workspace()
+        });
+
     wasm_bindgen_futures::spawn_local(async move {
         eframe::WebRunner::new()
             .start(
                 canvas,
                 web_options,
-                Box::new(|cc| Ok(Box::new(app::AgentApp::new(cc)))),
+                Box::new(move |cc| Ok(Box::new(app::AgentApp::new(cc, storage, storage_meter, workspace)))),
             )
             .await
             .expect("Failed to start eframe");
@@ -0,0 +1,63 @@
+//! Browser notifications for turns that finish while the tab is hidden.
+//!
+//! Hidden tabs stop rendering frames, so this hooks the EventBus directly
+//! instead of waiting for the UI to drain events.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use agent_core::event_bus::EventBus;
+use agent_types::event::AgentEvent;
+use web_sys::{Notification, NotificationOptions, NotificationPermission, VisibilityState};
+
+/// Ask the browser for notification permission. Must be called from a
+/// user gesture (e.g. toggling the setting) or browsers ignore the request.
+pub fn request_permission() {
+    if Notification::permission() == NotificationPermission::Default {
+        if let Err(e) = Notification::request_permission() {
+            log::warn!("Notification permission request failed: {:?}", e);
+        }
+    }
+}
+
+/// Subscribe to the bus and notify on TurnEnd/Error while the tab is hidden.
+/// `enabled` mirrors the `ui.notify_on_turn_end` setting.
+pub fn install(bus: &EventBus, enabled: Rc<Cell<bool>>) {
+    // Only one notification per turn: a failing turn may emit Error + TurnEnd.
+    let notified = Cell::new(false);
+    bus.subscribe(move |event| {
+        let (title, body) = match event {
+            AgentEvent::TurnStart { .. } => {
+                notified.set(false);
+                return;
+            }
+            AgentEvent::TurnEnd { .. } => ("Agent finished", "The agent has finished its turn.".to_string()),
+            AgentEvent::Error { message } => ("Agent error", message.clone()),
+            _ => return,
+        };
+        if notified.get() || !enabled.get() || !tab_hidden() {
+            return;
+        }
+        notified.set(true);
+        show(title, &body);
+    });
+}
+
+fn tab_hidden() -> bool {
+    web_sys::window()
+        .and_then(|w| w.document())
+        .map(|d| d.visibility_state() == VisibilityState::Hidden)
+        .unwrap_or(false)
+}
+
+fn show(title: &str, body: &str) {
+    if Notification::permission() != NotificationPermission::Granted {
+        return;
+    }
+    let opts = NotificationOptions::new();
+    opts.set_body(body);
+    opts.set_tag("agent-turn");
+    if let Err(e) = Notification::new_with_options(title, &opts) {
+        log::warn!("Failed to show notification: {:?}", e);
+    }
+}
@@ -0,0 +1,93 @@
+//! Tiny hash router — reflects the active session in `location.hash`.
+//!
+//! Routes look like `#/session/<id>`. Setting the hash pushes a history
+//! entry, so browser back/forward moves between sessions; the `hashchange`
+//! listener hands the new id to the app, which switches on its next frame.
+//!
+//! Share links look like `#/share/<payload>`, the payload being the packed
+//! conversation (see `agent_platform::share`).
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+const SESSION_ROUTE: &str = "#/session/";
+const SHARE_ROUTE: &str = "#/share/";
+
+/// Session id from the current URL, if the hash is a session route.
+pub fn current_session() -> Option<String> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    parse_session(&hash)
+}
+
+fn parse_session(hash: &str) -> Option<String> {
+    let id = hash.strip_prefix(SESSION_ROUTE)?.trim_end_matches('/');
+    if id.is_empty() {
+        None
+    } else {
+        Some(id.to_string())
+    }
+}
+
+/// Payload of the share link in the current URL, if it is one.
+pub fn current_share() -> Option<String> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    let payload = hash.strip_prefix(SHARE_ROUTE)?;
+    if payload.is_empty() {
+        None
+    } else {
+        Some(payload.to_string())
+    }
+}
+
+/// Link to this page opening the shared conversation in `payload`.
+pub fn share_url(payload: &str) -> Option<String> {
+    let location = web_sys::window()?.location();
+    let origin = location.origin().ok()?;
+    let path = location.pathname().ok()?;
+    Some(format!("{}{}{}{}", origin, path, SHARE_ROUTE, payload))
+}
+
+/// Navigate to a session, adding a history entry.
+pub fn navigate_to(id: &str) {
+    if current_session().as_deref() == Some(id) {
+        return;
+    }
+    if let Some(window) = web_sys::window() {
+        let _ = window.location().set_hash(&format!("/session/{}", id));
+    }
+}
+
+/// Point the URL at a session without adding a history entry
+/// (used for the session created on first load).
+pub fn replace_with(id: &str) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    if let Ok(history) = window.history() {
+        let url = format!("{}{}", SESSION_ROUTE, id);
+        let _ = history.replace_state_with_url(&JsValue::NULL, "", Some(&url));
+    }
+}
+
+/// Listen for back/forward (and manual) hash edits. The requested session
+/// id is written into `pending`, a share link's payload into `shared`, for
+/// the app to pick up.
+pub fn listen(pending: Rc<RefCell<Option<String>>>, shared: Rc<RefCell<Option<String>>>, ctx: egui::Context) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let onhashchange = Closure::wrap(Box::new(move |_: web_sys::Event| {
+        if let Some(id) = current_session() {
+            *pending.borrow_mut() = Some(id);
+            ctx.request_repaint();
+        } else if let Some(payload) = current_share() {
+            *shared.borrow_mut() = Some(payload);
+            ctx.request_repaint();
+        }
+    }) as Box<dyn FnMut(web_sys::Event)>);
+    let _ = window
+        .add_event_listener_with_callback("hashchange", onhashchange.as_ref().unchecked_ref());
+    onhashchange.forget();
+}
@@ -0,0 +1,94 @@
+//! Tab title and favicon status indicator.
+//!
+//! Mirrors the agent state into `document.title` and a small SVG favicon so
+//! progress is visible from the tab strip. Driven by an EventBus listener
+//! because hidden tabs don't render frames.
+
+use agent_core::event_bus::EventBus;
+use agent_types::event::AgentEvent;
+use wasm_bindgen::JsCast;
+
+const APP_TITLE: &str = "WASM Agent";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TabStatus {
+    Idle,
+    Working,
+    Done,
+    Error,
+}
+
+impl TabStatus {
+    fn title(self) -> String {
+        match self {
+            TabStatus::Idle => APP_TITLE.to_string(),
+            TabStatus::Working => format!("⏳ Working… — {}", APP_TITLE),
+            TabStatus::Done => format!("✅ Done — {}", APP_TITLE),
+            TabStatus::Error => format!("❌ Error — {}", APP_TITLE),
+        }
+    }
+
+    /// Colour of the favicon dot (matches the UI theme palette)
+    fn color(self) -> &'static str {
+        match self {
+            TabStatus::Idle => "#6366f1",
+            TabStatus::Working => "#eab308",
+            TabStatus::Done => "#22c55e",
+            TabStatus::Error => "#ef4444",
+        }
+    }
+
+    fn favicon_href(self) -> String {
+        let svg = format!(
+            "<svg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 16 16'>\
+             <circle cx='8' cy='8' r='7' fill='{}'/></svg>",
+            self.color()
+        );
+        format!("data:image/svg+xml,{}", svg.replace('#', "%23"))
+    }
+}
+
+/// Set the initial title/favicon and keep them in sync with agent events.
+pub fn install(bus: &EventBus) {
+    apply(TabStatus::Idle);
+    bus.subscribe(|event| {
+        let status = match event {
+            AgentEvent::TurnStart { .. } | AgentEvent::ToolExecStart { .. } => TabStatus::Working,
+            AgentEvent::TurnEnd { .. } => TabStatus::Done,
+            AgentEvent::Error { .. } => TabStatus::Error,
+            _ => return,
+        };
+        apply(status);
+    });
+}
+
+fn apply(status: TabStatus) {
+    let Some(document) = web_sys::window().and_then(|w| w.document()) else {
+        return;
+    };
+    // An error stays visible even though the turn then ends
+    if status == TabStatus::Done && document.title() == TabStatus::Error.title() {
+        return;
+    }
+    document.set_title(&status.title());
+
+    let link = match document.query_selector("link[rel~='icon']") {
+        Ok(Some(el)) => el.dyn_into::<web_sys::HtmlLinkElement>().ok(),
+        _ => create_icon_link(&document),
+    };
+    if let Some(link) = link {
+        link.set_href(&status.favicon_href());
+    }
+}
+
+fn create_icon_link(document: &web_sys::Document) -> Option<web_sys::HtmlLinkElement> {
+    let link = document
+        .create_element("link")
+        .ok()?
+        .dyn_into::<web_sys::HtmlLinkElement>()
+        .ok()?;
+    link.set_rel("icon");
+    link.set_type("image/svg+xml");
+    document.head()?.append_child(&link).ok()?;
+    Some(link)
+}
@@ -0,0 +1,115 @@
+//! Activity log — one [`TurnRecord`] per agent turn, for the stats panel.
+//!
+//! A [`TurnRecorder`] watches the event bus while a turn runs (start time,
+//! tool calls and their outcome, errors); when the turn is over the app adds
+//! its token usage and appends the record under `activity:<seq>`. Only the
+//! most recent [`MAX_ACTIVITY_RECORDS`] turns are kept.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use agent_types::{
+    Result,
+    activity::{ToolRun, TurnRecord},
+    event::AgentEvent,
+    pricing::UsageTotals,
+};
+
+use crate::ports::StoragePort;
+
+const KEY_PREFIX: &str = "activity:";
+
+/// Turns kept in the log; older ones are dropped
+pub const MAX_ACTIVITY_RECORDS: usize = 2000;
+
+/// Builds the record of the turn in progress from bus events
+#[derive(Default)]
+pub struct TurnRecorder {
+    current: Option<TurnRecord>,
+    /// Tool name by call id, until the call ends
+    running: HashMap<String, String>,
+}
+
+impl TurnRecorder {
+    pub fn observe(&mut self, event: &AgentEvent) {
+        match event {
+            AgentEvent::TurnStart { .. } => {
+                self.current = Some(TurnRecord::start());
+                self.running.clear();
+            }
+            AgentEvent::ToolExecStart { call_id, tool_name, .. } => {
+                self.running.insert(call_id.clone(), tool_name.clone());
+            }
+            AgentEvent::ToolExecEnd { call_id, success, .. } => {
+                if let (Some(record), Some(name)) = (self.current.as_mut(), self.running.remove(call_id)) {
+                    record.tools.push(ToolRun { name, success: *success });
+                }
+            }
+            AgentEvent::Error { message } => {
+                if let Some(record) = self.current.as_mut() {
+                    record.error = Some(message.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Close the turn in progress with its usage. None if no turn started.
+    pub fn finish(&mut self, usage: UsageTotals) -> Option<TurnRecord> {
+        let mut record = self.current.take()?;
+        record.finish();
+        record.usage = usage;
+        Some(record)
+    }
+}
+
+pub struct ActivityLog {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl ActivityLog {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    pub async fn append(&self, record: &TurnRecord) -> Result<()> {
+        let keys = self.keys().await?;
+        let seq = keys
+            .last()
+            .and_then(|k| k.strip_prefix(KEY_PREFIX)?.parse::<u64>().ok())
+            .map_or(1, |n| n + 1);
+        self.storage.set(&key(seq), &serde_json::to_vec(record)?).await?;
+
+        let excess = (keys.len() + 1).saturating_sub(MAX_ACTIVITY_RECORDS);
+        for old in &keys[..excess] {
+            self.storage.delete(old).await?;
+        }
+        Ok(())
+    }
+
+    /// All records, oldest first. Unreadable entries are skipped.
+    pub async fn records(&self) -> Result<Vec<TurnRecord>> {
+        let mut records = Vec::new();
+        for key in self.keys().await? {
+            let Some(bytes) = self.storage.get(&key).await? else {
+                continue;
+            };
+            match serde_json::from_slice::<TurnRecord>(&bytes) {
+                Ok(record) => records.push(record),
+                Err(e) => log::warn!("Skipping corrupt activity record {}: {}", key, e),
+            }
+        }
+        Ok(records)
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = self.storage.list_keys(KEY_PREFIX).await?;
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Zero-padded so keys sort in log order
+fn key(seq: u64) -> String {
+    format!("{}{:016}", KEY_PREFIX, seq)
+}
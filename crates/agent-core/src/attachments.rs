@@ -0,0 +1,63 @@
+//! Files the user attaches to a message as context.
+//!
+//! Attached files are read when the turn starts and shown to the LLM in a
+//! system message just before the user's message, for that turn only: the
+//! conversation keeps the paths (in the message metadata), not the
+//! contents. Each file is cut off at [`MAX_FILE_BYTES`], and files past
+//! [`MAX_TOTAL_BYTES`] are only named, so a large attachment can't crowd
+//! out the rest of the request.
+
+use agent_types::message::Message;
+
+use crate::ports::VfsPort;
+use crate::tools::{describe_binary, looks_binary};
+
+/// Bytes of one attached file shown to the LLM
+pub const MAX_FILE_BYTES: usize = 32 * 1024;
+/// Bytes of all attached files shown to the LLM in one turn
+pub const MAX_TOTAL_BYTES: usize = 96 * 1024;
+
+/// The context message for `paths`, or `None` without attachments
+pub async fn attachment_context(paths: &[String], vfs: &dyn VfsPort) -> Option<Message> {
+    if paths.is_empty() {
+        return None;
+    }
+    let mut sections = Vec::with_capacity(paths.len());
+    let mut budget = MAX_TOTAL_BYTES;
+    for path in paths {
+        let section = match vfs.read_file(path).await {
+            Ok(data) => format_attachment(path, &data, &mut budget),
+            Err(e) => format!("── {}\n[could not be read: {}]", path, e),
+        };
+        sections.push(section);
+    }
+    Some(Message::system(format!(
+        "The user attached these files to their message:\n\n{}",
+        sections.join("\n\n")
+    )))
+}
+
+/// One file under its header, cut to what is left of `budget`
+pub fn format_attachment(path: &str, data: &[u8], budget: &mut usize) -> String {
+    let header = format!("── {} ({} bytes)", path, data.len());
+    if looks_binary(data) {
+        return format!("{}\n{}", header, describe_binary(path, data));
+    }
+    if *budget == 0 {
+        return format!("{}\n[not shown: the attachments are over the size limit; use read_file]", header);
+    }
+    let shown = data.len().min(MAX_FILE_BYTES).min(*budget);
+    *budget -= shown;
+    let text = String::from_utf8_lossy(&data[..shown]);
+    if shown < data.len() {
+        format!(
+            "{}\n{}\n[truncated after {} of {} bytes; use read_file for the rest]",
+            header,
+            text.trim_end_matches(char::REPLACEMENT_CHARACTER),
+            shown,
+            data.len()
+        )
+    } else {
+        format!("{}\n{}", header, text)
+    }
+}
@@ -0,0 +1,40 @@
+//! Prompt batches — prompts lined up to run one after another, each as its
+//! own turn of the current conversation.
+//!
+//! A batch is written as plain text: prompts are separated by lines holding
+//! only `---`, or, without any such line, each non-empty line is a prompt.
+//! Lines starting with `#` are comments. A JSON array of strings is read as
+//! is.
+
+use agent_types::{AgentError, Result};
+
+use crate::ports::VfsPort;
+
+/// Separator between multi-line prompts
+pub const PROMPT_SEPARATOR: &str = "---";
+
+/// The prompts in `text`, in order
+pub fn parse_batch(text: &str) -> Vec<String> {
+    if let Ok(prompts) = serde_json::from_str::<Vec<String>>(text) {
+        return prompts.into_iter().map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+    }
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim_start().starts_with('#')).collect();
+    if !lines.iter().any(|line| line.trim() == PROMPT_SEPARATOR) {
+        return lines.iter().map(|line| line.trim()).filter(|line| !line.is_empty()).map(String::from).collect();
+    }
+    lines
+        .split(|line| line.trim() == PROMPT_SEPARATOR)
+        .map(|prompt| prompt.join("\n").trim().to_string())
+        .filter(|prompt| !prompt.is_empty())
+        .collect()
+}
+
+/// Read the prompts file at `path`
+pub async fn load_batch(vfs: &dyn VfsPort, path: &str) -> Result<Vec<String>> {
+    let bytes = vfs.read_file(path).await?;
+    let prompts = parse_batch(&String::from_utf8_lossy(&bytes));
+    if prompts.is_empty() {
+        return Err(AgentError::Config(format!("{} has no prompts", path)));
+    }
+    Ok(prompts)
+}
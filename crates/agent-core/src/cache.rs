@@ -0,0 +1,139 @@
+//! LLM response cache.
+//!
+//! [`CachedLlm`] answers a chat completion it has seen before from storage
+//! instead of the network, so re-running a workflow, an eval suite or a
+//! replayed session doesn't pay for identical prompts again. The key hashes
+//! everything that shapes the reply — provider, API base URL, model,
+//! messages, tools and sampling settings — but not message ids or
+//! timestamps. Entries expire after the
+//! configured TTL. Errors and streams are never cached.
+//!
+//! A cached reply comes back as a new message marked
+//! [`MessageMeta::cached`](agent_types::message::MessageMeta::cached) and
+//! without usage, so it isn't counted as spent tokens twice.
+
+use std::pin::Pin;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use agent_types::{
+    Result,
+    message::{Message, MessageMeta},
+};
+
+use crate::ports::{ChatRequest, ChatResponse, LlmPort, LlmStreamEvent, StoragePort};
+use crate::retrieval::content_hash;
+
+const KEY_PREFIX: &str = "llm-cache:";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Unix seconds
+    stored_at: i64,
+    message: Message,
+}
+
+pub struct CachedLlm {
+    inner: Rc<dyn LlmPort>,
+    storage: Rc<dyn StoragePort>,
+    ttl_secs: u64,
+    /// Provider and API base URL `inner` talks to, so the same model name
+    /// at another endpoint gets its own entries
+    provider: String,
+    api_base: String,
+}
+
+impl CachedLlm {
+    pub fn new(
+        inner: Rc<dyn LlmPort>,
+        storage: Rc<dyn StoragePort>,
+        ttl_secs: u64,
+        provider: impl Into<String>,
+        api_base: impl Into<String>,
+    ) -> Self {
+        Self { inner, storage, ttl_secs, provider: provider.into(), api_base: api_base.into() }
+    }
+
+    /// The stored reply for `key`, if there is one younger than the TTL.
+    /// Expired entries are deleted.
+    async fn lookup(&self, key: &str) -> Option<CacheEntry> {
+        let bytes = self.storage.get(key).await.ok()??;
+        let entry: CacheEntry = serde_json::from_slice(&bytes).ok()?;
+        let age = Utc::now().timestamp() - entry.stored_at;
+        if age < 0 || age as u64 >= self.ttl_secs {
+            let _ = self.storage.delete(key).await;
+            return None;
+        }
+        Some(entry)
+    }
+}
+
+/// Storage key for `req` sent to `provider` at `api_base`; the length
+/// guards against hash collisions
+pub fn cache_key(req: &ChatRequest, provider: &str, api_base: &str) -> String {
+    let messages: Vec<Message> = req.messages.iter().map(|m| Message { meta: None, ..m.clone() }).collect();
+    let canonical = json!({
+        "provider": provider,
+        "api_base": api_base,
+        "model": req.model,
+        "messages": messages,
+        "tools": req.tools,
+        "max_tokens": req.max_tokens,
+        "temperature": req.temperature,
+        "top_p": req.top_p,
+        "frequency_penalty": req.frequency_penalty,
+        "presence_penalty": req.presence_penalty,
+        "stop": req.stop,
+        "seed": req.seed,
+        "reasoning": req.reasoning,
+    })
+    .to_string();
+    format!("{}{:016x}-{}", KEY_PREFIX, content_hash(canonical.as_bytes()), canonical.len())
+}
+
+#[async_trait(?Send)]
+impl LlmPort for CachedLlm {
+    async fn chat_completion(&self, req: ChatRequest) -> Result<ChatResponse> {
+        let key = cache_key(&req, &self.provider, &self.api_base);
+        if let Some(entry) = self.lookup(&key).await {
+            log::info!("LLM response cache hit ({})", key);
+            let mut message = entry.message;
+            let stored = message.meta.take().unwrap_or_default();
+            message.meta = Some(MessageMeta {
+                model: stored.model,
+                provider: stored.provider,
+                cached: true,
+                ..MessageMeta::new()
+            });
+            return Ok(ChatResponse { message, usage: None });
+        }
+
+        let response = self.inner.chat_completion(req).await?;
+        let entry = CacheEntry {
+            stored_at: Utc::now().timestamp(),
+            message: response.message.clone(),
+        };
+        match serde_json::to_vec(&entry) {
+            Ok(bytes) => {
+                if let Err(e) = self.storage.set(&key, &bytes).await {
+                    log::warn!("Failed to cache LLM response: {}", e);
+                }
+            }
+            Err(e) => log::warn!("Failed to cache LLM response: {}", e),
+        }
+        Ok(response)
+    }
+
+    fn stream_chat(&self, req: ChatRequest) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+        self.inner.stream_chat(req)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        self.inner.list_models().await
+    }
+}
@@ -0,0 +1,68 @@
+//! Saved settings — the [`AgentConfig`] under `config`, restored through
+//! [`restore_config`] so schema changes never lose the user's settings.
+//!
+//! API keys are not saved here; they belong in the key vault.
+
+use std::rc::Rc;
+
+use agent_types::{
+    Result,
+    config::{AgentConfig, CONFIG_VERSION},
+    migration::{RestoredConfig, restore_config},
+};
+
+use crate::ports::StoragePort;
+
+/// Where the settings are saved
+pub const CONFIG_KEY: &str = "config";
+/// A save that could not be restored as is, kept for recovery
+pub const BACKUP_KEY: &str = "config:backup";
+
+pub struct ConfigStore {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl ConfigStore {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    /// The saved config, or `None` if nothing was saved yet. A save that is
+    /// unreadable, or from a newer version, is copied to [`BACKUP_KEY`]
+    /// before the next save can overwrite it.
+    pub async fn load(&self) -> Result<Option<RestoredConfig>> {
+        let Some(bytes) = self.storage.get(CONFIG_KEY).await? else {
+            return Ok(None);
+        };
+        match restore_config(&bytes) {
+            Ok(restored) => {
+                if restored.saved_version > CONFIG_VERSION {
+                    self.storage.set(BACKUP_KEY, &bytes).await?;
+                }
+                Ok(Some(restored))
+            }
+            Err(e) => {
+                log::warn!("Saved settings are unreadable: {}", e);
+                self.storage.set(BACKUP_KEY, &bytes).await?;
+                Ok(Some(RestoredConfig {
+                    config: AgentConfig::default(),
+                    saved_version: 0,
+                    warnings: vec![format!(
+                        "Saved settings could not be read ({}); using defaults. The old settings were kept under `{}`",
+                        e, BACKUP_KEY
+                    )],
+                }))
+            }
+        }
+    }
+
+    pub async fn save(&self, config: &AgentConfig) -> Result<()> {
+        let mut config = config.clone();
+        config.version = CONFIG_VERSION;
+        config.llm.api_key.clear();
+        for target in &mut config.llm.fallbacks {
+            target.api_key.clear();
+        }
+        self.storage.set(CONFIG_KEY, &serde_json::to_vec(&config)?).await
+    }
+}
@@ -0,0 +1,182 @@
+//! Context-window strategies — which earlier messages go into a request.
+//!
+//! The runtime keeps the whole conversation. Before each LLM call a
+//! [`ContextStrategy`] picks which of the earlier messages (after the system
+//! prompt, before the current turn's user message) are sent; the system
+//! prompt and the current turn are always sent whole.
+//!
+//! Strategies choose whole [`units`]: a user message, or an assistant
+//! message together with the tool results that answer it, so a tool call is
+//! never sent without its results or the other way round.
+
+use std::ops::Range;
+
+use agent_types::{
+    config::{ContextConfig, ContextStrategyKind},
+    message::{Message, Role},
+};
+
+pub trait ContextStrategy {
+    /// The `earlier` messages to send, in their original order.
+    fn select(&self, earlier: &[Message]) -> Vec<Message>;
+}
+
+/// The strategy chosen in `config`
+pub fn strategy_for(config: &ContextConfig) -> Box<dyn ContextStrategy> {
+    match config.strategy {
+        ContextStrategyKind::FullHistory => Box::new(FullHistory),
+        ContextStrategyKind::SlidingWindow => Box::new(SlidingWindow { turns: config.window_turns }),
+        ContextStrategyKind::RecentBudget => Box::new(RecentBudget { max_tokens: config.max_tokens }),
+        ContextStrategyKind::ImportanceWeighted => {
+            Box::new(ImportanceWeighted { max_tokens: config.max_tokens })
+        }
+    }
+}
+
+/// The messages to send: the system prompt, what `strategy` keeps of the
+/// earlier conversation, and everything from `turn_start` on.
+pub fn request_messages(strategy: &dyn ContextStrategy, messages: &[Message], turn_start: usize) -> Vec<Message> {
+    let mut out = Vec::with_capacity(messages.len());
+    out.extend(messages.first().cloned());
+    if turn_start > 1 {
+        out.extend(strategy.select(&messages[1..turn_start]));
+    }
+    out.extend_from_slice(&messages[turn_start.max(1).min(messages.len())..]);
+    out
+}
+
+/// Rough token count of a message: about four characters per token, plus
+/// a little per message for the role and framing
+pub fn estimate_tokens(message: &Message) -> usize {
+    let chars = message.content.as_text().chars().count()
+        + message
+            .tool_calls
+            .iter()
+            .map(|tc| tc.function.name.len() + tc.function.arguments.chars().count())
+            .sum::<usize>();
+    chars / 4 + 4
+}
+
+/// Split `messages` into units that are kept or dropped together: each
+/// user or assistant message starts one, tool results join the unit before.
+pub fn units(messages: &[Message]) -> Vec<Range<usize>> {
+    let mut units: Vec<Range<usize>> = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+        match units.last_mut() {
+            Some(unit) if message.role == Role::Tool => unit.end = i + 1,
+            _ => units.push(i..i + 1),
+        }
+    }
+    units
+}
+
+fn unit_tokens(messages: &[Message]) -> usize {
+    messages.iter().map(estimate_tokens).sum()
+}
+
+fn collect(earlier: &[Message], mut keep: Vec<Range<usize>>) -> Vec<Message> {
+    keep.sort_by_key(|unit| unit.start);
+    keep.into_iter().flat_map(|unit| earlier[unit].iter().cloned()).collect()
+}
+
+/// Everything, as before strategies existed
+pub struct FullHistory;
+
+impl ContextStrategy for FullHistory {
+    fn select(&self, earlier: &[Message]) -> Vec<Message> {
+        earlier.to_vec()
+    }
+}
+
+/// The last `turns` turns, counted by user messages
+pub struct SlidingWindow {
+    pub turns: usize,
+}
+
+impl ContextStrategy for SlidingWindow {
+    fn select(&self, earlier: &[Message]) -> Vec<Message> {
+        if self.turns == 0 {
+            return Vec::new();
+        }
+        let start = earlier
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, m)| m.role == Role::User)
+            .nth(self.turns - 1)
+            .map_or(0, |(i, _)| i);
+        earlier[start..].to_vec()
+    }
+}
+
+/// The most recent units that fit in `max_tokens`, without gaps
+pub struct RecentBudget {
+    pub max_tokens: usize,
+}
+
+impl ContextStrategy for RecentBudget {
+    fn select(&self, earlier: &[Message]) -> Vec<Message> {
+        let mut used = 0;
+        let keep = units(earlier)
+            .into_iter()
+            .rev()
+            .take_while(|unit| {
+                used += unit_tokens(&earlier[unit.clone()]);
+                used <= self.max_tokens
+            })
+            .collect();
+        collect(earlier, keep)
+    }
+}
+
+/// The most useful units that fit in `max_tokens`. The first request of
+/// the conversation, which usually states the task, ranks highest; then
+/// other user requests, final answers and tool traffic, each ranking lower
+/// the more turns ago it was.
+pub struct ImportanceWeighted {
+    pub max_tokens: usize,
+}
+
+impl ImportanceWeighted {
+    fn score(messages: &[Message], first_request: bool, turns_ago: usize) -> f32 {
+        if first_request {
+            return 4.0;
+        }
+        let first = &messages[0];
+        let weight = match first.role {
+            Role::User => 3.0,
+            Role::Assistant if first.tool_calls.is_empty() => 2.0,
+            _ => 1.0,
+        };
+        weight / (1.0 + turns_ago as f32 * 0.25)
+    }
+}
+
+impl ContextStrategy for ImportanceWeighted {
+    fn select(&self, earlier: &[Message]) -> Vec<Message> {
+        let units = units(earlier);
+        let first_request = units.iter().position(|u| earlier[u.start].role == Role::User);
+        let mut turns_ago = earlier.iter().filter(|m| m.role == Role::User).count();
+        let mut ranked: Vec<(f32, Range<usize>)> = Vec::with_capacity(units.len());
+        for (i, unit) in units.into_iter().enumerate() {
+            if earlier[unit.start].role == Role::User {
+                turns_ago -= 1;
+            }
+            let score = Self::score(&earlier[unit.clone()], first_request == Some(i), turns_ago);
+            ranked.push((score, unit));
+        }
+        // Highest score first; newer first among equals
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.start.cmp(&a.1.start)));
+
+        let mut used = 0;
+        let mut keep = Vec::new();
+        for (_, unit) in ranked {
+            let tokens = unit_tokens(&earlier[unit.clone()]);
+            if used + tokens <= self.max_tokens {
+                used += tokens;
+                keep.push(unit);
+            }
+        }
+        collect(earlier, keep)
+    }
+}
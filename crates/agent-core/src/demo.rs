@@ -0,0 +1,196 @@
+//! Demo mode — a canned, offline stand-in for a real LLM.
+//!
+//! [`DemoLlm`] needs no network or API key. It picks a short script by the
+//! words in the latest user message and plays it one step per request:
+//! real tool calls first (so the terminal, workspace files and chat all
+//! have something to show), then a final answer. The step is worked out
+//! from the request itself, so the same conversation always gets the same
+//! replies.
+
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use futures::Stream;
+use serde_json::json;
+
+use agent_types::{
+    Result,
+    config::LlmProvider,
+    message::{FunctionCall, Message, Role, ToolCallRequest},
+};
+
+use crate::ports::*;
+use crate::runtime::REVIEW_CONFIRMATION;
+
+const DEMO_DIR: &str = "/workspace/demo";
+
+const FOOTER: &str = "\n\n_This is demo mode: the answers are scripted and no model is called. \
+Choose a provider in Settings and add an API key to talk to a real model._";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    /// Explain the demo without using tools
+    Help,
+    /// Run commands in the shell
+    Shell,
+    /// Write, list and read workspace files
+    Files,
+    /// A bit of everything
+    Tour,
+}
+
+impl Script {
+    fn for_prompt(prompt: &str) -> Script {
+        let prompt = prompt.to_lowercase();
+        let words: Vec<&str> = prompt.split(|c: char| !c.is_alphanumeric()).collect();
+        let has = |list: &[&str]| words.iter().any(|w| list.contains(w));
+        if has(&["shell", "bash", "command", "commands", "terminal", "run", "ls"]) {
+            Script::Shell
+        } else if has(&["file", "files", "write", "create", "save", "read"]) {
+            Script::Files
+        } else if has(&["help", "hello", "hi", "hey"]) {
+            Script::Help
+        } else {
+            Script::Tour
+        }
+    }
+
+    /// The tool call of `step`, or `None` once the script is down to its answer
+    fn step(&self, step: usize) -> Option<(&'static str, serde_json::Value)> {
+        let notes = format!("{}/notes.md", DEMO_DIR);
+        let script = format!("{}/hello.sh", DEMO_DIR);
+        let call = match (self, step) {
+            (Script::Shell, 0) => (
+                "bash",
+                json!({ "command": "echo \"Hello from the WASM shell\" && pwd && ls -la /workspace" }),
+            ),
+            (Script::Files, 0) => (
+                "write_file",
+                json!({
+                    "path": notes,
+                    "content": "# Demo notes\n\nWritten by the demo agent.\n\n- Files live in a virtual filesystem\n- They persist in browser storage\n",
+                }),
+            ),
+            (Script::Files, 1) => ("list_dir", json!({ "path": DEMO_DIR })),
+            (Script::Files, 2) => ("read_file", json!({ "path": notes })),
+            (Script::Tour, 0) => ("list_dir", json!({ "path": "/workspace" })),
+            (Script::Tour, 1) => (
+                "write_file",
+                json!({
+                    "path": script,
+                    "content": "#!/bin/sh\necho \"Hello from a script in the workspace\"\ndate\n",
+                }),
+            ),
+            (Script::Tour, 2) => ("bash", json!({ "command": format!("sh {}", script) })),
+            _ => return None,
+        };
+        Some(call)
+    }
+
+    fn intro(&self, step: usize) -> &'static str {
+        match (self, step) {
+            (Script::Shell, _) => "Let me run a few commands in the shell.",
+            (Script::Files, 0) => "I'll write a small notes file to the workspace.",
+            (Script::Files, 1) => "Now let's check it is there.",
+            (Script::Files, _) => "And read it back.",
+            (Script::Tour, 0) => "First, a look at the workspace.",
+            (Script::Tour, 1) => "Next I'll write a small shell script.",
+            (Script::Tour, _) | (Script::Help, _) => "Now let's run it.",
+        }
+    }
+
+    fn answer(&self, last_output: Option<&str>) -> String {
+        let output = last_output
+            .map(|text| {
+                let lines: Vec<&str> = text.lines().take(12).collect();
+                format!("\n\n```\n{}\n```", lines.join("\n"))
+            })
+            .unwrap_or_default();
+        let body = match self {
+            Script::Help => "Hi! I'm the demo agent. I can show what the agent does without an API key:\n\n\
+                - ask me to **run a command** to see the shell and terminal\n\
+                - ask me to **write a file** to see the virtual filesystem\n\
+                - ask anything else for a short tour of both"
+                .to_string(),
+            Script::Shell => format!("The commands ran in the in-browser shell:{}", output),
+            Script::Files => format!(
+                "I wrote `{}/notes.md`, listed the folder and read the file back:{}\n\nOpen it from the chat to view or edit it.",
+                DEMO_DIR, output
+            ),
+            Script::Tour => format!(
+                "I looked around the workspace, wrote `{}/hello.sh` and ran it:{}",
+                DEMO_DIR, output
+            ),
+        };
+        format!("{}{}", body, FOOTER)
+    }
+}
+
+/// Scripted, offline LLM for trying the app without an API key
+#[derive(Debug, Default)]
+pub struct DemoLlm;
+
+impl DemoLlm {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// The reply to `messages`: the next tool call of the script for the
+    /// latest user message, or its final answer.
+    pub fn respond(&self, messages: &[Message]) -> Message {
+        let turn_start = messages.iter().rposition(|m| m.role == Role::User);
+        let Some(turn_start) = turn_start else {
+            return Message::assistant(Script::Help.answer(None));
+        };
+        let prompt = messages[turn_start].content.as_text();
+        // The self-review asks for this word when all is well, and it is
+        if prompt.contains(REVIEW_CONFIRMATION) {
+            return Message::assistant(REVIEW_CONFIRMATION);
+        }
+        let script = Script::for_prompt(prompt);
+        let turn = &messages[turn_start + 1..];
+        let step = turn.iter().filter(|m| m.role == Role::Assistant && !m.tool_calls.is_empty()).count();
+
+        match script.step(step) {
+            Some((name, arguments)) => {
+                let user_turns = messages.iter().filter(|m| m.role == Role::User).count();
+                let mut message = Message::assistant(script.intro(step));
+                message.tool_calls = vec![ToolCallRequest {
+                    id: format!("demo_{}_{}", user_turns, step),
+                    function: FunctionCall { name: name.to_string(), arguments: arguments.to_string() },
+                }];
+                message
+            }
+            None => {
+                let last_output = turn.iter().rev().find(|m| m.role == Role::Tool).map(|m| m.content.as_text());
+                Message::assistant(script.answer(last_output))
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmPort for DemoLlm {
+    async fn chat_completion(&self, req: ChatRequest) -> Result<ChatResponse> {
+        Ok(ChatResponse { message: self.respond(&req.messages), usage: None })
+    }
+
+    fn stream_chat(&self, req: ChatRequest) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+        let message = self.respond(&req.messages);
+        let mut events = vec![LlmStreamEvent::Delta(message.content.as_text().to_string())];
+        events.extend(message.tool_calls.into_iter().enumerate().map(|(index, call)| {
+            LlmStreamEvent::ToolCallDelta {
+                index,
+                id: Some(call.id),
+                name: Some(call.function.name),
+                arguments_delta: call.function.arguments,
+            }
+        }));
+        events.push(LlmStreamEvent::Done);
+        Box::pin(futures::stream::iter(events))
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(vec![LlmProvider::Demo.default_model().to_string()])
+    }
+}
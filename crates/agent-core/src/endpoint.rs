@@ -0,0 +1,307 @@
+//! An OpenAI-compatible API served from the page.
+//!
+//! With the local endpoint on, a service worker answers same-origin
+//! `fetch`es to [`CHAT_COMPLETIONS_PATH`] and [`MODELS_PATH`] by handing
+//! them to the app, which runs them here against the configured provider
+//! (fallbacks and response cache included) with the stored key. Other
+//! tools on the origin, such as notebooks in the workspace, get a working
+//! API without a key of their own; the key never leaves the app.
+//!
+//! Requests are read in OpenAI's chat completions format whatever the
+//! provider speaks; unset sampling options take the configured values.
+//! `stream: true` is answered with server-sent events.
+
+use std::pin::Pin;
+use std::rc::Rc;
+
+use futures::{Stream, StreamExt};
+use serde_json::{Value, json};
+
+use agent_types::{
+    AgentError, ErrorKind, Result,
+    config::LlmConfig,
+    message::{ContentPart, FunctionCall, ImageUrl, Message, MessageContent, Role, ToolCallRequest},
+    tool::{ToolDefinition, ToolParameters},
+};
+
+use crate::ports::{ChatRequest, ChatResponse, LlmPort, LlmStreamEvent};
+
+pub const CHAT_COMPLETIONS_PATH: &str = "/v1/chat/completions";
+pub const MODELS_PATH: &str = "/v1/models";
+
+/// What to send back for a request
+pub enum EndpointResponse {
+    Json { status: u16, body: String },
+    /// Server-sent events, each a whole `data: …` event
+    Events(Pin<Box<dyn Stream<Item = String>>>),
+}
+
+impl EndpointResponse {
+    fn json(status: u16, body: Value) -> Self {
+        EndpointResponse::Json { status, body: body.to_string() }
+    }
+
+    fn error(status: u16, kind: &str, message: impl Into<String>) -> Self {
+        Self::json(status, json!({ "error": { "message": message.into(), "type": kind } }))
+    }
+}
+
+/// Answer a request for `path` (below the endpoint's root, e.g.
+/// `/v1/models`) with `llm`
+pub async fn handle(llm: Rc<dyn LlmPort>, config: &LlmConfig, method: &str, path: &str, body: &str) -> EndpointResponse {
+    match (method, path.trim_end_matches('/')) {
+        ("GET", MODELS_PATH) => {
+            let mut models = llm.list_models().await.unwrap_or_default();
+            if !models.contains(&config.model) {
+                models.insert(0, config.model.clone());
+            }
+            let owner = config.provider.label();
+            let data: Vec<Value> =
+                models.iter().map(|id| json!({ "id": id, "object": "model", "owned_by": owner })).collect();
+            EndpointResponse::json(200, json!({ "object": "list", "data": data }))
+        }
+        ("POST", CHAT_COMPLETIONS_PATH) => {
+            let (req, stream) = match parse_request(body, config) {
+                Ok(parsed) => parsed,
+                Err(e) => return EndpointResponse::error(400, "invalid_request_error", e.to_string()),
+            };
+            let reply = Reply::new(&req.model);
+            if stream {
+                return EndpointResponse::Events(event_stream(llm.stream_chat(req), reply));
+            }
+            match llm.chat_completion(req).await {
+                Ok(response) => EndpointResponse::json(200, reply.completion(&response)),
+                Err(e) => EndpointResponse::error(error_status(&e), "api_error", e.user_message()),
+            }
+        }
+        (_, CHAT_COMPLETIONS_PATH) | (_, MODELS_PATH) => {
+            EndpointResponse::error(405, "invalid_request_error", format!("{} is not supported here", method))
+        }
+        _ => EndpointResponse::error(404, "invalid_request_error", format!("Unknown endpoint {}", path)),
+    }
+}
+
+/// A chat completions body as a request, and whether it asks for a stream
+pub fn parse_request(body: &str, config: &LlmConfig) -> Result<(ChatRequest, bool)> {
+    let body: Value = serde_json::from_str(body)?;
+    let messages = body["messages"]
+        .as_array()
+        .filter(|m| !m.is_empty())
+        .ok_or_else(|| AgentError::Other("`messages` must be a non-empty array".to_string()))?
+        .iter()
+        .map(parse_message)
+        .collect::<Result<Vec<_>>>()?;
+    let tools = body["tools"].as_array().map(|tools| tools.iter().filter_map(parse_tool).collect()).unwrap_or_default();
+    let number = |key: &str| body[key].as_f64().map(|n| n as f32);
+    let stop = match &body["stop"] {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(items) => items.iter().filter_map(|s| s.as_str().map(String::from)).collect(),
+        _ => config.stop.clone(),
+    };
+    let req = ChatRequest {
+        messages,
+        tools,
+        model: body["model"].as_str().filter(|m| !m.is_empty()).unwrap_or(&config.model).to_string(),
+        max_tokens: body["max_completion_tokens"]
+            .as_u64()
+            .or_else(|| body["max_tokens"].as_u64())
+            .map_or(config.max_tokens, |n| n as u32),
+        temperature: number("temperature").unwrap_or(config.temperature),
+        top_p: number("top_p").or(config.top_p),
+        frequency_penalty: number("frequency_penalty").or(config.frequency_penalty),
+        presence_penalty: number("presence_penalty").or(config.presence_penalty),
+        stop,
+        seed: body["seed"].as_u64().or(config.seed),
+        reasoning: config.reasoning.clone(),
+    };
+    Ok((req, body["stream"].as_bool().unwrap_or(false)))
+}
+
+fn parse_message(value: &Value) -> Result<Message> {
+    let role = match value["role"].as_str() {
+        Some("system") | Some("developer") => Role::System,
+        Some("user") => Role::User,
+        Some("assistant") => Role::Assistant,
+        Some("tool") => Role::Tool,
+        other => return Err(AgentError::Other(format!("Unknown message role {:?}", other.unwrap_or("")))),
+    };
+    let content = match &value["content"] {
+        Value::Array(parts) => MessageContent::Parts(
+            parts
+                .iter()
+                .filter_map(|part| match part["type"].as_str()? {
+                    "text" => Some(ContentPart::Text { text: part["text"].as_str()?.to_string() }),
+                    "image_url" => {
+                        let url = part["image_url"]["url"].as_str().or_else(|| part["image_url"].as_str())?;
+                        Some(ContentPart::ImageUrl { image_url: ImageUrl { url: url.to_string() } })
+                    }
+                    _ => None,
+                })
+                .collect(),
+        ),
+        content => MessageContent::Text(content.as_str().unwrap_or_default().to_string()),
+    };
+    let tool_calls = value["tool_calls"]
+        .as_array()
+        .map(|calls| {
+            calls
+                .iter()
+                .map(|call| ToolCallRequest {
+                    id: call["id"].as_str().unwrap_or_default().to_string(),
+                    function: FunctionCall {
+                        name: call["function"]["name"].as_str().unwrap_or_default().to_string(),
+                        arguments: call["function"]["arguments"].as_str().unwrap_or("{}").to_string(),
+                    },
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(Message {
+        role,
+        content,
+        tool_call_id: value["tool_call_id"].as_str().map(String::from),
+        tool_calls,
+        reasoning: None,
+        meta: None,
+    })
+}
+
+fn parse_tool(value: &Value) -> Option<ToolDefinition> {
+    let function = &value["function"];
+    let parameters = &function["parameters"];
+    Some(ToolDefinition {
+        name: function["name"].as_str()?.to_string(),
+        description: function["description"].as_str().unwrap_or_default().to_string(),
+        parameters: ToolParameters {
+            schema_type: "object".to_string(),
+            properties: parameters["properties"].as_object().cloned().unwrap_or_default(),
+            required: parameters["required"]
+                .as_array()
+                .map(|names| names.iter().filter_map(|n| n.as_str().map(String::from)).collect())
+                .unwrap_or_default(),
+        },
+        strict: function["strict"].as_bool().unwrap_or(false),
+    })
+}
+
+/// HTTP status for a provider error
+pub fn error_status(error: &AgentError) -> u16 {
+    if let Some(status) = error.status() {
+        return status;
+    }
+    match error.kind() {
+        ErrorKind::Auth => 401,
+        ErrorKind::RateLimited | ErrorKind::QuotaExceeded => 429,
+        ErrorKind::InvalidRequest | ErrorKind::ContextLength => 400,
+        ErrorKind::NotFound => 404,
+        ErrorKind::Timeout => 504,
+        ErrorKind::Unavailable => 503,
+        _ => 502,
+    }
+}
+
+/// The parts every response body shares
+struct Reply {
+    id: String,
+    created: i64,
+    model: String,
+}
+
+impl Reply {
+    fn new(model: &str) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            id: format!("chatcmpl-{}", now.timestamp_millis()),
+            created: now.timestamp(),
+            model: model.to_string(),
+        }
+    }
+
+    fn completion(&self, response: &ChatResponse) -> Value {
+        let message = &response.message;
+        let text = message.content.as_text();
+        let mut reply = json!({
+            "role": "assistant",
+            "content": if text.is_empty() && !message.tool_calls.is_empty() { Value::Null } else { text.into() },
+        });
+        if !message.tool_calls.is_empty() {
+            reply["tool_calls"] = message
+                .tool_calls
+                .iter()
+                .map(|call| {
+                    json!({
+                        "id": call.id,
+                        "type": "function",
+                        "function": { "name": call.function.name, "arguments": call.function.arguments },
+                    })
+                })
+                .collect();
+        }
+        if let Some(reasoning) = &message.reasoning {
+            reply["reasoning_content"] = reasoning.as_str().into();
+        }
+        let finish = if message.tool_calls.is_empty() { "stop" } else { "tool_calls" };
+        let mut body = json!({
+            "id": self.id,
+            "object": "chat.completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{ "index": 0, "message": reply, "finish_reason": finish }],
+        });
+        if let Some(usage) = &response.usage {
+            body["usage"] = json!({
+                "prompt_tokens": usage.prompt_tokens,
+                "completion_tokens": usage.completion_tokens,
+                "total_tokens": usage.total_tokens,
+            });
+        }
+        body
+    }
+
+    fn chunk(&self, delta: Value, finish: Option<&str>) -> String {
+        let chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish }],
+        });
+        format!("data: {}\n\n", chunk)
+    }
+}
+
+/// `events` as server-sent events, ending with `data: [DONE]`
+fn event_stream(events: Pin<Box<dyn Stream<Item = LlmStreamEvent>>>, reply: Reply) -> Pin<Box<dyn Stream<Item = String>>> {
+    let opening = reply.chunk(json!({ "role": "assistant", "content": "" }), None);
+    let body = events
+        .scan((reply, false), |(reply, called), event| {
+            let out = match event {
+                LlmStreamEvent::Delta(text) => vec![reply.chunk(json!({ "content": text }), None)],
+                LlmStreamEvent::ToolCallDelta { index, id, name, arguments_delta } => {
+                    *called = true;
+                    let mut call = json!({ "index": index, "function": { "arguments": arguments_delta } });
+                    if let Some(id) = id {
+                        call["id"] = id.into();
+                        call["type"] = "function".into();
+                    }
+                    if let Some(name) = name {
+                        call["function"]["name"] = name.into();
+                    }
+                    vec![reply.chunk(json!({ "tool_calls": [call] }), None)]
+                }
+                LlmStreamEvent::Reasoning(text) => vec![reply.chunk(json!({ "reasoning_content": text }), None)],
+                LlmStreamEvent::Usage(_) => Vec::new(),
+                LlmStreamEvent::Done => {
+                    let finish = if *called { "tool_calls" } else { "stop" };
+                    vec![reply.chunk(json!({}), Some(finish)), "data: [DONE]\n\n".to_string()]
+                }
+                LlmStreamEvent::Error(message) => vec![
+                    format!("data: {}\n\n", json!({ "error": { "message": message, "type": "api_error" } })),
+                    "data: [DONE]\n\n".to_string(),
+                ],
+            };
+            futures::future::ready(Some(futures::stream::iter(out)))
+        })
+        .flatten();
+    Box::pin(futures::stream::once(futures::future::ready(opening)).chain(body))
+}
@@ -0,0 +1,144 @@
+//! Scripted evaluation — run a suite of prompts against a config and
+//! compare the results with an earlier run.
+//!
+//! Each case of an [`EvalSuite`] is one turn of a fresh [`AgentRuntime`], so
+//! cases don't see each other's history. The outcome records the final
+//! message, the tools called, token usage and duration, and is checked
+//! against the case's expectations. Results are saved as JSON next to the
+//! suite (`<suite>.results.json`); the next run uses them as its baseline
+//! and writes a Markdown comparison (`<suite>.report.md`).
+//!
+//! Tools run against the real workspace, so suites should stick to
+//! prompts whose side effects are harmless.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use agent_types::{
+    AgentError, Result,
+    config::AgentConfig,
+    eval::{EvalCase, EvalOutcome, EvalReport, EvalSuite, render_report},
+    message::Role,
+    pricing::UsageTotals,
+};
+
+use crate::activity::TurnRecorder;
+use crate::event_bus::EventBus;
+use crate::ports::{LlmPort, ShellPort, VfsPort};
+use crate::runtime::AgentRuntime;
+
+/// Where the results of the suite at `suite_path` are kept
+pub fn results_path(suite_path: &str) -> String {
+    format!("{}.results.json", suite_path.trim_end_matches(".json"))
+}
+
+/// Where the Markdown report for the suite at `suite_path` is written
+pub fn report_path(suite_path: &str) -> String {
+    format!("{}.report.md", suite_path.trim_end_matches(".json"))
+}
+
+pub async fn load_suite(vfs: &dyn VfsPort, path: &str) -> Result<EvalSuite> {
+    let bytes = vfs.read_file(path).await?;
+    let suite: EvalSuite = serde_json::from_slice(&bytes)
+        .map_err(|e| AgentError::Config(format!("Invalid eval suite {}: {}", path, e)))?;
+    if suite.cases.is_empty() {
+        return Err(AgentError::Config(format!("Eval suite {} has no cases", path)));
+    }
+    Ok(suite)
+}
+
+/// Run one case in a fresh runtime.
+pub async fn run_case(
+    config: &AgentConfig,
+    case: &EvalCase,
+    llm: &dyn LlmPort,
+    shell: &dyn ShellPort,
+    vfs: &dyn VfsPort,
+) -> EvalOutcome {
+    let bus = EventBus::new();
+    let recorder = Rc::new(RefCell::new(TurnRecorder::default()));
+    let observer = recorder.clone();
+    bus.subscribe(move |event| observer.borrow_mut().observe(event));
+
+    let mut runtime = AgentRuntime::new(config.clone(), bus);
+    let result = runtime.run_turn(&case.prompt, llm, shell, vfs).await;
+    let history = runtime.history();
+    let usage = UsageTotals::last_turn(history);
+    let record = recorder.borrow_mut().finish(usage.clone());
+
+    let mut outcome = EvalOutcome {
+        name: case.name.clone(),
+        final_message: history
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::Assistant && m.tool_calls.is_empty())
+            .map(|m| m.content.as_text().to_string())
+            .unwrap_or_default(),
+        tool_calls: Vec::new(),
+        usage,
+        duration_ms: 0,
+        error: result.err().map(|e| e.to_string()),
+        failures: Vec::new(),
+    };
+    if let Some(record) = record {
+        outcome.tool_calls = record.tools.into_iter().map(|run| run.name).collect();
+        outcome.duration_ms = record.duration_ms;
+        if outcome.error.is_none() {
+            outcome.error = record.error;
+        }
+    }
+    outcome.check(case);
+    outcome
+}
+
+/// Run every case of `suite`, in order.
+pub async fn run_suite(
+    config: &AgentConfig,
+    suite: &EvalSuite,
+    llm: &dyn LlmPort,
+    shell: &dyn ShellPort,
+    vfs: &dyn VfsPort,
+) -> EvalReport {
+    let target = format!("{} / {}", config.llm.provider.label(), config.llm.model);
+    let mut report = EvalReport::new(&suite.name, &target);
+    for case in &suite.cases {
+        let outcome = run_case(config, case, llm, shell, vfs).await;
+        log::info!(
+            "Eval case {}: {}",
+            case.name,
+            if outcome.passed() { "pass".to_string() } else { outcome.failures.join("; ") }
+        );
+        report.outcomes.push(outcome);
+    }
+    report
+}
+
+/// Run the suite at `suite_path`, compare with the previous results and
+/// write the report and the new results. Returns the report and the
+/// Markdown summary.
+pub async fn run_suite_file(
+    config: &AgentConfig,
+    suite_path: &str,
+    llm: &dyn LlmPort,
+    shell: &dyn ShellPort,
+    vfs: &dyn VfsPort,
+) -> Result<(EvalReport, String)> {
+    let suite = load_suite(vfs, suite_path).await?;
+    let results = results_path(suite_path);
+    let baseline = match vfs.read_file(&results).await {
+        Ok(bytes) => match serde_json::from_slice::<EvalReport>(&bytes) {
+            Ok(report) => Some(report),
+            Err(e) => {
+                log::warn!("Ignoring unreadable eval results {}: {}", results, e);
+                None
+            }
+        },
+        Err(_) => None,
+    };
+
+    let report = run_suite(config, &suite, llm, shell, vfs).await;
+    let summary = render_report(&report, baseline.as_ref());
+    vfs.write_file(&report_path(suite_path), summary.as_bytes()).await?;
+    vfs.write_file(&results, &serde_json::to_vec_pretty(&report)?).await?;
+    Ok((report, summary))
+}
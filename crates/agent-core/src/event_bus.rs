@@ -2,28 +2,77 @@
 //!
 //! The bus is single-threaded (WASM constraint) and uses interior mutability
 //! via RefCell. Events are buffered and drained by the UI on each frame.
+//!
+//! Listeners can additionally subscribe to be called synchronously on every
+//! emit. This matters in the browser: a hidden tab stops rendering frames, so
+//! anything that must react while the tab is in the background (notifications,
+//! tab title) cannot wait for the next `drain()`.
+//!
+//! A bus built with [`EventBus::with_delta_coalescing`] merges consecutive
+//! `LlmDelta` events that are still queued, so a token-by-token stream
+//! becomes roughly one event per frame (or per `max_chars` of text) instead
+//! of one per token. Listeners still see every delta as it is emitted.
 
 use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::rc::Rc;
 use agent_types::event::AgentEvent;
 
+type Listener = Rc<dyn Fn(&AgentEvent)>;
+
+/// Queued delta text merged into one event before a new one is started
+pub const DEFAULT_DELTA_COALESCE_CHARS: usize = 512;
+
 /// Shared event bus — clone-cheap via Rc.
 #[derive(Clone)]
 pub struct EventBus {
     inner: Rc<RefCell<VecDeque<AgentEvent>>>,
+    listeners: Rc<RefCell<Vec<Listener>>>,
+    /// Merge queued deltas up to this many bytes (0 = never merge)
+    coalesce_chars: usize,
 }
 
 impl EventBus {
     pub fn new() -> Self {
         Self {
             inner: Rc::new(RefCell::new(VecDeque::new())),
+            listeners: Rc::new(RefCell::new(Vec::new())),
+            coalesce_chars: 0,
+        }
+    }
+
+    /// A bus that merges consecutive queued `LlmDelta` events until the
+    /// merged text reaches `max_chars` bytes.
+    pub fn with_delta_coalescing(max_chars: usize) -> Self {
+        Self {
+            coalesce_chars: max_chars,
+            ..Self::new()
         }
     }
 
     /// Publish an event. Called by the agent runtime.
     pub fn emit(&self, event: AgentEvent) {
-        self.inner.borrow_mut().push_back(event);
+        // Snapshot the listener list so a listener may itself emit or subscribe.
+        let listeners: Vec<Listener> = self.listeners.borrow().clone();
+        for listener in &listeners {
+            listener(&event);
+        }
+        let mut queue = self.inner.borrow_mut();
+        if let (AgentEvent::LlmDelta { token }, Some(AgentEvent::LlmDelta { token: queued })) =
+            (&event, queue.back_mut())
+        {
+            if queued.len() < self.coalesce_chars {
+                queued.push_str(token);
+                return;
+            }
+        }
+        queue.push_back(event);
+    }
+
+    /// Register a callback invoked synchronously for every emitted event.
+    /// Listeners observe events; they do not consume them from the queue.
+    pub fn subscribe(&self, listener: impl Fn(&AgentEvent) + 'static) {
+        self.listeners.borrow_mut().push(Rc::new(listener));
     }
 
     /// Drain all pending events. Called by the UI layer each frame.
@@ -0,0 +1,92 @@
+//! Provider fallback chain.
+//!
+//! [`FallbackLlm`] wraps the primary provider and the configured fallbacks.
+//! A request that fails with a retryable error (rate limit, server error,
+//! network, timeout) is sent to the next entry, with that entry's model.
+//! The response is annotated with the provider and model that answered, and
+//! each switch is reported as [`AgentEvent::LlmFallback`].
+
+use std::pin::Pin;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use futures::Stream;
+
+use agent_types::{AgentError, Result, event::AgentEvent};
+
+use crate::event_bus::EventBus;
+use crate::ports::{ChatRequest, ChatResponse, LlmPort, LlmStreamEvent};
+
+/// One provider in the chain
+pub struct FallbackEntry {
+    /// Provider label, recorded on the messages it answers
+    pub provider: String,
+    pub model: String,
+    pub llm: Rc<dyn LlmPort>,
+}
+
+pub struct FallbackLlm {
+    chain: Vec<FallbackEntry>,
+    event_bus: EventBus,
+}
+
+impl FallbackLlm {
+    /// `chain[0]` is the primary provider.
+    pub fn new(chain: Vec<FallbackEntry>, event_bus: EventBus) -> Self {
+        Self { chain, event_bus }
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmPort for FallbackLlm {
+    async fn chat_completion(&self, req: ChatRequest) -> Result<ChatResponse> {
+        let mut last_error: Option<AgentError> = None;
+        for (i, entry) in self.chain.iter().enumerate() {
+            if let Some(e) = &last_error {
+                self.event_bus.emit(AgentEvent::LlmFallback {
+                    provider: entry.provider.clone(),
+                    model: entry.model.clone(),
+                    reason: e.user_message(),
+                });
+            }
+
+            let request = ChatRequest {
+                model: entry.model.clone(),
+                ..req.clone()
+            };
+            match entry.llm.chat_completion(request).await {
+                Ok(mut response) => {
+                    let meta = response.message.stamp();
+                    meta.provider = Some(entry.provider.clone());
+                    meta.model.get_or_insert_with(|| entry.model.clone());
+                    return Ok(response);
+                }
+                Err(e) if e.is_retryable() && i + 1 < self.chain.len() => {
+                    log::warn!("{} / {} failed ({}), falling back", entry.provider, entry.model, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| AgentError::Config("No LLM provider configured".to_string())))
+    }
+
+    /// Streams come from the primary provider only: once tokens have been
+    /// shown, switching providers mid-answer would garble the output.
+    fn stream_chat(&self, req: ChatRequest) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+        match self.chain.first() {
+            Some(primary) => primary.llm.stream_chat(req),
+            None => Box::pin(futures::stream::iter([
+                LlmStreamEvent::Error("No LLM provider configured".to_string()),
+                LlmStreamEvent::Done,
+            ])),
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        match self.chain.first() {
+            Some(primary) => primary.llm.list_models().await,
+            None => Ok(Vec::new()),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Sandboxed JavaScript — the `run_js` tool.
+//!
+//! Scripts run through a [`JsPort`], which evaluates each one in a fresh
+//! worker with no DOM and stops it at [`JsConfig::timeout_ms`]. The model
+//! sees what the script logged, then
+//! its result or the exception it threw, capped at
+//! [`JsConfig::max_output_chars`]. Quick data transformations and
+//! snippets don't need the shell. The worker shares the app's origin, so
+//! this is not a security boundary.
+
+use std::rc::Rc;
+
+use agent_types::{
+    AgentError, ErrorKind, Result,
+    config::JsConfig,
+    tool::{ParamSchema, ToolDefinition, ToolParameters},
+};
+
+use crate::ports::{JsPort, JsRun};
+use crate::web::cap_text;
+
+pub struct JsRunner {
+    sandbox: Rc<dyn JsPort>,
+}
+
+impl JsRunner {
+    pub fn new(sandbox: Rc<dyn JsPort>) -> Self {
+        Self { sandbox }
+    }
+
+    pub fn tool_definition() -> ToolDefinition {
+        ToolDefinition {
+            name: "run_js".to_string(),
+            description: "Run JavaScript in a fresh Web Worker (no DOM) and return \
+                what it logged with console.log and the value of its last expression (promises are \
+                awaited). Each run starts fresh and is stopped after a few seconds. Use it for quick \
+                calculations and data transformations, or to try out a snippet"
+                .to_string(),
+            parameters: ToolParameters::object()
+                .property("code", ParamSchema::string("The JavaScript to run"), true),
+            strict: true,
+        }
+    }
+
+    /// Run `code` within the configured time limit
+    pub async fn run(&self, code: &str, config: &JsConfig) -> Result<JsRun> {
+        if code.trim().is_empty() {
+            return Err(AgentError::api(ErrorKind::InvalidRequest, "there is no code to run"));
+        }
+        self.sandbox.run(code, config.timeout_ms).await.map_err(|e| match e {
+            AgentError::Timeout(ms) => AgentError::api(
+                ErrorKind::Timeout,
+                format!("the script was stopped after {} ms; it may never finish", ms),
+            ),
+            e => e,
+        })
+    }
+}
+
+/// A run as the model sees it: the console output, then the result or the
+/// exception, cut to `max_chars` (0 = no limit)
+pub fn format_run(run: &JsRun, max_chars: usize) -> String {
+    let mut sections = Vec::new();
+    if !run.console.is_empty() {
+        sections.push(format!("Console:\n{}", run.console.join("\n")));
+    }
+    if let Some(error) = &run.error {
+        sections.push(format!("Uncaught {}", error));
+    } else if let Some(result) = &run.result {
+        sections.push(format!("Result: {}", result));
+    }
+    if sections.is_empty() {
+        return "The script finished without logging anything or returning a value".to_string();
+    }
+    cap_text(&sections.join("\n\n"), max_chars)
+}
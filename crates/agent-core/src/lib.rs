@@ -1,7 +1,33 @@
 pub mod ports;
 pub mod runtime;
+pub mod config_store;
+pub mod context;
+pub mod demo;
 pub mod event_bus;
+pub mod eval;
+pub mod fallback;
 pub mod tools;
+pub mod retrieval;
+pub mod memory;
+pub mod models;
+pub mod prompts;
+pub mod replay;
+pub mod snapshots;
+pub mod templates;
+pub mod undo;
+pub mod usage;
+pub mod activity;
+pub mod attachments;
+pub mod cache;
+pub mod batch;
+pub mod orchestrator;
+pub mod web;
+pub mod replace;
+pub mod js;
+pub mod sql;
+pub mod endpoint;
+pub mod logs;
+pub mod perf;
 
 #[cfg(test)]
 mod tests;
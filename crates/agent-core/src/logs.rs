@@ -0,0 +1,128 @@
+//! The in-app log: recent `log` records, kept for the log panel.
+//!
+//! The platform's logger writes every record to the browser console and
+//! to [`global`], a ring buffer of the last [`LOG_CAPACITY`] records, so a
+//! user can look at what happened, or download it for a bug report,
+//! without opening the developer tools.
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+
+use log::Level;
+
+/// Records kept before the oldest are dropped
+pub const LOG_CAPACITY: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub level: Level,
+    /// Module that logged it, e.g. `agent_platform::llm::worker`
+    pub target: String,
+    pub message: String,
+    /// Local time, RFC 3339 with milliseconds
+    pub timestamp: String,
+}
+
+impl LogRecord {
+    /// A record stamped with the current time
+    pub fn new(level: Level, target: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            target: target.into(),
+            message: message.into(),
+            timestamp: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+        }
+    }
+
+    /// The time of day, e.g. "14:05:09.312"
+    pub fn time(&self) -> &str {
+        self.timestamp.get(11..23).unwrap_or(&self.timestamp)
+    }
+
+    /// The record as one line of text
+    pub fn line(&self) -> String {
+        format!("{} {:<5} {}: {}", self.timestamp, self.level, self.target, self.message)
+    }
+}
+
+/// The most recent records, oldest first
+#[derive(Debug)]
+pub struct LogBuffer {
+    records: VecDeque<LogRecord>,
+    capacity: usize,
+    /// Records dropped to make room since the last clear
+    dropped: u64,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self { records: VecDeque::new(), capacity: capacity.max(1), dropped: 0 }
+    }
+
+    pub fn push(&mut self, record: LogRecord) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+            self.dropped += 1;
+        }
+        self.records.push_back(record);
+    }
+
+    pub fn records(&self) -> &VecDeque<LogRecord> {
+        &self.records
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.dropped = 0;
+    }
+
+    /// Every record, a line each
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        if self.dropped > 0 {
+            text.push_str(&format!("({} older records were dropped)\n", self.dropped));
+        }
+        for record in &self.records {
+            text.push_str(&record.line());
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Which records the log panel shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    /// The least severe level shown
+    pub level: Level,
+    /// Shown only if in the target or message, ignoring case
+    pub text: String,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self { level: Level::Info, text: String::new() }
+    }
+}
+
+impl LogFilter {
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        if record.level > self.level {
+            return false;
+        }
+        let needle = self.text.trim().to_lowercase();
+        needle.is_empty()
+            || record.target.to_lowercase().contains(&needle)
+            || record.message.to_lowercase().contains(&needle)
+    }
+}
+
+/// The app's log
+pub fn global() -> &'static Mutex<LogBuffer> {
+    static LOG: OnceLock<Mutex<LogBuffer>> = OnceLock::new();
+    LOG.get_or_init(|| Mutex::new(LogBuffer::new(LOG_CAPACITY)))
+}
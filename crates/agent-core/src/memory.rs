@@ -0,0 +1,180 @@
+//! Long-term memory — facts the agent keeps across sessions.
+//!
+//! Each memory is a JSON document stored under `memory:<id>`. Recall is a
+//! simple keyword match over the fact and its tags, so it works without an
+//! embeddings model.
+
+use std::rc::Rc;
+
+use agent_types::{
+    Result,
+    config::LlmConfig,
+    memory::Memory,
+    message::{Message, Role},
+    tool::{ParamSchema, ToolDefinition, ToolParameters},
+};
+
+use crate::ports::{ChatRequest, LlmPort, StoragePort};
+
+const KEY_PREFIX: &str = "memory:";
+
+pub struct MemoryStore {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl MemoryStore {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    pub async fn remember(&self, fact: &str, tags: Vec<String>) -> Result<Memory> {
+        let memory = Memory::new(fact.trim(), tags);
+        let bytes = serde_json::to_vec(&memory)?;
+        self.storage
+            .set(&format!("{}{}", KEY_PREFIX, memory.id), &bytes)
+            .await?;
+        Ok(memory)
+    }
+
+    pub async fn forget(&self, id: &str) -> Result<()> {
+        self.storage.delete(&format!("{}{}", KEY_PREFIX, id)).await
+    }
+
+    /// All memories, newest first. Unreadable entries are skipped.
+    pub async fn list(&self) -> Result<Vec<Memory>> {
+        let mut memories = Vec::new();
+        for key in self.storage.list_keys(KEY_PREFIX).await? {
+            let Some(bytes) = self.storage.get(&key).await? else {
+                continue;
+            };
+            match serde_json::from_slice::<Memory>(&bytes) {
+                Ok(memory) => memories.push(memory),
+                Err(e) => log::warn!("Skipping corrupt memory {}: {}", key, e),
+            }
+        }
+        memories.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(memories)
+    }
+
+    /// Memories sharing words with `query`, best match first.
+    /// Tag matches count double.
+    pub async fn recall(&self, query: &str, limit: usize) -> Result<Vec<Memory>> {
+        let terms = keywords(query);
+        let mut scored: Vec<(usize, Memory)> = self
+            .list()
+            .await?
+            .into_iter()
+            .filter_map(|m| {
+                let fact_words = keywords(&m.fact);
+                let tags: Vec<String> = m.tags.iter().map(|t| t.to_lowercase()).collect();
+                let score: usize = terms
+                    .iter()
+                    .map(|t| {
+                        usize::from(fact_words.contains(t)) + 2 * usize::from(tags.contains(t))
+                    })
+                    .sum();
+                (score > 0).then_some((score, m))
+            })
+            .collect();
+        // Stable sort keeps newest-first order among equal scores
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        Ok(scored.into_iter().take(limit).map(|(_, m)| m).collect())
+    }
+
+    pub fn remember_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "remember".to_string(),
+            description: "Store a fact in long-term memory (e.g. user preferences, project conventions). Memories persist across sessions".to_string(),
+            parameters: ToolParameters::object()
+                .property(
+                    "fact",
+                    ParamSchema::string("A self-contained fact worth remembering in future sessions"),
+                    true,
+                )
+                .property(
+                    "tags",
+                    ParamSchema::array(
+                        ParamSchema::string(""),
+                        "Optional keywords to help recall the fact later",
+                    ),
+                    false,
+                ),
+            strict: true,
+        }
+    }
+
+    pub fn recall_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "recall".to_string(),
+            description: "Search long-term memory for facts stored in earlier sessions".to_string(),
+            parameters: ToolParameters::object()
+                .property("query", ParamSchema::string("Keywords to search long-term memory for"), true),
+            strict: true,
+        }
+    }
+}
+
+/// Tag attached to memories created by archiving a session
+pub const SESSION_SUMMARY_TAG: &str = "session-summary";
+
+const SUMMARY_PROMPT: &str = "Summarize the conversation below in at most five short \
+bullet points: the user's goal, decisions made, files touched, and anything left \
+unfinished. Reply with the bullet points only.";
+
+/// Ask the LLM for a short summary of a conversation, e.g. before it is
+/// archived. Tool traffic is left out; only user and assistant text is sent.
+pub async fn summarize_conversation(
+    llm: &dyn LlmPort,
+    config: &LlmConfig,
+    history: &[Message],
+) -> Result<String> {
+    let transcript = history
+        .iter()
+        .filter_map(|m| {
+            let text = m.content.as_text().trim();
+            match m.role {
+                Role::User if !text.is_empty() => Some(format!("User: {}", text)),
+                Role::Assistant if !text.is_empty() => Some(format!("Assistant: {}", text)),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let req = ChatRequest {
+        messages: vec![
+            Message::system(SUMMARY_PROMPT),
+            Message::user(transcript),
+        ],
+        tools: Vec::new(),
+        model: config.model.clone(),
+        max_tokens: 512,
+        temperature: 0.2,
+        ..Default::default()
+    };
+    let response = llm.chat_completion(req).await?;
+    Ok(response.message.content.as_text().trim().to_string())
+}
+
+/// Bullet list of memories for prompts and tool output
+pub fn format_memories(memories: &[Memory]) -> String {
+    memories
+        .iter()
+        .map(|m| {
+            if m.tags.is_empty() {
+                format!("- {}", m.fact)
+            } else {
+                format!("- {} [{}]", m.fact, m.tags.join(", "))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Lowercased alphanumeric words of two or more characters
+fn keywords(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 2)
+        .map(|w| w.to_lowercase())
+        .collect()
+}
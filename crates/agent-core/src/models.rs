@@ -0,0 +1,76 @@
+//! Model catalog — the provider's model list, cached in storage.
+//!
+//! Listing models costs a request, so results are kept per provider and
+//! base URL under `models:<provider>:<base url>` and only refetched once
+//! they are older than the TTL or a refresh is forced.
+
+use std::rc::Rc;
+
+use agent_types::{
+    Result,
+    config::LlmConfig,
+    models::ModelList,
+};
+
+use crate::ports::{LlmPort, StoragePort};
+
+const KEY_PREFIX: &str = "models:";
+
+/// Cached lists older than this are refetched
+pub const MODEL_LIST_TTL_SECS: i64 = 24 * 60 * 60;
+
+pub struct ModelCatalog {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl ModelCatalog {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    fn key(config: &LlmConfig) -> String {
+        let base = config
+            .api_base
+            .as_deref()
+            .unwrap_or_else(|| config.provider.default_base_url());
+        format!("{}{}:{}", KEY_PREFIX, config.provider.label(), base.trim_end_matches('/'))
+    }
+
+    /// The stored list for this provider, fresh or not
+    pub async fn cached(&self, config: &LlmConfig) -> Result<Option<ModelList>> {
+        let Some(bytes) = self.storage.get(&Self::key(config)).await? else {
+            return Ok(None);
+        };
+        Ok(serde_json::from_slice(&bytes).ok())
+    }
+
+    /// Fetch the list from the provider and store it
+    pub async fn refresh(&self, llm: &dyn LlmPort, config: &LlmConfig) -> Result<ModelList> {
+        let list = ModelList::new(llm.list_models().await?);
+        let bytes = serde_json::to_vec(&list)?;
+        self.storage.set(&Self::key(config), &bytes).await?;
+        Ok(list)
+    }
+
+    /// The cached list if still fresh, otherwise a refetched one. If the
+    /// fetch fails but an older list is stored, the stale list is returned
+    /// rather than the error.
+    pub async fn get(&self, llm: &dyn LlmPort, config: &LlmConfig) -> Result<ModelList> {
+        let cached = self.cached(config).await?;
+        if let Some(list) = &cached {
+            if !list.is_stale(MODEL_LIST_TTL_SECS) {
+                return Ok(list.clone());
+            }
+        }
+        match self.refresh(llm, config).await {
+            Ok(list) => Ok(list),
+            Err(e) => match cached {
+                Some(list) => {
+                    log::warn!("Model list refresh failed, using cached list: {}", e);
+                    Ok(list)
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Planner/executor orchestration.
+//!
+//! A planned turn splits the user's request into steps before any work is
+//! done. The planner model writes the plan; each step then runs as one turn
+//! of a fresh executor runtime, which has the tools and sees the request,
+//! the plan and the results of the earlier steps, but not the conversation.
+//! Finally the planner writes the answer from the step results. Planner and
+//! executor can be different models of the configured provider, e.g. a
+//! strong planner and a cheap executor.
+//!
+//! Progress is reported as [`AgentEvent::PlanReady`], `PlanStepStart` and
+//! `PlanStepEnd`, and the executors' tool events are passed on as they
+//! happen. The conversation keeps the request and the answer; the answer
+//! carries the usage of every request made for it. A failed step ends the
+//! plan, and the answer says what was and wasn't done.
+
+use serde::Deserialize;
+
+use agent_types::{
+    Result,
+    event::AgentEvent,
+    message::{Message, Role},
+};
+
+use crate::event_bus::EventBus;
+use crate::ports::{ChatRequest, LlmPort, ShellPort, VfsPort};
+use crate::runtime::{AgentRuntime, AgentState, add_usage, stamp_reply};
+
+const PLANNER_PROMPT: &str = "You plan work for an assistant that can read and write files in a \
+workspace and run shell commands. Split the user's latest request into steps that can each be \
+carried out on their own, in order. Reply with only a JSON array of steps, each an object with a \
+short \"title\" and the \"instructions\" for carrying it out. Use as few steps as the request needs, \
+and at most {max}; a simple request is a single step.";
+
+const ANSWER_PROMPT: &str = "You planned steps for the user's latest request, and they have been \
+carried out; their results follow the request. Answer the user from them: say what was done and \
+what was found. If a step failed, say so and what is left to do.";
+
+/// Longest step result shown in the step's `PlanStepEnd` event
+const SUMMARY_CHARS: usize = 300;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PlanStep {
+    pub title: String,
+    #[serde(default)]
+    pub instructions: String,
+}
+
+/// The steps in the planner's reply: a JSON array, possibly in a code
+/// fence, or else a numbered or bulleted list
+pub fn parse_plan(reply: &str) -> Vec<PlanStep> {
+    let json = reply.find('[').zip(reply.rfind(']')).filter(|(start, end)| start < end);
+    if let Some((start, end)) = json {
+        if let Ok(steps) = serde_json::from_str::<Vec<PlanStep>>(&reply[start..=end]) {
+            return steps.into_iter().filter(|step| !step.title.trim().is_empty()).collect();
+        }
+    }
+    reply
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let item = line
+                .strip_prefix(['-', '*'])
+                .or_else(|| {
+                    let digits = line.find(|c: char| !c.is_ascii_digit()).filter(|&n| n > 0)?;
+                    line[digits..].strip_prefix(['.', ')'])
+                })?
+                .trim();
+            (!item.is_empty()).then(|| PlanStep { title: item.to_string(), instructions: String::new() })
+        })
+        .collect()
+}
+
+/// The executor's prompt for step `index`, given the results so far
+pub fn step_prompt(request: &str, steps: &[PlanStep], index: usize, results: &[String]) -> String {
+    let mut prompt = format!("You are carrying out one step of a plan for this request:\n\n{}\n\nThe plan:\n", request);
+    for (i, step) in steps.iter().enumerate() {
+        prompt.push_str(&format!("{}. {}\n", i + 1, step.title));
+    }
+    if !results.is_empty() {
+        prompt.push_str("\nResults of the earlier steps:\n");
+        for (i, result) in results.iter().enumerate() {
+            prompt.push_str(&format!("\n### {}. {}\n{}\n", i + 1, steps[i].title, result));
+        }
+    }
+    let step = &steps[index];
+    prompt.push_str(&format!("\nNow do step {}: {}\n", index + 1, step.title));
+    if !step.instructions.trim().is_empty() {
+        prompt.push_str(&format!("{}\n", step.instructions.trim()));
+    }
+    prompt.push_str("\nDo only this step, then reply with a short summary of what you did and found.");
+    prompt
+}
+
+/// The results of `steps`, as shown to the planner to write the answer
+fn step_results(steps: &[PlanStep], results: &[Result<String>]) -> String {
+    let mut prompt = "Results of the steps:\n".to_string();
+    for (i, step) in steps.iter().enumerate() {
+        let result = match results.get(i) {
+            Some(Ok(text)) => text.clone(),
+            Some(Err(e)) => format!("[failed: {}]", e.user_message()),
+            None => "[not carried out]".to_string(),
+        };
+        prompt.push_str(&format!("\n### {}. {}\n{}\n", i + 1, step.title, result));
+    }
+    prompt
+}
+
+/// The request to the planner model: its instructions, then the
+/// conversation's text messages, ending with `last`
+fn planner_request(runtime: &AgentRuntime, system: String, last: Message) -> ChatRequest {
+    let llm = &runtime.config.llm;
+    let mut messages = vec![Message::system(system)];
+    messages.extend(
+        runtime
+            .history()
+            .iter()
+            .filter(|m| m.role == Role::User || (m.role == Role::Assistant && m.tool_calls.is_empty()))
+            .cloned(),
+    );
+    messages.push(last);
+    ChatRequest {
+        messages,
+        tools: Vec::new(),
+        model: runtime.config.plan.planner(&llm.model).to_string(),
+        max_tokens: llm.max_tokens,
+        temperature: llm.temperature,
+        top_p: llm.top_p,
+        frequency_penalty: llm.frequency_penalty,
+        presence_penalty: llm.presence_penalty,
+        stop: llm.stop.clone(),
+        seed: llm.seed,
+        reasoning: llm.reasoning.clone(),
+    }
+}
+
+/// Events of an executor shown as they happen
+fn passed_on(event: &AgentEvent) -> bool {
+    matches!(
+        event,
+        AgentEvent::ToolExecStart { .. }
+            | AgentEvent::ToolOutput { .. }
+            | AgentEvent::ToolProgress { .. }
+            | AgentEvent::ToolArtifact { .. }
+            | AgentEvent::ToolExecEnd { .. }
+            | AgentEvent::LlmCallEnd { .. }
+            | AgentEvent::LlmFallback { .. }
+    )
+}
+
+/// Run `request` as a planned turn of `runtime`
+pub async fn run_planned_turn(
+    runtime: &mut AgentRuntime,
+    request: &str,
+    attachments: &[String],
+    llm: &dyn LlmPort,
+    shell: &dyn ShellPort,
+    vfs: &dyn VfsPort,
+) -> Result<()> {
+    let turn_id = runtime.begin_turn(request, vfs).await;
+    let mut user_msg = Message::user(request);
+    user_msg.stamp().attachments = attachments.to_vec();
+    runtime.state = AgentState::Thinking;
+
+    // Plan
+    let max_steps = runtime.config.plan.max_steps.max(1);
+    let system = PLANNER_PROMPT.replace("{max}", &max_steps.to_string());
+    let req = planner_request(runtime, system, user_msg.clone());
+    let planner_model = req.model.clone();
+    runtime.messages.push(user_msg);
+    let plan = match runtime.call_llm(llm, req).await {
+        Ok(response) => stamp_reply(response, &planner_model, &runtime.config),
+        Err(e) => return Err(fail(runtime, e)),
+    };
+    let mut steps = parse_plan(plan.content.as_text());
+    steps.truncate(max_steps);
+    if steps.is_empty() {
+        log::warn!("The planner's reply had no steps; running the request as one step");
+        steps.push(PlanStep { title: request.lines().next().unwrap_or(request).to_string(), instructions: String::new() });
+    }
+    runtime.event_bus.emit(AgentEvent::PlanReady {
+        steps: steps.iter().map(|step| step.title.clone()).collect(),
+    });
+
+    // Carry out the steps, each in a fresh executor
+    let mut config = runtime.config.clone();
+    config.llm.model = runtime.config.plan.executor(&runtime.config.llm.model).to_string();
+    let mut answer_meta = plan.meta.clone().unwrap_or_default();
+    let mut results: Vec<Result<String>> = Vec::new();
+    for index in 0..steps.len() {
+        runtime.event_bus.emit(AgentEvent::PlanStepStart { index });
+        let bus = EventBus::new();
+        let main_bus = runtime.event_bus.clone();
+        bus.subscribe(move |event| {
+            if passed_on(event) {
+                main_bus.emit(event.clone());
+            }
+        });
+        let mut executor = runtime.executor(config.clone(), bus);
+        let done: Vec<String> = results.iter().filter_map(|r| r.as_ref().ok().cloned()).collect();
+        let prompt = step_prompt(request, &steps, index, &done);
+        let outcome = executor.run_turn_with_attachments(&prompt, attachments, llm, shell, vfs).await;
+        for message in executor.history() {
+            add_usage(&mut answer_meta, message.meta.as_ref());
+        }
+        let result = outcome.map(|()| {
+            executor
+                .history()
+                .iter()
+                .rev()
+                .find(|m| m.role == Role::Assistant && m.tool_calls.is_empty())
+                .map(|m| m.content.as_text().to_string())
+                .unwrap_or_default()
+        });
+        let (success, summary) = match &result {
+            Ok(text) => (true, text.chars().take(SUMMARY_CHARS).collect()),
+            Err(e) => (false, e.user_message()),
+        };
+        runtime.event_bus.emit(AgentEvent::PlanStepEnd { index, success, summary });
+        results.push(result);
+        if !success {
+            break;
+        }
+    }
+
+    // Answer from the results
+    runtime.state = AgentState::Thinking;
+    let last = Message::user(step_results(&steps, &results));
+    let req = planner_request(runtime, ANSWER_PROMPT.to_string(), last);
+    let mut answer = match runtime.call_llm(llm, req).await {
+        Ok(response) => stamp_reply(response, &planner_model, &runtime.config),
+        Err(e) => return Err(fail(runtime, e)),
+    };
+    let meta = answer.stamp();
+    add_usage(meta, Some(&answer_meta));
+    let text = answer.content.as_text().to_string();
+    runtime.messages.push(answer);
+    runtime.event_bus.emit(AgentEvent::LlmComplete { text });
+    runtime.state = AgentState::Idle;
+    runtime.event_bus.emit(AgentEvent::TurnEnd { turn_id });
+    Ok(())
+}
+
+/// End the turn with `error`
+fn fail(runtime: &mut AgentRuntime, error: agent_types::AgentError) -> agent_types::AgentError {
+    runtime.state = AgentState::Error(error.to_string());
+    runtime.event_bus.emit(AgentEvent::Error { message: error.user_message() });
+    error
+}
@@ -0,0 +1,194 @@
+//! Numbers for the performance overlay: recent frame times, events drained
+//! per frame, and storage operations in flight.
+//!
+//! The app records a [`FrameSample`] every frame and wraps its root
+//! storage in [`MeteredStorage`], so a slow frame or a flood of events or
+//! storage calls shows up on a user's machine without a profiler.
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use agent_types::{Result, storage::KeyMeta};
+
+use crate::ports::{KeyPage, StoragePort, StorageUsage};
+
+/// Frames kept for the graph
+pub const FRAME_HISTORY: usize = 240;
+
+/// Frame time at 60 fps, in milliseconds
+pub const FRAME_BUDGET_MS: f32 = 1000.0 / 60.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameSample {
+    /// Time since the previous frame
+    pub interval_ms: f32,
+    /// Time spent building this frame, if the platform measures it
+    pub cpu_ms: Option<f32>,
+    /// Runtime events drained at the start of the frame
+    pub events: usize,
+}
+
+/// The last [`FRAME_HISTORY`] frames, oldest first
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    samples: VecDeque<FrameSample>,
+}
+
+/// Averages and peaks over [`FrameStats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameSummary {
+    pub mean_interval_ms: f32,
+    pub max_interval_ms: f32,
+    pub mean_cpu_ms: f32,
+    pub max_events: usize,
+    /// Frames that took longer than [`FRAME_BUDGET_MS`] to build
+    pub slow_frames: usize,
+}
+
+impl FrameStats {
+    pub fn push(&mut self, sample: FrameSample) {
+        if self.samples.len() == FRAME_HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn samples(&self) -> &VecDeque<FrameSample> {
+        &self.samples
+    }
+
+    pub fn last(&self) -> Option<&FrameSample> {
+        self.samples.back()
+    }
+
+    pub fn summary(&self) -> FrameSummary {
+        let count = self.samples.len().max(1) as f32;
+        let cpu: Vec<f32> = self.samples.iter().filter_map(|s| s.cpu_ms).collect();
+        FrameSummary {
+            mean_interval_ms: self.samples.iter().map(|s| s.interval_ms).sum::<f32>() / count,
+            max_interval_ms: self.samples.iter().map(|s| s.interval_ms).fold(0.0, f32::max),
+            mean_cpu_ms: cpu.iter().sum::<f32>() / cpu.len().max(1) as f32,
+            max_events: self.samples.iter().map(|s| s.events).max().unwrap_or(0),
+            slow_frames: cpu.iter().filter(|&&ms| ms > FRAME_BUDGET_MS).count(),
+        }
+    }
+}
+
+/// Storage operations started and finished through a [`MeteredStorage`]
+#[derive(Debug, Default)]
+pub struct StorageMeter {
+    pending: Cell<usize>,
+    total: Cell<u64>,
+}
+
+impl StorageMeter {
+    /// Operations started and not yet finished
+    pub fn pending(&self) -> usize {
+        self.pending.get()
+    }
+
+    /// Operations started so far
+    pub fn total(&self) -> u64 {
+        self.total.get()
+    }
+
+    fn start(&self) -> Pending<'_> {
+        self.pending.set(self.pending.get() + 1);
+        self.total.set(self.total.get() + 1);
+        Pending(self)
+    }
+}
+
+/// An operation in flight; finished when dropped, so a failed or
+/// abandoned call is not counted as pending forever
+struct Pending<'a>(&'a StorageMeter);
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        self.0.pending.set(self.0.pending.get().saturating_sub(1));
+    }
+}
+
+/// Storage counting its calls in a [`StorageMeter`]
+pub struct MeteredStorage {
+    inner: Rc<dyn StoragePort>,
+    meter: Rc<StorageMeter>,
+}
+
+impl MeteredStorage {
+    pub fn new(inner: Rc<dyn StoragePort>, meter: Rc<StorageMeter>) -> Self {
+        Self { inner, meter }
+    }
+}
+
+#[async_trait(?Send)]
+impl StoragePort for MeteredStorage {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let _op = self.meter.start();
+        self.inner.get(key).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<()> {
+        let _op = self.meter.start();
+        self.inner.set(key, value).await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Result<Vec<Option<Vec<u8>>>> {
+        let _op = self.meter.start();
+        self.inner.get_many(keys).await
+    }
+
+    async fn set_many(&self, entries: &[(&str, &[u8])]) -> Result<()> {
+        let _op = self.meter.start();
+        self.inner.set_many(entries).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let _op = self.meter.start();
+        self.inner.delete(key).await
+    }
+
+    async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let _op = self.meter.start();
+        self.inner.list_keys(prefix).await
+    }
+
+    async fn list_keys_page(&self, prefix: &str, limit: usize, after: Option<&str>) -> Result<KeyPage> {
+        let _op = self.meter.start();
+        self.inner.list_keys_page(prefix, limit, after).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        let _op = self.meter.start();
+        self.inner.exists(key).await
+    }
+
+    async fn meta(&self, key: &str) -> Result<Option<KeyMeta>> {
+        let _op = self.meter.start();
+        self.inner.meta(key).await
+    }
+
+    async fn list_meta(&self, prefix: &str) -> Result<Vec<(String, KeyMeta)>> {
+        let _op = self.meter.start();
+        self.inner.list_meta(prefix).await
+    }
+
+    async fn stored_bytes(&self, prefix: &str) -> Result<u64> {
+        let _op = self.meter.start();
+        self.inner.stored_bytes(prefix).await
+    }
+
+    fn backend_name(&self) -> &str {
+        self.inner.backend_name()
+    }
+
+    fn usage(&self) -> Option<StorageUsage> {
+        self.inner.usage()
+    }
+
+    fn set_byte_cap(&self, max_bytes: usize) {
+        self.inner.set_byte_cap(max_bytes)
+    }
+}
@@ -8,9 +8,11 @@ use std::pin::Pin;
 use async_trait::async_trait;
 use futures::Stream;
 use agent_types::{
-    Result,
+    AgentError, ErrorKind, Result,
+    config::ReasoningConfig,
     message::Message,
-    tool::{DirEntry, ExecHandle, ExecResult, FileStat, ToolDefinition},
+    storage::KeyMeta,
+    tool::{DirEntry, ExecHandle, ExecOptions, ExecResult, FileStat, RunningExec, ToolDefinition},
 };
 
 // ─── LLM Port ────────────────────────────────────────────────
@@ -27,6 +29,10 @@ pub enum LlmStreamEvent {
         name: Option<String>,
         arguments_delta: String,
     },
+    /// Part of the reasoning trace of a reasoning model
+    Reasoning(String),
+    /// Tokens used, reported once near the end of the stream
+    Usage(TokenUsage),
     /// Stream finished
     Done,
     /// Error during streaming
@@ -34,13 +40,19 @@ pub enum LlmStreamEvent {
 }
 
 /// Request to send to an LLM
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ChatRequest {
     pub messages: Vec<Message>,
     pub tools: Vec<ToolDefinition>,
     pub model: String,
     pub max_tokens: u32,
     pub temperature: f32,
+    pub top_p: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub stop: Vec<String>,
+    pub seed: Option<u64>,
+    pub reasoning: ReasoningConfig,
 }
 
 /// Complete (non-streaming) response from an LLM
@@ -50,12 +62,7 @@ pub struct ChatResponse {
     pub usage: Option<TokenUsage>,
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct TokenUsage {
-    pub prompt_tokens: u32,
-    pub completion_tokens: u32,
-    pub total_tokens: u32,
-}
+pub use agent_types::message::TokenUsage;
 
 #[async_trait(?Send)]
 pub trait LlmPort {
@@ -77,12 +84,13 @@ pub trait LlmPort {
 #[async_trait(?Send)]
 pub trait ShellPort {
     /// Execute a command and return the full result
-    async fn execute(&self, cmd: &str, timeout_ms: Option<u64>) -> Result<ExecResult>;
+    async fn execute(&self, cmd: &str, opts: &ExecOptions) -> Result<ExecResult>;
 
     /// Execute a command with streaming output
     fn execute_streaming(
         &self,
         cmd: &str,
+        opts: &ExecOptions,
     ) -> Pin<Box<dyn Stream<Item = ShellStreamEvent>>>;
 
     /// Cancel a running execution
@@ -90,6 +98,32 @@ pub trait ShellPort {
 
     /// Check if the shell runtime is ready
     fn is_ready(&self) -> bool;
+
+    /// Commands executing now, oldest first
+    fn list_running(&self) -> Vec<RunningExec> {
+        Vec::new()
+    }
+
+    /// Execute a command with `stdin` piped to its standard input, which
+    /// is closed once written
+    async fn execute_with_stdin(&self, _cmd: &str, _stdin: &str, _opts: &ExecOptions) -> Result<ExecResult> {
+        Err(no_stdin())
+    }
+
+    /// Write to the standard input of a running command started with
+    /// stdin open. Fails if it has exited or its stdin was closed.
+    async fn write_stdin(&self, _handle: ExecHandle, _data: &str) -> Result<()> {
+        Err(no_stdin())
+    }
+
+    /// Close the standard input of a running command, signalling EOF
+    async fn close_stdin(&self, _handle: ExecHandle) -> Result<()> {
+        Err(no_stdin())
+    }
+}
+
+fn no_stdin() -> AgentError {
+    AgentError::api(ErrorKind::Unavailable, "This shell does not accept standard input")
 }
 
 #[derive(Debug, Clone)]
@@ -116,13 +150,108 @@ pub trait StoragePort {
     /// List keys with a given prefix
     async fn list_keys(&self, prefix: &str) -> Result<Vec<String>>;
 
+    /// List keys with a given prefix a page at a time, in key order: at
+    /// most `limit` keys (0 = all) after the continuation token `after`.
+    /// Pass the returned `next` back as `after` for the following page.
+    async fn list_keys_page(&self, prefix: &str, limit: usize, after: Option<&str>) -> Result<KeyPage> {
+        let mut keys = self.list_keys(prefix).await?;
+        keys.sort();
+        if let Some(after) = after {
+            keys.retain(|k| k.as_str() > after);
+        }
+        Ok(KeyPage::from_sorted(keys, limit))
+    }
+
+    /// Get several values at once, in the order of `keys`
+    async fn get_many(&self, keys: &[&str]) -> Result<Vec<Option<Vec<u8>>>> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(self.get(key).await?);
+        }
+        Ok(values)
+    }
+
+    /// Set several values at once. Backends that can write them in one
+    /// transaction do, so either all are written or none are.
+    async fn set_many(&self, entries: &[(&str, &[u8])]) -> Result<()> {
+        for (key, value) in entries {
+            self.set(key, value).await?;
+        }
+        Ok(())
+    }
+
     /// Check if a key exists
     async fn exists(&self, key: &str) -> Result<bool> {
         Ok(self.get(key).await?.is_some())
     }
 
+    /// Size, write time and type of a value. Backends that keep metadata
+    /// answer without reading the value.
+    async fn meta(&self, key: &str) -> Result<Option<KeyMeta>> {
+        Ok(self.get(key).await?.map(|value| KeyMeta::untimed(key, value.len() as u64)))
+    }
+
+    /// Keys with a given prefix and their metadata, in any order
+    async fn list_meta(&self, prefix: &str) -> Result<Vec<(String, KeyMeta)>> {
+        let mut out = Vec::new();
+        for key in self.list_keys(prefix).await? {
+            if let Some(meta) = self.meta(&key).await? {
+                out.push((key, meta));
+            }
+        }
+        Ok(out)
+    }
+
+    /// Total bytes of the values under a prefix
+    async fn stored_bytes(&self, prefix: &str) -> Result<u64> {
+        Ok(self.list_meta(prefix).await?.iter().map(|(_, meta)| meta.size).sum())
+    }
+
     /// Name of this backend (for logging/debug)
     fn backend_name(&self) -> &str;
+
+    /// Memory use, for backends that hold everything in the WASM heap
+    fn usage(&self) -> Option<StorageUsage> {
+        None
+    }
+
+    /// Limit the bytes a heap-backed store keeps (0 = no limit); other
+    /// backends ignore it
+    fn set_byte_cap(&self, _max_bytes: usize) {}
+}
+
+/// One page of [`StoragePort::list_keys_page`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyPage {
+    pub keys: Vec<String>,
+    /// Continuation token, `None` on the last page
+    pub next: Option<String>,
+}
+
+impl KeyPage {
+    /// The first `limit` of `keys` (0 = all), which are sorted and start
+    /// after the previous page
+    pub fn from_sorted(mut keys: Vec<String>, limit: usize) -> Self {
+        if limit == 0 || keys.len() <= limit {
+            return Self { keys, next: None };
+        }
+        keys.truncate(limit);
+        let next = keys.last().cloned();
+        Self { keys, next }
+    }
+}
+
+/// How much a heap-backed store holds and what it had to drop
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageUsage {
+    /// Bytes of keys and values held
+    pub bytes: usize,
+    pub entries: usize,
+    /// Byte cap, 0 if unbounded
+    pub cap: usize,
+    /// Entries evicted to stay under the cap
+    pub evictions: u64,
+    pub evicted_bytes: u64,
 }
 
 // ─── Virtual Filesystem Port ─────────────────────────────────
@@ -131,9 +260,106 @@ pub trait StoragePort {
 pub trait VfsPort {
     async fn read_file(&self, path: &str) -> Result<Vec<u8>>;
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<()>;
+
+    /// Add `data` to the end of a file, creating it if missing
+    async fn append_file(&self, path: &str, data: &[u8]) -> Result<()> {
+        let mut contents = if self.exists(path).await? {
+            self.read_file(path).await?
+        } else {
+            Vec::new()
+        };
+        contents.extend_from_slice(data);
+        self.write_file(path, &contents).await
+    }
+
     async fn delete_file(&self, path: &str) -> Result<()>;
     async fn list_dir(&self, path: &str) -> Result<Vec<DirEntry>>;
     async fn stat(&self, path: &str) -> Result<FileStat>;
     async fn mkdir(&self, path: &str) -> Result<()>;
     async fn exists(&self, path: &str) -> Result<bool>;
 }
+
+// ─── Embeddings Port ─────────────────────────────────────────
+
+#[async_trait(?Send)]
+pub trait EmbeddingsPort {
+    /// Embed a batch of texts. Returns one vector per input, in order.
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    /// Identifier of the embedding model (vectors from different models
+    /// are not comparable, so indexes record this)
+    fn model_name(&self) -> &str;
+}
+
+// ─── Fetch Port ──────────────────────────────────────────────
+
+/// A fetched web resource
+#[derive(Debug, Clone)]
+pub struct FetchedPage {
+    /// The URL asked for
+    pub url: String,
+    /// The Content-Type header, e.g. `text/html; charset=utf-8`
+    pub content_type: String,
+    pub body: String,
+}
+
+#[async_trait(?Send)]
+pub trait FetchPort {
+    /// GET `url`. A response with an error status is an error.
+    async fn fetch(&self, url: &str) -> Result<FetchedPage>;
+}
+
+// ─── JavaScript Port ─────────────────────────────────────────
+
+/// What a script printed and how it ended
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct JsRun {
+    /// Console output, one entry per call; warnings and errors are
+    /// prefixed with their level
+    pub console: Vec<String>,
+    /// The value of the last expression (awaited if it is a promise), if
+    /// it isn't undefined
+    pub result: Option<String>,
+    /// The exception the script threw
+    pub error: Option<String>,
+}
+
+#[async_trait(?Send)]
+pub trait JsPort {
+    /// Evaluate `code` in a fresh sandbox, stopping it after `timeout_ms`.
+    /// A script that throws is a successful run with `error` set; a
+    /// timeout is an error.
+    async fn run(&self, code: &str, timeout_ms: u32) -> Result<JsRun>;
+}
+
+// ─── SQL Port ────────────────────────────────────────────────
+
+/// The rows of one statement that returned any
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SqlRows {
+    pub columns: Vec<String>,
+    /// Cells as JSON: numbers, strings and nulls (blobs are described as
+    /// strings)
+    pub values: Vec<Vec<serde_json::Value>>,
+}
+
+/// What a batch of SQL statements returned
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SqlOutput {
+    /// One entry per statement that returned rows
+    pub results: Vec<SqlRows>,
+    /// Rows changed by the last statement
+    pub changes: u64,
+    /// The database file after the statements ran
+    pub image: Vec<u8>,
+}
+
+#[async_trait(?Send)]
+pub trait SqlPort {
+    /// Run `sql` (one or more statements) against the SQLite database file
+    /// `image`, or a new empty database with None, stopping after
+    /// `timeout_ms`. The database is not kept: the changed file is returned.
+    async fn exec(&self, image: Option<Vec<u8>>, sql: &str, timeout_ms: u32) -> Result<SqlOutput>;
+}
@@ -0,0 +1,48 @@
+//! Prompt library — reusable snippets for the chat input.
+//!
+//! Each snippet is a JSON document stored under `prompts:<id>`.
+
+use std::rc::Rc;
+
+use agent_types::{Result, prompt::PromptSnippet};
+
+use crate::ports::StoragePort;
+
+const KEY_PREFIX: &str = "prompts:";
+
+pub struct PromptLibrary {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl PromptLibrary {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    pub async fn save(&self, snippet: &PromptSnippet) -> Result<()> {
+        let bytes = serde_json::to_vec(snippet)?;
+        self.storage
+            .set(&format!("{}{}", KEY_PREFIX, snippet.id), &bytes)
+            .await
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        self.storage.delete(&format!("{}{}", KEY_PREFIX, id)).await
+    }
+
+    /// All snippets sorted by name. Unreadable entries are skipped.
+    pub async fn list(&self) -> Result<Vec<PromptSnippet>> {
+        let mut snippets = Vec::new();
+        for key in self.storage.list_keys(KEY_PREFIX).await? {
+            let Some(bytes) = self.storage.get(&key).await? else {
+                continue;
+            };
+            match serde_json::from_slice::<PromptSnippet>(&bytes) {
+                Ok(snippet) => snippets.push(snippet),
+                Err(e) => log::warn!("Skipping corrupt prompt {}: {}", key, e),
+            }
+        }
+        snippets.sort_by_key(|s| s.name.to_lowercase());
+        Ok(snippets)
+    }
+}
@@ -0,0 +1,228 @@
+//! Project-wide search and replace — the `replace_in_files` tool.
+//!
+//! A regex (or literal text) is replaced in every file under a directory
+//! whose path matches a glob. Each file's change is shown as hunks of the
+//! lines it touches, so a dry run can be reviewed before anything is
+//! written. Binary files, files over [`MAX_FILE_BYTES`] and hidden
+//! directories are left alone.
+
+use regex_lite::Regex;
+
+use agent_types::{AgentError, ErrorKind, Result};
+
+use crate::ports::VfsPort;
+use crate::retrieval::walk_files;
+use crate::tools::looks_binary;
+
+/// Directory searched unless the call names another
+pub const DEFAULT_ROOT: &str = "/workspace";
+/// Larger files are skipped
+pub const MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// Preview lines shown before the rest are counted instead
+pub const MAX_PREVIEW_LINES: usize = 200;
+
+/// Lines of a file as they were and as they will be
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    /// 1-based number of the first line
+    pub line: usize,
+    pub old: String,
+    pub new: String,
+}
+
+/// The replacement in one file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: String,
+    pub count: usize,
+    pub hunks: Vec<Hunk>,
+    pub new_text: String,
+}
+
+/// The changes a replacement makes across files
+#[derive(Debug, Default)]
+pub struct ReplacePlan {
+    pub changes: Vec<FileChange>,
+    /// Files that matched the glob and were searched
+    pub searched: usize,
+}
+
+impl ReplacePlan {
+    pub fn occurrences(&self) -> usize {
+        self.changes.iter().map(|c| c.count).sum()
+    }
+}
+
+/// What to replace, and with what
+pub struct Replacer {
+    regex: Regex,
+    replacement: String,
+    literal: bool,
+}
+
+impl Replacer {
+    /// `pattern` is a regex, or with `literal` the exact text to find. A
+    /// regex replacement may insert groups with `$1` or `${name}`.
+    pub fn new(pattern: &str, replacement: &str, literal: bool) -> Result<Self> {
+        if pattern.is_empty() {
+            return Err(AgentError::api(ErrorKind::InvalidRequest, "the pattern is empty"));
+        }
+        let source = if literal { regex_lite::escape(pattern) } else { pattern.to_string() };
+        let regex = Regex::new(&source)
+            .map_err(|e| AgentError::api(ErrorKind::InvalidRequest, format!("invalid regex: {}", e)))?;
+        Ok(Self { regex, replacement: replacement.to_string(), literal })
+    }
+
+    /// `text` with every match replaced, the number of changed matches and
+    /// the hunks; None if nothing changes
+    pub fn apply(&self, text: &str) -> Option<(String, usize, Vec<Hunk>)> {
+        let edits: Vec<(usize, usize, String)> = self
+            .regex
+            .captures_iter(text)
+            .filter_map(|caps| {
+                let m = caps.get(0)?;
+                let mut with = String::new();
+                if self.literal {
+                    with.push_str(&self.replacement);
+                } else {
+                    caps.expand(&self.replacement, &mut with);
+                }
+                (m.as_str() != with).then(|| (m.start(), m.end(), with))
+            })
+            .collect();
+        if edits.is_empty() {
+            return None;
+        }
+
+        let new_text = splice(text, (0, text.len()), &edits);
+        let line_start = |at: usize| text[..at].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = |at: usize| text[at..].find('\n').map_or(text.len(), |i| at + i);
+        let mut hunks = Vec::new();
+        let mut group: Vec<(usize, usize, String)> = Vec::new();
+        let mut span = (0, 0);
+        for edit in edits.iter().cloned() {
+            let (start, end) = (line_start(edit.0), line_end(edit.1));
+            if !group.is_empty() && start > span.1 {
+                hunks.push(hunk(text, span, &group));
+                group.clear();
+            }
+            if group.is_empty() {
+                span = (start, end);
+            }
+            span.1 = span.1.max(end);
+            group.push(edit);
+        }
+        hunks.push(hunk(text, span, &group));
+        Some((new_text, edits.len(), hunks))
+    }
+
+    /// The changes to every file under `root` whose path relative to it
+    /// matches `glob`
+    pub async fn plan(&self, vfs: &dyn VfsPort, root: &str, glob: &str) -> Result<ReplacePlan> {
+        let root = root.trim_end_matches('/');
+        let mut plan = ReplacePlan::default();
+        for path in walk_files(vfs, root, false).await? {
+            let relative = path.strip_prefix(root).unwrap_or(&path).trim_start_matches('/');
+            if !glob_match(glob, relative) {
+                continue;
+            }
+            if vfs.stat(&path).await.is_ok_and(|stat| stat.size > MAX_FILE_BYTES) {
+                continue;
+            }
+            let data = vfs.read_file(&path).await?;
+            if looks_binary(&data) {
+                continue;
+            }
+            plan.searched += 1;
+            let text = String::from_utf8_lossy(&data);
+            if let Some((new_text, count, hunks)) = self.apply(&text) {
+                plan.changes.push(FileChange { path, count, hunks, new_text });
+            }
+        }
+        Ok(plan)
+    }
+}
+
+/// `text[start..end]` with `edits` (positions in `text`, within the
+/// range) applied
+fn splice(text: &str, (start, end): (usize, usize), edits: &[(usize, usize, String)]) -> String {
+    let mut out = String::with_capacity(end - start);
+    let mut at = start;
+    for (from, to, with) in edits {
+        out.push_str(&text[at..*from]);
+        out.push_str(with);
+        at = *to;
+    }
+    out.push_str(&text[at..end]);
+    out
+}
+
+fn hunk(text: &str, span: (usize, usize), edits: &[(usize, usize, String)]) -> Hunk {
+    Hunk {
+        line: text[..span.0].matches('\n').count() + 1,
+        old: text[span.0..span.1].to_string(),
+        new: splice(text, span, edits),
+    }
+}
+
+/// The changes as a diff: `-` lines as they were, `+` as they will be,
+/// cut off after `max_lines`
+pub fn format_diff(changes: &[FileChange], max_lines: usize) -> String {
+    let mut lines = Vec::new();
+    for change in changes {
+        lines.push(format!("--- {} ({} change{})", change.path, change.count, if change.count == 1 { "" } else { "s" }));
+        for hunk in &change.hunks {
+            lines.push(format!("@@ line {} @@", hunk.line));
+            lines.extend(hunk.old.lines().map(|l| format!("-{}", l)));
+            lines.extend(hunk.new.lines().map(|l| format!("+{}", l)));
+        }
+    }
+    if lines.len() > max_lines {
+        let rest = lines.len() - max_lines;
+        lines.truncate(max_lines);
+        lines.push(format!("[… {} more diff lines]", rest));
+    }
+    lines.join("\n")
+}
+
+/// Whether `path` (relative, `/`-separated) matches `glob`. `*` and `?`
+/// stay within a directory, `**` spans any number of them, and `{a,b}`
+/// matches either. A glob without `/` is matched against the file name.
+pub fn glob_match(glob: &str, path: &str) -> bool {
+    let glob = glob.trim().trim_start_matches("./");
+    let target = if glob.contains('/') { path } else { path.rsplit('/').next().unwrap_or(path) };
+    let target: Vec<char> = target.chars().collect();
+    expand_braces(glob).iter().any(|pattern| {
+        let pattern: Vec<char> = pattern.chars().collect();
+        matches(&pattern, &target)
+    })
+}
+
+/// Every alternative of the first `{a,b}` group, expanded recursively
+fn expand_braces(glob: &str) -> Vec<String> {
+    let Some(open) = glob.find('{') else {
+        return vec![glob.to_string()];
+    };
+    let Some(close) = glob[open..].find('}').map(|i| open + i) else {
+        return vec![glob.to_string()];
+    };
+    glob[open + 1..close]
+        .split(',')
+        .flat_map(|alt| expand_braces(&format!("{}{}{}", &glob[..open], alt, &glob[close + 1..])))
+        .collect()
+}
+
+fn matches(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            matches(rest, path) || (0..path.len()).any(|i| path[i] == '/' && matches(rest, &path[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != '/')
+            .any(|i| matches(rest, &path[i..])),
+        ['?', rest @ ..] => path.first().is_some_and(|&c| c != '/') && matches(rest, &path[1..]),
+        [c, rest @ ..] => path.first() == Some(c) && matches(rest, &path[1..]),
+    }
+}
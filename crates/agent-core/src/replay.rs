@@ -0,0 +1,321 @@
+//! Session record and replay.
+//!
+//! While recording, [`RecordingLlm`] and [`RecordingShell`] pass calls
+//! through to the live adapters and append each result to the session's
+//! [`Recording`] (`recording:<session id>`). [`replay_session`] runs the
+//! recorded prompts again with a [`TapePlayer`] standing in for both ports,
+//! so the agent loop gets exactly the responses it got live — no network,
+//! API key or shell needed. File tools still act on the workspace.
+//!
+//! If the loop asks for something the recording doesn't have next (a
+//! different command, an extra LLM call), the call fails with an error
+//! naming the step, and the replay carries on with the next prompt.
+
+use std::cell::{Cell, RefCell};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use futures::Stream;
+
+use agent_types::{
+    AgentError, Result,
+    replay::{Recording, TapeEntry},
+    tool::{ExecHandle, ExecOptions, ExecResult, RunningExec},
+};
+
+use crate::ports::*;
+use crate::runtime::AgentRuntime;
+
+const KEY_PREFIX: &str = "recording:";
+
+pub struct RecordingStore {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl RecordingStore {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    pub async fn load(&self, session_id: &str) -> Result<Option<Recording>> {
+        match self.storage.get(&key(session_id)).await? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Add `entry` to the session's recording, starting one if needed.
+    pub async fn append(&self, session_id: &str, entry: TapeEntry) -> Result<()> {
+        let mut recording = self
+            .load(session_id)
+            .await?
+            .unwrap_or_else(|| Recording::new(session_id));
+        recording.entries.push(entry);
+        self.storage.set(&key(session_id), &serde_json::to_vec(&recording)?).await
+    }
+
+    pub async fn delete(&self, session_id: &str) -> Result<()> {
+        self.storage.delete(&key(session_id)).await
+    }
+}
+
+fn key(session_id: &str) -> String {
+    format!("{}{}", KEY_PREFIX, session_id)
+}
+
+// ─── Recording ───────────────────────────────────────────────
+
+/// Records every chat completion. Streams are passed through unrecorded.
+pub struct RecordingLlm {
+    inner: Rc<dyn LlmPort>,
+    store: Rc<RecordingStore>,
+    session_id: String,
+}
+
+impl RecordingLlm {
+    pub fn new(inner: Rc<dyn LlmPort>, store: Rc<RecordingStore>, session_id: &str) -> Self {
+        Self { inner, store, session_id: session_id.to_string() }
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmPort for RecordingLlm {
+    async fn chat_completion(&self, req: ChatRequest) -> Result<ChatResponse> {
+        let request_len = req.messages.len();
+        let result = self.inner.chat_completion(req).await;
+        let entry = match &result {
+            Ok(response) => TapeEntry::Llm {
+                request_len,
+                message: Some(Box::new(response.message.clone())),
+                usage: response.usage.clone(),
+                error: None,
+            },
+            Err(e) => TapeEntry::Llm { request_len, message: None, usage: None, error: Some(e.to_string()) },
+        };
+        if let Err(e) = self.store.append(&self.session_id, entry).await {
+            log::warn!("Failed to record LLM response: {}", e);
+        }
+        result
+    }
+
+    fn stream_chat(&self, req: ChatRequest) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+        self.inner.stream_chat(req)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        self.inner.list_models().await
+    }
+}
+
+/// Records every `execute`. Streaming execution is passed through unrecorded.
+pub struct RecordingShell {
+    inner: Rc<dyn ShellPort>,
+    store: Rc<RecordingStore>,
+    session_id: String,
+}
+
+impl RecordingShell {
+    pub fn new(inner: Rc<dyn ShellPort>, store: Rc<RecordingStore>, session_id: &str) -> Self {
+        Self { inner, store, session_id: session_id.to_string() }
+    }
+
+    async fn record(&self, cmd: &str, result: &Result<ExecResult>) {
+        let entry = TapeEntry::Shell {
+            command: cmd.to_string(),
+            result: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+        if let Err(e) = self.store.append(&self.session_id, entry).await {
+            log::warn!("Failed to record shell result: {}", e);
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl ShellPort for RecordingShell {
+    async fn execute(&self, cmd: &str, opts: &ExecOptions) -> Result<ExecResult> {
+        let result = self.inner.execute(cmd, opts).await;
+        self.record(cmd, &result).await;
+        result
+    }
+
+    fn execute_streaming(&self, cmd: &str, opts: &ExecOptions) -> Pin<Box<dyn Stream<Item = ShellStreamEvent>>> {
+        self.inner.execute_streaming(cmd, opts)
+    }
+
+    async fn cancel(&self, handle: ExecHandle) -> Result<()> {
+        self.inner.cancel(handle).await
+    }
+
+    fn is_ready(&self) -> bool {
+        self.inner.is_ready()
+    }
+
+    fn list_running(&self) -> Vec<RunningExec> {
+        self.inner.list_running()
+    }
+
+    async fn execute_with_stdin(&self, cmd: &str, stdin: &str, opts: &ExecOptions) -> Result<ExecResult> {
+        let result = self.inner.execute_with_stdin(cmd, stdin, opts).await;
+        self.record(cmd, &result).await;
+        result
+    }
+
+    async fn write_stdin(&self, handle: ExecHandle, data: &str) -> Result<()> {
+        self.inner.write_stdin(handle, data).await
+    }
+
+    async fn close_stdin(&self, handle: ExecHandle) -> Result<()> {
+        self.inner.close_stdin(handle).await
+    }
+}
+
+// ─── Replay ──────────────────────────────────────────────────
+
+/// Serves a recording's entries, in order, as both LLM and shell
+pub struct TapePlayer {
+    entries: Vec<TapeEntry>,
+    pos: Cell<usize>,
+    /// First point where the replay left the recording
+    diverged: RefCell<Option<String>>,
+}
+
+impl TapePlayer {
+    pub fn new(recording: &Recording) -> Self {
+        Self {
+            entries: recording.entries.clone(),
+            pos: Cell::new(0),
+            diverged: RefCell::new(None),
+        }
+    }
+
+    /// The next recorded prompt. Entries the last turn didn't use are skipped.
+    pub fn next_prompt(&self) -> Option<String> {
+        while let Some(entry) = self.entries.get(self.pos.get()) {
+            self.pos.set(self.pos.get() + 1);
+            match entry {
+                TapeEntry::Prompt { text } => return Some(text.clone()),
+                _ => {
+                    self.diverge(format!("step {} was not replayed", self.pos.get()));
+                }
+            }
+        }
+        None
+    }
+
+    /// Why the replay left the recording, if it did
+    pub fn divergence(&self) -> Option<String> {
+        self.diverged.borrow().clone()
+    }
+
+    fn diverge(&self, message: String) -> AgentError {
+        log::warn!("Replay diverged: {}", message);
+        self.diverged.borrow_mut().get_or_insert(message.clone());
+        AgentError::Other(format!("Replay diverged: {}", message))
+    }
+
+    /// The next entry, if it is of the `wanted` kind. A prompt is never
+    /// consumed here, so the next turn still starts where it should.
+    fn take(&self, wanted: &str, is_wanted: impl Fn(&TapeEntry) -> bool) -> Result<&TapeEntry> {
+        let step = self.pos.get() + 1;
+        match self.entries.get(self.pos.get()) {
+            Some(entry) if is_wanted(entry) => {
+                self.pos.set(step);
+                Ok(entry)
+            }
+            Some(TapeEntry::Prompt { .. }) | None => {
+                Err(self.diverge(format!("step {}: {} not in the recording", step, wanted)))
+            }
+            Some(_) => {
+                self.pos.set(step);
+                Err(self.diverge(format!("step {}: recording has a different call than {}", step, wanted)))
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmPort for TapePlayer {
+    async fn chat_completion(&self, req: ChatRequest) -> Result<ChatResponse> {
+        let TapeEntry::Llm { request_len, message, usage, error } =
+            self.take("an LLM call", |e| matches!(e, TapeEntry::Llm { .. }))?
+        else {
+            unreachable!("take returns the wanted kind");
+        };
+        if *request_len != req.messages.len() {
+            log::warn!(
+                "Replayed LLM request has {} messages, the recorded one had {}",
+                req.messages.len(),
+                request_len
+            );
+        }
+        match (message, error) {
+            (Some(message), _) => Ok(ChatResponse { message: (**message).clone(), usage: usage.clone() }),
+            (None, error) => Err(AgentError::Other(error.clone().unwrap_or_default())),
+        }
+    }
+
+    fn stream_chat(&self, _req: ChatRequest) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+        Box::pin(futures::stream::iter([
+            LlmStreamEvent::Error("Streams are not recorded".to_string()),
+            LlmStreamEvent::Done,
+        ]))
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+#[async_trait(?Send)]
+impl ShellPort for TapePlayer {
+    async fn execute(&self, cmd: &str, _opts: &ExecOptions) -> Result<ExecResult> {
+        let wanted = format!("command `{}`", cmd);
+        let TapeEntry::Shell { command, result, error } =
+            self.take(&wanted, |e| matches!(e, TapeEntry::Shell { command, .. } if command == cmd))?
+        else {
+            unreachable!("take returns the wanted kind");
+        };
+        log::debug!("Replaying `{}`", command);
+        match (result, error) {
+            (Some(result), _) => Ok(result.clone()),
+            (None, error) => Err(AgentError::Shell(error.clone().unwrap_or_default())),
+        }
+    }
+
+    fn execute_streaming(&self, _cmd: &str, _opts: &ExecOptions) -> Pin<Box<dyn Stream<Item = ShellStreamEvent>>> {
+        Box::pin(futures::stream::iter([ShellStreamEvent::Error(
+            "Streaming commands are not recorded".to_string(),
+        )]))
+    }
+
+    async fn cancel(&self, _handle: ExecHandle) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        true
+    }
+
+    /// Input isn't recorded, so a command is replayed by its text alone
+    async fn execute_with_stdin(&self, cmd: &str, _stdin: &str, opts: &ExecOptions) -> Result<ExecResult> {
+        self.execute(cmd, opts).await
+    }
+}
+
+/// Run every prompt of `recording` through `runtime` against the recorded
+/// responses. Errors the recording itself contains are replayed like the
+/// rest; the result is an error only if the replay left the recording.
+pub async fn replay_session(runtime: &mut AgentRuntime, recording: &Recording, vfs: &dyn VfsPort) -> Result<()> {
+    let player = TapePlayer::new(recording);
+    while let Some(prompt) = player.next_prompt() {
+        if let Err(e) = runtime.run_turn(&prompt, &player, &player, vfs).await {
+            log::info!("Replayed turn ended with an error: {}", e);
+        }
+    }
+    match player.divergence() {
+        Some(message) => Err(AgentError::Other(format!("Replay diverged: {}", message))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,347 @@
+//! Workspace retrieval (RAG) — chunk VFS files, embed them, and search.
+//!
+//! The index is a single JSON document stored under `rag:index`. It is
+//! refreshed incrementally before each search: a file is only read again
+//! when its size or modification time changes, only re-embedded when its
+//! content hash changes, and entries for deleted files are dropped. A file
+//! that can't be read is logged and skipped.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use agent_types::{
+    Result,
+    tool::{ParamSchema, ToolDefinition, ToolParameters},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::ports::{EmbeddingsPort, StoragePort, VfsPort};
+
+pub const INDEX_KEY: &str = "rag:index";
+
+/// Files larger than this are not indexed
+const MAX_FILE_BYTES: usize = 256 * 1024;
+/// Lines per chunk, and lines shared between neighbouring chunks
+const CHUNK_LINES: usize = 40;
+const CHUNK_OVERLAP: usize = 5;
+/// Texts sent to the embeddings port per call
+const EMBED_BATCH: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedChunk {
+    pub path: String,
+    /// 1-based, inclusive line range
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VectorIndex {
+    /// Embedding model the vectors came from
+    pub model: String,
+    /// path → content hash of the indexed version
+    pub files: HashMap<String, u64>,
+    /// path → size and modification time when last read, for every file
+    /// checked (indexable or not)
+    #[serde(default)]
+    pub stamps: HashMap<String, String>,
+    pub chunks: Vec<IndexedChunk>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub score: f32,
+    pub chunk: IndexedChunk,
+}
+
+pub struct WorkspaceRetriever {
+    embeddings: Rc<dyn EmbeddingsPort>,
+    storage: Rc<dyn StoragePort>,
+    root: String,
+    /// In-memory copy of the stored index
+    index: RefCell<Option<VectorIndex>>,
+}
+
+impl WorkspaceRetriever {
+    pub fn new(
+        embeddings: Rc<dyn EmbeddingsPort>,
+        storage: Rc<dyn StoragePort>,
+        root: impl Into<String>,
+    ) -> Self {
+        Self {
+            embeddings,
+            storage,
+            root: root.into(),
+            index: RefCell::new(None),
+        }
+    }
+
+    pub fn tool_definition() -> ToolDefinition {
+        ToolDefinition {
+            name: "search_workspace".to_string(),
+            description: "Semantic search over files in the workspace. Returns the most relevant excerpts with their paths and line numbers".to_string(),
+            parameters: ToolParameters::object()
+                .property(
+                    "query",
+                    ParamSchema::string("What to look for, in natural language or keywords"),
+                    true,
+                )
+                .property(
+                    "top_k",
+                    ParamSchema::integer("Maximum number of excerpts to return (default 5)").range(1.0, 20.0),
+                    false,
+                ),
+            strict: true,
+        }
+    }
+
+    async fn load_index(&self) -> Result<VectorIndex> {
+        if let Some(index) = self.index.borrow_mut().take() {
+            return Ok(index);
+        }
+        let stored = match self.storage.get(INDEX_KEY).await? {
+            Some(bytes) => serde_json::from_slice::<VectorIndex>(&bytes).unwrap_or_default(),
+            None => VectorIndex::default(),
+        };
+        // Vectors from another model are not comparable — start over
+        if stored.model != self.embeddings.model_name() {
+            return Ok(VectorIndex {
+                model: self.embeddings.model_name().to_string(),
+                ..Default::default()
+            });
+        }
+        Ok(stored)
+    }
+
+    /// Bring the index up to date with the workspace.
+    /// Returns the number of files that were (re-)embedded.
+    pub async fn refresh(&self, vfs: &dyn VfsPort) -> Result<usize> {
+        self.refresh_with_progress(vfs, &|_, _| {}).await
+    }
+
+    /// [`refresh`](Self::refresh), calling `progress(checked, total)` as
+    /// each file is checked
+    pub async fn refresh_with_progress(&self, vfs: &dyn VfsPort, progress: &dyn Fn(usize, usize)) -> Result<usize> {
+        let mut index = self.load_index().await?;
+        let result = self.refresh_index(&mut index, vfs, progress).await;
+        *self.index.borrow_mut() = Some(index);
+        result
+    }
+
+    async fn refresh_index(
+        &self,
+        index: &mut VectorIndex,
+        vfs: &dyn VfsPort,
+        progress: &dyn Fn(usize, usize),
+    ) -> Result<usize> {
+        let files = walk_files(vfs, &self.root, false).await?;
+        let total = files.len();
+        let mut changed = 0;
+        let mut restamped = false;
+        let mut seen = Vec::with_capacity(files.len());
+
+        for (checked, path) in files.iter().cloned().enumerate() {
+            progress(checked, total);
+            let stamp = file_stamp(vfs, &path).await;
+            if stamp.is_some() && index.stamps.get(&path) == stamp.as_ref() {
+                if index.files.contains_key(&path) {
+                    seen.push(path);
+                }
+                continue;
+            }
+            let data = match vfs.read_file(&path).await {
+                Ok(data) => data,
+                Err(e) => {
+                    log::warn!("Not indexing {}: {}", path, e);
+                    // Keep what was indexed before rather than dropping it
+                    if index.files.contains_key(&path) {
+                        seen.push(path);
+                    }
+                    continue;
+                }
+            };
+            let previous = match stamp.clone() {
+                Some(stamp) => index.stamps.insert(path.clone(), stamp),
+                None => index.stamps.remove(&path),
+            };
+            restamped |= previous != stamp;
+            let Some(text) = indexable_text(&data) else {
+                continue;
+            };
+            seen.push(path.clone());
+            let hash = content_hash(&data);
+            if index.files.get(&path) == Some(&hash) {
+                continue;
+            }
+
+            let chunks = chunk_text(text);
+            let mut vectors = Vec::with_capacity(chunks.len());
+            for batch in chunks.chunks(EMBED_BATCH) {
+                let texts: Vec<String> = batch.iter().map(|c| c.2.clone()).collect();
+                vectors.extend(self.embeddings.embed(&texts).await?);
+            }
+
+            index.chunks.retain(|c| c.path != path);
+            for ((start_line, end_line, text), vector) in chunks.into_iter().zip(vectors) {
+                index.chunks.push(IndexedChunk {
+                    path: path.clone(),
+                    start_line,
+                    end_line,
+                    text,
+                    vector,
+                });
+            }
+            index.files.insert(path, hash);
+            changed += 1;
+        }
+
+        progress(total, total);
+
+        let before = index.files.len();
+        index.files.retain(|p, _| seen.contains(p));
+        index.chunks.retain(|c| seen.contains(&c.path));
+        let removed = before - index.files.len();
+        let stamped = index.stamps.len();
+        index.stamps.retain(|p, _| files.contains(p));
+        restamped |= index.stamps.len() != stamped;
+
+        if changed > 0 || removed > 0 || restamped {
+            let bytes = serde_json::to_vec(&*index)?;
+            self.storage.set(INDEX_KEY, &bytes).await?;
+        }
+        Ok(changed)
+    }
+
+    /// Refresh the index, then return the `top_k` chunks most similar to `query`.
+    pub async fn search(&self, vfs: &dyn VfsPort, query: &str, top_k: usize) -> Result<Vec<SearchHit>> {
+        self.search_with_progress(vfs, query, top_k, &|_, _| {}).await
+    }
+
+    /// [`search`](Self::search), reporting the index refresh through
+    /// `progress(checked, total)`
+    pub async fn search_with_progress(
+        &self,
+        vfs: &dyn VfsPort,
+        query: &str,
+        top_k: usize,
+        progress: &dyn Fn(usize, usize),
+    ) -> Result<Vec<SearchHit>> {
+        self.refresh_with_progress(vfs, progress).await?;
+        let query_vec = self
+            .embeddings
+            .embed(&[query.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+
+        let index = self.index.borrow();
+        let Some(index) = index.as_ref() else {
+            return Ok(Vec::new());
+        };
+        let mut hits: Vec<SearchHit> = index
+            .chunks
+            .iter()
+            .map(|c| SearchHit {
+                score: cosine_similarity(&query_vec, &c.vector),
+                chunk: c.clone(),
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(top_k);
+        Ok(hits)
+    }
+}
+
+/// Recursively list files under `root`. Hidden directories are skipped
+/// unless `include_hidden` is set.
+pub(crate) async fn walk_files(vfs: &dyn VfsPort, root: &str, include_hidden: bool) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut stack = vec![root.trim_end_matches('/').to_string()];
+    while let Some(dir) = stack.pop() {
+        for entry in vfs.list_dir(&dir).await? {
+            let path = format!("{}/{}", dir, entry.name);
+            if entry.is_dir {
+                if include_hidden || !entry.name.starts_with('.') {
+                    stack.push(path);
+                }
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The file's size and modification time, or None when the VFS doesn't
+/// report a modification time (the file is then always read).
+async fn file_stamp(vfs: &dyn VfsPort, path: &str) -> Option<String> {
+    let stat = vfs.stat(path).await.ok()?;
+    Some(format!("{}@{}", stat.size, stat.modified?))
+}
+
+/// The file as text, or None if it is too large or looks binary.
+fn indexable_text(data: &[u8]) -> Option<&str> {
+    if data.is_empty() || data.len() > MAX_FILE_BYTES || data.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(data).ok()
+}
+
+/// FNV-1a — cheap change detection, not security
+pub(crate) fn content_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Split text into overlapping line windows: (start_line, end_line, text),
+/// with 1-based inclusive line numbers. Whitespace-only windows are skipped.
+pub fn chunk_text(text: &str) -> Vec<(usize, usize, String)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let body = lines[start..end].join("\n");
+        if !body.trim().is_empty() {
+            chunks.push((start + 1, end, body));
+        }
+        if end == lines.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Render hits as excerpts for the model.
+pub fn format_hits(hits: &[SearchHit]) -> String {
+    hits.iter()
+        .map(|h| {
+            format!(
+                "── {}:{}-{} (score {:.2})\n{}",
+                h.chunk.path, h.chunk.start_line, h.chunk.end_line, h.score, h.chunk.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
@@ -7,16 +7,33 @@
 //! 4. Loop back to step 1
 //! 5. If LLM returns text only, emit the response and stop
 
+use std::rc::Rc;
+
 use agent_types::{
-    Result,
+    AgentError, Result,
+    activity::format_duration,
     config::AgentConfig,
     event::AgentEvent,
-    message::{Message, ToolCallRequest},
-    tool::ToolResult,
+    message::{Message, MessageMeta, Role, ToolCallRequest},
+    pricing::price_for,
+    storage::{content_type_for, detect_content_type},
+    tool::{ExecOptions, ExecResult, ToolDefinition, ToolResult, ToolResultData},
 };
+use crate::attachments::attachment_context;
+use crate::context::{request_messages, strategy_for};
 use crate::event_bus::EventBus;
+use crate::memory::{MemoryStore, format_memories};
+use crate::js::{JsRunner, format_run};
 use crate::ports::*;
-use crate::tools::{ToolRegistry, parse_tool_args};
+use crate::replace::{DEFAULT_ROOT, MAX_PREVIEW_LINES, Replacer, format_diff};
+use crate::retrieval::{WorkspaceRetriever, format_hits};
+use crate::snapshots::SnapshotStore;
+use crate::sql::SqlDatabases;
+use crate::tools::{
+    SHOWN_IMAGE_TYPES, ToolRegistry, describe_binary, looks_binary, parse_tool_args, read_lines, script_command, shell_quote,
+};
+use crate::undo::{UndoJournal, UndoScope, format_undone};
+use crate::web::WebReader;
 
 /// The agent runtime state
 pub struct AgentRuntime {
@@ -26,6 +43,20 @@ pub struct AgentRuntime {
     pub tools: ToolRegistry,
     pub state: AgentState,
     turn_counter: u64,
+    /// Workspace search, when an embeddings model is available
+    retriever: Option<Rc<WorkspaceRetriever>>,
+    /// Long-term memory shared across sessions
+    memory: Option<Rc<MemoryStore>>,
+    /// Prior contents of files changed by tools
+    undo: Option<Rc<UndoJournal>>,
+    /// Workspace snapshots taken at the start of each turn
+    snapshots: Option<Rc<SnapshotStore>>,
+    /// Web page reading, when enabled
+    web: Option<Rc<WebReader>>,
+    /// Sandboxed JavaScript, when enabled
+    js: Option<Rc<JsRunner>>,
+    /// SQLite databases, when enabled
+    sql: Option<Rc<SqlDatabases>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -37,6 +68,7 @@ pub enum AgentState {
 }
 
 impl AgentRuntime {
+    #[allow(clippy::vec_init_then_push)]
     pub fn new(config: AgentConfig, event_bus: EventBus) -> Self {
         let mut messages = Vec::new();
         // Push the system prompt as the first message
@@ -49,7 +81,85 @@ impl AgentRuntime {
             tools: ToolRegistry::new(),
             state: AgentState::Idle,
             turn_counter: 0,
+            retriever: None,
+            memory: None,
+            undo: None,
+            snapshots: None,
+            web: None,
+            js: None,
+            sql: None,
+        }
+    }
+
+    /// Enable or disable long-term memory. Registers the `remember` and
+    /// `recall` tools while a store is set.
+    pub fn set_memory(&mut self, memory: Option<Rc<MemoryStore>>) {
+        match &memory {
+            Some(_) => {
+                self.tools.register(MemoryStore::remember_tool());
+                self.tools.register(MemoryStore::recall_tool());
+            }
+            None => {
+                self.tools.unregister("remember");
+                self.tools.unregister("recall");
+            }
+        }
+        self.memory = memory;
+    }
+
+    /// Enable or disable workspace retrieval. Registers the
+    /// `search_workspace` tool while a retriever is set.
+    pub fn set_retriever(&mut self, retriever: Option<Rc<WorkspaceRetriever>>) {
+        match &retriever {
+            Some(_) => self.tools.register(WorkspaceRetriever::tool_definition()),
+            None => self.tools.unregister("search_workspace"),
+        }
+        self.retriever = retriever;
+    }
+
+    /// Set the undo journal for file changes (e.g. when the workspace
+    /// changes). Registers the `undo_file_change` tool while one is set.
+    pub fn set_undo(&mut self, undo: Option<Rc<UndoJournal>>) {
+        match &undo {
+            Some(_) => self.tools.register(UndoJournal::undo_tool()),
+            None => self.tools.unregister("undo_file_change"),
+        }
+        self.undo = undo;
+    }
+
+    /// Enable or disable reading web pages. Registers the `read_url` tool
+    /// while a reader is set.
+    pub fn set_web_reader(&mut self, web: Option<Rc<WebReader>>) {
+        match &web {
+            Some(_) => self.tools.register(WebReader::tool_definition()),
+            None => self.tools.unregister("read_url"),
+        }
+        self.web = web;
+    }
+
+    /// Enable or disable running JavaScript. Registers the `run_js` tool
+    /// while a runner is set.
+    pub fn set_js_runner(&mut self, js: Option<Rc<JsRunner>>) {
+        match &js {
+            Some(_) => self.tools.register(JsRunner::tool_definition()),
+            None => self.tools.unregister("run_js"),
+        }
+        self.js = js;
+    }
+
+    /// Enable or disable SQL databases (e.g. when the workspace changes).
+    /// Registers the `sql` tool while they are set.
+    pub fn set_sql(&mut self, sql: Option<Rc<SqlDatabases>>) {
+        match &sql {
+            Some(_) => self.tools.register(SqlDatabases::tool_definition()),
+            None => self.tools.unregister("sql"),
         }
+        self.sql = sql;
+    }
+
+    /// Set where the workspace is snapshotted at the start of each turn
+    pub fn set_snapshots(&mut self, snapshots: Option<Rc<SnapshotStore>>) {
+        self.snapshots = snapshots;
     }
 
     /// Run one full agent turn: user message → (think/act/observe)* → response.
@@ -63,48 +173,149 @@ impl AgentRuntime {
         shell: &dyn ShellPort,
         vfs: &dyn VfsPort,
     ) -> Result<()> {
-        self.turn_counter += 1;
-        let turn_id = self.turn_counter;
-        self.event_bus.emit(AgentEvent::TurnStart { turn_id });
+        self.run_turn_with_attachments(user_input, &[], llm, shell, vfs).await
+    }
+
+    /// Run a turn with VFS files attached to the user's message. Their
+    /// contents are shown to the LLM for this turn only; the message keeps
+    /// the paths.
+    pub async fn run_turn_with_attachments(
+        &mut self,
+        user_input: &str,
+        attachments: &[String],
+        llm: &dyn LlmPort,
+        shell: &dyn ShellPort,
+        vfs: &dyn VfsPort,
+    ) -> Result<()> {
+        let turn_id = self.begin_turn(user_input, vfs).await;
 
         // Add user message
-        self.messages.push(Message::user(user_input));
+        let user_index = self.messages.len();
+        let mut user_msg = Message::user(user_input);
+        user_msg.stamp().attachments = attachments.to_vec();
+        // File changes are grouped for undo by the message that caused them
+        let turn_key = user_msg.stamp().id.clone();
+        self.messages.push(user_msg);
+
+        // Relevant workspace excerpts and attached files are shown to the
+        // LLM for this turn only
+        let context = self.retrieval_context(user_input, vfs).await;
+        let attached = attachment_context(attachments, vfs).await;
+        let system_prompt = self.system_prompt_with_memories().await;
+
+        // Tools run this turn, and where the self-review request sits once
+        // the answer is being reviewed (shown to the LLM, not kept)
+        let mut tools_run = 0;
+        let mut review: Option<(usize, Message)> = None;
+        // Bytes of tool output added to the conversation this turn
+        let mut output_bytes = 0;
+        let strategy = strategy_for(&self.config.context);
 
         // Agent loop: think → act → observe → repeat
         const MAX_ITERATIONS: usize = 20;
         for _ in 0..MAX_ITERATIONS {
             self.state = AgentState::Thinking;
 
-            // Think: call the LLM
+            // Think: call the LLM with what the context strategy keeps of
+            // earlier turns; positions below shift by what it dropped
+            let mut messages = request_messages(strategy.as_ref(), &self.messages, user_index);
+            let dropped = self.messages.len() - messages.len();
+            if let Some(prompt) = &system_prompt {
+                messages[0] = prompt.clone();
+            }
+            if let Some((index, request)) = &review {
+                messages.insert(index - dropped, request.clone());
+            }
+            if let Some(files) = &attached {
+                messages.insert(user_index - dropped, files.clone());
+            }
+            if let Some(ctx) = &context {
+                messages.insert(user_index - dropped, ctx.clone());
+            }
             let req = ChatRequest {
-                messages: self.messages.clone(),
-                tools: self.tools.definitions(),
+                messages,
+                tools: self.allowed_tools(),
                 model: self.config.llm.model.clone(),
                 max_tokens: self.config.llm.max_tokens,
                 temperature: self.config.llm.temperature,
+                top_p: self.config.llm.top_p,
+                frequency_penalty: self.config.llm.frequency_penalty,
+                presence_penalty: self.config.llm.presence_penalty,
+                stop: self.config.llm.stop.clone(),
+                seed: self.config.llm.seed,
+                reasoning: self.config.llm.reasoning.clone(),
             };
 
-            let response = llm.chat_completion(req).await.map_err(|e| {
+            #[allow(clippy::manual_inspect)]
+            let response = self.call_llm(llm, req).await.map_err(|e| {
                 self.state = AgentState::Error(e.to_string());
                 self.event_bus.emit(AgentEvent::Error {
-                    message: e.to_string(),
+                    message: e.user_message(),
                 });
                 e
             })?;
 
-            let assistant_msg = response.message;
+            let mut assistant_msg = stamp_reply(response, &self.config.llm.model, &self.config);
+            if let Some(reasoning) = &assistant_msg.reasoning {
+                self.event_bus.emit(AgentEvent::LlmReasoning {
+                    text: reasoning.clone(),
+                });
+            }
+
+            // The first reply to the review request follows the draft answer
+            let draft = review
+                .as_ref()
+                .filter(|(index, _)| *index == self.messages.len())
+                .map(|(index, _)| index - 1);
 
             // Check if the assistant wants to call tools
             if assistant_msg.tool_calls.is_empty() {
-                // No tool calls — final text response
-                let text = assistant_msg.content.as_text().to_string();
-                self.messages.push(assistant_msg);
+                // No tool calls — final text response, unless it gets reviewed
+                if review.is_none()
+                    && self.config.review.enabled
+                    && tools_run >= self.config.review.min_tool_calls
+                {
+                    self.messages.push(assistant_msg);
+                    review = Some((self.messages.len(), Message::user(review_prompt(user_input))));
+                    self.event_bus.emit(AgentEvent::ReviewStart);
+                    continue;
+                }
+
+                let text = match draft {
+                    // Confirmed: the draft stands, with the review's usage
+                    Some(index) if is_confirmation(assistant_msg.content.as_text()) => {
+                        let kept = &mut self.messages[index];
+                        add_usage(kept.stamp(), assistant_msg.meta.as_ref());
+                        kept.content.as_text().to_string()
+                    }
+                    // Revised without further tool use: the new answer
+                    // replaces the draft
+                    Some(index) => {
+                        let replaced = self.messages.remove(index);
+                        add_usage(assistant_msg.stamp(), replaced.meta.as_ref());
+                        let text = assistant_msg.content.as_text().to_string();
+                        self.messages.push(assistant_msg);
+                        text
+                    }
+                    None => {
+                        let text = assistant_msg.content.as_text().to_string();
+                        self.messages.push(assistant_msg);
+                        text
+                    }
+                };
                 self.event_bus.emit(AgentEvent::LlmComplete { text });
                 self.state = AgentState::Idle;
                 self.event_bus.emit(AgentEvent::TurnEnd { turn_id });
                 return Ok(());
             }
 
+            // The review found work left to do; the draft stays in the
+            // conversation as an interim answer
+            if let Some(index) = draft {
+                let text = self.messages[index].content.as_text().to_string();
+                self.event_bus.emit(AgentEvent::LlmDelta { token: text });
+            }
+
             // Emit the assistant's reasoning text if any
             let reasoning = assistant_msg.content.as_text().to_string();
             if !reasoning.is_empty() {
@@ -114,19 +325,35 @@ impl AgentRuntime {
             }
 
             let tool_calls = assistant_msg.tool_calls.clone();
+            tools_run += tool_calls.len();
             self.messages.push(assistant_msg);
 
             // Act: execute each tool call
             for tc in &tool_calls {
                 let result = self
-                    .execute_tool(tc, shell, vfs)
+                    .execute_tool(tc, &turn_key, shell, vfs)
                     .await;
 
-                // Observe: append tool result
-                let tool_msg = Message::tool_result(
+                // Observe: append tool result, or a pointer to it when it
+                // doesn't fit in what's left of the turn's output budget
+                let budget = self.config.tool_output.turn_budget_bytes;
+                let mut data = result.data;
+                let output = if budget > 0 && output_bytes + result.output.len() > budget {
+                    let remaining = budget.saturating_sub(output_bytes);
+                    let (pointer, stored) = self.store_omitted_output(&tc.id, &result.output, remaining, vfs).await;
+                    if let Some(path) = stored {
+                        data.get_or_insert_with(Default::default).artifacts.push(path);
+                    }
+                    pointer
+                } else {
+                    output_bytes += result.output.len();
+                    result.output
+                };
+                let mut tool_msg = Message::tool_result(
                     &tc.id,
-                    &result.output,
+                    &output,
                 );
+                tool_msg.stamp().tool_data = data;
                 self.messages.push(tool_msg);
             }
         }
@@ -140,10 +367,12 @@ impl AgentRuntime {
         Ok(())
     }
 
-    /// Execute a single tool call and return the result
+    /// Execute a single tool call and return the result. `turn_key`
+    /// identifies the turn in the undo journal.
     async fn execute_tool(
         &mut self,
         tc: &ToolCallRequest,
+        turn_key: &str,
         shell: &dyn ShellPort,
         vfs: &dyn VfsPort,
     ) -> ToolResult {
@@ -160,6 +389,20 @@ impl AgentRuntime {
             tool_name: tool_name.clone(),
             arguments: tc.function.arguments.clone(),
         });
+        let started = chrono::Utc::now();
+
+        // Only offered tools should be called, but a model may try others
+        if !self.config.tool_allowed(&tool_name) {
+            let output = format!("The {} tool is not available in this session.", tool_name);
+            self.event_bus.emit(AgentEvent::ToolExecEnd {
+                call_id: call_id.clone(),
+                result: output.clone(),
+                success: false,
+                duration_ms: 0,
+                data: None,
+            });
+            return ToolResult { call_id, output, success: false, data: None };
+        }
 
         let args = match parse_tool_args(&tc.function.arguments) {
             Ok(v) => v,
@@ -169,74 +412,180 @@ impl AgentRuntime {
                     call_id: call_id.clone(),
                     result: output.clone(),
                     success: false,
+                    duration_ms: elapsed_ms(started),
+                    data: None,
                 });
-                return ToolResult { call_id, output, success: false };
+                return ToolResult { call_id, output, success: false, data: None };
             }
         };
 
         let result = match tool_name.as_str() {
             "bash" => {
                 let cmd = args["command"].as_str().unwrap_or("");
-                let timeout = args.get("timeout_ms").and_then(|v| v.as_u64());
-                match shell.execute(cmd, timeout).await {
-                    Ok(exec) => {
-                        let mut output = String::new();
-                        if !exec.stdout.is_empty() {
-                            output.push_str(&exec.stdout);
-                        }
-                        if !exec.stderr.is_empty() {
-                            if !output.is_empty() {
-                                output.push('\n');
+                let opts = ExecOptions {
+                    cwd: args["cwd"].as_str().map(String::from),
+                    env: Vec::new(),
+                    timeout_ms: args.get("timeout_ms").and_then(|v| v.as_u64()),
+                };
+                let result = match args["stdin"].as_str() {
+                    Some(stdin) => shell.execute_with_stdin(cmd, stdin, &opts).await,
+                    None => shell.execute(cmd, &opts).await,
+                };
+                match result {
+                    Ok(exec) => self.exec_result(&call_id, &exec, &args, vfs).await,
+                    Err(e) => ToolResult {
+                        call_id: call_id.clone(),
+                        output: format!("Shell error: {}", e),
+                        success: false,
+                        data: None,
+                    },
+                }
+            }
+            "run_script" => {
+                let path = args["path"].as_str().unwrap_or("");
+                let opts = ExecOptions {
+                    timeout_ms: args.get("timeout_ms").and_then(|v| v.as_u64()),
+                    ..ExecOptions::default()
+                };
+                let script_args: Vec<String> = args["args"]
+                    .as_array()
+                    .map(|values| values.iter().filter_map(|v| v.as_str()).map(shell_quote).collect())
+                    .unwrap_or_default();
+                match vfs.read_file(path).await {
+                    Ok(data) if looks_binary(&data) => ToolResult {
+                        call_id: call_id.clone(),
+                        output: format!("{} is a binary file, not a script", path),
+                        success: false,
+                        data: None,
+                    },
+                    Ok(data) => {
+                        let command = script_command(path, &String::from_utf8_lossy(&data), &script_args.join(" "));
+                        match shell.execute(&command, &opts).await {
+                            Ok(exec) => {
+                                for chunk in [&exec.stdout, &exec.stderr] {
+                                    if !chunk.is_empty() {
+                                        self.event_bus.emit(AgentEvent::ToolOutput {
+                                            call_id: call_id.clone(),
+                                            chunk: chunk.clone(),
+                                        });
+                                    }
+                                }
+                                self.exec_result(&call_id, &exec, &args, vfs).await
                             }
-                            output.push_str("STDERR: ");
-                            output.push_str(&exec.stderr);
-                        }
-                        output.push_str(&format!("\n[exit code: {}]", exec.exit_code));
-                        ToolResult {
-                            call_id: call_id.clone(),
-                            output,
-                            success: exec.exit_code == 0,
+                            Err(e) => ToolResult {
+                                call_id: call_id.clone(),
+                                output: format!("Shell error: {}", e),
+                                success: false,
+                                data: None,
+                            },
                         }
                     }
                     Err(e) => ToolResult {
                         call_id: call_id.clone(),
-                        output: format!("Shell error: {}", e),
+                        output: format!("Failed to read script {}: {}", path, e),
                         success: false,
+                        data: None,
                     },
                 }
             }
             "read_file" => {
                 let path = args["path"].as_str().unwrap_or("");
+                let force_text = args["force_text"].as_bool().unwrap_or(false);
+                let offset = args["offset"].as_u64().map(|n| n as usize);
+                let limit = args["limit"].as_u64().map(|n| n as usize);
                 match vfs.read_file(path).await {
                     Ok(data) => {
-                        let text = String::from_utf8_lossy(&data).to_string();
+                        let binary = !force_text && looks_binary(&data);
+                        let text = if binary {
+                            describe_binary(path, &data)
+                        } else if offset.is_some() || limit.is_some() {
+                            read_lines(&String::from_utf8_lossy(&data), offset, limit)
+                        } else {
+                            String::from_utf8_lossy(&data).to_string()
+                        };
+                        // An image is shown to the user as well
+                        let image = detect_content_type(path, &data).filter(|mime| SHOWN_IMAGE_TYPES.contains(mime));
+                        let data = match image {
+                            Some(mime) if binary => {
+                                self.event_bus.emit(AgentEvent::ToolArtifact {
+                                    call_id: call_id.clone(),
+                                    path: path.to_string(),
+                                    mime_type: mime.to_string(),
+                                });
+                                Some(ToolResultData { images: vec![path.to_string()], ..Default::default() })
+                            }
+                            _ => None,
+                        };
                         ToolResult {
                             call_id: call_id.clone(),
                             output: text,
                             success: true,
+                            data,
                         }
                     }
                     Err(e) => ToolResult {
                         call_id: call_id.clone(),
                         output: format!("Read error: {}", e),
                         success: false,
+                        data: None,
                     },
                 }
             }
             "write_file" => {
                 let path = args["path"].as_str().unwrap_or("");
                 let content = args["content"].as_str().unwrap_or("");
-                match vfs.write_file(path, content.as_bytes()).await {
-                    Ok(()) => ToolResult {
-                        call_id: call_id.clone(),
-                        output: format!("Written {} bytes to {}", content.len(), path),
-                        success: true,
+                let mode = args["mode"].as_str().unwrap_or("overwrite");
+                let refused = match mode {
+                    "overwrite" | "append" => None,
+                    "create_new" => match vfs.exists(path).await {
+                        Ok(false) => None,
+                        Ok(true) => Some(format!(
+                            "Write error: {} already exists; use mode overwrite or append to change it",
+                            path
+                        )),
+                        Err(e) => Some(format!("Write error: {}", e)),
                     },
-                    Err(e) => ToolResult {
+                    other => Some(format!(
+                        "Write error: unknown mode \"{}\"; use overwrite, append or create_new",
+                        other
+                    )),
+                };
+                match refused {
+                    Some(output) => ToolResult {
                         call_id: call_id.clone(),
-                        output: format!("Write error: {}", e),
+                        output,
                         success: false,
+                        data: None,
                     },
+                    None => {
+                        if let Some(undo) = &self.undo {
+                            match undo.record(vfs, turn_key, "write_file", path).await {
+                                Ok(true) => {}
+                                Ok(false) => log::warn!("{} is too large to capture for undo", path),
+                                Err(e) => log::warn!("Failed to record undo for {}: {}", path, e),
+                            }
+                        }
+                        let written =
+                            self.write_with_progress(vfs, &call_id, path, content.as_bytes(), mode == "append").await;
+                        match written {
+                            Ok(()) => ToolResult {
+                                call_id: call_id.clone(),
+                                output: if mode == "append" {
+                                    format!("Appended {} bytes to {}", content.len(), path)
+                                } else {
+                                    format!("Written {} bytes to {}", content.len(), path)
+                                },
+                                success: true,
+                                data: Some(ToolResultData { artifacts: vec![path.to_string()], ..Default::default() }),
+                            },
+                            Err(e) => ToolResult {
+                                call_id: call_id.clone(),
+                                output: format!("Write error: {}", e),
+                                success: false,
+                                data: None,
+                            },
+                        }
+                    }
                 }
             }
             "list_dir" => {
@@ -251,19 +600,247 @@ impl AgentRuntime {
                             call_id: call_id.clone(),
                             output: listing.join("\n"),
                             success: true,
+                            data: None,
                         }
                     }
                     Err(e) => ToolResult {
                         call_id: call_id.clone(),
                         output: format!("List error: {}", e),
                         success: false,
+                        data: None,
+                    },
+                }
+            }
+            "search_workspace" => {
+                let query = args["query"].as_str().unwrap_or("");
+                let top_k = args.get("top_k").and_then(|v| v.as_u64()).unwrap_or(5) as usize;
+                match &self.retriever {
+                    Some(retriever) => {
+                        let progress = |checked: usize, total: usize| {
+                            self.progress(&call_id, checked as u64, Some(total as u64), "files");
+                        };
+                        match retriever.search_with_progress(vfs, query, top_k, &progress).await {
+                            Ok(hits) if hits.is_empty() => ToolResult {
+                                call_id: call_id.clone(),
+                                output: "No matching files in the workspace".to_string(),
+                                success: true,
+                                data: None,
+                            },
+                            Ok(hits) => ToolResult {
+                                call_id: call_id.clone(),
+                                output: format_hits(&hits),
+                                success: true,
+                                data: None,
+                            },
+                            Err(e) => ToolResult {
+                                call_id: call_id.clone(),
+                                output: format!("Search error: {}", e),
+                                success: false,
+                                data: None,
+                            },
+                        }
+                    }
+                    None => ToolResult {
+                        call_id: call_id.clone(),
+                        output: "Workspace search is not enabled".to_string(),
+                        success: false,
+                        data: None,
+                    },
+                }
+            }
+            "remember" => {
+                let fact = args["fact"].as_str().unwrap_or("");
+                let tags: Vec<String> = args
+                    .get("tags")
+                    .and_then(|v| v.as_array())
+                    .map(|a| a.iter().filter_map(|t| t.as_str().map(String::from)).collect())
+                    .unwrap_or_default();
+                match &self.memory {
+                    Some(_) if fact.trim().is_empty() => ToolResult {
+                        call_id: call_id.clone(),
+                        output: "Nothing to remember: fact is empty".to_string(),
+                        success: false,
+                        data: None,
+                    },
+                    Some(memory) => match memory.remember(fact, tags).await {
+                        Ok(m) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: format!("Remembered (id {})", m.id),
+                            success: true,
+                            data: None,
+                        },
+                        Err(e) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: format!("Memory error: {}", e),
+                            success: false,
+                            data: None,
+                        },
+                    },
+                    None => ToolResult {
+                        call_id: call_id.clone(),
+                        output: "Long-term memory is not enabled".to_string(),
+                        success: false,
+                        data: None,
+                    },
+                }
+            }
+            "recall" => {
+                let query = args["query"].as_str().unwrap_or("");
+                match &self.memory {
+                    Some(memory) => match memory.recall(query, 10).await {
+                        Ok(found) if found.is_empty() => ToolResult {
+                            call_id: call_id.clone(),
+                            output: "No matching memories".to_string(),
+                            success: true,
+                            data: None,
+                        },
+                        Ok(found) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: format_memories(&found),
+                            success: true,
+                            data: None,
+                        },
+                        Err(e) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: format!("Memory error: {}", e),
+                            success: false,
+                            data: None,
+                        },
+                    },
+                    None => ToolResult {
+                        call_id: call_id.clone(),
+                        output: "Long-term memory is not enabled".to_string(),
+                        success: false,
+                        data: None,
+                    },
+                }
+            }
+            "undo_file_change" => {
+                let scope = match args["scope"].as_str() {
+                    Some("turn") => UndoScope::Turn,
+                    _ => UndoScope::Last,
+                };
+                match &self.undo {
+                    Some(undo) => match undo.undo(vfs, scope).await {
+                        Ok(undone) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: format_undone(&undone),
+                            success: true,
+                            data: None,
+                        },
+                        Err(e) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: format!("Undo error: {}", e),
+                            success: false,
+                            data: None,
+                        },
+                    },
+                    None => ToolResult {
+                        call_id: call_id.clone(),
+                        output: "Undo is not enabled".to_string(),
+                        success: false,
+                        data: None,
+                    },
+                }
+            }
+            "replace_in_files" => {
+                match self.replace_in_files(&args, turn_key, vfs).await {
+                    Ok((output, data)) => ToolResult {
+                        call_id: call_id.clone(),
+                        output,
+                        success: true,
+                        data: Some(data),
+                    },
+                    Err(e) => ToolResult {
+                        call_id: call_id.clone(),
+                        output: format!("Replace error: {}", e),
+                        success: false,
+                        data: None,
+                    },
+                }
+            }
+            "read_url" => {
+                let url = args["url"].as_str().unwrap_or("");
+                let refresh = args.get("refresh").and_then(|v| v.as_bool()).unwrap_or(false);
+                match &self.web {
+                    Some(web) => match web.read(url, refresh, &self.config.web, vfs).await {
+                        Ok(text) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: text,
+                            success: true,
+                            data: None,
+                        },
+                        Err(e) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: format!("Could not read {}: {}", url, e),
+                            success: false,
+                            data: None,
+                        },
+                    },
+                    None => ToolResult {
+                        call_id: call_id.clone(),
+                        output: "Reading web pages is not enabled".to_string(),
+                        success: false,
+                        data: None,
                     },
                 }
             }
+            "run_js" => {
+                let code = args["code"].as_str().unwrap_or("");
+                match &self.js {
+                    Some(js) => match js.run(code, &self.config.js).await {
+                        Ok(run) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: format_run(&run, self.config.js.max_output_chars),
+                            success: run.error.is_none(),
+                            data: Some(ToolResultData {
+                                mime_type: Some("text/plain".to_string()),
+                                json: serde_json::to_value(&run).ok(),
+                                ..Default::default()
+                            }),
+                        },
+                        Err(e) => ToolResult {
+                            call_id: call_id.clone(),
+                            output: format!("JavaScript error: {}", e),
+                            success: false,
+                            data: None,
+                        },
+                    },
+                    None => ToolResult {
+                        call_id: call_id.clone(),
+                        output: "Running JavaScript is not enabled".to_string(),
+                        success: false,
+                        data: None,
+                    },
+                }
+            }
+            "sql" => match &self.sql {
+                Some(sql) => match sql.call(&args, &self.config.sql, vfs).await {
+                    Ok((output, data)) => ToolResult {
+                        call_id: call_id.clone(),
+                        output,
+                        success: true,
+                        data: Some(data),
+                    },
+                    Err(e) => ToolResult {
+                        call_id: call_id.clone(),
+                        output: format!("SQL error: {}", e),
+                        success: false,
+                        data: None,
+                    },
+                },
+                None => ToolResult {
+                    call_id: call_id.clone(),
+                    output: "SQL databases are not enabled".to_string(),
+                    success: false,
+                    data: None,
+                },
+            },
             _ => ToolResult {
                 call_id: call_id.clone(),
                 output: format!("Unknown tool: {}", tool_name),
                 success: false,
+                data: None,
             },
         };
 
@@ -271,11 +848,334 @@ impl AgentRuntime {
             call_id: result.call_id.clone(),
             result: result.output.clone(),
             success: result.success,
+            duration_ms: elapsed_ms(started),
+            data: result.data.clone(),
         });
 
         result
     }
 
+    /// The result of a finished shell command. When it succeeded, the
+    /// images the call says it wrote are checked and shown with it; those
+    /// that can't be are noted for the model.
+    async fn exec_result(
+        &self,
+        call_id: &str,
+        exec: &ExecResult,
+        args: &serde_json::Value,
+        vfs: &dyn VfsPort,
+    ) -> ToolResult {
+        let mut output = format_exec(exec);
+        let mut data = exec_data(exec);
+        let paths: Vec<&str> = match args["images"].as_array() {
+            Some(paths) if exec.exit_code == 0 => paths.iter().filter_map(|p| p.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        let mut unshown = Vec::new();
+        for path in paths {
+            match shown_image_type(path, vfs).await {
+                Ok(mime) => {
+                    self.event_bus.emit(AgentEvent::ToolArtifact {
+                        call_id: call_id.to_string(),
+                        path: path.to_string(),
+                        mime_type: mime,
+                    });
+                    data.images.push(path.to_string());
+                }
+                Err(reason) => unshown.push(reason),
+            }
+        }
+        if !unshown.is_empty() {
+            output.push_str(&format!("\n[images not shown: {}]", unshown.join("; ")));
+        }
+        ToolResult {
+            call_id: call_id.to_string(),
+            output,
+            success: exec.exit_code == 0,
+            data: Some(data),
+        }
+    }
+
+    /// Excerpts relevant to `query`, as a system message, when auto-injection
+    /// is on and something scores above the configured threshold.
+    /// Failures are logged and the turn continues without context.
+    async fn retrieval_context(&self, query: &str, vfs: &dyn VfsPort) -> Option<Message> {
+        let retriever = self.retriever.as_ref()?;
+        let cfg = &self.config.retrieval;
+        if !cfg.auto_inject || cfg.top_k == 0 {
+            return None;
+        }
+        let hits = match retriever.search(vfs, query, cfg.top_k).await {
+            Ok(hits) => hits,
+            Err(e) => {
+                log::warn!("Workspace retrieval failed: {}", e);
+                return None;
+            }
+        };
+        let hits: Vec<_> = hits.into_iter().filter(|h| h.score >= cfg.min_score).collect();
+        if hits.is_empty() {
+            return None;
+        }
+        Some(Message::system(format!(
+            "Possibly relevant excerpts from the workspace:\n\n{}",
+            format_hits(&hits)
+        )))
+    }
+
+    /// The system prompt with recent memories appended, when memory
+    /// injection is on and there is something to inject.
+    async fn system_prompt_with_memories(&self) -> Option<Message> {
+        let memory = self.memory.as_ref()?;
+        let cfg = &self.config.memory;
+        if !cfg.inject_into_prompt || cfg.max_injected == 0 {
+            return None;
+        }
+        let memories = match memory.list().await {
+            Ok(memories) => memories,
+            Err(e) => {
+                log::warn!("Failed to load memories: {}", e);
+                return None;
+            }
+        };
+        if memories.is_empty() {
+            return None;
+        }
+        let recent: Vec<_> = memories.into_iter().take(cfg.max_injected).collect();
+        Some(Message::system(format!(
+            "{}\nThings you remember from earlier sessions:\n{}\n",
+            self.messages[0].content.as_text(),
+            format_memories(&recent)
+        )))
+    }
+
+    /// Report progress of tool call `call_id`
+    fn progress(&self, call_id: &str, done: u64, total: Option<u64>, unit: &str) {
+        self.event_bus.emit(AgentEvent::ToolProgress {
+            call_id: call_id.to_string(),
+            done,
+            total,
+            unit: unit.to_string(),
+        });
+    }
+
+    /// Write (or append) `data` to `path` in one call, so a failure leaves
+    /// the file as it was. Past [`WRITE_PROGRESS_BYTES`] progress is
+    /// reported before and after the write.
+    async fn write_with_progress(
+        &self,
+        vfs: &dyn VfsPort,
+        call_id: &str,
+        path: &str,
+        data: &[u8],
+        append: bool,
+    ) -> Result<()> {
+        let total = data.len() as u64;
+        let report = data.len() > WRITE_PROGRESS_BYTES;
+        if report {
+            self.progress(call_id, 0, Some(total), "bytes");
+        }
+        if append {
+            vfs.append_file(path, data).await?;
+        } else {
+            vfs.write_file(path, data).await?;
+        }
+        if report {
+            self.progress(call_id, total, Some(total), "bytes");
+        }
+        Ok(())
+    }
+
+    /// Carry out a `replace_in_files` call: plan the replacement, then
+    /// unless it is a dry run write each changed file, captured for undo
+    /// first. Returns the summary and diff.
+    async fn replace_in_files(
+        &self,
+        args: &serde_json::Value,
+        turn_key: &str,
+        vfs: &dyn VfsPort,
+    ) -> Result<(String, ToolResultData)> {
+        let text = |name: &str| args[name].as_str().map(str::trim).filter(|v| !v.is_empty());
+        let flag = |name: &str| args.get(name).and_then(|v| v.as_bool()).unwrap_or(false);
+        let (root, glob) = (text("path").unwrap_or(DEFAULT_ROOT), text("glob").unwrap_or("**"));
+        let pattern = args["pattern"].as_str().unwrap_or("");
+        let replacement = args["replacement"].as_str().unwrap_or("");
+        let plan = Replacer::new(pattern, replacement, flag("literal"))?.plan(vfs, root, glob).await?;
+        let (occurrences, files) = (plan.occurrences(), plan.changes.len());
+        let paths: Vec<String> = plan.changes.iter().map(|change| change.path.clone()).collect();
+        let mut data = ToolResultData {
+            json: Some(serde_json::json!({
+                "replacements": occurrences,
+                "files": paths,
+                "searched": plan.searched,
+                "dry_run": flag("dry_run"),
+            })),
+            ..Default::default()
+        };
+        if files == 0 {
+            let text = format!("No matches in the {} files matching {} under {}", plan.searched, glob, root);
+            return Ok((text, data));
+        }
+        let diff = format_diff(&plan.changes, MAX_PREVIEW_LINES);
+        if flag("dry_run") {
+            let text = format!(
+                "Dry run: {} replacements in {} of {} files; nothing was written.\n\n{}",
+                occurrences, files, plan.searched, diff
+            );
+            return Ok((text, data));
+        }
+        for (written, change) in plan.changes.iter().enumerate() {
+            if let Some(undo) = &self.undo {
+                match undo.record(vfs, turn_key, "replace_in_files", &change.path).await {
+                    Ok(true) => {}
+                    Ok(false) => log::warn!("{} is too large to capture for undo", change.path),
+                    Err(e) => log::warn!("Failed to record undo for {}: {}", change.path, e),
+                }
+            }
+            if let Err(e) = vfs.write_file(&change.path, change.new_text.as_bytes()).await {
+                return Err(AgentError::Fs {
+                    path: change.path.clone(),
+                    message: format!("{} (after changing {} of {} files)", e, written, files),
+                });
+            }
+        }
+        data.artifacts = paths;
+        let text = format!("Made {} replacements in {} of {} files.\n\n{}", occurrences, files, plan.searched, diff);
+        Ok((text, data))
+    }
+
+    /// Write a tool result that doesn't fit in the `remaining` bytes of the
+    /// turn's output budget to the VFS, and return the note the LLM sees
+    /// instead, with where the result was written.
+    async fn store_omitted_output(
+        &self,
+        call_id: &str,
+        output: &str,
+        remaining: usize,
+        vfs: &dyn VfsPort,
+    ) -> (String, Option<String>) {
+        let cfg = &self.config.tool_output;
+        let dir = cfg.outputs_dir.trim_end_matches('/');
+        let name: String = call_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = format!("{}/{}.txt", dir, name);
+
+        let mut parent = String::new();
+        for part in dir.split('/').filter(|p| !p.is_empty()) {
+            parent.push('/');
+            parent.push_str(part);
+            let _ = vfs.mkdir(&parent).await;
+        }
+        match vfs.write_file(&path, output.as_bytes()).await {
+            Ok(()) => {
+                let text = format!(
+                    "[Output omitted: it doesn't fit in this turn's tool output budget \
+                     ({} of {} KiB left). The full output ({} bytes) is stored at {}; \
+                     read it if you need it.]",
+                    remaining / 1024,
+                    cfg.turn_budget_bytes / 1024,
+                    output.len(),
+                    path
+                );
+                (text, Some(path))
+            }
+            Err(e) => {
+                log::warn!("Failed to store omitted tool output at {}: {}", path, e);
+                let text = format!(
+                    "[Output omitted: it doesn't fit in this turn's tool output budget \
+                     ({} of {} KiB left), and the full output ({} bytes) could not be stored: {}]",
+                    remaining / 1024,
+                    cfg.turn_budget_bytes / 1024,
+                    output.len(),
+                    e
+                );
+                (text, None)
+            }
+        }
+    }
+
+    /// Conversation history without the system prompt (what sessions persist)
+    pub fn history(&self) -> &[Message] {
+        &self.messages[1..]
+    }
+
+    /// Use `prompt` as the system prompt from the next request on.
+    pub fn set_system_prompt(&mut self, prompt: &str) {
+        self.config.system_prompt = prompt.to_string();
+        self.messages[0] = Message::system(prompt);
+    }
+
+    /// Replace the conversation with a restored history, keeping the
+    /// current system prompt. Any system messages in `history` are dropped.
+    pub fn restore(&mut self, history: Vec<Message>) {
+        self.messages.truncate(1);
+        self.messages
+            .extend(history.into_iter().filter(|m| m.role != Role::System));
+        self.state = AgentState::Idle;
+        self.turn_counter = 0;
+    }
+
+    /// Start turn `user_input`: number it, announce it and snapshot the
+    /// workspace. Returns the turn id.
+    pub(crate) async fn begin_turn(&mut self, user_input: &str, vfs: &dyn VfsPort) -> u64 {
+        self.turn_counter += 1;
+        let turn_id = self.turn_counter;
+        self.event_bus.emit(AgentEvent::TurnStart { turn_id });
+
+        if let Some(snapshots) = &self.snapshots {
+            let label: String = user_input.chars().take(80).collect();
+            if let Err(e) = snapshots.take(vfs, &label).await {
+                log::warn!("Failed to snapshot the workspace: {}", e);
+            }
+        }
+        turn_id
+    }
+
+    /// Definitions of the registered tools the config allows
+    pub fn allowed_tools(&self) -> Vec<ToolDefinition> {
+        self.tools.definitions().into_iter().filter(|tool| self.config.tool_allowed(&tool.name)).collect()
+    }
+
+    /// Send `req`, reporting how long it took
+    pub(crate) async fn call_llm(&self, llm: &dyn LlmPort, req: ChatRequest) -> Result<ChatResponse> {
+        let model = req.model.clone();
+        let started = chrono::Utc::now();
+        let response = llm.chat_completion(req).await;
+        self.event_bus.emit(AgentEvent::LlmCallEnd {
+            model,
+            latency_ms: elapsed_ms(started),
+            error: response.as_ref().err().map(|e| e.user_message()),
+        });
+        response
+    }
+
+    /// A runtime with `config` and its own event bus that shares this
+    /// one's workspace search, memory and undo journal, e.g. to carry out
+    /// one step of a plan
+    pub(crate) fn executor(&self, config: AgentConfig, event_bus: EventBus) -> AgentRuntime {
+        let mut executor = AgentRuntime::new(config, event_bus);
+        executor.set_retriever(self.retriever.clone());
+        executor.set_memory(self.memory.clone());
+        executor.set_undo(self.undo.clone());
+        executor.set_web_reader(self.web.clone());
+        executor.set_js_runner(self.js.clone());
+        executor.set_sql(self.sql.clone());
+        executor
+    }
+
+    /// Take back the last user message if nothing answered it, e.g. when
+    /// the turn couldn't reach the provider, so it can be sent again later
+    /// without appearing twice. Returns whether it was taken back.
+    pub fn withdraw_unanswered(&mut self) -> bool {
+        if self.messages.len() < 2 || self.messages.last().is_none_or(|m| m.role != Role::User) {
+            return false;
+        }
+        self.messages.pop();
+        self.state = AgentState::Idle;
+        true
+    }
+
     /// Reset the conversation (keep system prompt)
     pub fn reset(&mut self) {
         self.messages.truncate(1); // keep system prompt
@@ -283,3 +1183,117 @@ impl AgentRuntime {
         self.turn_counter = 0;
     }
 }
+
+/// Writes larger than this report progress
+pub const WRITE_PROGRESS_BYTES: usize = 1 << 20;
+
+/// What the model replies when the review finds nothing to fix
+pub const REVIEW_CONFIRMATION: &str = "CONFIRMED";
+
+/// Longest excerpt of the user's request quoted in the review prompt
+const REVIEW_REQUEST_CHARS: usize = 2000;
+
+/// What the LLM sees of a finished shell command
+fn format_exec(exec: &ExecResult) -> String {
+    let mut output = String::new();
+    if !exec.stdout.is_empty() {
+        output.push_str(&exec.stdout);
+    }
+    if !exec.stderr.is_empty() {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str("STDERR: ");
+        output.push_str(&exec.stderr);
+    }
+    if exec.duration_ms > 0 {
+        output.push_str(&format!(
+            "\n[exit code: {} after {}]",
+            exec.exit_code,
+            format_duration(exec.duration_ms)
+        ));
+    } else {
+        output.push_str(&format!("\n[exit code: {}]", exec.exit_code));
+    }
+    output
+}
+
+/// The exit code of a shell command, as tool result data
+fn exec_data(exec: &ExecResult) -> ToolResultData {
+    ToolResultData { mime_type: Some("text/plain".to_string()), exit_code: Some(exec.exit_code), ..Default::default() }
+}
+
+/// The media type of the image at `path`, or why the chat can't show it
+async fn shown_image_type(path: &str, vfs: &dyn VfsPort) -> std::result::Result<String, String> {
+    let stat = match vfs.stat(path).await {
+        Ok(stat) if !stat.is_dir => stat,
+        _ => return Err(format!("{} was not found", path)),
+    };
+    match stat.content_type.or_else(|| content_type_for(path).map(String::from)) {
+        Some(mime) if SHOWN_IMAGE_TYPES.contains(&mime.as_str()) => Ok(mime),
+        _ => Err(format!("{} is not a PNG, JPEG or BMP image", path)),
+    }
+}
+
+/// Milliseconds since `started`
+fn elapsed_ms(started: chrono::DateTime<chrono::Utc>) -> u64 {
+    chrono::Utc::now().signed_duration_since(started).num_milliseconds().max(0) as u64
+}
+
+/// The self-review request, quoting the original request
+fn review_prompt(user_input: &str) -> String {
+    let request: String = user_input.chars().take(REVIEW_REQUEST_CHARS).collect();
+    format!(
+        "Before finishing, check your work against the original request:\n\n\
+         {}\n\n\
+         Verify that it is fully done: files written, commands succeeded, nothing \
+         skipped. If it is complete and correct, reply with exactly {}. Otherwise \
+         fix what is missing now, using tools as needed, then give the corrected \
+         final answer.",
+        request, REVIEW_CONFIRMATION
+    )
+}
+
+fn is_confirmation(reply: &str) -> bool {
+    reply
+        .trim()
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .eq_ignore_ascii_case(REVIEW_CONFIRMATION)
+}
+
+/// The reply in `response`, stamped with the model (`model` unless the
+/// provider named it), provider, usage and estimated cost
+pub(crate) fn stamp_reply(response: ChatResponse, model: &str, config: &AgentConfig) -> Message {
+    let mut message = response.message;
+    let meta = message.stamp();
+    if meta.model.is_none() {
+        meta.model = Some(model.to_string());
+    }
+    if meta.provider.is_none() {
+        meta.provider = Some(config.llm.provider.label().to_string());
+    }
+    if meta.usage.is_none() {
+        meta.usage = response.usage;
+    }
+    if meta.cost_usd.is_none() {
+        let price = meta.model.as_deref().and_then(|m| price_for(m, &config.prices));
+        meta.cost_usd = price.zip(meta.usage.as_ref()).map(|(p, u)| p.cost(u));
+    }
+    message
+}
+
+/// Add the usage and cost recorded in `extra` to `meta`
+pub(crate) fn add_usage(meta: &mut MessageMeta, extra: Option<&MessageMeta>) {
+    let Some(extra) = extra else {
+        return;
+    };
+    if let Some(usage) = &extra.usage {
+        let total = meta.usage.get_or_insert_with(Default::default);
+        total.prompt_tokens += usage.prompt_tokens;
+        total.completion_tokens += usage.completion_tokens;
+        total.total_tokens += usage.total_tokens;
+    }
+    if let Some(cost) = extra.cost_usd {
+        *meta.cost_usd.get_or_insert(0.0) += cost;
+    }
+}
@@ -0,0 +1,158 @@
+//! Per-turn workspace snapshots.
+//!
+//! When a turn starts, the runtime records a manifest of every file under
+//! the workspace root (`snapshot:<seq>`). File contents are stored once per
+//! distinct content under `snapshot-blob:<hash>`, so a snapshot of a mostly
+//! unchanged workspace only costs its manifest. Restoring rewrites changed
+//! files and deletes files the snapshot did not have. Only the most recent
+//! [`MAX_SNAPSHOTS`] are kept; blobs nothing refers to any more are dropped.
+
+use std::collections::{BTreeMap, HashSet};
+use std::rc::Rc;
+
+use agent_types::{AgentError, Result, snapshot::Snapshot};
+
+use crate::ports::{StoragePort, VfsPort};
+use crate::retrieval::{content_hash, walk_files};
+
+const KEY_PREFIX: &str = "snapshot:";
+const BLOB_PREFIX: &str = "snapshot-blob:";
+
+/// Snapshots kept; older ones are dropped
+pub const MAX_SNAPSHOTS: usize = 20;
+
+pub struct SnapshotStore {
+    storage: Rc<dyn StoragePort>,
+    /// Directory that is snapshotted, e.g. "/workspace"
+    root: String,
+}
+
+impl SnapshotStore {
+    pub fn new(storage: Rc<dyn StoragePort>, root: &str) -> Self {
+        Self {
+            storage,
+            root: root.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Record the current state of the workspace.
+    pub async fn take(&self, vfs: &dyn VfsPort, label: &str) -> Result<Snapshot> {
+        let mut files = BTreeMap::new();
+        for path in walk_files(vfs, &self.root, true).await? {
+            let data = vfs.read_file(&path).await?;
+            let blob = blob_key(&data);
+            if !self.storage.exists(&blob).await? {
+                self.storage.set(&blob, &data).await?;
+            }
+            files.insert(path, blob);
+        }
+
+        let keys = self.keys().await?;
+        let seq = keys
+            .last()
+            .and_then(|k| k.strip_prefix(KEY_PREFIX)?.parse::<u64>().ok())
+            .map_or(1, |n| n + 1);
+        let snapshot = Snapshot::new(seq, label, files);
+        self.storage.set(&key(seq), &serde_json::to_vec(&snapshot)?).await?;
+
+        let excess = (keys.len() + 1).saturating_sub(MAX_SNAPSHOTS);
+        if excess > 0 {
+            for old in &keys[..excess] {
+                self.storage.delete(old).await?;
+            }
+            self.collect_garbage().await?;
+        }
+        Ok(snapshot)
+    }
+
+    /// All snapshots, newest first. Unreadable entries are skipped.
+    pub async fn list(&self) -> Result<Vec<Snapshot>> {
+        let mut snapshots = Vec::new();
+        for key in self.keys().await?.into_iter().rev() {
+            let Some(bytes) = self.storage.get(&key).await? else {
+                continue;
+            };
+            match serde_json::from_slice::<Snapshot>(&bytes) {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(e) => log::warn!("Skipping corrupt snapshot {}: {}", key, e),
+            }
+        }
+        Ok(snapshots)
+    }
+
+    /// Put the workspace back the way it was in snapshot `seq`.
+    /// Returns how many files were written or deleted.
+    pub async fn restore(&self, vfs: &dyn VfsPort, seq: u64) -> Result<usize> {
+        let bytes = self
+            .storage
+            .get(&key(seq))
+            .await?
+            .ok_or_else(|| AgentError::Storage(format!("Snapshot {} not found", seq)))?;
+        let snapshot: Snapshot = serde_json::from_slice(&bytes)?;
+
+        // Work out every change before making any, so a missing blob
+        // can't leave the workspace half restored
+        let mut writes = Vec::new();
+        for (path, blob) in &snapshot.files {
+            let current = match vfs.read_file(path).await {
+                Ok(data) => Some(blob_key(&data)),
+                Err(_) => None,
+            };
+            if current.as_ref() == Some(blob) {
+                continue;
+            }
+            let data = self
+                .storage
+                .get(blob)
+                .await?
+                .ok_or_else(|| AgentError::Storage(format!("Snapshot {} is missing {}", seq, path)))?;
+            writes.push((path, data));
+        }
+        let deletes: Vec<String> = walk_files(vfs, &self.root, true)
+            .await?
+            .into_iter()
+            .filter(|path| !snapshot.files.contains_key(path))
+            .collect();
+
+        for path in &deletes {
+            vfs.delete_file(path).await?;
+        }
+        for (path, data) in &writes {
+            vfs.write_file(path, data).await?;
+        }
+        Ok(deletes.len() + writes.len())
+    }
+
+    /// Delete blobs no remaining snapshot refers to.
+    async fn collect_garbage(&self) -> Result<()> {
+        let live: HashSet<String> = self
+            .list()
+            .await?
+            .into_iter()
+            .flat_map(|s| s.files.into_values())
+            .collect();
+        for blob in self.storage.list_keys(BLOB_PREFIX).await? {
+            if !live.contains(&blob) {
+                self.storage.delete(&blob).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = self.storage.list_keys(KEY_PREFIX).await?;
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Zero-padded so keys sort in history order
+fn key(seq: u64) -> String {
+    format!("{}{:016}", KEY_PREFIX, seq)
+}
+
+/// Content-addressed blob key; the length guards against hash collisions
+/// between files of different sizes
+fn blob_key(data: &[u8]) -> String {
+    format!("{}{:016x}-{}", BLOB_PREFIX, content_hash(data), data.len())
+}
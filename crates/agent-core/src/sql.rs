@@ -0,0 +1,335 @@
+//! SQL over SQLite databases — the `sql` tool.
+//!
+//! The agent can load CSV files from the VFS into tables and answer
+//! questions about them with real queries. Databases are named (`main`
+//! unless the call says otherwise) and kept per workspace: each database
+//! file is stored under `sql:<name>` and handed to the [`SqlPort`] with
+//! every call, and saved again when the statements changed it. A failed
+//! batch changes nothing.
+
+use std::rc::Rc;
+
+use agent_types::{
+    AgentError, ErrorKind, Result,
+    config::{SqlConfig, is_alias_name},
+    tool::{ParamSchema, ToolDefinition, ToolParameters, ToolResultData},
+};
+
+use crate::ports::{SqlPort, SqlRows, StoragePort, VfsPort};
+
+/// Database used unless the call names another
+pub const DEFAULT_DATABASE: &str = "main";
+/// Larger CSV files are not loaded
+pub const MAX_CSV_BYTES: u64 = 16 * 1024 * 1024;
+
+const KEY_PREFIX: &str = "sql:";
+/// Rows per INSERT statement when loading a CSV file
+const INSERT_BATCH: usize = 500;
+/// Longest cell shown in a result table
+const MAX_CELL_CHARS: usize = 200;
+
+pub struct SqlDatabases {
+    engine: Rc<dyn SqlPort>,
+    storage: Rc<dyn StoragePort>,
+}
+
+impl SqlDatabases {
+    pub fn new(engine: Rc<dyn SqlPort>, storage: Rc<dyn StoragePort>) -> Self {
+        Self { engine, storage }
+    }
+
+    pub fn tool_definition() -> ToolDefinition {
+        ToolDefinition {
+            name: "sql".to_string(),
+            description: "Run SQLite statements against a database kept in this workspace, optionally \
+                loading a CSV file from the workspace into a table first. Use it to answer questions \
+                about tabular data with real queries rather than reading and adding up rows yourself. \
+                List the tables with SELECT name, sql FROM sqlite_master"
+                .to_string(),
+            parameters: ToolParameters::object()
+                .property("query", ParamSchema::string("SQL to run; several statements may be separated by ;"), false)
+                .property(
+                    "csv_path",
+                    ParamSchema::string("A CSV (or .tsv) file to load before the query; its first row names the columns"),
+                    false,
+                )
+                .property(
+                    "table",
+                    ParamSchema::string("Table to load the CSV into, replacing any table of that name. Default: the file name"),
+                    false,
+                )
+                .property("database", ParamSchema::string("Database name. Default: main"), false),
+            strict: true,
+        }
+    }
+
+    /// Carry out a `sql` tool call: load the CSV if one is given, run the
+    /// query, and describe what happened. The data has the rows shown to
+    /// the model and how many each result had.
+    pub async fn call(
+        &self,
+        args: &serde_json::Value,
+        config: &SqlConfig,
+        vfs: &dyn VfsPort,
+    ) -> Result<(String, ToolResultData)> {
+        let text_arg = |key: &str| args[key].as_str().map(str::trim).filter(|s| !s.is_empty());
+        let database = text_arg("database").unwrap_or(DEFAULT_DATABASE);
+        if !is_alias_name(database) {
+            return Err(invalid(format!(
+                "\"{}\" is not a database name; use letters, digits, -, _ and .",
+                database
+            )));
+        }
+        let query = text_arg("query");
+        let csv_path = text_arg("csv_path");
+        if query.is_none() && csv_path.is_none() {
+            return Err(invalid("give a query to run, or a csv_path to load"));
+        }
+
+        let mut sql = String::new();
+        let mut notes = Vec::new();
+        if let Some(path) = csv_path {
+            if vfs.stat(path).await.is_ok_and(|stat| stat.size > MAX_CSV_BYTES) {
+                return Err(invalid(format!("{} is larger than {} MiB", path, MAX_CSV_BYTES >> 20)));
+            }
+            let data = vfs.read_file(path).await?;
+            let table = text_arg("table").map_or_else(|| table_name(path), String::from);
+            let delimiter = if path.to_ascii_lowercase().ends_with(".tsv") { '\t' } else { ',' };
+            let import = csv_to_sql(&table, &String::from_utf8_lossy(&data), delimiter)?;
+            sql.push_str(&import.sql);
+            notes.push(import.summary(&table, path));
+        }
+        if let Some(query) = query {
+            sql.push_str(query);
+        }
+
+        let key = format!("{}{}", KEY_PREFIX, database);
+        let image = self.storage.get(&key).await?;
+        let output = self.engine.exec(image.clone(), &sql, config.timeout_ms).await.map_err(|e| match e {
+            AgentError::Timeout(ms) => {
+                AgentError::api(ErrorKind::Timeout, format!("the statements were stopped after {} ms", ms))
+            }
+            e => e,
+        })?;
+        if image.as_ref() != Some(&output.image) {
+            self.storage.set(&key, &output.image).await?;
+        }
+
+        notes.extend(output.results.iter().map(|rows| format_rows(rows, config.max_rows)));
+        if query.is_some() && output.results.is_empty() {
+            notes.push(format!("Done; {} row{} changed", output.changes, if output.changes == 1 { "" } else { "s" }));
+        }
+        let data = ToolResultData {
+            mime_type: Some("text/markdown".to_string()),
+            json: Some(serde_json::json!({
+                "results": output.results.iter().map(|rows| serde_json::json!({
+                    "columns": rows.columns,
+                    "values": &rows.values[..rows.values.len().min(config.max_rows)],
+                    "total_rows": rows.values.len(),
+                })).collect::<Vec<_>>(),
+                "changes": output.changes,
+            })),
+            ..Default::default()
+        };
+        Ok((notes.join("\n\n"), data))
+    }
+}
+
+fn invalid(message: impl Into<String>) -> AgentError {
+    AgentError::api(ErrorKind::InvalidRequest, message)
+}
+
+/// A table name from a file path: its name without the extension, with
+/// anything but letters, digits and `_` replaced by `_`
+pub fn table_name(path: &str) -> String {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    let stem = file.split_once('.').map_or(file, |(stem, _)| stem);
+    let name: String = stem.chars().map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    if name.is_empty() { "data".to_string() } else { name }
+}
+
+/// A CSV file as SQL that (re)creates a table and fills it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvImport {
+    pub sql: String,
+    /// Column names and their SQLite types
+    pub columns: Vec<(String, &'static str)>,
+    pub rows: usize,
+}
+
+impl CsvImport {
+    fn summary(&self, table: &str, path: &str) -> String {
+        let columns: Vec<String> = self.columns.iter().map(|(name, ty)| format!("{} {}", name, ty)).collect();
+        format!(
+            "Loaded {} row{} from {} into table \"{}\" ({})",
+            self.rows,
+            if self.rows == 1 { "" } else { "s" },
+            path,
+            table,
+            columns.join(", ")
+        )
+    }
+}
+
+/// SQL that replaces `table` with the records of `text`. The first record
+/// names the columns; a column whose cells are all integers is INTEGER,
+/// all numbers REAL, and anything else TEXT. Empty cells are NULL.
+pub fn csv_to_sql(table: &str, text: &str, delimiter: char) -> Result<CsvImport> {
+    let mut records = parse_csv(text, delimiter).into_iter();
+    let Some(header) = records.next() else {
+        return Err(invalid("the CSV file is empty"));
+    };
+    let mut names: Vec<String> = Vec::new();
+    for (i, name) in header.iter().enumerate() {
+        let name = name.trim();
+        let base = if name.is_empty() { format!("column{}", i + 1) } else { name.to_string() };
+        let mut unique = base.clone();
+        let mut n = 2;
+        while names.iter().any(|existing| existing.eq_ignore_ascii_case(&unique)) {
+            unique = format!("{}_{}", base, n);
+            n += 1;
+        }
+        names.push(unique);
+    }
+    let rows: Vec<Vec<String>> = records
+        .map(|mut record| {
+            record.resize(names.len(), String::new());
+            record
+        })
+        .collect();
+    let types: Vec<&'static str> = (0..names.len())
+        .map(|i| column_type(rows.iter().map(|row| row[i].trim()).filter(|cell| !cell.is_empty())))
+        .collect();
+
+    let table_ident = quote_ident(table);
+    let columns: Vec<String> = names.iter().zip(&types).map(|(name, ty)| format!("{} {}", quote_ident(name), ty)).collect();
+    let mut sql = format!(
+        "BEGIN;\nDROP TABLE IF EXISTS {table};\nCREATE TABLE {table} ({columns});\n",
+        table = table_ident,
+        columns = columns.join(", ")
+    );
+    for batch in rows.chunks(INSERT_BATCH) {
+        let values: Vec<String> = batch
+            .iter()
+            .map(|row| {
+                let cells: Vec<String> = row.iter().zip(&types).map(|(cell, ty)| literal(cell.trim(), ty)).collect();
+                format!("({})", cells.join(", "))
+            })
+            .collect();
+        sql.push_str(&format!("INSERT INTO {} VALUES\n{};\n", table_ident, values.join(",\n")));
+    }
+    sql.push_str("COMMIT;\n");
+    Ok(CsvImport { sql, columns: names.into_iter().zip(types).collect(), rows: rows.len() })
+}
+
+/// The records of a CSV text. Fields may be quoted with `"`, with `""`
+/// for a quote inside; quoted fields may span lines. Blank lines are
+/// skipped.
+pub fn parse_csv(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                if record.len() > 1 || !record[0].trim().is_empty() {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// The SQLite type for cells: INTEGER, REAL or TEXT
+fn column_type<'a>(cells: impl Iterator<Item = &'a str>) -> &'static str {
+    let mut ty = "INTEGER";
+    for cell in cells {
+        let numeric = cell.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+        // A leading zero (an id or postcode) would be lost as a number
+        let padded = cell.len() > 1 && cell.starts_with('0') && !cell.starts_with("0.");
+        if !numeric || padded {
+            return "TEXT";
+        }
+        if cell.parse::<i64>().is_err() {
+            if cell.parse::<f64>().is_err() {
+                return "TEXT";
+            }
+            ty = "REAL";
+        }
+    }
+    ty
+}
+
+fn literal(cell: &str, ty: &str) -> String {
+    match ty {
+        _ if cell.is_empty() => "NULL".to_string(),
+        "TEXT" => format!("'{}'", cell.replace('\'', "''")),
+        _ => cell.to_string(),
+    }
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Rows as a markdown table, cut off after `max_rows`
+pub fn format_rows(rows: &SqlRows, max_rows: usize) -> String {
+    let escape = |text: &str| text.replace('|', "\\|").replace(['\n', '\r'], " ");
+    let mut lines = vec![
+        format!("| {} |", rows.columns.iter().map(|c| escape(c)).collect::<Vec<_>>().join(" | ")),
+        format!("|{}|", vec!["---"; rows.columns.len()].join("|")),
+    ];
+    for row in rows.values.iter().take(max_rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|value| {
+                let text = match value {
+                    serde_json::Value::Null => "NULL".to_string(),
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                let mut cell: String = text.chars().take(MAX_CELL_CHARS).collect();
+                if cell.len() < text.len() {
+                    cell.push('…');
+                }
+                escape(&cell)
+            })
+            .collect();
+        lines.push(format!("| {} |", cells.join(" | ")));
+    }
+    let total = rows.values.len();
+    lines.push(if total > max_rows {
+        format!("(showing {} of {} rows)", max_rows, total)
+    } else {
+        format!("({} row{})", total, if total == 1 { "" } else { "s" })
+    });
+    lines.join("\n")
+}
@@ -0,0 +1,76 @@
+//! Workspace templates — starter files for a new workspace.
+//!
+//! Template files are embedded from `templates/<id>/` at build time.
+//! Files whose names would confuse tooling in this repo are stored under a
+//! different name (e.g. `Cargo.toml.in`) and mapped back here.
+
+use agent_types::Result;
+
+use crate::ports::VfsPort;
+
+#[derive(Debug, PartialEq)]
+pub struct WorkspaceTemplate {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// (path relative to the workspace root, contents)
+    pub files: &'static [(&'static str, &'static str)],
+}
+
+pub const TEMPLATES: &[WorkspaceTemplate] = &[
+    WorkspaceTemplate {
+        id: "empty",
+        name: "Empty",
+        description: "Just the default folders",
+        files: &[],
+    },
+    WorkspaceTemplate {
+        id: "rust",
+        name: "Rust crate",
+        description: "Cargo binary crate with src/main.rs",
+        files: &[
+            ("Cargo.toml", include_str!("../templates/rust/Cargo.toml.in")),
+            ("src/main.rs", include_str!("../templates/rust/src/main.rs")),
+            (".gitignore", include_str!("../templates/rust/gitignore")),
+            ("README.md", include_str!("../templates/rust/README.md")),
+        ],
+    },
+    WorkspaceTemplate {
+        id: "python",
+        name: "Python script",
+        description: "main.py with a requirements.txt",
+        files: &[
+            ("main.py", include_str!("../templates/python/main.py")),
+            ("requirements.txt", include_str!("../templates/python/requirements.txt")),
+            ("README.md", include_str!("../templates/python/README.md")),
+        ],
+    },
+    WorkspaceTemplate {
+        id: "website",
+        name: "Static website",
+        description: "index.html, style.css and script.js",
+        files: &[
+            ("index.html", include_str!("../templates/website/index.html")),
+            ("style.css", include_str!("../templates/website/style.css")),
+            ("script.js", include_str!("../templates/website/script.js")),
+            ("README.md", include_str!("../templates/website/README.md")),
+        ],
+    },
+];
+
+impl WorkspaceTemplate {
+    pub fn find(id: &str) -> Option<&'static WorkspaceTemplate> {
+        TEMPLATES.iter().find(|t| t.id == id)
+    }
+
+    /// Write the template's files under `root`. Existing files are
+    /// overwritten. Returns the number of files written.
+    pub async fn apply(&self, vfs: &dyn VfsPort, root: &str) -> Result<usize> {
+        let root = root.trim_end_matches('/');
+        for (path, contents) in self.files {
+            vfs.write_file(&format!("{}/{}", root, path), contents.as_bytes())
+                .await?;
+        }
+        Ok(self.files.len())
+    }
+}
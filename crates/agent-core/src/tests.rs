@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::event_bus::EventBus;
     use crate::tools::{ToolRegistry, parse_tool_args};
@@ -65,6 +66,86 @@ mod tests {
         assert_eq!(events.len(), 100);
     }
 
+    #[test]
+    fn test_event_bus_subscribe_sees_events_without_draining() {
+        let bus = EventBus::new();
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let seen_clone = seen.clone();
+        bus.subscribe(move |e| {
+            if let AgentEvent::TurnEnd { turn_id } = e {
+                seen_clone.borrow_mut().push(*turn_id);
+            }
+        });
+
+        bus.emit(AgentEvent::TurnStart { turn_id: 7 });
+        bus.emit(AgentEvent::TurnEnd { turn_id: 7 });
+
+        assert_eq!(*seen.borrow(), vec![7]);
+        assert_eq!(bus.drain().len(), 2);
+    }
+
+    #[test]
+    fn test_event_bus_listener_may_emit() {
+        let bus = EventBus::new();
+        let bus_clone = bus.clone();
+        bus.subscribe(move |e| {
+            if matches!(e, AgentEvent::TurnStart { .. }) {
+                bus_clone.emit(AgentEvent::LlmDelta { token: "nested".to_string() });
+            }
+        });
+
+        bus.emit(AgentEvent::TurnStart { turn_id: 1 });
+        assert_eq!(bus.drain().len(), 2);
+    }
+
+    #[test]
+    fn test_event_bus_coalesces_large_stream() {
+        let bus = EventBus::with_delta_coalescing(64);
+        let seen = std::rc::Rc::new(std::cell::Cell::new(0));
+        let seen_clone = seen.clone();
+        bus.subscribe(move |_| seen_clone.set(seen_clone.get() + 1));
+
+        let mut expected = String::new();
+        for i in 0..10_000 {
+            let token = format!("t{} ", i % 10);
+            expected.push_str(&token);
+            bus.emit(AgentEvent::LlmDelta { token });
+        }
+        let events = bus.drain();
+        // 30 000 bytes in chunks of at least 64 bytes
+        assert!(events.len() <= 30_000 / 64 + 1, "{} events", events.len());
+        assert_eq!(seen.get(), 10_000);
+        let text: String = events
+            .into_iter()
+            .map(|e| match e {
+                AgentEvent::LlmDelta { token } => token,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn test_event_bus_coalescing_keeps_event_order() {
+        let bus = EventBus::with_delta_coalescing(1024);
+        bus.emit(AgentEvent::LlmDelta { token: "a".to_string() });
+        bus.emit(AgentEvent::LlmDelta { token: "b".to_string() });
+        bus.emit(AgentEvent::ToolExecStart {
+            call_id: "1".to_string(),
+            tool_name: "bash".to_string(),
+            arguments: "{}".to_string(),
+        });
+        bus.emit(AgentEvent::LlmDelta { token: "c".to_string() });
+        let events = bus.drain();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], AgentEvent::LlmDelta { token } if token == "ab"));
+        assert!(matches!(&events[2], AgentEvent::LlmDelta { token } if token == "c"));
+
+        // Nothing is merged into text the UI has already drained
+        bus.emit(AgentEvent::LlmDelta { token: "d".to_string() });
+        assert_eq!(bus.drain().len(), 1);
+    }
+
     // ─── ToolRegistry Tests ──────────────────────────────────
 
     #[test]
@@ -113,6 +194,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_builtin_tools_are_strict_with_constraints() {
+        let registry = ToolRegistry::new();
+        assert!(registry.definitions().iter().all(|t| t.strict));
+        let bash = registry.get("bash").unwrap();
+        assert_eq!(bash.parameters.properties["timeout_ms"]["minimum"], 1.0);
+        assert_eq!(bash.parameters.required, vec!["command"]);
+    }
+
     // ─── parse_tool_args Tests ───────────────────────────────
 
     #[test]
@@ -169,6 +259,44 @@ mod tests {
         assert_eq!(runtime.state, AgentState::Idle);
     }
 
+    #[test]
+    fn test_runtime_restore_history() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        runtime.restore(vec![
+            Message::system("stale prompt"),
+            Message::user("hello"),
+            Message::assistant("hi"),
+        ]);
+
+        assert_eq!(runtime.messages.len(), 3);
+        assert_eq!(runtime.messages[0].content.as_text(), AgentConfig::default().system_prompt);
+        assert_eq!(runtime.history().len(), 2);
+        assert_eq!(runtime.history()[0].content.as_text(), "hello");
+    }
+
+    #[test]
+    fn test_runtime_session_overrides() {
+        let overrides = agent_types::session::SessionConfig {
+            system_prompt: Some("You only answer in haiku.".to_string()),
+            model: Some("gpt-4o".to_string()),
+            temperature: Some(0.1),
+            persona: None,
+        };
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        runtime.restore(vec![Message::user("hello"), Message::assistant("hi")]);
+        let config = overrides.apply(&runtime.config);
+        runtime.config.llm = config.llm;
+        runtime.set_system_prompt(&config.system_prompt);
+        assert_eq!(runtime.history().len(), 2);
+
+        let llm = MockLlmRecorder::new();
+        block_on(runtime.run_turn("Write about rust", &llm, &MockShell, &MockVfs::new())).unwrap();
+        let requests = llm.requests.borrow();
+        assert_eq!(requests[0].model, "gpt-4o");
+        assert_eq!(requests[0].temperature, 0.1);
+        assert_eq!(requests[0].messages[0].content.as_text(), "You only answer in haiku.");
+    }
+
     #[test]
     fn test_agent_state_eq() {
         assert_eq!(AgentState::Idle, AgentState::Idle);
@@ -233,6 +361,8 @@ mod tests {
                                 arguments: r#"{"command":"echo test"}"#.to_string(),
                             },
                         }],
+                        reasoning: None,
+                        meta: None,
                     },
                     usage: None,
                 })
@@ -262,17 +392,20 @@ mod tests {
 
     #[async_trait(?Send)]
     impl ShellPort for MockShell {
-        async fn execute(&self, cmd: &str, _timeout_ms: Option<u64>) -> agent_types::Result<ExecResult> {
+        async fn execute(&self, cmd: &str, opts: &ExecOptions) -> agent_types::Result<ExecResult> {
+            let cwd = opts.cwd.as_ref().map(|dir| format!(" (in {})", dir)).unwrap_or_default();
             Ok(ExecResult {
-                stdout: format!("mock output for: {}", cmd),
+                stdout: format!("mock output for: {}{}", cmd, cwd),
                 stderr: String::new(),
                 exit_code: 0,
+                duration_ms: 0,
             })
         }
 
         fn execute_streaming(
             &self,
             _cmd: &str,
+            _opts: &ExecOptions,
         ) -> Pin<Box<dyn Stream<Item = ShellStreamEvent>>> {
             Box::pin(futures::stream::empty())
         }
@@ -284,6 +417,20 @@ mod tests {
         fn is_ready(&self) -> bool {
             true
         }
+
+        async fn execute_with_stdin(
+            &self,
+            cmd: &str,
+            stdin: &str,
+            _opts: &ExecOptions,
+        ) -> agent_types::Result<ExecResult> {
+            Ok(ExecResult {
+                stdout: format!("mock output for: {} <<< {}", cmd, stdin),
+                stderr: String::new(),
+                exit_code: 0,
+                duration_ms: 0,
+            })
+        }
     }
 
     /// Mock VFS
@@ -322,12 +469,22 @@ mod tests {
             Ok(())
         }
 
-        async fn list_dir(&self, _path: &str) -> agent_types::Result<Vec<DirEntry>> {
-            Ok(vec![DirEntry {
-                name: "test.txt".to_string(),
-                is_dir: false,
-                size: 100,
-            }])
+        async fn list_dir(&self, path: &str) -> agent_types::Result<Vec<DirEntry>> {
+            // Direct children of `path`; directories are implied by the
+            // paths of the files in them
+            let prefix = format!("{}/", path.trim_end_matches('/'));
+            let mut entries: Vec<DirEntry> = Vec::new();
+            for (p, data) in self.files.borrow().iter() {
+                let Some(name) = p.strip_prefix(&prefix) else { continue };
+                let entry = match name.split_once('/') {
+                    Some((dir, _)) => DirEntry { name: dir.to_string(), is_dir: true, size: 0, content_type: None },
+                    None => DirEntry { name: name.to_string(), is_dir: false, size: data.len() as u64, content_type: None },
+                };
+                if !entries.iter().any(|e| e.name == entry.name) {
+                    entries.push(entry);
+                }
+            }
+            Ok(entries)
         }
 
         async fn stat(&self, path: &str) -> agent_types::Result<FileStat> {
@@ -336,6 +493,7 @@ mod tests {
                     size: self.files.borrow()[path].len() as u64,
                     is_dir: false,
                     modified: None,
+                    content_type: None,
                 })
             } else {
                 Err(agent_types::AgentError::Fs {
@@ -408,6 +566,36 @@ mod tests {
         assert!(events.len() >= 2); // TurnStart + LlmComplete + TurnEnd
     }
 
+    #[test]
+    fn test_agent_loop_stamps_message_meta() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let llm = MockLlmWithToolCall {
+            call_count: std::cell::RefCell::new(0),
+        };
+        block_on(runtime.run_turn("List files", &llm, &MockShell, &MockVfs::new())).unwrap();
+
+        // user, assistant (tool call), tool result, assistant
+        let history = runtime.history();
+        assert!(history.iter().all(|m| m.meta.is_some()));
+        let ids: std::collections::HashSet<&str> = history.iter().filter_map(|m| m.id()).collect();
+        assert_eq!(ids.len(), history.len());
+
+        let answer = history[3].meta.as_ref().unwrap();
+        assert_eq!(answer.model.as_deref(), Some(AgentConfig::default().llm.model.as_str()));
+        assert!(history[0].meta.as_ref().unwrap().model.is_none());
+    }
+
+    #[test]
+    fn test_agent_loop_records_usage() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let llm = MockLlm {
+            response_text: "Hi".to_string(),
+        };
+        block_on(runtime.run_turn("Hi", &llm, &MockShell, &MockVfs::new())).unwrap();
+        let usage = runtime.messages[2].meta.as_ref().unwrap().usage.clone().unwrap();
+        assert_eq!(usage.total_tokens, 15);
+    }
+
     #[test]
     fn test_agent_loop_with_tool_call() {
         let bus = EventBus::new();
@@ -496,6 +684,28 @@ mod tests {
         let events = bus.drain();
         let has_error = events.iter().any(|e| matches!(e, AgentEvent::Error { .. }));
         assert!(has_error, "Missing Error event");
+        let reported = events.iter().any(|e| matches!(e, AgentEvent::LlmCallEnd { error: Some(_), .. }));
+        assert!(reported, "Missing LlmCallEnd event");
+    }
+
+    #[test]
+    fn test_withdraw_unanswered_message() {
+        let bus = EventBus::new();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), bus);
+        let shell = MockShell;
+        let vfs = MockVfs::new();
+
+        assert!(block_on(runtime.run_turn("Hi", &MockLlmError, &shell, &vfs)).is_err());
+        assert!(runtime.withdraw_unanswered());
+        assert!(runtime.history().is_empty());
+        assert_eq!(runtime.state, AgentState::Idle);
+        assert!(!runtime.withdraw_unanswered());
+
+        // An answered message stays
+        let llm = MockLlm { response_text: "Hello!".to_string() };
+        block_on(runtime.run_turn("Hi", &llm, &shell, &vfs)).unwrap();
+        assert!(!runtime.withdraw_unanswered());
+        assert_eq!(runtime.history().len(), 2);
     }
 
     // ─── Mock VFS Operation Tests ────────────────────────────
@@ -529,4 +739,2901 @@ mod tests {
             assert!(!vfs.exists("/test.txt").await.unwrap());
         });
     }
+
+    // ─── Retrieval Tests ─────────────────────────────────────
+
+    use crate::retrieval::{INDEX_KEY, WorkspaceRetriever, chunk_text, cosine_similarity};
+    use std::rc::Rc;
+
+    /// Mock embeddings: hashed bag-of-words, so texts sharing words are similar
+    struct MockEmbeddings;
+
+    #[async_trait(?Send)]
+    impl EmbeddingsPort for MockEmbeddings {
+        async fn embed(&self, texts: &[String]) -> agent_types::Result<Vec<Vec<f32>>> {
+            Ok(texts
+                .iter()
+                .map(|t| {
+                    let mut v = vec![0.0; 64];
+                    for word in t.split_whitespace() {
+                        let h = word.to_lowercase().bytes().fold(7usize, |h, b| h * 31 + b as usize);
+                        v[h % 64] += 1.0;
+                    }
+                    v
+                })
+                .collect())
+        }
+
+        fn model_name(&self) -> &str {
+            "mock-embeddings"
+        }
+    }
+
+    /// Mock key-value storage
+    struct MockStorage {
+        data: std::cell::RefCell<std::collections::HashMap<String, Vec<u8>>>,
+    }
+
+    impl MockStorage {
+        fn new() -> Self {
+            Self {
+                data: std::cell::RefCell::new(std::collections::HashMap::new()),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl StoragePort for MockStorage {
+        async fn get(&self, key: &str) -> agent_types::Result<Option<Vec<u8>>> {
+            Ok(self.data.borrow().get(key).cloned())
+        }
+
+        async fn set(&self, key: &str, value: &[u8]) -> agent_types::Result<()> {
+            self.data.borrow_mut().insert(key.to_string(), value.to_vec());
+            Ok(())
+        }
+
+        async fn delete(&self, key: &str) -> agent_types::Result<()> {
+            self.data.borrow_mut().remove(key);
+            Ok(())
+        }
+
+        async fn list_keys(&self, prefix: &str) -> agent_types::Result<Vec<String>> {
+            Ok(self.data.borrow().keys().filter(|k| k.starts_with(prefix)).cloned().collect())
+        }
+
+        fn backend_name(&self) -> &str {
+            "mock"
+        }
+    }
+
+    /// Mock LLM that records every request, optionally makes one tool call,
+    /// and otherwise answers with plain text
+    struct MockLlmRecorder {
+        requests: std::cell::RefCell<Vec<ChatRequest>>,
+        tool_call: std::cell::RefCell<Option<(String, String)>>,
+    }
+
+    impl MockLlmRecorder {
+        fn new() -> Self {
+            Self {
+                requests: std::cell::RefCell::new(Vec::new()),
+                tool_call: std::cell::RefCell::new(None),
+            }
+        }
+
+        fn calling(name: &str, arguments: &str) -> Self {
+            let llm = Self::new();
+            *llm.tool_call.borrow_mut() = Some((name.to_string(), arguments.to_string()));
+            llm
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl LlmPort for MockLlmRecorder {
+        async fn chat_completion(&self, req: ChatRequest) -> agent_types::Result<ChatResponse> {
+            self.requests.borrow_mut().push(req);
+            if let Some((name, arguments)) = self.tool_call.borrow_mut().take() {
+                return Ok(ChatResponse {
+                    message: Message {
+                        role: Role::Assistant,
+                        content: MessageContent::Text(String::new()),
+                        tool_call_id: None,
+                        tool_calls: vec![ToolCallRequest {
+                            id: "call_1".to_string(),
+                            function: FunctionCall { name, arguments },
+                        }],
+                        reasoning: None,
+                        meta: None,
+                    },
+                    usage: None,
+                });
+            }
+            Ok(ChatResponse {
+                message: Message::assistant("ok"),
+                usage: None,
+            })
+        }
+
+        fn stream_chat(
+            &self,
+            _req: ChatRequest,
+        ) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+            Box::pin(futures::stream::once(async { LlmStreamEvent::Done }))
+        }
+
+        async fn list_models(&self) -> agent_types::Result<Vec<String>> {
+            Ok(vec![])
+        }
+    }
+
+    fn workspace_vfs() -> MockVfs {
+        let vfs = MockVfs::new();
+        block_on(async {
+            vfs.write_file("/ws/build.md", b"Run cargo build to compile the rust crate").await.unwrap();
+            vfs.write_file("/ws/pets.md", b"The cat sleeps on the warm sofa all day").await.unwrap();
+            vfs.write_file("/ws/logo.png", &[0x89, 0x50, 0x00, 0x47]).await.unwrap();
+        });
+        vfs
+    }
+
+    #[test]
+    fn test_chunk_text_short() {
+        let chunks = chunk_text("one\ntwo\nthree");
+        assert_eq!(chunks, vec![(1, 3, "one\ntwo\nthree".to_string())]);
+    }
+
+    #[test]
+    fn test_chunk_text_overlapping_windows() {
+        let text: Vec<String> = (1..=100).map(|i| format!("line {}", i)).collect();
+        let chunks = chunk_text(&text.join("\n"));
+        let ranges: Vec<(usize, usize)> = chunks.iter().map(|c| (c.0, c.1)).collect();
+        assert_eq!(ranges, vec![(1, 40), (36, 75), (71, 100)]);
+        assert!(chunks[1].2.starts_with("line 36"));
+    }
+
+    #[test]
+    fn test_chunk_text_skips_blank() {
+        assert!(chunk_text("").is_empty());
+        assert!(chunk_text("\n   \n").is_empty());
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 2.0], &[1.0, 2.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_retriever_search_ranks_relevant_file() {
+        let vfs = workspace_vfs();
+        let storage = Rc::new(MockStorage::new());
+        let retriever = WorkspaceRetriever::new(Rc::new(MockEmbeddings), storage.clone(), "/ws");
+
+        let hits = block_on(retriever.search(&vfs, "how to build the rust crate", 5)).unwrap();
+        // The binary file is not indexed
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].chunk.path, "/ws/build.md");
+        assert!(hits[0].score > hits[1].score);
+        assert!(storage.data.borrow().contains_key(INDEX_KEY));
+    }
+
+    #[test]
+    fn test_retriever_refresh_is_incremental() {
+        let vfs = workspace_vfs();
+        let storage = Rc::new(MockStorage::new());
+        let retriever = WorkspaceRetriever::new(Rc::new(MockEmbeddings), storage.clone(), "/ws");
+
+        block_on(async {
+            assert_eq!(retriever.refresh(&vfs).await.unwrap(), 2);
+            assert_eq!(retriever.refresh(&vfs).await.unwrap(), 0);
+
+            vfs.write_file("/ws/pets.md", b"The dog barks").await.unwrap();
+            assert_eq!(retriever.refresh(&vfs).await.unwrap(), 1);
+
+            vfs.delete_file("/ws/pets.md").await.unwrap();
+            retriever.refresh(&vfs).await.unwrap();
+            let hits = retriever.search(&vfs, "dog", 5).await.unwrap();
+            assert!(hits.iter().all(|h| h.chunk.path != "/ws/pets.md"));
+        });
+
+        // A fresh retriever picks up the stored index instead of re-embedding
+        let reloaded = WorkspaceRetriever::new(Rc::new(MockEmbeddings), storage, "/ws");
+        assert_eq!(block_on(reloaded.refresh(&vfs)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_retriever_reports_files_checked() {
+        let vfs = workspace_vfs();
+        let retriever = WorkspaceRetriever::new(Rc::new(MockEmbeddings), Rc::new(MockStorage::new()), "/ws");
+        let seen = std::cell::RefCell::new(Vec::new());
+        block_on(retriever.refresh_with_progress(&vfs, &|checked, total| seen.borrow_mut().push((checked, total))))
+            .unwrap();
+        let seen = seen.into_inner();
+        let total = seen[0].1;
+        assert!(total > 0);
+        assert_eq!(seen.first(), Some(&(0, total)));
+        assert_eq!(seen.last(), Some(&(total, total)));
+        assert_eq!(seen.len(), total + 1);
+    }
+
+    /// VFS that reports a modification time per write, counts reads, and
+    /// fails to read the paths in `unreadable`
+    struct StampedVfs {
+        inner: MockVfs,
+        writes: std::cell::RefCell<std::collections::HashMap<String, u32>>,
+        reads: std::cell::Cell<usize>,
+        unreadable: std::cell::RefCell<Vec<String>>,
+    }
+
+    #[async_trait(?Send)]
+    impl VfsPort for StampedVfs {
+        async fn read_file(&self, path: &str) -> agent_types::Result<Vec<u8>> {
+            self.reads.set(self.reads.get() + 1);
+            if self.unreadable.borrow().iter().any(|p| p == path) {
+                return Err(agent_types::AgentError::Fs { path: path.to_string(), message: "locked".to_string() });
+            }
+            self.inner.read_file(path).await
+        }
+
+        async fn write_file(&self, path: &str, data: &[u8]) -> agent_types::Result<()> {
+            *self.writes.borrow_mut().entry(path.to_string()).or_default() += 1;
+            self.inner.write_file(path, data).await
+        }
+
+        async fn delete_file(&self, path: &str) -> agent_types::Result<()> {
+            self.inner.delete_file(path).await
+        }
+
+        async fn list_dir(&self, path: &str) -> agent_types::Result<Vec<DirEntry>> {
+            self.inner.list_dir(path).await
+        }
+
+        async fn stat(&self, path: &str) -> agent_types::Result<FileStat> {
+            let mut stat = self.inner.stat(path).await?;
+            stat.modified = self.writes.borrow().get(path).map(|n| format!("write {}", n));
+            Ok(stat)
+        }
+
+        async fn mkdir(&self, path: &str) -> agent_types::Result<()> {
+            self.inner.mkdir(path).await
+        }
+
+        async fn exists(&self, path: &str) -> agent_types::Result<bool> {
+            self.inner.exists(path).await
+        }
+    }
+
+    #[test]
+    fn test_retriever_refresh_skips_unchanged_and_unreadable_files() {
+        let vfs = StampedVfs {
+            inner: MockVfs::new(),
+            writes: Default::default(),
+            reads: Default::default(),
+            unreadable: Default::default(),
+        };
+        let retriever = WorkspaceRetriever::new(Rc::new(MockEmbeddings), Rc::new(MockStorage::new()), "/ws");
+
+        block_on(async {
+            vfs.write_file("/ws/build.md", b"Run cargo build to compile the rust crate").await.unwrap();
+            vfs.write_file("/ws/pets.md", b"The cat sleeps on the warm sofa all day").await.unwrap();
+            vfs.write_file("/ws/logo.png", &[0x89, 0x50, 0x00, 0x47]).await.unwrap();
+            assert_eq!(retriever.refresh(&vfs).await.unwrap(), 2);
+            assert_eq!(vfs.reads.get(), 3);
+
+            // Nothing changed, so nothing is read again
+            assert_eq!(retriever.refresh(&vfs).await.unwrap(), 0);
+            assert_eq!(vfs.reads.get(), 3);
+
+            vfs.write_file("/ws/pets.md", b"The dog barks").await.unwrap();
+            assert_eq!(retriever.refresh(&vfs).await.unwrap(), 1);
+            assert_eq!(vfs.reads.get(), 4);
+
+            // An unreadable file is skipped, keeping what was indexed for it
+            vfs.write_file("/ws/build.md", b"Run cargo test").await.unwrap();
+            vfs.write_file("/ws/notes.md", b"Some notes").await.unwrap();
+            vfs.unreadable.borrow_mut().push("/ws/build.md".to_string());
+            assert_eq!(retriever.refresh(&vfs).await.unwrap(), 1);
+            let hits = retriever.search(&vfs, "cargo build", 5).await.unwrap();
+            assert!(hits.iter().any(|h| h.chunk.path == "/ws/build.md"));
+
+            // and read again once it can be
+            vfs.unreadable.borrow_mut().clear();
+            assert_eq!(retriever.refresh(&vfs).await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn test_runtime_set_retriever_registers_tool() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        assert!(runtime.tools.get("search_workspace").is_none());
+
+        let retriever = WorkspaceRetriever::new(Rc::new(MockEmbeddings), Rc::new(MockStorage::new()), "/ws");
+        runtime.set_retriever(Some(Rc::new(retriever)));
+        assert!(runtime.tools.get("search_workspace").is_some());
+
+        runtime.set_retriever(None);
+        assert!(runtime.tools.get("search_workspace").is_none());
+    }
+
+    #[test]
+    fn test_runtime_injects_retrieved_context() {
+        let mut config = AgentConfig::default();
+        config.retrieval.min_score = 0.3;
+        let mut runtime = AgentRuntime::new(config, EventBus::new());
+        let retriever = WorkspaceRetriever::new(Rc::new(MockEmbeddings), Rc::new(MockStorage::new()), "/ws");
+        runtime.set_retriever(Some(Rc::new(retriever)));
+
+        let llm = MockLlmRecorder::new();
+        let vfs = workspace_vfs();
+        block_on(runtime.run_turn("cargo build the rust crate", &llm, &MockShell, &vfs)).unwrap();
+
+        let requests = llm.requests.borrow();
+        let sent = &requests[0].messages;
+        // system prompt, injected context, user message
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[1].role, Role::System);
+        assert!(sent[1].content.as_text().contains("/ws/build.md"));
+        assert!(!sent[1].content.as_text().contains("/ws/pets.md"));
+        assert_eq!(sent[2].role, Role::User);
+
+        // The context is not kept in the conversation history
+        assert_eq!(runtime.history().len(), 2);
+    }
+
+    // ─── Memory Tests ────────────────────────────────────────
+
+    use crate::memory::{MemoryStore, format_memories, summarize_conversation};
+
+    #[test]
+    fn test_memory_remember_and_list() {
+        let store = MemoryStore::new(Rc::new(MockStorage::new()));
+        block_on(async {
+            let m = store.remember("  User prefers tabs  ", vec!["style".to_string()]).await.unwrap();
+            assert_eq!(m.fact, "User prefers tabs");
+            let all = store.list().await.unwrap();
+            assert_eq!(all, vec![m.clone()]);
+
+            store.forget(&m.id).await.unwrap();
+            assert!(store.list().await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_memory_recall_ranks_by_keywords() {
+        let store = MemoryStore::new(Rc::new(MockStorage::new()));
+        block_on(async {
+            store.remember("The project uses Rust and egui", vec![]).await.unwrap();
+            store.remember("Deploy with build.sh", vec!["rust".to_string()]).await.unwrap();
+            store.remember("User's name is Sam", vec![]).await.unwrap();
+
+            let found = store.recall("How do I build Rust code?", 10).await.unwrap();
+            assert_eq!(found.len(), 2);
+            // Tag match (2) + "build" (1) beats a single fact word
+            assert_eq!(found[0].fact, "Deploy with build.sh");
+
+            assert!(store.recall("weather", 10).await.unwrap().is_empty());
+            assert_eq!(store.recall("rust", 1).await.unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_format_memories() {
+        let a = agent_types::memory::Memory::new("likes tea", vec![]);
+        let b = agent_types::memory::Memory::new("uses vim", vec!["editor".to_string()]);
+        assert_eq!(format_memories(&[a, b]), "- likes tea\n- uses vim [editor]");
+    }
+
+    #[test]
+    fn test_runtime_set_memory_registers_tools() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        runtime.set_memory(Some(Rc::new(MemoryStore::new(Rc::new(MockStorage::new())))));
+        assert!(runtime.tools.get("remember").is_some());
+        assert!(runtime.tools.get("recall").is_some());
+
+        runtime.set_memory(None);
+        assert!(runtime.tools.get("remember").is_none());
+        assert!(runtime.tools.get("recall").is_none());
+    }
+
+    #[test]
+    fn test_runtime_remember_tool_persists_and_injects() {
+        let storage = Rc::new(MockStorage::new());
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        runtime.set_memory(Some(Rc::new(MemoryStore::new(storage.clone()))));
+        let vfs = MockVfs::new();
+
+        let llm = MockLlmRecorder::calling("remember", r#"{"fact":"User likes short answers","tags":["style"]}"#);
+        block_on(runtime.run_turn("Keep it short from now on", &llm, &MockShell, &vfs)).unwrap();
+        assert!(runtime.messages[3].content.as_text().starts_with("Remembered"));
+        // Nothing was remembered yet when the first request went out
+        assert!(!llm.requests.borrow()[0].messages[0].content.as_text().contains("short answers"));
+
+        // A new session sees the memory in its system prompt
+        let mut next = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        next.set_memory(Some(Rc::new(MemoryStore::new(storage))));
+        let llm = MockLlmRecorder::new();
+        block_on(next.run_turn("Hi", &llm, &MockShell, &vfs)).unwrap();
+        let prompt = llm.requests.borrow()[0].messages[0].content.as_text().to_string();
+        assert!(prompt.contains("- User likes short answers [style]"));
+        // The stored conversation keeps the plain system prompt
+        assert!(!next.messages[0].content.as_text().contains("short answers"));
+    }
+
+    #[test]
+    fn test_runtime_memory_injection_can_be_disabled() {
+        let storage = Rc::new(MockStorage::new());
+        let store = MemoryStore::new(storage);
+        block_on(store.remember("secret fact", vec![])).unwrap();
+
+        let mut config = AgentConfig::default();
+        config.memory.inject_into_prompt = false;
+        let mut runtime = AgentRuntime::new(config, EventBus::new());
+        runtime.set_memory(Some(Rc::new(store)));
+
+        let llm = MockLlmRecorder::new();
+        block_on(runtime.run_turn("Hi", &llm, &MockShell, &MockVfs::new())).unwrap();
+        assert!(!llm.requests.borrow()[0].messages[0].content.as_text().contains("secret fact"));
+    }
+
+    #[test]
+    fn test_summarize_conversation_sends_transcript_only() {
+        let llm = MockLlmRecorder::new();
+        let history = vec![
+            Message::user("Make a todo app"),
+            Message {
+                role: Role::Assistant,
+                content: MessageContent::Text(String::new()),
+                tool_call_id: None,
+                tool_calls: vec![ToolCallRequest {
+                    id: "call_1".to_string(),
+                    function: FunctionCall {
+                        name: "write_file".to_string(),
+                        arguments: "{}".to_string(),
+                    },
+                }],
+                reasoning: None,
+                meta: None,
+            },
+            Message::tool_result("call_1", "Written 10 bytes"),
+            Message::assistant("Created /workspace/todo.html"),
+        ];
+
+        let summary = block_on(summarize_conversation(&llm, &AgentConfig::default().llm, &history)).unwrap();
+        assert_eq!(summary, "ok");
+
+        let requests = llm.requests.borrow();
+        assert!(requests[0].tools.is_empty());
+        assert_eq!(requests[0].messages.len(), 2);
+        assert_eq!(
+            requests[0].messages[1].content.as_text(),
+            "User: Make a todo app\n\nAssistant: Created /workspace/todo.html"
+        );
+    }
+
+    // ─── Model Catalog Tests ─────────────────────────────────
+
+    use crate::models::{MODEL_LIST_TTL_SECS, ModelCatalog};
+    use agent_types::models::ModelList;
+
+    /// Mock LLM whose model listing fails when `models` is None
+    struct MockModelLister {
+        models: Option<Vec<String>>,
+        calls: std::cell::Cell<usize>,
+    }
+
+    impl MockModelLister {
+        fn new(models: Option<Vec<&str>>) -> Self {
+            Self {
+                models: models.map(|m| m.into_iter().map(String::from).collect()),
+                calls: std::cell::Cell::new(0),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl LlmPort for MockModelLister {
+        async fn chat_completion(&self, _req: ChatRequest) -> agent_types::Result<ChatResponse> {
+            Err(agent_types::AgentError::Llm("not used".to_string()))
+        }
+
+        fn stream_chat(
+            &self,
+            _req: ChatRequest,
+        ) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+            Box::pin(futures::stream::once(async { LlmStreamEvent::Done }))
+        }
+
+        async fn list_models(&self) -> agent_types::Result<Vec<String>> {
+            self.calls.set(self.calls.get() + 1);
+            self.models
+                .clone()
+                .ok_or_else(|| agent_types::AgentError::Network("offline".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_model_catalog_fetches_then_uses_cache() {
+        let catalog = ModelCatalog::new(std::rc::Rc::new(MockStorage::new()));
+        let llm = MockModelLister::new(Some(vec!["b-model", "a-model"]));
+        let config = AgentConfig::default().llm;
+
+        assert!(block_on(catalog.cached(&config)).unwrap().is_none());
+        let list = block_on(catalog.get(&llm, &config)).unwrap();
+        assert_eq!(list.models, vec!["a-model", "b-model"]);
+
+        let again = block_on(catalog.get(&llm, &config)).unwrap();
+        assert_eq!(again, list);
+        assert_eq!(llm.calls.get(), 1);
+
+        block_on(catalog.refresh(&llm, &config)).unwrap();
+        assert_eq!(llm.calls.get(), 2);
+    }
+
+    #[test]
+    fn test_model_catalog_keyed_by_provider() {
+        let catalog = ModelCatalog::new(std::rc::Rc::new(MockStorage::new()));
+        let llm = MockModelLister::new(Some(vec!["m"]));
+        let mut config = AgentConfig::default().llm;
+        block_on(catalog.get(&llm, &config)).unwrap();
+
+        config.api_base = Some("http://localhost:1234".to_string());
+        assert!(block_on(catalog.cached(&config)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_model_catalog_refetches_stale_list() {
+        let storage = std::rc::Rc::new(MockStorage::new());
+        let catalog = ModelCatalog::new(storage.clone());
+        let config = AgentConfig::default().llm;
+        block_on(catalog.get(&MockModelLister::new(Some(vec!["old"])), &config)).unwrap();
+
+        // Age the stored list past the TTL
+        let key = storage.data.borrow().keys().next().unwrap().clone();
+        let mut stale: ModelList = serde_json::from_slice(&storage.data.borrow()[&key]).unwrap();
+        stale.fetched_at = "2000-01-01T00:00:00Z".to_string();
+        storage.data.borrow_mut().insert(key, serde_json::to_vec(&stale).unwrap());
+        assert!(stale.is_stale(MODEL_LIST_TTL_SECS));
+
+        let list = block_on(catalog.get(&MockModelLister::new(Some(vec!["new"])), &config)).unwrap();
+        assert_eq!(list.models, vec!["new"]);
+
+        // A failed refetch falls back to the stale list
+        let key = storage.data.borrow().keys().next().unwrap().clone();
+        storage.data.borrow_mut().insert(key, serde_json::to_vec(&stale).unwrap());
+        let list = block_on(catalog.get(&MockModelLister::new(None), &config)).unwrap();
+        assert_eq!(list.models, vec!["old"]);
+    }
+
+    #[test]
+    fn test_model_catalog_error_without_cache() {
+        let catalog = ModelCatalog::new(std::rc::Rc::new(MockStorage::new()));
+        let config = AgentConfig::default().llm;
+        assert!(block_on(catalog.get(&MockModelLister::new(None), &config)).is_err());
+    }
+
+    // ─── Workspace Template Tests ────────────────────────────
+
+    use crate::templates::{TEMPLATES, WorkspaceTemplate};
+
+    #[test]
+    fn test_templates_have_unique_ids() {
+        let ids: Vec<&str> = TEMPLATES.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec!["empty", "rust", "python", "website"]);
+        assert!(WorkspaceTemplate::find("empty").unwrap().files.is_empty());
+        assert!(WorkspaceTemplate::find("missing").is_none());
+    }
+
+    #[test]
+    fn test_template_apply_writes_files() {
+        let vfs = MockVfs::new();
+        let template = WorkspaceTemplate::find("rust").unwrap();
+        let written = block_on(template.apply(&vfs, "/workspace/")).unwrap();
+        assert_eq!(written, template.files.len());
+
+        let manifest = block_on(vfs.read_file("/workspace/Cargo.toml")).unwrap();
+        assert!(String::from_utf8(manifest).unwrap().contains("[package]"));
+        assert!(block_on(vfs.exists("/workspace/src/main.rs")).unwrap());
+        assert!(block_on(vfs.exists("/workspace/.gitignore")).unwrap());
+    }
+
+    // ─── Prompt Library Tests ────────────────────────────────
+
+    use crate::prompts::PromptLibrary;
+    use agent_types::prompt::PromptSnippet;
+
+    #[test]
+    fn test_prompt_library_save_list_delete() {
+        let library = PromptLibrary::new(std::rc::Rc::new(MockStorage::new()));
+        let review = PromptSnippet::new("review", "Review {{file}}");
+        let explain = PromptSnippet::new("Explain", "Explain this");
+        block_on(library.save(&review)).unwrap();
+        block_on(library.save(&explain)).unwrap();
+
+        let names: Vec<String> = block_on(library.list()).unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["Explain", "review"]);
+
+        block_on(library.delete(&review.id)).unwrap();
+        assert_eq!(block_on(library.list()).unwrap(), vec![explain]);
+    }
+
+    // ─── Undo Journal Tests ──────────────────────────────────
+
+    use crate::undo::{MAX_UNDO_ENTRIES, UndoJournal, UndoScope, format_undone};
+
+    #[test]
+    fn test_undo_last_restores_and_removes() {
+        let journal = UndoJournal::new(Rc::new(MockStorage::new()));
+        let vfs = MockVfs::new();
+        block_on(async {
+            vfs.write_file("/ws/a.txt", b"old").await.unwrap();
+            journal.record(&vfs, "t1", "write_file", "/ws/a.txt").await.unwrap();
+            vfs.write_file("/ws/a.txt", b"new").await.unwrap();
+            journal.record(&vfs, "t1", "write_file", "/ws/b.txt").await.unwrap();
+            vfs.write_file("/ws/b.txt", b"created").await.unwrap();
+        });
+
+        let undone = block_on(journal.undo(&vfs, UndoScope::Last)).unwrap();
+        assert_eq!(format_undone(&undone), "Removed /ws/b.txt");
+        assert!(!block_on(vfs.exists("/ws/b.txt")).unwrap());
+        assert_eq!(block_on(vfs.read_file("/ws/a.txt")).unwrap(), b"new");
+
+        let undone = block_on(journal.undo(&vfs, UndoScope::Last)).unwrap();
+        assert_eq!(format_undone(&undone), "Restored /ws/a.txt");
+        assert_eq!(block_on(vfs.read_file("/ws/a.txt")).unwrap(), b"old");
+        assert!(block_on(journal.undo(&vfs, UndoScope::Last)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_undo_turn_reverts_only_latest_turn() {
+        let journal = UndoJournal::new(Rc::new(MockStorage::new()));
+        let vfs = MockVfs::new();
+        block_on(async {
+            for (turn, content) in [("t1", "one"), ("t2", "two"), ("t2", "three")] {
+                journal.record(&vfs, turn, "write_file", "/ws/a.txt").await.unwrap();
+                vfs.write_file("/ws/a.txt", content.as_bytes()).await.unwrap();
+            }
+        });
+
+        let undone = block_on(journal.undo(&vfs, UndoScope::Turn)).unwrap();
+        assert_eq!(undone.len(), 2);
+        assert_eq!(block_on(vfs.read_file("/ws/a.txt")).unwrap(), b"one");
+        assert_eq!(block_on(journal.entries()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_undo_journal_is_bounded() {
+        let journal = UndoJournal::new(Rc::new(MockStorage::new()));
+        let vfs = MockVfs::new();
+        block_on(async {
+            for i in 0..MAX_UNDO_ENTRIES + 3 {
+                journal.record(&vfs, "t", "write_file", &format!("/ws/{}.txt", i)).await.unwrap();
+            }
+        });
+        let entries = block_on(journal.entries()).unwrap();
+        assert_eq!(entries.len(), MAX_UNDO_ENTRIES);
+        assert_eq!(entries[0].path, "/ws/3.txt");
+        assert!(entries.windows(2).all(|w| w[0].seq < w[1].seq));
+    }
+
+    #[test]
+    fn test_runtime_write_file_is_undoable() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        runtime.set_undo(Some(Rc::new(UndoJournal::new(Rc::new(MockStorage::new())))));
+        assert!(runtime.tools.get("undo_file_change").is_some());
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/ws/main.rs", b"fn main() {}")).unwrap();
+
+        let llm = MockLlmRecorder::calling("write_file", r#"{"path":"/ws/main.rs","content":"oops"}"#);
+        block_on(runtime.run_turn("Rewrite main", &llm, &MockShell, &vfs)).unwrap();
+        assert_eq!(block_on(vfs.read_file("/ws/main.rs")).unwrap(), b"oops");
+
+        let llm = MockLlmRecorder::calling("undo_file_change", r#"{"scope":"turn"}"#);
+        block_on(runtime.run_turn("Undo that", &llm, &MockShell, &vfs)).unwrap();
+        assert_eq!(block_on(vfs.read_file("/ws/main.rs")).unwrap(), b"fn main() {}");
+        assert_eq!(runtime.messages[7].content.as_text(), "Restored /ws/main.rs");
+    }
+
+    // ─── Snapshot Tests ──────────────────────────────────────
+
+    use crate::snapshots::{MAX_SNAPSHOTS, SnapshotStore};
+
+    #[test]
+    fn test_snapshot_restore_rewrites_and_deletes() {
+        let store = SnapshotStore::new(Rc::new(MockStorage::new()), "/ws");
+        let vfs = workspace_vfs();
+        let before = block_on(store.take(&vfs, "Tidy up")).unwrap();
+        assert_eq!(before.files.len(), 3);
+
+        block_on(async {
+            vfs.write_file("/ws/build.md", b"changed").await.unwrap();
+            vfs.delete_file("/ws/pets.md").await.unwrap();
+            vfs.write_file("/ws/new.md", b"added").await.unwrap();
+        });
+        let changed = block_on(store.restore(&vfs, before.seq)).unwrap();
+        assert_eq!(changed, 3);
+        assert!(block_on(vfs.read_file("/ws/build.md")).unwrap().starts_with(b"Run cargo"));
+        assert!(block_on(vfs.exists("/ws/pets.md")).unwrap());
+        assert!(!block_on(vfs.exists("/ws/new.md")).unwrap());
+        assert_eq!(block_on(vfs.read_file("/ws/logo.png")).unwrap(), vec![0x89, 0x50, 0x00, 0x47]);
+    }
+
+    #[test]
+    fn test_snapshots_share_unchanged_blobs() {
+        let storage = Rc::new(MockStorage::new());
+        let store = SnapshotStore::new(storage.clone(), "/ws");
+        let vfs = workspace_vfs();
+        block_on(store.take(&vfs, "one")).unwrap();
+        block_on(vfs.write_file("/ws/build.md", b"edited")).unwrap();
+        block_on(store.take(&vfs, "two")).unwrap();
+
+        let blobs = block_on(storage.list_keys("snapshot-blob:")).unwrap();
+        assert_eq!(blobs.len(), 4);
+        let labels: Vec<String> = block_on(store.list()).unwrap().into_iter().map(|s| s.label).collect();
+        assert_eq!(labels, vec!["two", "one"]);
+    }
+
+    #[test]
+    fn test_snapshots_pruned_with_their_blobs() {
+        let storage = Rc::new(MockStorage::new());
+        let store = SnapshotStore::new(storage.clone(), "/ws");
+        let vfs = MockVfs::new();
+        block_on(async {
+            for i in 0..MAX_SNAPSHOTS + 2 {
+                vfs.write_file("/ws/a.txt", format!("version {}", i).as_bytes()).await.unwrap();
+                store.take(&vfs, &format!("turn {}", i)).await.unwrap();
+            }
+        });
+        let list = block_on(store.list()).unwrap();
+        assert_eq!(list.len(), MAX_SNAPSHOTS);
+        assert_eq!(list.last().unwrap().label, "turn 2");
+        assert_eq!(block_on(storage.list_keys("snapshot-blob:")).unwrap().len(), MAX_SNAPSHOTS);
+    }
+
+    #[test]
+    fn test_runtime_snapshots_at_turn_start() {
+        let store = Rc::new(SnapshotStore::new(Rc::new(MockStorage::new()), "/ws"));
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        runtime.set_snapshots(Some(store.clone()));
+        let vfs = workspace_vfs();
+
+        let llm = MockLlmRecorder::calling("write_file", r#"{"path":"/ws/build.md","content":"gone"}"#);
+        block_on(runtime.run_turn("Rewrite the build notes", &llm, &MockShell, &vfs)).unwrap();
+        let snapshot = block_on(store.list()).unwrap().remove(0);
+        assert_eq!(snapshot.label, "Rewrite the build notes");
+
+        block_on(store.restore(&vfs, snapshot.seq)).unwrap();
+        assert!(block_on(vfs.read_file("/ws/build.md")).unwrap().starts_with(b"Run cargo"));
+    }
+
+    // ─── Fallback Chain Tests ────────────────────────────────
+
+    use crate::fallback::{FallbackEntry, FallbackLlm};
+    use agent_types::{AgentError, ErrorKind};
+
+    /// Mock LLM that fails every request with `error`, counting them
+    struct MockLlmFailing {
+        error: AgentError,
+        calls: std::cell::Cell<usize>,
+    }
+
+    impl MockLlmFailing {
+        fn new(error: AgentError) -> Self {
+            Self { error, calls: std::cell::Cell::new(0) }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl LlmPort for MockLlmFailing {
+        async fn chat_completion(&self, _req: ChatRequest) -> agent_types::Result<ChatResponse> {
+            self.calls.set(self.calls.get() + 1);
+            Err(self.error.clone())
+        }
+
+        fn stream_chat(
+            &self,
+            _req: ChatRequest,
+        ) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+            Box::pin(futures::stream::once(async { LlmStreamEvent::Done }))
+        }
+
+        async fn list_models(&self) -> agent_types::Result<Vec<String>> {
+            Ok(vec![])
+        }
+    }
+
+    fn entry(provider: &str, model: &str, llm: Rc<dyn LlmPort>) -> FallbackEntry {
+        FallbackEntry {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            llm,
+        }
+    }
+
+    #[test]
+    fn test_fallback_on_retryable_error() {
+        let bus = EventBus::new();
+        let primary = Rc::new(MockLlmFailing::new(AgentError::from_http(503, "overloaded")));
+        let backup = Rc::new(MockLlm { response_text: "from backup".to_string() });
+        let llm = FallbackLlm::new(
+            vec![entry("DeepSeek", "deepseek-chat", primary.clone()), entry("OpenAI", "gpt-4o", backup)],
+            bus.clone(),
+        );
+
+        let response = block_on(llm.chat_completion(ChatRequest::default())).unwrap();
+        assert_eq!(response.message.content.as_text(), "from backup");
+        let meta = response.message.meta.unwrap();
+        assert_eq!(meta.provider.as_deref(), Some("OpenAI"));
+        assert_eq!(meta.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(primary.calls.get(), 1);
+
+        let events = bus.drain();
+        assert!(matches!(
+            &events[..],
+            [AgentEvent::LlmFallback { provider, model, .. }] if provider == "OpenAI" && model == "gpt-4o"
+        ));
+    }
+
+    #[test]
+    fn test_fallback_stops_on_permanent_error() {
+        let bus = EventBus::new();
+        let primary = Rc::new(MockLlmFailing::new(AgentError::from_http(401, "bad key")));
+        let backup = Rc::new(MockLlmFailing::new(AgentError::from_http(503, "down")));
+        let llm = FallbackLlm::new(
+            vec![entry("DeepSeek", "deepseek-chat", primary), entry("OpenAI", "gpt-4o", backup.clone())],
+            bus.clone(),
+        );
+
+        let err = block_on(llm.chat_completion(ChatRequest::default())).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Auth);
+        assert_eq!(backup.calls.get(), 0);
+        assert!(bus.drain().is_empty());
+    }
+
+    #[test]
+    fn test_fallback_returns_last_error_when_all_fail() {
+        let bus = EventBus::new();
+        let llm = FallbackLlm::new(
+            vec![
+                entry("DeepSeek", "deepseek-chat", Rc::new(MockLlmFailing::new(AgentError::Network("down".to_string())))),
+                entry("OpenAI", "gpt-4o", Rc::new(MockLlmFailing::new(AgentError::from_http(429, "slow down")))),
+            ],
+            bus,
+        );
+        let err = block_on(llm.chat_completion(ChatRequest::default())).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::RateLimited);
+    }
+
+    #[test]
+    fn test_agent_loop_records_provider() {
+        let bus = EventBus::new();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), bus);
+        let llm = MockLlm { response_text: "hi".to_string() };
+        block_on(runtime.run_turn("Hi", &llm, &MockShell, &MockVfs::new())).unwrap();
+
+        let meta = runtime.history().last().unwrap().meta.clone().unwrap();
+        assert_eq!(meta.provider.as_deref(), Some("DeepSeek"));
+    }
+
+    // ─── Usage Ledger Tests ──────────────────────────────────
+
+    use crate::usage::UsageLedger;
+    use agent_types::pricing::UsageTotals;
+
+    #[test]
+    fn test_usage_ledger_accumulates() {
+        let ledger = UsageLedger::new(Rc::new(MockStorage::new()));
+        assert_eq!(block_on(ledger.total()).unwrap(), UsageTotals::default());
+
+        let turn = UsageTotals { turns: 1, prompt_tokens: 100, completion_tokens: 10, cost_usd: 0.5 };
+        block_on(ledger.record(&turn)).unwrap();
+        let total = block_on(ledger.record(&turn)).unwrap();
+        assert_eq!(total.turns, 2);
+        assert_eq!(total.prompt_tokens, 200);
+        assert_eq!(block_on(ledger.total()).unwrap(), total);
+    }
+
+    #[test]
+    fn test_agent_loop_records_cost() {
+        let bus = EventBus::new();
+        let mut config = AgentConfig::default();
+        config.prices.insert(
+            "deepseek-chat".to_string(),
+            agent_types::pricing::ModelPrice::new(1_000_000.0, 0.0),
+        );
+        let mut runtime = AgentRuntime::new(config, bus);
+        let llm = MockLlm { response_text: "hi".to_string() };
+        block_on(runtime.run_turn("Hi", &llm, &MockShell, &MockVfs::new())).unwrap();
+
+        // MockLlm reports 10 prompt tokens at $1 each
+        let meta = runtime.history().last().unwrap().meta.clone().unwrap();
+        assert_eq!(meta.cost_usd, Some(10.0));
+    }
+
+    // ─── Activity Log Tests ──────────────────────────────────
+
+    use crate::activity::{ActivityLog, MAX_ACTIVITY_RECORDS, TurnRecorder};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_turn_recorder_collects_tool_runs() {
+        let bus = EventBus::new();
+        let recorder = Rc::new(RefCell::new(TurnRecorder::default()));
+        let observer = recorder.clone();
+        bus.subscribe(move |event| observer.borrow_mut().observe(event));
+
+        // Events before a turn starts are ignored
+        assert!(recorder.borrow_mut().finish(UsageTotals::default()).is_none());
+
+        bus.emit(AgentEvent::TurnStart { turn_id: 1 });
+        for (id, name, success) in [("a", "bash", true), ("b", "write_file", false)] {
+            bus.emit(AgentEvent::ToolExecStart {
+                call_id: id.to_string(),
+                tool_name: name.to_string(),
+                arguments: "{}".to_string(),
+            });
+            bus.emit(AgentEvent::ToolExecEnd {
+                call_id: id.to_string(),
+                result: String::new(),
+                success,
+                duration_ms: 0,
+                data: None,
+            });
+        }
+        bus.emit(AgentEvent::Error { message: "boom".to_string() });
+
+        let usage = UsageTotals { turns: 1, prompt_tokens: 5, completion_tokens: 1, cost_usd: 0.0 };
+        let record = recorder.borrow_mut().finish(usage.clone()).unwrap();
+        assert_eq!(record.tools.len(), 2);
+        assert_eq!(record.tools[1].name, "write_file");
+        assert!(!record.tools[1].success);
+        assert_eq!(record.error.as_deref(), Some("boom"));
+        assert_eq!(record.usage, usage);
+        assert!(recorder.borrow_mut().finish(UsageTotals::default()).is_none());
+    }
+
+    #[test]
+    fn test_activity_log_appends_and_prunes() {
+        let storage = Rc::new(MockStorage::new());
+        let log = ActivityLog::new(storage.clone());
+        let mut record = agent_types::activity::TurnRecord::start();
+        for i in 0..MAX_ACTIVITY_RECORDS + 3 {
+            record.duration_ms = i as u64;
+            block_on(log.append(&record)).unwrap();
+        }
+        let records = block_on(log.records()).unwrap();
+        assert_eq!(records.len(), MAX_ACTIVITY_RECORDS);
+        assert_eq!(records[0].duration_ms, 3);
+        assert_eq!(records.last().unwrap().duration_ms, MAX_ACTIVITY_RECORDS as u64 + 2);
+
+        // Corrupt entries are skipped
+        block_on(storage.set("activity:9999999999999999", b"not json")).unwrap();
+        assert_eq!(block_on(log.records()).unwrap().len(), MAX_ACTIVITY_RECORDS);
+    }
+
+    // ─── Eval Harness Tests ──────────────────────────────────
+
+    use crate::eval::{load_suite, report_path, results_path, run_case, run_suite_file};
+    use agent_types::eval::EvalCase;
+
+    #[test]
+    fn test_eval_run_case_records_outcome() {
+        let case = EvalCase {
+            name: "runs a command".to_string(),
+            prompt: "Run echo".to_string(),
+            expect_contains: vec!["done".to_string()],
+            expect_tools: vec!["bash".to_string(), "write_file".to_string()],
+        };
+        let llm = MockLlmWithToolCall { call_count: RefCell::new(0) };
+        let outcome = block_on(run_case(&AgentConfig::default(), &case, &llm, &MockShell, &MockVfs::new()));
+
+        assert_eq!(outcome.final_message, "Done! The command ran successfully.");
+        assert_eq!(outcome.tool_calls, vec!["bash"]);
+        assert!(outcome.error.is_none());
+        assert_eq!(outcome.failures, vec!["tool write_file not called"]);
+        assert!(!outcome.passed());
+    }
+
+    #[test]
+    fn test_eval_suite_file_compares_with_previous_run() {
+        let vfs = MockVfs::new();
+        let suite = r#"{ "name": "smoke", "cases": [
+            { "name": "greets", "prompt": "Hi", "expect_contains": ["hello"] },
+            { "name": "answers", "prompt": "2+2?", "expect_contains": ["4"] }
+        ] }"#;
+        block_on(vfs.write_file("/workspace/eval.json", suite.as_bytes())).unwrap();
+        let llm = MockLlm { response_text: "Hello!".to_string() };
+        let config = AgentConfig::default();
+
+        let (report, summary) =
+            block_on(run_suite_file(&config, "/workspace/eval.json", &llm, &MockShell, &vfs)).unwrap();
+        assert_eq!(report.passed(), 1);
+        assert_eq!(report.outcomes[0].usage.prompt_tokens, 10);
+        assert!(!summary.contains("Compared with"));
+        assert_eq!(results_path("/workspace/eval.json"), "/workspace/eval.results.json");
+        assert!(block_on(vfs.exists("/workspace/eval.results.json")).unwrap());
+
+        // The second run is compared with the first
+        block_on(run_suite_file(&config, "/workspace/eval.json", &llm, &MockShell, &vfs)).unwrap();
+        let written = block_on(vfs.read_file(&report_path("/workspace/eval.json"))).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.contains("Compared with"));
+        assert!(written.contains("| answers | FAIL |"));
+    }
+
+    #[test]
+    fn test_eval_suite_rejects_bad_files() {
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/workspace/empty.json", br#"{ "cases": [] }"#)).unwrap();
+        block_on(vfs.write_file("/workspace/bad.json", b"not json")).unwrap();
+        assert!(block_on(load_suite(&vfs, "/workspace/empty.json")).is_err());
+        assert!(block_on(load_suite(&vfs, "/workspace/bad.json")).is_err());
+        assert!(block_on(load_suite(&vfs, "/workspace/missing.json")).is_err());
+    }
+
+    // ─── Record / Replay Tests ───────────────────────────────
+
+    use crate::replay::{RecordingLlm, RecordingShell, RecordingStore, replay_session};
+    use agent_types::replay::{Recording, TapeEntry};
+
+    fn texts(history: &[Message]) -> Vec<String> {
+        history.iter().map(|m| m.content.as_text().to_string()).collect()
+    }
+
+    #[test]
+    fn test_recorded_session_replays_identically() {
+        let store = Rc::new(RecordingStore::new(Rc::new(MockStorage::new())));
+        let llm: Rc<dyn LlmPort> = Rc::new(MockLlmWithToolCall { call_count: RefCell::new(0) });
+        let shell: Rc<dyn ShellPort> = Rc::new(MockShell);
+        let recording_llm = RecordingLlm::new(llm, store.clone(), "s1");
+        let recording_shell = RecordingShell::new(shell, store.clone(), "s1");
+
+        let mut live = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        block_on(store.append("s1", TapeEntry::Prompt { text: "Run echo".to_string() })).unwrap();
+        block_on(live.run_turn("Run echo", &recording_llm, &recording_shell, &MockVfs::new())).unwrap();
+
+        let recording = block_on(store.load("s1")).unwrap().unwrap();
+        assert_eq!(recording.turns(), 1);
+        // prompt, tool call, shell command, answer
+        assert_eq!(recording.entries.len(), 4);
+        assert!(matches!(&recording.entries[2], TapeEntry::Shell { command, .. } if command == "echo test"));
+
+        let mut replayed = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        block_on(replay_session(&mut replayed, &recording, &MockVfs::new())).unwrap();
+        assert_eq!(texts(replayed.history()), texts(live.history()));
+        assert!(texts(replayed.history()).iter().any(|t| t.contains("mock output for: echo test")));
+    }
+
+    #[test]
+    fn test_replay_reports_divergence() {
+        // The loop asks the LLM first, but the recording has a command
+        let mut recording = Recording::new("s2");
+        recording.entries = vec![
+            TapeEntry::Prompt { text: "Hi".to_string() },
+            TapeEntry::Shell { command: "ls".to_string(), result: None, error: Some("boom".to_string()) },
+        ];
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let err = block_on(replay_session(&mut runtime, &recording, &MockVfs::new())).unwrap_err();
+        assert!(err.to_string().contains("Replay diverged: step 2"));
+    }
+
+    #[test]
+    fn test_recording_store_missing_session() {
+        let store = RecordingStore::new(Rc::new(MockStorage::new()));
+        assert!(block_on(store.load("nope")).unwrap().is_none());
+        block_on(store.append("s", TapeEntry::Prompt { text: "a".to_string() })).unwrap();
+        block_on(store.delete("s")).unwrap();
+        assert!(block_on(store.load("s")).unwrap().is_none());
+    }
+
+    // ─── Self-Review Tests ───────────────────────────────────
+
+    use crate::runtime::REVIEW_CONFIRMATION;
+    use std::collections::VecDeque;
+
+    /// Mock LLM that plays back scripted replies and records the requests
+    struct MockLlmScript {
+        replies: RefCell<VecDeque<Message>>,
+        requests: RefCell<Vec<ChatRequest>>,
+    }
+
+    impl MockLlmScript {
+        fn new(replies: Vec<Message>) -> Self {
+            Self { replies: RefCell::new(replies.into()), requests: RefCell::new(Vec::new()) }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl LlmPort for MockLlmScript {
+        async fn chat_completion(&self, req: ChatRequest) -> agent_types::Result<ChatResponse> {
+            self.requests.borrow_mut().push(req);
+            let message = self.replies.borrow_mut().pop_front().expect("unscripted LLM call");
+            Ok(ChatResponse {
+                message,
+                usage: Some(TokenUsage { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 }),
+            })
+        }
+
+        fn stream_chat(&self, _req: ChatRequest) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+            Box::pin(futures::stream::once(async { LlmStreamEvent::Done }))
+        }
+
+        async fn list_models(&self) -> agent_types::Result<Vec<String>> {
+            Ok(vec![])
+        }
+    }
+
+    fn bash_calls(commands: &[&str]) -> Message {
+        let mut message = Message::assistant("");
+        message.tool_calls = commands
+            .iter()
+            .enumerate()
+            .map(|(i, command)| ToolCallRequest {
+                id: format!("call_{}", i),
+                function: FunctionCall {
+                    name: "bash".to_string(),
+                    arguments: format!(r#"{{"command":"{}"}}"#, command),
+                },
+            })
+            .collect();
+        message
+    }
+
+    fn reviewing_runtime() -> AgentRuntime {
+        let mut config = AgentConfig::default();
+        config.review.enabled = true;
+        AgentRuntime::new(config, EventBus::new())
+    }
+
+    #[test]
+    fn test_review_confirmed_keeps_answer() {
+        let mut runtime = reviewing_runtime();
+        let llm = MockLlmScript::new(vec![
+            bash_calls(&["ls", "cat a"]),
+            Message::assistant("Done"),
+            Message::assistant(REVIEW_CONFIRMATION),
+        ]);
+        block_on(runtime.run_turn("Summarize a", &llm, &MockShell, &MockVfs::new())).unwrap();
+
+        // user, tool calls, 2 results, answer — the review leaves no trace
+        let history = runtime.history();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[4].content.as_text(), "Done");
+        assert_eq!(history[4].meta.as_ref().unwrap().usage.as_ref().unwrap().total_tokens, 30);
+
+        let requests = llm.requests.borrow();
+        assert_eq!(requests.len(), 3);
+        let review = requests[2].messages.last().unwrap();
+        assert_eq!(review.role, Role::User);
+        assert!(review.content.as_text().contains("Summarize a"));
+    }
+
+    #[test]
+    fn test_review_revision_replaces_draft() {
+        let mut runtime = reviewing_runtime();
+        let llm = MockLlmScript::new(vec![
+            bash_calls(&["ls", "cat a"]),
+            Message::assistant("Done"),
+            Message::assistant("Done, but a is empty"),
+        ]);
+        block_on(runtime.run_turn("Summarize a", &llm, &MockShell, &MockVfs::new())).unwrap();
+
+        let history = runtime.history();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[4].content.as_text(), "Done, but a is empty");
+        assert_eq!(history[4].meta.as_ref().unwrap().usage.as_ref().unwrap().total_tokens, 30);
+    }
+
+    #[test]
+    fn test_review_fixes_with_tools_once() {
+        let mut runtime = reviewing_runtime();
+        let llm = MockLlmScript::new(vec![
+            bash_calls(&["ls", "cat a"]),
+            Message::assistant("Done"),
+            bash_calls(&["touch b"]),
+            Message::assistant("Created b as well"),
+        ]);
+        block_on(runtime.run_turn("Summarize a, create b", &llm, &MockShell, &MockVfs::new())).unwrap();
+
+        // The draft stays as an interim answer; the final one isn't reviewed again
+        let texts: Vec<_> = runtime.history().iter().map(|m| m.content.as_text().to_string()).collect();
+        assert_eq!(texts[4], "Done");
+        assert_eq!(texts.last().unwrap(), "Created b as well");
+        let requests = llm.requests.borrow();
+        assert_eq!(requests.len(), 4);
+        // The review request is still shown while the fixes are made
+        assert!(requests[3].messages.iter().any(|m| m.content.as_text().contains(REVIEW_CONFIRMATION)));
+    }
+
+    #[test]
+    fn test_review_skipped_for_short_turns() {
+        let mut runtime = reviewing_runtime();
+        let llm = MockLlmScript::new(vec![bash_calls(&["ls"]), Message::assistant("Done")]);
+        block_on(runtime.run_turn("List", &llm, &MockShell, &MockVfs::new())).unwrap();
+        assert_eq!(llm.requests.borrow().len(), 2);
+
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let llm = MockLlmScript::new(vec![bash_calls(&["ls", "pwd"]), Message::assistant("Done")]);
+        block_on(runtime.run_turn("List", &llm, &MockShell, &MockVfs::new())).unwrap();
+        assert_eq!(llm.requests.borrow().len(), 2);
+    }
+
+    // ─── Tool Output Budget Tests ────────────────────────────
+
+    #[test]
+    fn test_tool_output_budget_stores_later_results() {
+        let mut config = AgentConfig::default();
+        config.tool_output.turn_budget_bytes = 70;
+        let mut runtime = AgentRuntime::new(config, EventBus::new());
+        let vfs = MockVfs::new();
+        let llm = MockLlmScript::new(vec![bash_calls(&["one", "two", "three"]), Message::assistant("Done")]);
+        block_on(runtime.run_turn("Run them", &llm, &MockShell, &vfs)).unwrap();
+
+        // Each result is checked against what's left: the first two fit
+        let results: Vec<_> = runtime
+            .history()
+            .iter()
+            .filter(|m| m.role == Role::Tool)
+            .map(|m| m.content.as_text().to_string())
+            .collect();
+        assert!(results[0].starts_with("mock output for: one"));
+        assert!(results[1].starts_with("mock output for: two"));
+        assert!(results[2].starts_with("[Output omitted"));
+        assert!(results[2].contains("/workspace/.agent/outputs/call_2.txt"));
+
+        let stored = block_on(vfs.read_file("/workspace/.agent/outputs/call_2.txt")).unwrap();
+        assert!(stored.starts_with(b"mock output for: three"));
+        let omitted = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        let data = omitted.meta.as_ref().and_then(|m| m.tool_data.as_ref()).unwrap();
+        assert_eq!(data.artifacts, vec!["/workspace/.agent/outputs/call_2.txt".to_string()]);
+        assert_eq!(data.exit_code, Some(0));
+    }
+
+    #[test]
+    fn test_tool_output_budget_resets_each_turn() {
+        let mut config = AgentConfig::default();
+        config.tool_output.turn_budget_bytes = 40;
+        let mut runtime = AgentRuntime::new(config, EventBus::new());
+        for _ in 0..2 {
+            let llm = MockLlmScript::new(vec![bash_calls(&["ls"]), Message::assistant("Done")]);
+            block_on(runtime.run_turn("List", &llm, &MockShell, &MockVfs::new())).unwrap();
+            let last_result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+            assert!(last_result.content.as_text().starts_with("mock output for: ls"));
+        }
+    }
+
+    #[test]
+    fn test_tool_output_budget_stores_result_larger_than_budget() {
+        let mut config = AgentConfig::default();
+        config.tool_output.turn_budget_bytes = 10;
+        let mut runtime = AgentRuntime::new(config, EventBus::new());
+        let vfs = MockVfs::new();
+        let llm = MockLlmScript::new(vec![bash_calls(&["ls"]), Message::assistant("Done")]);
+        block_on(runtime.run_turn("List", &llm, &MockShell, &vfs)).unwrap();
+
+        // Even the first result of the turn never enters the context in full
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        let text = result.content.as_text();
+        assert!(text.starts_with("[Output omitted"));
+        assert!(!text.contains("mock output for: ls"));
+        let stored = block_on(vfs.read_file("/workspace/.agent/outputs/call_0.txt")).unwrap();
+        assert!(stored.starts_with(b"mock output for: ls"));
+    }
+
+    // ─── Binary File Tests ───────────────────────────────────
+
+    #[test]
+    fn test_looks_binary() {
+        use crate::tools::looks_binary;
+
+        assert!(!looks_binary(b"fn main() {}\n"));
+        assert!(!looks_binary("héllo wörld".as_bytes()));
+        assert!(!looks_binary(b""));
+        assert!(looks_binary(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0, 0, 0x0d]));
+        assert!(looks_binary(&[0xff, 0xfe, 0xfd, b'a', 0xc3]));
+        // A character cut off by the sample size doesn't count
+        let mut text = "a".repeat(8191).into_bytes();
+        text.extend_from_slice("é".as_bytes());
+        assert!(!looks_binary(&text));
+    }
+
+    fn read_call(arguments: &str) -> Message {
+        let mut message = Message::assistant("");
+        message.tool_calls = vec![ToolCallRequest {
+            id: "call_0".to_string(),
+            function: FunctionCall { name: "read_file".to_string(), arguments: arguments.to_string() },
+        }];
+        message
+    }
+
+    #[test]
+    fn test_read_file_describes_binary() {
+        let vfs = MockVfs::new();
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        png.extend(std::iter::repeat_n(0u8, 4096));
+        block_on(vfs.write_file("/ws/logo.png", &png)).unwrap();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let llm = MockLlmScript::new(vec![read_call(r#"{"path":"/ws/logo.png"}"#), Message::assistant("Done")]);
+        block_on(runtime.run_turn("Read it", &llm, &MockShell, &vfs)).unwrap();
+
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        let text = result.content.as_text();
+        assert!(text.starts_with("/ws/logo.png is a binary file (4104 bytes, image/png)"), "{}", text);
+        assert!(text.contains("89 50 4e 47 0d 0a 1a 0a"));
+        assert!(text.contains("force_text"));
+        assert!(text.len() < 600);
+    }
+
+    #[test]
+    fn test_read_file_force_text() {
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/ws/data.bin", b"ab\0cd")).unwrap();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let call = read_call(r#"{"path":"/ws/data.bin","force_text":true}"#);
+        let llm = MockLlmScript::new(vec![call, Message::assistant("Done")]);
+        block_on(runtime.run_turn("Read it", &llm, &MockShell, &vfs)).unwrap();
+
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        assert_eq!(result.content.as_text(), "ab\0cd");
+    }
+
+    // ─── Line Range Tests ────────────────────────────────────
+
+    #[test]
+    fn test_read_lines_ranges() {
+        use crate::tools::read_lines;
+
+        let text = (1..=12).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+        assert_eq!(
+            read_lines(&text, Some(9), Some(2)),
+            " 9\tline 9\n10\tline 10\n[Lines 9-10 of 12; read from offset 11 to continue]"
+        );
+        // Without a limit the rest of the file is returned
+        assert_eq!(read_lines(&text, Some(11), None), "11\tline 11\n12\tline 12\n");
+        // Without an offset reading starts at line 1
+        assert!(read_lines(&text, None, Some(1)).starts_with("1\tline 1\n[Lines 1-1 of 12"));
+        assert_eq!(read_lines(&text, Some(13), None), "[The file has 12 lines; offset 13 is past the end]");
+        assert_eq!(read_lines(&text, Some(12), Some(usize::MAX)), "12\tline 12\n");
+    }
+
+    #[test]
+    fn test_read_file_with_offset_and_limit() {
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/ws/a.txt", b"one\ntwo\nthree\nfour\n")).unwrap();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let call = read_call(r#"{"path":"/ws/a.txt","offset":2,"limit":2}"#);
+        let llm = MockLlmScript::new(vec![call, Message::assistant("Done")]);
+        block_on(runtime.run_turn("Read it", &llm, &MockShell, &vfs)).unwrap();
+
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        assert_eq!(
+            result.content.as_text(),
+            "2\ttwo\n3\tthree\n[Lines 2-3 of 4; read from offset 4 to continue]"
+        );
+    }
+
+    // ─── Write Mode Tests ────────────────────────────────────
+
+    fn write_call(arguments: &str) -> Message {
+        let mut message = Message::assistant("");
+        message.tool_calls = vec![ToolCallRequest {
+            id: "call_0".to_string(),
+            function: FunctionCall { name: "write_file".to_string(), arguments: arguments.to_string() },
+        }];
+        message
+    }
+
+    fn run_write(vfs: &MockVfs, arguments: &str) -> (String, bool) {
+        let bus = EventBus::new();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), bus.clone());
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = events.clone();
+        bus.subscribe(move |e| {
+            if let AgentEvent::ToolExecEnd { success, .. } = e {
+                sink.borrow_mut().push(*success);
+            }
+        });
+        let llm = MockLlmScript::new(vec![write_call(arguments), Message::assistant("Done")]);
+        block_on(runtime.run_turn("Write it", &llm, &MockShell, vfs)).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        let success = events.borrow()[0];
+        (result.content.as_text().to_string(), success)
+    }
+
+    #[test]
+    fn test_write_file_append_mode() {
+        let vfs = MockVfs::new();
+        let (output, ok) = run_write(&vfs, r#"{"path":"/ws/log.txt","content":"one\n","mode":"append"}"#);
+        assert!(ok);
+        assert_eq!(output, "Appended 4 bytes to /ws/log.txt");
+        run_write(&vfs, r#"{"path":"/ws/log.txt","content":"two\n","mode":"append"}"#);
+        assert_eq!(block_on(vfs.read_file("/ws/log.txt")).unwrap(), b"one\ntwo\n");
+    }
+
+    #[test]
+    fn test_write_file_create_new_refuses_existing() {
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/ws/a.txt", b"keep")).unwrap();
+        let (output, ok) = run_write(&vfs, r#"{"path":"/ws/a.txt","content":"new","mode":"create_new"}"#);
+        assert!(!ok);
+        assert!(output.contains("already exists"), "{}", output);
+        assert_eq!(block_on(vfs.read_file("/ws/a.txt")).unwrap(), b"keep");
+
+        let (_, ok) = run_write(&vfs, r#"{"path":"/ws/b.txt","content":"new","mode":"create_new"}"#);
+        assert!(ok);
+        assert_eq!(block_on(vfs.read_file("/ws/b.txt")).unwrap(), b"new");
+    }
+
+    #[test]
+    fn test_write_file_unknown_mode() {
+        let vfs = MockVfs::new();
+        let (output, ok) = run_write(&vfs, r#"{"path":"/ws/a.txt","content":"x","mode":"prepend"}"#);
+        assert!(!ok);
+        assert!(output.contains("unknown mode"));
+        assert!(!block_on(vfs.exists("/ws/a.txt")).unwrap());
+    }
+
+    #[test]
+    fn test_large_write_reports_progress() {
+        use crate::runtime::WRITE_PROGRESS_BYTES;
+
+        let vfs = MockVfs::new();
+        let content = "x".repeat(WRITE_PROGRESS_BYTES * 2 + 10);
+        let bus = EventBus::new();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), bus.clone());
+        let progress = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = progress.clone();
+        bus.subscribe(move |e| {
+            if let AgentEvent::ToolProgress { done, total, unit, .. } = e {
+                sink.borrow_mut().push((*done, *total, unit.clone()));
+            }
+        });
+        let arguments = serde_json::json!({ "path": "/ws/big.txt", "content": content }).to_string();
+        let llm = MockLlmScript::new(vec![write_call(&arguments), Message::assistant("Done")]);
+        block_on(runtime.run_turn("Write it", &llm, &MockShell, &vfs)).unwrap();
+
+        // Written in one call, with progress around it
+        let total = Some(content.len() as u64);
+        assert_eq!(
+            *progress.borrow(),
+            vec![(0, total, "bytes".to_string()), (content.len() as u64, total, "bytes".to_string())]
+        );
+        assert_eq!(block_on(vfs.read_file("/ws/big.txt")).unwrap(), content.as_bytes());
+
+        // Small writes finish in one go
+        progress.borrow_mut().clear();
+        run_write(&vfs, r#"{"path":"/ws/big.txt","content":"small"}"#);
+        assert_eq!(block_on(vfs.read_file("/ws/big.txt")).unwrap(), b"small");
+    }
+
+    // ─── Run Script Tests ────────────────────────────────────
+
+    #[test]
+    fn test_script_command_copies_and_runs_script() {
+        use crate::tools::script_command;
+
+        assert_eq!(
+            script_command("/ws/it's.sh", "echo hi\n", "'a b'"),
+            "mkdir -p '/ws' && cat > '/ws/it'\\''s.sh' <<'AGENT_SCRIPT_EOF'\necho hi\n\
+             AGENT_SCRIPT_EOF\nbash '/ws/it'\\''s.sh' 'a b'"
+        );
+        // A script containing the delimiter gets another one
+        let command = script_command("/run.sh", "cat <<'AGENT_SCRIPT_EOF'\nx\nAGENT_SCRIPT_EOF", "");
+        assert!(command.starts_with("mkdir -p '/' && cat > '/run.sh' <<'AGENT_SCRIPT_EOF_'\n"), "{}", command);
+        assert!(command.ends_with("\nAGENT_SCRIPT_EOF\nAGENT_SCRIPT_EOF_\nbash '/run.sh'"), "{}", command);
+    }
+
+    /// Run one call of `tool` and return its result
+    fn tool_turn(tool: &str, arguments: &str) -> String {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let mut call = Message::assistant("");
+        call.tool_calls = vec![ToolCallRequest {
+            id: "call_0".to_string(),
+            function: FunctionCall { name: tool.to_string(), arguments: arguments.to_string() },
+        }];
+        let llm = MockLlmScript::new(vec![call, Message::assistant("Done")]);
+        block_on(runtime.run_turn("Run it", &llm, &MockShell, &MockVfs::new())).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        result.content.as_text().to_string()
+    }
+
+    #[test]
+    fn test_bash_tool_pipes_stdin() {
+        let output = tool_turn("bash", r#"{"command":"python -","stdin":"print(1)"}"#);
+        assert!(output.starts_with("mock output for: python - <<< print(1)"), "{}", output);
+        let output = tool_turn("bash", r#"{"command":"ls"}"#);
+        assert!(output.starts_with("mock output for: ls"), "{}", output);
+    }
+
+    #[test]
+    fn test_bash_tool_runs_in_cwd() {
+        let output = tool_turn("bash", r#"{"command":"cargo test","cwd":"/workspace/app"}"#);
+        assert!(output.starts_with("mock output for: cargo test (in /workspace/app)"), "{}", output);
+    }
+
+    #[test]
+    fn test_shell_port_rejects_stdin_by_default() {
+        struct NoInput;
+        #[async_trait(?Send)]
+        impl ShellPort for NoInput {
+            async fn execute(&self, _cmd: &str, _opts: &ExecOptions) -> agent_types::Result<ExecResult> {
+                unreachable!()
+            }
+            fn execute_streaming(&self, _cmd: &str, _opts: &ExecOptions) -> Pin<Box<dyn Stream<Item = ShellStreamEvent>>> {
+                Box::pin(futures::stream::empty())
+            }
+            async fn cancel(&self, _handle: ExecHandle) -> agent_types::Result<()> {
+                Ok(())
+            }
+            fn is_ready(&self) -> bool {
+                true
+            }
+        }
+        let err = block_on(NoInput.write_stdin(ExecHandle(1), "x")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unavailable);
+        assert!(block_on(NoInput.close_stdin(ExecHandle(1))).is_err());
+        assert!(block_on(NoInput.execute_with_stdin("cat", "x", &ExecOptions::default())).is_err());
+    }
+
+    fn run_script_turn(vfs: &MockVfs, arguments: &str) -> (String, Vec<String>) {
+        let bus = EventBus::new();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), bus.clone());
+        let chunks = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = chunks.clone();
+        bus.subscribe(move |e| {
+            if let AgentEvent::ToolOutput { chunk, .. } = e {
+                sink.borrow_mut().push(chunk.clone());
+            }
+        });
+        let mut call = Message::assistant("");
+        call.tool_calls = vec![ToolCallRequest {
+            id: "call_0".to_string(),
+            function: FunctionCall { name: "run_script".to_string(), arguments: arguments.to_string() },
+        }];
+        let llm = MockLlmScript::new(vec![call, Message::assistant("Done")]);
+        block_on(runtime.run_turn("Run it", &llm, &MockShell, vfs)).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        let chunks = chunks.borrow().clone();
+        (result.content.as_text().to_string(), chunks)
+    }
+
+    #[test]
+    fn test_run_script_tool() {
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/ws/build.sh", b"echo building\n")).unwrap();
+        let (output, chunks) = run_script_turn(&vfs, r#"{"path":"/ws/build.sh","args":["--fast","it's"]}"#);
+        assert!(output.contains("cat > '/ws/build.sh' <<'AGENT_SCRIPT_EOF'\necho building\n"), "{}", output);
+        assert!(output.contains("bash '/ws/build.sh' '--fast' 'it'\\''s'"), "{}", output);
+        assert!(output.ends_with("[exit code: 0]"));
+        // Output is also sent to the terminal
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].starts_with("mock output for: mkdir -p '/ws'"));
+
+        let (output, chunks) = run_script_turn(&vfs, r#"{"path":"/ws/missing.sh"}"#);
+        assert!(output.starts_with("Failed to read script /ws/missing.sh"), "{}", output);
+        assert!(chunks.is_empty());
+    }
+
+    // ─── Context Strategy Tests ──────────────────────────────
+
+    use crate::context::{
+        ContextStrategy, ImportanceWeighted, RecentBudget, SlidingWindow, estimate_tokens, units,
+    };
+    use agent_types::config::ContextStrategyKind;
+
+    /// Three earlier turns: a request, a tool call with its result, and an answer each
+    fn earlier_turns() -> Vec<Message> {
+        let mut messages = Vec::new();
+        for turn in 0..3 {
+            messages.push(Message::user(format!("request {}", turn)));
+            let mut call = bash_calls(&["ls"]);
+            call.tool_calls[0].id = format!("call_{}", turn);
+            messages.push(call);
+            messages.push(Message::tool_result(format!("call_{}", turn), "x".repeat(400)));
+            messages.push(Message::assistant(format!("answer {}", turn)));
+        }
+        messages
+    }
+
+    fn texts_of(messages: &[Message]) -> Vec<String> {
+        messages.iter().map(|m| m.content.as_text().to_string()).collect()
+    }
+
+    #[test]
+    fn test_context_units_keep_tool_results_with_calls() {
+        let earlier = earlier_turns();
+        let units = units(&earlier);
+        assert_eq!(units.len(), 9);
+        assert_eq!(units[1], 1..3);
+        assert_eq!(units[2], 3..4);
+    }
+
+    #[test]
+    fn test_sliding_window_keeps_last_turns() {
+        let earlier = earlier_turns();
+        let kept = SlidingWindow { turns: 2 }.select(&earlier);
+        assert_eq!(kept.len(), 8);
+        assert_eq!(kept[0].content.as_text(), "request 1");
+        assert!(SlidingWindow { turns: 0 }.select(&earlier).is_empty());
+        assert_eq!(SlidingWindow { turns: 10 }.select(&earlier).len(), 12);
+    }
+
+    #[test]
+    fn test_recent_budget_keeps_newest_without_gaps() {
+        let earlier = earlier_turns();
+        // The last answer and tool round-trip fit; the request before them doesn't
+        let budget = estimate_tokens(&earlier[11]) + estimate_tokens(&earlier[9]) + estimate_tokens(&earlier[10]);
+        let kept = RecentBudget { max_tokens: budget + 5 }.select(&earlier);
+        assert_eq!(texts_of(&kept)[2], "answer 2");
+        assert_eq!(kept.len(), 3);
+        assert_eq!(kept[0].tool_calls.len(), 1);
+    }
+
+    #[test]
+    fn test_importance_weighted_prefers_requests_and_answers() {
+        let earlier = earlier_turns();
+        let kept = ImportanceWeighted { max_tokens: 60 }.select(&earlier);
+        let texts = texts_of(&kept);
+        // Requests and answers fit; the large tool results don't
+        assert_eq!(
+            texts,
+            vec!["request 0", "answer 0", "request 1", "answer 1", "request 2", "answer 2"]
+        );
+        assert!(kept.iter().all(|m| m.role != Role::Tool && m.tool_calls.is_empty()));
+
+        // With room for a single unit, the first request wins
+        let kept = ImportanceWeighted { max_tokens: 8 }.select(&earlier);
+        assert_eq!(texts_of(&kept), vec!["request 0"]);
+    }
+
+    #[test]
+    fn test_runtime_applies_context_strategy() {
+        let mut config = AgentConfig::default();
+        config.context.strategy = ContextStrategyKind::SlidingWindow;
+        config.context.window_turns = 1;
+        let mut runtime = AgentRuntime::new(config, EventBus::new());
+        let llm = MockLlmRecorder::new();
+        for prompt in ["one", "two", "three"] {
+            block_on(runtime.run_turn(prompt, &llm, &MockShell, &MockVfs::new())).unwrap();
+        }
+
+        // system, the previous turn, the current request
+        let requests = llm.requests.borrow();
+        let sent = texts_of(&requests[2].messages);
+        assert_eq!(requests[2].messages[0].role, Role::System);
+        assert_eq!(&sent[1..], ["two", "ok", "three"]);
+        // The full history is kept
+        assert_eq!(runtime.history().len(), 6);
+    }
+
+    // ─── Config Store Tests ──────────────────────────────────
+
+    use crate::config_store::{BACKUP_KEY, ConfigStore};
+
+    #[test]
+    fn test_config_store_round_trip_without_keys() {
+        let storage = Rc::new(MockStorage::new());
+        let store = ConfigStore::new(storage.clone());
+        assert!(block_on(store.load()).unwrap().is_none());
+
+        let mut config = AgentConfig::default();
+        config.llm.api_key = "sk-secret".to_string();
+        config.llm.model = "deepseek-reasoner".to_string();
+        block_on(store.save(&config)).unwrap();
+        let saved = String::from_utf8(block_on(storage.get("config")).unwrap().unwrap()).unwrap();
+        assert!(!saved.contains("sk-secret"));
+
+        let restored = block_on(store.load()).unwrap().unwrap();
+        assert_eq!(restored.config.llm.model, "deepseek-reasoner");
+        assert!(restored.config.llm.api_key.is_empty());
+        assert!(restored.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_config_store_backs_up_unreadable_save() {
+        let storage = Rc::new(MockStorage::new());
+        block_on(storage.set("config", b"{ truncated")).unwrap();
+        let store = ConfigStore::new(storage.clone());
+
+        let restored = block_on(store.load()).unwrap().unwrap();
+        assert_eq!(restored.config.llm.model, AgentConfig::default().llm.model);
+        assert_eq!(restored.warnings.len(), 1);
+        assert_eq!(block_on(storage.get(BACKUP_KEY)).unwrap().unwrap(), b"{ truncated");
+    }
+
+    // ─── Demo LLM Tests ──────────────────────────────────────
+
+    use crate::demo::DemoLlm;
+
+    fn tool_names(history: &[Message]) -> Vec<String> {
+        history
+            .iter()
+            .flat_map(|m| m.tool_calls.iter().map(|tc| tc.function.name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_demo_llm_files_script() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let vfs = MockVfs::new();
+        block_on(runtime.run_turn("Please write a file for me", &DemoLlm::new(), &MockShell, &vfs)).unwrap();
+
+        assert_eq!(tool_names(runtime.history()), vec!["write_file", "list_dir", "read_file"]);
+        let notes = block_on(vfs.read_file("/workspace/demo/notes.md")).unwrap();
+        assert!(String::from_utf8(notes).unwrap().starts_with("# Demo notes"));
+        let answer = runtime.history().last().unwrap().content.as_text().to_string();
+        assert!(answer.contains("# Demo notes"));
+        assert!(answer.contains("demo mode"));
+    }
+
+    #[test]
+    fn test_demo_llm_shell_and_help_scripts() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let vfs = MockVfs::new();
+        block_on(runtime.run_turn("run a command", &DemoLlm::new(), &MockShell, &vfs)).unwrap();
+        assert_eq!(tool_names(runtime.history()), vec!["bash"]);
+        assert!(runtime.history().last().unwrap().content.as_text().contains("mock output for: echo"));
+
+        // A second turn starts its own script, with fresh call ids
+        block_on(runtime.run_turn("hi there", &DemoLlm::new(), &MockShell, &vfs)).unwrap();
+        assert_eq!(tool_names(runtime.history()).len(), 1);
+        assert!(runtime.history().last().unwrap().content.as_text().starts_with("Hi!"));
+
+        block_on(runtime.run_turn("show me around", &DemoLlm::new(), &MockShell, &vfs)).unwrap();
+        let ids: Vec<String> = runtime
+            .history()
+            .iter()
+            .flat_map(|m| m.tool_calls.iter().map(|tc| tc.id.clone()))
+            .collect();
+        assert_eq!(ids, vec!["demo_1_0", "demo_3_0", "demo_3_1", "demo_3_2"]);
+    }
+
+    #[test]
+    fn test_demo_llm_confirms_reviews() {
+        let mut config = AgentConfig::default();
+        config.review.enabled = true;
+        let mut runtime = AgentRuntime::new(config, EventBus::new());
+        block_on(runtime.run_turn("write a file", &DemoLlm::new(), &MockShell, &MockVfs::new())).unwrap();
+        let answer = runtime.history().last().unwrap().content.as_text().to_string();
+        assert!(answer.contains("I wrote `/workspace/demo/notes.md`"));
+        assert_eq!(block_on(DemoLlm::new().list_models()).unwrap(), vec!["demo"]);
+    }
+
+    // ─── Attachment Tests ────────────────────────────────────
+
+    #[test]
+    fn test_attachments_shown_for_their_turn_only() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/workspace/notes.md", b"remember the milk")).unwrap();
+        let llm = MockLlmScript::new(vec![Message::assistant("Milk."), Message::assistant("Hi.")]);
+        let attached = vec!["/workspace/notes.md".to_string(), "/workspace/missing.md".to_string()];
+        block_on(runtime.run_turn_with_attachments("What do I need?", &attached, &llm, &MockShell, &vfs)).unwrap();
+        block_on(runtime.run_turn("Hello", &llm, &MockShell, &vfs)).unwrap();
+
+        let requests = llm.requests.borrow();
+        let first = &requests[0].messages;
+        let context = first[first.len() - 2].content.as_text();
+        assert_eq!(first[first.len() - 2].role, Role::System);
+        assert!(context.contains("── /workspace/notes.md (17 bytes)\nremember the milk"));
+        assert!(context.contains("── /workspace/missing.md\n[could not be read"));
+        // Later requests see the path on the message, not the contents
+        assert!(!requests[1].messages.iter().any(|m| m.content.as_text().contains("remember the milk")));
+        let user = runtime.history().iter().find(|m| m.role == Role::User).unwrap();
+        assert_eq!(user.meta.as_ref().unwrap().attachments, attached);
+    }
+
+    #[test]
+    fn test_attachment_size_caps() {
+        use crate::attachments::{MAX_FILE_BYTES, format_attachment};
+        let mut budget = MAX_FILE_BYTES + 10;
+        let big = vec![b'a'; MAX_FILE_BYTES + 100];
+        let text = format_attachment("/big.txt", &big, &mut budget);
+        assert!(text.ends_with(&format!("[truncated after {} of {} bytes; use read_file for the rest]", MAX_FILE_BYTES, big.len())));
+        assert_eq!(budget, 10);
+
+        let text = format_attachment("/small.txt", b"0123456789abc", &mut budget);
+        assert!(text.ends_with("0123456789\n[truncated after 10 of 13 bytes; use read_file for the rest]"));
+        let text = format_attachment("/more.txt", b"x", &mut budget);
+        assert!(text.contains("[not shown"));
+    }
+
+    // ─── Response Cache Tests ────────────────────────────────
+
+    use crate::cache::{CachedLlm, cache_key};
+
+    fn cache_request(text: &str) -> ChatRequest {
+        let mut user = Message::user(text);
+        user.stamp();
+        ChatRequest { messages: vec![user], model: "mock-model".to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_cache_key_ignores_message_ids() {
+        let a = cache_request("hello");
+        let b = cache_request("hello");
+        assert_ne!(a.messages[0].meta.as_ref().unwrap().id, b.messages[0].meta.as_ref().unwrap().id);
+        let key = |req: &ChatRequest| cache_key(req, "OpenAI", "https://api.openai.com");
+        assert_eq!(key(&a), key(&b));
+        assert!(key(&a).starts_with("llm-cache:"));
+        assert_ne!(key(&a), key(&cache_request("hello!")));
+        assert_ne!(key(&a), key(&ChatRequest { temperature: 0.9, ..a.clone() }));
+    }
+
+    #[test]
+    fn test_cache_key_includes_endpoint() {
+        let req = cache_request("hello");
+        let key = cache_key(&req, "OpenAI", "https://api.openai.com");
+        assert_ne!(key, cache_key(&req, "Custom", "https://api.openai.com"));
+        assert_ne!(key, cache_key(&req, "OpenAI", "http://localhost:8080"));
+    }
+
+    #[test]
+    fn test_cached_llm_answers_repeat_requests_from_storage() {
+        let inner = Rc::new(MockLlmRecorder::new());
+        let storage = Rc::new(MockStorage::new());
+        let llm = CachedLlm::new(inner.clone(), storage.clone(), 3600, "OpenAI", "https://api.openai.com");
+
+        let first = block_on(llm.chat_completion(cache_request("hello"))).unwrap();
+        assert!(!first.message.meta.as_ref().is_some_and(|m| m.cached));
+        let second = block_on(llm.chat_completion(cache_request("hello"))).unwrap();
+        assert_eq!(inner.requests.borrow().len(), 1);
+        assert_eq!(second.message.content.as_text(), first.message.content.as_text());
+        assert!(second.message.meta.as_ref().unwrap().cached);
+        assert!(second.usage.is_none());
+
+        block_on(llm.chat_completion(cache_request("something else"))).unwrap();
+        assert_eq!(inner.requests.borrow().len(), 2);
+
+        // Expired entries are fetched again
+        let expired = CachedLlm::new(inner.clone(), storage, 0, "OpenAI", "https://api.openai.com");
+        block_on(expired.chat_completion(cache_request("hello"))).unwrap();
+        assert_eq!(inner.requests.borrow().len(), 3);
+    }
+
+    // ─── Batch Tests ─────────────────────────────────────────
+
+    use crate::batch::{load_batch, parse_batch};
+
+    #[test]
+    fn test_parse_batch_lines_and_separators() {
+        assert_eq!(
+            parse_batch("# refactor\nFix src/a.rs\n\n  Fix src/b.rs  \n"),
+            vec!["Fix src/a.rs", "Fix src/b.rs"]
+        );
+        assert_eq!(
+            parse_batch("Rename foo\nin src/a.rs\n---\n# skipped\nRename foo\nin src/b.rs\n---\n"),
+            vec!["Rename foo\nin src/a.rs", "Rename foo\nin src/b.rs"]
+        );
+        assert_eq!(parse_batch(r#"["one", " ", "two\nlines"]"#), vec!["one", "two\nlines"]);
+        assert!(parse_batch("# only comments\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_load_batch_file() {
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/workspace/prompts.txt", b"first\nsecond")).unwrap();
+        block_on(vfs.write_file("/workspace/empty.txt", b"# nothing")).unwrap();
+        assert_eq!(block_on(load_batch(&vfs, "/workspace/prompts.txt")).unwrap(), vec!["first", "second"]);
+        assert!(block_on(load_batch(&vfs, "/workspace/empty.txt")).is_err());
+        assert!(block_on(load_batch(&vfs, "/workspace/missing.txt")).is_err());
+    }
+
+    // ─── Orchestration Tests ─────────────────────────────────
+
+    use crate::orchestrator::{PlanStep, parse_plan, run_planned_turn, step_prompt};
+
+    #[test]
+    fn test_parse_plan_formats() {
+        let fenced = "Here is the plan:\n```json\n[{\"title\": \"Find callers\", \"instructions\": \"grep for foo\"}, \
+                      {\"title\": \"Rename\"}]\n```";
+        assert_eq!(
+            parse_plan(fenced),
+            vec![
+                PlanStep { title: "Find callers".to_string(), instructions: "grep for foo".to_string() },
+                PlanStep { title: "Rename".to_string(), instructions: String::new() },
+            ]
+        );
+        let listed: Vec<String> = parse_plan("Plan:\n1. Read the file\n2) Fix it\n- Test it\n\nThanks")
+            .into_iter()
+            .map(|step| step.title)
+            .collect();
+        assert_eq!(listed, vec!["Read the file", "Fix it", "Test it"]);
+        assert!(parse_plan("I will just do it.").is_empty());
+    }
+
+    #[test]
+    fn test_step_prompt_shows_plan_and_results() {
+        let steps = vec![
+            PlanStep { title: "Find".to_string(), instructions: String::new() },
+            PlanStep { title: "Fix".to_string(), instructions: "Only src/".to_string() },
+        ];
+        let prompt = step_prompt("Fix foo", &steps, 1, &["found 3".to_string()]);
+        assert!(prompt.contains("Fix foo"));
+        assert!(prompt.contains("1. Find\n2. Fix"));
+        assert!(prompt.contains("### 1. Find\nfound 3"));
+        assert!(prompt.contains("Now do step 2: Fix\nOnly src/"));
+    }
+
+    #[test]
+    fn test_planned_turn_runs_steps_in_executors() {
+        let bus = EventBus::new();
+        let mut config = AgentConfig::default();
+        config.plan.enabled = true;
+        config.plan.planner_model = "planner".to_string();
+        config.plan.executor_model = "executor".to_string();
+        let mut runtime = AgentRuntime::new(config, bus.clone());
+        let llm = MockLlmScript::new(vec![
+            Message::assistant(r#"[{"title": "List files"}, {"title": "Report"}]"#),
+            bash_calls(&["ls"]),
+            Message::assistant("Listed 2 files"),
+            Message::assistant("Reported"),
+            Message::assistant("All done"),
+        ]);
+        let vfs = MockVfs::new();
+
+        block_on(run_planned_turn(&mut runtime, "Look around", &[], &llm, &MockShell, &vfs)).unwrap();
+
+        let requests = llm.requests.borrow();
+        let models: Vec<&str> = requests.iter().map(|r| r.model.as_str()).collect();
+        assert_eq!(models, vec!["planner", "executor", "executor", "executor", "planner"]);
+        assert!(requests[0].tools.is_empty());
+        assert!(!requests[1].tools.is_empty());
+        let second_step = requests[3].messages.last().unwrap().content.as_text().to_string();
+        assert!(second_step.contains("Listed 2 files") && second_step.contains("Now do step 2: Report"));
+        let answer_request = requests[4].messages.last().unwrap().content.as_text().to_string();
+        assert!(answer_request.contains("### 2. Report\nReported"));
+
+        // The conversation keeps the request and the answer, with the
+        // usage of all five requests
+        let history = runtime.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].content.as_text(), "All done");
+        assert_eq!(history[1].meta.as_ref().unwrap().usage.as_ref().unwrap().total_tokens, 75);
+        assert_eq!(runtime.state, AgentState::Idle);
+
+        let events = bus.drain();
+        assert!(events.iter().any(|e| matches!(e, AgentEvent::PlanReady { steps } if steps.len() == 2)));
+        assert!(events.iter().any(|e| matches!(e, AgentEvent::ToolExecStart { tool_name, .. } if tool_name == "bash")));
+        let ends: Vec<bool> = events
+            .iter()
+            .filter_map(|e| match e {
+                AgentEvent::PlanStepEnd { success, .. } => Some(*success),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ends, vec![true, true]);
+        assert!(matches!(events.last(), Some(AgentEvent::TurnEnd { .. })));
+    }
+
+    // ─── Persona Tests ───────────────────────────────────────
+
+    use agent_types::session::SessionConfig;
+
+    #[test]
+    fn test_persona_limits_offered_tools() {
+        let config = SessionConfig { persona: Some("Code Reviewer".to_string()), ..Default::default() }
+            .apply(&AgentConfig::default());
+        let mut runtime = AgentRuntime::new(config, EventBus::new());
+        let llm = MockLlmRecorder::new();
+        block_on(runtime.run_turn("Review src/lib.rs", &llm, &MockShell, &MockVfs::new())).unwrap();
+
+        let requests = llm.requests.borrow();
+        let mut offered: Vec<&str> = requests[0].tools.iter().map(|t| t.name.as_str()).collect();
+        offered.sort();
+        assert_eq!(offered, vec!["list_dir", "read_file"]);
+
+        let config = SessionConfig { persona: Some("Writer".to_string()), ..Default::default() }
+            .apply(&AgentConfig::default());
+        let runtime = AgentRuntime::new(config, EventBus::new());
+        assert!(runtime.allowed_tools().is_empty());
+    }
+
+    #[test]
+    fn test_persona_rejects_disallowed_tool_call() {
+        let config = SessionConfig { persona: Some("Code Reviewer".to_string()), ..Default::default() }
+            .apply(&AgentConfig::default());
+        let mut runtime = AgentRuntime::new(config, EventBus::new());
+        let vfs = MockVfs::new();
+        let llm = MockLlmRecorder::calling("write_file", r#"{"path":"/ws/a.txt","content":"x"}"#);
+        block_on(runtime.run_turn("Fix it", &llm, &MockShell, &vfs)).unwrap();
+
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        assert_eq!(result.content.as_text(), "The write_file tool is not available in this session.");
+        assert!(block_on(vfs.read_file("/ws/a.txt")).is_err());
+    }
+
+    // ─── Web Tests ───────────────────────────────────────────
+
+    use crate::web::{WebReader, cache_path, cap_text, decode_entities, extract_html, page_text, resolve_url};
+    use agent_types::config::WebConfig;
+
+    const ARTICLE_PAGE: &str = r#"<!DOCTYPE html>
+<html><head><title>Borrowing &amp; Lifetimes</title>
+<style>body { color: red; }</style>
+<script>var tracking = "<p>not text</p>";</script></head>
+<body class="nav-open">
+<nav><a href="/">Home</a> <a href="/blog">Blog</a></nav>
+<div class="ad-slot">Buy now!</div>
+<article>
+<h1>Borrowing</h1>
+<p>A <strong>reference</strong> lets you use a value
+   without <em>owning</em> it. See <a href="/book/ch04.html">chapter 4</a>.</p>
+<ul><li>Shared: <code>&amp;T</code></li><li>Unique: <code>&amp;mut T</code></li></ul>
+<pre><code>let r = &amp;x;
+println!("{r}");</code></pre>
+<div class="share-buttons">Share this</div>
+</article>
+<aside>Related posts</aside>
+<footer>&copy; 2024</footer>
+</body></html>"#;
+
+    #[test]
+    fn test_extract_html_keeps_main_content() {
+        let (title, text) = extract_html(ARTICLE_PAGE, "https://example.com/blog/borrowing");
+        assert_eq!(title.as_deref(), Some("Borrowing & Lifetimes"));
+        assert_eq!(
+            text,
+            "# Borrowing\n\n\
+             A **reference** lets you use a value without _owning_ it. \
+             See [chapter 4](https://example.com/book/ch04.html).\n\n\
+             - Shared: `&T`\n\
+             - Unique: `&mut T`\n\n\
+             ```\nlet r = &x;\nprintln!(\"{r}\");\n```"
+        );
+    }
+
+    #[test]
+    fn test_extract_html_without_article_uses_body() {
+        let html = "<html><body><header><h2>Site</h2></header><div id=\"sidebar\">Links</div>\
+                    <div class=\"content has-sidebar\"><p>Hello&nbsp;world</p><ol start=\"3\"><li>three</li>\
+                    <li>four</li></ol></div><table><tr><td>a</td><td>b</td></tr></table></body></html>";
+        let (title, text) = extract_html(html, "https://example.com/");
+        assert_eq!(title, None);
+        assert_eq!(text, "## Site\n\nHello world\n\n3. three\n4. four\n\na | b");
+    }
+
+    #[test]
+    fn test_resolve_url_and_entities() {
+        let base = "https://example.com/docs/guide/intro.html?lang=en";
+        assert_eq!(resolve_url(base, "setup.html"), "https://example.com/docs/guide/setup.html");
+        assert_eq!(resolve_url(base, "/about"), "https://example.com/about");
+        assert_eq!(resolve_url(base, "//cdn.example.com/x.js"), "https://cdn.example.com/x.js");
+        assert_eq!(resolve_url(base, "https://other.org/"), "https://other.org/");
+        assert_eq!(resolve_url(base, "mailto:me@example.com"), "mailto:me@example.com");
+        assert_eq!(resolve_url("https://example.com", "a"), "https://example.com/a");
+
+        assert_eq!(decode_entities("a &lt;b&gt; &#65;&#x42; &rsquo; &unknown; & c"), "a <b> AB ’ &unknown; & c");
+    }
+
+    #[test]
+    fn test_cap_text() {
+        assert_eq!(cap_text("short", 100), "short");
+        assert_eq!(cap_text("short", 0), "short");
+        let text = format!("{}\n\n{}", "a".repeat(80), "b".repeat(80));
+        assert_eq!(cap_text(&text, 100), format!("{}\n\n[Truncated: showing 80 of 162 characters]", "a".repeat(80)));
+        // Without a paragraph break in the second half the cut is exact
+        assert!(cap_text(&"é".repeat(50), 10).starts_with(&format!("{}\n\n[Truncated: showing 10 of 50", "é".repeat(10))));
+    }
+
+    #[test]
+    fn test_page_text_by_content_type() {
+        let page = |content_type: &str, body: &str| FetchedPage {
+            url: "https://example.com/x".to_string(),
+            content_type: content_type.to_string(),
+            body: body.to_string(),
+        };
+        let text = page_text(&page("text/html; charset=utf-8", "<title>T</title><p>Hi</p>")).unwrap();
+        assert_eq!(text, "# T\nSource: https://example.com/x\n\nHi");
+        let text = page_text(&page("application/json", "{\"a\": 1}\n")).unwrap();
+        assert_eq!(text, "Source: https://example.com/x\n\n{\"a\": 1}");
+        let text = page_text(&page("text/html", "<div id=\"root\"></div><script>render()</script>")).unwrap();
+        assert!(text.ends_with("[The page has no readable text; it may need JavaScript to show its content]"));
+        let err = page_text(&page("image/png", "\u{89}PNG")).unwrap_err();
+        assert!(err.to_string().contains("image/png"));
+    }
+
+    #[test]
+    fn test_cache_path() {
+        assert_eq!(
+            cache_path("/ws/web/", "https://example.com/a/b?q=1"),
+            format!("/ws/web/example.com-a-b-q-1-{:08x}.md", crate::retrieval::content_hash(b"https://example.com/a/b?q=1") as u32)
+        );
+    }
+
+    /// Fetcher that serves one page and counts requests
+    struct MockFetch {
+        page: FetchedPage,
+        calls: std::cell::Cell<usize>,
+    }
+
+    #[async_trait(?Send)]
+    impl FetchPort for MockFetch {
+        async fn fetch(&self, url: &str) -> agent_types::Result<FetchedPage> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(FetchedPage { url: url.to_string(), ..self.page.clone() })
+        }
+    }
+
+    fn article_fetch() -> std::rc::Rc<MockFetch> {
+        std::rc::Rc::new(MockFetch {
+            page: FetchedPage {
+                url: String::new(),
+                content_type: "text/html".to_string(),
+                body: ARTICLE_PAGE.to_string(),
+            },
+            calls: std::cell::Cell::new(0),
+        })
+    }
+
+    #[test]
+    fn test_read_url_tool() {
+        let fetch = article_fetch();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        assert!(runtime.tools.get("read_url").is_none());
+        runtime.set_web_reader(Some(std::rc::Rc::new(WebReader::new(fetch.clone()))));
+        assert!(runtime.tools.get("read_url").is_some());
+
+        let llm = MockLlmRecorder::calling("read_url", r#"{"url":"https://example.com/blog/borrowing"}"#);
+        block_on(runtime.run_turn("Summarize it", &llm, &MockShell, &MockVfs::new())).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        let text = result.content.as_text();
+        assert!(text.starts_with("# Borrowing & Lifetimes\nSource: https://example.com/blog/borrowing\n\n# Borrowing"));
+        assert!(!text.contains("Buy now") && !text.contains("tracking") && !text.contains("Related"));
+        assert_eq!(fetch.calls.get(), 1);
+
+        let llm = MockLlmRecorder::calling("read_url", r#"{"url":"file:///etc/passwd"}"#);
+        block_on(runtime.run_turn("Read this", &llm, &MockShell, &MockVfs::new())).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        assert!(result.content.as_text().contains("is not an http(s) URL"));
+        assert_eq!(fetch.calls.get(), 1);
+    }
+
+    #[test]
+    fn test_read_url_caches_in_vfs() {
+        let fetch = article_fetch();
+        let reader = WebReader::new(fetch.clone());
+        let vfs = MockVfs::new();
+        let config = WebConfig { cache: true, cache_dir: "/ws/web".to_string(), max_chars: 40, ..Default::default() };
+        let url = "https://example.com/blog/borrowing";
+
+        let first = block_on(reader.read(url, false, &config, &vfs)).unwrap();
+        assert!(first.contains("[Truncated: showing"));
+        // The whole text is kept; only what the model sees is capped
+        let cached = block_on(vfs.read_file(&cache_path("/ws/web", url))).unwrap();
+        assert!(String::from_utf8(cached).unwrap().contains("&mut T"));
+
+        let second = block_on(reader.read(url, false, &config, &vfs)).unwrap();
+        assert!(second.starts_with("[Cached copy from /ws/web/"));
+        assert_eq!(fetch.calls.get(), 1);
+
+        block_on(reader.read(url, true, &config, &vfs)).unwrap();
+        assert_eq!(fetch.calls.get(), 2);
+
+        // Without caching nothing is kept
+        let vfs = MockVfs::new();
+        let config = WebConfig { cache_dir: "/ws/web".to_string(), ..Default::default() };
+        block_on(reader.read(url, false, &config, &vfs)).unwrap();
+        assert!(block_on(vfs.read_file(&cache_path("/ws/web", url))).is_err());
+    }
+
+    // ─── Replace Tests ───────────────────────────────────────
+
+    use crate::replace::{Replacer, format_diff, glob_match};
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.rs", "src/deep/lib.rs"));
+        assert!(!glob_match("*.rs", "src/lib.rsx"));
+        assert!(glob_match("src/*.rs", "src/lib.rs"));
+        assert!(!glob_match("src/*.rs", "src/deep/lib.rs"));
+        assert!(glob_match("src/**/*.rs", "src/lib.rs"));
+        assert!(glob_match("src/**/*.rs", "src/a/b/lib.rs"));
+        assert!(!glob_match("src/**/*.rs", "tests/lib.rs"));
+        assert!(glob_match("**", "any/thing.txt"));
+        assert!(glob_match("*.{md,txt}", "docs/notes.txt"));
+        assert!(glob_match("./docs/?.md", "docs/a.md"));
+        assert!(!glob_match("docs/?.md", "docs/ab.md"));
+    }
+
+    #[test]
+    fn test_replacer_groups_hunks_by_line() {
+        let replacer = Replacer::new(r"old_(\w+)\(", "new_$1(", false).unwrap();
+        let text = "fn a() { old_call(1); old_call(2); }\nlet x = 1;\n\nold_other(3);\n";
+        let (new_text, count, hunks) = replacer.apply(text).unwrap();
+        assert_eq!(new_text, "fn a() { new_call(1); new_call(2); }\nlet x = 1;\n\nnew_other(3);\n");
+        assert_eq!(count, 3);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].line, hunks[0].old.as_str()), (1, "fn a() { old_call(1); old_call(2); }"));
+        assert_eq!((hunks[1].line, hunks[1].new.as_str()), (4, "new_other(3);"));
+
+        // Literal patterns and replacements are taken as written
+        let literal = Replacer::new("a.b($1)", "$x", true).unwrap();
+        assert_eq!(literal.apply("a.b($1) axb($1)").unwrap().0, "$x axb($1)");
+        // Matches replaced with themselves are not changes
+        assert!(Replacer::new("same", "same", true).unwrap().apply("same").is_none());
+        assert!(Replacer::new("(unclosed", "", false).is_err());
+        assert!(Replacer::new("", "x", true).is_err());
+    }
+
+    #[test]
+    fn test_format_diff_is_capped() {
+        let replacer = Replacer::new("a", "b", true).unwrap();
+        let (new_text, count, hunks) = replacer.apply("a\nx\na\n").unwrap();
+        let change = crate::replace::FileChange { path: "/ws/f.txt".to_string(), count, hunks, new_text };
+        assert_eq!(
+            format_diff(std::slice::from_ref(&change), 100),
+            "--- /ws/f.txt (2 changes)\n@@ line 1 @@\n-a\n+b\n@@ line 3 @@\n-a\n+b"
+        );
+        assert_eq!(format_diff(&[change], 2), "--- /ws/f.txt (2 changes)\n@@ line 1 @@\n[… 5 more diff lines]");
+    }
+
+    fn replace_call(arguments: &str) -> Message {
+        let mut message = Message::assistant("");
+        message.tool_calls = vec![ToolCallRequest {
+            id: "call_0".to_string(),
+            function: FunctionCall { name: "replace_in_files".to_string(), arguments: arguments.to_string() },
+        }];
+        message
+    }
+
+    fn replace_workspace() -> MockVfs {
+        let vfs = MockVfs::new();
+        block_on(async {
+            vfs.write_file("/workspace/src/main.rs", b"use log::info;\ninfo!(\"hi\");\n").await.unwrap();
+            vfs.write_file("/workspace/src/util/mod.rs", b"pub fn f() { info!(\"f\"); }\n").await.unwrap();
+            vfs.write_file("/workspace/README.md", b"Call info!(...) to log.\n").await.unwrap();
+            vfs.write_file("/workspace/.git/config", b"info!(\n").await.unwrap();
+        });
+        vfs
+    }
+
+    #[test]
+    fn test_replace_in_files_dry_run_writes_nothing() {
+        let vfs = replace_workspace();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let call = replace_call(r#"{"pattern":"info!","replacement":"debug!","glob":"**/*.rs","literal":true,"dry_run":true}"#);
+        let llm = MockLlmScript::new(vec![call, Message::assistant("Done")]);
+        block_on(runtime.run_turn("Preview", &llm, &MockShell, &vfs)).unwrap();
+
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        let text = result.content.as_text();
+        assert!(text.starts_with("Dry run: 2 replacements in 2 of 2 files; nothing was written."), "{}", text);
+        assert!(text.contains("--- /workspace/src/util/mod.rs (1 change)\n@@ line 1 @@\n-pub fn f() { info!(\"f\"); }"));
+        assert!(block_on(vfs.read_file("/workspace/src/main.rs")).unwrap().starts_with(b"use log::info;\ninfo!"));
+    }
+
+    #[test]
+    fn test_replace_in_files_writes_and_can_be_undone() {
+        let vfs = replace_workspace();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let undo = Rc::new(UndoJournal::new(Rc::new(MockStorage::new())));
+        runtime.set_undo(Some(undo.clone()));
+        let call = replace_call(r#"{"pattern":"info!\\(\"(\\w+)\"\\)","replacement":"debug!(\"[$1]\")"}"#);
+        let llm = MockLlmScript::new(vec![call, Message::assistant("Done")]);
+        block_on(runtime.run_turn("Rename the macro", &llm, &MockShell, &vfs)).unwrap();
+
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        // README.md is searched but has no match; .git is hidden
+        assert!(result.content.as_text().starts_with("Made 2 replacements in 2 of 3 files."), "{}", result.content.as_text());
+        assert_eq!(block_on(vfs.read_file("/workspace/src/main.rs")).unwrap(), b"use log::info;\ndebug!(\"[hi]\");\n");
+        assert_eq!(block_on(vfs.read_file("/workspace/.git/config")).unwrap(), b"info!(\n");
+
+        let call = replace_call(r#"{"pattern":"nothing here","replacement":"x","literal":true}"#);
+        let llm = MockLlmScript::new(vec![call, Message::assistant("Done")]);
+        block_on(runtime.run_turn("Again", &llm, &MockShell, &vfs)).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        assert_eq!(result.content.as_text(), "No matches in the 3 files matching ** under /workspace");
+
+        let undone = block_on(undo.undo(&vfs, UndoScope::Turn)).unwrap();
+        assert_eq!(undone.len(), 2);
+        assert_eq!(block_on(vfs.read_file("/workspace/src/main.rs")).unwrap(), b"use log::info;\ninfo!(\"hi\");\n");
+    }
+
+    // ─── JavaScript Tests ────────────────────────────────────
+
+    use crate::js::{JsRunner, format_run};
+
+    struct MockJs {
+        outcome: fn(&str) -> agent_types::Result<JsRun>,
+        timeouts: RefCell<Vec<u32>>,
+    }
+
+    #[async_trait(?Send)]
+    impl JsPort for MockJs {
+        async fn run(&self, code: &str, timeout_ms: u32) -> agent_types::Result<JsRun> {
+            self.timeouts.borrow_mut().push(timeout_ms);
+            (self.outcome)(code)
+        }
+    }
+
+    fn mock_js(outcome: fn(&str) -> agent_types::Result<JsRun>) -> Rc<MockJs> {
+        Rc::new(MockJs { outcome, timeouts: RefCell::new(Vec::new()) })
+    }
+
+    fn run_js_turn(js: Rc<MockJs>, code: &str) -> (String, bool) {
+        let bus = EventBus::new();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), bus.clone());
+        runtime.set_js_runner(Some(Rc::new(JsRunner::new(js))));
+        let success = Rc::new(RefCell::new(None));
+        let sink = success.clone();
+        bus.subscribe(move |e| {
+            if let AgentEvent::ToolExecEnd { success, .. } = e {
+                *sink.borrow_mut() = Some(*success);
+            }
+        });
+        let args = serde_json::json!({ "code": code }).to_string();
+        let llm = MockLlmRecorder::calling("run_js", &args);
+        block_on(runtime.run_turn("Run it", &llm, &MockShell, &MockVfs::new())).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        let success = success.borrow().unwrap();
+        (result.content.as_text().to_string(), success)
+    }
+
+    #[test]
+    fn test_format_run() {
+        let run = JsRun {
+            console: vec!["sorting".to_string(), "[warn] 2 duplicates".to_string()],
+            result: Some("[1, 2, 3]".to_string()),
+            error: None,
+        };
+        assert_eq!(format_run(&run, 0), "Console:\nsorting\n[warn] 2 duplicates\n\nResult: [1, 2, 3]");
+
+        let thrown = JsRun { error: Some("TypeError: x is undefined".to_string()), ..JsRun::default() };
+        assert_eq!(format_run(&thrown, 0), "Uncaught TypeError: x is undefined");
+        assert!(format_run(&JsRun::default(), 0).contains("without logging anything"));
+
+        let long = JsRun { result: Some("x".repeat(100)), ..JsRun::default() };
+        assert!(format_run(&long, 20).contains("[Truncated: showing 20 of 108 characters]"));
+    }
+
+    #[test]
+    fn test_run_js_tool() {
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        assert!(runtime.tools.get("run_js").is_none());
+        runtime.set_js_runner(Some(Rc::new(JsRunner::new(mock_js(|_| Ok(JsRun::default()))))));
+        assert!(runtime.tools.get("run_js").is_some());
+        runtime.set_js_runner(None);
+        assert!(runtime.tools.get("run_js").is_none());
+
+        let js = mock_js(|code| {
+            Ok(JsRun { console: vec![format!("ran {} chars", code.len())], result: Some("6".to_string()), error: None })
+        });
+        let (output, success) = run_js_turn(js.clone(), "[1, 2, 3].reduce((a, b) => a + b)");
+        assert_eq!(output, "Console:\nran 33 chars\n\nResult: 6");
+        assert!(success);
+        assert_eq!(*js.timeouts.borrow(), vec![5_000]);
+
+        // A script that throws ran, but the call failed
+        let js = mock_js(|_| Ok(JsRun { error: Some("SyntaxError: Unexpected token".to_string()), ..JsRun::default() }));
+        let (output, success) = run_js_turn(js, "let =");
+        assert_eq!(output, "Uncaught SyntaxError: Unexpected token");
+        assert!(!success);
+    }
+
+    #[test]
+    fn test_run_js_timeout_and_empty_code() {
+        let js = mock_js(|_| Err(AgentError::Timeout(5_000)));
+        let (output, success) = run_js_turn(js, "while (true) {}");
+        assert!(output.contains("stopped after 5000 ms"), "{}", output);
+        assert!(!success);
+
+        let js = mock_js(|_| Ok(JsRun::default()));
+        let (output, success) = run_js_turn(js.clone(), "  ");
+        assert!(output.contains("no code to run"));
+        assert!(!success);
+        assert!(js.timeouts.borrow().is_empty());
+
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let llm = MockLlmRecorder::calling("run_js", r#"{"code":"1"}"#);
+        block_on(runtime.run_turn("Run it", &llm, &MockShell, &MockVfs::new())).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        assert_eq!(result.content.as_text(), "Running JavaScript is not enabled");
+    }
+
+    // ─── SQL Tests ───────────────────────────────────────────
+
+    use crate::sql::{SqlDatabases, csv_to_sql, format_rows, parse_csv, table_name};
+
+    type SqlOutcome = fn(Option<&[u8]>, &str) -> agent_types::Result<SqlOutput>;
+
+    struct MockSql {
+        outcome: SqlOutcome,
+        calls: RefCell<Vec<(Option<Vec<u8>>, String)>>,
+    }
+
+    #[async_trait(?Send)]
+    impl SqlPort for MockSql {
+        async fn exec(&self, image: Option<Vec<u8>>, sql: &str, _timeout_ms: u32) -> agent_types::Result<SqlOutput> {
+            let outcome = (self.outcome)(image.as_deref(), sql);
+            self.calls.borrow_mut().push((image, sql.to_string()));
+            outcome
+        }
+    }
+
+    /// Writes append to the database file; a SELECT returns one row
+    fn mock_sql() -> Rc<MockSql> {
+        Rc::new(MockSql {
+            outcome: |image, sql| {
+                let mut image = image.unwrap_or_default().to_vec();
+                if sql.contains("INSERT") || sql.contains("UPDATE") {
+                    image.extend_from_slice(b"+");
+                }
+                let results = if sql.contains("SELECT") {
+                    vec![SqlRows {
+                        columns: vec!["region".to_string(), "total".to_string()],
+                        values: vec![vec![serde_json::json!("north"), serde_json::json!(42.5)]],
+                    }]
+                } else {
+                    Vec::new()
+                };
+                Ok(SqlOutput { results, changes: 2, image })
+            },
+            calls: RefCell::new(Vec::new()),
+        })
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let text = "\u{feff}name,notes\r\n\"Smith, J\",\"said \"\"hi\"\"\nthen left\"\r\n\r\nLee,\n";
+        assert_eq!(
+            parse_csv(text, ','),
+            vec![
+                vec!["name".to_string(), "notes".to_string()],
+                vec!["Smith, J".to_string(), "said \"hi\"\nthen left".to_string()],
+                vec!["Lee".to_string(), String::new()],
+            ]
+        );
+        assert_eq!(parse_csv("a\tb\n1\t2", '\t'), vec![vec!["a", "b"], vec!["1", "2"]]);
+        assert!(parse_csv("", ',').is_empty());
+    }
+
+    #[test]
+    fn test_csv_to_sql_infers_column_types() {
+        let text = "id,zip,price,name,,Name\n1,02134,9.5,O'Brien,x,a\n2,10001,12,\"Say \"\"hi\"\"\",,b\n3\n";
+        let import = csv_to_sql("orders", text, ',').unwrap();
+        assert_eq!(import.rows, 3);
+        assert_eq!(
+            import.columns,
+            vec![
+                ("id".to_string(), "INTEGER"),
+                ("zip".to_string(), "TEXT"),
+                ("price".to_string(), "REAL"),
+                ("name".to_string(), "TEXT"),
+                ("column5".to_string(), "TEXT"),
+                ("Name_2".to_string(), "TEXT"),
+            ]
+        );
+        assert!(import.sql.starts_with("BEGIN;\nDROP TABLE IF EXISTS \"orders\";\n"));
+        assert!(import.sql.contains(
+            "CREATE TABLE \"orders\" (\"id\" INTEGER, \"zip\" TEXT, \"price\" REAL, \"name\" TEXT, \"column5\" TEXT, \"Name_2\" TEXT);"
+        ));
+        assert!(import.sql.contains("(1, '02134', 9.5, 'O''Brien', 'x', 'a'),\n"));
+        assert!(import.sql.contains("(2, '10001', 12, 'Say \"hi\"', NULL, 'b'),\n"));
+        assert!(import.sql.contains("(3, NULL, NULL, NULL, NULL, NULL);\nCOMMIT;\n"));
+
+        assert!(csv_to_sql("t", "\n\n", ',').is_err());
+        assert_eq!(table_name("/workspace/data/Q1 sales.csv"), "Q1_sales");
+    }
+
+    #[test]
+    fn test_format_rows() {
+        let rows = SqlRows {
+            columns: vec!["a".to_string(), "b|c".to_string()],
+            values: vec![
+                vec![serde_json::json!(1), serde_json::Value::Null],
+                vec![serde_json::json!(2), serde_json::json!("x\ny")],
+                vec![serde_json::json!(3), serde_json::json!("z")],
+            ],
+        };
+        assert_eq!(format_rows(&rows, 10), "| a | b\\|c |\n|---|---|\n| 1 | NULL |\n| 2 | x y |\n| 3 | z |\n(3 rows)");
+        assert!(format_rows(&rows, 2).ends_with("| 2 | x y |\n(showing 2 of 3 rows)"));
+    }
+
+    #[test]
+    fn test_sql_tool_loads_csv_and_keeps_database() {
+        let engine = mock_sql();
+        let storage = Rc::new(MockStorage::new());
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        assert!(runtime.tools.get("sql").is_none());
+        runtime.set_sql(Some(Rc::new(SqlDatabases::new(engine.clone(), storage.clone()))));
+        assert!(runtime.tools.get("sql").is_some());
+
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/workspace/sales.csv", b"region,amount\nnorth,40\nnorth,2.5\n")).unwrap();
+        let args = r#"{"csv_path":"/workspace/sales.csv","query":"SELECT region, sum(amount) AS total FROM sales"}"#;
+        let llm = MockLlmRecorder::calling("sql", args);
+        block_on(runtime.run_turn("Total by region?", &llm, &MockShell, &vfs)).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        assert_eq!(
+            result.content.as_text(),
+            "Loaded 2 rows from /workspace/sales.csv into table \"sales\" (region TEXT, amount REAL)\n\n\
+             | region | total |\n|---|---|\n| north | 42.5 |\n(1 row)"
+        );
+        {
+            let calls = engine.calls.borrow();
+            assert_eq!(calls[0].0, None);
+            assert!(calls[0].1.contains("CREATE TABLE \"sales\""));
+            assert!(calls[0].1.ends_with("COMMIT;\nSELECT region, sum(amount) AS total FROM sales"));
+        }
+        assert_eq!(block_on(storage.get("sql:main")).unwrap(), Some(b"+".to_vec()));
+        let data = result.meta.as_ref().and_then(|m| m.tool_data.as_ref()).unwrap();
+        assert_eq!(data.mime_type.as_deref(), Some("text/markdown"));
+        let json = data.json.as_ref().unwrap();
+        assert_eq!(json["results"][0]["values"][0][1], 42.5);
+        assert_eq!(json["results"][0]["total_rows"], 1);
+
+        // The stored database is used by the next call
+        let llm = MockLlmRecorder::calling("sql", r#"{"query":"UPDATE sales SET amount = 0"}"#);
+        block_on(runtime.run_turn("Zero them", &llm, &MockShell, &vfs)).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        assert_eq!(result.content.as_text(), "Done; 2 rows changed");
+        assert_eq!(engine.calls.borrow()[1].0, Some(b"+".to_vec()));
+        assert_eq!(block_on(storage.get("sql:main")).unwrap(), Some(b"++".to_vec()));
+    }
+
+    #[test]
+    fn test_sql_tool_errors() {
+        let engine = mock_sql();
+        let databases = SqlDatabases::new(engine.clone(), Rc::new(MockStorage::new()));
+        let config = agent_types::config::SqlConfig::default();
+        let vfs = MockVfs::new();
+        let call = |args: serde_json::Value| block_on(databases.call(&args, &config, &vfs)).unwrap_err().to_string();
+        assert!(call(serde_json::json!({})).contains("give a query"));
+        assert!(call(serde_json::json!({"query": "SELECT 1", "database": "my db"})).contains("is not a database name"));
+        assert!(call(serde_json::json!({"csv_path": "/workspace/missing.csv"})).contains("missing.csv"));
+        assert!(engine.calls.borrow().is_empty());
+
+        let slow = Rc::new(MockSql { outcome: |_, _| Err(AgentError::Timeout(30_000)), calls: RefCell::new(Vec::new()) });
+        let databases = SqlDatabases::new(slow, Rc::new(MockStorage::new()));
+        let error = block_on(databases.call(&serde_json::json!({"query": "SELECT 1"}), &config, &vfs)).unwrap_err();
+        assert!(error.to_string().contains("stopped after 30000 ms"));
+
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), EventBus::new());
+        let llm = MockLlmRecorder::calling("sql", r#"{"query":"SELECT 1"}"#);
+        block_on(runtime.run_turn("Query", &llm, &MockShell, &vfs)).unwrap();
+        let result = runtime.history().iter().rfind(|m| m.role == Role::Tool).unwrap();
+        assert_eq!(result.content.as_text(), "SQL databases are not enabled");
+    }
+
+    // ─── Tool Result Data Tests ──────────────────────────────
+
+    /// The data of each tool result, from the messages and from the
+    /// `ToolExecEnd` events
+    fn run_for_data(runtime: &mut AgentRuntime, bus: &EventBus, calls: Message, vfs: &MockVfs) -> Vec<Option<ToolResultData>> {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let sink = events.clone();
+        bus.subscribe(move |e| {
+            if let AgentEvent::ToolExecEnd { data, .. } = e {
+                sink.borrow_mut().push(data.clone());
+            }
+        });
+        let llm = MockLlmScript::new(vec![calls, Message::assistant("Done")]);
+        block_on(runtime.run_turn("Go", &llm, &MockShell, vfs)).unwrap();
+        let stored: Vec<Option<ToolResultData>> = runtime
+            .history()
+            .iter()
+            .filter(|m| m.role == Role::Tool)
+            .map(|m| m.meta.as_ref().and_then(|meta| meta.tool_data.clone()))
+            .collect();
+        assert_eq!(stored, *events.borrow());
+        stored
+    }
+
+    #[test]
+    fn test_tool_results_carry_data() {
+        let bus = EventBus::new();
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), bus.clone());
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/workspace/a.rs", b"let x = old;\n")).unwrap();
+        let mut calls = Message::assistant("");
+        calls.tool_calls = [
+            ("bash", r#"{"command":"ls"}"#),
+            ("write_file", r#"{"path":"/workspace/notes.md","content":"hi"}"#),
+            ("replace_in_files", r#"{"pattern":"old","replacement":"new"}"#),
+            ("read_file", r#"{"path":"/workspace/notes.md"}"#),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (name, arguments))| ToolCallRequest {
+            id: format!("call_{}", i),
+            function: FunctionCall { name: name.to_string(), arguments: arguments.to_string() },
+        })
+        .collect();
+
+        let data = run_for_data(&mut runtime, &bus, calls, &vfs);
+        let bash = data[0].as_ref().unwrap();
+        assert_eq!((bash.exit_code, bash.mime_type.as_deref()), (Some(0), Some("text/plain")));
+        assert_eq!(data[1].as_ref().unwrap().artifacts, vec!["/workspace/notes.md".to_string()]);
+        let replace = data[2].as_ref().unwrap();
+        assert_eq!(replace.artifacts, vec!["/workspace/a.rs".to_string()]);
+        assert_eq!(replace.json.as_ref().unwrap()["replacements"], 1);
+        assert_eq!(replace.json.as_ref().unwrap()["dry_run"], false);
+        assert_eq!(data[3], None);
+    }
+
+    // ─── Image Artifact Tests ───────────────────────────────
+
+    #[test]
+    fn test_tools_flag_images() {
+        let bus = EventBus::new();
+        let shown = Rc::new(RefCell::new(Vec::new()));
+        let sink = shown.clone();
+        bus.subscribe(move |e| {
+            if let AgentEvent::ToolArtifact { call_id, path, mime_type } = e {
+                sink.borrow_mut().push(format!("{} {} {}", call_id, path, mime_type));
+            }
+        });
+        let mut runtime = AgentRuntime::new(AgentConfig::default(), bus.clone());
+        let vfs = MockVfs::new();
+        block_on(vfs.write_file("/workspace/plot.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")).unwrap();
+        block_on(vfs.write_file("/workspace/notes.txt", b"hi")).unwrap();
+        let mut calls = Message::assistant("");
+        calls.tool_calls = [
+            (
+                "bash",
+                r#"{"command":"python plot.py","images":["/workspace/plot.png","/workspace/missing.png","/workspace/notes.txt"]}"#,
+            ),
+            ("read_file", r#"{"path":"/workspace/plot.png"}"#),
+            ("read_file", r#"{"path":"/workspace/notes.txt"}"#),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (name, arguments))| ToolCallRequest {
+            id: format!("call_{}", i),
+            function: FunctionCall { name: name.to_string(), arguments: arguments.to_string() },
+        })
+        .collect();
+
+        let data = run_for_data(&mut runtime, &bus, calls, &vfs);
+        let plot = vec!["/workspace/plot.png".to_string()];
+        assert_eq!(data[0].as_ref().unwrap().images, plot);
+        assert_eq!(data[1].as_ref().unwrap().images, plot);
+        assert_eq!(data[2], None);
+        assert_eq!(
+            *shown.borrow(),
+            vec!["call_0 /workspace/plot.png image/png", "call_1 /workspace/plot.png image/png"]
+        );
+        let bash = runtime.history().iter().find(|m| m.role == Role::Tool).unwrap();
+        assert!(bash.content.as_text().ends_with(
+            "[images not shown: /workspace/missing.png was not found; /workspace/notes.txt is not a PNG, JPEG or \
+             BMP image]"
+        ));
+    }
+
+    // ─── Local Endpoint Tests ────────────────────────────────
+
+    use crate::endpoint::{EndpointResponse, handle, parse_request};
+    use agent_types::config::LlmConfig;
+
+    /// Answers with a tool call, streams "Hi there"
+    struct EndpointLlm;
+
+    #[async_trait(?Send)]
+    impl LlmPort for EndpointLlm {
+        async fn chat_completion(&self, req: ChatRequest) -> agent_types::Result<ChatResponse> {
+            let mut message = Message::assistant("");
+            message.tool_calls = vec![ToolCallRequest {
+                id: "call_1".to_string(),
+                function: FunctionCall { name: req.tools[0].name.clone(), arguments: "{\"city\":\"Oslo\"}".to_string() },
+            }];
+            Ok(ChatResponse { message, usage: Some(TokenUsage { prompt_tokens: 7, completion_tokens: 3, total_tokens: 10 }) })
+        }
+
+        fn stream_chat(&self, _req: ChatRequest) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+            Box::pin(futures::stream::iter(vec![
+                LlmStreamEvent::Delta("Hi".to_string()),
+                LlmStreamEvent::Delta(" there".to_string()),
+                LlmStreamEvent::Done,
+            ]))
+        }
+
+        async fn list_models(&self) -> agent_types::Result<Vec<String>> {
+            Ok(vec!["other-model".to_string()])
+        }
+    }
+
+    fn json_reply(response: EndpointResponse) -> (u16, serde_json::Value) {
+        match response {
+            EndpointResponse::Json { status, body } => (status, serde_json::from_str(&body).unwrap()),
+            EndpointResponse::Events(_) => panic!("expected a JSON reply"),
+        }
+    }
+
+    #[test]
+    fn test_endpoint_parses_openai_requests_with_configured_defaults() {
+        let config = LlmConfig::default();
+        let body = r#"{
+            "messages": [
+                {"role": "developer", "content": "Be brief"},
+                {"role": "user", "content": [{"type": "text", "text": "Weather?"}]},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "c1", "type": "function", "function": {"name": "weather", "arguments": "{}"}}
+                ]},
+                {"role": "tool", "tool_call_id": "c1", "content": "Sunny"}
+            ],
+            "tools": [{"type": "function", "function": {"name": "weather", "parameters": {
+                "type": "object", "properties": {"city": {"type": "string"}}, "required": ["city"]
+            }}}],
+            "max_tokens": 50,
+            "stop": "END",
+            "stream": true
+        }"#;
+        let (req, stream) = parse_request(body, &config).unwrap();
+        assert!(stream);
+        assert_eq!(req.model, config.model);
+        assert_eq!(req.temperature, config.temperature);
+        assert_eq!(req.max_tokens, 50);
+        assert_eq!(req.stop, vec!["END".to_string()]);
+        assert_eq!(req.messages[0].role, Role::System);
+        assert_eq!(req.messages[1].content.as_text(), "Weather?");
+        assert_eq!(req.messages[2].tool_calls[0].function.name, "weather");
+        assert_eq!(req.messages[3].tool_call_id.as_deref(), Some("c1"));
+        assert_eq!(req.tools[0].parameters.required, vec!["city".to_string()]);
+
+        assert!(parse_request(r#"{"messages": []}"#, &config).is_err());
+        assert!(parse_request(r#"{"messages": [{"role": "robot", "content": "hi"}]}"#, &config).is_err());
+    }
+
+    #[test]
+    fn test_endpoint_answers_in_openai_format() {
+        let llm: std::rc::Rc<dyn LlmPort> = std::rc::Rc::new(EndpointLlm);
+        let config = LlmConfig::default();
+        let body = r#"{"model": "m1", "messages": [{"role": "user", "content": "Weather in Oslo?"}],
+            "tools": [{"type": "function", "function": {"name": "weather"}}]}"#;
+
+        let (status, reply) = json_reply(block_on(handle(llm.clone(), &config, "POST", "/v1/chat/completions", body)));
+        assert_eq!(status, 200);
+        assert_eq!(reply["object"], "chat.completion");
+        assert_eq!(reply["model"], "m1");
+        let choice = &reply["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert!(choice["message"]["content"].is_null());
+        assert_eq!(choice["message"]["tool_calls"][0]["type"], "function");
+        assert_eq!(choice["message"]["tool_calls"][0]["function"]["arguments"], "{\"city\":\"Oslo\"}");
+        assert_eq!(reply["usage"]["total_tokens"], 10);
+
+        let (_, models) = json_reply(block_on(handle(llm.clone(), &config, "GET", "/v1/models", "")));
+        let ids: Vec<&str> = models["data"].as_array().unwrap().iter().map(|m| m["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec![config.model.as_str(), "other-model"]);
+
+        let (status, reply) = json_reply(block_on(handle(llm.clone(), &config, "POST", "/v1/chat/completions", "{")));
+        assert_eq!(status, 400);
+        assert_eq!(reply["error"]["type"], "invalid_request_error");
+        assert_eq!(json_reply(block_on(handle(llm.clone(), &config, "GET", "/v1/chat/completions", ""))).0, 405);
+        assert_eq!(json_reply(block_on(handle(llm, &config, "GET", "/v1/embeddings", ""))).0, 404);
+    }
+
+    #[test]
+    fn test_endpoint_streams_server_sent_events() {
+        use futures::StreamExt;
+
+        let llm: std::rc::Rc<dyn LlmPort> = std::rc::Rc::new(EndpointLlm);
+        let body = r#"{"messages": [{"role": "user", "content": "Hi"}], "stream": true}"#;
+        let EndpointResponse::Events(events) =
+            block_on(handle(llm, &LlmConfig::default(), "POST", "/v1/chat/completions", body))
+        else {
+            panic!("expected a stream");
+        };
+        let events: Vec<String> = block_on(events.collect());
+        assert_eq!(events.len(), 5);
+        assert!(events.iter().all(|e| e.starts_with("data: ") && e.ends_with("\n\n")));
+        let chunk = |i: usize| -> serde_json::Value { serde_json::from_str(&events[i][6..]).unwrap() };
+        assert_eq!(chunk(0)["choices"][0]["delta"]["role"], "assistant");
+        let text: String = (1..3).map(|i| chunk(i)["choices"][0]["delta"]["content"].as_str().unwrap().to_string()).collect();
+        assert_eq!(text, "Hi there");
+        assert_eq!(chunk(3)["choices"][0]["finish_reason"], "stop");
+        assert_eq!(events[4], "data: [DONE]\n\n");
+    }
+
+    // ─── In-App Log Tests ────────────────────────────────────
+
+    use crate::logs::{LogBuffer, LogFilter, LogRecord};
+
+    #[test]
+    fn test_log_buffer_keeps_the_newest_records() {
+        let mut buffer = LogBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(LogRecord::new(log::Level::Info, "agent_core::runtime", format!("turn {}", i)));
+        }
+        let messages: Vec<&str> = buffer.records().iter().map(|r| r.message.as_str()).collect();
+        assert_eq!(messages, vec!["turn 2", "turn 3", "turn 4"]);
+        assert_eq!(buffer.dropped(), 2);
+
+        let text = buffer.to_text();
+        assert!(text.starts_with("(2 older records were dropped)\n"));
+        assert!(text.contains(" INFO  agent_core::runtime: turn 4\n"));
+        assert_eq!(text.lines().count(), 4);
+
+        buffer.clear();
+        assert!(buffer.records().is_empty());
+        assert_eq!(buffer.dropped(), 0);
+    }
+
+    #[test]
+    fn test_log_filter_by_level_and_text() {
+        let warn = LogRecord::new(log::Level::Warn, "agent_platform::vfs", "Quota nearly used");
+        let debug = LogRecord::new(log::Level::Debug, "agent_core::runtime", "Sending request");
+        assert_eq!(warn.time().len(), 12);
+
+        let mut filter = LogFilter::default();
+        assert!(filter.matches(&warn));
+        assert!(!filter.matches(&debug));
+
+        filter.level = log::Level::Trace;
+        filter.text = "RUNTIME".to_string();
+        assert!(!filter.matches(&warn));
+        assert!(filter.matches(&debug));
+        filter.text = " quota ".to_string();
+        assert!(filter.matches(&warn));
+    }
+
+    // ─── Perf Overlay Tests ──────────────────────────────────
+
+    use crate::perf::{FRAME_HISTORY, FrameSample, FrameStats, MeteredStorage, StorageMeter};
+
+    #[test]
+    fn test_frame_stats_keep_recent_frames_and_summarize_them() {
+        let mut frames = FrameStats::default();
+        for i in 0..FRAME_HISTORY + 10 {
+            frames.push(FrameSample { interval_ms: 16.0, cpu_ms: Some(4.0), events: i % 3 });
+        }
+        frames.push(FrameSample { interval_ms: 48.0, cpu_ms: Some(30.0), events: 120 });
+        assert_eq!(frames.samples().len(), FRAME_HISTORY);
+        assert_eq!(frames.last().unwrap().events, 120);
+
+        let summary = frames.summary();
+        assert_eq!(summary.max_interval_ms, 48.0);
+        assert_eq!(summary.max_events, 120);
+        assert_eq!(summary.slow_frames, 1);
+        assert!(summary.mean_cpu_ms > 4.0 && summary.mean_cpu_ms < 5.0);
+
+        assert_eq!(FrameStats::default().summary().mean_interval_ms, 0.0);
+    }
+
+    #[test]
+    fn test_metered_storage_counts_operations_in_flight() {
+        use std::task::{Context, Poll};
+
+        /// Storage whose reads never finish
+        struct StuckStorage;
+
+        #[async_trait(?Send)]
+        impl StoragePort for StuckStorage {
+            async fn get(&self, _key: &str) -> agent_types::Result<Option<Vec<u8>>> {
+                futures::future::pending().await
+            }
+            async fn set(&self, _key: &str, _value: &[u8]) -> agent_types::Result<()> {
+                Ok(())
+            }
+            async fn delete(&self, _key: &str) -> agent_types::Result<()> {
+                Ok(())
+            }
+            async fn list_keys(&self, _prefix: &str) -> agent_types::Result<Vec<String>> {
+                Ok(Vec::new())
+            }
+            fn backend_name(&self) -> &str {
+                "stuck"
+            }
+        }
+
+        let meter = std::rc::Rc::new(StorageMeter::default());
+        let storage = MeteredStorage::new(std::rc::Rc::new(StuckStorage), meter.clone());
+        block_on(storage.set("a", b"1")).unwrap();
+        block_on(storage.set_many(&[("b", b"2"), ("c", b"3")])).unwrap();
+        assert_eq!(meter.total(), 2);
+        assert_eq!(meter.pending(), 0);
+        assert_eq!(storage.backend_name(), "stuck");
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut read = storage.get("a");
+        assert!(matches!(read.as_mut().poll(&mut cx), Poll::Pending));
+        assert_eq!(meter.pending(), 1);
+        drop(read);
+        assert_eq!(meter.pending(), 0);
+        assert_eq!(meter.total(), 3);
+    }
 }
@@ -3,8 +3,27 @@
 //! Tools follow the OpenAI function-calling schema so they work across providers.
 
 use std::collections::HashMap;
-use agent_types::tool::{ToolDefinition, ToolParameters};
-use serde_json::{json, Map, Value};
+use agent_types::storage::detect_content_type;
+use agent_types::tool::{ParamSchema, ToolDefinition, ToolParameters};
+use serde_json::Value;
+
+/// Every tool the agent can be given: the built-ins, then those registered
+/// when undo, memory, workspace search or web reading is available
+pub const TOOL_NAMES: &[&str] = &[
+    "bash",
+    "read_file",
+    "write_file",
+    "list_dir",
+    "run_script",
+    "replace_in_files",
+    "undo_file_change",
+    "remember",
+    "recall",
+    "search_workspace",
+    "read_url",
+    "run_js",
+    "sql",
+];
 
 /// Registry of available tools
 pub struct ToolRegistry {
@@ -28,96 +47,179 @@ impl ToolRegistry {
         self.tools.values().cloned().collect()
     }
 
-    fn register(&mut self, tool: ToolDefinition) {
+    pub fn register(&mut self, tool: ToolDefinition) {
         self.tools.insert(tool.name.clone(), tool);
     }
 
+    pub fn unregister(&mut self, name: &str) {
+        self.tools.remove(name);
+    }
+
     fn register_builtins(&mut self) {
         self.register(Self::bash_tool());
         self.register(Self::read_file_tool());
         self.register(Self::write_file_tool());
         self.register(Self::list_dir_tool());
+        self.register(Self::run_script_tool());
+        self.register(Self::replace_in_files_tool());
     }
 
     fn bash_tool() -> ToolDefinition {
-        let mut props = Map::new();
-        props.insert("command".to_string(), json!({
-            "type": "string",
-            "description": "The bash command to execute"
-        }));
-        props.insert("timeout_ms".to_string(), json!({
-            "type": "integer",
-            "description": "Optional timeout in milliseconds"
-        }));
-
         ToolDefinition {
             name: "bash".to_string(),
             description: "Execute a bash command in the WASIX shell environment".to_string(),
-            parameters: ToolParameters {
-                schema_type: "object".to_string(),
-                properties: props,
-                required: vec!["command".to_string()],
-            },
+            parameters: ToolParameters::object()
+                .property("command", ParamSchema::string("The bash command to execute"), true)
+                .property(
+                    "timeout_ms",
+                    ParamSchema::integer("Optional timeout in milliseconds").range(1.0, 600_000.0),
+                    false,
+                )
+                .property(
+                    "cwd",
+                    ParamSchema::string("Optional directory to run the command in, instead of `cd dir &&`"),
+                    false,
+                )
+                .property(
+                    "stdin",
+                    ParamSchema::string(
+                        "Optional text piped to the command's standard input, e.g. a diff for \
+                         `patch -p1` or a program for `python -`",
+                    ),
+                    false,
+                )
+                .property("images", images_param(), false),
+            strict: true,
         }
     }
 
     fn read_file_tool() -> ToolDefinition {
-        let mut props = Map::new();
-        props.insert("path".to_string(), json!({
-            "type": "string",
-            "description": "Path to the file to read"
-        }));
-
         ToolDefinition {
             name: "read_file".to_string(),
-            description: "Read the contents of a file from the virtual filesystem".to_string(),
-            parameters: ToolParameters {
-                schema_type: "object".to_string(),
-                properties: props,
-                required: vec!["path".to_string()],
-            },
+            description: "Read the contents of a file from the virtual filesystem. Binary files \
+                are described (size, type, first bytes) instead of returned; PNG, JPEG and BMP \
+                images are also shown to the user. Pass offset and/or limit to read part of a \
+                large file; those lines come back numbered"
+                .to_string(),
+            parameters: ToolParameters::object()
+                .property("path", ParamSchema::string("Path to the file to read"), true)
+                .property(
+                    "offset",
+                    ParamSchema::integer("First line to read, starting at 1").range(1.0, 1e9),
+                    false,
+                )
+                .property(
+                    "limit",
+                    ParamSchema::integer("Number of lines to read").range(1.0, 1e9),
+                    false,
+                )
+                .property(
+                    "force_text",
+                    ParamSchema::boolean("Return the file as text even if it looks binary"),
+                    false,
+                ),
+            strict: true,
         }
     }
 
     fn write_file_tool() -> ToolDefinition {
-        let mut props = Map::new();
-        props.insert("path".to_string(), json!({
-            "type": "string",
-            "description": "Path to the file to write"
-        }));
-        props.insert("content".to_string(), json!({
-            "type": "string",
-            "description": "Content to write to the file"
-        }));
-
         ToolDefinition {
             name: "write_file".to_string(),
             description: "Write content to a file in the virtual filesystem".to_string(),
-            parameters: ToolParameters {
-                schema_type: "object".to_string(),
-                properties: props,
-                required: vec!["path".to_string(), "content".to_string()],
-            },
+            parameters: ToolParameters::object()
+                .property("path", ParamSchema::string("Path to the file to write"), true)
+                .property("content", ParamSchema::string("Content to write to the file"), true)
+                .property(
+                    "mode",
+                    ParamSchema::string(
+                        "overwrite (default) replaces the file, append adds to its end, \
+                         create_new fails if the file already exists",
+                    )
+                    .one_of(&["overwrite", "append", "create_new"]),
+                    false,
+                ),
+            strict: true,
         }
     }
 
     fn list_dir_tool() -> ToolDefinition {
-        let mut props = Map::new();
-        props.insert("path".to_string(), json!({
-            "type": "string",
-            "description": "Directory path to list"
-        }));
-
         ToolDefinition {
             name: "list_dir".to_string(),
             description: "List files and directories at the given path".to_string(),
-            parameters: ToolParameters {
-                schema_type: "object".to_string(),
-                properties: props,
-                required: vec!["path".to_string()],
-            },
+            parameters: ToolParameters::object()
+                .property("path", ParamSchema::string("Directory path to list"), true),
+            strict: true,
+        }
+    }
+
+    fn replace_in_files_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "replace_in_files".to_string(),
+            description: "Replace a regex (or literal text) in every file matching a glob, for mechanical \
+                refactors across many files. Returns a diff of the changed lines; use dry_run to preview \
+                without writing"
+                .to_string(),
+            parameters: ToolParameters::object()
+                .property("pattern", ParamSchema::string("Regex to find, or the exact text with literal"), true)
+                .property(
+                    "replacement",
+                    ParamSchema::string("Replacement text; $1 or ${name} insert a capture group unless literal"),
+                    true,
+                )
+                .property(
+                    "glob",
+                    ParamSchema::string(
+                        "Files to change, relative to path, e.g. src/**/*.rs or *.{md,txt}; \
+                         without a / it matches file names at any depth. Default: all files",
+                    ),
+                    false,
+                )
+                .property(
+                    "path",
+                    ParamSchema::string("Directory to search. Default: /workspace"),
+                    false,
+                )
+                .property("literal", ParamSchema::boolean("Treat pattern as plain text, not a regex"), false)
+                .property(
+                    "dry_run",
+                    ParamSchema::boolean("Only show what would change; write nothing"),
+                    false,
+                ),
+            strict: true,
         }
     }
+
+    fn run_script_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "run_script".to_string(),
+            description: "Run a script file from the virtual filesystem with bash. Put \
+                multi-step workflows in a script with write_file, then run it"
+                .to_string(),
+            parameters: ToolParameters::object()
+                .property("path", ParamSchema::string("Path to the script to run"), true)
+                .property(
+                    "args",
+                    ParamSchema::array(ParamSchema::string("An argument"), "Arguments passed to the script"),
+                    false,
+                )
+                .property(
+                    "timeout_ms",
+                    ParamSchema::integer("Optional timeout in milliseconds").range(1.0, 600_000.0),
+                    false,
+                )
+                .property("images", images_param(), false),
+            strict: true,
+        }
+    }
+}
+
+/// The `images` parameter of the shell tools
+fn images_param() -> ParamSchema {
+    ParamSchema::array(
+        ParamSchema::string("Absolute path of a PNG, JPEG or BMP file"),
+        "Image files the command writes, e.g. a saved plot or screenshot, to show the user \
+         under the result",
+    )
 }
 
 impl Default for ToolRegistry {
@@ -130,3 +232,106 @@ impl Default for ToolRegistry {
 pub fn parse_tool_args(args: &str) -> Result<Value, serde_json::Error> {
     serde_json::from_str(args)
 }
+
+/// Lines `offset..offset + limit` of `text` (1-based; `None` for the start
+/// or the end), each prefixed with its line number, plus a note on how to
+/// continue if lines remain
+pub fn read_lines(text: &str, offset: Option<usize>, limit: Option<usize>) -> String {
+    let total = text.lines().count();
+    let start = offset.unwrap_or(1).max(1);
+    if start > total {
+        return format!("[The file has {} lines; offset {} is past the end]", total, start);
+    }
+    let end = limit.map_or(total, |limit| (start - 1).saturating_add(limit).min(total));
+    let width = end.to_string().len();
+    let mut out: String = text
+        .lines()
+        .enumerate()
+        .skip(start - 1)
+        .take(end + 1 - start)
+        .map(|(i, line)| format!("{:>width$}\t{}\n", i + 1, line, width = width))
+        .collect();
+    if end < total {
+        out.push_str(&format!(
+            "[Lines {}-{} of {}; read from offset {} to continue]",
+            start,
+            end,
+            total,
+            end + 1
+        ));
+    }
+    out
+}
+
+/// Quote `s` for bash as a single word
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// A bash command that copies `script` into the shell's filesystem at
+/// `path` and runs it there, followed by `args` (already shell syntax)
+pub fn script_command(path: &str, script: &str, args: &str) -> String {
+    let mut delimiter = "AGENT_SCRIPT_EOF".to_string();
+    while script.lines().any(|line| line == delimiter) {
+        delimiter.push('_');
+    }
+    let dir = match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &path[..i],
+    };
+    let quoted = shell_quote(path);
+    let mut command = format!("mkdir -p {} && cat > {} <<'{}'\n{}", shell_quote(dir), quoted, delimiter, script);
+    if !script.ends_with('\n') {
+        command.push('\n');
+    }
+    command.push_str(&format!("{}\nbash {}", delimiter, quoted));
+    if !args.trim().is_empty() {
+        command.push(' ');
+        command.push_str(args.trim());
+    }
+    command
+}
+
+/// Bytes examined to decide whether a file is binary
+const BINARY_SNIFF_BYTES: usize = 8192;
+/// Bytes shown in the hex preview of a binary file
+const BINARY_PREVIEW_BYTES: usize = 64;
+
+/// Whether `data` looks binary: it has a NUL byte, or more than a tenth of
+/// the characters in its first bytes are not valid UTF-8
+pub fn looks_binary(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(BINARY_SNIFF_BYTES)];
+    if head.contains(&0) {
+        return true;
+    }
+    let text = String::from_utf8_lossy(head);
+    let mut chars = text.chars().count();
+    let mut invalid = text.chars().filter(|&c| c == char::REPLACEMENT_CHARACTER).count();
+    // A multi-byte character cut off at the end of the sample is fine
+    if head.len() < data.len() && text.ends_with(char::REPLACEMENT_CHARACTER) {
+        chars -= 1;
+        invalid -= 1;
+    }
+    invalid * 10 > chars
+}
+
+/// Image types the chat shows with a tool result
+pub const SHOWN_IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/bmp"];
+
+/// What read_file returns for a binary file instead of its bytes
+pub fn describe_binary(path: &str, data: &[u8]) -> String {
+    let preview = &data[..data.len().min(BINARY_PREVIEW_BYTES)];
+    let hex: Vec<String> = preview
+        .chunks(16)
+        .map(|row| row.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" "))
+        .collect();
+    format!(
+        "{} is a binary file ({} bytes, {}). First {} bytes:\n{}\n\
+         Call read_file with force_text: true to read it as text anyway.",
+        path,
+        data.len(),
+        detect_content_type(path, data).unwrap_or("unknown type"),
+        preview.len(),
+        hex.join("\n")
+    )
+}
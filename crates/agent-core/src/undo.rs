@@ -0,0 +1,155 @@
+//! Undo journal — prior contents of files the agent changed.
+//!
+//! Before a file-modifying tool runs, the runtime records what the file
+//! held (or that it did not exist) under `undo:<seq>`. Undoing writes the
+//! old contents back, newest change first. Only the most recent
+//! [`MAX_UNDO_ENTRIES`] changes are kept.
+
+use std::rc::Rc;
+
+use agent_types::{
+    Result,
+    tool::{ParamSchema, ToolDefinition, ToolParameters},
+    undo::UndoEntry,
+};
+
+use crate::ports::{StoragePort, VfsPort};
+
+const KEY_PREFIX: &str = "undo:";
+
+/// Changes kept in the journal; older ones are dropped
+pub const MAX_UNDO_ENTRIES: usize = 50;
+/// Files larger than this are not captured, so changes to them can't be undone
+pub const MAX_UNDO_FILE_BYTES: u64 = 1024 * 1024;
+
+/// How much to undo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoScope {
+    /// The most recent change
+    Last,
+    /// Every change from the turn that made the most recent change
+    Turn,
+}
+
+pub struct UndoJournal {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl UndoJournal {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    /// Capture `path` before `tool` changes it. Returns false when the file
+    /// is too large to capture.
+    pub async fn record(
+        &self,
+        vfs: &dyn VfsPort,
+        turn_id: &str,
+        tool: &str,
+        path: &str,
+    ) -> Result<bool> {
+        let previous = if vfs.exists(path).await? {
+            if vfs.stat(path).await?.size > MAX_UNDO_FILE_BYTES {
+                return Ok(false);
+            }
+            Some(vfs.read_file(path).await?)
+        } else {
+            None
+        };
+
+        let keys = self.keys().await?;
+        let seq = keys
+            .last()
+            .and_then(|k| k.strip_prefix(KEY_PREFIX)?.parse::<u64>().ok())
+            .map_or(1, |n| n + 1);
+        let entry = UndoEntry::new(seq, turn_id, tool, path, previous);
+        self.storage.set(&key(seq), &serde_json::to_vec(&entry)?).await?;
+
+        let excess = (keys.len() + 1).saturating_sub(MAX_UNDO_ENTRIES);
+        for old in &keys[..excess] {
+            self.storage.delete(old).await?;
+        }
+        Ok(true)
+    }
+
+    /// All recorded changes, oldest first. Unreadable entries are skipped.
+    pub async fn entries(&self) -> Result<Vec<UndoEntry>> {
+        let mut entries = Vec::new();
+        for key in self.keys().await? {
+            let Some(bytes) = self.storage.get(&key).await? else {
+                continue;
+            };
+            match serde_json::from_slice::<UndoEntry>(&bytes) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => log::warn!("Skipping corrupt undo entry {}: {}", key, e),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Revert the changes in `scope`, newest first, and drop them from the
+    /// journal. Returns the entries that were undone.
+    pub async fn undo(&self, vfs: &dyn VfsPort, scope: UndoScope) -> Result<Vec<UndoEntry>> {
+        let mut entries = self.entries().await?;
+        let Some(last) = entries.last() else {
+            return Ok(Vec::new());
+        };
+        let take = match scope {
+            UndoScope::Last => 1,
+            UndoScope::Turn => {
+                let turn_id = last.turn_id.clone();
+                entries.iter().rev().take_while(|e| e.turn_id == turn_id).count()
+            }
+        };
+        let undone: Vec<UndoEntry> = entries.split_off(entries.len() - take).into_iter().rev().collect();
+        for entry in &undone {
+            match &entry.previous {
+                Some(data) => vfs.write_file(&entry.path, data).await?,
+                None => vfs.delete_file(&entry.path).await?,
+            }
+            self.storage.delete(&key(entry.seq)).await?;
+        }
+        Ok(undone)
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = self.storage.list_keys(KEY_PREFIX).await?;
+        keys.sort();
+        Ok(keys)
+    }
+
+    pub fn undo_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "undo_file_change".to_string(),
+            description: "Revert files changed by write_file or replace_in_files: either the most recent change, or every change made while answering the most recent user message".to_string(),
+            parameters: ToolParameters::object().property(
+                "scope",
+                ParamSchema::string("\"last\" for the most recent change, \"turn\" for all changes from the latest turn")
+                    .one_of(&["last", "turn"]),
+                true,
+            ),
+            strict: true,
+        }
+    }
+}
+
+/// Zero-padded so keys sort in journal order
+fn key(seq: u64) -> String {
+    format!("{}{:016}", KEY_PREFIX, seq)
+}
+
+/// One line per undone change, for tool output and the status bar
+pub fn format_undone(entries: &[UndoEntry]) -> String {
+    if entries.is_empty() {
+        return "No file changes to undo".to_string();
+    }
+    entries
+        .iter()
+        .map(|e| match e.previous {
+            Some(_) => format!("Restored {}", e.path),
+            None => format!("Removed {}", e.path),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
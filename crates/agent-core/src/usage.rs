@@ -0,0 +1,39 @@
+//! All-time usage ledger — tokens and estimated cost across every session
+//! and workspace, kept under `usage:total`.
+
+use std::rc::Rc;
+
+use agent_types::{Result, pricing::UsageTotals};
+
+use crate::ports::StoragePort;
+
+const TOTAL_KEY: &str = "usage:total";
+
+pub struct UsageLedger {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl UsageLedger {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    /// Totals so far; zero if nothing has been recorded or the record is unreadable
+    pub async fn total(&self) -> Result<UsageTotals> {
+        let Some(bytes) = self.storage.get(TOTAL_KEY).await? else {
+            return Ok(UsageTotals::default());
+        };
+        Ok(serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            log::warn!("Resetting unreadable usage totals: {}", e);
+            UsageTotals::default()
+        }))
+    }
+
+    /// Add a turn's usage and return the new totals.
+    pub async fn record(&self, turn: &UsageTotals) -> Result<UsageTotals> {
+        let mut total = self.total().await?;
+        total.add(turn);
+        self.storage.set(TOTAL_KEY, &serde_json::to_vec(&total)?).await?;
+        Ok(total)
+    }
+}
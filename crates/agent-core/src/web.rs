@@ -0,0 +1,706 @@
+//! Reading web pages — the `read_url` tool.
+//!
+//! Raw HTML is mostly markup and boilerplate, so pages are cut down before
+//! the model sees them: scripts, styles, navigation, headers, footers,
+//! forms and elements whose class or id marks them as ads, banners or
+//! sidebars are dropped; the page's main content (`<main>`, or else its
+//! longest `<article>`) is kept when it marks one; and what is left is
+//! written as markdown and capped at [`WebConfig::max_chars`]. Plain text
+//! and JSON are returned as they are.
+//!
+//! With caching on, the text of each page is written to the VFS under
+//! [`WebConfig::cache_dir`] and read back instead of fetching it again.
+
+use std::rc::Rc;
+
+use agent_types::{
+    AgentError, ErrorKind, Result,
+    config::WebConfig,
+    tool::{ParamSchema, ToolDefinition, ToolParameters},
+};
+
+use crate::ports::{FetchPort, FetchedPage, VfsPort};
+use crate::retrieval::content_hash;
+
+/// Elements dropped with everything in them
+const DROPPED_TAGS: &[&str] = &[
+    "head", "title", "script", "style", "noscript", "template", "svg", "canvas", "iframe", "nav", "footer",
+    "aside", "form", "button", "select", "dialog", "menu",
+];
+
+/// Words in a class or id that mark an element as boilerplate
+const NOISE_WORDS: &[&str] = &[
+    "ad", "ads", "advert", "advertisement", "banner", "breadcrumb", "breadcrumbs", "comments", "cookie",
+    "cookies", "footer", "menu", "modal", "nav", "navbar", "newsletter", "popup", "promo", "related", "share",
+    "sharing", "sidebar", "social", "sponsored", "subscribe", "toolbar",
+];
+
+/// Words in a class or id that mark an element as content, whatever else
+/// it says (e.g. `content has-sidebar`)
+const CONTENT_WORDS: &[&str] = &["article", "body", "content", "entry", "main", "post", "story", "text"];
+
+/// Elements whose content is not markup
+const RAW_TEXT_TAGS: &[&str] = &["script", "style", "title", "textarea"];
+
+/// Elements that never have a closing tag
+const VOID_TAGS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+pub struct WebReader {
+    fetcher: Rc<dyn FetchPort>,
+}
+
+impl WebReader {
+    pub fn new(fetcher: Rc<dyn FetchPort>) -> Self {
+        Self { fetcher }
+    }
+
+    pub fn tool_definition() -> ToolDefinition {
+        ToolDefinition {
+            name: "read_url".to_string(),
+            description: "Fetch a web page and return its main text as markdown, without navigation, \
+                ads or scripts. Long pages are cut off"
+                .to_string(),
+            parameters: ToolParameters::object()
+                .property("url", ParamSchema::string("The http(s) URL of the page"), true)
+                .property(
+                    "refresh",
+                    ParamSchema::boolean("Fetch the page again instead of using a cached copy"),
+                    false,
+                ),
+            strict: true,
+        }
+    }
+
+    /// The text of the page at `url`, as the model sees it
+    pub async fn read(&self, url: &str, refresh: bool, config: &WebConfig, vfs: &dyn VfsPort) -> Result<String> {
+        let url = url.trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(AgentError::api(ErrorKind::InvalidRequest, format!("\"{}\" is not an http(s) URL", url)));
+        }
+        let path = cache_path(&config.cache_dir, url);
+        if config.cache && !refresh {
+            if let Ok(bytes) = vfs.read_file(&path).await {
+                let text = String::from_utf8_lossy(&bytes);
+                return Ok(format!(
+                    "[Cached copy from {}; pass refresh to fetch the page again]\n{}",
+                    path,
+                    cap_text(&text, config.max_chars)
+                ));
+            }
+        }
+
+        let page = self.fetcher.fetch(url).await?;
+        let text = page_text(&page)?;
+        if config.cache {
+            let dir = config.cache_dir.trim_end_matches('/');
+            let mut parent = String::new();
+            for part in dir.split('/').filter(|p| !p.is_empty()) {
+                parent.push('/');
+                parent.push_str(part);
+                let _ = vfs.mkdir(&parent).await;
+            }
+            if let Err(e) = vfs.write_file(&path, text.as_bytes()).await {
+                log::warn!("Failed to cache {} at {}: {}", url, path, e);
+            }
+        }
+        Ok(cap_text(&text, config.max_chars))
+    }
+}
+
+/// Where the text of `url` is cached: a readable name from the host and
+/// path, made unique by a hash of the whole URL
+pub fn cache_path(dir: &str, url: &str) -> String {
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let mut name = String::new();
+    for c in rest.chars() {
+        if name.chars().count() >= 60 {
+            break;
+        }
+        let c = if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' };
+        if !(c == '-' && name.ends_with('-')) {
+            name.push(c);
+        }
+    }
+    format!(
+        "{}/{}-{:08x}.md",
+        dir.trim_end_matches('/'),
+        name.trim_matches('-'),
+        content_hash(url.as_bytes()) as u32
+    )
+}
+
+/// The readable text of a fetched page, headed by its title and source
+pub fn page_text(page: &FetchedPage) -> Result<String> {
+    let content_type = page.content_type.to_lowercase();
+    let html = content_type.contains("html")
+        || (content_type.is_empty() && page.body.trim_start().starts_with('<'));
+    let (title, body) = if html {
+        extract_html(&page.body, &page.url)
+    } else if content_type.is_empty()
+        || content_type.starts_with("text/")
+        || ["json", "xml", "javascript"].iter().any(|t| content_type.contains(t))
+    {
+        (None, page.body.trim().to_string())
+    } else {
+        return Err(AgentError::api(
+            ErrorKind::InvalidRequest,
+            format!("{} is {}, not a page that can be read as text", page.url, page.content_type),
+        ));
+    };
+    let body = if body.is_empty() {
+        "[The page has no readable text; it may need JavaScript to show its content]".to_string()
+    } else {
+        body
+    };
+    Ok(match title {
+        Some(title) => format!("# {}\nSource: {}\n\n{}", title, page.url, body),
+        None => format!("Source: {}\n\n{}", page.url, body),
+    })
+}
+
+/// `text` cut to `max_chars` (0 = no limit), at a paragraph break if
+/// there is one in the second half
+pub fn cap_text(text: &str, max_chars: usize) -> String {
+    let total = text.chars().count();
+    if max_chars == 0 || total <= max_chars {
+        return text.to_string();
+    }
+    let end = text.char_indices().nth(max_chars).map(|(i, _)| i).unwrap_or(text.len());
+    let cut = &text[..end];
+    let cut = match cut.rfind("\n\n") {
+        Some(i) if i > end / 2 => &cut[..i],
+        _ => cut,
+    }
+    .trim_end();
+    format!("{}\n\n[Truncated: showing {} of {} characters]", cut, cut.chars().count(), total)
+}
+
+// ─── HTML ────────────────────────────────────────────────────
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Text(&'a str),
+    Open { name: String, attrs: &'a str, self_closing: bool },
+    Close(String),
+}
+
+/// Split `html` into text and tags. Comments and doctypes are left out,
+/// and the content of script-like elements is a single text token.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let bytes = html.as_bytes();
+    let mut pos = 0;
+    let mut text_start = 0;
+    while let Some(offset) = html[pos..].find('<') {
+        let start = pos + offset;
+        let next = bytes.get(start + 1).copied().unwrap_or(b' ');
+        let tag_end = |from: usize, marker: &str| html[from..].find(marker).map(|i| from + i + marker.len());
+        let end = if html[start..].starts_with("<!--") {
+            tag_end(start + 4, "-->")
+        } else if next == b'!' || next == b'?' {
+            tag_end(start, ">")
+        } else if next == b'/' || next.is_ascii_alphabetic() {
+            end_of_tag(html, start)
+        } else {
+            pos = start + 1;
+            continue;
+        };
+        let end = end.unwrap_or(html.len());
+        if text_start < start {
+            tokens.push(Token::Text(&html[text_start..start]));
+        }
+        pos = end;
+        text_start = end;
+
+        let inner = html[start + 1..end].trim_end_matches('>');
+        if let Some(name) = inner.strip_prefix('/') {
+            tokens.push(Token::Close(tag_name(name)));
+            continue;
+        }
+        if next == b'!' || next == b'?' {
+            continue;
+        }
+        let name = tag_name(inner);
+        let attrs = &inner[name.len().min(inner.len())..];
+        let self_closing = attrs.trim_end().ends_with('/');
+        let raw = RAW_TEXT_TAGS.contains(&name.as_str()) && !self_closing;
+        tokens.push(Token::Open { name: name.clone(), attrs, self_closing });
+        if raw {
+            let close = format!("</{}", name);
+            let content_end = find_ignore_case(&html[pos..], &close).map(|i| pos + i).unwrap_or(html.len());
+            tokens.push(Token::Text(&html[pos..content_end]));
+            tokens.push(Token::Close(name));
+            pos = html[content_end..].find('>').map(|i| content_end + i + 1).unwrap_or(html.len());
+            text_start = pos;
+        }
+    }
+    if text_start < html.len() {
+        tokens.push(Token::Text(&html[text_start..]));
+    }
+    tokens
+}
+
+/// Index just past the `>` closing the tag at `start`, skipping quoted
+/// attribute values
+fn end_of_tag(html: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in html[start..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(start + i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.as_bytes().windows(needle.len()).position(|w| w.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// The value of attribute `name` in a tag's attribute text
+fn attr(attrs: &str, name: &str) -> Option<String> {
+    let mut rest = attrs;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '/').unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (v, remaining) = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let close = after[1..].find(q).map(|i| i + 1).unwrap_or(after.len());
+                    (&after[1..close], after.get(close + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = v;
+            rest = remaining;
+        }
+        if key.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value));
+        }
+    }
+}
+
+/// Replace character references with the characters they stand for
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let decoded = rest[1..].find(';').filter(|&n| n > 0 && n <= 10).and_then(|n| {
+            let entity = &rest[1..n + 1];
+            let c = match entity {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => ' ',
+                "mdash" => '—',
+                "ndash" => '–',
+                "hellip" => '…',
+                "lsquo" => '‘',
+                "rsquo" => '’',
+                "ldquo" => '“',
+                "rdquo" => '”',
+                "copy" => '©',
+                _ => {
+                    let code = entity.strip_prefix('#')?;
+                    let code = match code.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => code.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, n + 2))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `href` made absolute against the page at `base`
+pub fn resolve_url(base: &str, href: &str) -> String {
+    let scheme_end = href.find(':').filter(|&i| !href[..i].contains(['/', '?', '#']));
+    if scheme_end.is_some() {
+        return href.to_string();
+    }
+    let Some((scheme, rest)) = base.split_once("://") else {
+        return href.to_string();
+    };
+    if let Some(net) = href.strip_prefix("//") {
+        return format!("{}://{}", scheme, net);
+    }
+    let origin_end = rest.find('/').unwrap_or(rest.len());
+    let origin = format!("{}://{}", scheme, &rest[..origin_end]);
+    if href.starts_with('/') {
+        return format!("{}{}", origin, href);
+    }
+    let path = &rest[origin_end..];
+    let path = &path[..path.find(['?', '#']).unwrap_or(path.len())];
+    let dir = &path[..path.rfind('/').map(|i| i + 1).unwrap_or(0)];
+    let dir = if dir.is_empty() { "/" } else { dir };
+    format!("{}{}{}", origin, dir, href)
+}
+
+/// Whether a tag's class or id marks the element as boilerplate
+fn is_noise(attrs: &str) -> bool {
+    let words: Vec<String> = ["class", "id"]
+        .iter()
+        .filter_map(|name| attr(attrs, name))
+        .flat_map(|value| {
+            value
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .map(|word| word.to_ascii_lowercase())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let any_of = |list: &[&str]| words.iter().any(|word| list.contains(&word.as_str()));
+    any_of(NOISE_WORDS) && !any_of(CONTENT_WORDS)
+}
+
+/// Token range of the element opened at `open`, including its tags
+fn element_range(tokens: &[Token], open: usize) -> std::ops::Range<usize> {
+    let Token::Open { name, .. } = &tokens[open] else {
+        return open..open + 1;
+    };
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::Open { name: n, self_closing: false, .. } if n == name => depth += 1,
+            Token::Close(n) if n == name => {
+                depth -= 1;
+                if depth == 0 {
+                    return open..i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    open..tokens.len()
+}
+
+fn text_len(tokens: &[Token]) -> usize {
+    tokens
+        .iter()
+        .map(|t| match t {
+            Token::Text(text) => text.trim().len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The tokens of the page's main content: `<main>`, its longest
+/// `<article>`, its `<body>`, or else all of it
+fn main_content<'a, 'b>(tokens: &'b [Token<'a>]) -> &'b [Token<'a>] {
+    let opened = |wanted: &str| -> Vec<std::ops::Range<usize>> {
+        tokens
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| match t {
+                Token::Open { name, attrs, .. }
+                    if name == wanted || (wanted == "main" && attr(attrs, "role").as_deref() == Some("main")) =>
+                {
+                    Some(element_range(tokens, i))
+                }
+                _ => None,
+            })
+            .collect()
+    };
+    let with_text = |range: &std::ops::Range<usize>| text_len(&tokens[range.clone()]) > 0;
+    let range = opened("main")
+        .into_iter()
+        .find(with_text)
+        .or_else(|| opened("article").into_iter().filter(with_text).max_by_key(|r| text_len(&tokens[r.clone()])))
+        .or_else(|| opened("body").into_iter().next());
+    match range {
+        Some(range) => &tokens[range],
+        None => tokens,
+    }
+}
+
+/// The page's title and its main content as markdown
+pub fn extract_html(html: &str, base_url: &str) -> (Option<String>, String) {
+    let tokens = tokenize(html);
+    let title = tokens.windows(2).find_map(|pair| match pair {
+        [Token::Open { name, .. }, Token::Text(text)] if name == "title" => {
+            Some(collapse_whitespace(&decode_entities(text)))
+        }
+        _ => None,
+    });
+    let mut md = Markdown::default();
+    let mut skip: Option<(String, usize)> = None;
+    for token in main_content(&tokens) {
+        if let Some((name, depth)) = &mut skip {
+            match token {
+                Token::Open { name: n, self_closing: false, .. } if n == name => *depth += 1,
+                Token::Close(n) if n == name => {
+                    *depth -= 1;
+                    if *depth == 0 {
+                        skip = None;
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match token {
+            Token::Text(text) => md.text(&decode_entities(text)),
+            Token::Open { name, attrs, self_closing } => {
+                let void = *self_closing || VOID_TAGS.contains(&name.as_str());
+                let structural = ["html", "body", "main", "article"].contains(&name.as_str());
+                if !void && (DROPPED_TAGS.contains(&name.as_str()) || (!structural && is_noise(attrs))) {
+                    skip = Some((name.clone(), 1));
+                    continue;
+                }
+                md.open(name, attrs, base_url);
+            }
+            Token::Close(name) => md.close(name),
+        }
+    }
+    (title.filter(|t| !t.is_empty()), md.finish())
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Markdown written from a stream of tags and text
+#[derive(Default)]
+struct Markdown {
+    out: String,
+    /// Whitespace seen since the last word
+    space: bool,
+    /// Open lists, innermost last: the next number for `<ol>`, None for `<ul>`
+    lists: Vec<Option<usize>>,
+    /// Inside `<pre>`
+    pre: usize,
+    quote: usize,
+    /// Target and start in `out` of the open link
+    link: Option<(String, usize)>,
+}
+
+impl Markdown {
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    /// End the paragraph
+    fn block(&mut self) {
+        if self.out.is_empty() {
+            return;
+        }
+        while !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+        self.space = false;
+    }
+
+    /// End the line
+    fn line(&mut self) {
+        if !self.at_line_start() {
+            self.out.push('\n');
+        }
+        self.space = false;
+    }
+
+    /// Start a word (or markup that goes before one)
+    fn word_start(&mut self) {
+        if self.at_line_start() {
+            for _ in 0..self.quote {
+                self.out.push_str("> ");
+            }
+        } else if self.space && !self.out.ends_with([' ', '[', '(']) {
+            self.out.push(' ');
+        }
+        self.space = false;
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.pre > 0 {
+            self.out.push_str(text);
+            return;
+        }
+        if text.starts_with(char::is_whitespace) {
+            self.space = true;
+        }
+        let mut words = text.split_whitespace().peekable();
+        while let Some(word) = words.next() {
+            self.word_start();
+            self.out.push_str(word);
+            self.space = words.peek().is_some();
+        }
+        if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+            self.space = true;
+        }
+    }
+
+    fn mark(&mut self, mark: &str) {
+        if self.pre == 0 {
+            self.word_start();
+            self.out.push_str(mark);
+        }
+    }
+
+    fn open(&mut self, name: &str, attrs: &str, base_url: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.block();
+                let level = name[1..].parse().unwrap_or(1);
+                self.out.push_str(&format!("{} ", "#".repeat(level)));
+            }
+            "p" | "div" | "section" | "figure" | "table" | "dl" | "address" | "details" | "summary" => self.block(),
+            "ul" => {
+                self.line();
+                self.lists.push(None);
+            }
+            "ol" => {
+                self.line();
+                let start = attr(attrs, "start").and_then(|s| s.parse().ok()).unwrap_or(1);
+                self.lists.push(Some(start));
+            }
+            "li" => {
+                self.line();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.out.push_str(&format!("{}{}", indent, marker));
+            }
+            "blockquote" => {
+                self.block();
+                self.quote += 1;
+            }
+            "pre" => {
+                self.block();
+                self.out.push_str("```\n");
+                self.pre += 1;
+            }
+            "br" => {
+                self.out.push('\n');
+                self.space = false;
+            }
+            "hr" => {
+                self.block();
+                self.out.push_str("---");
+                self.block();
+            }
+            "tr" | "dt" | "dd" => self.line(),
+            "td" | "th" if !self.at_line_start() => {
+                self.out.push_str(" |");
+                self.space = true;
+            }
+            "strong" | "b" => self.mark("**"),
+            "em" | "i" => self.mark("_"),
+            "code" => self.mark("`"),
+            "a" => {
+                let href = attr(attrs, "href").filter(|h| !h.starts_with('#') && !h.starts_with("javascript:"));
+                if let (Some(href), None, 0) = (href, &self.link, self.pre) {
+                    self.word_start();
+                    self.link = Some((resolve_url(base_url, href.trim()), self.out.len()));
+                    self.out.push('[');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "p" | "div" | "section" | "figure" | "table" | "dl"
+            | "address" | "details" | "summary" => self.block(),
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block();
+                } else {
+                    self.line();
+                }
+            }
+            "li" | "tr" | "dt" | "dd" => self.line(),
+            "blockquote" => {
+                self.quote = self.quote.saturating_sub(1);
+                self.block();
+            }
+            "pre" => {
+                self.pre = self.pre.saturating_sub(1);
+                self.line();
+                self.out.push_str("```");
+                self.block();
+            }
+            "strong" | "b" if self.pre == 0 => self.out.push_str("**"),
+            "em" | "i" if self.pre == 0 => self.out.push('_'),
+            "code" if self.pre == 0 => self.out.push('`'),
+            "a" => {
+                if let Some((href, start)) = self.link.take() {
+                    if self.out[start + 1..].trim().is_empty() {
+                        self.out.truncate(start);
+                    } else {
+                        self.out.push_str(&format!("]({})", href));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The markdown, without trailing spaces or runs of blank lines
+    fn finish(self) -> String {
+        let mut result = String::with_capacity(self.out.len());
+        let mut blank = 0;
+        for line in self.out.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                blank += 1;
+                if blank > 1 {
+                    continue;
+                }
+            } else {
+                blank = 0;
+            }
+            result.push_str(line);
+            result.push('\n');
+        }
+        result.trim().to_string()
+    }
+}
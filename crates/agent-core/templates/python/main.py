@@ -0,0 +1,9 @@
+"""Entry point."""
+
+
+def main() -> None:
+    print("Hello, world!")
+
+
+if __name__ == "__main__":
+    main()
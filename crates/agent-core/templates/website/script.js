@@ -0,0 +1 @@
+document.getElementById("message").textContent = "Edit index.html, style.css and script.js to get started.";
@@ -174,6 +174,7 @@ fn runtime_reset() {
 }
 
 #[wasm_bindgen_test]
+#[allow(clippy::eq_op)]
 fn agent_state_eq() {
     assert_eq!(AgentState::Idle, AgentState::Idle);
     assert_eq!(AgentState::Thinking, AgentState::Thinking);
@@ -231,6 +232,8 @@ impl LlmPort for MockLlmWithToolCall {
                             arguments: r#"{"command":"echo test"}"#.to_string(),
                         },
                     }],
+                    reasoning: None,
+                    meta: None,
                 },
                 usage: None,
             })
@@ -255,15 +258,16 @@ struct MockShell;
 
 #[async_trait(?Send)]
 impl ShellPort for MockShell {
-    async fn execute(&self, cmd: &str, _timeout_ms: Option<u64>) -> agent_types::Result<ExecResult> {
+    async fn execute(&self, cmd: &str, _opts: &ExecOptions) -> agent_types::Result<ExecResult> {
         Ok(ExecResult {
             stdout: format!("mock output for: {}", cmd),
             stderr: String::new(),
             exit_code: 0,
+            duration_ms: 0,
         })
     }
 
-    fn execute_streaming(&self, _cmd: &str) -> Pin<Box<dyn Stream<Item = ShellStreamEvent>>> {
+    fn execute_streaming(&self, _cmd: &str, _opts: &ExecOptions) -> Pin<Box<dyn Stream<Item = ShellStreamEvent>>> {
         Box::pin(futures::stream::empty())
     }
 
@@ -316,6 +320,7 @@ impl VfsPort for MockVfs {
             name: "test.txt".to_string(),
             is_dir: false,
             size: 100,
+            content_type: None,
         }])
     }
 
@@ -325,6 +330,7 @@ impl VfsPort for MockVfs {
                 size: self.files.borrow()[path].len() as u64,
                 is_dir: false,
                 modified: None,
+                content_type: None,
             })
         } else {
             Err(agent_types::AgentError::Fs {
@@ -0,0 +1,145 @@
+//! Panic boundary — what the user sees when the app panics.
+//!
+//! A panic aborts the WASM module, so egui stops drawing and the canvas
+//! freezes. The hook installed here logs the panic to the console (with
+//! `console_error_panic_hook`), writes the active session to
+//! `localStorage` (synchronously, as nothing async will run again) and
+//! covers the page with an overlay: the message, a backtrace to copy, and
+//! a button that reloads the page. [`restore`] puts the written session
+//! back into storage when the app starts again.
+
+use std::cell::{Cell, RefCell};
+
+use wasm_bindgen::prelude::*;
+
+use agent_types::session::SessionRecovery;
+
+use crate::session::SessionManager;
+use crate::workspace::WorkspaceManager;
+
+#[wasm_bindgen(inline_js = r#"
+const KEY = "agent-crash-recovery";
+export function crash_save(text) {
+    try {
+        localStorage.setItem(KEY, text);
+        return true;
+    } catch (e) {
+        return false;
+    }
+}
+export function crash_take() {
+    try {
+        const text = localStorage.getItem(KEY);
+        localStorage.removeItem(KEY);
+        return text;
+    } catch (e) {
+        return null;
+    }
+}
+export function crash_stack() {
+    return new Error().stack || "";
+}
+export function crash_overlay(message, stack, saved) {
+    if (document.getElementById("agent-crash")) return;
+    const el = (tag, style, text) => {
+        const node = document.createElement(tag);
+        node.style.cssText = style;
+        if (text) node.textContent = text;
+        return node;
+    };
+    const overlay = el("div", "position:fixed;inset:0;z-index:10000;display:flex;align-items:center;justify-content:center;" +
+        "background:rgba(12,12,16,0.92);font:14px system-ui,sans-serif;color:#e6e6e6");
+    overlay.id = "agent-crash";
+    const box = el("div", "max-width:720px;width:90%;max-height:85vh;display:flex;flex-direction:column;gap:12px;" +
+        "padding:20px;border-radius:8px;background:#1e1e24;border:1px solid #c0504d");
+    box.appendChild(el("h2", "margin:0;font-size:18px;color:#f07070", "The app stopped working"));
+    box.appendChild(el("div", "white-space:pre-wrap", message));
+    box.appendChild(el("div", "color:#a0a0a8", saved
+        ? "The conversation was saved and will be restored after reloading."
+        : "The conversation could not be saved; anything since the last finished turn may be lost."));
+    const details = message + "\n\n" + stack;
+    box.appendChild(el("pre", "margin:0;overflow:auto;flex:1;min-height:80px;padding:8px;border-radius:4px;" +
+        "background:#121216;font:12px monospace;color:#b0b0b8", stack));
+    const buttons = el("div", "display:flex;gap:8px;justify-content:flex-end");
+    const button = (label, primary) => el("button", "padding:6px 14px;border-radius:4px;cursor:pointer;font:inherit;" +
+        (primary ? "border:none;background:#4a7dff;color:white" : "border:1px solid #555;background:none;color:inherit"), label);
+    const copy = button("Copy details", false);
+    copy.onclick = () => navigator.clipboard.writeText(details).then(
+        () => { copy.textContent = "Copied"; },
+        () => { copy.textContent = "Copy failed"; },
+    );
+    const reload = button("Reload and restore last session", true);
+    reload.onclick = () => location.reload();
+    buttons.append(copy, reload);
+    box.appendChild(buttons);
+    overlay.appendChild(box);
+    document.body.appendChild(overlay);
+}
+"#)]
+extern "C" {
+    fn crash_save(text: &str) -> bool;
+    fn crash_take() -> Option<String>;
+    fn crash_stack() -> String;
+    fn crash_overlay(message: &str, stack: &str, saved: bool);
+}
+
+/// The active session and its workspace, or None if it needn't be saved
+type RecoveryFn = Box<dyn Fn() -> Option<SessionRecovery>>;
+
+thread_local! {
+    static RECOVERY: RefCell<Option<RecoveryFn>> = const { RefCell::new(None) };
+    static PANICKED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Install the panic hook. Call before anything else can panic; a hook
+/// installed later (eframe's) still passes panics on to this one.
+pub fn install() {
+    std::panic::set_hook(Box::new(|info| {
+        console_error_panic_hook::hook(info);
+        // A panic while handling one shouldn't stack up overlays
+        if PANICKED.with(|p| p.replace(true)) {
+            return;
+        }
+        let saved = save_recovery();
+        crash_overlay(&info.to_string(), &crash_stack(), saved);
+    }));
+}
+
+/// What to write out if the app panics; replaces the last one
+pub fn set_recovery(recovery: impl Fn() -> Option<SessionRecovery> + 'static) {
+    RECOVERY.with(|r| *r.borrow_mut() = Some(Box::new(recovery)));
+}
+
+fn save_recovery() -> bool {
+    let recovery = RECOVERY.with(|r| r.try_borrow().ok().and_then(|r| r.as_ref().and_then(|f| f())));
+    let Some(recovery) = recovery else {
+        // Nothing unsaved
+        return true;
+    };
+    serde_json::to_string(&recovery).map(|text| crash_save(&text)).unwrap_or(false)
+}
+
+/// Put back a session written out by a panic, if it is newer than the
+/// stored copy. Returns its id.
+pub async fn restore(workspaces: &WorkspaceManager) -> Option<String> {
+    let text = crash_take()?;
+    let recovery: SessionRecovery = match serde_json::from_str(&text) {
+        Ok(recovery) => recovery,
+        Err(e) => {
+            log::warn!("Discarding unreadable crash recovery: {}", e);
+            return None;
+        }
+    };
+    let workspace = workspaces.load(&recovery.workspace_id).await.ok().flatten()?;
+    let sessions = SessionManager::new(workspaces.session_storage_for(&workspace));
+    let stored = sessions.load(&recovery.session.id).await.ok().flatten();
+    if !recovery.is_newer_than(stored.as_ref()) {
+        return None;
+    }
+    if let Err(e) = sessions.save(&recovery.session).await {
+        log::warn!("Failed to restore the session saved at the crash: {}", e);
+        return None;
+    }
+    log::info!("Restored session {} after a crash", recovery.session.id);
+    Some(recovery.session.id)
+}
@@ -0,0 +1,150 @@
+//! Mermaid diagrams and TeX formulas drawn to pixels for the chat.
+//!
+//! The mermaid ES module is imported lazily from a CDN the first time a
+//! diagram is drawn. Its SVG is sized from the viewBox, drawn onto a canvas
+//! over a background matching the theme, and read back as RGBA so egui can
+//! show it as a texture. Labels are plain SVG text rather than HTML, which
+//! would taint the canvas and make the pixels unreadable.
+//!
+//! Display math goes the same way through MathJax's SVG output, loaded
+//! from a CDN on first use; its glyphs are paths, so no fonts are needed.
+
+use js_sys::{Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use agent_types::{AgentError, Result};
+
+#[wasm_bindgen(inline_js = r##"
+let mermaid = null;
+let theme = null;
+let counter = 0;
+async function load(dark) {
+    if (!mermaid) {
+        mermaid = (await import("https://cdn.jsdelivr.net/npm/mermaid@11/dist/mermaid.esm.min.mjs")).default;
+    }
+    const wanted = dark ? "dark" : "default";
+    if (theme !== wanted) {
+        mermaid.initialize({
+            startOnLoad: false,
+            securityLevel: "strict",
+            theme: wanted,
+            htmlLabels: false,
+            flowchart: { htmlLabels: false },
+        });
+        theme = wanted;
+    }
+    return mermaid;
+}
+/**
+ * Draw an SVG element at `width` × `height` pixels, over `background` if
+ * given, and read back the RGBA pixels
+ */
+async function rasterize(root, width, height, background) {
+    root.setAttribute("width", width);
+    root.setAttribute("height", height);
+    const url = URL.createObjectURL(new Blob([new XMLSerializer().serializeToString(root)], { type: "image/svg+xml" }));
+    try {
+        const img = new Image();
+        img.src = url;
+        await img.decode();
+        const canvas = new OffscreenCanvas(width, height);
+        const ctx = canvas.getContext("2d");
+        if (background) {
+            ctx.fillStyle = background;
+            ctx.fillRect(0, 0, width, height);
+        }
+        ctx.drawImage(img, 0, 0, width, height);
+        const pixels = ctx.getImageData(0, 0, width, height).data;
+        return { width, height, rgba: new Uint8Array(pixels.buffer) };
+    } finally {
+        URL.revokeObjectURL(url);
+    }
+}
+export async function mermaid_render(source, scale, dark) {
+    const lib = await load(dark);
+    const { svg } = await lib.render(`agent-mermaid-${++counter}`, source);
+    const root = new DOMParser().parseFromString(svg, "image/svg+xml").documentElement;
+    const box = root.viewBox.baseVal;
+    root.removeAttribute("style");
+    const width = Math.max(1, Math.ceil(box.width * scale));
+    const height = Math.max(1, Math.ceil(box.height * scale));
+    return rasterize(root, width, height, dark ? "#1e1e1e" : "#ffffff");
+}
+let mathjax = null;
+function loadMathJax() {
+    if (!mathjax) {
+        mathjax = new Promise((resolve, reject) => {
+            // Without a font cache each formula's SVG holds its own glyphs
+            window.MathJax = { startup: { typeset: false }, svg: { fontCache: "none" } };
+            const script = document.createElement("script");
+            script.src = "https://cdn.jsdelivr.net/npm/mathjax@3/es5/tex-svg.js";
+            script.async = true;
+            script.onload = () => window.MathJax.startup.promise.then(() => resolve(window.MathJax), reject);
+            script.onerror = () => {
+                mathjax = null;
+                reject(new Error("MathJax could not be loaded from the CDN"));
+            };
+            document.head.appendChild(script);
+        });
+    }
+    return mathjax;
+}
+export async function math_render(tex, scale, dark) {
+    const lib = await loadMathJax();
+    const root = (await lib.tex2svgPromise(tex, { display: true })).querySelector("svg");
+    if (!root) throw new Error("MathJax produced no output");
+    const error = root.querySelector("[data-mjx-error]");
+    if (error) throw new Error(error.getAttribute("data-mjx-error"));
+    // The viewBox is in thousandths of an em; formulas are set at 18 px
+    const box = root.viewBox.baseVal;
+    const width = Math.max(1, Math.ceil(box.width * 0.018 * scale));
+    const height = Math.max(1, Math.ceil(box.height * 0.018 * scale));
+    root.setAttribute("style", `color: ${dark ? "#e6e6e6" : "#1e1e1e"}`);
+    return rasterize(root, width, height, null);
+}
+"##)]
+extern "C" {
+    #[wasm_bindgen(catch)]
+    async fn mermaid_render(source: &str, scale: f32, dark: bool) -> std::result::Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch)]
+    async fn math_render(tex: &str, scale: f32, dark: bool) -> std::result::Result<JsValue, JsValue>;
+}
+
+/// A diagram or formula as pixels
+pub struct DiagramPixels {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+/// Draw mermaid `source` at `scale` pixels per point, themed dark or light
+pub async fn render_mermaid(source: &str, scale: f32, dark: bool) -> Result<DiagramPixels> {
+    read_pixels(mermaid_render(source, scale, dark).await)
+}
+
+/// Draw the TeX formula `tex` as display math at `scale` pixels per point,
+/// in a color for a dark or light background. The background stays
+/// transparent.
+pub async fn render_math(tex: &str, scale: f32, dark: bool) -> Result<DiagramPixels> {
+    read_pixels(math_render(tex, scale, dark).await)
+}
+
+fn read_pixels(drawn: std::result::Result<JsValue, JsValue>) -> Result<DiagramPixels> {
+    let drawn = drawn.map_err(|e| {
+        let message = e
+            .dyn_ref::<js_sys::Error>()
+            .map(|err| String::from(err.message()))
+            .or_else(|| e.as_string())
+            .unwrap_or_else(|| format!("{:?}", e));
+        AgentError::Other(message)
+    })?;
+    let field = |name: &str| Reflect::get(&drawn, &name.into()).map_err(|e| AgentError::JsInterop(format!("{:?}", e)));
+    let width = field("width")?.as_f64().unwrap_or(0.0) as usize;
+    let height = field("height")?.as_f64().unwrap_or(0.0) as usize;
+    let rgba = Uint8Array::new(&field("rgba")?).to_vec();
+    if rgba.len() != width * height * 4 {
+        return Err(AgentError::JsInterop("the drawing's pixels don't match its size".to_string()));
+    }
+    Ok(DiagramPixels { width, height, rgba })
+}
@@ -0,0 +1,169 @@
+//! Local embeddings adapter — transformers.js in a dedicated Web Worker.
+//!
+//! Architecture mirrors the shell adapter:
+//! - Main thread ←→ module Worker (`embeddings_worker.js`)
+//! - JSON-serialized EmbeddingsCommand/EmbeddingsEvent over postMessage
+//!
+//! Workspace content never leaves the browser; only the model weights are
+//! downloaded (once, then cached by the browser).
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use wasm_bindgen::prelude::*;
+use web_sys::{ErrorEvent, MessageEvent, Worker, WorkerOptions, WorkerType};
+
+use agent_core::event_bus::EventBus;
+use agent_core::ports::EmbeddingsPort;
+use agent_types::{
+    AgentError, ErrorKind, Result,
+    event::{AgentEvent, EmbeddingsCommand, EmbeddingsEvent},
+};
+
+use crate::llm::deadline;
+
+/// Small, fast sentence-transformer (384 dimensions)
+pub const DEFAULT_EMBEDDING_MODEL: &str = "Xenova/all-MiniLM-L6-v2";
+
+/// Longest wait for one batch of vectors; the first one includes
+/// downloading the model
+const EMBED_TIMEOUT_MS: u32 = 120_000;
+
+type PendingMap = HashMap<u64, oneshot::Sender<Result<Vec<Vec<f32>>>>>;
+
+pub struct TransformersEmbeddings {
+    worker: Worker,
+    model: String,
+    next_id: RefCell<u64>,
+    pending: Rc<RefCell<PendingMap>>,
+    /// Why the worker failed, after which every request fails at once
+    failure: Rc<RefCell<Option<String>>>,
+}
+
+impl TransformersEmbeddings {
+    /// Spawn the embeddings worker and start loading `model`.
+    /// Download progress is reported on the event bus.
+    pub fn new(model: &str, event_bus: EventBus) -> Result<Self> {
+        let options = WorkerOptions::new();
+        options.set_type(WorkerType::Module);
+        let worker = Worker::new_with_options("./embeddings_worker.js", &options)
+            .map_err(|e| AgentError::JsInterop(format!("Failed to create embeddings worker: {:?}", e)))?;
+
+        let pending: Rc<RefCell<PendingMap>> = Rc::new(RefCell::new(HashMap::new()));
+
+        let pending_clone = pending.clone();
+        let onmessage = Closure::wrap(Box::new(move |event: MessageEvent| {
+            let Ok(json) = js_sys::JSON::stringify(&event.data()) else {
+                return;
+            };
+            let s: String = json.into();
+            let Ok(worker_event) = serde_json::from_str::<EmbeddingsEvent>(&s) else {
+                return;
+            };
+            match worker_event {
+                EmbeddingsEvent::Progress { progress, text } => {
+                    event_bus.emit(AgentEvent::ModelLoadProgress { progress, text });
+                }
+                EmbeddingsEvent::Ready => log::info!("Embeddings worker ready"),
+                EmbeddingsEvent::Vectors { id, vectors } => {
+                    if let Some(sender) = pending_clone.borrow_mut().remove(&id) {
+                        let _ = sender.send(Ok(vectors));
+                    }
+                }
+                EmbeddingsEvent::Error { id, message } => {
+                    log::warn!("Embeddings worker error: {}", message);
+                    if let Some(sender) = pending_clone.borrow_mut().remove(&id) {
+                        let _ = sender.send(Err(AgentError::Other(message)));
+                    }
+                }
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        onmessage.forget();
+
+        // A worker that can't load (missing script, transformers.js blocked
+        // or offline) never answers; fail what waits on it and what comes later
+        let failure = Rc::new(RefCell::new(None));
+        let on_failure = failure.clone();
+        let pending_failed = pending.clone();
+        let onerror = Closure::wrap(Box::new(move |event: ErrorEvent| {
+            event.prevent_default();
+            let message = format!(
+                "the embeddings worker failed ({}): embeddings_worker.js may be missing, or transformers.js \
+                 could not be loaded from the CDN",
+                event.message()
+            );
+            log::warn!("{}", message);
+            for (_, sender) in pending_failed.borrow_mut().drain() {
+                let _ = sender.send(Err(AgentError::api(ErrorKind::Unavailable, message.clone())));
+            }
+            *on_failure.borrow_mut() = Some(message);
+        }) as Box<dyn FnMut(ErrorEvent)>);
+        worker.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        onerror.forget();
+
+        let adapter = Self {
+            worker,
+            model: model.to_string(),
+            next_id: RefCell::new(1),
+            pending,
+            failure,
+        };
+        adapter.send_command(&EmbeddingsCommand::Init {
+            model: model.to_string(),
+        })?;
+        Ok(adapter)
+    }
+
+    fn send_command(&self, cmd: &EmbeddingsCommand) -> Result<()> {
+        let json = serde_json::to_string(cmd)?;
+        let js_val = js_sys::JSON::parse(&json)
+            .map_err(|e| AgentError::JsInterop(format!("{:?}", e)))?;
+        self.worker
+            .post_message(&js_val)
+            .map_err(|e| AgentError::JsInterop(format!("{:?}", e)))
+    }
+}
+
+#[async_trait(?Send)]
+impl EmbeddingsPort for TransformersEmbeddings {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        if let Some(message) = self.failure.borrow().clone() {
+            return Err(AgentError::api(ErrorKind::Unavailable, message));
+        }
+        let id = {
+            let mut next = self.next_id.borrow_mut();
+            let id = *next;
+            *next += 1;
+            id
+        };
+        let (sender, receiver) = oneshot::channel();
+        self.pending.borrow_mut().insert(id, sender);
+
+        if let Err(e) = self.send_command(&EmbeddingsCommand::Embed {
+            id,
+            texts: texts.to_vec(),
+        }) {
+            self.pending.borrow_mut().remove(&id);
+            return Err(e);
+        }
+
+        match deadline(receiver, Some(EMBED_TIMEOUT_MS)).await {
+            Ok(reply) => reply.map_err(|_| AgentError::Other("Embeddings channel closed".to_string()))?,
+            Err(ms) => {
+                self.pending.borrow_mut().remove(&id);
+                Err(AgentError::Timeout(ms.into()))
+            }
+        }
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+}
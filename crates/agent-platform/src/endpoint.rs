@@ -0,0 +1,126 @@
+//! The page side of the local OpenAI-compatible endpoint.
+//!
+//! `api_sw.js`, registered as a service worker, catches same-origin
+//! requests to the endpoint's paths and posts each one here with a
+//! `MessagePort`; the handler's answer goes back over the port, a stream
+//! chunk by chunk. See `agent_core::endpoint` for what is served.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use futures::StreamExt;
+use wasm_bindgen::prelude::*;
+
+use agent_core::endpoint::EndpointResponse;
+use agent_types::{AgentError, Result};
+
+const SERVICE_WORKER: &str = "./api_sw.js";
+
+#[wasm_bindgen(inline_js = r#"
+let handler = null;
+let listening = false;
+function announce(on) {
+    navigator.serviceWorker.ready.then((reg) => reg.active && reg.active.postMessage({ type: "serve", on }));
+}
+export async function endpoint_start(script, onRequest) {
+    if (!("serviceWorker" in navigator)) {
+        throw new Error("service workers aren't available here; they need HTTPS or localhost");
+    }
+    await navigator.serviceWorker.register(script);
+    await navigator.serviceWorker.ready;
+    handler = onRequest;
+    if (!listening) {
+        listening = true;
+        navigator.serviceWorker.addEventListener("message", (event) => {
+            const data = event.data || {};
+            if (data.type === "who_serves" && handler) {
+                announce(true);
+            } else if (data.type === "api_request" && event.ports[0]) {
+                if (handler) {
+                    handler(data.method, data.path, data.body, event.ports[0]);
+                } else {
+                    const body = JSON.stringify({ error: { message: "The local endpoint was turned off", type: "api_error" } });
+                    event.ports[0].postMessage({ type: "reply", status: 503, body });
+                }
+            }
+        });
+        navigator.serviceWorker.startMessages();
+    }
+    announce(true);
+}
+export function endpoint_stop() {
+    if (handler) {
+        handler = null;
+        announce(false);
+    }
+}
+export function endpoint_reply(port, status, body) {
+    port.postMessage({ type: "reply", status, body });
+}
+export function endpoint_stream(port) {
+    port.postMessage({ type: "stream" });
+}
+export function endpoint_chunk(port, text) {
+    port.postMessage({ type: "chunk", text });
+}
+export function endpoint_end(port) {
+    port.postMessage({ type: "end" });
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(catch)]
+    async fn endpoint_start(script: &str, on_request: &JsValue) -> std::result::Result<JsValue, JsValue>;
+    fn endpoint_stop();
+    fn endpoint_reply(port: &JsValue, status: u16, body: &str);
+    fn endpoint_stream(port: &JsValue);
+    fn endpoint_chunk(port: &JsValue, text: &str);
+    fn endpoint_end(port: &JsValue);
+}
+
+/// Answers a request: method, path (e.g. `/v1/models`) and body
+pub type EndpointHandler = Rc<dyn Fn(String, String, String) -> Pin<Box<dyn Future<Output = EndpointResponse>>>>;
+
+/// The endpoint while it is on; dropping it turns it off. The service
+/// worker stays registered, answering with an error until a page turns
+/// the endpoint on again.
+pub struct LocalEndpoint {
+    _on_request: Closure<dyn FnMut(String, String, String, JsValue)>,
+}
+
+impl LocalEndpoint {
+    /// Register the service worker and answer its requests with `handler`
+    pub async fn start(handler: EndpointHandler) -> Result<Self> {
+        let on_request = Closure::new(move |method: String, path: String, body: String, port: JsValue| {
+            let answer = handler(method, path, body);
+            wasm_bindgen_futures::spawn_local(async move {
+                match answer.await {
+                    EndpointResponse::Json { status, body } => endpoint_reply(&port, status, &body),
+                    EndpointResponse::Events(mut events) => {
+                        endpoint_stream(&port);
+                        while let Some(text) = events.next().await {
+                            endpoint_chunk(&port, &text);
+                        }
+                        endpoint_end(&port);
+                    }
+                }
+            });
+        });
+        endpoint_start(SERVICE_WORKER, on_request.as_ref()).await.map_err(|e| {
+            let message = e
+                .dyn_ref::<js_sys::Error>()
+                .map(|err| String::from(err.message()))
+                .or_else(|| e.as_string())
+                .unwrap_or_else(|| format!("{:?}", e));
+            AgentError::JsInterop(message)
+        })?;
+        Ok(Self { _on_request: on_request })
+    }
+}
+
+impl Drop for LocalEndpoint {
+    fn drop(&mut self) {
+        // Before the closure goes, so the page stops calling it
+        endpoint_stop();
+    }
+}
@@ -0,0 +1,63 @@
+//! Web fetch adapter — `fetch()` for the `read_url` tool.
+//!
+//! Most sites don't allow cross-origin requests from a browser, so pages
+//! are requested through the CORS proxy set for the LLM provider, when
+//! there is one.
+
+use async_trait::async_trait;
+use gloo_net::http::Request;
+
+use agent_core::ports::{FetchPort, FetchedPage};
+use agent_types::{AgentError, Result, config::proxied_url};
+
+use crate::llm::deadline;
+
+/// Give up on a page after this long
+const FETCH_TIMEOUT_MS: u32 = 30_000;
+
+pub struct BrowserFetch {
+    proxy: Option<String>,
+}
+
+impl BrowserFetch {
+    pub fn new(proxy: Option<String>) -> Self {
+        Self { proxy }
+    }
+}
+
+#[async_trait(?Send)]
+impl FetchPort for BrowserFetch {
+    async fn fetch(&self, url: &str) -> Result<FetchedPage> {
+        let controller = web_sys::AbortController::new().ok();
+        let signal = controller.as_ref().map(|c| c.signal());
+        let request = Request::get(&proxied_url(self.proxy.as_deref(), url))
+            .abort_signal(signal.as_ref())
+            .header("Accept", "text/html, text/plain;q=0.9, */*;q=0.5");
+        let exchange = async {
+            let response = request.send().await.map_err(|e| {
+                let hint = if self.proxy.is_some() {
+                    "the CORS proxy could not be reached or rejected the request — check the proxy URL in Settings"
+                } else {
+                    "the site may not allow requests from a browser (CORS) — set a CORS proxy in Settings"
+                };
+                AgentError::Network(format!("{}; {}", e, hint))
+            })?;
+            if !response.ok() {
+                let status = response.status();
+                return Err(AgentError::from_http(status, response.status_text().as_str()));
+            }
+            let content_type = response.headers().get("content-type").unwrap_or_default();
+            let body = response.text().await.map_err(|e| AgentError::Network(e.to_string()))?;
+            Ok(FetchedPage { url: url.to_string(), content_type, body })
+        };
+        match deadline(exchange, Some(FETCH_TIMEOUT_MS)).await {
+            Ok(result) => result,
+            Err(ms) => {
+                if let Some(controller) = &controller {
+                    controller.abort();
+                }
+                Err(AgentError::Timeout(ms.into()))
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! JavaScript sandbox adapter — each script in its own Web Worker.
+//!
+//! A worker has no DOM, and `js_worker.js` removes `fetch`, storage APIs and
+//! `importScripts` before evaluating the script, capturing the console as
+//! it goes. Every run gets a fresh worker, so nothing carries over between
+//! scripts, and a script that doesn't finish in time is stopped by
+//! terminating its worker. Dynamic `import()` and OPFS can't be removed from
+//! a same-origin worker, so this limits mistakes rather than isolating code.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use wasm_bindgen::prelude::*;
+use web_sys::{ErrorEvent, MessageEvent, Worker};
+
+use agent_core::ports::{JsPort, JsRun};
+use agent_types::{AgentError, ErrorKind, Result};
+
+use crate::llm::deadline;
+
+type Reply = Rc<RefCell<Option<oneshot::Sender<Result<JsRun>>>>>;
+
+#[derive(Default)]
+pub struct WorkerJs;
+
+impl WorkerJs {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait(?Send)]
+impl JsPort for WorkerJs {
+    async fn run(&self, code: &str, timeout_ms: u32) -> Result<JsRun> {
+        let worker = Worker::new("./js_worker.js").map_err(|e| {
+            AgentError::api(ErrorKind::Unavailable, format!("Failed to create the JavaScript worker: {:?}", e))
+        })?;
+        let (sender, receiver) = oneshot::channel();
+        let reply: Reply = Rc::new(RefCell::new(Some(sender)));
+
+        let on_reply = reply.clone();
+        let onmessage = Closure::wrap(Box::new(move |event: MessageEvent| {
+            let run = js_sys::JSON::stringify(&event.data())
+                .map_err(|e| AgentError::JsInterop(format!("{:?}", e)))
+                .and_then(|json| Ok(serde_json::from_str::<JsRun>(&String::from(json))?));
+            if let Some(sender) = on_reply.borrow_mut().take() {
+                let _ = sender.send(run);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let on_failure = reply.clone();
+        let onerror = Closure::wrap(Box::new(move |event: ErrorEvent| {
+            event.prevent_default();
+            let message = format!(
+                "the JavaScript worker failed to start ({}): js_worker.js may be missing or blocked",
+                event.message()
+            );
+            if let Some(sender) = on_failure.borrow_mut().take() {
+                let _ = sender.send(Err(AgentError::api(ErrorKind::Unavailable, message)));
+            }
+        }) as Box<dyn FnMut(ErrorEvent)>);
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        worker.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+
+        let outcome = match worker.post_message(&JsValue::from_str(code)) {
+            Ok(()) => match deadline(receiver, Some(timeout_ms)).await {
+                Ok(Ok(run)) => run,
+                Ok(Err(_)) => Err(AgentError::Other("JavaScript worker channel closed".to_string())),
+                Err(ms) => Err(AgentError::Timeout(ms.into())),
+            },
+            Err(e) => Err(AgentError::JsInterop(format!("{:?}", e))),
+        };
+        worker.terminate();
+        worker.set_onmessage(None);
+        worker.set_onerror(None);
+        outcome
+    }
+}
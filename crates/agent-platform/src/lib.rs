@@ -2,6 +2,18 @@ pub mod llm;
 pub mod storage;
 pub mod shell;
 pub mod vfs;
+pub mod session;
+pub mod workspace;
+pub mod embeddings;
+pub mod vault;
+pub mod fetch;
+pub mod js;
+pub mod sql;
+pub mod diagram;
+pub mod share;
+pub mod endpoint;
+pub mod logging;
+pub mod crash;
 
 #[cfg(test)]
 mod tests;
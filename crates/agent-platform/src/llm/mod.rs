@@ -1,3 +1,26 @@
 pub mod openai_compat;
+pub mod resume;
+pub mod wire;
+pub mod webllm;
+pub mod worker;
+
+use std::future::Future;
+
+use futures::future::{self, Either};
 
 pub use openai_compat::OpenAiCompatProvider;
+pub use webllm::WebLlmProvider;
+pub use worker::LlmWorker;
+
+/// Run `fut`, giving up after `timeout_ms` (never with `None`). The error
+/// is the timeout, for [`AgentError::Timeout`](agent_types::AgentError::Timeout);
+/// the caller aborts whatever `fut` left in flight.
+pub(crate) async fn deadline<T>(fut: impl Future<Output = T>, timeout_ms: Option<u32>) -> Result<T, u32> {
+    let Some(ms) = timeout_ms else {
+        return Ok(fut.await);
+    };
+    match future::select(std::pin::pin!(fut), gloo_timers::future::TimeoutFuture::new(ms)).await {
+        Either::Left((value, _)) => Ok(value),
+        Either::Right(_) => Err(ms),
+    }
+}
@@ -1,173 +1,705 @@
 //! OpenAI-compatible LLM adapter.
 //!
 //! Works with DeepSeek, OpenAI, and any provider using the
-//! OpenAI chat completions API format.
-//! Uses browser `fetch()` via gloo-net for WASM compatibility.
-
+//! OpenAI chat completions API format. Anthropic and Gemini are reached
+//! through their own APIs, with requests and replies mapped by
+//! [`WireFormat`].
+//!
+//! Model families with request quirks — OpenAI's o-series and GPT-5,
+//! DeepSeek's reasoners — are looked up in [`MODEL_FAMILIES`], and their
+//! requests adjusted: `max_completion_tokens`, no sampling parameters, the
+//! system prompt as a `developer` message, alternating turns. A model that
+//! turns down tool calls is asked again without tools, and from then on
+//! answers without them.
+//!
+//! Requests go through the [`LlmWorker`] when one is available, so network
+//! I/O and SSE parsing stay off the main thread; otherwise they use
+//! browser `fetch()` via gloo-net directly (no streaming). Either way a
+//! request without a reply after the configured timeout is aborted and
+//! fails with [`AgentError::Timeout`], which is not retried. Through the
+//! worker, chat completions are streamed even when the caller wants the
+//! whole reply, so one that breaks off partway can be continued (see
+//! [`super::resume`]); a stream that fails before anything arrived is
+//! sent again as a plain request, with retries.
+
+use std::cell::Cell;
 use std::pin::Pin;
+use std::rc::Rc;
 use async_trait::async_trait;
-use futures::stream::{self, Stream};
+use futures::channel::mpsc;
+use futures::stream::{self, Stream, StreamExt};
 use gloo_net::http::Request;
 use serde::Deserialize;
 use serde_json::{json, Value};
 
 use agent_core::ports::*;
+
+use super::resume::StreamResume;
+use super::wire::WireFormat;
+use super::worker::LlmWorker;
 use agent_types::{
     Result, AgentError,
-    config::LlmConfig,
+    config::{LlmConfig, LlmProvider},
     message::{Message, MessageContent, Role, ToolCallRequest, FunctionCall},
+    tool::ToolParameters,
 };
 
 /// Provider that speaks the OpenAI chat completions protocol.
 /// Compatible with: DeepSeek, OpenAI, Groq, Together, Mistral, etc.
+#[derive(Clone)]
 pub struct OpenAiCompatProvider {
     config: LlmConfig,
     base_url: String,
+    /// Request and reply layout of the provider's API
+    wire: WireFormat,
+    /// Off-main-thread transport; requests fall back to in-page fetch without it
+    worker: Option<Rc<LlmWorker>>,
+    /// The model turned down tool calls; requests go without tools since
+    tools_refused: Rc<Cell<bool>>,
 }
 
 impl OpenAiCompatProvider {
-    pub fn new(config: LlmConfig) -> Self {
+    pub fn new(config: LlmConfig, worker: Option<Rc<LlmWorker>>) -> Self {
         let base_url = config
             .api_base
             .clone()
             .unwrap_or_else(|| config.provider.default_base_url().to_string());
-        Self { config, base_url }
+        let wire = WireFormat::for_provider(&config.provider);
+        Self { config, base_url, wire, worker, tools_refused: Rc::new(Cell::new(false)) }
+    }
+
+    /// Full request URL for an API path, through the CORS proxy if set
+    fn url(&self, path: &str) -> String {
+        self.config.request_url(&format!("{}{}", self.base_url, path))
+    }
+
+    fn proxied(&self) -> bool {
+        self.config.proxy().is_some()
+    }
+
+    /// The network worker, unless it has failed; requests then go out
+    /// from the main thread
+    fn live_worker(&self) -> Option<&Rc<LlmWorker>> {
+        self.worker.as_ref().filter(|worker| worker.failure().is_none())
+    }
+
+    fn headers(&self, json_body: bool) -> Vec<(String, String)> {
+        request_headers(&self.config, json_body)
+    }
+}
+
+/// Headers for a request: the provider's auth (a bearer token for chat
+/// completions), the JSON content type when there is a body, then the
+/// configured extra headers. An extra header replaces a built-in one of
+/// the same name; ones without a name are skipped.
+pub(crate) fn request_headers(config: &LlmConfig, json_body: bool) -> Vec<(String, String)> {
+    let mut headers = WireFormat::for_provider(&config.provider).auth_headers(&config.api_key);
+    if json_body {
+        headers.push(("Content-Type".to_string(), "application/json".to_string()));
+    }
+    for (name, value) in &config.headers {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        headers.push((name.to_string(), value.clone()));
+    }
+    headers
+}
+
+/// Build an OpenAI chat completions request body.
+/// Shared with other adapters that accept the same wire format.
+/// `provider` selects how reasoning settings are expressed.
+pub(crate) fn build_request_body(req: &ChatRequest, provider: &LlmProvider) -> Value {
+    let quirks = model_quirks(&req.model);
+    let mut messages: Vec<Value> = if quirks.alternating_turns {
+        merge_consecutive(&req.messages).iter().map(message_to_json).collect()
+    } else {
+        req.messages.iter().map(message_to_json).collect()
+    };
+    if let Some(role) = quirks.system_role {
+        for msg in messages.iter_mut().filter(|m| m["role"] == "system") {
+            msg["role"] = json!(role);
+        }
+    }
+
+    let mut body = json!({
+        "model": req.model,
+        "messages": messages,
+        "temperature": req.temperature,
+    });
+    let max_tokens = if quirks.max_completion_tokens { "max_completion_tokens" } else { "max_tokens" };
+    body[max_tokens] = json!(req.max_tokens);
+
+    // Optional sampling parameters are only sent when set, so providers
+    // apply their own defaults otherwise
+    if let Some(top_p) = req.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    if let Some(penalty) = req.frequency_penalty {
+        body["frequency_penalty"] = json!(penalty);
+    }
+    if let Some(penalty) = req.presence_penalty {
+        body["presence_penalty"] = json!(penalty);
+    }
+    if !req.stop.is_empty() {
+        body["stop"] = json!(req.stop);
+    }
+    if let Some(seed) = req.seed {
+        body["seed"] = json!(seed);
+    }
+    if quirks.fixed_sampling {
+        if let Some(obj) = body.as_object_mut() {
+            for unsupported in ["temperature", "top_p", "frequency_penalty", "presence_penalty"] {
+                obj.remove(unsupported);
+            }
+        }
+    }
+
+    match provider {
+        LlmProvider::OpenAI | LlmProvider::Google | LlmProvider::Custom => {
+            if let Some(effort) = req.reasoning.effort {
+                body["reasoning_effort"] = json!(effort.as_str());
+            }
+        }
+        LlmProvider::Anthropic => {
+            if let Some(budget) = req.reasoning.budget_tokens {
+                // Anthropic requires at least 1024 thinking tokens and
+                // rejects sampling overrides while thinking is enabled
+                body["thinking"] = json!({
+                    "type": "enabled",
+                    "budget_tokens": budget.max(1024),
+                });
+                if let Some(obj) = body.as_object_mut() {
+                    obj.remove("temperature");
+                    obj.remove("top_p");
+                }
+            }
+        }
+        // deepseek-reasoner always reasons and has no knobs
+        LlmProvider::DeepSeek | LlmProvider::Local | LlmProvider::Demo => {}
     }
 
-    fn build_request_body(&self, req: &ChatRequest) -> Value {
-        let messages: Vec<Value> = req
-            .messages
+    if !req.tools.is_empty() {
+        let tools: Vec<Value> = req
+            .tools
             .iter()
-            .map(|m| message_to_json(m))
+            .map(|t| {
+                // Only OpenAI enforces strict schemas; elsewhere the flag is
+                // dropped and the plain schema sent
+                let strict = t.strict && *provider == LlmProvider::OpenAI;
+                let mut function = json!({
+                    "name": t.name,
+                    "description": t.description,
+                    "parameters": if strict {
+                        strict_parameters(&t.parameters)
+                    } else {
+                        json!(t.parameters)
+                    },
+                });
+                if strict {
+                    function["strict"] = json!(true);
+                }
+                json!({
+                    "type": "function",
+                    "function": function,
+                })
+            })
             .collect();
+        body["tools"] = json!(tools);
+    }
 
-        let mut body = json!({
-            "model": req.model,
-            "messages": messages,
-            "max_tokens": req.max_tokens,
-            "temperature": req.temperature,
-        });
+    body
+}
 
-        if !req.tools.is_empty() {
-            let tools: Vec<Value> = req
-                .tools
-                .iter()
-                .map(|t| {
-                    json!({
-                        "type": "function",
-                        "function": {
-                            "name": t.name,
-                            "description": t.description,
-                            "parameters": t.parameters,
-                        }
-                    })
-                })
-                .collect();
-            body["tools"] = json!(tools);
+/// How a model family's requests differ from plain chat completions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ModelQuirks {
+    /// Takes `max_completion_tokens` instead of `max_tokens`
+    pub max_completion_tokens: bool,
+    /// Rejects or ignores temperature, top_p and the penalties
+    pub fixed_sampling: bool,
+    /// Role system messages are sent as: `developer`, or `user` for models
+    /// that take no instructions
+    pub system_role: Option<&'static str>,
+    /// Rejects two user or assistant messages in a row
+    pub alternating_turns: bool,
+}
+
+const O_SERIES: ModelQuirks = ModelQuirks {
+    max_completion_tokens: true,
+    fixed_sampling: true,
+    system_role: Some("developer"),
+    alternating_turns: false,
+};
+
+const O_SERIES_PREVIEW: ModelQuirks = ModelQuirks { system_role: Some("user"), ..O_SERIES };
+
+const DEEPSEEK_REASONER: ModelQuirks = ModelQuirks {
+    max_completion_tokens: false,
+    fixed_sampling: true,
+    system_role: None,
+    alternating_turns: true,
+};
+
+/// Model name prefixes with request quirks, matched in order against the
+/// name without any `vendor/` prefix (so gateways match too)
+pub(crate) const MODEL_FAMILIES: &[(&str, ModelQuirks)] = &[
+    ("o1-mini", O_SERIES_PREVIEW),
+    ("o1-preview", O_SERIES_PREVIEW),
+    ("o1", O_SERIES),
+    ("o3", O_SERIES),
+    ("o4", O_SERIES),
+    ("gpt-5", O_SERIES),
+    ("deepseek-reasoner", DEEPSEEK_REASONER),
+    ("deepseek-r1", DEEPSEEK_REASONER),
+];
+
+/// The quirks of `model`, none for models not in [`MODEL_FAMILIES`]
+pub(crate) fn model_quirks(model: &str) -> ModelQuirks {
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    MODEL_FAMILIES
+        .iter()
+        .find(|(prefix, _)| {
+            // `o3` and `o3-mini`, not `o30b`
+            name.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with(['-', ':', '.']))
+        })
+        .map(|(_, quirks)| *quirks)
+        .unwrap_or_default()
+}
+
+/// Join back-to-back user messages, and assistant messages without tool
+/// calls, which the reasoner models reject
+pub(crate) fn merge_consecutive(messages: &[Message]) -> Vec<Message> {
+    let mut merged: Vec<Message> = Vec::with_capacity(messages.len());
+    for msg in messages {
+        let joinable = matches!(msg.role, Role::User | Role::Assistant) && msg.tool_calls.is_empty();
+        if let Some(last) = merged.last_mut() {
+            if joinable && last.role == msg.role && last.tool_calls.is_empty() {
+                let text = format!("{}\n\n{}", last.content.as_text(), msg.content.as_text());
+                last.content = MessageContent::Text(text);
+                continue;
+            }
         }
+        merged.push(msg.clone());
+    }
+    merged
+}
 
-        body
+/// Whether the provider turned a request down because the model can't
+/// call tools
+pub(crate) fn rejects_tools(error: &AgentError) -> bool {
+    let AgentError::Api { status: Some(400 | 422), message, .. } = error else {
+        return false;
+    };
+    let message = message.to_lowercase();
+    (message.contains("function call") || message.contains("tool"))
+        && (message.contains("support") || message.contains("not allowed"))
+}
+
+/// Told to a model that can't call tools
+const NO_TOOLS_NOTE: &str = "Tools are not available with this model. Answer from the conversation, \
+and give any commands or file changes for the user to carry out.";
+
+/// `req` for a model that can't call tools: no tool definitions, and
+/// earlier calls and results in the history as plain text
+pub(crate) fn without_tools(req: &mut ChatRequest) {
+    req.tools.clear();
+    for msg in &mut req.messages {
+        match msg.role {
+            Role::Assistant if !msg.tool_calls.is_empty() => {
+                let mut text = msg.content.as_text().to_string();
+                for tc in msg.tool_calls.drain(..) {
+                    text.push_str(&format!("\n[called {} with {}]", tc.function.name, tc.function.arguments));
+                }
+                msg.content = MessageContent::Text(text.trim_start().to_string());
+            }
+            Role::Tool => {
+                msg.role = Role::User;
+                msg.content = MessageContent::Text(format!("[tool result]\n{}", msg.content.as_text()));
+                msg.tool_call_id = None;
+            }
+            _ => {}
+        }
+    }
+    match req.messages.first_mut() {
+        Some(first) if first.role == Role::System => {
+            first.content = MessageContent::Text(format!("{}\n\n{}", first.content.as_text(), NO_TOOLS_NOTE));
+        }
+        _ => req.messages.insert(0, Message::system(NO_TOOLS_NOTE)),
     }
 }
 
-#[async_trait(?Send)]
-impl LlmPort for OpenAiCompatProvider {
-    async fn chat_completion(&self, req: ChatRequest) -> Result<ChatResponse> {
-        let url = format!("{}/v1/chat/completions", self.base_url);
-        let body = self.build_request_body(&req);
-
-        let response = Request::post(&url)
-            .header("Content-Type", "application/json")
-            .header("Authorization", &format!("Bearer {}", self.config.api_key))
-            .json(&body)
-            .map_err(|e| AgentError::Network(e.to_string()))?
-            .send()
-            .await
-            .map_err(|e| AgentError::Network(e.to_string()))?;
-
-        if !response.ok() {
-            let status = response.status();
-            let text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "unknown error".to_string());
-            return Err(AgentError::Llm(format!(
-                "HTTP {}: {}",
-                status, text
-            )));
+/// Rewrite a schema for OpenAI strict mode: every property must be listed
+/// as required and extra properties forbidden, so optional parameters
+/// become nullable instead.
+fn strict_parameters(params: &ToolParameters) -> Value {
+    let mut properties = params.properties.clone();
+    for (name, schema) in properties.iter_mut() {
+        if params.required.contains(name) {
+            continue;
         }
+        if let Some(ty) = schema.get("type").and_then(Value::as_str) {
+            schema["type"] = json!([ty, "null"]);
+            // An enum still has to admit the null the type now allows
+            if let Some(values) = schema.get_mut("enum").and_then(Value::as_array_mut) {
+                if !values.contains(&Value::Null) {
+                    values.push(Value::Null);
+                }
+            }
+        }
+    }
+    json!({
+        "type": params.schema_type,
+        "properties": properties,
+        "required": properties.keys().collect::<Vec<_>>(),
+        "additionalProperties": false,
+    })
+}
+
+/// Convert a decoded chat completions response into a ChatResponse.
+pub(crate) fn parse_chat_response(data: ApiResponse) -> Result<ChatResponse> {
+    let choice = data
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| AgentError::Llm("No choices in response".to_string()))?;
+
+    let mut message = parse_api_message(choice.message);
+    let usage = data.usage.map(|u| TokenUsage {
+        prompt_tokens: u.prompt_tokens,
+        completion_tokens: u.completion_tokens,
+        total_tokens: u.total_tokens,
+    });
 
-        let data: ApiResponse = response
-            .json()
-            .await
-            .map_err(|e| AgentError::Llm(e.to_string()))?;
+    let meta = message.stamp();
+    meta.model = data.model;
+    meta.usage = usage.clone();
 
-        let choice = data
-            .choices
+    Ok(ChatResponse { message, usage })
+}
+
+/// A streamed reply, gathered into a chat completions response body for
+/// `model`. Usage from several attempts (after a resume) is added up.
+/// Fails with the stream's error, or with `None` if it failed before any
+/// text or tool call arrived, so the request can be sent again whole.
+pub(crate) fn collect_stream(events: &[LlmStreamEvent], model: &str) -> std::result::Result<Value, Option<String>> {
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    // (id, name, arguments) by index
+    let mut calls: Vec<(String, String, String)> = Vec::new();
+    let mut usage: Option<TokenUsage> = None;
+    for event in events {
+        match event {
+            LlmStreamEvent::Delta(delta) => text.push_str(delta),
+            LlmStreamEvent::Reasoning(delta) => reasoning.push_str(delta),
+            LlmStreamEvent::ToolCallDelta { index, id, name, arguments_delta } => {
+                if calls.len() <= *index {
+                    calls.resize(*index + 1, Default::default());
+                }
+                let call = &mut calls[*index];
+                if let Some(id) = id {
+                    call.0 = id.clone();
+                }
+                if let Some(name) = name {
+                    call.1.push_str(name);
+                }
+                call.2.push_str(arguments_delta);
+            }
+            LlmStreamEvent::Usage(u) => {
+                let total = usage.get_or_insert_with(TokenUsage::default);
+                total.prompt_tokens += u.prompt_tokens;
+                total.completion_tokens += u.completion_tokens;
+                total.total_tokens += u.total_tokens;
+            }
+            LlmStreamEvent::Error(message) if text.is_empty() && calls.is_empty() => {
+                log::warn!("Stream failed before the reply began ({}), sending the request whole", message);
+                return Err(None);
+            }
+            LlmStreamEvent::Error(message) => return Err(Some(message.clone())),
+            LlmStreamEvent::Done => {}
+        }
+    }
+
+    let mut message = json!({ "role": "assistant", "content": text });
+    if !reasoning.is_empty() {
+        message["reasoning_content"] = json!(reasoning);
+    }
+    if !calls.is_empty() {
+        message["tool_calls"] = calls
             .into_iter()
-            .next()
-            .ok_or_else(|| AgentError::Llm("No choices in response".to_string()))?;
-
-        let message = parse_api_message(choice.message);
-        let usage = data.usage.map(|u| TokenUsage {
-            prompt_tokens: u.prompt_tokens,
-            completion_tokens: u.completion_tokens,
-            total_tokens: u.total_tokens,
-        });
+            .map(|(id, name, arguments)| {
+                json!({ "id": id, "type": "function", "function": { "name": name, "arguments": arguments } })
+            })
+            .collect();
+    }
+    Ok(json!({ "model": model, "choices": [{ "message": message }], "usage": usage }))
+}
+
+/// Attempts per request, including the first
+const MAX_ATTEMPTS: u32 = 4;
+/// Upper bound for a single backoff wait, whatever Retry-After says
+const MAX_BACKOFF_MS: u32 = 30_000;
+
+impl OpenAiCompatProvider {
+    /// Send `req` and parse the reply, retrying transient failures
+    async fn complete(&self, req: &ChatRequest) -> Result<ChatResponse> {
+        if let Some(worker) = self.live_worker().filter(|_| self.wire == WireFormat::OpenAi) {
+            let (sender, receiver) = mpsc::unbounded();
+            self.clone().stream_resuming(worker.clone(), req.clone(), sender, true).await;
+            let events: Vec<LlmStreamEvent> = receiver.collect().await;
+            match collect_stream(&events, &req.model) {
+                Ok(data) => return self.wire.parse_response(data),
+                Err(Some(message)) => return Err(AgentError::Llm(message)),
+                Err(None) => {}
+            }
+        }
+
+        let url = self.url(&self.wire.chat_path(&req.model));
+        let body = self.wire.build_body(req, &self.config);
+
+        // Retry transient failures (rate limits, 5xx, network) with
+        // exponential backoff, honouring Retry-After when given
+        let mut attempt = 1;
+        let data = loop {
+            match self.send_once(&url, &body).await {
+                Ok(data) => break data,
+                // A timeout has already waited long enough
+                Err((e, retry_after))
+                    if e.is_retryable() && !matches!(e, AgentError::Timeout(_)) && attempt < MAX_ATTEMPTS =>
+                {
+                    let delay = retry_after
+                        .unwrap_or(1000 << (attempt - 1))
+                        .min(MAX_BACKOFF_MS);
+                    log::warn!("LLM request failed ({}), retrying in {}ms", e, delay);
+                    gloo_timers::future::TimeoutFuture::new(delay).await;
+                    attempt += 1;
+                }
+                Err((e, _)) => return Err(e),
+            }
+        };
+
+        self.wire.parse_response(data)
+    }
+
+    /// Stream `req` through the worker into `out`, asking for the rest of
+    /// a reply that breaks off. With `usage`, the provider is asked to
+    /// report token usage at the end of the stream.
+    async fn stream_resuming(
+        self,
+        worker: Rc<LlmWorker>,
+        req: ChatRequest,
+        out: mpsc::UnboundedSender<LlmStreamEvent>,
+        usage: bool,
+    ) {
+        let url = self.url(&self.wire.chat_path(&req.model));
+        let emit = |events: Vec<LlmStreamEvent>| events.into_iter().all(|e| out.unbounded_send(e).is_ok());
+        let mut resume = StreamResume::default();
+        let mut attempt = req.clone();
+        loop {
+            let mut body = self.wire.build_body(&attempt, &self.config);
+            body["stream"] = json!(true);
+            if usage {
+                body["stream_options"] = json!({ "include_usage": true });
+            }
+            let mut events = worker.stream(&url, self.headers(true), body.to_string(), self.proxied());
+            let error = loop {
+                // A stream quiet for the whole timeout has stalled
+                match super::deadline(events.next(), self.config.timeout_ms()).await {
+                    Ok(Some(LlmStreamEvent::Done)) => {
+                        let mut rest = resume.flush();
+                        rest.push(LlmStreamEvent::Done);
+                        emit(rest);
+                        return;
+                    }
+                    Ok(Some(LlmStreamEvent::Error(e))) => break e,
+                    Ok(Some(event)) => {
+                        if !emit(resume.accept(event)) {
+                            // Nobody is listening any more
+                            return;
+                        }
+                    }
+                    Ok(None) => break "The stream ended before the reply was complete".to_string(),
+                    Err(ms) => break AgentError::Timeout(ms.into()).user_message(),
+                }
+            };
+            if !emit(resume.flush()) {
+                return;
+            }
+            if !resume.can_resume() {
+                emit(vec![LlmStreamEvent::Error(error), LlmStreamEvent::Done]);
+                return;
+            }
+            log::warn!("Stream broke off ({}), asking for the rest", error);
+            attempt = resume.resume(&req);
+        }
+    }
 
-        Ok(ChatResponse { message, usage })
+    /// One POST to the chat completions endpoint. On failure, also returns
+    /// the server's Retry-After hint in milliseconds, if any.
+    async fn send_once(&self, url: &str, body: &Value) -> std::result::Result<Value, (AgentError, Option<u32>)> {
+        if let Some(worker) = self.live_worker() {
+            let reply = worker
+                .fetch("POST", url, self.headers(true), Some(body.to_string()), self.proxied(), self.config.timeout_ms())
+                .await
+                .map_err(|e| (e, None))?;
+            if !reply.ok() {
+                return Err((AgentError::from_http(reply.status, &reply.body), reply.retry_after));
+            }
+            return serde_json::from_str(&reply.body).map_err(|e| (AgentError::Llm(e.to_string()), None));
+        }
+
+        let controller = web_sys::AbortController::new().ok();
+        let signal = controller.as_ref().map(|c| c.signal());
+        let mut request = Request::post(url).abort_signal(signal.as_ref());
+        for (name, value) in self.headers(true) {
+            request = request.header(&name, &value);
+        }
+        let exchange = async {
+            let response = request
+                .body(body.to_string())
+                .map_err(|e| (AgentError::Llm(e.to_string()), None))?
+                .send()
+                .await
+                .map_err(|e| (AgentError::fetch_failed(&e.to_string(), self.proxied()), None))?;
+
+            if !response.ok() {
+                let status = response.status();
+                let retry_after = response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|v| v.trim().parse::<u32>().ok())
+                    .map(|secs| secs.saturating_mul(1000));
+                let text = response.text().await.unwrap_or_default();
+                return Err((AgentError::from_http(status, &text), retry_after));
+            }
+
+            response
+                .json()
+                .await
+                .map_err(|e| (AgentError::Llm(e.to_string()), None))
+        };
+        match super::deadline(exchange, self.config.timeout_ms()).await {
+            Ok(result) => result,
+            Err(ms) => {
+                if let Some(controller) = &controller {
+                    controller.abort();
+                }
+                Err((AgentError::Timeout(ms.into()), None))
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmPort for OpenAiCompatProvider {
+    async fn chat_completion(&self, mut req: ChatRequest) -> Result<ChatResponse> {
+        if self.tools_refused.get() {
+            without_tools(&mut req);
+        }
+        match self.complete(&req).await {
+            Err(e) if !req.tools.is_empty() && rejects_tools(&e) => {
+                log::warn!("{} doesn't take tool calls ({}); continuing without tools", req.model, e);
+                self.tools_refused.set(true);
+                without_tools(&mut req);
+                self.complete(&req).await
+            }
+            result => result,
+        }
     }
 
     fn stream_chat(
         &self,
-        _req: ChatRequest,
+        req: ChatRequest,
     ) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
-        // Streaming via SSE requires ReadableStream parsing.
-        // For now, return a placeholder that yields Done immediately.
-        // Full SSE streaming will be implemented in a follow-up.
-        Box::pin(stream::once(async { LlmStreamEvent::Done }))
+        let worker = self.live_worker().filter(|_| self.wire == WireFormat::OpenAi);
+        let Some(worker) = worker else {
+            // SSE parsing only happens in the worker, and only for chat
+            // completions: otherwise the reply comes in one piece,
+            // replayed as a stream
+            let provider = self.clone();
+            return Box::pin(
+                stream::once(async move { provider.chat_completion(req).await })
+                    .flat_map(|result| stream::iter(replay_events(result))),
+            );
+        };
+        let mut req = req;
+        if self.tools_refused.get() {
+            without_tools(&mut req);
+        }
+        let (sender, receiver) = mpsc::unbounded();
+        wasm_bindgen_futures::spawn_local(self.clone().stream_resuming(worker.clone(), req, sender, false));
+        Box::pin(receiver)
     }
 
     async fn list_models(&self) -> Result<Vec<String>> {
-        let url = format!("{}/v1/models", self.base_url);
-
-        let response = Request::get(&url)
-            .header("Authorization", &format!("Bearer {}", self.config.api_key))
-            .send()
-            .await
-            .map_err(|e| AgentError::Network(e.to_string()))?;
-
-        if !response.ok() {
-            return Err(AgentError::Llm(format!(
-                "HTTP {}",
-                response.status()
-            )));
-        }
+        let url = self.url(self.wire.models_path());
+
+        let data: Value = if let Some(worker) = self.live_worker() {
+            let reply = worker.fetch("GET", &url, self.headers(false), None, self.proxied(), None).await?;
+            if !reply.ok() {
+                return Err(AgentError::from_http(reply.status, &reply.body));
+            }
+            serde_json::from_str(&reply.body).map_err(|e| AgentError::Llm(e.to_string()))?
+        } else {
+            let mut request = Request::get(&url);
+            for (name, value) in self.headers(false) {
+                request = request.header(&name, &value);
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| AgentError::fetch_failed(&e.to_string(), self.proxied()))?;
 
-        let data: Value = response
-            .json()
-            .await
-            .map_err(|e| AgentError::Llm(e.to_string()))?;
-
-        let models = data["data"]
-            .as_array()
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|m| m["id"].as_str().map(String::from))
-                    .collect()
-            })
-            .unwrap_or_default();
+            if !response.ok() {
+                let status = response.status();
+                let text = response.text().await.unwrap_or_default();
+                return Err(AgentError::from_http(status, &text));
+            }
+
+            response
+                .json()
+                .await
+                .map_err(|e| AgentError::Llm(e.to_string()))?
+        };
+
+        Ok(self.wire.parse_models(&data))
+    }
+}
 
-        Ok(models)
+/// A whole reply as stream events: its text, each tool call, then done
+fn replay_events(result: Result<ChatResponse>) -> Vec<LlmStreamEvent> {
+    let message = match result {
+        Ok(response) => response.message,
+        Err(e) => return vec![LlmStreamEvent::Error(e.to_string()), LlmStreamEvent::Done],
+    };
+    let mut events = Vec::new();
+    let text = message.content.as_text();
+    if !text.is_empty() {
+        events.push(LlmStreamEvent::Delta(text.to_string()));
     }
+    for (index, tc) in message.tool_calls.into_iter().enumerate() {
+        events.push(LlmStreamEvent::ToolCallDelta {
+            index,
+            id: Some(tc.id),
+            name: Some(tc.function.name),
+            arguments_delta: tc.function.arguments,
+        });
+    }
+    events.push(LlmStreamEvent::Done);
+    events
 }
 
 // ─── API response types ──────────────────────────────────────
 
 #[derive(Deserialize)]
-struct ApiResponse {
+pub(crate) struct ApiResponse {
     choices: Vec<ApiChoice>,
     usage: Option<ApiUsage>,
+    /// Model that actually served the request
+    #[serde(default)]
+    model: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -182,6 +714,26 @@ struct ApiMessage {
     content: Option<String>,
     #[serde(default)]
     tool_calls: Vec<ApiToolCall>,
+    /// Reasoning trace — `reasoning_content` on DeepSeek, `reasoning` on
+    /// some OpenAI-compatible gateways
+    #[serde(default, alias = "reasoning")]
+    reasoning_content: Option<String>,
+    /// Sources cited by models with web search
+    #[serde(default)]
+    annotations: Vec<ApiAnnotation>,
+}
+
+#[derive(Deserialize)]
+struct ApiAnnotation {
+    #[serde(default)]
+    url_citation: Option<ApiUrlCitation>,
+}
+
+#[derive(Deserialize)]
+struct ApiUrlCitation {
+    url: String,
+    #[serde(default)]
+    title: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -205,7 +757,9 @@ struct ApiUsage {
 
 // ─── Serialization helpers ───────────────────────────────────
 
-fn message_to_json(msg: &Message) -> Value {
+/// `reasoning` is deliberately not sent back: DeepSeek rejects requests
+/// that echo `reasoning_content` in the history.
+pub(crate) fn message_to_json(msg: &Message) -> Value {
     let role = match msg.role {
         Role::System => "system",
         Role::User => "user",
@@ -252,7 +806,12 @@ fn parse_api_message(api: ApiMessage) -> Message {
         _ => Role::Assistant,
     };
 
-    let content = MessageContent::Text(api.content.unwrap_or_default());
+    let urls: Vec<ApiUrlCitation> = api.annotations.into_iter().filter_map(|a| a.url_citation).collect();
+    let (reasoning, text) = match api.reasoning_content {
+        Some(reasoning) => (Some(reasoning), api.content.unwrap_or_default()),
+        None => split_think_tags(api.content.unwrap_or_default()),
+    };
+    let content = MessageContent::Text(append_url_citations(text, &urls));
 
     let tool_calls: Vec<ToolCallRequest> = api
         .tool_calls
@@ -271,5 +830,46 @@ fn parse_api_message(api: ApiMessage) -> Message {
         content,
         tool_call_id: None,
         tool_calls,
+        reasoning: reasoning.filter(|r| !r.trim().is_empty()),
+        meta: None,
+    }
+}
+
+/// Cited URLs as `[n]: url "title"` lines after the text, numbered after
+/// the reference lines it already has, so the chat shows them as footnotes
+fn append_url_citations(text: String, urls: &[ApiUrlCitation]) -> String {
+    let cited = text.lines().filter_map(|line| {
+        let (number, _) = line.trim().strip_prefix('[')?.split_once("]:")?;
+        number.parse::<usize>().ok()
+    });
+    let mut next = cited.max().unwrap_or(0) + 1;
+    let mut lines: Vec<String> = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for citation in urls {
+        if seen.contains(&citation.url.as_str()) || text.contains(&format!("]: {}", citation.url)) {
+            continue;
+        }
+        seen.push(&citation.url);
+        lines.push(match &citation.title {
+            Some(title) => format!("[{}]: {} \"{}\"", next, citation.url, title.replace('"', "'")),
+            None => format!("[{}]: {}", next, citation.url),
+        });
+        next += 1;
+    }
+    if lines.is_empty() {
+        return text;
+    }
+    format!("{}\n\n{}", text.trim_end(), lines.join("\n"))
+}
+
+/// R1 distills served by other hosts put their reasoning in the text, in a
+/// leading `<think>` block: split it off as the reasoning
+pub(crate) fn split_think_tags(text: String) -> (Option<String>, String) {
+    let Some(rest) = text.trim_start().strip_prefix("<think>") else {
+        return (None, text);
+    };
+    match rest.split_once("</think>") {
+        Some((reasoning, answer)) => (Some(reasoning.trim().to_string()), answer.trim_start().to_string()),
+        None => (None, text),
     }
 }
@@ -0,0 +1,103 @@
+//! Picking up streamed replies that break off.
+//!
+//! A stream that fails or goes quiet after text has arrived is requested
+//! again: the partial text goes back as the assistant's message with an
+//! instruction to continue, and the continuation is appended to what the
+//! consumer already has. Models often restart a few words back, so the
+//! start of a continuation is held until it can be checked for overlap
+//! with the partial text. Replies that had started a tool call are not
+//! resumed: half a JSON argument can't be continued reliably.
+//!
+//! The OpenAI-compatible adapter streams through the worker for
+//! `chat_completion` as well as `stream_chat`, so agent turns are resumed
+//! too; other wire formats and main-thread fetches get replies whole.
+
+use agent_core::ports::{ChatRequest, LlmStreamEvent};
+use agent_types::message::Message;
+
+/// Continuation requests per reply
+pub const MAX_RESUMES: u32 = 2;
+/// Continuation text held back before checking it for repeats
+const OVERLAP_WINDOW: usize = 64;
+/// Shorter repeats are as likely to be chance
+const MIN_OVERLAP: usize = 8;
+
+pub const CONTINUE_PROMPT: &str = "Your previous reply was cut off by a network error. Continue it exactly \
+where it stopped, without repeating anything and without an introduction.";
+
+/// What has been streamed of one reply so far
+#[derive(Debug, Default)]
+pub struct StreamResume {
+    /// Text passed on to the consumer, across attempts
+    partial: String,
+    tool_calls: bool,
+    resumes: u32,
+    /// Start of a continuation, not yet checked for overlap
+    held: Option<String>,
+}
+
+impl StreamResume {
+    /// Take an event from the current attempt; returns what to pass on
+    pub fn accept(&mut self, event: LlmStreamEvent) -> Vec<LlmStreamEvent> {
+        match event {
+            LlmStreamEvent::Delta(text) => match self.held.as_mut() {
+                Some(held) => {
+                    held.push_str(&text);
+                    if held.len() < OVERLAP_WINDOW {
+                        return Vec::new();
+                    }
+                    self.flush()
+                }
+                None => {
+                    self.partial.push_str(&text);
+                    vec![LlmStreamEvent::Delta(text)]
+                }
+            },
+            LlmStreamEvent::ToolCallDelta { .. } => {
+                self.tool_calls = true;
+                let mut events = self.flush();
+                events.push(event);
+                events
+            }
+            other => vec![other],
+        }
+    }
+
+    /// Pass on held continuation text, minus what repeats the partial text
+    pub fn flush(&mut self) -> Vec<LlmStreamEvent> {
+        let Some(held) = self.held.take() else {
+            return Vec::new();
+        };
+        let text = strip_overlap(&self.partial, &held).to_string();
+        if text.is_empty() {
+            return Vec::new();
+        }
+        self.partial.push_str(&text);
+        vec![LlmStreamEvent::Delta(text)]
+    }
+
+    /// Whether a broken-off attempt can be continued
+    pub fn can_resume(&self) -> bool {
+        !self.partial.trim().is_empty() && !self.tool_calls && self.resumes < MAX_RESUMES
+    }
+
+    /// The request continuing `req` from the text streamed so far. Held
+    /// text should be flushed first.
+    pub fn resume(&mut self, req: &ChatRequest) -> ChatRequest {
+        self.resumes += 1;
+        self.held = Some(String::new());
+        let mut messages = req.messages.clone();
+        messages.push(Message::assistant(&self.partial));
+        messages.push(Message::user(CONTINUE_PROMPT));
+        ChatRequest { messages, ..req.clone() }
+    }
+}
+
+/// `continuation` without a start that repeats the end of `partial`
+pub fn strip_overlap<'a>(partial: &str, continuation: &'a str) -> &'a str {
+    let longest = partial.len().min(continuation.len());
+    (MIN_OVERLAP..=longest)
+        .rev()
+        .find(|&k| continuation.is_char_boundary(k) && partial.ends_with(&continuation[..k]))
+        .map_or(continuation, |k| &continuation[k..])
+}
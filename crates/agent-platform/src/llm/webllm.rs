@@ -0,0 +1,164 @@
+//! In-browser LLM adapter backed by WebLLM (WebGPU).
+//!
+//! The model runs fully client-side: no API key, and once the weights are
+//! cached by the browser it works offline. WebLLM exposes an
+//! OpenAI-compatible `chat.completions.create`, so request/response mapping
+//! is shared with the OpenAI-compatible adapter.
+//!
+//! The WebLLM ES module is imported lazily from a CDN on first use.
+
+use std::cell::RefCell;
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use futures::stream::{self, Stream};
+use wasm_bindgen::prelude::*;
+
+use agent_core::event_bus::EventBus;
+use agent_core::ports::*;
+use agent_types::{
+    AgentError, ErrorKind, Result,
+    config::{LlmConfig, LlmProvider},
+    event::AgentEvent,
+};
+
+use super::openai_compat::{build_request_body, parse_chat_response, ApiResponse};
+
+#[wasm_bindgen(inline_js = r#"
+let webllm = null;
+async function load() {
+    if (!webllm) {
+        webllm = await import("https://esm.run/@mlc-ai/web-llm");
+    }
+    return webllm;
+}
+export function webllm_supported() {
+    return !!navigator.gpu;
+}
+export async function webllm_create_engine(model, onProgress) {
+    const lib = await load();
+    return await lib.CreateMLCEngine(model, {
+        initProgressCallback: (r) => onProgress(r.progress ?? 0, r.text ?? ""),
+    });
+}
+export async function webllm_chat(engine, body) {
+    const resp = await engine.chat.completions.create(JSON.parse(body));
+    return JSON.stringify(resp);
+}
+export async function webllm_models() {
+    const lib = await load();
+    return JSON.stringify(lib.prebuiltAppConfig.model_list.map((m) => m.model_id));
+}
+"#)]
+extern "C" {
+    fn webllm_supported() -> bool;
+
+    #[wasm_bindgen(catch)]
+    async fn webllm_create_engine(model: &str, on_progress: &JsValue) -> std::result::Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch)]
+    async fn webllm_chat(engine: &JsValue, body: &str) -> std::result::Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch)]
+    async fn webllm_models() -> std::result::Result<JsValue, JsValue>;
+}
+
+pub struct WebLlmProvider {
+    config: LlmConfig,
+    event_bus: EventBus,
+    /// MLCEngine instance, created on first request
+    engine: RefCell<Option<JsValue>>,
+}
+
+impl WebLlmProvider {
+    pub fn new(config: LlmConfig, event_bus: EventBus) -> Self {
+        Self {
+            config,
+            event_bus,
+            engine: RefCell::new(None),
+        }
+    }
+
+    fn model(&self) -> &str {
+        if self.config.model.is_empty() {
+            LlmProvider::Local.default_model()
+        } else {
+            &self.config.model
+        }
+    }
+
+    /// Load the model (downloading weights if not cached), emitting
+    /// `ModelLoadProgress` events along the way.
+    async fn engine(&self) -> Result<JsValue> {
+        if let Some(engine) = self.engine.borrow().as_ref() {
+            return Ok(engine.clone());
+        }
+
+        if !webllm_supported() {
+            return Err(AgentError::api(
+                ErrorKind::Unavailable,
+                "WebGPU is not available in this browser",
+            ));
+        }
+
+        let bus = self.event_bus.clone();
+        let on_progress = Closure::<dyn FnMut(f64, String)>::new(move |progress: f64, text: String| {
+            bus.emit(AgentEvent::ModelLoadProgress {
+                progress: progress as f32,
+                text,
+            });
+        });
+
+        let engine = webllm_create_engine(self.model(), on_progress.as_ref())
+            .await
+            .map_err(|e| AgentError::Llm(format!("Failed to load local model: {}", js_error_text(&e))))?;
+        *self.engine.borrow_mut() = Some(engine.clone());
+        Ok(engine)
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmPort for WebLlmProvider {
+    async fn chat_completion(&self, mut req: ChatRequest) -> Result<ChatResponse> {
+        let engine = self.engine().await?;
+
+        req.model = self.model().to_string();
+        // WebLLM only supports function calling on a few (Hermes) models and
+        // rejects `tools` for the rest, so plain chat is used elsewhere.
+        if !req.model.contains("Hermes") {
+            req.tools.clear();
+        }
+        let body = build_request_body(&req, &LlmProvider::Local).to_string();
+
+        let raw = webllm_chat(&engine, &body)
+            .await
+            .map_err(|e| AgentError::Llm(js_error_text(&e)))?;
+        let json = raw
+            .as_string()
+            .ok_or_else(|| AgentError::Llm("Unexpected WebLLM response".to_string()))?;
+        let data: ApiResponse = serde_json::from_str(&json)?;
+        parse_chat_response(data)
+    }
+
+    fn stream_chat(
+        &self,
+        _req: ChatRequest,
+    ) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+        Box::pin(stream::once(async { LlmStreamEvent::Done }))
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let raw = webllm_models()
+            .await
+            .map_err(|e| AgentError::Llm(js_error_text(&e)))?;
+        let json = raw.as_string().unwrap_or_default();
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+fn js_error_text(e: &JsValue) -> String {
+    e.dyn_ref::<js_sys::Error>()
+        .map(|err| String::from(err.message()))
+        .or_else(|| e.as_string())
+        .unwrap_or_else(|| format!("{:?}", e))
+}
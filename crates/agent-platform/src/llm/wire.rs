@@ -0,0 +1,533 @@
+//! Per-provider message mapping.
+//!
+//! The conversation is kept in OpenAI's shape: tool calls ride on assistant
+//! messages and each result is a separate `tool` message. Anthropic and
+//! Gemini want tool calls and results as content blocks instead, results
+//! inside user turns, with user and assistant turns strictly alternating.
+//! [`WireFormat`] maps a [`ChatRequest`] to each protocol's request body
+//! and the reply back to a [`Message`], so the same history round-trips
+//! through every provider.
+//!
+//! Gemini takes tool schemas in its own OpenAPI subset, so
+//! [`gemini_schema`] rewrites the JSON schemas of the tool definitions,
+//! and the configured safety settings go with every Gemini request.
+
+use std::collections::HashMap;
+
+use serde_json::{Value, json};
+
+use agent_core::ports::{ChatRequest, ChatResponse, TokenUsage};
+use agent_types::{
+    AgentError, Result,
+    config::{LlmConfig, LlmProvider, SafetySetting},
+    message::{FunctionCall, Message, MessageContent, Role, ToolCallRequest},
+};
+
+use super::openai_compat::{ApiResponse, build_request_body, parse_chat_response};
+
+/// Anthropic API version sent with every request
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// The request and response layout a provider's API uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// Chat completions: DeepSeek, OpenAI and compatible gateways
+    OpenAi,
+    /// Anthropic Messages API
+    Anthropic,
+    /// Gemini `generateContent`
+    Gemini,
+}
+
+impl WireFormat {
+    pub fn for_provider(provider: &LlmProvider) -> Self {
+        match provider {
+            LlmProvider::Anthropic => WireFormat::Anthropic,
+            LlmProvider::Google => WireFormat::Gemini,
+            _ => WireFormat::OpenAi,
+        }
+    }
+
+    /// Path of the chat endpoint, under the base URL
+    pub fn chat_path(&self, model: &str) -> String {
+        match self {
+            WireFormat::OpenAi => "/v1/chat/completions".to_string(),
+            WireFormat::Anthropic => "/v1/messages".to_string(),
+            WireFormat::Gemini => format!("/v1beta/models/{}:generateContent", model),
+        }
+    }
+
+    /// Path of the model list, under the base URL
+    pub fn models_path(&self) -> &'static str {
+        match self {
+            WireFormat::OpenAi | WireFormat::Anthropic => "/v1/models",
+            WireFormat::Gemini => "/v1beta/models",
+        }
+    }
+
+    /// The authentication headers for `api_key`
+    pub fn auth_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        match self {
+            WireFormat::OpenAi => vec![("Authorization".to_string(), format!("Bearer {}", api_key))],
+            WireFormat::Anthropic => vec![
+                ("x-api-key".to_string(), api_key.to_string()),
+                ("anthropic-version".to_string(), ANTHROPIC_VERSION.to_string()),
+                // Calls come straight from the page, without a backend
+                ("anthropic-dangerous-direct-browser-access".to_string(), "true".to_string()),
+            ],
+            WireFormat::Gemini => vec![("x-goog-api-key".to_string(), api_key.to_string())],
+        }
+    }
+
+    pub fn build_body(&self, req: &ChatRequest, config: &LlmConfig) -> Value {
+        match self {
+            WireFormat::OpenAi => build_request_body(req, &config.provider),
+            WireFormat::Anthropic => anthropic_body(req),
+            WireFormat::Gemini => gemini_body(req, &config.safety),
+        }
+    }
+
+    pub fn parse_response(&self, data: Value) -> Result<ChatResponse> {
+        match self {
+            WireFormat::OpenAi => {
+                let data: ApiResponse = serde_json::from_value(data).map_err(|e| AgentError::Llm(e.to_string()))?;
+                parse_chat_response(data)
+            }
+            WireFormat::Anthropic => parse_anthropic_response(&data),
+            WireFormat::Gemini => parse_gemini_response(&data),
+        }
+    }
+
+    /// Model ids in a model list response
+    pub fn parse_models(&self, data: &Value) -> Vec<String> {
+        let (list, key) = match self {
+            WireFormat::OpenAi | WireFormat::Anthropic => (&data["data"], "id"),
+            WireFormat::Gemini => (&data["models"], "name"),
+        };
+        list.as_array()
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|m| m[key].as_str())
+                    .map(|id| id.strip_prefix("models/").unwrap_or(id).to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Blocks of consecutive messages of the same role, merged into one turn
+#[derive(Default)]
+struct Turns {
+    turns: Vec<(&'static str, Vec<Value>)>,
+}
+
+impl Turns {
+    fn push(&mut self, role: &'static str, blocks: Vec<Value>) {
+        if blocks.is_empty() {
+            return;
+        }
+        match self.turns.last_mut() {
+            Some((last, existing)) if *last == role => existing.extend(blocks),
+            _ => self.turns.push((role, blocks)),
+        }
+    }
+}
+
+/// Tool call arguments as a JSON object, `{}` if they don't parse as one
+fn arguments_object(arguments: &str) -> Value {
+    serde_json::from_str::<Value>(arguments)
+        .ok()
+        .filter(Value::is_object)
+        .unwrap_or_else(|| json!({}))
+}
+
+/// Text of the system messages, wherever they are in the history
+fn system_text(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .filter(|m| m.role == Role::System)
+        .map(|m| m.content.as_text())
+        .filter(|t| !t.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+// ─── Anthropic ───────────────────────────────────────────────
+
+/// The system prompt and the turns of a Messages API request. System
+/// messages (the prompt, and context the runtime adds before a user
+/// message) all go to the system prompt; tool results become
+/// `tool_result` blocks of the next user turn.
+pub(crate) fn anthropic_messages(messages: &[Message]) -> (String, Vec<Value>) {
+    let mut turns = Turns::default();
+    for msg in messages {
+        let text = msg.content.as_text();
+        match msg.role {
+            Role::System => {}
+            Role::User if text.is_empty() => {}
+            Role::User => turns.push("user", vec![json!({ "type": "text", "text": text })]),
+            Role::Assistant => {
+                let mut blocks = Vec::new();
+                if !text.trim().is_empty() {
+                    blocks.push(json!({ "type": "text", "text": text }));
+                }
+                blocks.extend(msg.tool_calls.iter().map(|tc| {
+                    json!({
+                        "type": "tool_use",
+                        "id": tc.id,
+                        "name": tc.function.name,
+                        "input": arguments_object(&tc.function.arguments),
+                    })
+                }));
+                turns.push("assistant", blocks);
+            }
+            Role::Tool => turns.push(
+                "user",
+                vec![json!({
+                    "type": "tool_result",
+                    "tool_use_id": msg.tool_call_id.as_deref().unwrap_or_default(),
+                    "content": text,
+                })],
+            ),
+        }
+    }
+    let turns = turns
+        .turns
+        .into_iter()
+        .map(|(role, content)| json!({ "role": role, "content": content }))
+        .collect();
+    (system_text(messages), turns)
+}
+
+pub(crate) fn anthropic_body(req: &ChatRequest) -> Value {
+    let (system, messages) = anthropic_messages(&req.messages);
+    let mut body = json!({
+        "model": req.model,
+        "max_tokens": req.max_tokens,
+        "messages": messages,
+        "temperature": req.temperature,
+    });
+    if !system.is_empty() {
+        body["system"] = json!(system);
+    }
+    if let Some(top_p) = req.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    if !req.stop.is_empty() {
+        body["stop_sequences"] = json!(req.stop);
+    }
+    if let Some(budget) = req.reasoning.budget_tokens {
+        // As over chat completions: at least 1024 thinking tokens, and no
+        // sampling overrides while thinking
+        body["thinking"] = json!({ "type": "enabled", "budget_tokens": budget.max(1024) });
+        if let Some(obj) = body.as_object_mut() {
+            obj.remove("temperature");
+            obj.remove("top_p");
+        }
+    }
+    if !req.tools.is_empty() {
+        let tools: Vec<Value> = req
+            .tools
+            .iter()
+            .map(|t| json!({ "name": t.name, "description": t.description, "input_schema": t.parameters }))
+            .collect();
+        body["tools"] = json!(tools);
+    }
+    body
+}
+
+pub(crate) fn parse_anthropic_response(data: &Value) -> Result<ChatResponse> {
+    let blocks = data["content"]
+        .as_array()
+        .ok_or_else(|| AgentError::Llm("No content in response".to_string()))?;
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    let mut tool_calls = Vec::new();
+    for block in blocks {
+        match block["type"].as_str() {
+            Some("text") => text.push_str(block["text"].as_str().unwrap_or_default()),
+            Some("thinking") => reasoning.push_str(block["thinking"].as_str().unwrap_or_default()),
+            Some("tool_use") => tool_calls.push(ToolCallRequest {
+                id: block["id"].as_str().unwrap_or_default().to_string(),
+                function: FunctionCall {
+                    name: block["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: block["input"].to_string(),
+                },
+            }),
+            _ => {}
+        }
+    }
+    let usage = data["usage"].as_object().map(|u| {
+        let prompt = u.get("input_tokens").and_then(Value::as_u64).unwrap_or(0) as u32;
+        let completion = u.get("output_tokens").and_then(Value::as_u64).unwrap_or(0) as u32;
+        TokenUsage { prompt_tokens: prompt, completion_tokens: completion, total_tokens: prompt + completion }
+    });
+    Ok(response(text, reasoning, tool_calls, data["model"].as_str(), usage))
+}
+
+// ─── Gemini ──────────────────────────────────────────────────
+
+/// The system instruction and the contents of a `generateContent`
+/// request. Function results are matched to their call by name, which
+/// Gemini needs instead of the call id.
+pub(crate) fn gemini_contents(messages: &[Message]) -> (String, Vec<Value>) {
+    let names: HashMap<&str, &str> = messages
+        .iter()
+        .flat_map(|m| &m.tool_calls)
+        .map(|tc| (tc.id.as_str(), tc.function.name.as_str()))
+        .collect();
+    let mut turns = Turns::default();
+    for msg in messages {
+        let text = msg.content.as_text();
+        match msg.role {
+            Role::System => {}
+            Role::User if text.is_empty() => {}
+            Role::User => turns.push("user", vec![json!({ "text": text })]),
+            Role::Assistant => {
+                let mut parts = Vec::new();
+                if !text.trim().is_empty() {
+                    parts.push(json!({ "text": text }));
+                }
+                parts.extend(msg.tool_calls.iter().map(|tc| {
+                    json!({
+                        "functionCall": {
+                            "name": tc.function.name,
+                            "args": arguments_object(&tc.function.arguments),
+                        }
+                    })
+                }));
+                turns.push("model", parts);
+            }
+            Role::Tool => {
+                let name = msg.tool_call_id.as_deref().and_then(|id| names.get(id)).copied().unwrap_or_default();
+                turns.push(
+                    "user",
+                    vec![json!({
+                        "functionResponse": { "name": name, "response": { "content": text } }
+                    })],
+                );
+            }
+        }
+    }
+    let contents = turns
+        .turns
+        .into_iter()
+        .map(|(role, parts)| json!({ "role": role, "parts": parts }))
+        .collect();
+    (system_text(messages), contents)
+}
+
+pub(crate) fn gemini_body(req: &ChatRequest, safety: &[SafetySetting]) -> Value {
+    let (system, contents) = gemini_contents(&req.messages);
+    let mut config = json!({
+        "maxOutputTokens": req.max_tokens,
+        "temperature": req.temperature,
+    });
+    if let Some(top_p) = req.top_p {
+        config["topP"] = json!(top_p);
+    }
+    if !req.stop.is_empty() {
+        config["stopSequences"] = json!(req.stop);
+    }
+    if let Some(seed) = req.seed {
+        config["seed"] = json!(seed);
+    }
+    if let Some(budget) = req.reasoning.budget_tokens {
+        config["thinkingConfig"] = json!({ "thinkingBudget": budget, "includeThoughts": true });
+    }
+    let mut body = json!({ "contents": contents, "generationConfig": config });
+    if !system.is_empty() {
+        body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+    }
+    if !safety.is_empty() {
+        body["safetySettings"] = safety
+            .iter()
+            .map(|s| json!({ "category": s.category.as_str(), "threshold": s.threshold.as_str() }))
+            .collect();
+    }
+    if !req.tools.is_empty() {
+        let declarations: Vec<Value> = req
+            .tools
+            .iter()
+            .map(|t| {
+                let mut declaration = json!({ "name": t.name, "description": t.description });
+                let parameters = gemini_schema(&json!(t.parameters));
+                // A tool without parameters has no schema at all
+                if parameters.get("properties").is_some() {
+                    declaration["parameters"] = parameters;
+                }
+                declaration
+            })
+            .collect();
+        body["tools"] = json!([{ "functionDeclarations": declarations }]);
+    }
+    body
+}
+
+/// Schema keywords Gemini's OpenAPI subset takes as they are
+const GEMINI_SCHEMA_KEYS: &[&str] = &["description", "minItems", "maxItems", "minimum", "maximum", "minLength", "maxLength"];
+
+/// `schema` in the subset of OpenAPI that function declarations accept.
+/// Gemini rejects the request on keywords it doesn't know (`$schema`,
+/// `additionalProperties`, `default`, `const`...), so anything else is
+/// dropped or rewritten: a `["string", "null"]` type becomes a nullable
+/// string, `const` a one-value enum, `oneOf` an `anyOf`. Enums must be
+/// strings and objects must have properties.
+pub(crate) fn gemini_schema(schema: &Value) -> Value {
+    let Some(obj) = schema.as_object() else {
+        return json!({});
+    };
+    let mut out = serde_json::Map::new();
+    match obj.get("type") {
+        Some(Value::String(t)) => {
+            out.insert("type".to_string(), json!(t));
+        }
+        Some(Value::Array(types)) => {
+            if let Some(t) = types.iter().filter_map(Value::as_str).find(|t| *t != "null") {
+                out.insert("type".to_string(), json!(t));
+            }
+            if types.iter().any(|t| t == "null") {
+                out.insert("nullable".to_string(), json!(true));
+            }
+        }
+        _ => {}
+    }
+    if obj.get("nullable") == Some(&json!(true)) {
+        out.insert("nullable".to_string(), json!(true));
+    }
+    for key in GEMINI_SCHEMA_KEYS {
+        if let Some(value) = obj.get(*key) {
+            out.insert(key.to_string(), value.clone());
+        }
+    }
+    let is_string = out.get("type").is_none_or(|t| t == "string");
+    if let Some(format) = obj.get("format").and_then(Value::as_str) {
+        // Strings only take these two; numbers their width
+        let known = if is_string {
+            matches!(format, "enum" | "date-time")
+        } else {
+            matches!(format, "float" | "double" | "int32" | "int64")
+        };
+        if known {
+            out.insert("format".to_string(), json!(format));
+        }
+    }
+    let values = obj.get("enum").and_then(Value::as_array).cloned().or_else(|| obj.get("const").map(|c| vec![c.clone()]));
+    if let Some(values) = values.filter(|v| is_string && !v.is_empty() && v.iter().all(Value::is_string)) {
+        out.insert("type".to_string(), json!("string"));
+        out.insert("enum".to_string(), Value::Array(values));
+    }
+    if let Some(items) = obj.get("items") {
+        out.insert("items".to_string(), gemini_schema(items));
+    }
+    let variants = obj.get("anyOf").or_else(|| obj.get("oneOf")).and_then(Value::as_array);
+    if let Some(variants) = variants {
+        out.insert("anyOf".to_string(), variants.iter().map(gemini_schema).collect());
+    }
+    let properties: serde_json::Map<String, Value> = obj
+        .get("properties")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(name, property)| (name.clone(), gemini_schema(property)))
+        .collect();
+    if !properties.is_empty() {
+        let required: Vec<&Value> = obj
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|name| name.as_str().is_some_and(|n| properties.contains_key(n)))
+            .collect();
+        if !required.is_empty() {
+            out.insert("required".to_string(), json!(required));
+        }
+        out.insert("properties".to_string(), Value::Object(properties));
+    }
+    Value::Object(out)
+}
+
+pub(crate) fn parse_gemini_response(data: &Value) -> Result<ChatResponse> {
+    let Some(candidate) = data["candidates"].get(0) else {
+        let reason = data["promptFeedback"]["blockReason"].as_str().unwrap_or("no candidates");
+        return Err(AgentError::Llm(format!("No response from Gemini ({})", reason)));
+    };
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    let mut calls = Vec::new();
+    for part in candidate["content"]["parts"].as_array().into_iter().flatten() {
+        if let Some(call) = part.get("functionCall") {
+            // `args` is left out for calls without arguments
+            let args = call.get("args").filter(|a| a.is_object()).cloned().unwrap_or_else(|| json!({}));
+            calls.push(Call {
+                id: call["id"].as_str().map(String::from),
+                name: call["name"].as_str().unwrap_or_default().to_string(),
+                arguments: args.to_string(),
+            });
+        } else if let Some(t) = part["text"].as_str() {
+            if part["thought"].as_bool() == Some(true) {
+                reasoning.push_str(t);
+            } else {
+                text.push_str(t);
+            }
+        }
+    }
+    let usage = data["usageMetadata"].as_object().map(|u| {
+        let count = |key: &str| u.get(key).and_then(Value::as_u64).unwrap_or(0) as u32;
+        let prompt = count("promptTokenCount");
+        let completion = count("candidatesTokenCount");
+        TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: u.get("totalTokenCount").and_then(Value::as_u64).map_or(prompt + completion, |t| t as u32),
+        }
+    });
+    if text.is_empty() && calls.is_empty() {
+        // Filtered or garbled replies end without content
+        let reason = candidate["finishReason"].as_str().unwrap_or("STOP");
+        if reason != "STOP" && reason != "MAX_TOKENS" {
+            return Err(AgentError::Llm(format!("Gemini stopped without an answer ({})", reason)));
+        }
+    }
+    let mut response = response(text, reasoning, Vec::new(), data["modelVersion"].as_str(), usage);
+    // Older models give calls no ids; the runtime needs them to pair results
+    let prefix: String = response.message.stamp().id.chars().take(8).collect();
+    response.message.tool_calls = calls
+        .into_iter()
+        .enumerate()
+        .map(|(i, call)| ToolCallRequest {
+            id: call.id.unwrap_or_else(|| format!("call_{}_{}", prefix, i)),
+            function: FunctionCall { name: call.name, arguments: call.arguments },
+        })
+        .collect();
+    Ok(response)
+}
+
+/// A `functionCall` part
+struct Call {
+    id: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+/// An assistant reply with its metadata filled in
+fn response(
+    text: String,
+    reasoning: String,
+    tool_calls: Vec<ToolCallRequest>,
+    model: Option<&str>,
+    usage: Option<TokenUsage>,
+) -> ChatResponse {
+    let mut message = Message {
+        role: Role::Assistant,
+        content: MessageContent::Text(text),
+        tool_call_id: None,
+        tool_calls,
+        reasoning: Some(reasoning).filter(|r| !r.trim().is_empty()),
+        meta: None,
+    };
+    let meta = message.stamp();
+    meta.model = model.map(String::from);
+    meta.usage = usage.clone();
+    ChatResponse { message, usage }
+}
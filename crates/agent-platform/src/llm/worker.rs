@@ -0,0 +1,276 @@
+//! LLM network worker — provider HTTP requests off the main thread.
+//!
+//! Architecture mirrors the shell adapter:
+//! - Main thread ←→ Web Worker (`llm_worker.js`)
+//! - JSON-serialized LlmWorkerCommand/LlmWorkerEvent over postMessage
+//!
+//! The worker does the `fetch()` and, for streaming requests, the SSE
+//! parsing, posting back batched text and tool call deltas. Adapters use
+//! it as a transport: [`LlmWorker::fetch`] for whole responses and
+//! [`LlmWorker::stream`] for streamed chat completions. If the worker fails
+//! (e.g. `llm_worker.js` is missing), waiting requests fail and
+//! [`LlmWorker::failure`] says why, so providers can fetch on the main
+//! thread instead.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use futures::channel::{mpsc, oneshot};
+use futures::stream::{self, Stream};
+use wasm_bindgen::prelude::*;
+use web_sys::{ErrorEvent, MessageEvent, Worker};
+
+use agent_core::ports::LlmStreamEvent;
+use agent_types::{
+    AgentError, ErrorKind, Result,
+    event::{LlmWorkerCommand, LlmWorkerEvent},
+};
+
+/// A complete HTTP response relayed by the worker
+#[derive(Debug, Clone)]
+pub struct HttpReply {
+    pub status: u16,
+    /// Retry-After hint in milliseconds
+    pub retry_after: Option<u32>,
+    pub body: String,
+}
+
+impl HttpReply {
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// A request waiting on the worker. The flag records whether it went
+/// through a CORS proxy, which changes the advice when fetch() fails.
+pub(crate) enum Pending {
+    Response(oneshot::Sender<Result<HttpReply>>, bool),
+    Stream(mpsc::UnboundedSender<LlmStreamEvent>, bool),
+}
+
+pub struct LlmWorker {
+    worker: Worker,
+    next_id: RefCell<u64>,
+    pending: Rc<RefCell<HashMap<u64, Pending>>>,
+    /// Why the worker failed, once it has
+    failure: Rc<RefCell<Option<String>>>,
+}
+
+impl LlmWorker {
+    /// Spawn the network worker.
+    pub fn new() -> Result<Self> {
+        let worker = Worker::new("./llm_worker.js").map_err(|e| {
+            AgentError::api(ErrorKind::Unavailable, format!("Failed to create LLM worker: {:?}", e))
+        })?;
+
+        let pending: Rc<RefCell<HashMap<u64, Pending>>> = Rc::new(RefCell::new(HashMap::new()));
+
+        let pending_clone = pending.clone();
+        let onmessage = Closure::wrap(Box::new(move |event: MessageEvent| {
+            let Ok(json) = js_sys::JSON::stringify(&event.data()) else {
+                return;
+            };
+            let s: String = json.into();
+            let Ok(worker_event) = serde_json::from_str::<LlmWorkerEvent>(&s) else {
+                return;
+            };
+            dispatch(&mut pending_clone.borrow_mut(), worker_event);
+        }) as Box<dyn FnMut(MessageEvent)>);
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        onmessage.forget();
+
+        let failure = Rc::new(RefCell::new(None));
+        let on_failure = failure.clone();
+        let pending_failed = pending.clone();
+        let onerror = Closure::wrap(Box::new(move |event: ErrorEvent| {
+            event.prevent_default();
+            let message = format!(
+                "the LLM worker failed ({}): llm_worker.js may be missing or blocked by the page's CSP",
+                event.message()
+            );
+            log::warn!("{}; requests will be sent from the main thread", message);
+            for (_, request) in pending_failed.borrow_mut().drain() {
+                match request {
+                    Pending::Response(sender, _) => {
+                        let _ = sender.send(Err(AgentError::api(ErrorKind::Unavailable, message.clone())));
+                    }
+                    Pending::Stream(sender, _) => {
+                        let _ = sender.unbounded_send(LlmStreamEvent::Error(message.clone()));
+                        let _ = sender.unbounded_send(LlmStreamEvent::Done);
+                    }
+                }
+            }
+            *on_failure.borrow_mut() = Some(message);
+        }) as Box<dyn FnMut(ErrorEvent)>);
+        worker.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        onerror.forget();
+
+        Ok(Self {
+            worker,
+            next_id: RefCell::new(1),
+            pending,
+            failure,
+        })
+    }
+
+    /// Why the worker failed, if it has. A failed worker answers nothing.
+    pub fn failure(&self) -> Option<String> {
+        self.failure.borrow().clone()
+    }
+
+    fn next_request_id(&self) -> u64 {
+        let mut next = self.next_id.borrow_mut();
+        let id = *next;
+        *next += 1;
+        id
+    }
+
+    fn send_command(&self, cmd: &LlmWorkerCommand) -> Result<()> {
+        let json = serde_json::to_string(cmd)?;
+        let js_val = js_sys::JSON::parse(&json)
+            .map_err(|e| AgentError::JsInterop(format!("{:?}", e)))?;
+        self.worker
+            .post_message(&js_val)
+            .map_err(|e| AgentError::JsInterop(format!("{:?}", e)))
+    }
+
+    /// Perform a request and return the whole response, whatever its status.
+    /// `proxied` says whether `url` points at a CORS proxy. A request still
+    /// running after `timeout_ms` is aborted and fails with
+    /// [`AgentError::Timeout`].
+    pub async fn fetch(
+        &self,
+        method: &str,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: Option<String>,
+        proxied: bool,
+        timeout_ms: Option<u32>,
+    ) -> Result<HttpReply> {
+        let id = self.next_request_id();
+        let (sender, receiver) = oneshot::channel();
+        self.pending.borrow_mut().insert(id, Pending::Response(sender, proxied));
+
+        if let Err(e) = self.send_command(&LlmWorkerCommand::Fetch {
+            id,
+            method: method.to_string(),
+            url: url.to_string(),
+            headers,
+            body,
+            stream: false,
+        }) {
+            self.pending.borrow_mut().remove(&id);
+            return Err(e);
+        }
+
+        match super::deadline(receiver, timeout_ms).await {
+            Ok(reply) => reply
+                .map_err(|_| AgentError::api(ErrorKind::Unavailable, "LLM worker stopped before the request finished"))?,
+            Err(ms) => {
+                self.pending.borrow_mut().remove(&id);
+                let _ = self.send_command(&LlmWorkerCommand::Abort { id });
+                Err(AgentError::Timeout(ms.into()))
+            }
+        }
+    }
+
+    /// POST a streaming chat completions request. The stream ends with
+    /// `Done`, or with an `Error` if the request failed.
+    pub fn stream(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: String,
+        proxied: bool,
+    ) -> Pin<Box<dyn Stream<Item = LlmStreamEvent>>> {
+        let id = self.next_request_id();
+        let (sender, receiver) = mpsc::unbounded();
+        self.pending.borrow_mut().insert(id, Pending::Stream(sender, proxied));
+
+        if let Err(e) = self.send_command(&LlmWorkerCommand::Fetch {
+            id,
+            method: "POST".to_string(),
+            url: url.to_string(),
+            headers,
+            body: Some(body),
+            stream: true,
+        }) {
+            self.pending.borrow_mut().remove(&id);
+            return Box::pin(stream::once(async move { LlmStreamEvent::Error(e.user_message()) }));
+        }
+        Box::pin(receiver)
+    }
+}
+
+/// Route a worker event to the request waiting for it. Requests are
+/// removed once their final event has been delivered.
+pub(crate) fn dispatch(pending: &mut HashMap<u64, Pending>, event: LlmWorkerEvent) {
+    let id = match &event {
+        LlmWorkerEvent::Response { id, .. }
+        | LlmWorkerEvent::Delta { id, .. }
+        | LlmWorkerEvent::ToolCallDelta { id, .. }
+        | LlmWorkerEvent::Reasoning { id, .. }
+        | LlmWorkerEvent::Usage { id, .. }
+        | LlmWorkerEvent::Done { id }
+        | LlmWorkerEvent::Error { id, .. } => *id,
+    };
+    let finished = matches!(
+        event,
+        LlmWorkerEvent::Response { .. } | LlmWorkerEvent::Done { .. } | LlmWorkerEvent::Error { .. }
+    );
+
+    match pending.get(&id) {
+        Some(Pending::Stream(sender, proxied)) => {
+            for item in stream_events(event, *proxied) {
+                let _ = sender.unbounded_send(item);
+            }
+        }
+        Some(Pending::Response(..)) if finished => {
+            if let Some(Pending::Response(sender, proxied)) = pending.remove(&id) {
+                let _ = sender.send(match event {
+                    LlmWorkerEvent::Response { status, retry_after, body, .. } => Ok(HttpReply {
+                        status,
+                        retry_after,
+                        body,
+                    }),
+                    LlmWorkerEvent::Error { message, .. } => Err(AgentError::fetch_failed(&message, proxied)),
+                    _ => Err(AgentError::Llm("Unexpected streaming event".to_string())),
+                });
+            }
+            return;
+        }
+        _ => {}
+    }
+    if finished {
+        pending.remove(&id);
+    }
+}
+
+/// Stream events for one worker event on a streaming request
+pub fn stream_events(event: LlmWorkerEvent, proxied: bool) -> Vec<LlmStreamEvent> {
+    match event {
+        LlmWorkerEvent::Delta { text, .. } => vec![LlmStreamEvent::Delta(text)],
+        LlmWorkerEvent::ToolCallDelta { index, call_id, name, arguments, .. } => {
+            vec![LlmStreamEvent::ToolCallDelta {
+                index,
+                id: call_id,
+                name,
+                arguments_delta: arguments,
+            }]
+        }
+        LlmWorkerEvent::Reasoning { text, .. } => vec![LlmStreamEvent::Reasoning(text)],
+        LlmWorkerEvent::Usage { usage, .. } => vec![LlmStreamEvent::Usage(usage)],
+        LlmWorkerEvent::Done { .. } => vec![LlmStreamEvent::Done],
+        // A non-2xx status arrives as a plain response
+        LlmWorkerEvent::Response { status, body, .. } => vec![
+            LlmStreamEvent::Error(AgentError::from_http(status, &body).user_message()),
+            LlmStreamEvent::Done,
+        ],
+        LlmWorkerEvent::Error { message, .. } => vec![
+            LlmStreamEvent::Error(AgentError::fetch_failed(&message, proxied).user_message()),
+            LlmStreamEvent::Done,
+        ],
+    }
+}
@@ -0,0 +1,46 @@
+//! The app's logger: every `log` record goes to the browser console and to
+//! the in-app log (`agent_core::logs`).
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use wasm_bindgen::JsValue;
+
+use agent_core::logs::{self, LogRecord};
+
+struct AppLogger;
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        let line = JsValue::from_str(&format!("{} {}: {}", record.level(), record.target(), message));
+        match record.level() {
+            Level::Error => web_sys::console::error_1(&line),
+            Level::Warn => web_sys::console::warn_1(&line),
+            Level::Info => web_sys::console::info_1(&line),
+            Level::Debug => web_sys::console::log_1(&line),
+            Level::Trace => web_sys::console::debug_1(&line),
+        }
+        // A record logged while the panel holds the buffer would otherwise
+        // wait on itself; the console still has it
+        if let Ok(mut buffer) = logs::global().try_lock() {
+            buffer.push(LogRecord::new(record.level(), record.target(), message));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: AppLogger = AppLogger;
+
+/// Install the logger, keeping records at `level` and more severe
+pub fn init(level: LevelFilter) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
@@ -0,0 +1,80 @@
+//! Session persistence — built on top of StoragePort.
+//!
+//! Each session is stored as JSON under `session:<id>`.
+
+use std::rc::Rc;
+use agent_core::ports::StoragePort;
+use agent_types::{
+    Result,
+    session::{Session, SessionSummary},
+};
+
+const SESSION_PREFIX: &str = "session:";
+
+pub struct SessionManager {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl SessionManager {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    fn key_for(id: &str) -> String {
+        format!("{}{}", SESSION_PREFIX, id)
+    }
+
+    pub async fn save(&self, session: &Session) -> Result<()> {
+        let json = serde_json::to_vec(session)?;
+        self.storage.set(&Self::key_for(&session.id), &json).await
+    }
+
+    pub async fn load(&self, id: &str) -> Result<Option<Session>> {
+        match self.storage.get(&Self::key_for(id)).await? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        self.storage.delete(&Self::key_for(id)).await
+    }
+
+    /// Bytes the stored sessions take
+    pub async fn stored_bytes(&self) -> Result<u64> {
+        self.storage.stored_bytes(SESSION_PREFIX).await
+    }
+
+    /// Delete the sessions last updated before `cutoff` (RFC 3339), except
+    /// `keep`. Returns how many were deleted.
+    pub async fn delete_older_than(&self, cutoff: &str, keep: &str) -> Result<usize> {
+        let mut deleted = 0;
+        for summary in self.list().await? {
+            if summary.id != keep && summary.updated_at.as_str() < cutoff {
+                self.delete(&summary.id).await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// All stored sessions, most recently updated first.
+    /// Entries that fail to deserialize are skipped with a warning.
+    pub async fn list(&self) -> Result<Vec<SessionSummary>> {
+        let keys = self.storage.list_keys(SESSION_PREFIX).await?;
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let values = self.storage.get_many(&keys).await?;
+        let mut summaries = Vec::with_capacity(keys.len());
+        for (key, bytes) in keys.into_iter().zip(values) {
+            let Some(bytes) = bytes else {
+                continue;
+            };
+            match serde_json::from_slice::<Session>(&bytes) {
+                Ok(session) => summaries.push(session.summary()),
+                Err(e) => log::warn!("Skipping unreadable session {}: {}", key, e),
+            }
+        }
+        summaries.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        Ok(summaries)
+    }
+}
@@ -0,0 +1,49 @@
+//! Share links: a conversation packed into a URL fragment.
+//!
+//! The transcript's JSON is compressed with the browser's raw deflate
+//! `CompressionStream` and written as base64url, so it can sit after the
+//! `#` of a link, which browsers never send to a server.
+
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+
+use agent_types::share::{SharedTranscript, decode_base64url, encode_base64url};
+use agent_types::{AgentError, Result};
+
+#[wasm_bindgen(inline_js = r#"
+async function pipe(bytes, stream) {
+    const out = new Blob([bytes]).stream().pipeThrough(stream);
+    return new Uint8Array(await new Response(out).arrayBuffer());
+}
+export function share_deflate(bytes) {
+    return pipe(bytes, new CompressionStream("deflate-raw"));
+}
+export function share_inflate(bytes) {
+    return pipe(bytes, new DecompressionStream("deflate-raw"));
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(catch)]
+    async fn share_deflate(bytes: &[u8]) -> std::result::Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch)]
+    async fn share_inflate(bytes: &[u8]) -> std::result::Result<JsValue, JsValue>;
+}
+
+/// `transcript` as the payload of a share link
+pub async fn pack(transcript: &SharedTranscript) -> Result<String> {
+    let json = serde_json::to_vec(transcript)?;
+    let compressed = share_deflate(&json)
+        .await
+        .map_err(|e| AgentError::JsInterop(format!("Couldn't compress the conversation: {:?}", e)))?;
+    Ok(encode_base64url(&Uint8Array::new(&compressed).to_vec()))
+}
+
+/// The transcript in a share link's payload
+pub async fn unpack(payload: &str) -> Result<SharedTranscript> {
+    let compressed = decode_base64url(payload)?;
+    let json = share_inflate(&compressed)
+        .await
+        .map_err(|_| AgentError::Other("The link is damaged or incomplete".to_string()))?;
+    SharedTranscript::from_json(&Uint8Array::new(&json).to_vec())
+}
@@ -4,8 +4,13 @@
 //! - Main thread (egui) ←→ Web Worker (Wasmer-JS + WASIX bash)
 //! - Communication via postMessage with JSON-serialized WorkerCommand/WorkerEvent
 //! - The Worker loads the Wasmer-JS SDK and spawns WASIX bash processes
+//! - Output is capped per command (see [`CappedOutput`]) and a command that
+//!   keeps writing past [`ShellConfig::kill_after_bytes`] is cancelled
+//! - A watchdog pings the worker. If it isn't ready within
+//!   [`READY_TIMEOUT_MS`], or stops answering while idle, the adapter gives
+//!   up and reports why through [`WasmerShellAdapter::failure`]
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::pin::Pin;
 use std::rc::Rc;
@@ -18,81 +23,214 @@ use web_sys::{MessageEvent, Worker};
 
 use agent_core::ports::{ShellPort, ShellStreamEvent};
 use agent_types::{
-    AgentError, Result,
+    AgentError, ErrorKind, Result,
+    config::ShellConfig,
     event::{WorkerCommand, WorkerEvent},
-    tool::{ExecHandle, ExecResult},
+    tool::{ExecHandle, ExecOptions, ExecResult, RunningExec},
 };
 
 /// Shell adapter that communicates with Wasmer-JS via a Web Worker.
 pub struct WasmerShellAdapter {
     worker: Worker,
-    ready: RefCell<bool>,
+    /// Set by the message handler once the worker reports `Ready`
+    ready: Rc<RefCell<bool>>,
     next_id: RefCell<u64>,
     /// Pending one-shot results, keyed by execution ID
     pending: Rc<RefCell<HashMap<u64, PendingExec>>>,
+    /// Output limits, shared with the app so settings changes apply
+    limits: Rc<Cell<ShellConfig>>,
+    /// Why the worker was given up on, once it has been
+    failure: Rc<RefCell<Option<String>>>,
 }
 
 struct PendingExec {
-    stdout: String,
-    stderr: String,
+    command: String,
+    /// `Date.now()` when the command was sent
+    started_ms: f64,
+    /// Started with stdin open, and not closed since
+    stdin_open: bool,
+    stdout: CappedOutput,
+    stderr: CappedOutput,
     sender: Option<oneshot::Sender<ExecResult>>,
 }
 
+impl PendingExec {
+    fn finish(mut self, exit_code: i32) {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(ExecResult {
+                stdout: self.stdout.finish(),
+                stderr: self.stderr.finish(),
+                exit_code,
+                duration_ms: (js_sys::Date::now() - self.started_ms).max(0.0) as u64,
+            });
+        }
+    }
+}
+
+/// Exit code reported for a command killed for writing too much output or
+/// cancelled
+pub const KILLED_EXIT_CODE: i32 = 137;
+
+/// How long the worker has to report `Ready`
+pub const READY_TIMEOUT_MS: u32 = 20_000;
+/// Time between heartbeat pings
+const HEARTBEAT_INTERVAL_MS: u32 = 5_000;
+/// An idle worker that hasn't been heard from for this long is given up on
+const HEARTBEAT_TIMEOUT_MS: f64 = 30_000.0;
+
+/// What to tell the user when the worker never became ready
+pub fn startup_diagnostic(heard_from_worker: bool, cross_origin_isolated: bool, waited_ms: u32) -> String {
+    let secs = waited_ms / 1000;
+    let mut message = if heard_from_worker {
+        format!("The shell worker is running but Wasmer did not become ready within {} s.", secs)
+    } else {
+        format!(
+            "The shell worker did not respond within {} s: worker.js may be missing, or workers may be \
+             blocked by the page's Content-Security-Policy.",
+            secs
+        )
+    };
+    if !cross_origin_isolated {
+        message.push_str(
+            " The page is not cross-origin isolated; serve it with the headers \
+             Cross-Origin-Opener-Policy: same-origin and Cross-Origin-Embedder-Policy: require-corp \
+             so WASIX can use SharedArrayBuffer.",
+        );
+    }
+    message.push_str(" Shell commands are disabled until the page is reloaded.");
+    message
+}
+
+/// One output stream of a command, capped at `limit` bytes: the first and
+/// last `limit / 2` bytes are kept, the middle is only counted.
+pub struct CappedOutput {
+    limit: usize,
+    head: String,
+    /// Set once output has spilled past the head; later text goes to the tail
+    head_full: bool,
+    tail: String,
+    total: usize,
+}
+
+impl CappedOutput {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            head: String::new(),
+            head_full: false,
+            tail: String::new(),
+            total: 0,
+        }
+    }
+
+    pub fn push(&mut self, data: &str) {
+        self.total += data.len();
+        let half = self.limit / 2;
+        let mut rest = data;
+        if !self.head_full {
+            let take = floor_char_boundary(rest, half - self.head.len());
+            self.head.push_str(&rest[..take]);
+            rest = &rest[take..];
+            self.head_full = !rest.is_empty();
+        }
+        self.tail.push_str(rest);
+        // Trim only once the tail is well over size, to keep pushes cheap
+        if self.tail.len() > half.max(1024) * 2 {
+            self.trim_tail();
+        }
+    }
+
+    /// Bytes written to the stream so far, including elided ones
+    pub fn total_bytes(&self) -> usize {
+        self.total
+    }
+
+    /// The kept output, with a marker where bytes were elided
+    pub fn finish(mut self) -> String {
+        self.trim_tail();
+        let elided = self.total - self.head.len() - self.tail.len();
+        if elided == 0 {
+            return self.head + &self.tail;
+        }
+        format!("{}\n[… {} bytes elided …]\n{}", self.head, elided, self.tail)
+    }
+
+    fn trim_tail(&mut self) {
+        let half = self.limit / 2;
+        if self.tail.len() > half {
+            let excess = self.tail.len() - half;
+            let cut = (excess..=self.tail.len())
+                .find(|&i| self.tail.is_char_boundary(i))
+                .unwrap_or(self.tail.len());
+            self.tail.drain(..cut);
+        }
+    }
+}
+
+/// Largest char boundary in `s` at or below `max`
+fn floor_char_boundary(s: &str, max: usize) -> usize {
+    if max >= s.len() {
+        return s.len();
+    }
+    (0..=max).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0)
+}
+
 impl WasmerShellAdapter {
     /// Create a new shell adapter. Spawns the Web Worker.
-    pub fn new() -> Result<Self> {
+    pub fn new(limits: Rc<Cell<ShellConfig>>) -> Result<Self> {
         // Create the worker from the bundled JS file
         let worker = Worker::new("./worker.js")
-            .map_err(|e| AgentError::Shell(format!("Failed to create worker: {:?}", e)))?;
+            .map_err(|e| {
+                AgentError::api(ErrorKind::Unavailable, format!("Failed to create shell worker: {:?}", e))
+            })?;
 
         let pending: Rc<RefCell<HashMap<u64, PendingExec>>> =
             Rc::new(RefCell::new(HashMap::new()));
         let ready = Rc::new(RefCell::new(false));
+        // `Date.now()` of the last message from the worker (0 = none yet)
+        let last_heard = Rc::new(Cell::new(0.0));
 
         // Set up message handler for worker events
         let pending_clone = pending.clone();
         let ready_clone = ready.clone();
+        let heard_clone = last_heard.clone();
+        let limits_clone = limits.clone();
+        let worker_clone = worker.clone();
         let onmessage = Closure::wrap(Box::new(move |event: MessageEvent| {
+            heard_clone.set(js_sys::Date::now());
             let data = event.data();
             if let Ok(json_str) = js_sys::JSON::stringify(&data) {
                 let s: String = json_str.into();
                 if let Ok(worker_event) = serde_json::from_str::<WorkerEvent>(&s) {
                     match worker_event {
+                        WorkerEvent::Pong { .. } => {}
                         WorkerEvent::Ready => {
                             *ready_clone.borrow_mut() = true;
                             log::info!("Wasmer-JS worker ready");
                         }
                         WorkerEvent::Stdout { id, data } => {
-                            if let Some(exec) = pending_clone.borrow_mut().get_mut(&id) {
-                                exec.stdout.push_str(&data);
+                            let kill_after = limits_clone.get().kill_after_bytes;
+                            if append_output(&pending_clone, id, &data, false, kill_after) {
+                                kill(&worker_clone, id);
                             }
                         }
                         WorkerEvent::Stderr { id, data } => {
-                            if let Some(exec) = pending_clone.borrow_mut().get_mut(&id) {
-                                exec.stderr.push_str(&data);
+                            let kill_after = limits_clone.get().kill_after_bytes;
+                            if append_output(&pending_clone, id, &data, true, kill_after) {
+                                kill(&worker_clone, id);
                             }
                         }
                         WorkerEvent::ExitCode { id, code } => {
-                            if let Some(mut exec) = pending_clone.borrow_mut().remove(&id) {
-                                if let Some(sender) = exec.sender.take() {
-                                    let _ = sender.send(ExecResult {
-                                        stdout: exec.stdout,
-                                        stderr: exec.stderr,
-                                        exit_code: code,
-                                    });
-                                }
+                            let exec = pending_clone.borrow_mut().remove(&id);
+                            if let Some(exec) = exec {
+                                exec.finish(code);
                             }
                         }
                         WorkerEvent::Error { id, message } => {
-                            if let Some(mut exec) = pending_clone.borrow_mut().remove(&id) {
-                                exec.stderr.push_str(&message);
-                                if let Some(sender) = exec.sender.take() {
-                                    let _ = sender.send(ExecResult {
-                                        stdout: exec.stdout,
-                                        stderr: exec.stderr,
-                                        exit_code: 1,
-                                    });
-                                }
+                            let exec = pending_clone.borrow_mut().remove(&id);
+                            if let Some(mut exec) = exec {
+                                exec.stderr.push(&message);
+                                exec.finish(1);
                             }
                         }
                     }
@@ -110,14 +248,31 @@ impl WasmerShellAdapter {
             .post_message(&js_val)
             .map_err(|e| AgentError::Shell(format!("postMessage failed: {:?}", e)))?;
 
+        let failure = Rc::new(RefCell::new(None));
+        wasm_bindgen_futures::spawn_local(watchdog(
+            worker.clone(),
+            ready.clone(),
+            last_heard,
+            pending.clone(),
+            failure.clone(),
+        ));
+
         Ok(Self {
             worker,
-            ready: RefCell::new(false),
+            ready,
             next_id: RefCell::new(1),
             pending,
+            limits,
+            failure,
         })
     }
 
+    /// Why the worker was given up on, if it has been. The adapter then
+    /// runs nothing, and the app should switch to a stand-in shell.
+    pub fn failure(&self) -> Option<String> {
+        self.failure.borrow().clone()
+    }
+
     fn next_exec_id(&self) -> u64 {
         let mut id = self.next_id.borrow_mut();
         let current = *id;
@@ -125,29 +280,23 @@ impl WasmerShellAdapter {
         current
     }
 
-    fn send_command(&self, cmd: &WorkerCommand) -> Result<()> {
-        let json = serde_json::to_string(cmd)
-            .map_err(|e| AgentError::Shell(e.to_string()))?;
-        let js_val = js_sys::JSON::parse(&json)
-            .map_err(|e| AgentError::Shell(format!("{:?}", e)))?;
-        self.worker
-            .post_message(&js_val)
-            .map_err(|e| AgentError::Shell(format!("{:?}", e)))?;
-        Ok(())
-    }
-}
-
-#[async_trait(?Send)]
-impl ShellPort for WasmerShellAdapter {
-    async fn execute(&self, cmd: &str, timeout_ms: Option<u64>) -> Result<ExecResult> {
+    /// Send `cmd` to the worker. Resolves with its result once it exits.
+    fn start(&self, cmd: &str, opts: &ExecOptions, stdin: bool) -> Result<(u64, oneshot::Receiver<ExecResult>)> {
+        if let Some(reason) = self.failure() {
+            return Err(AgentError::api(ErrorKind::Unavailable, reason));
+        }
         let id = self.next_exec_id();
         let (sender, receiver) = oneshot::channel();
 
+        let max_output = self.limits.get().max_output_bytes;
         self.pending.borrow_mut().insert(
             id,
             PendingExec {
-                stdout: String::new(),
-                stderr: String::new(),
+                command: cmd.to_string(),
+                started_ms: js_sys::Date::now(),
+                stdin_open: stdin,
+                stdout: CappedOutput::new(max_output),
+                stderr: CappedOutput::new(max_output),
                 sender: Some(sender),
             },
         );
@@ -155,17 +304,142 @@ impl ShellPort for WasmerShellAdapter {
         self.send_command(&WorkerCommand::ExecBash {
             id,
             cmd: cmd.to_string(),
-            timeout_ms,
+            timeout_ms: opts.timeout_ms,
+            cwd: opts.cwd.clone(),
+            env: opts.env.clone(),
+            stdin,
         })?;
+        Ok((id, receiver))
+    }
 
-        receiver
-            .await
-            .map_err(|_| AgentError::Shell("Execution channel closed".to_string()))
+    /// Fails unless `handle` is running with stdin open
+    fn check_stdin(&self, handle: ExecHandle) -> Result<()> {
+        match self.pending.borrow().get(&handle.0) {
+            None => Err(AgentError::Shell(format!("Command {} has already exited", handle.0))),
+            Some(exec) if !exec.stdin_open => {
+                Err(AgentError::Shell(format!("Standard input of command {} is closed", handle.0)))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn send_command(&self, cmd: &WorkerCommand) -> Result<()> {
+        let json = serde_json::to_string(cmd)
+            .map_err(|e| AgentError::Shell(e.to_string()))?;
+        let js_val = js_sys::JSON::parse(&json)
+            .map_err(|e| AgentError::Shell(format!("{:?}", e)))?;
+        self.worker
+            .post_message(&js_val)
+            .map_err(|e| AgentError::Shell(format!("{:?}", e)))?;
+        Ok(())
+    }
+}
+
+/// Add output to a pending command. If it has now written more than
+/// `kill_after` bytes (0 = no limit), it is finished as killed and true is
+/// returned so the caller can stop the process.
+fn append_output(
+    pending: &RefCell<HashMap<u64, PendingExec>>,
+    id: u64,
+    data: &str,
+    is_stderr: bool,
+    kill_after: usize,
+) -> bool {
+    let mut pending = pending.borrow_mut();
+    let Some(exec) = pending.get_mut(&id) else {
+        return false;
+    };
+    if is_stderr {
+        exec.stderr.push(data);
+    } else {
+        exec.stdout.push(data);
+    }
+    let total = exec.stdout.total_bytes() + exec.stderr.total_bytes();
+    if kill_after == 0 || total <= kill_after {
+        return false;
+    }
+    if let Some(mut exec) = pending.remove(&id) {
+        exec.stderr.push(&format!(
+            "\n[killed: output exceeded {} bytes]",
+            kill_after
+        ));
+        exec.finish(KILLED_EXIT_CODE);
+    }
+    true
+}
+
+/// Ping the worker until it is ready, and keep pinging while it runs. Gives
+/// up (recording why, failing pending commands and stopping the worker) if
+/// it isn't ready in time or goes quiet while idle.
+async fn watchdog(
+    worker: Worker,
+    ready: Rc<RefCell<bool>>,
+    last_heard: Rc<Cell<f64>>,
+    pending: Rc<RefCell<HashMap<u64, PendingExec>>>,
+    failure: Rc<RefCell<Option<String>>>,
+) {
+    let started = js_sys::Date::now();
+    let mut seq = 0;
+    loop {
+        let ping = serde_json::to_string(&WorkerCommand::Ping { seq }).unwrap_or_default();
+        if let Ok(js_val) = js_sys::JSON::parse(&ping) {
+            let _ = worker.post_message(&js_val);
+        }
+        seq += 1;
+        gloo_timers::future::TimeoutFuture::new(HEARTBEAT_INTERVAL_MS).await;
+
+        let now = js_sys::Date::now();
+        let reason = if !*ready.borrow() {
+            if now - started < READY_TIMEOUT_MS as f64 {
+                continue;
+            }
+            let isolated = web_sys::window()
+                .and_then(|w| js_sys::Reflect::get(&w, &JsValue::from_str("crossOriginIsolated")).ok())
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            startup_diagnostic(last_heard.get() > 0.0, isolated, READY_TIMEOUT_MS)
+        } else if pending.borrow().is_empty() && now - last_heard.get() > HEARTBEAT_TIMEOUT_MS {
+            // A worker running a command may be blocked in synchronous WASI
+            // I/O, so only an idle one is expected to answer
+            "The shell worker stopped responding. Shell commands are disabled until the page is reloaded."
+                .to_string()
+        } else {
+            continue;
+        };
+
+        log::error!("{}", reason);
+        *ready.borrow_mut() = false;
+        let stranded: Vec<PendingExec> = pending.borrow_mut().drain().map(|(_, exec)| exec).collect();
+        for mut exec in stranded {
+            exec.stderr.push(&reason);
+            exec.finish(127);
+        }
+        worker.terminate();
+        *failure.borrow_mut() = Some(reason);
+        return;
+    }
+}
+
+/// Ask the worker to stop a runaway process
+fn kill(worker: &Worker, id: u64) {
+    log::warn!("Killing shell command {} for excessive output", id);
+    let cmd = serde_json::to_string(&WorkerCommand::CancelExec { id }).unwrap_or_default();
+    if let Ok(js_val) = js_sys::JSON::parse(&cmd) {
+        let _ = worker.post_message(&js_val);
+    }
+}
+
+#[async_trait(?Send)]
+impl ShellPort for WasmerShellAdapter {
+    async fn execute(&self, cmd: &str, opts: &ExecOptions) -> Result<ExecResult> {
+        let (_, receiver) = self.start(cmd, opts, false)?;
+        wait(receiver).await
     }
 
     fn execute_streaming(
         &self,
         _cmd: &str,
+        _opts: &ExecOptions,
     ) -> Pin<Box<dyn Stream<Item = ShellStreamEvent>>> {
         // Streaming shell will be implemented with mpsc channels in follow-up
         Box::pin(stream::once(async {
@@ -174,10 +448,60 @@ impl ShellPort for WasmerShellAdapter {
     }
 
     async fn cancel(&self, handle: ExecHandle) -> Result<()> {
+        // The worker sends nothing back for a killed process, so the
+        // command is finished here
+        let exec = self.pending.borrow_mut().remove(&handle.0);
+        if let Some(mut exec) = exec {
+            exec.stderr.push("\n[cancelled]");
+            exec.finish(KILLED_EXIT_CODE);
+        }
         self.send_command(&WorkerCommand::CancelExec { id: handle.0 })
     }
 
     fn is_ready(&self) -> bool {
         *self.ready.borrow()
     }
+
+    fn list_running(&self) -> Vec<RunningExec> {
+        let now = js_sys::Date::now();
+        let mut running: Vec<RunningExec> = self
+            .pending
+            .borrow()
+            .iter()
+            .map(|(id, exec)| RunningExec {
+                handle: ExecHandle(*id),
+                command: exec.command.clone(),
+                elapsed_ms: (now - exec.started_ms).max(0.0) as u64,
+            })
+            .collect();
+        running.sort_by_key(|exec| exec.handle.0);
+        running
+    }
+
+    async fn execute_with_stdin(&self, cmd: &str, stdin: &str, opts: &ExecOptions) -> Result<ExecResult> {
+        let (id, receiver) = self.start(cmd, opts, true)?;
+        self.write_stdin(ExecHandle(id), stdin).await?;
+        self.close_stdin(ExecHandle(id)).await?;
+        wait(receiver).await
+    }
+
+    async fn write_stdin(&self, handle: ExecHandle, data: &str) -> Result<()> {
+        self.check_stdin(handle)?;
+        self.send_command(&WorkerCommand::WriteStdin { id: handle.0, data: data.to_string() })
+    }
+
+    async fn close_stdin(&self, handle: ExecHandle) -> Result<()> {
+        self.check_stdin(handle)?;
+        if let Some(exec) = self.pending.borrow_mut().get_mut(&handle.0) {
+            exec.stdin_open = false;
+        }
+        self.send_command(&WorkerCommand::CloseStdin { id: handle.0 })
+    }
+}
+
+/// The result of a started command
+async fn wait(receiver: oneshot::Receiver<ExecResult>) -> Result<ExecResult> {
+    receiver
+        .await
+        .map_err(|_| AgentError::api(ErrorKind::Unavailable, "Shell worker stopped before the command finished"))
 }
@@ -0,0 +1,97 @@
+//! SQLite adapter — sql.js in a Web Worker per call.
+//!
+//! `sql_worker.js` loads sql.js (SQLite compiled to WebAssembly) from a
+//! CDN, opens the database file it is sent, runs the statements and sends
+//! the results back with the changed file. A fresh worker per call means a
+//! query that runs too long is stopped by terminating it.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use js_sys::{Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+use web_sys::{ErrorEvent, MessageEvent, Worker};
+
+use agent_core::ports::{SqlOutput, SqlPort, SqlRows};
+use agent_types::{AgentError, ErrorKind, Result};
+
+use crate::llm::deadline;
+
+type Reply = Rc<RefCell<Option<oneshot::Sender<Result<SqlOutput>>>>>;
+
+#[derive(Default)]
+pub struct SqlJs;
+
+impl SqlJs {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+fn interop(e: JsValue) -> AgentError {
+    AgentError::JsInterop(format!("{:?}", e))
+}
+
+/// The worker's reply as an outcome
+fn read_reply(data: &JsValue) -> Result<SqlOutput> {
+    if let Some(error) = Reflect::get(data, &"error".into()).map_err(interop)?.as_string() {
+        return Err(AgentError::api(ErrorKind::InvalidRequest, error));
+    }
+    let image = Uint8Array::new(&Reflect::get(data, &"image".into()).map_err(interop)?).to_vec();
+    let results = js_sys::JSON::stringify(&Reflect::get(data, &"results".into()).map_err(interop)?).map_err(interop)?;
+    let results: Vec<SqlRows> = serde_json::from_str(&String::from(results))?;
+    let changes = Reflect::get(data, &"changes".into()).map_err(interop)?.as_f64().unwrap_or(0.0) as u64;
+    Ok(SqlOutput { results, changes, image })
+}
+
+#[async_trait(?Send)]
+impl SqlPort for SqlJs {
+    async fn exec(&self, image: Option<Vec<u8>>, sql: &str, timeout_ms: u32) -> Result<SqlOutput> {
+        let worker = Worker::new("./sql_worker.js").map_err(|e| {
+            AgentError::api(ErrorKind::Unavailable, format!("Failed to create the SQL worker: {:?}", e))
+        })?;
+        let (sender, receiver) = oneshot::channel();
+        let reply: Reply = Rc::new(RefCell::new(Some(sender)));
+
+        let on_reply = reply.clone();
+        let onmessage = Closure::wrap(Box::new(move |event: MessageEvent| {
+            if let Some(sender) = on_reply.borrow_mut().take() {
+                let _ = sender.send(read_reply(&event.data()));
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let on_failure = reply.clone();
+        let onerror = Closure::wrap(Box::new(move |event: ErrorEvent| {
+            event.prevent_default();
+            let message = format!(
+                "the SQL worker failed to start ({}): sql_worker.js may be missing, or sql.js could not be \
+                 loaded from the CDN",
+                event.message()
+            );
+            if let Some(sender) = on_failure.borrow_mut().take() {
+                let _ = sender.send(Err(AgentError::api(ErrorKind::Unavailable, message)));
+            }
+        }) as Box<dyn FnMut(ErrorEvent)>);
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        worker.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+
+        let message = Object::new();
+        let image = image.map_or(JsValue::NULL, |bytes| Uint8Array::from(bytes.as_slice()).into());
+        let sent = Reflect::set(&message, &"sql".into(), &sql.into())
+            .and_then(|_| Reflect::set(&message, &"image".into(), &image))
+            .and_then(|_| worker.post_message(&message));
+        let outcome = match sent {
+            Ok(()) => match deadline(receiver, Some(timeout_ms)).await {
+                Ok(Ok(output)) => output,
+                Ok(Err(_)) => Err(AgentError::Other("SQL worker channel closed".to_string())),
+                Err(ms) => Err(AgentError::Timeout(ms.into())),
+            },
+            Err(e) => Err(interop(e)),
+        };
+        worker.terminate();
+        worker.set_onmessage(None);
+        worker.set_onerror(None);
+        outcome
+    }
+}
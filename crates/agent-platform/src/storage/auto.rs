@@ -6,10 +6,13 @@
 use std::rc::Rc;
 use agent_core::ports::StoragePort;
 use agent_types::Result;
+use agent_types::config::StorageConfig;
 use super::{IndexedDbStorage, MemoryStorage};
 
 /// Try to open the best available storage backend.
-/// Returns a trait object so callers are backend-agnostic.
+/// Returns a trait object so callers are backend-agnostic. The memory
+/// fallback starts with the default cap; callers apply the configured one
+/// with `set_byte_cap` once settings are loaded.
 pub async fn auto_detect_storage() -> Result<Rc<dyn StoragePort>> {
     // Try IndexedDB first (persistent)
     match IndexedDbStorage::open().await {
@@ -19,7 +22,8 @@ pub async fn auto_detect_storage() -> Result<Rc<dyn StoragePort>> {
         }
         Err(e) => {
             log::warn!("IndexedDB unavailable ({}), falling back to memory", e);
-            Ok(Rc::new(MemoryStorage::new()))
+            let cap = StorageConfig::default().memory_cap_bytes;
+            Ok(Rc::new(MemoryStorage::with_byte_cap(cap)))
         }
     }
 }
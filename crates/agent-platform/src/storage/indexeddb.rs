@@ -1,19 +1,33 @@
 //! IndexedDB storage backend.
 //! Persistent across page reloads. Works in all modern browsers.
 //! Uses web-sys bindings with wasm-bindgen-futures for async operations.
+//!
+//! Values live in the `kv` store. A second store, `meta`, holds each key's
+//! [`KeyMeta`] (size, write time, content type) and is written in the same
+//! transaction, so sizes and stats never need the values themselves.
 
 use async_trait::async_trait;
 use js_sys::{Array, Uint8Array};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{IdbDatabase, IdbTransactionMode};
+use std::collections::HashMap;
 
-use agent_core::ports::StoragePort;
-use agent_types::{AgentError, Result};
+use web_sys::{
+    DomException, IdbCursorWithValue, IdbDatabase, IdbKeyRange, IdbObjectStore, IdbRequest, IdbTransaction,
+    IdbTransactionMode, IdbVersionChangeEvent,
+};
+
+use agent_core::ports::{KeyPage, StoragePort};
+use agent_types::{
+    AgentError, ErrorKind, Result,
+    storage::{KeyMeta, detect_content_type},
+};
 
 const DB_NAME: &str = "agent_storage";
 const STORE_NAME: &str = "kv";
-const DB_VERSION: u32 = 1;
+const META_STORE: &str = "meta";
+/// 1: `kv` only. 2: adds `meta`, filled from `kv` on upgrade.
+const DB_VERSION: u32 = 2;
 
 pub struct IndexedDbStorage {
     db: IdbDatabase,
@@ -27,43 +41,84 @@ impl IndexedDbStorage {
 
         let idb_factory = window
             .indexed_db()
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?
+            .map_err(storage_error)?
             .ok_or_else(|| AgentError::Storage("IndexedDB not available".to_string()))?;
 
         let open_req = idb_factory
             .open_with_u32(DB_NAME, DB_VERSION)
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
+            .map_err(storage_error)?;
 
-        // Handle upgrade: create object store if needed
+        // Handle upgrade: create object stores if needed
         let open_req_clone = open_req.clone();
-        let onupgrade = Closure::once(move |_event: web_sys::Event| {
+        let onupgrade = Closure::once(move |event: IdbVersionChangeEvent| {
             let db: IdbDatabase = open_req_clone
                 .result()
                 .unwrap()
                 .dyn_into()
                 .unwrap();
-            // Try to create the object store; ignore error if it already exists
+            // Try to create the object stores; ignore errors if they already exist
             let _ = db.create_object_store(STORE_NAME);
+            if event.old_version() < 2.0 {
+                let _ = db.create_object_store(META_STORE);
+                if event.old_version() >= 1.0 {
+                    if let Some(tx) = open_req_clone.transaction() {
+                        if let Err(e) = backfill_meta(&tx) {
+                            log::warn!("Could not index existing storage keys: {:?}", e);
+                        }
+                    }
+                }
+            }
         });
         open_req.set_onupgradeneeded(Some(onupgrade.as_ref().unchecked_ref()));
         onupgrade.forget();
 
         let db: IdbDatabase = JsFuture::from(idb_request_to_promise(&open_req)?)
             .await
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?
+            .map_err(storage_error)?
             .dyn_into()
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
+            .map_err(storage_error)?;
 
         Ok(Self { db })
     }
 
+    /// Keys in `range`, in key order; at most `limit` of them unless 0.
+    /// The store filters by range itself, so only matching keys cross
+    /// into WASM.
+    async fn keys_in(&self, range: &IdbKeyRange, limit: usize) -> Result<Vec<String>> {
+        let store = self.transaction(IdbTransactionMode::Readonly)?;
+        let req = if limit == 0 {
+            store.get_all_keys_with_key(range)
+        } else {
+            store.get_all_keys_with_key_and_limit(range, limit.min(u32::MAX as usize) as u32)
+        }
+        .map_err(storage_error)?;
+
+        let result = JsFuture::from(idb_request_to_promise(&req)?)
+            .await
+            .map_err(storage_error)?;
+        let array: Array = result
+            .dyn_into()
+            .map_err(storage_error)?;
+        Ok(array.iter().filter_map(|key| key.as_string()).collect())
+    }
+
+    /// A read-write transaction over both stores. Writes are only durable
+    /// once the transaction commits, so callers finish with
+    /// [`wait_for_commit`].
+    fn write_transaction(&self) -> Result<IdbTransaction> {
+        let stores = Array::of2(&JsValue::from_str(STORE_NAME), &JsValue::from_str(META_STORE));
+        self.db
+            .transaction_with_str_sequence_and_mode(&stores, IdbTransactionMode::Readwrite)
+            .map_err(storage_error)
+    }
+
     fn transaction(&self, mode: IdbTransactionMode) -> Result<web_sys::IdbObjectStore> {
         let tx = self
             .db
             .transaction_with_str_and_mode(STORE_NAME, mode)
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
+            .map_err(storage_error)?;
         tx.object_store(STORE_NAME)
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))
+            .map_err(storage_error)
     }
 }
 
@@ -73,11 +128,11 @@ impl StoragePort for IndexedDbStorage {
         let store = self.transaction(IdbTransactionMode::Readonly)?;
         let req = store
             .get(&JsValue::from_str(key))
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
+            .map_err(storage_error)?;
 
         let result = JsFuture::from(idb_request_to_promise(&req)?)
             .await
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
+            .map_err(storage_error)?;
 
         if result.is_undefined() || result.is_null() {
             return Ok(None);
@@ -88,45 +143,128 @@ impl StoragePort for IndexedDbStorage {
     }
 
     async fn set(&self, key: &str, value: &[u8]) -> Result<()> {
-        let store = self.transaction(IdbTransactionMode::Readwrite)?;
+        let tx = self.write_transaction()?;
         let js_value = Uint8Array::from(value);
-        store
-            .put_with_key(&js_value, &JsValue::from_str(key))
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
-        Ok(())
+        tx.object_store(STORE_NAME)
+            .and_then(|store| store.put_with_key(&js_value, &JsValue::from_str(key)))
+            .map_err(storage_error)?;
+        let meta_store = tx.object_store(META_STORE).map_err(storage_error)?;
+        put_meta(&meta_store, key, &KeyMeta::for_value(key, value))?;
+        wait_for_commit(&tx).await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Result<Vec<Option<Vec<u8>>>> {
+        let store = self.transaction(IdbTransactionMode::Readonly)?;
+        // Issue every read before awaiting any, so they share the transaction
+        let requests = keys
+            .iter()
+            .map(|key| store.get(&JsValue::from_str(key)).map_err(storage_error))
+            .collect::<Result<Vec<_>>>()?;
+        let mut values = Vec::with_capacity(requests.len());
+        for req in &requests {
+            let result = await_request(req).await?;
+            values.push((!result.is_undefined() && !result.is_null()).then(|| Uint8Array::new(&result).to_vec()));
+        }
+        Ok(values)
+    }
+
+    async fn set_many(&self, entries: &[(&str, &[u8])]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let tx = self.write_transaction()?;
+        let store = tx.object_store(STORE_NAME).map_err(storage_error)?;
+        let meta_store = tx.object_store(META_STORE).map_err(storage_error)?;
+        for (key, value) in entries {
+            store
+                .put_with_key(&Uint8Array::from(*value), &JsValue::from_str(key))
+                .map_err(storage_error)?;
+            put_meta(&meta_store, key, &KeyMeta::for_value(key, value))?;
+        }
+        wait_for_commit(&tx).await
     }
 
     async fn delete(&self, key: &str) -> Result<()> {
-        let store = self.transaction(IdbTransactionMode::Readwrite)?;
-        store
-            .delete(&JsValue::from_str(key))
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
-        Ok(())
+        let tx = self.write_transaction()?;
+        for name in [STORE_NAME, META_STORE] {
+            tx.object_store(name)
+                .and_then(|store| store.delete(&JsValue::from_str(key)))
+                .map_err(storage_error)?;
+        }
+        wait_for_commit(&tx).await
     }
 
-    async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
-        let store = self.transaction(IdbTransactionMode::Readonly)?;
-        let req = store
-            .get_all_keys()
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
+    async fn meta(&self, key: &str) -> Result<Option<KeyMeta>> {
+        let tx = self
+            .db
+            .transaction_with_str(META_STORE)
+            .map_err(storage_error)?;
+        let req = tx
+            .object_store(META_STORE)
+            .and_then(|store| store.get(&JsValue::from_str(key)))
+            .map_err(storage_error)?;
+        match parse_meta(&await_request(&req).await?) {
+            Some(meta) => Ok(Some(meta)),
+            // Written without metadata; work it out from the value
+            None => Ok(self.get(key).await?.map(|value| KeyMeta::untimed(key, value.len() as u64))),
+        }
+    }
 
-        let result = JsFuture::from(idb_request_to_promise(&req)?)
-            .await
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
+    async fn list_meta(&self, prefix: &str) -> Result<Vec<(String, KeyMeta)>> {
+        let Some(range) = prefix_range(prefix, None)? else {
+            return Ok(Vec::new());
+        };
+        let stores = Array::of2(&JsValue::from_str(STORE_NAME), &JsValue::from_str(META_STORE));
+        let tx = self
+            .db
+            .transaction_with_str_sequence(&stores)
+            .map_err(storage_error)?;
+        let meta_store = tx.object_store(META_STORE).map_err(storage_error)?;
+        let key_req = tx
+            .object_store(STORE_NAME)
+            .and_then(|store| store.get_all_keys_with_key(&range))
+            .map_err(storage_error)?;
+        let meta_key_req = meta_store.get_all_keys_with_key(&range).map_err(storage_error)?;
+        let meta_req = meta_store.get_all_with_key(&range).map_err(storage_error)?;
 
-        let array: Array = result
-            .dyn_into()
-            .map_err(|e| AgentError::Storage(format!("{:?}", e)))?;
+        let keys: Array = await_request(&key_req).await?.dyn_into().map_err(storage_error)?;
+        let meta_keys: Array = await_request(&meta_key_req).await?.dyn_into().map_err(storage_error)?;
+        let metas: Array = await_request(&meta_req).await?.dyn_into().map_err(storage_error)?;
+        let mut known: HashMap<String, KeyMeta> = meta_keys
+            .iter()
+            .zip(metas.iter())
+            .filter_map(|(key, meta)| Some((key.as_string()?, parse_meta(&meta)?)))
+            .collect();
 
-        let mut keys = Vec::new();
-        for i in 0..array.length() {
-            if let Some(key) = array.get(i).as_string() {
-                if key.starts_with(prefix) {
-                    keys.push(key);
-                }
-            }
+        let mut out = Vec::with_capacity(keys.length() as usize);
+        for key in keys.iter().filter_map(|key| key.as_string()) {
+            let meta = match known.remove(&key) {
+                Some(meta) => meta,
+                None => match self.get(&key).await? {
+                    Some(value) => KeyMeta::untimed(&key, value.len() as u64),
+                    None => continue,
+                },
+            };
+            out.push((key, meta));
         }
-        Ok(keys)
+        Ok(out)
+    }
+
+    async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        match prefix_range(prefix, None)? {
+            Some(range) => self.keys_in(&range, 0).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn list_keys_page(&self, prefix: &str, limit: usize, after: Option<&str>) -> Result<KeyPage> {
+        // One extra key tells whether another page follows
+        let fetch = if limit == 0 { 0 } else { limit + 1 };
+        let keys = match prefix_range(prefix, after)? {
+            Some(range) => self.keys_in(&range, fetch).await?,
+            None => Vec::new(),
+        };
+        Ok(KeyPage::from_sorted(keys, limit))
     }
 
     fn backend_name(&self) -> &str {
@@ -134,6 +272,95 @@ impl StoragePort for IndexedDbStorage {
     }
 }
 
+fn put_meta(store: &IdbObjectStore, key: &str, meta: &KeyMeta) -> Result<()> {
+    let json = serde_json::to_string(meta)?;
+    store
+        .put_with_key(&JsValue::from_str(&json), &JsValue::from_str(key))
+        .map_err(storage_error)?;
+    Ok(())
+}
+
+fn parse_meta(value: &JsValue) -> Option<KeyMeta> {
+    serde_json::from_str(&value.as_string()?).ok()
+}
+
+/// Write metadata for every value already in `kv`, during the upgrade to
+/// version 2. Runs inside the upgrade transaction, which stays open until
+/// the cursor is done, so the database opens with `meta` complete.
+fn backfill_meta(tx: &IdbTransaction) -> std::result::Result<(), JsValue> {
+    let meta_store = tx.object_store(META_STORE)?;
+    let cursor_req = tx.object_store(STORE_NAME)?.open_cursor()?;
+    let req = cursor_req.clone();
+    let onsuccess = Closure::<dyn FnMut()>::new(move || {
+        // A null result means the cursor is past the last value
+        let Some(cursor) = req.result().ok().and_then(|r| r.dyn_into::<IdbCursorWithValue>().ok()) else {
+            return;
+        };
+        if let (Some(key), Ok(value)) = (cursor.key().ok().and_then(|k| k.as_string()), cursor.value()) {
+            let bytes = Uint8Array::new(&value);
+            let mut meta = KeyMeta::untimed(&key, bytes.length() as u64);
+            let head = bytes.subarray(0, bytes.length().min(16)).to_vec();
+            meta.content_type = detect_content_type(&key, &head).map(String::from);
+            if let Err(e) = put_meta(&meta_store, &key, &meta) {
+                log::warn!("Could not index {}: {}", key, e);
+            }
+        }
+        let _ = cursor.continue_();
+    });
+    cursor_req.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+    onsuccess.forget();
+    Ok(())
+}
+
+/// Keys starting with `prefix`, after `after` if given; `None` if no key
+/// can follow `after`. Every such key sorts below `prefix + '\u{ffff}'`.
+fn prefix_range(prefix: &str, after: Option<&str>) -> Result<Option<IdbKeyRange>> {
+    let upper = JsValue::from_str(&format!("{}\u{ffff}", prefix));
+    let range = match after {
+        Some(after) if after.starts_with(prefix) => {
+            IdbKeyRange::bound_with_lower_open(&JsValue::from_str(after), &upper, true)
+        }
+        // Past every key with the prefix
+        Some(after) if after > prefix => return Ok(None),
+        _ => IdbKeyRange::bound(&JsValue::from_str(prefix), &upper),
+    };
+    range.map(Some).map_err(storage_error)
+}
+
+/// Wait until `tx` commits. A failed request aborts the transaction, and
+/// some failures, such as a full quota, only show up at commit; either way
+/// the error is returned here.
+async fn wait_for_commit(tx: &IdbTransaction) -> Result<()> {
+    let tx_for_callbacks = tx.clone();
+    let promise = js_sys::Promise::new(&mut move |resolve, reject| {
+        let oncomplete = Closure::once(move |_: web_sys::Event| {
+            let _ = resolve.call0(&JsValue::NULL);
+        });
+        let tx_for_error = tx_for_callbacks.clone();
+        let onabort = Closure::once(move |_: web_sys::Event| {
+            let error = tx_for_error
+                .error()
+                .map(JsValue::from)
+                .unwrap_or_else(|| JsValue::from_str("IDB transaction aborted"));
+            let _ = reject.call1(&JsValue::NULL, &error);
+        });
+        tx_for_callbacks.set_oncomplete(Some(oncomplete.as_ref().unchecked_ref()));
+        // A failed request bubbles an error event and then aborts; only
+        // the abort is final
+        tx_for_callbacks.set_onabort(Some(onabort.as_ref().unchecked_ref()));
+        oncomplete.forget();
+        onabort.forget();
+    });
+    JsFuture::from(promise).await.map_err(storage_error)?;
+    Ok(())
+}
+
+async fn await_request(req: &IdbRequest) -> Result<JsValue> {
+    JsFuture::from(idb_request_to_promise(req)?)
+        .await
+        .map_err(storage_error)
+}
+
 /// Convert an IdbRequest to a JS Promise for use with JsFuture.
 /// Wraps the callback-based IDB API into a Future-compatible Promise.
 fn idb_request_to_promise(req: &web_sys::IdbRequest) -> Result<js_sys::Promise> {
@@ -148,8 +375,15 @@ fn idb_request_to_promise(req: &web_sys::IdbRequest) -> Result<js_sys::Promise>
                 &req_inner.result().unwrap_or(JsValue::UNDEFINED),
             );
         });
+        let req_for_error = req_for_success.clone();
         let onerror = Closure::once(move |_: web_sys::Event| {
-            let _ = reject.call1(&JsValue::NULL, &JsValue::from_str("IDB request failed"));
+            let error = req_for_error
+                .error()
+                .ok()
+                .flatten()
+                .map(JsValue::from)
+                .unwrap_or_else(|| JsValue::from_str("IDB request failed"));
+            let _ = reject.call1(&JsValue::NULL, &error);
         });
         req_for_callbacks.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
         req_for_callbacks.set_onerror(Some(onerror.as_ref().unchecked_ref()));
@@ -158,3 +392,14 @@ fn idb_request_to_promise(req: &web_sys::IdbRequest) -> Result<js_sys::Promise>
     });
     Ok(promise)
 }
+
+/// Map an IndexedDB failure, classifying a full quota as `StorageFull`.
+fn storage_error(e: JsValue) -> AgentError {
+    match e.dyn_ref::<DomException>() {
+        Some(ex) if ex.name() == "QuotaExceededError" => {
+            AgentError::api(ErrorKind::StorageFull, ex.message())
+        }
+        Some(ex) => AgentError::Storage(format!("{}: {}", ex.name(), ex.message())),
+        None => AgentError::Storage(e.as_string().unwrap_or_else(|| format!("{:?}", e))),
+    }
+}
@@ -1,46 +1,165 @@
 //! In-memory storage backend.
 //! Fastest option but not persistent across page reloads.
+//!
+//! Everything lives in the WASM heap, so the store can be capped: once the
+//! keys and values held pass the cap, the least recently used entries are
+//! evicted. The saved settings are never evicted.
 
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use async_trait::async_trait;
-use agent_core::ports::StoragePort;
-use agent_types::Result;
+use agent_core::config_store::CONFIG_KEY;
+use agent_core::ports::{StoragePort, StorageUsage};
+use agent_types::error::ErrorKind;
+use agent_types::{AgentError, Result, storage::KeyMeta};
+
+/// Keys that are never evicted
+const PINNED_KEYS: &[&str] = &[CONFIG_KEY];
+
+struct Entry {
+    value: Vec<u8>,
+    meta: KeyMeta,
+    /// Tick of the last get or set, the entry's key in `Inner::recency`
+    used: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    data: HashMap<String, Entry>,
+    /// Keys by the tick they were last used, oldest first
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    usage: StorageUsage,
+}
+
+impl Inner {
+    fn touch(&mut self, key: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.data.get_mut(key) {
+            self.recency.remove(&entry.used);
+            entry.used = tick;
+            self.recency.insert(tick, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.data.remove(key)?;
+        self.recency.remove(&entry.used);
+        self.usage.bytes -= key.len() + entry.value.len();
+        self.usage.entries -= 1;
+        Some(entry)
+    }
+
+    /// Evict least recently used entries until under the cap, sparing
+    /// pinned keys and `keep`
+    fn evict(&mut self, keep: &str) {
+        if self.usage.cap == 0 {
+            return;
+        }
+        while self.usage.bytes > self.usage.cap {
+            let victim = self
+                .recency
+                .values()
+                .find(|k| k.as_str() != keep && !PINNED_KEYS.contains(&k.as_str()))
+                .cloned();
+            let Some(victim) = victim else {
+                break;
+            };
+            if let Some(entry) = self.remove(&victim) {
+                log::debug!("Memory storage evicted {} ({} bytes)", victim, entry.value.len());
+                self.usage.evictions += 1;
+                self.usage.evicted_bytes += (victim.len() + entry.value.len()) as u64;
+            }
+        }
+    }
+}
 
 pub struct MemoryStorage {
-    data: RefCell<HashMap<String, Vec<u8>>>,
+    inner: RefCell<Inner>,
 }
 
 impl MemoryStorage {
+    /// An unbounded store
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        Self {
-            data: RefCell::new(HashMap::new()),
-        }
+        Self::with_byte_cap(0)
+    }
+
+    /// A store that keeps at most `max_bytes` of keys and values (0 = no limit)
+    pub fn with_byte_cap(max_bytes: usize) -> Self {
+        let storage = Self {
+            inner: RefCell::new(Inner::default()),
+        };
+        storage.inner.borrow_mut().usage.cap = max_bytes;
+        storage
     }
 }
 
 #[async_trait(?Send)]
 impl StoragePort for MemoryStorage {
     async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
-        Ok(self.data.borrow().get(key).cloned())
+        let mut inner = self.inner.borrow_mut();
+        inner.touch(key);
+        Ok(inner.data.get(key).map(|entry| entry.value.clone()))
     }
 
     async fn set(&self, key: &str, value: &[u8]) -> Result<()> {
-        self.data
-            .borrow_mut()
-            .insert(key.to_string(), value.to_vec());
+        let mut inner = self.inner.borrow_mut();
+        let size = key.len() + value.len();
+        let cap = inner.usage.cap;
+        if cap > 0 && size > cap && !PINNED_KEYS.contains(&key) {
+            return Err(AgentError::api(
+                ErrorKind::StorageFull,
+                format!("{} is {} bytes, more than the {} byte memory storage cap", key, size, cap),
+            ));
+        }
+        inner.remove(key);
+        let meta = KeyMeta::for_value(key, value);
+        inner.data.insert(key.to_string(), Entry { value: value.to_vec(), meta, used: 0 });
+        inner.usage.bytes += size;
+        inner.usage.entries += 1;
+        inner.touch(key);
+        inner.evict(key);
         Ok(())
     }
 
+    async fn get_many(&self, keys: &[&str]) -> Result<Vec<Option<Vec<u8>>>> {
+        let mut inner = self.inner.borrow_mut();
+        Ok(keys
+            .iter()
+            .map(|key| {
+                inner.touch(key);
+                inner.data.get(*key).map(|entry| entry.value.clone())
+            })
+            .collect())
+    }
+
     async fn delete(&self, key: &str) -> Result<()> {
-        self.data.borrow_mut().remove(key);
+        self.inner.borrow_mut().remove(key);
         Ok(())
     }
 
+    async fn meta(&self, key: &str) -> Result<Option<KeyMeta>> {
+        Ok(self.inner.borrow().data.get(key).map(|entry| entry.meta.clone()))
+    }
+
+    async fn list_meta(&self, prefix: &str) -> Result<Vec<(String, KeyMeta)>> {
+        Ok(self
+            .inner
+            .borrow()
+            .data
+            .iter()
+            .filter(|(k, _)| k.starts_with(prefix))
+            .map(|(k, entry)| (k.clone(), entry.meta.clone()))
+            .collect())
+    }
+
     async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
         let keys: Vec<String> = self
-            .data
+            .inner
             .borrow()
+            .data
             .keys()
             .filter(|k| k.starts_with(prefix))
             .cloned()
@@ -51,4 +170,14 @@ impl StoragePort for MemoryStorage {
     fn backend_name(&self) -> &str {
         "memory"
     }
+
+    fn usage(&self) -> Option<StorageUsage> {
+        Some(self.inner.borrow().usage)
+    }
+
+    fn set_byte_cap(&self, max_bytes: usize) {
+        let mut inner = self.inner.borrow_mut();
+        inner.usage.cap = max_bytes;
+        inner.evict("");
+    }
 }
@@ -1,7 +1,9 @@
 pub mod memory;
 pub mod indexeddb;
 pub mod auto;
+pub mod namespaced;
 
 pub use memory::MemoryStorage;
 pub use indexeddb::IndexedDbStorage;
 pub use auto::auto_detect_storage;
+pub use namespaced::NamespacedStorage;
@@ -0,0 +1,97 @@
+//! Storage view confined to a key prefix.
+//!
+//! Every key is stored as `<namespace><key>` in the inner backend, and
+//! `list_keys` strips the namespace again, so code written against a
+//! plain StoragePort works unchanged inside a namespace.
+
+use std::rc::Rc;
+use async_trait::async_trait;
+use agent_core::ports::{KeyPage, StoragePort, StorageUsage};
+use agent_types::{Result, storage::KeyMeta};
+
+pub struct NamespacedStorage {
+    inner: Rc<dyn StoragePort>,
+    namespace: String,
+}
+
+impl NamespacedStorage {
+    pub fn new(inner: Rc<dyn StoragePort>, namespace: impl Into<String>) -> Self {
+        Self {
+            inner,
+            namespace: namespace.into(),
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.namespace, key)
+    }
+}
+
+#[async_trait(?Send)]
+impl StoragePort for NamespacedStorage {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.get(&self.key(key)).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.inner.set(&self.key(key), value).await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> Result<Vec<Option<Vec<u8>>>> {
+        let keys: Vec<String> = keys.iter().map(|k| self.key(k)).collect();
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        self.inner.get_many(&keys).await
+    }
+
+    async fn set_many(&self, entries: &[(&str, &[u8])]) -> Result<()> {
+        let keys: Vec<String> = entries.iter().map(|(k, _)| self.key(k)).collect();
+        let entries: Vec<(&str, &[u8])> = keys.iter().map(String::as_str).zip(entries.iter().map(|(_, v)| *v)).collect();
+        self.inner.set_many(&entries).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.inner.delete(&self.key(key)).await
+    }
+
+    async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let keys = self.inner.list_keys(&self.key(prefix)).await?;
+        Ok(keys
+            .into_iter()
+            .filter_map(|k| k.strip_prefix(&self.namespace).map(String::from))
+            .collect())
+    }
+
+    async fn meta(&self, key: &str) -> Result<Option<KeyMeta>> {
+        self.inner.meta(&self.key(key)).await
+    }
+
+    async fn list_meta(&self, prefix: &str) -> Result<Vec<(String, KeyMeta)>> {
+        let entries = self.inner.list_meta(&self.key(prefix)).await?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|(k, meta)| k.strip_prefix(&self.namespace).map(|k| (k.to_string(), meta)))
+            .collect())
+    }
+
+    async fn list_keys_page(&self, prefix: &str, limit: usize, after: Option<&str>) -> Result<KeyPage> {
+        let after = after.map(|k| self.key(k));
+        let page = self.inner.list_keys_page(&self.key(prefix), limit, after.as_deref()).await?;
+        let strip = |k: String| k.strip_prefix(&self.namespace).map(String::from);
+        Ok(KeyPage {
+            keys: page.keys.into_iter().filter_map(strip).collect(),
+            next: page.next.and_then(strip),
+        })
+    }
+
+    fn backend_name(&self) -> &str {
+        self.inner.backend_name()
+    }
+
+    fn usage(&self) -> Option<StorageUsage> {
+        self.inner.usage()
+    }
+
+    fn set_byte_cap(&self, max_bytes: usize) {
+        self.inner.set_byte_cap(max_bytes)
+    }
+}
@@ -3,9 +3,13 @@
 //! IndexedDB, Shell (Worker), and LLM (fetch) tests require wasm-pack + headless browser.
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::storage::MemoryStorage;
     use crate::vfs::StorageVfs;
+    use crate::session::SessionManager;
+    use agent_types::message::Message;
+    use agent_types::session::Session;
     use agent_core::ports::{StoragePort, VfsPort};
     use std::rc::Rc;
 
@@ -165,6 +169,194 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_memory_storage_list_keys_page() {
+        let storage = MemoryStorage::new();
+        block_on(async {
+            for key in ["file:c", "file:a", "other:x", "file:b", "file:d", "file:e"] {
+                storage.set(key, b"1").await.unwrap();
+            }
+            let first = storage.list_keys_page("file:", 2, None).await.unwrap();
+            assert_eq!(first.keys, vec!["file:a", "file:b"]);
+            assert_eq!(first.next.as_deref(), Some("file:b"));
+
+            let second = storage.list_keys_page("file:", 2, first.next.as_deref()).await.unwrap();
+            assert_eq!(second.keys, vec!["file:c", "file:d"]);
+            let last = storage.list_keys_page("file:", 2, second.next.as_deref()).await.unwrap();
+            assert_eq!(last.keys, vec!["file:e"]);
+            assert_eq!(last.next, None);
+
+            let all = storage.list_keys_page("file:", 0, None).await.unwrap();
+            assert_eq!(all.keys.len(), 5);
+            assert_eq!(all.next, None);
+        });
+    }
+
+    #[test]
+    fn test_namespaced_storage_list_keys_page() {
+        use crate::storage::NamespacedStorage;
+
+        let inner = Rc::new(MemoryStorage::new());
+        let storage = NamespacedStorage::new(inner.clone(), "ws:");
+        block_on(async {
+            inner.set("file:outside", b"1").await.unwrap();
+            for key in ["file:a", "file:b", "file:c"] {
+                storage.set(key, b"1").await.unwrap();
+            }
+            let first = storage.list_keys_page("file:", 2, None).await.unwrap();
+            assert_eq!(first.keys, vec!["file:a", "file:b"]);
+            assert_eq!(first.next.as_deref(), Some("file:b"));
+            let second = storage.list_keys_page("file:", 2, first.next.as_deref()).await.unwrap();
+            assert_eq!(second.keys, vec!["file:c"]);
+            assert_eq!(second.next, None);
+        });
+    }
+
+    #[test]
+    fn test_memory_storage_meta() {
+        let storage = MemoryStorage::new();
+        block_on(async {
+            storage.set("vfs:/a.txt", b"hello").await.unwrap();
+            storage.set("vfs:/b.png", &[0; 10]).await.unwrap();
+            storage.set("session:x", b"{}").await.unwrap();
+
+            let meta = storage.meta("vfs:/a.txt").await.unwrap().unwrap();
+            assert_eq!(meta.size, 5);
+            assert_eq!(meta.content_type.as_deref(), Some("text/plain"));
+            assert!(storage.meta("vfs:/missing").await.unwrap().is_none());
+
+            let mut listed = storage.list_meta("vfs:").await.unwrap();
+            listed.sort_by(|a, b| a.0.cmp(&b.0));
+            let sizes: Vec<(&str, u64)> = listed.iter().map(|(k, m)| (k.as_str(), m.size)).collect();
+            assert_eq!(sizes, vec![("vfs:/a.txt", 5), ("vfs:/b.png", 10)]);
+            assert_eq!(storage.stored_bytes("vfs:").await.unwrap(), 15);
+        });
+    }
+
+    #[test]
+    fn test_namespaced_storage_list_meta() {
+        use crate::storage::NamespacedStorage;
+
+        let inner = Rc::new(MemoryStorage::new());
+        let storage = NamespacedStorage::new(inner.clone(), "ws:");
+        block_on(async {
+            inner.set("vfs:/outside", b"1").await.unwrap();
+            storage.set("vfs:/inside", b"12").await.unwrap();
+            let listed = storage.list_meta("vfs:").await.unwrap();
+            assert_eq!(listed.len(), 1);
+            assert_eq!(listed[0].0, "vfs:/inside");
+            assert_eq!(storage.meta("vfs:/inside").await.unwrap().unwrap().size, 2);
+        });
+    }
+
+    #[test]
+    fn test_memory_storage_get_many_and_set_many() {
+        let storage = MemoryStorage::new();
+        block_on(async {
+            storage.set_many(&[("a", b"1"), ("b", b"22")]).await.unwrap();
+            let values = storage.get_many(&["b", "missing", "a"]).await.unwrap();
+            assert_eq!(values, vec![Some(b"22".to_vec()), None, Some(b"1".to_vec())]);
+            assert!(storage.get_many(&[]).await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_namespaced_storage_get_many_and_set_many() {
+        use crate::storage::NamespacedStorage;
+
+        let inner = Rc::new(MemoryStorage::new());
+        let storage = NamespacedStorage::new(inner.clone(), "ws:");
+        block_on(async {
+            storage.set_many(&[("a", b"1"), ("b", b"2")]).await.unwrap();
+            assert_eq!(inner.get("ws:a").await.unwrap(), Some(b"1".to_vec()));
+            assert!(inner.get("a").await.unwrap().is_none());
+            let values = storage.get_many(&["b", "a"]).await.unwrap();
+            assert_eq!(values, vec![Some(b"2".to_vec()), Some(b"1".to_vec())]);
+        });
+    }
+
+    #[test]
+    fn test_memory_storage_evicts_least_recently_used() {
+        // Each entry is a 1-byte key plus 10 bytes
+        let storage = MemoryStorage::with_byte_cap(33);
+        block_on(async {
+            storage.set("a", &[0; 10]).await.unwrap();
+            storage.set("b", &[0; 10]).await.unwrap();
+            storage.set("c", &[0; 10]).await.unwrap();
+            // Reading "a" makes "b" the oldest
+            storage.get("a").await.unwrap();
+            storage.set("d", &[0; 10]).await.unwrap();
+
+            assert!(storage.exists("a").await.unwrap());
+            assert!(!storage.exists("b").await.unwrap());
+            assert!(storage.exists("d").await.unwrap());
+            let usage = storage.usage().unwrap();
+            assert_eq!(usage.bytes, 33);
+            assert_eq!(usage.entries, 3);
+            assert_eq!(usage.evictions, 1);
+            assert_eq!(usage.evicted_bytes, 11);
+        });
+    }
+
+    #[test]
+    fn test_memory_storage_never_evicts_config() {
+        use agent_core::config_store::CONFIG_KEY;
+
+        let storage = MemoryStorage::with_byte_cap(40);
+        block_on(async {
+            storage.set(CONFIG_KEY, &[0; 14]).await.unwrap();
+            storage.set("a", &[0; 10]).await.unwrap();
+            storage.set("b", &[0; 10]).await.unwrap();
+
+            assert!(storage.exists(CONFIG_KEY).await.unwrap());
+            assert!(!storage.exists("a").await.unwrap());
+            assert!(storage.exists("b").await.unwrap());
+        });
+    }
+
+    #[test]
+    fn test_memory_storage_rejects_value_over_cap() {
+        let storage = MemoryStorage::with_byte_cap(16);
+        block_on(async {
+            storage.set("a", &[0; 4]).await.unwrap();
+            let err = storage.set("big", &[0; 32]).await.unwrap_err();
+            assert_eq!(err.kind(), agent_types::error::ErrorKind::StorageFull);
+            // Nothing was evicted for the rejected write
+            assert!(storage.exists("a").await.unwrap());
+            assert_eq!(storage.usage().unwrap().evictions, 0);
+        });
+    }
+
+    #[test]
+    fn test_memory_storage_lowering_cap_evicts() {
+        let storage = MemoryStorage::new();
+        block_on(async {
+            storage.set("a", &[0; 10]).await.unwrap();
+            storage.set("b", &[0; 10]).await.unwrap();
+            storage.set("a", &[1; 10]).await.unwrap();
+            assert_eq!(storage.usage().unwrap().bytes, 22);
+
+            storage.set_byte_cap(11);
+            assert!(!storage.exists("b").await.unwrap());
+            assert_eq!(storage.get("a").await.unwrap(), Some(vec![1; 10]));
+            let usage = storage.usage().unwrap();
+            assert_eq!((usage.bytes, usage.cap, usage.evictions), (11, 11, 1));
+        });
+    }
+
+    #[test]
+    fn test_namespaced_storage_reports_inner_usage() {
+        use crate::storage::NamespacedStorage;
+
+        let inner = Rc::new(MemoryStorage::with_byte_cap(1024));
+        let storage = NamespacedStorage::new(inner.clone(), "ws:");
+        block_on(async {
+            storage.set("k", &[0; 10]).await.unwrap();
+        });
+        assert_eq!(storage.usage(), inner.usage());
+        assert_eq!(storage.usage().unwrap().bytes, 14);
+    }
+
     // ─── VFS Tests (built on MemoryStorage) ──────────────────
 
     fn make_vfs() -> StorageVfs {
@@ -240,6 +432,34 @@ mod tests {
             let stat = vfs.stat("/data.bin").await.unwrap();
             assert!(!stat.is_dir);
             assert_eq!(stat.size, 5);
+            assert!(stat.modified.is_some());
+        });
+    }
+
+    #[test]
+    fn test_vfs_append_file() {
+        let vfs = make_vfs();
+        block_on(async {
+            vfs.append_file("/logs/run.log", b"one\n").await.unwrap();
+            vfs.append_file("/logs/run.log", b"two\n").await.unwrap();
+            assert_eq!(vfs.read_file("/logs/run.log").await.unwrap(), b"one\ntwo\n");
+            // The new file's directory was created with it
+            assert!(vfs.stat("/logs").await.unwrap().is_dir);
+        });
+    }
+
+    #[test]
+    fn test_vfs_content_type() {
+        let vfs = make_vfs();
+        block_on(async {
+            vfs.write_file("/docs/readme.md", b"# Hi").await.unwrap();
+            vfs.write_file("/docs/plot", b"\x89PNG\r\n\x1a\n....").await.unwrap();
+            let stat = vfs.stat("/docs/plot").await.unwrap();
+            assert_eq!(stat.content_type.as_deref(), Some("image/png"));
+
+            let entries = vfs.list_dir("/docs").await.unwrap();
+            let types: Vec<Option<&str>> = entries.iter().map(|e| e.content_type.as_deref()).collect();
+            assert_eq!(types, vec![Some("image/png"), Some("text/markdown")]);
         });
     }
 
@@ -340,4 +560,962 @@ mod tests {
             assert_eq!(data, b"content");
         });
     }
+
+    // ─── SessionManager Tests ────────────────────────────────
+
+    #[test]
+    fn test_session_manager_save_and_load() {
+        let manager = SessionManager::new(Rc::new(MemoryStorage::new()));
+        block_on(async {
+            let mut session = Session::new("abc".to_string());
+            session.update_messages(vec![Message::user("hello"), Message::assistant("hi")]);
+            manager.save(&session).await.unwrap();
+
+            let loaded = manager.load("abc").await.unwrap().unwrap();
+            assert_eq!(loaded.title, "hello");
+            assert_eq!(loaded.messages.len(), 2);
+            assert!(manager.load("missing").await.unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn test_session_manager_list_and_delete() {
+        let storage = Rc::new(MemoryStorage::new());
+        let manager = SessionManager::new(storage.clone());
+        block_on(async {
+            let mut older = Session::new("older".to_string());
+            older.updated_at = "2026-01-01T00:00:00Z".to_string();
+            let mut newer = Session::new("newer".to_string());
+            newer.updated_at = "2026-02-01T00:00:00Z".to_string();
+            manager.save(&older).await.unwrap();
+            manager.save(&newer).await.unwrap();
+            storage.set("session:corrupt", b"not json").await.unwrap();
+
+            let list = manager.list().await.unwrap();
+            let ids: Vec<&str> = list.iter().map(|s| s.id.as_str()).collect();
+            assert_eq!(ids, vec!["newer", "older"]);
+
+            manager.delete("newer").await.unwrap();
+            assert_eq!(manager.list().await.unwrap().len(), 1);
+        });
+    }
+
+    // ─── Workspace Tests ─────────────────────────────────────
+
+    use crate::storage::NamespacedStorage;
+    use crate::workspace::WorkspaceManager;
+    use agent_types::workspace::Workspace;
+
+    #[test]
+    fn test_namespaced_storage_isolates_keys() {
+        let root = Rc::new(MemoryStorage::new());
+        let ns = NamespacedStorage::new(root.clone(), "ws:a:");
+        block_on(async {
+            ns.set("vfs:/workspace/x", b"1").await.unwrap();
+            root.set("vfs:/workspace/y", b"2").await.unwrap();
+
+            assert_eq!(ns.get("vfs:/workspace/x").await.unwrap(), Some(b"1".to_vec()));
+            assert!(ns.get("vfs:/workspace/y").await.unwrap().is_none());
+            assert!(root.exists("ws:a:vfs:/workspace/x").await.unwrap());
+            assert_eq!(ns.list_keys("vfs:").await.unwrap(), vec!["vfs:/workspace/x"]);
+
+            ns.delete("vfs:/workspace/x").await.unwrap();
+            assert!(!root.exists("ws:a:vfs:/workspace/x").await.unwrap());
+        });
+    }
+
+    #[test]
+    fn test_workspace_vfs_trees_are_separate() {
+        let root = Rc::new(MemoryStorage::new());
+        let manager = WorkspaceManager::new(root);
+        block_on(async {
+            let other = manager.create("Other", false).await.unwrap();
+            let default_vfs = StorageVfs::new(manager.storage_for(&Workspace::default_workspace()));
+            let other_vfs = StorageVfs::new(manager.storage_for(&other));
+
+            default_vfs.write_file("/workspace/a.txt", b"a").await.unwrap();
+            other_vfs.write_file("/workspace/b.txt", b"b").await.unwrap();
+
+            let names = |entries: Vec<agent_types::tool::DirEntry>| {
+                entries.into_iter().map(|e| e.name).collect::<Vec<_>>()
+            };
+            assert_eq!(names(default_vfs.list_dir("/workspace").await.unwrap()), vec!["a.txt"]);
+            assert_eq!(names(other_vfs.list_dir("/workspace").await.unwrap()), vec!["b.txt"]);
+        });
+    }
+
+    #[test]
+    fn test_workspace_manager_create_rename_list() {
+        let manager = WorkspaceManager::new(Rc::new(MemoryStorage::new()));
+        block_on(async {
+            let list = manager.list().await.unwrap();
+            assert_eq!(list.len(), 1);
+            assert!(list[0].is_default());
+
+            let mut first = manager.create("First", false).await.unwrap();
+            first.created_at = "2026-01-01T00:00:00Z".to_string();
+            manager.save(&first).await.unwrap();
+            manager.create("  Second ", true).await.unwrap();
+            manager.rename(&first.id, "Renamed").await.unwrap();
+
+            let names: Vec<String> = manager.list().await.unwrap().into_iter().map(|w| w.name).collect();
+            assert_eq!(names, vec!["Default", "Renamed", "Second"]);
+            assert!(manager.rename("missing", "x").await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_workspace_manager_delete_removes_namespace() {
+        let root = Rc::new(MemoryStorage::new());
+        let manager = WorkspaceManager::new(root.clone());
+        block_on(async {
+            let ws = manager.create("Doomed", true).await.unwrap();
+            manager.storage_for(&ws).set("vfs:/workspace/f", b"x").await.unwrap();
+            manager.session_storage_for(&ws).set("session:s", b"{}").await.unwrap();
+            root.set("vfs:/workspace/keep", b"y").await.unwrap();
+            manager.set_current(&ws.id).await.unwrap();
+            assert_eq!(manager.current().await.unwrap().id, ws.id);
+
+            manager.delete(&ws.id).await.unwrap();
+            assert!(manager.load(&ws.id).await.unwrap().is_none());
+            assert!(root.list_keys("ws:").await.unwrap().is_empty());
+            assert!(root.exists("vfs:/workspace/keep").await.unwrap());
+            assert!(manager.current().await.unwrap().is_default());
+
+            assert!(manager.delete("default").await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_workspace_shared_sessions_use_root_storage() {
+        let root = Rc::new(MemoryStorage::new());
+        let manager = WorkspaceManager::new(root.clone());
+        block_on(async {
+            let shared = manager.create("Shared", false).await.unwrap();
+            let sessions = SessionManager::new(manager.session_storage_for(&shared));
+            sessions.save(&Session::new("s1".to_string())).await.unwrap();
+            assert!(root.exists("session:s1").await.unwrap());
+        });
+    }
+
+    // ─── Request Body Tests ──────────────────────────────────
+
+    use crate::llm::openai_compat::{
+        ApiResponse, build_request_body, message_to_json, parse_chat_response, request_headers,
+    };
+    use agent_core::ports::ChatRequest;
+    use agent_types::config::{LlmProvider, ReasoningConfig, ReasoningEffort};
+    use agent_types::tool::{ParamSchema, ToolDefinition, ToolParameters};
+
+    #[test]
+    fn test_request_body_omits_unset_sampling_params() {
+        let req = ChatRequest {
+            messages: vec![Message::user("hi")],
+            model: "m".to_string(),
+            max_tokens: 100,
+            temperature: 0.5,
+            ..Default::default()
+        };
+        let body = build_request_body(&req, &LlmProvider::OpenAI);
+        assert_eq!(body["model"], "m");
+        for key in ["top_p", "frequency_penalty", "presence_penalty", "stop", "seed", "tools"] {
+            assert!(body.get(key).is_none(), "unexpected {}", key);
+        }
+    }
+
+    #[test]
+    fn test_request_body_includes_sampling_params() {
+        let req = ChatRequest {
+            messages: vec![Message::user("hi")],
+            model: "m".to_string(),
+            max_tokens: 100,
+            temperature: 0.5,
+            top_p: Some(0.5),
+            frequency_penalty: Some(0.25),
+            presence_penalty: Some(-1.0),
+            stop: vec!["###".to_string()],
+            seed: Some(42),
+            ..Default::default()
+        };
+        let body = build_request_body(&req, &LlmProvider::OpenAI);
+        assert_eq!(body["top_p"], 0.5);
+        assert_eq!(body["frequency_penalty"], 0.25);
+        assert_eq!(body["presence_penalty"], -1.0);
+        assert_eq!(body["stop"], serde_json::json!(["###"]));
+        assert_eq!(body["seed"], 42);
+    }
+
+    #[test]
+    fn test_request_body_reasoning_effort_for_openai() {
+        let req = ChatRequest {
+            reasoning: ReasoningConfig {
+                effort: Some(ReasoningEffort::High),
+                budget_tokens: Some(2048),
+            },
+            ..Default::default()
+        };
+        let body = build_request_body(&req, &LlmProvider::OpenAI);
+        assert_eq!(body["reasoning_effort"], "high");
+        assert!(body.get("thinking").is_none());
+
+        // DeepSeek has no reasoning knobs
+        let body = build_request_body(&req, &LlmProvider::DeepSeek);
+        assert!(body.get("reasoning_effort").is_none());
+    }
+
+    #[test]
+    fn test_request_body_thinking_budget_for_anthropic() {
+        let req = ChatRequest {
+            temperature: 0.7,
+            top_p: Some(0.9),
+            reasoning: ReasoningConfig {
+                effort: Some(ReasoningEffort::Low),
+                budget_tokens: Some(100),
+            },
+            ..Default::default()
+        };
+        let body = build_request_body(&req, &LlmProvider::Anthropic);
+        assert_eq!(body["thinking"]["type"], "enabled");
+        assert_eq!(body["thinking"]["budget_tokens"], 1024);
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("top_p").is_none());
+        assert!(body.get("reasoning_effort").is_none());
+    }
+
+    fn strict_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "search".to_string(),
+            description: "Search".to_string(),
+            parameters: ToolParameters::object()
+                .property("query", ParamSchema::string("Query"), true)
+                .property("limit", ParamSchema::integer("Max hits").range(1.0, 20.0), false),
+            strict: true,
+        }
+    }
+
+    #[test]
+    fn test_request_body_strict_tool_for_openai() {
+        let req = ChatRequest {
+            tools: vec![strict_tool()],
+            ..Default::default()
+        };
+        let body = build_request_body(&req, &LlmProvider::OpenAI);
+        let function = &body["tools"][0]["function"];
+        assert_eq!(function["strict"], true);
+        let params = &function["parameters"];
+        assert_eq!(params["additionalProperties"], false);
+        assert_eq!(params["required"], serde_json::json!(["limit", "query"]));
+        // Optional parameters become nullable; constraints are kept
+        assert_eq!(params["properties"]["limit"]["type"], serde_json::json!(["integer", "null"]));
+        assert_eq!(params["properties"]["limit"]["maximum"], 20.0);
+        assert_eq!(params["properties"]["query"]["type"], "string");
+    }
+
+    #[test]
+    fn test_request_body_strict_optional_enum_allows_null() {
+        let mut tool = strict_tool();
+        tool.parameters = tool.parameters.property(
+            "order",
+            ParamSchema::string("Sort order").one_of(&["asc", "desc"]),
+            false,
+        );
+        let req = ChatRequest {
+            tools: vec![tool],
+            ..Default::default()
+        };
+        let body = build_request_body(&req, &LlmProvider::OpenAI);
+        let order = &body["tools"][0]["function"]["parameters"]["properties"]["order"];
+        assert_eq!(order["type"], serde_json::json!(["string", "null"]));
+        assert_eq!(order["enum"], serde_json::json!(["asc", "desc", null]));
+    }
+
+    #[test]
+    fn test_request_body_strict_ignored_for_other_providers() {
+        let req = ChatRequest {
+            tools: vec![strict_tool()],
+            ..Default::default()
+        };
+        let body = build_request_body(&req, &LlmProvider::DeepSeek);
+        let function = &body["tools"][0]["function"];
+        assert!(function.get("strict").is_none());
+        assert_eq!(function["parameters"]["required"], serde_json::json!(["query"]));
+        assert_eq!(function["parameters"]["properties"]["limit"]["type"], "integer");
+    }
+
+    #[test]
+    fn test_parse_response_keeps_reasoning_content() {
+        let json = r#"{"choices":[{"message":{"role":"assistant","content":"42","reasoning_content":"6 times 7"}}],"usage":null}"#;
+        let data: ApiResponse = serde_json::from_str(json).unwrap();
+        let response = parse_chat_response(data).unwrap();
+        assert_eq!(response.message.content.as_text(), "42");
+        assert_eq!(response.message.reasoning.as_deref(), Some("6 times 7"));
+    }
+
+    #[test]
+    fn test_parse_response_accepts_reasoning_alias() {
+        let json = r#"{"choices":[{"message":{"role":"assistant","content":"hi","reasoning":"greet back"}}],"usage":null}"#;
+        let data: ApiResponse = serde_json::from_str(json).unwrap();
+        let response = parse_chat_response(data).unwrap();
+        assert_eq!(response.message.reasoning.as_deref(), Some("greet back"));
+    }
+
+    #[test]
+    fn test_parse_response_drops_blank_reasoning() {
+        let json = r#"{"choices":[{"message":{"role":"assistant","content":"hi","reasoning_content":"  "}}],"usage":null}"#;
+        let data: ApiResponse = serde_json::from_str(json).unwrap();
+        assert!(parse_chat_response(data).unwrap().message.reasoning.is_none());
+    }
+
+    #[test]
+    fn test_parse_response_appends_url_citations() {
+        let json = r#"{"choices":[{"message":{"role":"assistant","content":"See [1].\n\n[1]: /workspace/a.rs:3",
+            "annotations":[
+                {"type":"url_citation","url_citation":{"url":"https://a.example","title":"A \"quoted\"","start_index":0,"end_index":3}},
+                {"type":"url_citation","url_citation":{"url":"https://a.example"}},
+                {"type":"url_citation","url_citation":{"url":"https://b.example"}},
+                {"type":"file_citation"}
+            ]}}],"usage":null}"#;
+        let data: ApiResponse = serde_json::from_str(json).unwrap();
+        let response = parse_chat_response(data).unwrap();
+        assert_eq!(
+            response.message.content.as_text(),
+            "See [1].\n\n[1]: /workspace/a.rs:3\n\n[2]: https://a.example \"A 'quoted'\"\n[3]: https://b.example"
+        );
+    }
+
+    #[test]
+    fn test_reasoning_not_sent_back() {
+        let mut msg = Message::assistant("42");
+        msg.reasoning = Some("6 times 7".to_string());
+        let json = message_to_json(&msg);
+        assert!(json.get("reasoning_content").is_none());
+        assert!(json.get("reasoning").is_none());
+    }
+
+    #[test]
+    fn test_parse_response_fills_meta() {
+        let json = r#"{"model":"deepseek-chat-v3","choices":[{"message":{"role":"assistant","content":"hi"}}],"usage":{"prompt_tokens":7,"completion_tokens":1,"total_tokens":8}}"#;
+        let data: ApiResponse = serde_json::from_str(json).unwrap();
+        let response = parse_chat_response(data).unwrap();
+        let meta = response.message.meta.unwrap();
+        assert!(!meta.id.is_empty());
+        assert_eq!(meta.model.as_deref(), Some("deepseek-chat-v3"));
+        assert_eq!(meta.usage.unwrap().total_tokens, 8);
+        assert_eq!(response.usage.unwrap().prompt_tokens, 7);
+    }
+
+    #[test]
+    fn test_request_headers_include_custom_headers() {
+        let mut config = agent_types::config::LlmConfig {
+            api_key: "sk-test".to_string(),
+            ..Default::default()
+        };
+        config.headers = vec![
+            ("x-portkey-provider".to_string(), "openai".to_string()),
+            ("authorization".to_string(), "Gateway abc".to_string()),
+            ("  ".to_string(), "ignored".to_string()),
+        ];
+
+        let headers = request_headers(&config, true);
+        assert_eq!(headers.len(), 3);
+        assert!(headers.contains(&("Content-Type".to_string(), "application/json".to_string())));
+        assert!(headers.contains(&("x-portkey-provider".to_string(), "openai".to_string())));
+        // The configured auth header replaces the bearer token
+        let auth: Vec<_> = headers.iter().filter(|(n, _)| n.eq_ignore_ascii_case("authorization")).collect();
+        assert_eq!(auth, [&("authorization".to_string(), "Gateway abc".to_string())]);
+
+        assert_eq!(request_headers(&config, false).len(), 2);
+    }
+
+    // ─── DeepSeek Reasoner Tests ─────────────────────────────
+
+    use crate::llm::openai_compat::{model_quirks, rejects_tools, split_think_tags, without_tools};
+
+    #[test]
+    fn test_reasoner_body_drops_sampling_and_merges_turns() {
+        assert!(model_quirks("deepseek-reasoner").alternating_turns);
+        assert!(model_quirks("deepseek/deepseek-r1:free").alternating_turns);
+        assert!(model_quirks("deepseek-r1-distill-llama-70b").alternating_turns);
+        assert!(!model_quirks("deepseek-chat").alternating_turns);
+
+        let req = ChatRequest {
+            messages: vec![Message::system("sys"), Message::system("ctx"), Message::user("a"), Message::user("b")],
+            model: "deepseek-reasoner".to_string(),
+            max_tokens: 100,
+            temperature: 0.5,
+            top_p: Some(0.9),
+            presence_penalty: Some(1.0),
+            ..Default::default()
+        };
+        let body = build_request_body(&req, &LlmProvider::DeepSeek);
+        for key in ["temperature", "top_p", "presence_penalty"] {
+            assert!(body.get(key).is_none(), "unexpected {}", key);
+        }
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2]["content"], "a\n\nb");
+
+        // Other models on DeepSeek keep their settings
+        let chat = ChatRequest { model: "deepseek-chat".to_string(), ..req };
+        let body = build_request_body(&chat, &LlmProvider::DeepSeek);
+        assert_eq!(body["temperature"], 0.5);
+        assert_eq!(body["messages"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_o_series_body_uses_completion_tokens_and_developer_role() {
+        assert_eq!(model_quirks("o3-mini").system_role, Some("developer"));
+        assert_eq!(model_quirks("openai/o4-mini").system_role, Some("developer"));
+        assert_eq!(model_quirks("o1-mini").system_role, Some("user"));
+        assert_eq!(model_quirks("gpt-4o"), Default::default());
+        assert_eq!(model_quirks("o30b-instruct"), Default::default());
+
+        let req = ChatRequest {
+            messages: vec![Message::system("sys"), Message::user("hi")],
+            model: "o3".to_string(),
+            max_tokens: 2000,
+            temperature: 0.7,
+            top_p: Some(0.9),
+            ..Default::default()
+        };
+        let body = build_request_body(&req, &LlmProvider::OpenAI);
+        assert_eq!(body["max_completion_tokens"], 2000);
+        for key in ["max_tokens", "temperature", "top_p"] {
+            assert!(body.get(key).is_none(), "unexpected {}", key);
+        }
+        assert_eq!(body["messages"][0]["role"], "developer");
+        assert_eq!(body["messages"][1]["role"], "user");
+
+        let body = build_request_body(&ChatRequest { model: "gpt-4o".to_string(), ..req }, &LlmProvider::OpenAI);
+        assert_eq!(body["max_tokens"], 2000);
+        assert_eq!(body["messages"][0]["role"], "system");
+    }
+
+    #[test]
+    fn test_tool_refusal_falls_back_to_plain_history() {
+        let refusal = agent_types::AgentError::from_http(
+            400,
+            r#"{"error":{"message":"deepseek-reasoner does not support Function Calling"}}"#,
+        );
+        assert!(rejects_tools(&refusal));
+        assert!(!rejects_tools(&agent_types::AgentError::from_http(400, r#"{"error":{"message":"bad max_tokens"}}"#)));
+
+        let mut req = ChatRequest { messages: tool_history(), model: "deepseek-reasoner".to_string(), ..Default::default() };
+        req.tools = vec![ToolDefinition {
+            name: "bash".to_string(),
+            description: "Run".to_string(),
+            parameters: ToolParameters::object(),
+            strict: false,
+        }];
+        without_tools(&mut req);
+        assert!(req.tools.is_empty());
+        assert!(req.messages[0].content.as_text().contains("Tools are not available"));
+        assert_eq!(req.messages[2].content.as_text(), "Checking\n[called bash with {\"command\":\"ls\"}]\n[called bash with {\"command\":\"pwd\"}]");
+        assert!(req.messages.iter().all(|m| m.role != agent_types::message::Role::Tool && m.tool_calls.is_empty()));
+        assert_eq!(req.messages[3].content.as_text(), "[tool result]\na.txt");
+    }
+
+    #[test]
+    fn test_think_tags_split_into_reasoning() {
+        let (reasoning, text) = split_think_tags("<think>\nadd them\n</think>\n\n4".to_string());
+        assert_eq!(reasoning.as_deref(), Some("add them"));
+        assert_eq!(text, "4");
+        assert_eq!(split_think_tags("plain".to_string()), (None, "plain".to_string()));
+        assert_eq!(split_think_tags("<think>unfinished".to_string()).0, None);
+    }
+
+    // ─── Wire Format Tests ───────────────────────────────────
+
+    use crate::llm::wire::{WireFormat, anthropic_messages, gemini_contents, gemini_schema};
+    use agent_types::message::{FunctionCall, ToolCallRequest};
+
+    /// A turn with two tool calls, then context and a follow-up question
+    fn tool_history() -> Vec<Message> {
+        let mut assistant = Message::assistant("Checking");
+        assistant.tool_calls = ["ls", "pwd"]
+            .iter()
+            .enumerate()
+            .map(|(i, cmd)| ToolCallRequest {
+                id: format!("call_{}", i),
+                function: FunctionCall { name: "bash".to_string(), arguments: format!(r#"{{"command":"{}"}}"#, cmd) },
+            })
+            .collect();
+        vec![
+            Message::system("Be brief."),
+            Message::user("What is here?"),
+            assistant,
+            Message::tool_result("call_0", "a.txt"),
+            Message::tool_result("call_1", "/workspace"),
+            Message::system("Relevant: a.txt"),
+            Message::user("And now?"),
+        ]
+    }
+
+    #[test]
+    fn test_anthropic_tool_results_in_user_turns() {
+        let (system, turns) = anthropic_messages(&tool_history());
+        assert_eq!(system, "Be brief.\n\nRelevant: a.txt");
+        let roles: Vec<_> = turns.iter().map(|t| t["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        assert_eq!(turns[1]["content"][1], serde_json::json!({
+            "type": "tool_use", "id": "call_0", "name": "bash", "input": { "command": "ls" }
+        }));
+        let results = &turns[2]["content"];
+        assert_eq!(results[0], serde_json::json!({ "type": "tool_result", "tool_use_id": "call_0", "content": "a.txt" }));
+        assert_eq!(results[1]["tool_use_id"], "call_1");
+        assert_eq!(results[2], serde_json::json!({ "type": "text", "text": "And now?" }));
+    }
+
+    #[test]
+    fn test_anthropic_reply_round_trips() {
+        let reply = serde_json::json!({
+            "model": "claude-x",
+            "content": [
+                { "type": "thinking", "thinking": "list it" },
+                { "type": "text", "text": "Listing" },
+                { "type": "tool_use", "id": "toolu_1", "name": "bash", "input": { "command": "ls" } }
+            ],
+            "usage": { "input_tokens": 12, "output_tokens": 3 }
+        });
+        let response = WireFormat::Anthropic.parse_response(reply).unwrap();
+        let message = response.message;
+        assert_eq!(message.content.as_text(), "Listing");
+        assert_eq!(message.reasoning.as_deref(), Some("list it"));
+        assert_eq!(message.meta.as_ref().unwrap().model.as_deref(), Some("claude-x"));
+        assert_eq!(response.usage.unwrap().total_tokens, 15);
+
+        let (_, turns) = anthropic_messages(&[Message::user("hi"), message, Message::tool_result("toolu_1", "ok")]);
+        assert_eq!(turns[1]["content"][1]["input"], serde_json::json!({ "command": "ls" }));
+        assert_eq!(turns[2]["content"][0]["tool_use_id"], "toolu_1");
+    }
+
+    #[test]
+    fn test_gemini_function_responses_by_name() {
+        let (system, contents) = gemini_contents(&tool_history());
+        assert_eq!(system, "Be brief.\n\nRelevant: a.txt");
+        let roles: Vec<_> = contents.iter().map(|c| c["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["user", "model", "user"]);
+        assert_eq!(contents[1]["parts"][2]["functionCall"], serde_json::json!({ "name": "bash", "args": { "command": "pwd" } }));
+        assert_eq!(
+            contents[2]["parts"][0],
+            serde_json::json!({ "functionResponse": { "name": "bash", "response": { "content": "a.txt" } } })
+        );
+        assert_eq!(contents[2]["parts"][2], serde_json::json!({ "text": "And now?" }));
+    }
+
+    #[test]
+    fn test_gemini_reply_gets_call_ids() {
+        let reply = serde_json::json!({
+            "modelVersion": "gemini-x",
+            "candidates": [{ "content": { "role": "model", "parts": [
+                { "text": "plan", "thought": true },
+                { "functionCall": { "name": "bash", "args": { "command": "ls" } } },
+                { "functionCall": { "name": "read_file", "args": { "path": "/a" } } }
+            ] } }],
+            "usageMetadata": { "promptTokenCount": 5, "candidatesTokenCount": 2, "totalTokenCount": 9 }
+        });
+        let response = WireFormat::Gemini.parse_response(reply).unwrap();
+        let calls = &response.message.tool_calls;
+        assert_eq!(calls.len(), 2);
+        assert_ne!(calls[0].id, calls[1].id);
+        assert_eq!(calls[1].function.arguments, r#"{"path":"/a"}"#);
+        assert_eq!(response.message.reasoning.as_deref(), Some("plan"));
+        assert_eq!(response.message.content.as_text(), "");
+        assert_eq!(response.usage.unwrap().total_tokens, 9);
+
+        let blocked = serde_json::json!({ "promptFeedback": { "blockReason": "SAFETY" } });
+        assert!(WireFormat::Gemini.parse_response(blocked).unwrap_err().to_string().contains("SAFETY"));
+    }
+
+    #[test]
+    fn test_gemini_schema_drops_unsupported_keywords() {
+        let schema = serde_json::json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "path": { "type": "string", "format": "uri", "default": "/" },
+                "depth": { "type": ["integer", "null"], "minimum": 0 },
+                "mode": { "const": "fast" },
+                "tags": { "type": "array", "items": { "type": "string", "enum": ["a", "b"] } },
+                "options": { "type": "object", "properties": {} }
+            },
+            "required": ["path", "missing"]
+        });
+        let out = gemini_schema(&schema);
+        assert_eq!(out, serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "depth": { "type": "integer", "nullable": true, "minimum": 0 },
+                "mode": { "type": "string", "enum": ["fast"] },
+                "tags": { "type": "array", "items": { "type": "string", "enum": ["a", "b"] } },
+                "options": { "type": "object" }
+            },
+            "required": ["path"]
+        }));
+    }
+
+    #[test]
+    fn test_gemini_body_sends_safety_and_declarations() {
+        use agent_types::config::{HarmCategory, HarmThreshold, LlmConfig, SafetySetting};
+
+        let config = LlmConfig {
+            provider: LlmProvider::Google,
+            safety: vec![SafetySetting { category: HarmCategory::DangerousContent, threshold: HarmThreshold::BlockNone }],
+            ..Default::default()
+        };
+        let req = ChatRequest {
+            messages: vec![Message::user("hi")],
+            tools: vec![
+                ToolDefinition {
+                    name: "list".to_string(),
+                    description: "List".to_string(),
+                    parameters: ToolParameters::object(),
+                    strict: false,
+                },
+                ToolDefinition {
+                    name: "read".to_string(),
+                    description: "Read".to_string(),
+                    parameters: serde_json::from_value(serde_json::json!({
+                        "type": "object",
+                        "properties": { "path": { "type": "string" } },
+                        "required": ["path"]
+                    }))
+                    .unwrap(),
+                    strict: true,
+                },
+            ],
+            ..Default::default()
+        };
+        let body = WireFormat::Gemini.build_body(&req, &config);
+        assert_eq!(
+            body["safetySettings"],
+            serde_json::json!([{ "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "BLOCK_NONE" }])
+        );
+        let declarations = &body["tools"][0]["functionDeclarations"];
+        assert!(declarations[0].get("parameters").is_none());
+        assert_eq!(declarations[1]["parameters"]["required"], serde_json::json!(["path"]));
+
+        let body = WireFormat::Gemini.build_body(&req, &LlmConfig::default());
+        assert!(body.get("safetySettings").is_none());
+    }
+
+    #[test]
+    fn test_gemini_calls_keep_ids_and_empty_args() {
+        let reply = serde_json::json!({
+            "candidates": [{ "content": { "role": "model", "parts": [
+                { "functionCall": { "id": "fc_1", "name": "list_files" } }
+            ] }, "finishReason": "STOP" }]
+        });
+        let calls = WireFormat::Gemini.parse_response(reply).unwrap().message.tool_calls;
+        assert_eq!(calls[0].id, "fc_1");
+        assert_eq!(calls[0].function.arguments, "{}");
+
+        let filtered = serde_json::json!({ "candidates": [{ "finishReason": "SAFETY" }] });
+        let err = WireFormat::Gemini.parse_response(filtered).unwrap_err();
+        assert!(err.to_string().contains("SAFETY"));
+    }
+
+    #[test]
+    fn test_wire_format_endpoints_and_auth() {
+        assert_eq!(WireFormat::Gemini.chat_path("gemini-2.5-pro"), "/v1beta/models/gemini-2.5-pro:generateContent");
+        let models = serde_json::json!({ "models": [{ "name": "models/gemini-2.5-pro" }] });
+        assert_eq!(WireFormat::Gemini.parse_models(&models), vec!["gemini-2.5-pro"]);
+
+        let config = agent_types::config::LlmConfig {
+            provider: LlmProvider::Anthropic,
+            api_key: "sk-ant".to_string(),
+            ..Default::default()
+        };
+        let headers = request_headers(&config, true);
+        assert!(headers.contains(&("x-api-key".to_string(), "sk-ant".to_string())));
+        assert!(headers.iter().any(|(n, _)| n == "anthropic-version"));
+        assert!(!headers.iter().any(|(n, _)| n == "Authorization"));
+    }
+
+    // ─── Shell Output Cap Tests ──────────────────────────────
+
+    use crate::shell::CappedOutput;
+
+    #[test]
+    fn test_capped_output_under_limit_is_unchanged() {
+        let mut out = CappedOutput::new(64);
+        out.push("hello ");
+        out.push("world");
+        assert_eq!(out.total_bytes(), 11);
+        assert_eq!(out.finish(), "hello world");
+    }
+
+    #[test]
+    fn test_capped_output_keeps_head_and_tail() {
+        let mut out = CappedOutput::new(8);
+        for line in ["abcd", "efgh", "ijkl", "mnop"] {
+            out.push(line);
+        }
+        assert_eq!(out.total_bytes(), 16);
+        assert_eq!(out.finish(), "abcd\n[… 8 bytes elided …]\nmnop");
+    }
+
+    #[test]
+    fn test_capped_output_handles_large_streams_and_utf8() {
+        let mut out = CappedOutput::new(4096);
+        for _ in 0..10_000 {
+            out.push("yés\n");
+        }
+        let total = out.total_bytes();
+        let text = out.finish();
+        assert_eq!(total, 50_000);
+        assert!(text.starts_with("yés\nyés\n"));
+        assert!(text.ends_with("yés\n"));
+        assert!(text.len() < 4096 + 64);
+        assert!(text.contains("bytes elided"));
+    }
+
+    // ─── Shell Startup Diagnostic Tests ──────────────────────
+
+    use crate::shell::startup_diagnostic;
+
+    #[test]
+    fn test_startup_diagnostic_silent_worker() {
+        let message = startup_diagnostic(false, true, 20_000);
+        assert!(message.contains("did not respond within 20 s"));
+        assert!(message.contains("Content-Security-Policy"));
+        assert!(!message.contains("Cross-Origin-Opener-Policy"));
+        assert!(message.ends_with("disabled until the page is reloaded."));
+    }
+
+    #[test]
+    fn test_startup_diagnostic_running_worker_without_isolation() {
+        let message = startup_diagnostic(true, false, 20_000);
+        assert!(message.contains("Wasmer did not become ready"));
+        assert!(message.contains("Cross-Origin-Opener-Policy: same-origin"));
+        assert!(message.contains("Cross-Origin-Embedder-Policy: require-corp"));
+    }
+
+    // ─── LLM Worker Tests ────────────────────────────────────
+
+    use crate::llm::worker::{Pending, dispatch};
+    use agent_core::ports::LlmStreamEvent;
+    use agent_types::event::LlmWorkerEvent;
+
+    #[test]
+    fn test_llm_worker_event_from_js_shape() {
+        let event: LlmWorkerEvent = serde_json::from_str(
+            r#"{"type":"ToolCallDelta","id":3,"index":0,"call_id":null,"name":"bash","arguments":"{\"co"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            event,
+            LlmWorkerEvent::ToolCallDelta { id: 3, call_id: None, name: Some(ref n), .. } if n == "bash"
+        ));
+        let event: LlmWorkerEvent =
+            serde_json::from_str(r#"{"type":"Response","id":1,"status":429,"retry_after":null,"body":""}"#).unwrap();
+        assert!(matches!(event, LlmWorkerEvent::Response { status: 429, retry_after: None, .. }));
+    }
+
+    #[test]
+    fn test_llm_worker_dispatch_stream_until_done() {
+        use futures::StreamExt;
+        let mut pending = std::collections::HashMap::new();
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        pending.insert(7, Pending::Stream(sender, false));
+
+        dispatch(&mut pending, LlmWorkerEvent::Delta { id: 7, text: "Hel".to_string() });
+        dispatch(&mut pending, LlmWorkerEvent::Delta { id: 7, text: "lo".to_string() });
+        dispatch(&mut pending, LlmWorkerEvent::Done { id: 7 });
+        assert!(pending.is_empty());
+
+        let events: Vec<LlmStreamEvent> = futures::executor::block_on(receiver.collect());
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[1], LlmStreamEvent::Delta(t) if t == "lo"));
+        assert!(matches!(events[2], LlmStreamEvent::Done));
+    }
+
+    #[test]
+    fn test_llm_worker_dispatch_response_and_errors() {
+        let mut pending = std::collections::HashMap::new();
+        let (sender, mut receiver) = futures::channel::oneshot::channel();
+        pending.insert(1, Pending::Response(sender, false));
+        dispatch(&mut pending, LlmWorkerEvent::Response {
+            id: 1,
+            status: 503,
+            retry_after: Some(2000),
+            body: "busy".to_string(),
+        });
+        let reply = receiver.try_recv().unwrap().unwrap().unwrap();
+        assert!(!reply.ok());
+        assert_eq!(reply.retry_after, Some(2000));
+
+        // A failed stream reports the error, then ends
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        pending.insert(2, Pending::Stream(sender, false));
+        dispatch(&mut pending, LlmWorkerEvent::Error { id: 2, message: "Failed to fetch".to_string() });
+        let events: Vec<LlmStreamEvent> = futures::executor::block_on(futures::StreamExt::collect(receiver));
+        assert!(matches!(&events[0], LlmStreamEvent::Error(m) if m.contains("Failed to fetch")));
+        assert!(matches!(&events[0], LlmStreamEvent::Error(m) if m.contains("CORS proxy")));
+        assert!(matches!(events[1], LlmStreamEvent::Done));
+        assert!(pending.is_empty());
+    }
+
+    // ─── Stream Resume Tests ─────────────────────────────────
+
+    use crate::llm::openai_compat::collect_stream;
+    use crate::llm::resume::{CONTINUE_PROMPT, MAX_RESUMES, StreamResume, strip_overlap};
+
+    fn delta(text: &str) -> LlmStreamEvent {
+        LlmStreamEvent::Delta(text.to_string())
+    }
+
+    fn texts(events: &[LlmStreamEvent]) -> String {
+        events
+            .iter()
+            .map(|e| match e {
+                LlmStreamEvent::Delta(t) => t.as_str(),
+                _ => "",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_strip_overlap_drops_repeated_start() {
+        assert_eq!(strip_overlap("The quick brown fox", "brown fox jumps"), " jumps");
+        // Too short to tell from chance
+        assert_eq!(strip_overlap("one two", "two three"), "two three");
+        assert_eq!(strip_overlap("abc", "xyz"), "xyz");
+        assert_eq!(strip_overlap("", "text"), "text");
+    }
+
+    #[test]
+    fn test_resume_continues_partial_text() {
+        let req = ChatRequest { messages: vec![Message::user("Write a story")], ..Default::default() };
+        let mut resume = StreamResume::default();
+        assert!(!resume.can_resume());
+        let mut out = resume.accept(delta("Once upon a time, in a land far away"));
+        assert!(resume.can_resume());
+
+        let next = resume.resume(&req);
+        assert_eq!(next.messages.len(), 3);
+        assert_eq!(next.messages[1].content.as_text(), "Once upon a time, in a land far away");
+        assert_eq!(next.messages[2].content.as_text(), CONTINUE_PROMPT);
+
+        // The continuation restarts a few words back; it is held until
+        // the overlap can be cut
+        assert!(resume.accept(delta("in a land far away, ")).is_empty());
+        out.extend(resume.accept(delta("there lived a dragon who guarded a mountain of gold coins.")));
+        out.extend(resume.flush());
+        assert_eq!(
+            texts(&out),
+            "Once upon a time, in a land far away, there lived a dragon who guarded a mountain of gold coins."
+        );
+
+        for _ in 1..MAX_RESUMES {
+            resume.resume(&req);
+        }
+        assert!(!resume.can_resume());
+    }
+
+    #[test]
+    fn test_resume_skips_tool_calls_and_empty_replies() {
+        let mut resume = StreamResume::default();
+        resume.accept(delta("Let me look."));
+        resume.accept(LlmStreamEvent::ToolCallDelta {
+            index: 0,
+            id: Some("call_0".to_string()),
+            name: Some("bash".to_string()),
+            arguments_delta: "{\"comm".to_string(),
+        });
+        assert!(!resume.can_resume());
+
+        let mut resume = StreamResume::default();
+        resume.accept(delta("  "));
+        assert!(!resume.can_resume());
+    }
+
+    #[test]
+    fn test_collect_stream_builds_whole_reply() {
+        let usage = |prompt, completion| agent_types::message::TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+        };
+        let events = vec![
+            LlmStreamEvent::Reasoning("Check the file first.".to_string()),
+            delta("Let me "),
+            delta("look."),
+            LlmStreamEvent::ToolCallDelta {
+                index: 0,
+                id: Some("call_0".to_string()),
+                name: Some("read_file".to_string()),
+                arguments_delta: "{\"path\":".to_string(),
+            },
+            LlmStreamEvent::ToolCallDelta { index: 0, id: None, name: None, arguments_delta: "\"/a\"}".to_string() },
+            LlmStreamEvent::Usage(usage(10, 5)),
+            LlmStreamEvent::Usage(usage(20, 3)),
+            LlmStreamEvent::Done,
+        ];
+        let data = collect_stream(&events, "deepseek-reasoner").unwrap();
+        let response = WireFormat::OpenAi.parse_response(data).unwrap();
+        assert_eq!(response.message.content.as_text(), "Let me look.");
+        assert_eq!(response.message.reasoning.as_deref(), Some("Check the file first."));
+        assert_eq!(response.message.tool_calls[0].id, "call_0");
+        assert_eq!(response.message.tool_calls[0].function.arguments, "{\"path\":\"/a\"}");
+        assert_eq!(response.usage, Some(usage(30, 8)));
+        assert_eq!(response.message.meta.unwrap().model.as_deref(), Some("deepseek-reasoner"));
+    }
+
+    #[test]
+    fn test_collect_stream_errors() {
+        // Nothing arrived: send the request again whole
+        let events = vec![LlmStreamEvent::Error("HTTP 429".to_string()), LlmStreamEvent::Done];
+        assert_eq!(collect_stream(&events, "m"), Err(None));
+
+        // Broke off after text, past the last resume
+        let events = vec![delta("Half a"), LlmStreamEvent::Error("reset".to_string()), LlmStreamEvent::Done];
+        assert_eq!(collect_stream(&events, "m"), Err(Some("reset".to_string())));
+    }
+
+    // ─── Storage Cleanup Tests ───────────────────────────────
+
+    #[test]
+    fn test_workspace_vacuum_removes_only_deleted_namespaces() {
+        let root = Rc::new(MemoryStorage::new());
+        let manager = WorkspaceManager::new(root.clone());
+        block_on(async {
+            let live = manager.create("Live", true).await.unwrap();
+            manager.storage_for(&live).set("vfs:/workspace/keep", b"x").await.unwrap();
+            root.set("ws:gone:vfs:/workspace/a", b"1").await.unwrap();
+            root.set("ws:gone:session:s", b"{}").await.unwrap();
+            root.set("vfs:/workspace/root", b"y").await.unwrap();
+
+            let mut orphans = manager.orphaned_keys().await.unwrap();
+            orphans.sort();
+            assert_eq!(orphans, vec!["ws:gone:session:s", "ws:gone:vfs:/workspace/a"]);
+            assert_eq!(manager.vacuum().await.unwrap(), 2);
+            assert!(manager.orphaned_keys().await.unwrap().is_empty());
+            assert!(manager.storage_for(&live).exists("vfs:/workspace/keep").await.unwrap());
+            assert!(root.exists("vfs:/workspace/root").await.unwrap());
+        });
+    }
+
+    #[test]
+    fn test_session_manager_deletes_old_sessions_but_the_open_one() {
+        let storage = Rc::new(MemoryStorage::new());
+        let manager = SessionManager::new(storage.clone());
+        let vfs = StorageVfs::new(storage);
+        block_on(async {
+            for (id, updated_at) in [("old", "2026-01-01T00:00:00Z"), ("open", "2026-01-02T00:00:00Z"), ("new", "2026-06-01T00:00:00Z")] {
+                let mut session = Session::new(id.to_string());
+                session.updated_at = updated_at.to_string();
+                manager.save(&session).await.unwrap();
+            }
+            vfs.write_file("/workspace/a.txt", b"abc").await.unwrap();
+            assert!(manager.stored_bytes().await.unwrap() > 0);
+            assert_eq!(vfs.stored_bytes().await.unwrap(), 3);
+
+            assert_eq!(manager.delete_older_than("2026-03-01T00:00:00Z", "open").await.unwrap(), 1);
+            let ids: Vec<String> = manager.list().await.unwrap().into_iter().map(|s| s.id).collect();
+            assert_eq!(ids, vec!["new", "open"]);
+        });
+    }
 }
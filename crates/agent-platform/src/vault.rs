@@ -0,0 +1,169 @@
+//! Passphrase-protected API key vault, encrypted with WebCrypto.
+//!
+//! The passphrase is stretched with PBKDF2-SHA256 into an AES-256-GCM key
+//! that encrypts the [`VaultKeys`]; only the [`SealedVault`] is stored
+//! (under `vault`). The derived key is non-extractable and lives only in
+//! memory, so the vault must be unlocked again after the page reloads.
+
+use std::rc::Rc;
+
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AesDerivedKeyParams, AesGcmParams, CryptoKey, Pbkdf2Params, SubtleCrypto};
+
+use agent_core::ports::StoragePort;
+use agent_types::{
+    AgentError, ErrorKind, Result,
+    vault::{SealedVault, VaultKeys, VAULT_KDF_ITERATIONS},
+};
+
+const VAULT_KEY: &str = "vault";
+const VAULT_VERSION: u32 = 1;
+
+/// The derived encryption key, held while the vault is unlocked
+pub struct VaultKey {
+    key: CryptoKey,
+    salt: Vec<u8>,
+    iterations: u32,
+}
+
+pub struct KeyVault {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl KeyVault {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    pub async fn exists(&self) -> Result<bool> {
+        self.storage.exists(VAULT_KEY).await
+    }
+
+    /// Create a vault protected by `passphrase`, replacing any existing one.
+    pub async fn create(&self, passphrase: &str, keys: &VaultKeys) -> Result<VaultKey> {
+        let salt = random_bytes(16)?;
+        let key = VaultKey {
+            key: derive_key(passphrase, &salt, VAULT_KDF_ITERATIONS).await?,
+            salt,
+            iterations: VAULT_KDF_ITERATIONS,
+        };
+        self.save(&key, keys).await?;
+        Ok(key)
+    }
+
+    /// Decrypt the stored keys. A wrong passphrase fails authentication.
+    pub async fn unlock(&self, passphrase: &str) -> Result<(VaultKey, VaultKeys)> {
+        let bytes = self
+            .storage
+            .get(VAULT_KEY)
+            .await?
+            .ok_or_else(|| AgentError::Config("No key vault has been set up".to_string()))?;
+        let sealed: SealedVault = serde_json::from_slice(&bytes)?;
+
+        let key = derive_key(passphrase, &sealed.salt, sealed.iterations).await?;
+        let algorithm = AesGcmParams::new_with_u8_array("AES-GCM", &Uint8Array::from(sealed.iv.as_slice()));
+        let plaintext = JsFuture::from(
+            subtle()?
+                .decrypt_with_object_and_u8_array(&algorithm, &key, &sealed.ciphertext)
+                .map_err(js_error)?,
+        )
+        .await
+        .map_err(|_| AgentError::Config("Wrong passphrase".to_string()))?;
+        let keys: VaultKeys = serde_json::from_slice(&Uint8Array::new(&plaintext).to_vec())?;
+
+        Ok((
+            VaultKey {
+                key,
+                salt: sealed.salt,
+                iterations: sealed.iterations,
+            },
+            keys,
+        ))
+    }
+
+    /// Encrypt and store `keys` under an unlocked key.
+    pub async fn save(&self, key: &VaultKey, keys: &VaultKeys) -> Result<()> {
+        // GCM needs a fresh IV for every encryption under the same key
+        let iv = random_bytes(12)?;
+        let algorithm = AesGcmParams::new_with_u8_array("AES-GCM", &Uint8Array::from(iv.as_slice()));
+        let ciphertext = JsFuture::from(
+            subtle()?
+                .encrypt_with_object_and_u8_array(&algorithm, &key.key, &serde_json::to_vec(keys)?)
+                .map_err(js_error)?,
+        )
+        .await
+        .map_err(js_error)?;
+
+        let sealed = SealedVault {
+            version: VAULT_VERSION,
+            iterations: key.iterations,
+            salt: key.salt.clone(),
+            iv,
+            ciphertext: Uint8Array::new(&ciphertext).to_vec(),
+        };
+        self.storage.set(VAULT_KEY, &serde_json::to_vec(&sealed)?).await
+    }
+
+    /// Delete the vault and the keys in it.
+    pub async fn remove(&self) -> Result<()> {
+        self.storage.delete(VAULT_KEY).await
+    }
+}
+
+fn subtle() -> Result<SubtleCrypto> {
+    let window = web_sys::window()
+        .ok_or_else(|| AgentError::api(ErrorKind::Unavailable, "No window for WebCrypto"))?;
+    Ok(window.crypto().map_err(js_error)?.subtle())
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>> {
+    let window = web_sys::window()
+        .ok_or_else(|| AgentError::api(ErrorKind::Unavailable, "No window for WebCrypto"))?;
+    let mut bytes = vec![0u8; len];
+    window
+        .crypto()
+        .map_err(js_error)?
+        .get_random_values_with_u8_array(&mut bytes)
+        .map_err(js_error)?;
+    Ok(bytes)
+}
+
+/// PBKDF2-SHA256 → non-extractable AES-256-GCM key
+async fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Result<CryptoKey> {
+    let subtle = subtle()?;
+    let material = JsFuture::from(
+        subtle
+            .import_key_with_str(
+                "raw",
+                &Uint8Array::from(passphrase.as_bytes()),
+                "PBKDF2",
+                false,
+                &Array::of1(&JsValue::from_str("deriveKey")),
+            )
+            .map_err(js_error)?,
+    )
+    .await
+    .map_err(js_error)?;
+
+    let params = Pbkdf2Params::new_with_str("PBKDF2", "SHA-256", iterations, &Uint8Array::from(salt));
+    let key = JsFuture::from(
+        subtle
+            .derive_key_with_object_and_object(
+                &params,
+                &material.unchecked_into(),
+                &AesDerivedKeyParams::new("AES-GCM", 256),
+                false,
+                &Array::of2(&JsValue::from_str("encrypt"), &JsValue::from_str("decrypt")),
+            )
+            .map_err(js_error)?,
+    )
+    .await
+    .map_err(js_error)?;
+    Ok(key.unchecked_into())
+}
+
+fn js_error(e: JsValue) -> AgentError {
+    AgentError::JsInterop(format!("{:?}", e))
+}
@@ -35,6 +35,11 @@ impl StorageVfs {
         format!("{}{}/{}", VFS_PREFIX, normalized, DIR_MARKER)
     }
 
+    /// Bytes the files take
+    pub async fn stored_bytes(&self) -> Result<u64> {
+        self.storage.stored_bytes(VFS_PREFIX).await
+    }
+
     #[allow(dead_code)]
     fn path_from_key(&self, key: &str) -> String {
         key.strip_prefix(VFS_PREFIX).unwrap_or(key).to_string()
@@ -55,12 +60,26 @@ impl VfsPort for StorageVfs {
     }
 
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
-        // Ensure parent directory exists
-        if let Some(parent) = parent_path(path) {
-            self.mkdir(&parent).await?;
+        // Write the parent directory marker and the file together
+        let key = self.key_for_path(path);
+        match parent_path(path) {
+            Some(parent) => {
+                let dir_key = self.dir_key(&parent);
+                self.storage.set_many(&[(&dir_key, b""), (&key, data)]).await
+            }
+            None => self.storage.set(&key, data).await,
         }
+    }
+
+    async fn append_file(&self, path: &str, data: &[u8]) -> Result<()> {
         let key = self.key_for_path(path);
-        self.storage.set(&key, data).await
+        let mut contents = self.storage.get(&key).await?.unwrap_or_default();
+        if contents.is_empty() {
+            // Possibly a new file, which needs its parent directory
+            return self.write_file(path, data).await;
+        }
+        contents.extend_from_slice(data);
+        self.storage.set(&key, &contents).await
     }
 
     async fn delete_file(&self, path: &str) -> Result<()> {
@@ -71,11 +90,12 @@ impl VfsPort for StorageVfs {
     async fn list_dir(&self, path: &str) -> Result<Vec<DirEntry>> {
         let normalized = normalize_path(path);
         let prefix = format!("{}{}/", VFS_PREFIX, normalized);
-        let keys = self.storage.list_keys(&prefix).await?;
+        // Sizes come from metadata, so no file is read to list a folder
+        let keys = self.storage.list_meta(&prefix).await?;
 
         let mut entries = std::collections::HashMap::new();
 
-        for key in &keys {
+        for (key, meta) in &keys {
             let rel = key.strip_prefix(&prefix).unwrap_or(key);
             // Get the first path component after the prefix
             let name = if let Some(slash_pos) = rel.find('/') {
@@ -89,20 +109,13 @@ impl VfsPort for StorageVfs {
             }
 
             let is_dir = rel.contains('/');
-            let size = if !is_dir {
-                self.storage
-                    .get(key)
-                    .await?
-                    .map(|v| v.len() as u64)
-                    .unwrap_or(0)
-            } else {
-                0
-            };
+            let (size, content_type) = if is_dir { (0, None) } else { (meta.size, meta.content_type.clone()) };
 
             entries.entry(name.to_string()).or_insert(DirEntry {
                 name: name.to_string(),
                 is_dir,
                 size,
+                content_type,
             });
         }
 
@@ -115,11 +128,12 @@ impl VfsPort for StorageVfs {
         let key = self.key_for_path(path);
 
         // Check if it's a file
-        if let Some(data) = self.storage.get(&key).await? {
+        if let Some(meta) = self.storage.meta(&key).await? {
             return Ok(FileStat {
-                size: data.len() as u64,
+                size: meta.size,
                 is_dir: false,
-                modified: None,
+                modified: meta.modified,
+                content_type: meta.content_type,
             });
         }
 
@@ -130,6 +144,7 @@ impl VfsPort for StorageVfs {
                 size: 0,
                 is_dir: true,
                 modified: None,
+                content_type: None,
             });
         }
 
@@ -0,0 +1,180 @@
+//! Workspace management — named projects, each in its own storage namespace.
+//!
+//! Workspace records live under `workspace:<id>` in the root storage. A
+//! workspace's files (and, if it keeps them separate, its sessions) live
+//! under the `ws:<id>:` key prefix. The default workspace uses the root
+//! storage directly, so data from before workspaces existed stays put.
+
+use std::rc::Rc;
+use agent_core::ports::StoragePort;
+use agent_types::{
+    AgentError, Result,
+    workspace::{DEFAULT_WORKSPACE_ID, Workspace},
+};
+
+use crate::storage::NamespacedStorage;
+
+const WORKSPACE_PREFIX: &str = "workspace:";
+/// Start of every workspace namespace, `ws:<id>:`
+const NAMESPACE_PREFIX: &str = "ws:";
+/// Id of the last active workspace
+const CURRENT_KEY: &str = "current-workspace";
+
+pub struct WorkspaceManager {
+    storage: Rc<dyn StoragePort>,
+}
+
+impl WorkspaceManager {
+    pub fn new(storage: Rc<dyn StoragePort>) -> Self {
+        Self { storage }
+    }
+
+    fn key_for(id: &str) -> String {
+        format!("{}{}", WORKSPACE_PREFIX, id)
+    }
+
+    fn namespace(id: &str) -> String {
+        format!("{}{}:", NAMESPACE_PREFIX, id)
+    }
+
+    /// All workspaces: the default one first, then oldest first.
+    /// Records that fail to deserialize are skipped with a warning.
+    pub async fn list(&self) -> Result<Vec<Workspace>> {
+        let mut workspaces = Vec::new();
+        let keys = self.storage.list_keys(WORKSPACE_PREFIX).await?;
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let values = self.storage.get_many(&keys).await?;
+        for (key, bytes) in keys.into_iter().zip(values) {
+            let Some(bytes) = bytes else {
+                continue;
+            };
+            match serde_json::from_slice::<Workspace>(&bytes) {
+                Ok(ws) if !ws.is_default() => workspaces.push(ws),
+                Ok(_) => {}
+                Err(e) => log::warn!("Skipping unreadable workspace {}: {}", key, e),
+            }
+        }
+        workspaces.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        workspaces.insert(0, self.load(DEFAULT_WORKSPACE_ID).await?.unwrap_or_else(Workspace::default_workspace));
+        Ok(workspaces)
+    }
+
+    pub async fn load(&self, id: &str) -> Result<Option<Workspace>> {
+        match self.storage.get(&Self::key_for(id)).await? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None if id == DEFAULT_WORKSPACE_ID => Ok(Some(Workspace::default_workspace())),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn save(&self, workspace: &Workspace) -> Result<()> {
+        let json = serde_json::to_vec(workspace)?;
+        self.storage.set(&Self::key_for(&workspace.id), &json).await
+    }
+
+    pub async fn create(&self, name: &str, separate_sessions: bool) -> Result<Workspace> {
+        let workspace = Workspace::new(name.trim(), separate_sessions);
+        self.save(&workspace).await?;
+        Ok(workspace)
+    }
+
+    pub async fn rename(&self, id: &str, name: &str) -> Result<Workspace> {
+        let mut workspace = self
+            .load(id)
+            .await?
+            .ok_or_else(|| AgentError::Storage(format!("No workspace {}", id)))?;
+        workspace.name = name.trim().to_string();
+        self.save(&workspace).await?;
+        Ok(workspace)
+    }
+
+    /// Delete a workspace and everything stored in its namespace.
+    /// The default workspace cannot be deleted.
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        if id == DEFAULT_WORKSPACE_ID {
+            return Err(AgentError::Config("The default workspace cannot be deleted".to_string()));
+        }
+        for key in self.storage.list_keys(&Self::namespace(id)).await? {
+            self.storage.delete(&key).await?;
+        }
+        self.storage.delete(&Self::key_for(id)).await?;
+        if self.current_id().await? == id {
+            self.storage.delete(CURRENT_KEY).await?;
+        }
+        Ok(())
+    }
+
+    /// Keys left in the namespace of a workspace that no longer exists,
+    /// e.g. by a delete that was interrupted
+    pub async fn orphaned_keys(&self) -> Result<Vec<String>> {
+        let mut orphans = Vec::new();
+        let mut live = std::collections::HashMap::new();
+        for key in self.storage.list_keys(NAMESPACE_PREFIX).await? {
+            let Some((id, _)) = key[NAMESPACE_PREFIX.len()..].split_once(':') else {
+                continue;
+            };
+            let exists = match live.get(id) {
+                Some(&exists) => exists,
+                None => {
+                    let exists = self.storage.exists(&Self::key_for(id)).await?;
+                    live.insert(id.to_string(), exists);
+                    exists
+                }
+            };
+            if !exists {
+                orphans.push(key);
+            }
+        }
+        Ok(orphans)
+    }
+
+    /// Delete the [`orphaned_keys`](Self::orphaned_keys). Returns how many
+    /// there were.
+    pub async fn vacuum(&self) -> Result<usize> {
+        let orphans = self.orphaned_keys().await?;
+        for key in &orphans {
+            self.storage.delete(key).await?;
+        }
+        Ok(orphans.len())
+    }
+
+    /// Storage holding the workspace's files and per-workspace data
+    pub fn storage_for(&self, workspace: &Workspace) -> Rc<dyn StoragePort> {
+        if workspace.is_default() {
+            self.storage.clone()
+        } else {
+            Rc::new(NamespacedStorage::new(self.storage.clone(), Self::namespace(&workspace.id)))
+        }
+    }
+
+    /// Storage holding the workspace's sessions
+    pub fn session_storage_for(&self, workspace: &Workspace) -> Rc<dyn StoragePort> {
+        if workspace.separate_sessions {
+            self.storage_for(workspace)
+        } else {
+            self.storage.clone()
+        }
+    }
+
+    async fn current_id(&self) -> Result<String> {
+        Ok(self
+            .storage
+            .get(CURRENT_KEY)
+            .await?
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .unwrap_or_else(|| DEFAULT_WORKSPACE_ID.to_string()))
+    }
+
+    /// The last active workspace, falling back to the default one
+    pub async fn current(&self) -> Result<Workspace> {
+        let id = self.current_id().await?;
+        Ok(self
+            .load(&id)
+            .await?
+            .unwrap_or_else(Workspace::default_workspace))
+    }
+
+    pub async fn set_current(&self, id: &str) -> Result<()> {
+        self.storage.set(CURRENT_KEY, id.as_bytes()).await
+    }
+}
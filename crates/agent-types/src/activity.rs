@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pricing::UsageTotals;
+
+/// "850 ms", "3.2 s", "2m 05s"
+pub fn format_duration(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{} ms", ms)
+    } else if ms < 60_000 {
+        format!("{:.1} s", ms as f64 / 1000.0)
+    } else {
+        format!("{}m {:02}s", ms / 60_000, (ms % 60_000) / 1000)
+    }
+}
+
+/// One tool call made during a turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolRun {
+    pub name: String,
+    pub success: bool,
+}
+
+/// What happened in one agent turn, as kept in the activity log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnRecord {
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub tools: Vec<ToolRun>,
+    /// The turn ended with an error
+    pub error: Option<String>,
+    #[serde(default)]
+    pub usage: UsageTotals,
+}
+
+impl TurnRecord {
+    /// A turn starting now
+    pub fn start() -> Self {
+        Self {
+            started_at: chrono::Utc::now().to_rfc3339(),
+            duration_ms: 0,
+            tools: Vec::new(),
+            error: None,
+            usage: UsageTotals::default(),
+        }
+    }
+
+    /// Set the duration from the start time to now
+    pub fn finish(&mut self) {
+        if let Ok(started) = chrono::DateTime::parse_from_rfc3339(&self.started_at) {
+            let elapsed = chrono::Utc::now().signed_duration_since(started);
+            self.duration_ms = elapsed.num_milliseconds().max(0) as u64;
+        }
+    }
+
+    /// UTC calendar day, "YYYY-MM-DD"
+    pub fn day(&self) -> &str {
+        self.started_at.get(..10).unwrap_or("")
+    }
+}
+
+/// Calls and failures of one tool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolStats {
+    pub calls: u64,
+    pub failures: u64,
+}
+
+impl ToolStats {
+    pub fn success_ratio(&self) -> f32 {
+        if self.calls == 0 {
+            return 0.0;
+        }
+        (self.calls - self.failures) as f32 / self.calls as f32
+    }
+}
+
+/// Activity on one day
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DayStats {
+    pub day: String,
+    pub turns: u64,
+    pub tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Summary of the activity log for the stats panel
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActivityStats {
+    pub turns: u64,
+    pub failed_turns: u64,
+    /// By tool name
+    pub tools: BTreeMap<String, ToolStats>,
+    pub usage: UsageTotals,
+    pub average_turn_ms: u64,
+    /// Oldest first, days without activity omitted
+    pub days: Vec<DayStats>,
+}
+
+impl ActivityStats {
+    pub fn from_records(records: &[TurnRecord]) -> Self {
+        let mut stats = Self::default();
+        let mut days: BTreeMap<&str, DayStats> = BTreeMap::new();
+        let mut total_ms = 0;
+        for record in records {
+            stats.turns += 1;
+            if record.error.is_some() {
+                stats.failed_turns += 1;
+            }
+            total_ms += record.duration_ms;
+            for run in &record.tools {
+                let tool = stats.tools.entry(run.name.clone()).or_default();
+                tool.calls += 1;
+                if !run.success {
+                    tool.failures += 1;
+                }
+            }
+            stats.usage.add(&record.usage);
+
+            let day = days.entry(record.day()).or_insert_with(|| DayStats {
+                day: record.day().to_string(),
+                ..Default::default()
+            });
+            day.turns += 1;
+            day.tokens += record.usage.prompt_tokens + record.usage.completion_tokens;
+            day.cost_usd += record.usage.cost_usd;
+        }
+        stats.average_turn_ms = total_ms.checked_div(stats.turns).unwrap_or(0);
+        stats.days = days.into_values().collect();
+        stats
+    }
+
+    pub fn tool_calls(&self) -> u64 {
+        self.tools.values().map(|t| t.calls).sum()
+    }
+
+    pub fn tool_failures(&self) -> u64 {
+        self.tools.values().map(|t| t.failures).sum()
+    }
+}
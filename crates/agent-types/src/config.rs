@@ -1,31 +1,923 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::pricing::ModelPrice;
+
+/// Schema version of [`AgentConfig`]; bump it with a new entry in
+/// [`crate::migration::MIGRATIONS`] when saved configs need rewriting.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Widest chat messages can be set to, in points
+pub const MAX_CHAT_WIDTH: f32 = 4000.0;
+/// Range of the UI scale setting
+pub const UI_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.5..=3.0;
+
 /// Top-level agent configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentConfig {
+    /// Schema version the config was saved with (0 before versioning)
+    #[serde(default)]
+    pub version: u32,
     pub llm: LlmConfig,
     pub storage: StorageConfig,
     pub system_prompt: String,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub retrieval: RetrievalConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    #[serde(default)]
+    pub web: WebConfig,
+    #[serde(default)]
+    pub js: JsConfig,
+    #[serde(default)]
+    pub sql: SqlConfig,
+    #[serde(default)]
+    pub review: ReviewConfig,
+    #[serde(default)]
+    pub plan: PlanConfig,
+    #[serde(default)]
+    pub cache: ResponseCacheConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default)]
+    pub shell: ShellConfig,
+    #[serde(default)]
+    pub tool_output: ToolOutputConfig,
+    /// Per-model price overrides, by exact model name
+    #[serde(default)]
+    pub prices: BTreeMap<String, ModelPrice>,
+    /// Record LLM and shell responses of each session so it can be replayed
+    #[serde(default)]
+    pub record_sessions: bool,
+    /// Answer same-origin requests to `/v1/chat/completions` with the
+    /// configured provider, through a service worker
+    #[serde(default)]
+    pub local_endpoint: bool,
+    /// Terminal command aliases as (name, replacement), expanded before a
+    /// typed command is run
+    #[serde(default)]
+    pub aliases: Vec<(String, String)>,
+    /// Roles a session can take on
+    #[serde(default = "default_personas")]
+    pub personas: Vec<Persona>,
+    /// Tools the agent may use; None = all of them. Set from the session's
+    /// persona.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_tools: Option<Vec<String>>,
 }
 
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             llm: LlmConfig::default(),
             storage: StorageConfig::default(),
             system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            ui: UiConfig::default(),
+            retrieval: RetrievalConfig::default(),
+            memory: MemoryConfig::default(),
+            web: WebConfig::default(),
+            js: JsConfig::default(),
+            sql: SqlConfig::default(),
+            review: ReviewConfig::default(),
+            plan: PlanConfig::default(),
+            cache: ResponseCacheConfig::default(),
+            context: ContextConfig::default(),
+            shell: ShellConfig::default(),
+            tool_output: ToolOutputConfig::default(),
+            prices: BTreeMap::new(),
+            record_sessions: false,
+            local_endpoint: false,
+            aliases: Vec::new(),
+            personas: default_personas(),
+            allowed_tools: None,
+        }
+    }
+}
+
+impl AgentConfig {
+    /// The persona called `name`
+    pub fn persona(&self, name: &str) -> Option<&Persona> {
+        self.personas.iter().find(|p| p.name == name)
+    }
+
+    /// Whether the agent may use the tool called `name`
+    pub fn tool_allowed(&self, name: &str) -> bool {
+        self.allowed_tools.as_ref().is_none_or(|tools| tools.iter().any(|t| t == name))
+    }
+
+    /// Repair values the settings panel would never produce, e.g. from an
+    /// edited or damaged save. Returns a warning for each repair.
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let defaults = AgentConfig::default();
+
+        let llm = &mut self.llm;
+        if llm.model.trim().is_empty() && llm.provider != LlmProvider::Custom {
+            llm.model = llm.provider.default_model().to_string();
+            warnings.push(format!("No model was set; using {}", llm.model));
+        }
+        if llm.max_tokens == 0 {
+            llm.max_tokens = defaults.llm.max_tokens;
+            warnings.push(format!("Max tokens was 0; using {}", llm.max_tokens));
+        }
+        clamp_setting(&mut llm.temperature, 0.0, 2.0, "Temperature", &mut warnings);
+        if let Some(top_p) = &mut llm.top_p {
+            clamp_setting(top_p, 0.0, 1.0, "Top P", &mut warnings);
+        }
+        if let Some(penalty) = &mut llm.frequency_penalty {
+            clamp_setting(penalty, -2.0, 2.0, "Frequency penalty", &mut warnings);
+        }
+        if let Some(penalty) = &mut llm.presence_penalty {
+            clamp_setting(penalty, -2.0, 2.0, "Presence penalty", &mut warnings);
+        }
+        for (name, url) in [("API base URL", &mut llm.api_base), ("CORS proxy", &mut llm.proxy_url)] {
+            if let Some(value) = url.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+                if !value.starts_with("http://") && !value.starts_with("https://") {
+                    warnings.push(format!("{} \"{}\" is not an http(s) URL and was cleared", name, value));
+                    *url = None;
+                }
+            }
+        }
+        let headers = llm.headers.len();
+        llm.headers.retain(|(name, _)| !name.trim().is_empty());
+        if llm.headers.len() < headers {
+            warnings.push("Headers without a name were removed".to_string());
+        }
+
+        let aliases = self.aliases.len();
+        self.aliases.retain(|(name, _)| is_alias_name(name));
+        if self.aliases.len() < aliases {
+            warnings.push("Aliases with an empty or invalid name were removed".to_string());
+        }
+
+        clamp_setting(&mut self.retrieval.min_score, 0.0, 1.0, "Retrieval minimum score", &mut warnings);
+        clamp_setting(&mut self.ui.chat.max_width, 0.0, MAX_CHAT_WIDTH, "Chat message width", &mut warnings);
+        let (min_scale, max_scale) = UI_SCALE_RANGE.into_inner();
+        clamp_setting(&mut self.ui.scale, min_scale, max_scale, "UI scale", &mut warnings);
+        if self.shell.max_output_bytes == 0 {
+            self.shell.max_output_bytes = defaults.shell.max_output_bytes;
+            warnings.push("Shell output limit was 0; using the default".to_string());
+        }
+        if self.context.max_tokens == 0 {
+            self.context.max_tokens = defaults.context.max_tokens;
+            warnings.push(format!("Context budget was 0; using {} tokens", self.context.max_tokens));
+        }
+        if self.plan.max_steps == 0 {
+            self.plan.max_steps = defaults.plan.max_steps;
+            warnings.push(format!("Plan step limit was 0; using {}", self.plan.max_steps));
+        }
+        if !self.tool_output.outputs_dir.starts_with('/') {
+            warnings.push(format!(
+                "Tool output folder \"{}\" is not an absolute path; using {}",
+                self.tool_output.outputs_dir, defaults.tool_output.outputs_dir
+            ));
+            self.tool_output.outputs_dir = defaults.tool_output.outputs_dir;
+        }
+        if !self.web.cache_dir.starts_with('/') {
+            warnings.push(format!(
+                "Web page folder \"{}\" is not an absolute path; using {}",
+                self.web.cache_dir, defaults.web.cache_dir
+            ));
+            self.web.cache_dir = defaults.web.cache_dir;
+        }
+        if self.js.timeout_ms == 0 {
+            self.js.timeout_ms = defaults.js.timeout_ms;
+            warnings.push(format!("JavaScript time limit was 0; using {} ms", self.js.timeout_ms));
+        }
+        if self.sql.timeout_ms == 0 {
+            self.sql.timeout_ms = defaults.sql.timeout_ms;
+            warnings.push(format!("SQL time limit was 0; using {} ms", self.sql.timeout_ms));
+        }
+        if self.system_prompt.trim().is_empty() {
+            self.system_prompt = defaults.system_prompt;
+            warnings.push("The system prompt was empty; using the default".to_string());
+        }
+        warnings
+    }
+}
+
+/// Alias names are plain words: letters, digits, `-`, `_` and `.`
+pub fn is_alias_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || "-_.".contains(c))
+}
+
+fn clamp_setting(value: &mut f32, min: f32, max: f32, name: &str, warnings: &mut Vec<String>) {
+    let clamped = if value.is_nan() { min } else { value.clamp(min, max) };
+    if clamped != *value {
+        warnings.push(format!("{} {} is out of range; using {}", name, value, clamped));
+        *value = clamped;
+    }
+}
+
+/// Browser/UI preferences that don't affect the agent itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// Show a browser notification when a turn ends while the tab is hidden
+    pub notify_on_turn_end: bool,
+    /// How chat messages are laid out
+    pub chat: ChatDisplayConfig,
+    /// Zoom of the whole UI, text included (1.0 = normal)
+    pub scale: f32,
+    /// Read focused and clicked widgets aloud with the browser's speech
+    /// synthesis
+    pub screen_reader: bool,
+    /// Fonts fetched for CJK text
+    pub fonts: FontConfig,
+    /// Color scheme
+    pub theme: ThemePreset,
+    /// Show frame times, event and storage counts over the UI
+    pub perf_overlay: bool,
+}
+
+/// Color schemes of the UI
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemePreset {
+    #[default]
+    Dark,
+    /// Black backgrounds, brighter text, thicker strokes and focus outlines
+    HighContrast,
+}
+
+impl ThemePreset {
+    pub fn all() -> &'static [ThemePreset] {
+        &[ThemePreset::Dark, ThemePreset::HighContrast]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ThemePreset::Dark => "Dark",
+            ThemePreset::HighContrast => "High contrast",
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            notify_on_turn_end: false,
+            chat: ChatDisplayConfig::default(),
+            scale: 1.0,
+            screen_reader: false,
+            fonts: FontConfig::default(),
+            theme: ThemePreset::Dark,
+            perf_overlay: false,
+        }
+    }
+}
+
+/// When the CJK fonts are fetched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontLoading {
+    /// Once text that needs them appears
+    #[default]
+    Auto,
+    /// At startup
+    Always,
+    Never,
+}
+
+impl FontLoading {
+    pub fn all() -> &'static [FontLoading] {
+        &[FontLoading::Auto, FontLoading::Always, FontLoading::Never]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FontLoading::Auto => "When needed",
+            FontLoading::Always => "At startup",
+            FontLoading::Never => "Never",
+        }
+    }
+}
+
+/// Extra fonts for scripts the built-in fonts don't cover
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontConfig {
+    pub loading: FontLoading,
+    /// Font files to fetch, relative to the page or absolute; earlier ones
+    /// take priority
+    pub urls: Vec<String>,
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            loading: FontLoading::Auto,
+            urls: vec!["NotoSansTC-Regular.otf".to_string()],
+        }
+    }
+}
+
+/// Spacing around and between chat messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatDensity {
+    #[default]
+    Comfortable,
+    Compact,
+}
+
+impl ChatDensity {
+    pub fn all() -> &'static [ChatDensity] {
+        &[ChatDensity::Comfortable, ChatDensity::Compact]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChatDensity::Comfortable => "Comfortable",
+            ChatDensity::Compact => "Compact",
+        }
+    }
+}
+
+/// Chat layout preferences
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatDisplayConfig {
+    pub density: ChatDensity,
+    /// Widest a message is drawn, in points; 0 uses the panel's width
+    pub max_width: f32,
+    /// Messages as bubbles, the user's on the right, instead of full-width
+    /// rows
+    pub bubbles: bool,
+    /// Show the assistant's answers in a monospace font
+    pub monospace_assistant: bool,
+    /// Offer to save a paste into the chat input longer than this many
+    /// characters as a workspace file (0 = never)
+    pub paste_file_chars: usize,
+}
+
+impl Default for ChatDisplayConfig {
+    fn default() -> Self {
+        Self {
+            density: ChatDensity::Comfortable,
+            max_width: 0.0,
+            bubbles: false,
+            monospace_assistant: false,
+            paste_file_chars: 4000,
+        }
+    }
+}
+
+/// Workspace retrieval (RAG) over locally embedded files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrievalConfig {
+    /// Load the embeddings model and offer the `search_workspace` tool
+    pub enabled: bool,
+    /// Add the best-matching excerpts to each turn automatically
+    pub auto_inject: bool,
+    /// Number of excerpts to inject
+    pub top_k: usize,
+    /// Minimum cosine similarity for an excerpt to be injected
+    pub min_score: f32,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            auto_inject: true,
+            top_k: 3,
+            min_score: 0.35,
+        }
+    }
+}
+
+/// Reading web pages with the `read_url` tool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebConfig {
+    /// Offer the `read_url` tool
+    pub enabled: bool,
+    /// Characters of a page's text returned to the model (0 = no limit)
+    pub max_chars: usize,
+    /// Keep the text of read pages in `cache_dir` and reuse it
+    pub cache: bool,
+    pub cache_dir: String,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_chars: 20_000,
+            cache: false,
+            cache_dir: "/workspace/.agent/web".to_string(),
+        }
+    }
+}
+
+/// Running JavaScript with the `run_js` tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsConfig {
+    /// Offer the `run_js` tool
+    pub enabled: bool,
+    /// Stop a script that runs longer than this
+    pub timeout_ms: u32,
+    /// Characters of console output and result returned to the model
+    /// (0 = no limit)
+    pub max_output_chars: usize,
+}
+
+impl Default for JsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_ms: 5_000,
+            max_output_chars: 20_000,
+        }
+    }
+}
+
+/// SQLite databases for the `sql` tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SqlConfig {
+    /// Offer the `sql` tool
+    pub enabled: bool,
+    /// Rows of a result shown to the model; the rest are counted
+    pub max_rows: usize,
+    /// Stop a query that runs longer than this
+    pub timeout_ms: u32,
+}
+
+impl Default for SqlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_rows: 100,
+            timeout_ms: 30_000,
+        }
+    }
+}
+
+/// Long-term memory shared by all sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Offer the `remember` / `recall` tools
+    pub enabled: bool,
+    /// Append the most recent memories to the system prompt
+    pub inject_into_prompt: bool,
+    /// How many memories to append
+    pub max_injected: usize,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            inject_into_prompt: true,
+            max_injected: 20,
+        }
+    }
+}
+
+/// Reuse of earlier LLM replies to identical requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheConfig {
+    pub enabled: bool,
+    /// How long a stored reply is reused
+    pub ttl_secs: u64,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+/// How earlier turns are trimmed to fit the request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategyKind {
+    /// Send the whole conversation
+    #[default]
+    FullHistory,
+    /// The last `window_turns` turns
+    SlidingWindow,
+    /// As many of the most recent messages as fit in `max_tokens`
+    RecentBudget,
+    /// The most useful earlier messages that fit in `max_tokens`: user
+    /// requests and answers before tool traffic, newer before older
+    ImportanceWeighted,
+}
+
+impl ContextStrategyKind {
+    pub fn all() -> &'static [ContextStrategyKind] {
+        &[
+            ContextStrategyKind::FullHistory,
+            ContextStrategyKind::SlidingWindow,
+            ContextStrategyKind::RecentBudget,
+            ContextStrategyKind::ImportanceWeighted,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContextStrategyKind::FullHistory => "Full history",
+            ContextStrategyKind::SlidingWindow => "Sliding window",
+            ContextStrategyKind::RecentBudget => "Recent messages",
+            ContextStrategyKind::ImportanceWeighted => "Importance weighted",
+        }
+    }
+}
+
+/// Which parts of the conversation are sent to the LLM. The full history
+/// is always kept in the session; the current turn is always sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextConfig {
+    pub strategy: ContextStrategyKind,
+    /// Earlier turns kept by the sliding window
+    pub window_turns: usize,
+    /// Estimated tokens of earlier messages kept by the budgeted strategies
+    pub max_tokens: usize,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            strategy: ContextStrategyKind::FullHistory,
+            window_turns: 10,
+            max_tokens: 32_000,
         }
     }
 }
 
+/// Self-review of final answers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReviewConfig {
+    /// Before a turn ends, ask the model to check its answer against the
+    /// request and fix what it missed
+    pub enabled: bool,
+    /// Only review turns that called at least this many tools
+    pub min_tool_calls: usize,
+}
+
+impl Default for ReviewConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_tool_calls: 2,
+        }
+    }
+}
+
+/// A role the agent can take on in a session: its own system prompt, the
+/// tools it may use and the model it prefers
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Persona {
+    pub name: String,
+    /// Replaces the global system prompt; empty = keep it
+    pub system_prompt: String,
+    /// Tools it may use; None = all of them
+    pub allowed_tools: Option<Vec<String>>,
+    /// Model it uses unless the session picks one; None = the main model
+    pub model: Option<String>,
+}
+
+/// The personas offered before the user edits the list
+pub fn default_personas() -> Vec<Persona> {
+    let tools = |names: &[&str]| Some(names.iter().map(|n| n.to_string()).collect());
+    vec![
+        Persona {
+            name: "Code Reviewer".to_string(),
+            system_prompt: format!(
+                "{}\nYou are reviewing code, not changing it. Read the files involved and point out bugs, \
+                 risky patterns and unclear code, citing the file and line. You cannot write files or run commands.\n",
+                DEFAULT_SYSTEM_PROMPT
+            ),
+            allowed_tools: tools(&["read_file", "list_dir", "search_workspace", "recall", "read_url"]),
+            model: None,
+        },
+        Persona {
+            name: "Sysadmin".to_string(),
+            system_prompt: format!(
+                "{}\nYou are a system administrator. Prefer the shell: inspect with commands before changing \
+                 anything, chain small steps, and show the commands you ran with their results.\n",
+                DEFAULT_SYSTEM_PROMPT
+            ),
+            allowed_tools: None,
+            model: None,
+        },
+        Persona {
+            name: "Writer".to_string(),
+            system_prompt: "You are a writing assistant. Draft, edit and restructure text as asked, keeping \
+                            the author's voice. You have no tools; work from what is in the conversation.\n"
+                .to_string(),
+            allowed_tools: tools(&[]),
+            model: None,
+        },
+    ]
+}
+
+/// Planner/executor orchestration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanConfig {
+    /// Have a planner split each request into steps, run each step in a
+    /// fresh executor runtime and answer from their results
+    pub enabled: bool,
+    /// Model that plans and writes the answer; empty = the main model
+    pub planner_model: String,
+    /// Model that carries out the steps; empty = the main model
+    pub executor_model: String,
+    pub max_steps: usize,
+}
+
+impl Default for PlanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            planner_model: String::new(),
+            executor_model: String::new(),
+            max_steps: 6,
+        }
+    }
+}
+
+impl PlanConfig {
+    /// The planner's model, given the main one
+    pub fn planner<'a>(&'a self, main: &'a str) -> &'a str {
+        non_empty(&self.planner_model).unwrap_or(main)
+    }
+
+    /// The executor's model, given the main one
+    pub fn executor<'a>(&'a self, main: &'a str) -> &'a str {
+        non_empty(&self.executor_model).unwrap_or(main)
+    }
+}
+
+fn non_empty(model: &str) -> Option<&str> {
+    Some(model.trim()).filter(|m| !m.is_empty())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmConfig {
     pub provider: LlmProvider,
     pub model: String,
     pub api_key: String,
     pub api_base: Option<String>,
+    /// CORS proxy for providers that reject browser-origin requests.
+    /// See [`LlmConfig::request_url`] for how it is applied.
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    /// Extra headers sent with every request, e.g. for gateways that need
+    /// their own auth. A header named like a built-in one replaces it.
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    /// Tried in order when the provider before fails with a retryable error
+    #[serde(default)]
+    pub fallbacks: Vec<FallbackTarget>,
     pub max_tokens: u32,
     pub temperature: f32,
+    /// Nucleus sampling; provider default when unset
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default)]
+    pub presence_penalty: Option<f32>,
+    /// Sequences that end generation
+    #[serde(default)]
+    pub stop: Vec<String>,
+    /// Fixed seed for (best-effort) reproducible sampling
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub reasoning: ReasoningConfig,
+    /// Gemini content filters; categories not listed keep Google's default
+    #[serde(default)]
+    pub safety: Vec<SafetySetting>,
+    /// Seconds to wait for a reply before giving up; 0 waits forever
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u32,
+}
+
+fn default_timeout_secs() -> u32 {
+    120
+}
+
+/// Knobs for reasoning models. Each provider only honours the ones it
+/// supports: OpenAI-style APIs take `effort`, Anthropic takes `budget_tokens`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReasoningConfig {
+    pub effort: Option<ReasoningEffort>,
+    /// Extended-thinking token budget
+    pub budget_tokens: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    pub fn all() -> &'static [ReasoningEffort] {
+        &[ReasoningEffort::Low, ReasoningEffort::Medium, ReasoningEffort::High]
+    }
+
+    /// Wire value, as used by `reasoning_effort`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+}
+
+/// How strictly Gemini filters one category of content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafetySetting {
+    pub category: HarmCategory,
+    pub threshold: HarmThreshold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HarmCategory {
+    Harassment,
+    HateSpeech,
+    SexuallyExplicit,
+    DangerousContent,
+    CivicIntegrity,
+}
+
+impl HarmCategory {
+    pub fn all() -> &'static [HarmCategory] {
+        &[
+            HarmCategory::Harassment,
+            HarmCategory::HateSpeech,
+            HarmCategory::SexuallyExplicit,
+            HarmCategory::DangerousContent,
+            HarmCategory::CivicIntegrity,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            HarmCategory::Harassment => "Harassment",
+            HarmCategory::HateSpeech => "Hate speech",
+            HarmCategory::SexuallyExplicit => "Sexually explicit",
+            HarmCategory::DangerousContent => "Dangerous content",
+            HarmCategory::CivicIntegrity => "Civic integrity",
+        }
+    }
+
+    /// Wire value, as used by `safetySettings`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HarmCategory::Harassment => "HARM_CATEGORY_HARASSMENT",
+            HarmCategory::HateSpeech => "HARM_CATEGORY_HATE_SPEECH",
+            HarmCategory::SexuallyExplicit => "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+            HarmCategory::DangerousContent => "HARM_CATEGORY_DANGEROUS_CONTENT",
+            HarmCategory::CivicIntegrity => "HARM_CATEGORY_CIVIC_INTEGRITY",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HarmThreshold {
+    /// No filtering, and no safety ratings in the reply
+    Off,
+    BlockNone,
+    BlockOnlyHigh,
+    BlockMediumAndAbove,
+    BlockLowAndAbove,
+}
+
+impl HarmThreshold {
+    pub fn all() -> &'static [HarmThreshold] {
+        &[
+            HarmThreshold::Off,
+            HarmThreshold::BlockNone,
+            HarmThreshold::BlockOnlyHigh,
+            HarmThreshold::BlockMediumAndAbove,
+            HarmThreshold::BlockLowAndAbove,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            HarmThreshold::Off => "Off",
+            HarmThreshold::BlockNone => "Block none",
+            HarmThreshold::BlockOnlyHigh => "Block high",
+            HarmThreshold::BlockMediumAndAbove => "Block medium+",
+            HarmThreshold::BlockLowAndAbove => "Block low+",
+        }
+    }
+
+    /// Wire value, as used by `safetySettings`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HarmThreshold::Off => "OFF",
+            HarmThreshold::BlockNone => "BLOCK_NONE",
+            HarmThreshold::BlockOnlyHigh => "BLOCK_ONLY_HIGH",
+            HarmThreshold::BlockMediumAndAbove => "BLOCK_MEDIUM_AND_ABOVE",
+            HarmThreshold::BlockLowAndAbove => "BLOCK_LOW_AND_ABOVE",
+        }
+    }
+}
+
+impl LlmConfig {
+    /// The request timeout in milliseconds, `None` without one
+    pub fn timeout_ms(&self) -> Option<u32> {
+        (self.timeout_secs > 0).then(|| self.timeout_secs.saturating_mul(1000))
+    }
+
+    /// The threshold set for `category`, `None` for Google's default
+    pub fn safety_threshold(&self, category: HarmCategory) -> Option<HarmThreshold> {
+        self.safety.iter().find(|s| s.category == category).map(|s| s.threshold)
+    }
+
+    /// Set or (with `None`) clear the threshold for `category`
+    pub fn set_safety_threshold(&mut self, category: HarmCategory, threshold: Option<HarmThreshold>) {
+        self.safety.retain(|s| s.category != category);
+        if let Some(threshold) = threshold {
+            self.safety.push(SafetySetting { category, threshold });
+        }
+    }
+
+    /// The CORS proxy in use, if any
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy_url.as_deref().map(str::trim).filter(|p| !p.is_empty())
+    }
+
+    /// The URL to actually request for `url`, going through the CORS proxy
+    /// when one is set. A proxy containing `{url}` has it replaced with the
+    /// target (`https://proxy.example/?target={url}`); otherwise the target
+    /// is appended as a path (`https://proxy.example/https://api...`).
+    pub fn request_url(&self, url: &str) -> String {
+        proxied_url(self.proxy(), url)
+    }
+}
+
+/// `url` as requested through `proxy`; see [`LlmConfig::request_url`]
+pub fn proxied_url(proxy: Option<&str>, url: &str) -> String {
+    match proxy {
+        Some(proxy) if proxy.contains("{url}") => proxy.replace("{url}", url),
+        Some(proxy) => format!("{}/{}", proxy.trim_end_matches('/'), url),
+        None => url.to_string(),
+    }
+}
+
+/// A provider and model to fall back to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FallbackTarget {
+    pub provider: LlmProvider,
+    pub model: String,
+    /// Empty to reuse the primary key when the provider is the same
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub api_base: Option<String>,
+}
+
+impl LlmConfig {
+    /// This config pointed at `target`. Sampling settings, proxy and headers
+    /// carry over; key and base URL do only when the provider is the same.
+    pub fn for_fallback(&self, target: &FallbackTarget) -> LlmConfig {
+        let same_provider = target.provider == self.provider;
+        LlmConfig {
+            provider: target.provider.clone(),
+            model: target.model.clone(),
+            api_key: if target.api_key.is_empty() && same_provider {
+                self.api_key.clone()
+            } else {
+                target.api_key.clone()
+            },
+            api_base: match &target.api_base {
+                Some(base) => Some(base.clone()),
+                None if same_provider => self.api_base.clone(),
+                None => None,
+            },
+            fallbacks: Vec::new(),
+            ..self.clone()
+        }
+    }
 }
 
 impl Default for LlmConfig {
@@ -35,8 +927,19 @@ impl Default for LlmConfig {
             model: "deepseek-chat".to_string(),
             api_key: String::new(),
             api_base: None,
+            proxy_url: None,
+            headers: Vec::new(),
+            fallbacks: Vec::new(),
             max_tokens: 4096,
             temperature: 0.7,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: Vec::new(),
+            seed: None,
+            reasoning: ReasoningConfig::default(),
+            safety: Vec::new(),
+            timeout_secs: default_timeout_secs(),
         }
     }
 }
@@ -47,7 +950,11 @@ pub enum LlmProvider {
     OpenAI,
     Anthropic,
     Google,
+    /// In-browser inference via WebLLM (WebGPU) — no API key, works offline
+    Local,
     Custom,
+    /// Scripted offline answers for trying the app without an API key
+    Demo,
 }
 
 impl LlmProvider {
@@ -57,7 +964,9 @@ impl LlmProvider {
             LlmProvider::OpenAI => "https://api.openai.com",
             LlmProvider::Anthropic => "https://api.anthropic.com",
             LlmProvider::Google => "https://generativelanguage.googleapis.com",
+            LlmProvider::Local => "",
             LlmProvider::Custom => "",
+            LlmProvider::Demo => "",
         }
     }
 
@@ -67,7 +976,9 @@ impl LlmProvider {
             LlmProvider::OpenAI,
             LlmProvider::Anthropic,
             LlmProvider::Google,
+            LlmProvider::Local,
             LlmProvider::Custom,
+            LlmProvider::Demo,
         ]
     }
 
@@ -77,20 +988,86 @@ impl LlmProvider {
             LlmProvider::OpenAI => "OpenAI",
             LlmProvider::Anthropic => "Anthropic",
             LlmProvider::Google => "Google",
+            LlmProvider::Local => "Local (WebLLM)",
             LlmProvider::Custom => "Custom",
+            LlmProvider::Demo => "Demo (offline)",
+        }
+    }
+
+    /// A sensible model to preselect when switching to this provider
+    pub fn default_model(&self) -> &str {
+        match self {
+            LlmProvider::DeepSeek => "deepseek-chat",
+            LlmProvider::OpenAI => "gpt-4o",
+            LlmProvider::Anthropic => "claude-sonnet-4-20250514",
+            LlmProvider::Google => "gemini-pro",
+            LlmProvider::Local => "Qwen2.5-1.5B-Instruct-q4f16_1-MLC",
+            LlmProvider::Custom => "",
+            LlmProvider::Demo => "demo",
+        }
+    }
+
+    /// Whether requests to this provider need an API key
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, LlmProvider::Local | LlmProvider::Demo)
+    }
+}
+
+/// Limits on shell command output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShellConfig {
+    /// Bytes of stdout (and of stderr) kept per command: the first and last
+    /// half of this are returned, anything in between is elided
+    pub max_output_bytes: usize,
+    /// Kill a command once it has written this many bytes in total (0 = never)
+    pub kill_after_bytes: usize,
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self {
+            max_output_bytes: 32 * 1024,
+            kill_after_bytes: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Limit on tool output shown to the LLM over a whole turn
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolOutputConfig {
+    /// Bytes of tool results added to the conversation per turn (0 = no
+    /// limit). A result that doesn't fit in what's left is replaced by a
+    /// note pointing at the full output in `outputs_dir`.
+    pub turn_budget_bytes: usize,
+    /// Where omitted results are written
+    pub outputs_dir: String,
+}
+
+impl Default for ToolOutputConfig {
+    fn default() -> Self {
+        Self {
+            turn_budget_bytes: 128 * 1024,
+            outputs_dir: "/workspace/.agent/outputs".to_string(),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StorageConfig {
     pub backend: StorageBackendType,
+    /// Bytes kept when storage falls back to memory (0 = no limit); the
+    /// least recently used entries are evicted beyond this
+    pub memory_cap_bytes: usize,
 }
 
 impl Default for StorageConfig {
     fn default() -> Self {
         Self {
             backend: StorageBackendType::Auto,
+            memory_cap_bytes: 64 * 1024 * 1024,
         }
     }
 }
@@ -109,10 +1086,12 @@ You have access to a virtual filesystem and a bash shell (via WASIX/Wasmer).
 Your default workspace is at /workspace. All files should be read/written there.
 
 Available tools:
-- bash: Execute shell commands
+- bash: Execute shell commands; pass cwd to choose the directory and stdin to pipe text into them (e.g. a diff for patch)
 - read_file: Read file contents from the virtual filesystem
 - write_file: Write content to a file in the virtual filesystem
 - list_dir: List directory contents
+- run_script: Run a script file from the virtual filesystem with bash
+- remember / recall: Save and look up facts in long-term memory (when enabled)
 
 Workspace layout:
   /workspace/         — project root
@@ -123,4 +1102,6 @@ Workspace layout:
 When the user asks you to perform tasks, use the appropriate tools.
 Always explain what you're doing before executing commands.
 Write files to /workspace/ so they persist in the virtual filesystem.
+To cite a source, put a marker like [1] in the text and a line like
+"[1]: /workspace/src/lib.rs:42" or "[1]: https://example.com" at the end.
 "#;
@@ -32,10 +32,182 @@ pub enum AgentError {
     #[error("JS interop error: {0}")]
     JsInterop(String),
 
+    /// A classified failure from a provider or browser API
+    #[error("{}", describe_api(*status, message))]
+    Api {
+        kind: ErrorKind,
+        /// HTTP status, when the failure came from an HTTP response
+        status: Option<u16>,
+        /// Provider-specific error code (e.g. `insufficient_quota`)
+        code: Option<String>,
+        message: String,
+    },
+
     #[error("{0}")]
     Other(String),
 }
 
+/// What went wrong, independent of where
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Missing or rejected credentials (401/403)
+    Auth,
+    /// Too many requests (429)
+    RateLimited,
+    /// Billing/quota exhausted — waiting won't help
+    QuotaExceeded,
+    /// The request was malformed or rejected (400/422)
+    InvalidRequest,
+    /// The conversation exceeds the model's context window
+    ContextLength,
+    /// Model or endpoint does not exist (404)
+    NotFound,
+    /// Provider-side failure (5xx)
+    Server,
+    Network,
+    Timeout,
+    /// A required browser capability or worker is unavailable
+    Unavailable,
+    /// Browser storage quota exceeded
+    StorageFull,
+    Cancelled,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => ErrorKind::Auth,
+            404 => ErrorKind::NotFound,
+            408 => ErrorKind::Timeout,
+            413 => ErrorKind::ContextLength,
+            429 => ErrorKind::RateLimited,
+            400..=499 => ErrorKind::InvalidRequest,
+            500..=599 => ErrorKind::Server,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl AgentError {
+    pub fn api(kind: ErrorKind, message: impl Into<String>) -> Self {
+        AgentError::Api {
+            kind,
+            status: None,
+            code: None,
+            message: message.into(),
+        }
+    }
+
+    /// Classify a failed HTTP response. Understands the common
+    /// `{"error": {"message", "type", "code"}}` body shape.
+    pub fn from_http(status: u16, body: &str) -> Self {
+        let parsed: Option<serde_json::Value> = serde_json::from_str(body).ok();
+        let error = parsed.as_ref().and_then(|v| v.get("error"));
+        let message = error
+            .and_then(|e| e.get("message").or(Some(e)))
+            .and_then(|m| m.as_str())
+            .map(String::from)
+            .unwrap_or_else(|| body.trim().to_string());
+        let code = error
+            .and_then(|e| e.get("code").or_else(|| e.get("type")))
+            .and_then(|c| c.as_str())
+            .map(String::from);
+
+        let kind = match code.as_deref() {
+            Some("insufficient_quota") => ErrorKind::QuotaExceeded,
+            Some("context_length_exceeded") => ErrorKind::ContextLength,
+            Some("invalid_api_key") | Some("authentication_error") => ErrorKind::Auth,
+            Some("overloaded_error") => ErrorKind::Server,
+            _ => ErrorKind::from_status(status),
+        };
+
+        AgentError::Api {
+            kind,
+            status: Some(status),
+            code,
+            message,
+        }
+    }
+
+    /// Classify a `fetch()` that failed without a response. Browsers report
+    /// CORS rejections the same way as unreachable hosts, so the message
+    /// points at both, and at the proxy setting.
+    pub fn fetch_failed(message: &str, proxied: bool) -> Self {
+        let lower = message.to_lowercase();
+        let opaque = ["failed to fetch", "networkerror", "load failed", "cors"]
+            .iter()
+            .any(|m| lower.contains(m));
+        if !opaque {
+            return AgentError::Network(message.to_string());
+        }
+        let hint = if proxied {
+            "the CORS proxy could not be reached or rejected the request — check the proxy URL in Settings"
+        } else {
+            "the provider may not allow requests from a browser (CORS) — set a CORS proxy in Settings"
+        };
+        AgentError::Network(format!("{}; {}", message.trim(), hint))
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            AgentError::Api { kind, .. } => *kind,
+            AgentError::Network(_) => ErrorKind::Network,
+            AgentError::Timeout(_) => ErrorKind::Timeout,
+            AgentError::Cancelled => ErrorKind::Cancelled,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            AgentError::Api { status, .. } => *status,
+            _ => None,
+        }
+    }
+
+    pub fn provider_code(&self) -> Option<&str> {
+        match self {
+            AgentError::Api { code, .. } => code.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the same request may succeed if tried again later
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::RateLimited | ErrorKind::Server | ErrorKind::Network | ErrorKind::Timeout
+        )
+    }
+
+    /// A short explanation for the UI, with a hint at the fix where there is one
+    pub fn user_message(&self) -> String {
+        let hint = match self.kind() {
+            ErrorKind::Auth => "Authentication failed — check the API key in Settings, or pick the Demo (offline) provider to try the app without one.",
+            ErrorKind::RateLimited => "The provider is rate limiting requests — try again in a moment.",
+            ErrorKind::QuotaExceeded => "The provider account is out of quota — check your plan or billing.",
+            ErrorKind::ContextLength => {
+                "The conversation is too long for this model — start a new session or pick a model with a larger context."
+            }
+            ErrorKind::NotFound => "The model or endpoint was not found — check the model name and API base URL.",
+            ErrorKind::Server => "The provider had a server error — try again shortly.",
+            ErrorKind::Network => "Could not reach the provider — check your connection and the API base URL.",
+            ErrorKind::Timeout => "The provider didn't answer in time — try again, or raise the request timeout in Settings.",
+            ErrorKind::StorageFull => "Browser storage is full — delete old sessions or files.",
+            _ => return self.to_string(),
+        };
+        format!("{} ({})", hint, self)
+    }
+}
+
+fn describe_api(status: Option<u16>, message: &str) -> String {
+    match status {
+        Some(status) => format!("HTTP {}: {}", status, message),
+        None => message.to_string(),
+    }
+}
+
 impl From<serde_json::Error> for AgentError {
     fn from(e: serde_json::Error) -> Self {
         AgentError::Serialization(e.to_string())
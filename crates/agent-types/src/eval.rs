@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+
+use crate::pricing::UsageTotals;
+
+/// A list of scripted prompts, as stored in the workspace:
+///
+/// ```json
+/// { "cases": [
+///     { "name": "greeting", "prompt": "Say hi", "expect_contains": ["hi"] },
+///     { "name": "lists files", "prompt": "What is in /workspace?", "expect_tools": ["list_dir"] }
+/// ] }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvalSuite {
+    #[serde(default)]
+    pub name: String,
+    pub cases: Vec<EvalCase>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalCase {
+    pub name: String,
+    pub prompt: String,
+    /// Substrings the final message must contain (case-insensitive)
+    #[serde(default)]
+    pub expect_contains: Vec<String>,
+    /// Tools that must be called at least once
+    #[serde(default)]
+    pub expect_tools: Vec<String>,
+}
+
+/// What one case did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalOutcome {
+    pub name: String,
+    pub final_message: String,
+    /// Tool names, in call order
+    pub tool_calls: Vec<String>,
+    #[serde(default)]
+    pub usage: UsageTotals,
+    pub duration_ms: u64,
+    /// The turn ended with an error
+    pub error: Option<String>,
+    /// Expectations that were not met
+    #[serde(default)]
+    pub failures: Vec<String>,
+}
+
+impl EvalOutcome {
+    /// Record unmet expectations of `case` in `failures`.
+    pub fn check(&mut self, case: &EvalCase) {
+        self.failures.clear();
+        if let Some(error) = &self.error {
+            self.failures.push(format!("error: {}", error));
+        }
+        let message = self.final_message.to_lowercase();
+        for text in &case.expect_contains {
+            if !message.contains(&text.to_lowercase()) {
+                self.failures.push(format!("missing text \"{}\"", text));
+            }
+        }
+        for tool in &case.expect_tools {
+            if !self.tool_calls.contains(tool) {
+                self.failures.push(format!("tool {} not called", tool));
+            }
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Results of one run of a suite
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub suite: String,
+    /// "Provider / model" the suite ran against
+    pub target: String,
+    pub started_at: String,
+    pub outcomes: Vec<EvalOutcome>,
+}
+
+impl EvalReport {
+    pub fn new(suite: &str, target: &str) -> Self {
+        Self {
+            suite: suite.to_string(),
+            target: target.to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            outcomes: Vec::new(),
+        }
+    }
+
+    pub fn passed(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.passed()).count()
+    }
+
+    pub fn usage(&self) -> UsageTotals {
+        let mut total = UsageTotals::default();
+        for outcome in &self.outcomes {
+            total.add(&outcome.usage);
+        }
+        total
+    }
+
+    pub fn outcome(&self, name: &str) -> Option<&EvalOutcome> {
+        self.outcomes.iter().find(|o| o.name == name)
+    }
+}
+
+/// How a case changed against the baseline run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseChange {
+    /// Passed before, fails now
+    Regressed,
+    /// Failed before, passes now
+    Fixed,
+    Unchanged,
+    /// Not in the baseline
+    Added,
+    /// In the baseline only
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseComparison {
+    pub name: String,
+    pub change: CaseChange,
+    pub passed: bool,
+    /// Different tools, or the same tools in a different order
+    pub tools_changed: bool,
+    pub message_changed: bool,
+    pub token_delta: i64,
+    pub duration_delta_ms: i64,
+}
+
+/// Compare `current` with `baseline`, case by case (matched by name).
+/// Cases of `current` come first, in order, then removed ones.
+pub fn compare(baseline: &EvalReport, current: &EvalReport) -> Vec<CaseComparison> {
+    let mut rows: Vec<CaseComparison> = current
+        .outcomes
+        .iter()
+        .map(|now| {
+            let Some(before) = baseline.outcome(&now.name) else {
+                return CaseComparison {
+                    name: now.name.clone(),
+                    change: CaseChange::Added,
+                    passed: now.passed(),
+                    tools_changed: false,
+                    message_changed: false,
+                    token_delta: 0,
+                    duration_delta_ms: 0,
+                };
+            };
+            let change = match (before.passed(), now.passed()) {
+                (true, false) => CaseChange::Regressed,
+                (false, true) => CaseChange::Fixed,
+                _ => CaseChange::Unchanged,
+            };
+            CaseComparison {
+                name: now.name.clone(),
+                change,
+                passed: now.passed(),
+                tools_changed: before.tool_calls != now.tool_calls,
+                message_changed: before.final_message.trim() != now.final_message.trim(),
+                token_delta: tokens(&now.usage) - tokens(&before.usage),
+                duration_delta_ms: now.duration_ms as i64 - before.duration_ms as i64,
+            }
+        })
+        .collect();
+    rows.extend(
+        baseline
+            .outcomes
+            .iter()
+            .filter(|before| current.outcome(&before.name).is_none())
+            .map(|before| CaseComparison {
+                name: before.name.clone(),
+                change: CaseChange::Removed,
+                passed: false,
+                tools_changed: false,
+                message_changed: false,
+                token_delta: 0,
+                duration_delta_ms: 0,
+            }),
+    );
+    rows
+}
+
+fn tokens(usage: &UsageTotals) -> i64 {
+    (usage.prompt_tokens + usage.completion_tokens) as i64
+}
+
+/// Markdown summary of `report`, with a comparison table when a baseline
+/// is given.
+pub fn render_report(report: &EvalReport, baseline: Option<&EvalReport>) -> String {
+    let mut out = format!(
+        "# Eval: {}\n\n{} · {}\n\n**{}/{} passed** · {} tokens · {}\n\n",
+        if report.suite.is_empty() { "suite" } else { &report.suite },
+        report.target,
+        report.started_at,
+        report.passed(),
+        report.outcomes.len(),
+        tokens(&report.usage()),
+        crate::pricing::format_usd(report.usage().cost_usd),
+    );
+
+    out.push_str("| Case | Result | Tools | Tokens | Time |\n|---|---|---|---|---|\n");
+    for o in &report.outcomes {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} ms |\n",
+            o.name,
+            if o.passed() { "pass" } else { "FAIL" },
+            if o.tool_calls.is_empty() { "–".to_string() } else { o.tool_calls.join(", ") },
+            tokens(&o.usage),
+            o.duration_ms,
+        ));
+    }
+
+    let failed: Vec<_> = report.outcomes.iter().filter(|o| !o.passed()).collect();
+    if !failed.is_empty() {
+        out.push_str("\n## Failures\n\n");
+        for o in failed {
+            out.push_str(&format!("- **{}**: {}\n", o.name, o.failures.join("; ")));
+        }
+    }
+
+    if let Some(baseline) = baseline {
+        out.push_str(&format!(
+            "\n## Compared with {} ({}, {}/{} passed)\n\n",
+            baseline.started_at,
+            baseline.target,
+            baseline.passed(),
+            baseline.outcomes.len()
+        ));
+        out.push_str("| Case | Change | Tools | Answer | Δ tokens | Δ time |\n|---|---|---|---|---|---|\n");
+        for row in compare(baseline, report) {
+            let change = match row.change {
+                CaseChange::Regressed => "**regressed**",
+                CaseChange::Fixed => "fixed",
+                CaseChange::Unchanged => "–",
+                CaseChange::Added => "new",
+                CaseChange::Removed => "removed",
+            };
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {:+} | {:+} ms |\n",
+                row.name,
+                change,
+                if row.tools_changed { "changed" } else { "–" },
+                if row.message_changed { "changed" } else { "–" },
+                row.token_delta,
+                row.duration_delta_ms,
+            ));
+        }
+    }
+    out
+}
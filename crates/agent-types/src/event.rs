@@ -13,20 +13,67 @@ pub enum AgentEvent {
     /// LLM finished a complete response
     LlmComplete { text: String },
 
+    /// Reasoning/thinking text returned alongside a response
+    LlmReasoning { text: String },
+
     /// A tool call is about to execute
     ToolExecStart { call_id: String, tool_name: String, arguments: String },
 
     /// Streaming output from a tool (e.g., bash stdout)
     ToolOutput { call_id: String, chunk: String },
 
+    /// Progress of a long tool call: `done` of `total` (when known) `unit`s,
+    /// e.g. bytes written or files indexed
+    ToolProgress { call_id: String, done: u64, total: Option<u64>, unit: String },
+
+    /// A tool call produced an image, saved in the VFS at `path`, to show
+    /// with its result
+    ToolArtifact { call_id: String, path: String, mime_type: String },
+
     /// Tool execution finished
-    ToolExecEnd { call_id: String, result: String, success: bool },
+    ToolExecEnd {
+        call_id: String,
+        result: String,
+        success: bool,
+        duration_ms: u64,
+        #[serde(default)]
+        data: Option<crate::tool::ToolResultData>,
+    },
 
     /// Agent finished the current turn
     TurnEnd { turn_id: u64 },
 
     /// An error occurred
     Error { message: String },
+
+    /// A local model is being downloaded/initialised (progress in 0.0..=1.0)
+    ModelLoadProgress { progress: f32, text: String },
+
+    /// The previous provider failed; the request is being retried on another
+    LlmFallback { provider: String, model: String, reason: String },
+
+    /// An LLM request finished, successfully or not, after `latency_ms`
+    LlmCallEnd { model: String, latency_ms: u64, error: Option<String> },
+
+    /// The model is checking its answer before the turn ends
+    ReviewStart,
+
+    /// The planner split the request into these steps, by title
+    PlanReady { steps: Vec<String> },
+
+    /// An executor started on step `index` of the plan
+    PlanStepStart { index: usize },
+
+    /// Step `index` of the plan finished; `summary` is the executor's reply
+    /// or why it failed
+    PlanStepEnd { index: usize, success: bool, summary: String },
+
+    /// The shell worker finished starting up and accepts commands
+    ShellReady,
+
+    /// The shell worker failed to start or stopped responding; shell
+    /// commands are disabled
+    ShellUnavailable { reason: String },
 }
 
 /// Events from the Wasmer-JS worker thread
@@ -39,13 +86,27 @@ pub enum WorkerCommand {
         cmd: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         timeout_ms: Option<u64>,
+        /// Working directory, or the worker's default
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cwd: Option<String>,
+        /// Extra environment variables, as `[name, value]` pairs
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        env: Vec<(String, String)>,
+        /// Keep stdin open for `WriteStdin` until `CloseStdin`; otherwise
+        /// the command starts with it closed
+        #[serde(default)]
+        stdin: bool,
     },
     /// Cancel a running execution
     CancelExec { id: u64 },
     /// Write to stdin of a running process
     WriteStdin { id: u64, data: String },
+    /// Close stdin of a running process
+    CloseStdin { id: u64 },
     /// Initialize the Wasmer-JS runtime
     Init,
+    /// Heartbeat; the worker answers with `Pong` carrying the same `seq`
+    Ping { seq: u64 },
 }
 
 /// Events from the worker back to main thread
@@ -62,4 +123,86 @@ pub enum WorkerEvent {
     ExitCode { id: u64, code: i32 },
     /// An error occurred in the worker
     Error { id: u64, message: String },
+    /// Answer to `Ping`
+    Pong { seq: u64 },
+}
+
+/// Commands to the embeddings worker (transformers.js)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EmbeddingsCommand {
+    /// Load the given sentence-transformer model
+    Init { model: String },
+    /// Embed a batch of texts
+    Embed { id: u64, texts: Vec<String> },
+}
+
+/// Events from the embeddings worker back to main thread
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EmbeddingsEvent {
+    /// Model download/initialisation progress (0.0..=1.0)
+    Progress { progress: f32, text: String },
+    /// Model loaded and ready
+    Ready,
+    /// Result vectors for an Embed command
+    Vectors { id: u64, vectors: Vec<Vec<f32>> },
+    /// An error occurred (id 0 = model load failure)
+    Error { id: u64, message: String },
+}
+
+/// Commands to the LLM network worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum LlmWorkerCommand {
+    /// Perform an HTTP request. With `stream` set, the response is read as
+    /// server-sent chat completion chunks and reported as Delta /
+    /// ToolCallDelta / Reasoning / Usage events; otherwise the whole body
+    /// comes back at once.
+    Fetch {
+        id: u64,
+        method: String,
+        url: String,
+        headers: Vec<(String, String)>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<String>,
+        stream: bool,
+    },
+    /// Abort a request in flight
+    Abort { id: u64 },
+}
+
+/// Events from the LLM network worker back to main thread
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum LlmWorkerEvent {
+    /// Complete response (also sent for a failed streaming request)
+    Response {
+        id: u64,
+        status: u16,
+        /// Retry-After hint in milliseconds
+        #[serde(default)]
+        retry_after: Option<u32>,
+        body: String,
+    },
+    /// Batched text of a streaming response
+    Delta { id: u64, text: String },
+    /// Part of a tool call in a streaming response
+    ToolCallDelta {
+        id: u64,
+        index: usize,
+        #[serde(default)]
+        call_id: Option<String>,
+        #[serde(default)]
+        name: Option<String>,
+        arguments: String,
+    },
+    /// Batched reasoning trace of a streaming response
+    Reasoning { id: u64, text: String },
+    /// Token usage of a streaming response
+    Usage { id: u64, usage: crate::message::TokenUsage },
+    /// A streaming response finished
+    Done { id: u64 },
+    /// The request failed before a response arrived (network, CORS, abort)
+    Error { id: u64, message: String },
 }
@@ -1,12 +1,26 @@
+pub mod activity;
 pub mod message;
 pub mod event;
 pub mod tool;
 pub mod config;
 pub mod error;
+pub mod eval;
 pub mod session;
+pub mod share;
+pub mod snapshot;
+pub mod storage;
+pub mod memory;
+pub mod migration;
+pub mod models;
+pub mod pricing;
+pub mod prompt;
+pub mod replay;
+pub mod undo;
+pub mod vault;
+pub mod workspace;
 
 #[cfg(test)]
 mod tests;
 
-pub use error::AgentError;
+pub use error::{AgentError, ErrorKind};
 pub type Result<T> = std::result::Result<T, AgentError>;
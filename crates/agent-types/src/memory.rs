@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// A fact the agent chose to remember across sessions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Memory {
+    pub id: String,
+    pub fact: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub created_at: String,
+}
+
+impl Memory {
+    pub fn new(fact: impl Into<String>, tags: Vec<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            fact: fact.into(),
+            tags,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
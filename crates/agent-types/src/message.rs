@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::tool::ToolResultData;
+
 /// Role in a conversation
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -21,6 +23,70 @@ pub struct Message {
     /// Tool calls requested by the assistant
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub tool_calls: Vec<ToolCallRequest>,
+    /// Reasoning/thinking text returned by reasoning models
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub reasoning: Option<String>,
+    /// Bookkeeping (id, timestamp, model, usage) — never sent to the LLM
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub meta: Option<MessageMeta>,
+}
+
+/// Per-message metadata, filled in by the runtime and LLM adapters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageMeta {
+    pub id: String,
+    pub created_at: String,
+    /// Model that generated the message (assistant messages only)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub model: Option<String>,
+    /// Provider that generated the message, by label (assistant messages only)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub provider: Option<String>,
+    /// Token usage of the request that produced the message
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub usage: Option<TokenUsage>,
+    /// Estimated USD cost of that request, when the model's price is known
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cost_usd: Option<f64>,
+    /// VFS files the user attached as context (user messages only)
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub attachments: Vec<String>,
+    /// Answered from the response cache rather than the provider
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub cached: bool,
+    /// Structured details of the result (tool messages only)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub tool_data: Option<ToolResultData>,
+}
+
+impl MessageMeta {
+    /// Fresh id and the current time; everything else unset
+    pub fn new() -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            model: None,
+            provider: None,
+            usage: None,
+            cost_usd: None,
+            attachments: Vec::new(),
+            cached: false,
+            tool_data: None,
+        }
+    }
+}
+
+impl Default for MessageMeta {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
 }
 
 /// Content of a message — text or structured parts
@@ -79,6 +145,8 @@ impl Message {
             content: MessageContent::Text(text.into()),
             tool_call_id: None,
             tool_calls: Vec::new(),
+            reasoning: None,
+            meta: None,
         }
     }
 
@@ -88,6 +156,8 @@ impl Message {
             content: MessageContent::Text(text.into()),
             tool_call_id: None,
             tool_calls: Vec::new(),
+            reasoning: None,
+            meta: None,
         }
     }
 
@@ -97,6 +167,8 @@ impl Message {
             content: MessageContent::Text(text.into()),
             tool_call_id: None,
             tool_calls: Vec::new(),
+            reasoning: None,
+            meta: None,
         }
     }
 
@@ -106,6 +178,19 @@ impl Message {
             content: MessageContent::Text(content.into()),
             tool_call_id: Some(call_id.into()),
             tool_calls: Vec::new(),
+            reasoning: None,
+            meta: None,
         }
     }
+
+    /// Metadata id, if the message has been stamped
+    pub fn id(&self) -> Option<&str> {
+        self.meta.as_ref().map(|m| m.id.as_str())
+    }
+
+    /// Attach fresh metadata (id + timestamp) if the message has none yet,
+    /// and return it for further filling in.
+    pub fn stamp(&mut self) -> &mut MessageMeta {
+        self.meta.get_or_insert_with(MessageMeta::new)
+    }
 }
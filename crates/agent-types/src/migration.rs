@@ -0,0 +1,220 @@
+//! Restoring a saved [`AgentConfig`] across schema changes.
+//!
+//! Saved configs carry a `version`. On restore the JSON is first brought up
+//! to [`CONFIG_VERSION`] by the migrations in [`MIGRATIONS`], then merged
+//! onto the defaults one field at a time: a field that no longer fits the
+//! schema keeps its default and yields a warning instead of losing the
+//! whole config. Finally [`AgentConfig::validate`] repairs values that are
+//! out of range.
+//!
+//! Exported settings files go through the same path when imported.
+
+use serde_json::{Map, Value};
+
+use crate::config::{AgentConfig, CONFIG_VERSION};
+use crate::{AgentError, Result};
+
+/// Rewrites a config of one version into the next
+pub type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` turns a version `n` config into version `n + 1`.
+/// Version 0 is a config saved before versioning, which has the same
+/// fields as version 1.
+pub const MIGRATIONS: &[Migration] = &[|_| {}];
+
+/// A restored config and what happened to it on the way
+#[derive(Debug, Clone)]
+pub struct RestoredConfig {
+    pub config: AgentConfig,
+    /// Version the config was saved with
+    pub saved_version: u32,
+    /// Settings that were dropped or repaired, for the user
+    pub warnings: Vec<String>,
+}
+
+/// Restore a saved config. Fails only if `bytes` are not a JSON object.
+pub fn restore_config(bytes: &[u8]) -> Result<RestoredConfig> {
+    let value: Value = serde_json::from_slice(bytes)?;
+    let Value::Object(mut saved) = value else {
+        return Err(AgentError::Config("Saved settings are not a JSON object".to_string()));
+    };
+    let saved_version = saved.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+
+    let mut warnings = Vec::new();
+    if saved_version > CONFIG_VERSION {
+        warnings.push(format!(
+            "Settings were saved by a newer version (v{}); settings this version doesn't know are ignored",
+            saved_version
+        ));
+    }
+    migrate(&mut saved, saved_version, MIGRATIONS);
+    saved.insert("version".to_string(), Value::from(CONFIG_VERSION));
+
+    let mut merged = serde_json::to_value(AgentConfig::default())?;
+    merge_fields(&mut merged, "", "", saved, &mut warnings);
+    let mut config: AgentConfig = serde_json::from_value(merged)?;
+    warnings.extend(config.validate());
+    Ok(RestoredConfig { config, saved_version, warnings })
+}
+
+/// Apply the migrations from `version` on, in order.
+pub fn migrate(config: &mut Map<String, Value>, version: u32, migrations: &[Migration]) {
+    for migration in migrations.iter().skip(version as usize) {
+        migration(config);
+    }
+}
+
+/// Copy `saved` onto the object at `pointer` in `root` field by field,
+/// recursing into objects. A field is kept only if the whole config still
+/// deserializes with it; `name` is the dotted path used in warnings.
+fn merge_fields(root: &mut Value, pointer: &str, name: &str, saved: Map<String, Value>, warnings: &mut Vec<String>) {
+    for (key, value) in saved {
+        let field_pointer = format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+        let field_name = if name.is_empty() { key.clone() } else { format!("{}.{}", name, key) };
+        let previous = root.pointer(&field_pointer).cloned();
+        let value = match (&previous, value) {
+            (Some(Value::Object(_)), Value::Object(fields)) => {
+                merge_fields(root, &field_pointer, &field_name, fields, warnings);
+                continue;
+            }
+            (_, value) => value,
+        };
+
+        let Some(Value::Object(parent)) = root.pointer_mut(pointer) else {
+            continue;
+        };
+        parent.insert(key.clone(), value);
+        if serde_json::from_value::<AgentConfig>(root.clone()).is_ok() {
+            continue;
+        }
+        if let Some(Value::Object(parent)) = root.pointer_mut(pointer) {
+            match previous {
+                Some(previous) => parent.insert(key, previous),
+                None => parent.remove(&key),
+            };
+        }
+        warnings.push(format!("Setting `{}` could not be read and was reset", field_name));
+    }
+}
+
+// ─── Export / import ─────────────────────────────────────────
+
+/// Pretty JSON for a settings file. Unless `include_secrets`, API keys and
+/// header values are blanked so the file can be shared.
+pub fn export_config(config: &AgentConfig, include_secrets: bool) -> Result<String> {
+    let mut config = config.clone();
+    config.version = CONFIG_VERSION;
+    if !include_secrets {
+        config.llm.api_key.clear();
+        for target in &mut config.llm.fallbacks {
+            target.api_key.clear();
+        }
+        for (_, value) in &mut config.llm.headers {
+            value.clear();
+        }
+    }
+    Ok(serde_json::to_string_pretty(&config)?)
+}
+
+/// Fill secrets an imported config left blank from `current`: the API key,
+/// fallback keys for the same provider and model, and header values for
+/// headers of the same name.
+pub fn keep_secrets(imported: &mut AgentConfig, current: &AgentConfig) {
+    if imported.llm.api_key.is_empty() {
+        imported.llm.api_key = current.llm.api_key.clone();
+    }
+    for target in &mut imported.llm.fallbacks {
+        if target.api_key.is_empty() {
+            if let Some(existing) = current
+                .llm
+                .fallbacks
+                .iter()
+                .find(|f| f.provider == target.provider && f.model == target.model)
+            {
+                target.api_key = existing.api_key.clone();
+            }
+        }
+    }
+    for (name, value) in &mut imported.llm.headers {
+        if value.is_empty() {
+            if let Some((_, existing)) = current.llm.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+                *value = existing.clone();
+            }
+        }
+    }
+}
+
+/// A setting that differs between two configs
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// Dotted path, e.g. `llm.temperature`
+    pub field: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Settings that differ between `current` and `incoming`, in field order.
+/// API keys (fallback ones included) and header values are shown masked.
+pub fn config_changes(current: &AgentConfig, incoming: &AgentConfig) -> Vec<ConfigChange> {
+    let (Ok(current), Ok(incoming)) = (serde_json::to_value(current), serde_json::to_value(incoming)) else {
+        return Vec::new();
+    };
+    let mut changes = Vec::new();
+    diff_values("", Some(&current), Some(&incoming), &mut changes);
+    changes
+}
+
+fn diff_values(field: &str, from: Option<&Value>, to: Option<&Value>, changes: &mut Vec<ConfigChange>) {
+    if from == to {
+        return;
+    }
+    if let (Some(Value::Object(a)), Some(Value::Object(b))) = (from, to) {
+        let keys = a.keys().chain(b.keys().filter(|k| !a.contains_key(*k)));
+        for key in keys {
+            let child = if field.is_empty() { key.clone() } else { format!("{}.{}", field, key) };
+            diff_values(&child, a.get(key), b.get(key), changes);
+        }
+        return;
+    }
+    changes.push(ConfigChange {
+        field: field.to_string(),
+        from: display_value(from.map(|v| redact(field, v)).as_ref()),
+        to: display_value(to.map(|v| redact(field, v)).as_ref()),
+    });
+}
+
+const MASK: &str = "••••••";
+
+/// `value`, found at `field`, with API keys and header values masked,
+/// however deep they sit (e.g. `llm.fallbacks[].api_key`)
+fn redact(field: &str, value: &Value) -> Value {
+    match value {
+        Value::String(s) if !s.is_empty() && field.ends_with("api_key") => Value::String(MASK.to_string()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, v)| (key.clone(), redact(&format!("{}.{}", field, key), v)))
+                .collect(),
+        ),
+        // A header is a [name, value] pair
+        Value::Array(items) if field.ends_with("headers") => items
+            .iter()
+            .map(|pair| match pair.as_array().map(Vec::as_slice) {
+                Some([name, Value::String(v)]) if !v.is_empty() => {
+                    Value::Array(vec![name.clone(), Value::String(MASK.to_string())])
+                }
+                _ => pair.clone(),
+            })
+            .collect(),
+        Value::Array(items) => items.iter().map(|v| redact(&format!("{}[]", field), v)).collect(),
+        other => other.clone(),
+    }
+}
+
+fn display_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "(none)".to_string(),
+        Some(Value::String(s)) if s.is_empty() => "(empty)".to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
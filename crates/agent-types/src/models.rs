@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// Models a provider reported via its model listing endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelList {
+    pub models: Vec<String>,
+    /// RFC 3339 time the list was fetched
+    pub fetched_at: String,
+}
+
+impl ModelList {
+    /// A freshly fetched list, sorted and deduplicated
+    pub fn new(mut models: Vec<String>) -> Self {
+        models.sort();
+        models.dedup();
+        Self {
+            models,
+            fetched_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Seconds since the list was fetched; None if the timestamp is unreadable
+    pub fn age_secs(&self) -> Option<i64> {
+        let fetched = chrono::DateTime::parse_from_rfc3339(&self.fetched_at).ok()?;
+        Some((chrono::Utc::now() - fetched.with_timezone(&chrono::Utc)).num_seconds())
+    }
+
+    /// Older than `ttl_secs`, or of unknown age
+    pub fn is_stale(&self, ttl_secs: i64) -> bool {
+        self.age_secs().is_none_or(|age| age >= ttl_secs)
+    }
+
+    /// Whether `model` is one of the listed models. An empty list (the
+    /// provider reported nothing) accepts any name.
+    pub fn offers(&self, model: &str) -> bool {
+        self.models.is_empty() || self.models.iter().any(|m| m == model)
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::message::{Message, Role, TokenUsage};
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+impl ModelPrice {
+    pub const fn new(input_per_mtok: f64, output_per_mtok: f64) -> Self {
+        Self { input_per_mtok, output_per_mtok }
+    }
+
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_mtok
+            + usage.completion_tokens as f64 * self.output_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+/// List prices of common models. Keys are model name prefixes, so dated
+/// snapshots ("gpt-4o-2024-08-06") are priced like their family.
+pub const PRICE_TABLE: &[(&str, ModelPrice)] = &[
+    ("deepseek-chat", ModelPrice::new(0.27, 1.10)),
+    ("deepseek-reasoner", ModelPrice::new(0.55, 2.19)),
+    ("gpt-4o", ModelPrice::new(2.50, 10.00)),
+    ("gpt-4o-mini", ModelPrice::new(0.15, 0.60)),
+    ("gpt-4.1", ModelPrice::new(2.00, 8.00)),
+    ("gpt-4.1-mini", ModelPrice::new(0.40, 1.60)),
+    ("gpt-4.1-nano", ModelPrice::new(0.10, 0.40)),
+    ("o3", ModelPrice::new(2.00, 8.00)),
+    ("o3-mini", ModelPrice::new(1.10, 4.40)),
+    ("o4-mini", ModelPrice::new(1.10, 4.40)),
+    ("claude-opus-4", ModelPrice::new(15.00, 75.00)),
+    ("claude-sonnet-4", ModelPrice::new(3.00, 15.00)),
+    ("claude-3-7-sonnet", ModelPrice::new(3.00, 15.00)),
+    ("claude-3-5-haiku", ModelPrice::new(0.80, 4.00)),
+    ("gemini-2.5-pro", ModelPrice::new(1.25, 10.00)),
+    ("gemini-2.5-flash", ModelPrice::new(0.30, 2.50)),
+    ("gemini-pro", ModelPrice::new(0.50, 1.50)),
+];
+
+/// Price of `model`: an exact override first, then the longest matching
+/// prefix in [`PRICE_TABLE`]. A routing prefix ("openai/gpt-4o") is ignored.
+/// None for unknown and local models.
+pub fn price_for(model: &str, overrides: &BTreeMap<String, ModelPrice>) -> Option<ModelPrice> {
+    if let Some(price) = overrides.get(model) {
+        return Some(*price);
+    }
+    let name = model.rsplit('/').next().unwrap_or(model);
+    PRICE_TABLE
+        .iter()
+        .filter(|(prefix, _)| name.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| *price)
+}
+
+/// Tokens and cost added up over some span of activity
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageTotals {
+    pub turns: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageTotals {
+    /// Usage and cost recorded on `messages`
+    pub fn from_messages(messages: &[Message]) -> Self {
+        let mut totals = Self::default();
+        for meta in messages.iter().filter_map(|m| m.meta.as_ref()) {
+            if let Some(usage) = &meta.usage {
+                totals.prompt_tokens += usage.prompt_tokens as u64;
+                totals.completion_tokens += usage.completion_tokens as u64;
+            }
+            totals.cost_usd += meta.cost_usd.unwrap_or(0.0);
+        }
+        totals.turns = messages.iter().filter(|m| m.role == Role::User).count() as u64;
+        totals
+    }
+
+    /// Usage of the latest turn: everything from the last user message on
+    pub fn last_turn(messages: &[Message]) -> Self {
+        let start = messages.iter().rposition(|m| m.role == Role::User).unwrap_or(0);
+        Self::from_messages(&messages[start..])
+    }
+
+    pub fn add(&mut self, other: &UsageTotals) {
+        self.turns += other.turns;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+}
+
+/// "$0.0042" for small amounts, "$1.27" otherwise
+pub fn format_usd(amount: f64) -> String {
+    if amount > 0.0 && amount < 0.01 {
+        format!("${:.4}", amount)
+    } else {
+        format!("${:.2}", amount)
+    }
+}
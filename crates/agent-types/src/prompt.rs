@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+/// A saved prompt the user can insert into the chat input.
+/// `{{name}}` marks a placeholder filled in at insert time; `{{name:file}}`,
+/// `{{name:multiline}}` and `{{name:a|b}}` say what it takes (see
+/// [`PromptVariable::parse`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptSnippet {
+    pub id: String,
+    pub name: String,
+    pub body: String,
+    pub created_at: String,
+}
+
+impl PromptSnippet {
+    pub fn new(name: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.into(),
+            body: body.into(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Placeholder names in order of first appearance
+    pub fn placeholders(&self) -> Vec<String> {
+        self.variables().into_iter().map(|v| v.name).collect()
+    }
+
+    /// Placeholders with what they take, in order of first appearance.
+    /// The first declaration of a name decides its kind.
+    pub fn variables(&self) -> Vec<PromptVariable> {
+        let mut variables: Vec<PromptVariable> = Vec::new();
+        for (_, spec) in placeholder_spans(&self.body) {
+            if let Some(variable) = PromptVariable::parse(spec) {
+                if !variables.iter().any(|v| v.name == variable.name) {
+                    variables.push(variable);
+                }
+            }
+        }
+        variables
+    }
+
+    /// The body with each placeholder replaced by its value.
+    /// Placeholders without a value are left as they are.
+    pub fn fill(&self, values: &[(String, String)]) -> String {
+        let mut out = String::with_capacity(self.body.len());
+        let mut last = 0;
+        for (range, spec) in placeholder_spans(&self.body) {
+            let Some(variable) = PromptVariable::parse(spec) else {
+                continue;
+            };
+            if let Some((_, value)) = values.iter().find(|(n, _)| *n == variable.name) {
+                out.push_str(&self.body[last..range.start]);
+                out.push_str(value);
+                last = range.end;
+            }
+        }
+        out.push_str(&self.body[last..]);
+        out
+    }
+}
+
+/// What a template variable takes
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableKind {
+    /// One line of text
+    Text,
+    /// Several lines of text
+    Multiline,
+    /// A path in the VFS
+    File,
+    /// One of the listed values
+    Choice(Vec<String>),
+}
+
+/// A `{{placeholder}}` of a prompt template
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptVariable {
+    pub name: String,
+    pub kind: VariableKind,
+}
+
+impl PromptVariable {
+    /// Parse the inside of `{{…}}`: `name`, `name:text`, `name:multiline`,
+    /// `name:file` or `name:a|b|c`. Without a kind, names like `file`,
+    /// `path` or `source_file` take a file and anything else text.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (name, kind) = match spec.split_once(':') {
+            Some((name, kind)) => (name.trim(), Some(kind.trim())),
+            None => (spec.trim(), None),
+        };
+        if name.is_empty() {
+            return None;
+        }
+        let kind = match kind {
+            None | Some("") => {
+                let file_like = ["file", "path"]
+                    .iter()
+                    .any(|w| name == *w || name.ends_with(&format!("_{}", w)));
+                if file_like { VariableKind::File } else { VariableKind::Text }
+            }
+            Some("text") => VariableKind::Text,
+            Some("multiline") => VariableKind::Multiline,
+            Some("file") => VariableKind::File,
+            Some(options) if options.contains('|') => VariableKind::Choice(
+                options.split('|').map(str::trim).filter(|o| !o.is_empty()).map(String::from).collect(),
+            ),
+            // Unknown kinds still take a value
+            Some(_) => VariableKind::Text,
+        };
+        Some(Self { name: name.to_string(), kind })
+    }
+
+    /// Value a form starts with: the first choice, otherwise empty
+    pub fn initial_value(&self) -> String {
+        match &self.kind {
+            VariableKind::Choice(options) => options.first().cloned().unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Each complete `{{…}}` in `body`: its byte range and what is inside
+fn placeholder_spans(body: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut spans = Vec::new();
+    let mut from = 0;
+    while let Some(start) = body[from..].find("{{").map(|i| from + i) {
+        let Some(len) = body[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + len + 2;
+        spans.push((start..end, &body[start + 2..start + 2 + len]));
+        from = end;
+    }
+    spans
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::message::{Message, TokenUsage};
+use crate::tool::ExecResult;
+
+/// One recorded step of a live session, in the order it happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TapeEntry {
+    /// The user sent a message, starting a turn
+    Prompt { text: String },
+    /// An LLM response, or the error in its place
+    Llm {
+        /// Messages in the request, to notice when a replay goes off script
+        request_len: usize,
+        message: Option<Box<Message>>,
+        usage: Option<TokenUsage>,
+        error: Option<String>,
+    },
+    /// A shell command and its result, or the error in its place
+    Shell {
+        command: String,
+        result: Option<ExecResult>,
+        error: Option<String>,
+    },
+}
+
+/// Everything the LLM and shell returned during one session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub session_id: String,
+    pub created_at: String,
+    pub entries: Vec<TapeEntry>,
+}
+
+impl Recording {
+    pub fn new(session_id: &str) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Number of recorded turns
+    pub fn turns(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e, TapeEntry::Prompt { .. }))
+            .count()
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
-use crate::message::Message;
+use crate::message::{Message, Role};
 use crate::config::AgentConfig;
+use crate::pricing::UsageTotals;
 
 /// A persisted conversation session
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,7 +11,12 @@ pub struct Session {
     pub messages: Vec<Message>,
     pub created_at: String,
     pub updated_at: String,
-    pub config: AgentConfig,
+    /// Settings chosen when the session was created
+    #[serde(default)]
+    pub config: SessionConfig,
+    /// LLM-written summary, set when the conversation is archived
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
 impl Session {
@@ -22,9 +28,117 @@ impl Session {
             messages: Vec::new(),
             created_at: now.clone(),
             updated_at: now,
-            config: AgentConfig::default(),
+            config: SessionConfig::default(),
+            note: None,
         }
     }
+
+    /// Replace the message history, bump `updated_at`, and derive a title
+    /// from the first user message if the session is still untitled.
+    pub fn update_messages(&mut self, messages: Vec<Message>) {
+        self.messages = messages;
+        self.updated_at = chrono::Utc::now().to_rfc3339();
+        if self.title == "New Session" {
+            if let Some(first) = self.messages.iter().find(|m| m.role == Role::User) {
+                self.title = title_from_text(first.content.as_text());
+            }
+        }
+    }
+
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            updated_at: self.updated_at.clone(),
+            message_count: self.messages.len(),
+            cost_usd: UsageTotals::from_messages(&self.messages).cost_usd,
+        }
+    }
+}
+
+/// Settings a session overrides; unset ones follow the global config.
+/// (Sessions saved before overrides existed stored the whole config here;
+/// that reads back as no overrides, so they follow the current prompt.)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredSessionConfig")]
+pub struct SessionConfig {
+    pub system_prompt: Option<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    /// Name of the persona the session takes on. Its prompt and model
+    /// apply unless the session sets its own.
+    pub persona: Option<String>,
+}
+
+impl SessionConfig {
+    pub fn is_empty(&self) -> bool {
+        self.system_prompt.is_none() && self.model.is_none() && self.temperature.is_none() && self.persona.is_none()
+    }
+
+    /// `global` with this session's persona and overrides applied. A
+    /// persona that no longer exists is ignored.
+    pub fn apply(&self, global: &AgentConfig) -> AgentConfig {
+        let mut config = global.clone();
+        if let Some(persona) = self.persona.as_deref().and_then(|name| global.persona(name)) {
+            if !persona.system_prompt.trim().is_empty() {
+                config.system_prompt = persona.system_prompt.clone();
+            }
+            if let Some(model) = persona.model.as_ref().filter(|m| !m.trim().is_empty()) {
+                config.llm.model = model.clone();
+            }
+            config.allowed_tools = persona.allowed_tools.clone();
+        }
+        if let Some(prompt) = &self.system_prompt {
+            config.system_prompt = prompt.clone();
+        }
+        if let Some(model) = &self.model {
+            config.llm.model = model.clone();
+        }
+        if let Some(temperature) = self.temperature {
+            config.llm.temperature = temperature;
+        }
+        config
+    }
+}
+
+/// `SessionConfig` as stored, old shape included
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StoredSessionConfig {
+    system_prompt: Option<String>,
+    model: Option<String>,
+    temperature: Option<f32>,
+    persona: Option<String>,
+    /// Only a whole `AgentConfig` has this
+    llm: Option<serde::de::IgnoredAny>,
+}
+
+impl From<StoredSessionConfig> for SessionConfig {
+    fn from(stored: StoredSessionConfig) -> Self {
+        if stored.llm.is_some() {
+            return Self::default();
+        }
+        Self {
+            system_prompt: stored.system_prompt,
+            model: stored.model,
+            temperature: stored.temperature,
+            persona: stored.persona,
+        }
+    }
+}
+
+/// First line of the text, truncated to a tab-friendly length
+fn title_from_text(text: &str) -> String {
+    const MAX_CHARS: usize = 40;
+    let line = text.lines().next().unwrap_or("").trim();
+    if line.chars().count() > MAX_CHARS {
+        let truncated: String = line.chars().take(MAX_CHARS).collect();
+        format!("{}…", truncated.trim_end())
+    } else if line.is_empty() {
+        "New Session".to_string()
+    } else {
+        line.to_string()
+    }
 }
 
 /// Summary of a session for listing
@@ -34,4 +148,26 @@ pub struct SessionSummary {
     pub title: String,
     pub updated_at: String,
     pub message_count: usize,
+    /// Estimated cost of the whole conversation
+    #[serde(default)]
+    pub cost_usd: f64,
+}
+
+/// The active session as it was when the app crashed, written out
+/// synchronously so a reload can put it back in storage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecovery {
+    /// Workspace whose session list it belongs to
+    pub workspace_id: String,
+    pub session: Session,
+}
+
+impl SessionRecovery {
+    /// Whether this copy holds messages the `stored` one lacks. Sessions
+    /// are saved after every turn, so usually only a turn that was still
+    /// running is missing.
+    pub fn is_newer_than(&self, stored: Option<&Session>) -> bool {
+        let saved = stored.map_or(0, |s| s.messages.len());
+        self.session.messages.len() > saved
+    }
 }
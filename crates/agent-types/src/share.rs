@@ -0,0 +1,114 @@
+//! Read-only copies of a conversation, for share links.
+//!
+//! A [`SharedTranscript`] holds what the chat shows and nothing else: no
+//! settings, keys, system prompt or usage. The app compresses its JSON and
+//! puts it in the link's fragment as base64url, so opening the link needs
+//! neither a server nor the sharing browser's storage.
+
+use serde::{Deserialize, Serialize};
+
+use crate::message::{Message, MessageContent, MessageMeta, Role};
+use crate::session::Session;
+use crate::{AgentError, Result};
+
+/// Format of [`SharedTranscript`]; links of a newer format are refused
+pub const SHARE_VERSION: u32 = 1;
+
+/// Links longer than this may be cut off by chat apps and mail clients
+pub const LONG_SHARE_LINK_CHARS: usize = 32 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedTranscript {
+    pub version: u32,
+    pub title: String,
+    /// When the conversation started
+    pub created_at: String,
+    pub messages: Vec<Message>,
+}
+
+impl SharedTranscript {
+    /// The conversation of `session`, without its system prompt or
+    /// settings. Messages keep their text, tool calls, reasoning and tool
+    /// results; image parts and usage are dropped.
+    pub fn from_session(session: &Session) -> Self {
+        let messages = session
+            .messages
+            .iter()
+            .filter(|m| m.role != Role::System)
+            .map(|m| Message {
+                role: m.role.clone(),
+                content: MessageContent::Text(m.content.as_text().to_string()),
+                tool_call_id: m.tool_call_id.clone(),
+                tool_calls: m.tool_calls.clone(),
+                reasoning: m.reasoning.clone(),
+                meta: m.meta.as_ref().map(|meta| MessageMeta {
+                    id: String::new(),
+                    created_at: meta.created_at.clone(),
+                    model: meta.model.clone(),
+                    provider: None,
+                    usage: None,
+                    cost_usd: None,
+                    attachments: meta.attachments.clone(),
+                    cached: false,
+                    tool_data: meta.tool_data.clone(),
+                }),
+            })
+            .collect();
+        Self {
+            version: SHARE_VERSION,
+            title: session.title.clone(),
+            created_at: session.created_at.clone(),
+            messages,
+        }
+    }
+
+    /// Read a transcript from its JSON, refusing formats this build doesn't know
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        let transcript: Self = serde_json::from_slice(json)?;
+        if transcript.version > SHARE_VERSION {
+            return Err(AgentError::Other(format!(
+                "This link was made by a newer version (format {}); update the app to open it",
+                transcript.version
+            )));
+        }
+        Ok(transcript)
+    }
+}
+
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// `bytes` as unpadded base64url, safe in a URL fragment
+pub fn encode_base64url(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - 8 * i)));
+        for i in 0..=chunk.len() {
+            out.push(BASE64URL[((n >> (18 - 6 * i)) & 63) as usize] as char);
+        }
+    }
+    out
+}
+
+/// Bytes of unpadded (or padded) base64url `text`
+pub fn decode_base64url(text: &str) -> Result<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.bytes() {
+        let value = BASE64URL
+            .iter()
+            .position(|&b| b == c)
+            .ok_or_else(|| AgentError::Other(format!("'{}' is not a base64url character", c as char)))?;
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    if count >= 6 {
+        return Err(AgentError::Other("base64url text has a stray character at the end".to_string()));
+    }
+    Ok(out)
+}
@@ -0,0 +1,34 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// The workspace's files as they were when a turn started.
+/// Contents live in shared blobs, so unchanged files cost nothing extra.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Position in the snapshot history; later snapshots have larger numbers
+    pub seq: u64,
+    /// What was about to happen, e.g. the user message of the turn
+    pub label: String,
+    pub created_at: String,
+    /// path → content blob key
+    pub files: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    pub fn new(seq: u64, label: impl Into<String>, files: BTreeMap<String, String>) -> Self {
+        Self {
+            seq,
+            label: label.into(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            files,
+        }
+    }
+
+    /// Local time the snapshot was taken, e.g. "14:05"
+    pub fn time_label(&self) -> String {
+        chrono::DateTime::parse_from_rfc3339(&self.created_at)
+            .map(|t| t.with_timezone(&chrono::Local).format("%H:%M").to_string())
+            .unwrap_or_default()
+    }
+}
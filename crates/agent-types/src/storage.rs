@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+/// What a storage backend knows about a value without reading it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyMeta {
+    /// Bytes in the value
+    pub size: u64,
+    /// When the value was last written (RFC 3339), if recorded
+    pub modified: Option<String>,
+    /// MIME type from the key's extension or the value's first bytes
+    pub content_type: Option<String>,
+}
+
+impl KeyMeta {
+    /// Metadata for `value` written to `key` now
+    pub fn for_value(key: &str, value: &[u8]) -> Self {
+        Self {
+            size: value.len() as u64,
+            modified: Some(chrono::Utc::now().to_rfc3339()),
+            content_type: detect_content_type(key, value).map(String::from),
+        }
+    }
+
+    /// Metadata for a value of `size` bytes whose write time is unknown,
+    /// e.g. one written before metadata was kept
+    pub fn untimed(key: &str, size: u64) -> Self {
+        Self {
+            size,
+            modified: None,
+            content_type: content_type_for(key).map(String::from),
+        }
+    }
+}
+
+/// Bytes in browser storage by kind, for the diagnostics section
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageBreakdown {
+    /// Settings and their backup
+    pub config: u64,
+    /// Sessions listed in the active workspace
+    pub sessions: u64,
+    /// Files of the active workspace
+    pub vfs: u64,
+    /// Everything stored, other workspaces included
+    pub total: u64,
+}
+
+impl StorageBreakdown {
+    /// Bytes not counted in the other fields: other workspaces, caches,
+    /// memories, recordings and the like
+    pub fn other(&self) -> u64 {
+        self.total.saturating_sub(self.config + self.sessions + self.vfs)
+    }
+}
+
+/// MIME type for a key or path by its extension, if it has a known one
+pub fn content_type_for(key: &str) -> Option<&'static str> {
+    let name = key.rsplit('/').next().unwrap_or(key);
+    let (_, ext) = name.rsplit_once('.')?;
+    let mime = match ext.to_ascii_lowercase().as_str() {
+        "txt" | "log" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "toml" => "application/toml",
+        "yaml" | "yml" => "application/yaml",
+        "xml" => "application/xml",
+        "sh" => "application/x-sh",
+        "py" => "text/x-python",
+        "rs" => "text/x-rust",
+        "wasm" => "application/wasm",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// MIME type from the first bytes of a file, for formats with a signature
+pub fn sniff_content_type(data: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\0asm", "application/wasm"),
+    ];
+    SIGNATURES
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|(_, mime)| *mime)
+}
+
+/// MIME type of a file written to `key`: by extension, else by signature
+pub fn detect_content_type(key: &str, data: &[u8]) -> Option<&'static str> {
+    content_type_for(key).or_else(|| sniff_content_type(data))
+}
@@ -1,5 +1,7 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
+    #[allow(unused_imports)]
     use super::*;
     use crate::message::*;
     use crate::event::*;
@@ -41,6 +43,24 @@ mod tests {
         assert_eq!(msg.tool_call_id, Some("call_123".to_string()));
     }
 
+    #[test]
+    fn test_tool_data_serialization() {
+        let data = ToolResultData { exit_code: Some(2), ..Default::default() };
+        assert_eq!(serde_json::to_string(&data).unwrap(), r#"{"exit_code":2}"#);
+
+        let mut message = Message::tool_result("call_0", "out");
+        message.stamp().tool_data = Some(data.clone());
+        let restored: Message = serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap();
+        assert_eq!(restored.meta.unwrap().tool_data, Some(data));
+
+        // Messages saved before tool data existed still load
+        let old: Message = serde_json::from_str(
+            r#"{"role":"tool","content":"out","tool_call_id":"c","meta":{"id":"m","created_at":"2026-01-01T00:00:00Z"}}"#,
+        )
+        .unwrap();
+        assert_eq!(old.meta.unwrap().tool_data, None);
+    }
+
     #[test]
     fn test_message_serialization_roundtrip() {
         let msg = Message::user("test input");
@@ -50,6 +70,46 @@ mod tests {
         assert_eq!(deserialized.content.as_text(), "test input");
     }
 
+    #[test]
+    fn test_message_stamp_is_idempotent() {
+        let mut msg = Message::user("hi");
+        assert!(msg.id().is_none());
+        let id = msg.stamp().id.clone();
+        assert!(!id.is_empty());
+        assert_eq!(msg.stamp().id, id);
+        assert_eq!(msg.id(), Some(id.as_str()));
+    }
+
+    #[test]
+    fn test_message_meta_serialization() {
+        let json = serde_json::to_string(&Message::user("hi")).unwrap();
+        assert!(!json.contains("meta"));
+
+        let mut msg = Message::assistant("hello");
+        let meta = msg.stamp();
+        meta.model = Some("gpt-4o".to_string());
+        meta.usage = Some(TokenUsage {
+            prompt_tokens: 3,
+            completion_tokens: 2,
+            total_tokens: 5,
+        });
+        let back: Message = serde_json::from_str(&serde_json::to_string(&msg).unwrap()).unwrap();
+        assert_eq!(back.meta, msg.meta);
+    }
+
+    #[test]
+    fn test_message_reasoning_serialization() {
+        // Omitted when absent, so older sessions and providers are unaffected
+        let json = serde_json::to_string(&Message::assistant("hi")).unwrap();
+        assert!(!json.contains("reasoning"));
+
+        let mut msg = Message::assistant("42");
+        msg.reasoning = Some("6 times 7".to_string());
+        let json = serde_json::to_string(&msg).unwrap();
+        let back: Message = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.reasoning.as_deref(), Some("6 times 7"));
+    }
+
     #[test]
     fn test_message_with_tool_calls_serialization() {
         let msg = Message {
@@ -63,6 +123,8 @@ mod tests {
                     arguments: r#"{"command":"ls"}"#.to_string(),
                 },
             }],
+            reasoning: None,
+            meta: None,
         };
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("bash"));
@@ -148,6 +210,9 @@ mod tests {
             id: 42,
             cmd: "echo hello".to_string(),
             timeout_ms: Some(5000),
+            cwd: None,
+            env: Vec::new(),
+            stdin: false,
         };
         let json = serde_json::to_string(&cmd).unwrap();
         assert!(json.contains("ExecBash"));
@@ -162,6 +227,31 @@ mod tests {
         assert!(json.contains("Init"));
     }
 
+    #[test]
+    fn test_worker_command_cwd_and_env() {
+        let cmd = WorkerCommand::ExecBash {
+            id: 1,
+            cmd: "make".to_string(),
+            timeout_ms: None,
+            cwd: Some("/workspace/app".to_string()),
+            env: vec![("CI".to_string(), "1".to_string())],
+            stdin: false,
+        };
+        let json = serde_json::to_string(&cmd).unwrap();
+        assert!(json.contains(r#""cwd":"/workspace/app","env":[["CI","1"]]"#), "{}", json);
+        // Older senders leave them out
+        let cmd: WorkerCommand = serde_json::from_str(r#"{"type":"ExecBash","id":1,"cmd":"ls"}"#).unwrap();
+        assert!(matches!(cmd, WorkerCommand::ExecBash { cwd: None, ref env, stdin: false, .. } if env.is_empty()));
+    }
+
+    #[test]
+    fn test_worker_heartbeat_serialization() {
+        let ping = serde_json::to_string(&WorkerCommand::Ping { seq: 7 }).unwrap();
+        assert_eq!(ping, r#"{"type":"Ping","seq":7}"#);
+        let pong: WorkerEvent = serde_json::from_str(r#"{"type":"Pong","seq":7}"#).unwrap();
+        assert!(matches!(pong, WorkerEvent::Pong { seq: 7 }));
+    }
+
     #[test]
     fn test_worker_event_ready() {
         let event = WorkerEvent::Ready;
@@ -199,6 +289,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_embeddings_protocol_roundtrip() {
+        let cmd = EmbeddingsCommand::Embed { id: 3, texts: vec!["hello".to_string()] };
+        let json = serde_json::to_string(&cmd).unwrap();
+        assert!(json.contains(r#""type":"Embed""#));
+
+        let event: EmbeddingsEvent =
+            serde_json::from_str(r#"{"type":"Vectors","id":3,"vectors":[[0.5,0.25]]}"#).unwrap();
+        if let EmbeddingsEvent::Vectors { id, vectors } = event {
+            assert_eq!(id, 3);
+            assert_eq!(vectors, vec![vec![0.5, 0.25]]);
+        } else {
+            panic!("Wrong variant");
+        }
+    }
+
     // ─── Tool Tests ──────────────────────────────────────────
 
     #[test]
@@ -211,6 +317,7 @@ mod tests {
                 properties: serde_json::Map::new(),
                 required: vec!["command".to_string()],
             },
+            strict: false,
         };
         let json = serde_json::to_string(&tool).unwrap();
         assert!(json.contains("bash"));
@@ -219,6 +326,28 @@ mod tests {
         let deserialized: ToolDefinition = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized.name, "bash");
         assert_eq!(deserialized.parameters.required, vec!["command"]);
+        assert!(!json.contains("strict"));
+        assert!(!deserialized.strict);
+    }
+
+    #[test]
+    fn test_param_schema_builder() {
+        let params = ToolParameters::object()
+            .property("mode", ParamSchema::string("How").one_of(&["fast", "slow"]), true)
+            .property("count", ParamSchema::integer("").range(1.0, 10.0), false)
+            .property("tags", ParamSchema::array(ParamSchema::string(""), "Labels"), false);
+        assert_eq!(params.required, vec!["mode"]);
+
+        let json = serde_json::to_value(&params).unwrap();
+        assert_eq!(json["properties"]["mode"]["enum"], serde_json::json!(["fast", "slow"]));
+        assert_eq!(json["properties"]["count"]["minimum"], 1.0);
+        assert_eq!(json["properties"]["count"]["maximum"], 10.0);
+        assert!(json["properties"]["count"].get("description").is_none());
+        assert_eq!(json["properties"]["tags"]["items"]["type"], "string");
+        assert!(json["properties"]["tags"].get("enum").is_none());
+
+        let schema: ParamSchema = serde_json::from_value(json["properties"]["mode"].clone()).unwrap();
+        assert_eq!(schema, ParamSchema::string("How").one_of(&["fast", "slow"]));
     }
 
     #[test]
@@ -227,17 +356,29 @@ mod tests {
             stdout: "hello\n".to_string(),
             stderr: String::new(),
             exit_code: 0,
+            duration_ms: 0,
         };
         assert_eq!(result.exit_code, 0);
         assert!(result.stderr.is_empty());
     }
 
+    #[test]
+    fn test_exec_result_duration_defaults_to_unmeasured() {
+        // Recordings made before durations were kept
+        let result: ExecResult =
+            serde_json::from_str(r#"{"stdout":"","stderr":"","exit_code":1}"#).unwrap();
+        assert_eq!(result.duration_ms, 0);
+        assert_eq!(crate::activity::format_duration(2_150), "2.1 s");
+        assert_eq!(crate::activity::format_duration(125_000), "2m 05s");
+    }
+
     #[test]
     fn test_dir_entry_serialization() {
         let entry = DirEntry {
             name: "file.txt".to_string(),
             is_dir: false,
             size: 1024,
+            content_type: None,
         };
         let json = serde_json::to_string(&entry).unwrap();
         let deserialized: DirEntry = serde_json::from_str(&json).unwrap();
@@ -252,6 +393,7 @@ mod tests {
             size: 2048,
             is_dir: true,
             modified: Some("2026-01-01T00:00:00Z".to_string()),
+            content_type: None,
         };
         let json = serde_json::to_string(&stat).unwrap();
         let deserialized: FileStat = serde_json::from_str(&json).unwrap();
@@ -291,6 +433,37 @@ mod tests {
         assert_eq!(deserialized.llm.model, "deepseek-chat");
     }
 
+    #[test]
+    fn test_chat_display_config_defaults_when_missing() {
+        use crate::config::{ChatDensity, UiConfig};
+
+        let ui: UiConfig = serde_json::from_str(r#"{"notify_on_turn_end":true}"#).unwrap();
+        assert_eq!(ui.chat.density, ChatDensity::Comfortable);
+        assert_eq!(ui.chat.max_width, 0.0);
+        assert!(!ui.chat.bubbles);
+        assert_eq!(ui.scale, 1.0);
+        assert!(!ui.screen_reader);
+
+        let ui: UiConfig = serde_json::from_str(r#"{"chat":{"density":"compact","max_width":900}}"#).unwrap();
+        assert_eq!(ui.chat.density, ChatDensity::Compact);
+        assert_eq!(ui.chat.max_width, 900.0);
+        assert!(!ui.chat.monospace_assistant);
+    }
+
+    #[test]
+    fn test_llm_config_sampling_params_default_when_missing() {
+        let json = r#"{"provider":"OpenAI","model":"gpt-4o","api_key":"","api_base":null,"max_tokens":100,"temperature":0.3}"#;
+        let config: LlmConfig = serde_json::from_str(json).unwrap();
+        assert!(config.top_p.is_none());
+        assert!(config.frequency_penalty.is_none());
+        assert!(config.presence_penalty.is_none());
+        assert!(config.stop.is_empty());
+        assert!(config.seed.is_none());
+        assert!(config.proxy_url.is_none());
+        assert!(config.headers.is_empty());
+        assert!(config.fallbacks.is_empty());
+    }
+
     #[test]
     fn test_llm_provider_base_urls() {
         assert_eq!(LlmProvider::DeepSeek.default_base_url(), "https://api.deepseek.com");
@@ -305,23 +478,131 @@ mod tests {
         assert_eq!(LlmProvider::OpenAI.label(), "OpenAI");
         assert_eq!(LlmProvider::Anthropic.label(), "Anthropic");
         assert_eq!(LlmProvider::Google.label(), "Google");
+        assert_eq!(LlmProvider::Local.label(), "Local (WebLLM)");
         assert_eq!(LlmProvider::Custom.label(), "Custom");
+        assert_eq!(LlmProvider::Demo.label(), "Demo (offline)");
     }
 
     #[test]
     fn test_llm_provider_all() {
         let all = LlmProvider::all();
-        assert_eq!(all.len(), 5);
+        assert_eq!(all.len(), 7);
         assert!(all.contains(&LlmProvider::DeepSeek));
         assert!(all.contains(&LlmProvider::OpenAI));
     }
 
+    #[test]
+    fn test_llm_provider_requires_api_key() {
+        assert!(LlmProvider::OpenAI.requires_api_key());
+        assert!(!LlmProvider::Local.requires_api_key());
+        assert!(!LlmProvider::Demo.requires_api_key());
+    }
+
+    #[test]
+    fn test_llm_provider_default_model() {
+        assert_eq!(LlmProvider::DeepSeek.default_model(), AgentConfig::default().llm.model);
+        assert!(LlmProvider::Local.default_model().ends_with("-MLC"));
+    }
+
+    #[test]
+    fn test_llm_config_request_url_through_proxy() {
+        let mut config = LlmConfig::default();
+        let url = "https://api.anthropic.com/v1/models";
+        assert_eq!(config.request_url(url), url);
+
+        config.proxy_url = Some("https://proxy.example/".to_string());
+        assert_eq!(config.request_url(url), "https://proxy.example/https://api.anthropic.com/v1/models");
+
+        config.proxy_url = Some("https://proxy.example/fetch?url={url}".to_string());
+        assert_eq!(config.request_url(url), "https://proxy.example/fetch?url=https://api.anthropic.com/v1/models");
+
+        // A blank proxy is the same as none
+        config.proxy_url = Some("  ".to_string());
+        assert!(config.proxy().is_none());
+        assert_eq!(config.request_url(url), url);
+    }
+
+    #[test]
+    fn test_llm_config_for_fallback() {
+        let config = LlmConfig {
+            api_key: "sk-deepseek".to_string(),
+            api_base: Some("https://gateway.example".to_string()),
+            temperature: 0.2,
+            fallbacks: vec![FallbackTarget {
+                provider: LlmProvider::OpenAI,
+                model: "gpt-4o".to_string(),
+                api_key: "sk-openai".to_string(),
+                api_base: None,
+            }],
+            ..Default::default()
+        };
+
+        let other = config.for_fallback(&config.fallbacks[0]);
+        assert_eq!(other.provider, LlmProvider::OpenAI);
+        assert_eq!(other.model, "gpt-4o");
+        assert_eq!(other.api_key, "sk-openai");
+        assert!(other.api_base.is_none());
+        assert_eq!(other.temperature, 0.2);
+        assert!(other.fallbacks.is_empty());
+
+        // Same provider, other model: key and base URL carry over
+        let same = config.for_fallback(&FallbackTarget {
+            provider: LlmProvider::DeepSeek,
+            model: "deepseek-reasoner".to_string(),
+            api_key: String::new(),
+            api_base: None,
+        });
+        assert_eq!(same.api_key, "sk-deepseek");
+        assert_eq!(same.api_base.as_deref(), Some("https://gateway.example"));
+    }
+
     #[test]
     fn test_storage_backend_type() {
         let config = StorageConfig::default();
         assert_eq!(config.backend, StorageBackendType::Auto);
     }
 
+    #[test]
+    fn test_storage_config_without_memory_cap_uses_default() {
+        let config: StorageConfig = serde_json::from_str(r#"{"backend":"Memory"}"#).unwrap();
+        assert_eq!(config.backend, StorageBackendType::Memory);
+        assert_eq!(config.memory_cap_bytes, StorageConfig::default().memory_cap_bytes);
+    }
+
+    #[test]
+    fn test_llm_timeout_defaults_and_disables() {
+        use crate::config::LlmConfig;
+
+        let llm: LlmConfig = serde_json::from_str(
+            r#"{"provider":"OpenAI","model":"gpt-4o","api_key":"","api_base":null,"max_tokens":100,"temperature":0.5}"#,
+        )
+        .unwrap();
+        assert_eq!(llm.timeout_secs, 120);
+        assert_eq!(llm.timeout_ms(), Some(120_000));
+        let llm = LlmConfig { timeout_secs: 0, ..llm };
+        assert_eq!(llm.timeout_ms(), None);
+
+        let message = crate::AgentError::Timeout(120_000).user_message();
+        assert!(message.contains("request timeout in Settings"), "{}", message);
+    }
+
+    #[test]
+    fn test_safety_thresholds_replace_per_category() {
+        use crate::config::{HarmCategory, HarmThreshold, LlmConfig};
+
+        let mut llm = LlmConfig::default();
+        assert_eq!(llm.safety_threshold(HarmCategory::HateSpeech), None);
+        llm.set_safety_threshold(HarmCategory::HateSpeech, Some(HarmThreshold::BlockOnlyHigh));
+        llm.set_safety_threshold(HarmCategory::HateSpeech, Some(HarmThreshold::Off));
+        assert_eq!(llm.safety.len(), 1);
+        assert_eq!(llm.safety_threshold(HarmCategory::HateSpeech), Some(HarmThreshold::Off));
+
+        let json = serde_json::to_string(&llm).unwrap();
+        assert!(json.contains(r#"{"category":"hate_speech","threshold":"off"}"#));
+        llm.set_safety_threshold(HarmCategory::HateSpeech, None);
+        assert!(llm.safety.is_empty());
+    }
+
     // ─── Session Tests ───────────────────────────────────────
 
     #[test]
@@ -343,6 +624,138 @@ mod tests {
         assert_eq!(deserialized.title, "New Session");
     }
 
+    #[test]
+    fn test_session_note_defaults_to_none() {
+        let mut value = serde_json::to_value(Session::new("s1".to_string())).unwrap();
+        value.as_object_mut().unwrap().remove("note");
+        let session: Session = serde_json::from_value(value).unwrap();
+        assert!(session.note.is_none());
+    }
+
+    #[test]
+    fn test_session_config_overrides_global() {
+        let global = AgentConfig::default();
+        let overrides = SessionConfig {
+            system_prompt: Some("You are a SQL expert.".to_string()),
+            model: Some("gpt-4o".to_string()),
+            temperature: None,
+            persona: None,
+        };
+        let config = overrides.apply(&global);
+        assert_eq!(config.system_prompt, "You are a SQL expert.");
+        assert_eq!(config.llm.model, "gpt-4o");
+        assert_eq!(config.llm.temperature, global.llm.temperature);
+        assert_eq!(config.llm.provider, global.llm.provider);
+
+        assert!(SessionConfig::default().is_empty());
+        assert_eq!(SessionConfig::default().apply(&global).llm.model, global.llm.model);
+    }
+
+    #[test]
+    fn test_session_persona_applies_under_overrides() {
+        let mut global = AgentConfig::default();
+        global.personas.push(Persona {
+            name: "Tester".to_string(),
+            system_prompt: "You write tests.".to_string(),
+            allowed_tools: Some(vec!["read_file".to_string()]),
+            model: Some("gpt-4o-mini".to_string()),
+        });
+        let session = SessionConfig { persona: Some("Tester".to_string()), ..Default::default() };
+        assert!(!session.is_empty());
+        let config = session.apply(&global);
+        assert_eq!(config.system_prompt, "You write tests.");
+        assert_eq!(config.llm.model, "gpt-4o-mini");
+        assert!(config.tool_allowed("read_file"));
+        assert!(!config.tool_allowed("bash"));
+
+        // The session's own settings win over the persona's
+        let session = SessionConfig { model: Some("gpt-4o".to_string()), ..session };
+        assert_eq!(session.apply(&global).llm.model, "gpt-4o");
+
+        // A persona that was removed is ignored
+        let session = SessionConfig { persona: Some("Gone".to_string()), ..Default::default() };
+        let config = session.apply(&global);
+        assert_eq!(config.system_prompt, global.system_prompt);
+        assert!(config.tool_allowed("bash"));
+    }
+
+    #[test]
+    fn test_default_personas() {
+        let config = AgentConfig::default();
+        let writer = SessionConfig { persona: Some("Writer".to_string()), ..Default::default() }.apply(&config);
+        assert!(!writer.tool_allowed("read_file"));
+        let reviewer = SessionConfig { persona: Some("Code Reviewer".to_string()), ..Default::default() }.apply(&config);
+        assert!(reviewer.tool_allowed("read_file"));
+        assert!(!reviewer.tool_allowed("write_file"));
+        assert!(!reviewer.tool_allowed("bash"));
+
+        // Configs saved before personas existed get the defaults
+        let mut value = serde_json::to_value(&config).unwrap();
+        value.as_object_mut().unwrap().remove("personas");
+        let restored: AgentConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(restored.personas.len(), 3);
+        assert!(restored.allowed_tools.is_none());
+    }
+
+    #[test]
+    fn test_session_reads_legacy_config() {
+        let mut value = serde_json::to_value(Session::new("s1".to_string())).unwrap();
+        value["config"] = serde_json::to_value(AgentConfig::default()).unwrap();
+        let session: Session = serde_json::from_value(value).unwrap();
+        assert_eq!(session.config.model, None);
+        assert_eq!(session.config.temperature, None);
+    }
+
+    #[test]
+    fn test_session_legacy_config_follows_current_prompt() {
+        let json = r#"{
+            "id": "old",
+            "title": "Old chat",
+            "messages": [],
+            "created_at": "2025-01-01T00:00:00Z",
+            "updated_at": "2025-01-01T00:00:00Z",
+            "config": {
+                "system_prompt": "You are an old default prompt.",
+                "llm": { "provider": "OpenAI", "model": "gpt-4", "temperature": 0.7 },
+                "max_iterations": 10
+            }
+        }"#;
+        let session: Session = serde_json::from_str(json).unwrap();
+        assert_eq!(session.config, SessionConfig::default());
+
+        let global = AgentConfig { system_prompt: "The new prompt.".to_string(), ..Default::default() };
+        assert_eq!(session.config.apply(&global).system_prompt, "The new prompt.");
+
+        // A session with its own prompt keeps it
+        let mut value = serde_json::to_value(Session::new("s1".to_string())).unwrap();
+        value["config"] = serde_json::json!({ "system_prompt": "Be brief." });
+        let session: Session = serde_json::from_value(value).unwrap();
+        assert_eq!(session.config.system_prompt.as_deref(), Some("Be brief."));
+    }
+
+    #[test]
+    fn test_session_update_messages_derives_title() {
+        let mut session = Session::new("s1".to_string());
+        session.update_messages(vec![
+            Message::user("Build a tiny web server in Rust please\nwith routing"),
+            Message::assistant("Sure"),
+        ]);
+        assert_eq!(session.title, "Build a tiny web server in Rust please");
+        assert_eq!(session.summary().message_count, 2);
+
+        // An existing title is kept
+        session.update_messages(vec![Message::user("Something else")]);
+        assert_eq!(session.title, "Build a tiny web server in Rust please");
+    }
+
+    #[test]
+    fn test_session_title_truncated() {
+        let mut session = Session::new("s1".to_string());
+        session.update_messages(vec![Message::user("x".repeat(100))]);
+        assert_eq!(session.title.chars().count(), 41);
+        assert!(session.title.ends_with('…'));
+    }
+
     #[test]
     fn test_session_summary_serialization() {
         let summary = SessionSummary {
@@ -350,6 +763,7 @@ mod tests {
             title: "Chat about Rust".to_string(),
             updated_at: "2026-01-01T00:00:00Z".to_string(),
             message_count: 5,
+            cost_usd: 0.25,
         };
         let json = serde_json::to_string(&summary).unwrap();
         let deserialized: SessionSummary = serde_json::from_str(&json).unwrap();
@@ -393,4 +807,699 @@ mod tests {
         let cloned = err.clone();
         assert_eq!(err.to_string(), cloned.to_string());
     }
+
+    #[test]
+    fn test_error_kind_from_status() {
+        assert_eq!(ErrorKind::from_status(401), ErrorKind::Auth);
+        assert_eq!(ErrorKind::from_status(403), ErrorKind::Auth);
+        assert_eq!(ErrorKind::from_status(404), ErrorKind::NotFound);
+        assert_eq!(ErrorKind::from_status(429), ErrorKind::RateLimited);
+        assert_eq!(ErrorKind::from_status(400), ErrorKind::InvalidRequest);
+        assert_eq!(ErrorKind::from_status(503), ErrorKind::Server);
+    }
+
+    #[test]
+    fn test_error_from_http_parses_provider_body() {
+        let body = r#"{"error":{"message":"You exceeded your current quota","type":"insufficient_quota","code":"insufficient_quota"}}"#;
+        let err = AgentError::from_http(429, body);
+        assert_eq!(err.kind(), ErrorKind::QuotaExceeded);
+        assert_eq!(err.status(), Some(429));
+        assert_eq!(err.provider_code(), Some("insufficient_quota"));
+        assert_eq!(err.to_string(), "HTTP 429: You exceeded your current quota");
+        // Quota errors don't go away by waiting
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_error_from_http_plain_body() {
+        let err = AgentError::from_http(502, "Bad Gateway\n");
+        assert_eq!(err.kind(), ErrorKind::Server);
+        assert_eq!(err.provider_code(), None);
+        assert_eq!(err.to_string(), "HTTP 502: Bad Gateway");
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_error_retryability() {
+        assert!(AgentError::from_http(429, "{}").is_retryable());
+        assert!(AgentError::Network("offline".to_string()).is_retryable());
+        assert!(AgentError::Timeout(1000).is_retryable());
+        assert!(!AgentError::from_http(401, "{}").is_retryable());
+        assert!(!AgentError::Llm("bad".to_string()).is_retryable());
+        assert!(!AgentError::api(ErrorKind::StorageFull, "full").is_retryable());
+    }
+
+    #[test]
+    fn test_error_user_message() {
+        let err = AgentError::from_http(401, r#"{"error":{"message":"Invalid key"}}"#);
+        let msg = err.user_message();
+        assert!(msg.starts_with("Authentication failed"));
+        assert!(msg.contains("HTTP 401: Invalid key"));
+
+        // Unclassified errors are shown as-is
+        let err = AgentError::Shell("boom".to_string());
+        assert_eq!(err.user_message(), "Shell error: boom");
+    }
+
+    #[test]
+    fn test_error_fetch_failed_suggests_proxy() {
+        let err = AgentError::fetch_failed("TypeError: Failed to fetch", false);
+        assert_eq!(err.kind(), ErrorKind::Network);
+        assert!(err.to_string().contains("set a CORS proxy"));
+
+        let err = AgentError::fetch_failed("NetworkError when attempting to fetch resource.", true);
+        assert!(err.to_string().contains("check the proxy URL"));
+
+        // Other failures are left alone
+        let err = AgentError::fetch_failed("connection reset", false);
+        assert_eq!(err.to_string(), "Network error: connection reset");
+    }
+
+    // ─── ModelList Tests ─────────────────────────────────────
+
+    #[test]
+    fn test_model_list_sorted_and_fresh() {
+        use crate::models::ModelList;
+        let list = ModelList::new(vec!["b".to_string(), "a".to_string(), "b".to_string()]);
+        assert_eq!(list.models, vec!["a", "b"]);
+        assert!(list.age_secs().unwrap() < 5);
+        assert!(!list.is_stale(60));
+        assert!(list.is_stale(0));
+    }
+
+    #[test]
+    fn test_model_list_stale_when_old_or_unreadable() {
+        use crate::models::ModelList;
+        let mut list = ModelList::new(vec!["a".to_string()]);
+        list.fetched_at = "2000-01-01T00:00:00Z".to_string();
+        assert!(list.is_stale(24 * 60 * 60));
+        list.fetched_at = "yesterday".to_string();
+        assert!(list.age_secs().is_none());
+        assert!(list.is_stale(i64::MAX));
+    }
+
+    #[test]
+    fn test_model_list_offers() {
+        use crate::models::ModelList;
+        let list = ModelList::new(vec!["gpt-4o".to_string()]);
+        assert!(list.offers("gpt-4o"));
+        assert!(!list.offers("gpt-4"));
+        // An empty listing can't rule anything out
+        assert!(ModelList::new(Vec::new()).offers("anything"));
+    }
+
+    // ─── PromptSnippet Tests ─────────────────────────────────
+
+    #[test]
+    fn test_prompt_placeholders_in_order() {
+        use crate::prompt::PromptSnippet;
+        let snippet = PromptSnippet::new("Review", "Review {{ file }} for {{issue}}, then {{file}} again {{");
+        assert_eq!(snippet.placeholders(), vec!["file", "issue"]);
+        assert!(PromptSnippet::new("Plain", "No variables").placeholders().is_empty());
+    }
+
+    #[test]
+    fn test_prompt_fill_keeps_missing_values() {
+        use crate::prompt::PromptSnippet;
+        let snippet = PromptSnippet::new("Review", "Review {{file}} for {{issue}}");
+        let values = vec![("file".to_string(), "main.rs".to_string())];
+        assert_eq!(snippet.fill(&values), "Review main.rs for {{issue}}");
+    }
+
+    #[test]
+    fn test_prompt_variable_kinds() {
+        use crate::prompt::{PromptSnippet, VariableKind};
+        let snippet = PromptSnippet::new(
+            "Port",
+            "Port {{source_file}} to {{lang:rust|go}}: {{notes:multiline}} {{target:file}} {{x:bogus}}",
+        );
+        let kinds: Vec<_> = snippet.variables().into_iter().map(|v| (v.name, v.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("source_file".to_string(), VariableKind::File),
+                ("lang".to_string(), VariableKind::Choice(vec!["rust".to_string(), "go".to_string()])),
+                ("notes".to_string(), VariableKind::Multiline),
+                ("target".to_string(), VariableKind::File),
+                ("x".to_string(), VariableKind::Text),
+            ]
+        );
+        let values = vec![("lang".to_string(), "go".to_string())];
+        assert_eq!(PromptSnippet::new("Port", "Use {{ lang:rust|go }}").fill(&values), "Use go");
+    }
+
+    // ─── Key Vault Tests ─────────────────────────────────────
+
+    use crate::vault::{SealedVault, VaultKeys};
+
+    #[test]
+    fn test_vault_keys_by_provider() {
+        let mut keys = VaultKeys::default();
+        assert!(keys.set(&LlmProvider::OpenAI, "sk-openai"));
+        assert!(!keys.set(&LlmProvider::OpenAI, "sk-openai"));
+        assert!(keys.set(&LlmProvider::Anthropic, "sk-ant"));
+        assert_eq!(keys.get(&LlmProvider::OpenAI), Some("sk-openai"));
+        assert_eq!(keys.get(&LlmProvider::DeepSeek), None);
+
+        // An empty key removes the entry
+        assert!(keys.set(&LlmProvider::OpenAI, ""));
+        assert!(!keys.set(&LlmProvider::OpenAI, ""));
+        assert_eq!(keys.api_keys.len(), 1);
+    }
+
+    #[test]
+    fn test_sealed_vault_roundtrip() {
+        let sealed = SealedVault {
+            version: 1,
+            iterations: 310_000,
+            salt: vec![1, 2, 3],
+            iv: vec![4, 5],
+            ciphertext: vec![6, 7, 8, 9],
+        };
+        let json = serde_json::to_string(&sealed).unwrap();
+        assert!(!json.contains("sk-"));
+        assert_eq!(serde_json::from_str::<SealedVault>(&json).unwrap(), sealed);
+    }
+
+    // ─── Pricing Tests ───────────────────────────────────────
+
+    use crate::pricing::{ModelPrice, UsageTotals, format_usd, price_for};
+
+    fn priced_reply(model: &str, prompt: u32, completion: u32, cost: f64) -> Message {
+        let mut msg = Message::assistant("ok");
+        let meta = msg.stamp();
+        meta.model = Some(model.to_string());
+        meta.usage = Some(TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+        });
+        meta.cost_usd = Some(cost);
+        msg
+    }
+
+    #[test]
+    fn test_price_for_uses_longest_prefix() {
+        let none = std::collections::BTreeMap::new();
+        assert_eq!(price_for("gpt-4o-2024-08-06", &none), Some(ModelPrice::new(2.50, 10.00)));
+        assert_eq!(price_for("gpt-4o-mini", &none), Some(ModelPrice::new(0.15, 0.60)));
+        assert_eq!(price_for("openai/gpt-4o-mini", &none), Some(ModelPrice::new(0.15, 0.60)));
+        assert!(price_for("Qwen2.5-1.5B-Instruct-q4f16_1-MLC", &none).is_none());
+    }
+
+    #[test]
+    fn test_price_override_wins() {
+        let mut overrides = std::collections::BTreeMap::new();
+        overrides.insert("gpt-4o".to_string(), ModelPrice::new(1.0, 2.0));
+        assert_eq!(price_for("gpt-4o", &overrides), Some(ModelPrice::new(1.0, 2.0)));
+        // Overrides are exact; the snapshot still uses the table
+        assert_eq!(price_for("gpt-4o-2024-08-06", &overrides), Some(ModelPrice::new(2.50, 10.00)));
+    }
+
+    #[test]
+    fn test_model_price_cost() {
+        let usage = TokenUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 500_000,
+            total_tokens: 1_500_000,
+        };
+        assert!((ModelPrice::new(3.0, 15.0).cost(&usage) - 10.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_usage_totals_session_and_last_turn() {
+        let messages = vec![
+            Message::system("sys"),
+            Message::user("first"),
+            priced_reply("gpt-4o", 100, 10, 0.01),
+            Message::user("second"),
+            priced_reply("gpt-4o", 200, 20, 0.02),
+            priced_reply("gpt-4o", 300, 30, 0.03),
+        ];
+        let session = UsageTotals::from_messages(&messages);
+        assert_eq!(session.turns, 2);
+        assert_eq!(session.prompt_tokens, 600);
+        assert!((session.cost_usd - 0.06).abs() < 1e-9);
+
+        let turn = UsageTotals::last_turn(&messages);
+        assert_eq!(turn.turns, 1);
+        assert_eq!(turn.completion_tokens, 50);
+        assert!((turn.cost_usd - 0.05).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_session_summary_includes_cost() {
+        let mut session = Session::new("s1".to_string());
+        session.update_messages(vec![Message::user("hi"), priced_reply("gpt-4o", 10, 1, 0.125)]);
+        assert!((session.summary().cost_usd - 0.125).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_format_usd() {
+        assert_eq!(format_usd(0.0), "$0.00");
+        assert_eq!(format_usd(0.0042), "$0.0042");
+        assert_eq!(format_usd(1.267), "$1.27");
+    }
+
+    // ─── Activity Stats Tests ────────────────────────────────
+
+    use crate::activity::{ActivityStats, ToolRun, TurnRecord};
+
+    fn record(started_at: &str, duration_ms: u64, tools: &[(&str, bool)], tokens: u64) -> TurnRecord {
+        TurnRecord {
+            started_at: started_at.to_string(),
+            duration_ms,
+            tools: tools
+                .iter()
+                .map(|(name, success)| ToolRun { name: name.to_string(), success: *success })
+                .collect(),
+            error: None,
+            usage: UsageTotals { turns: 1, prompt_tokens: tokens, completion_tokens: 0, cost_usd: 0.25 },
+        }
+    }
+
+    #[test]
+    fn test_activity_stats_from_records() {
+        let mut failed = record("2026-03-02T08:00:00+00:00", 3000, &[("bash", false)], 50);
+        failed.error = Some("rate limited".to_string());
+        let records = [
+            record("2026-03-01T10:00:00+00:00", 1000, &[("bash", true), ("read_file", true)], 100),
+            record("2026-03-01T11:00:00+00:00", 2000, &[], 100),
+            failed,
+        ];
+        let stats = ActivityStats::from_records(&records);
+
+        assert_eq!(stats.turns, 3);
+        assert_eq!(stats.failed_turns, 1);
+        assert_eq!(stats.average_turn_ms, 2000);
+        assert_eq!(stats.tool_calls(), 3);
+        assert_eq!(stats.tool_failures(), 1);
+        assert_eq!(stats.tools["bash"].success_ratio(), 0.5);
+        assert_eq!(stats.usage.prompt_tokens, 250);
+
+        assert_eq!(stats.days.len(), 2);
+        assert_eq!(stats.days[0].day, "2026-03-01");
+        assert_eq!(stats.days[0].turns, 2);
+        assert_eq!(stats.days[0].tokens, 200);
+        assert_eq!(stats.days[1].cost_usd, 0.25);
+    }
+
+    #[test]
+    fn test_activity_stats_empty() {
+        let stats = ActivityStats::from_records(&[]);
+        assert_eq!(stats.turns, 0);
+        assert_eq!(stats.average_turn_ms, 0);
+        assert!(stats.days.is_empty());
+    }
+
+    // ─── Eval Tests ──────────────────────────────────────────
+
+    use crate::eval::{CaseChange, EvalCase, EvalOutcome, EvalReport, compare, render_report};
+
+    fn outcome(name: &str, message: &str, tools: &[&str], tokens: u64) -> EvalOutcome {
+        EvalOutcome {
+            name: name.to_string(),
+            final_message: message.to_string(),
+            tool_calls: tools.iter().map(|t| t.to_string()).collect(),
+            usage: UsageTotals { turns: 1, prompt_tokens: tokens, completion_tokens: 0, cost_usd: 0.0 },
+            duration_ms: 100,
+            error: None,
+            failures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_eval_outcome_check() {
+        let case = EvalCase {
+            name: "ls".to_string(),
+            prompt: "List files".to_string(),
+            expect_contains: vec!["README".to_string()],
+            expect_tools: vec!["list_dir".to_string()],
+        };
+        let mut good = outcome("ls", "Found readme.md", &["list_dir"], 10);
+        good.check(&case);
+        assert!(good.passed());
+
+        let mut bad = outcome("ls", "Nothing here", &[], 10);
+        bad.error = Some("timeout".to_string());
+        bad.check(&case);
+        assert_eq!(bad.failures.len(), 3);
+    }
+
+    #[test]
+    fn test_eval_compare_reports_changes() {
+        let mut baseline = EvalReport::new("suite", "DeepSeek / deepseek-chat");
+        baseline.outcomes = vec![
+            outcome("kept", "same", &["bash"], 100),
+            outcome("broke", "ok", &[], 10),
+            outcome("gone", "", &[], 0),
+        ];
+        let mut current = EvalReport::new("suite", "DeepSeek / deepseek-chat");
+        let mut broke = outcome("broke", "not ok", &["bash"], 30);
+        broke.failures.push("missing text \"ok\"".to_string());
+        current.outcomes = vec![outcome("kept", "same ", &["bash"], 80), broke, outcome("new", "", &[], 5)];
+
+        let rows = compare(&baseline, &current);
+        let changes: Vec<_> = rows.iter().map(|r| (r.name.as_str(), r.change)).collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("kept", CaseChange::Unchanged),
+                ("broke", CaseChange::Regressed),
+                ("new", CaseChange::Added),
+                ("gone", CaseChange::Removed),
+            ]
+        );
+        assert!(!rows[0].message_changed && !rows[0].tools_changed);
+        assert_eq!(rows[0].token_delta, -20);
+        assert!(rows[1].tools_changed && rows[1].message_changed);
+
+        let summary = render_report(&current, Some(&baseline));
+        assert!(summary.contains("**2/3 passed**"));
+        assert!(summary.contains("| broke | **regressed** |"));
+    }
+
+    // ─── Replay Tests ────────────────────────────────────────
+
+    use crate::replay::{Recording, TapeEntry};
+
+    #[test]
+    fn test_recording_roundtrip() {
+        let mut recording = Recording::new("s1");
+        recording.entries = vec![
+            TapeEntry::Prompt { text: "ls".to_string() },
+            TapeEntry::Shell {
+                command: "ls".to_string(),
+                result: Some(ExecResult { stdout: "a.txt".to_string(), stderr: String::new(), exit_code: 0, duration_ms: 0 }),
+                error: None,
+            },
+            TapeEntry::Llm { request_len: 4, message: Some(Box::new(Message::assistant("a.txt"))), usage: None, error: None },
+        ];
+        let json = serde_json::to_string(&recording).unwrap();
+        assert!(json.contains(r#""kind":"prompt""#));
+
+        let back: Recording = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.turns(), 1);
+        assert!(matches!(&back.entries[1], TapeEntry::Shell { result: Some(r), .. } if r.stdout == "a.txt"));
+    }
+
+    // ─── Config Migration Tests ──────────────────────────────
+
+    use crate::migration::{Migration, migrate, restore_config};
+
+    #[test]
+    fn test_restore_config_round_trip() {
+        let mut config = AgentConfig::default();
+        config.llm.model = "gpt-4o".to_string();
+        config.llm.provider = LlmProvider::OpenAI;
+        config.memory.enabled = false;
+        let restored = restore_config(&serde_json::to_vec(&config).unwrap()).unwrap();
+        assert_eq!(restored.saved_version, CONFIG_VERSION);
+        assert!(restored.warnings.is_empty(), "{:?}", restored.warnings);
+        assert_eq!(restored.config.llm.model, "gpt-4o");
+        assert_eq!(restored.config.llm.provider, LlmProvider::OpenAI);
+        assert!(!restored.config.memory.enabled);
+    }
+
+    #[test]
+    fn test_restore_config_keeps_good_fields() {
+        // Unversioned, with a broken field, an unknown one and a missing section
+        let saved = serde_json::json!({
+            "llm": { "provider": "Gemini", "model": "my-model", "temperature": 0.2 },
+            "memory": { "enabled": false, "max_injected": "lots" },
+            "prices": { "gpt-4.1": { "input_per_mtok": 2.0, "output_per_mtok": 8.0 } },
+            "colour_scheme": "dark"
+        });
+        let restored = restore_config(&serde_json::to_vec(&saved).unwrap()).unwrap();
+        let config = restored.config;
+        assert_eq!(restored.saved_version, 0);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.llm.model, "my-model");
+        assert_eq!(config.llm.temperature, 0.2);
+        assert_eq!(config.llm.provider, LlmProvider::DeepSeek);
+        assert!(!config.memory.enabled);
+        assert_eq!(config.memory.max_injected, MemoryConfig::default().max_injected);
+        assert!(config.prices.contains_key("gpt-4.1"));
+        assert_eq!(config.storage.backend, StorageBackendType::Auto);
+        assert_eq!(
+            restored.warnings,
+            vec![
+                "Setting `llm.provider` could not be read and was reset",
+                "Setting `memory.max_injected` could not be read and was reset",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restore_config_rejects_non_objects() {
+        assert!(restore_config(b"not json").is_err());
+        assert!(restore_config(b"[1, 2]").is_err());
+    }
+
+    #[test]
+    fn test_restore_config_warns_about_newer_versions() {
+        let mut value = serde_json::to_value(AgentConfig::default()).unwrap();
+        value["version"] = serde_json::json!(CONFIG_VERSION + 1);
+        let restored = restore_config(&serde_json::to_vec(&value).unwrap()).unwrap();
+        assert_eq!(restored.saved_version, CONFIG_VERSION + 1);
+        assert_eq!(restored.warnings.len(), 1);
+        assert!(restored.warnings[0].contains("newer version"));
+    }
+
+    #[test]
+    fn test_migrations_run_in_order_from_saved_version() {
+        let migrations: &[Migration] = &[
+            |c| {
+                c.insert("a".to_string(), serde_json::json!(1));
+            },
+            |c| {
+                let a = c["a"].as_i64().unwrap_or(0);
+                c.insert("b".to_string(), serde_json::json!(a + 1));
+            },
+        ];
+        let mut from_zero = serde_json::Map::new();
+        migrate(&mut from_zero, 0, migrations);
+        assert_eq!(from_zero["b"], 2);
+
+        let mut from_one = serde_json::Map::new();
+        from_one.insert("a".to_string(), serde_json::json!(5));
+        migrate(&mut from_one, 1, migrations);
+        assert_eq!(from_one["b"], 6);
+
+        let mut current = serde_json::Map::new();
+        migrate(&mut current, 2, migrations);
+        assert!(current.is_empty());
+    }
+
+    #[test]
+    fn test_config_validate_repairs_out_of_range_values() {
+        let mut config = AgentConfig::default();
+        assert!(config.validate().is_empty());
+
+        config.llm.temperature = 5.0;
+        config.llm.top_p = Some(f32::NAN);
+        config.llm.model = " ".to_string();
+        config.llm.proxy_url = Some("localhost:8080".to_string());
+        config.llm.headers = vec![(String::new(), "x".to_string()), ("X-Team".to_string(), "a".to_string())];
+        config.tool_output.outputs_dir = "outputs".to_string();
+        config.web.cache_dir = "web".to_string();
+        config.js.timeout_ms = 0;
+        config.sql.timeout_ms = 0;
+        config.aliases = vec![("ll".to_string(), "ls -la".to_string()), ("g p".to_string(), "git push".to_string())];
+        config.ui.chat.max_width = -20.0;
+        config.ui.scale = 10.0;
+        let warnings = config.validate();
+        assert_eq!(warnings.len(), 12, "{:?}", warnings);
+        assert_eq!(config.ui.chat.max_width, 0.0);
+        assert_eq!(config.ui.scale, 3.0);
+        assert_eq!(config.aliases, vec![("ll".to_string(), "ls -la".to_string())]);
+        assert_eq!(config.llm.temperature, 2.0);
+        assert_eq!(config.llm.top_p, Some(0.0));
+        assert_eq!(config.llm.model, "deepseek-chat");
+        assert_eq!(config.llm.proxy_url, None);
+        assert_eq!(config.llm.headers.len(), 1);
+        assert_eq!(config.tool_output.outputs_dir, ToolOutputConfig::default().outputs_dir);
+        assert_eq!(config.web.cache_dir, WebConfig::default().cache_dir);
+        assert_eq!(config.js.timeout_ms, JsConfig::default().timeout_ms);
+        assert_eq!(config.sql.timeout_ms, SqlConfig::default().timeout_ms);
+        assert!(config.validate().is_empty());
+    }
+
+    // ─── Config Export / Import Tests ────────────────────────
+
+    use crate::migration::{config_changes, export_config, keep_secrets};
+
+    #[test]
+    fn test_export_config_blanks_secrets() {
+        let mut config = AgentConfig::default();
+        config.llm.api_key = "sk-main".to_string();
+        config.llm.headers = vec![("X-Gateway-Key".to_string(), "gw-secret".to_string())];
+        config.llm.fallbacks = vec![FallbackTarget {
+            provider: LlmProvider::OpenAI,
+            model: "gpt-4o".to_string(),
+            api_key: "sk-fallback".to_string(),
+            api_base: None,
+        }];
+
+        let shared = export_config(&config, false).unwrap();
+        assert!(!shared.contains("sk-main") && !shared.contains("sk-fallback") && !shared.contains("gw-secret"));
+        assert!(shared.contains("X-Gateway-Key"));
+        assert!(export_config(&config, true).unwrap().contains("sk-main"));
+
+        // Importing the shared file keeps the local secrets
+        let mut imported = restore_config(shared.as_bytes()).unwrap().config;
+        keep_secrets(&mut imported, &config);
+        assert_eq!(imported.llm.api_key, "sk-main");
+        assert_eq!(imported.llm.fallbacks[0].api_key, "sk-fallback");
+        assert_eq!(imported.llm.headers[0].1, "gw-secret");
+        assert!(config_changes(&config, &imported).is_empty());
+    }
+
+    #[test]
+    fn test_config_changes_lists_differences() {
+        let current = AgentConfig::default();
+        let mut incoming = current.clone();
+        incoming.llm.temperature = 0.2;
+        incoming.llm.api_key = "sk-new".to_string();
+        incoming.memory.enabled = false;
+        incoming.prices.insert("my-model".to_string(), crate::pricing::ModelPrice::new(1.0, 2.0));
+
+        let changes = config_changes(&current, &incoming);
+        let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["llm.api_key", "llm.temperature", "memory.enabled", "prices.my-model"]);
+        assert_eq!(changes[0].from, "(empty)");
+        assert_eq!(changes[0].to, "••••••");
+        assert_eq!(changes[2].from, "true");
+        assert_eq!(changes[2].to, "false");
+        assert_eq!(changes[3].from, "(none)");
+    }
+
+    #[test]
+    fn test_config_changes_masks_fallback_keys_and_headers() {
+        let current = AgentConfig::default();
+        let mut incoming = current.clone();
+        incoming.llm.headers = vec![("X-Gateway-Key".to_string(), "gw-SECRET".to_string())];
+        incoming.llm.fallbacks = vec![FallbackTarget {
+            provider: LlmProvider::OpenAI,
+            model: "gpt-4o".to_string(),
+            api_key: "sk-SECRET".to_string(),
+            api_base: None,
+        }];
+        let imported = restore_config(export_config(&incoming, true).unwrap().as_bytes()).unwrap().config;
+
+        let changes = config_changes(&current, &imported);
+        let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["llm.fallbacks", "llm.headers"]);
+        for change in &changes {
+            assert!(!change.to.contains("SECRET"), "{} shows a secret: {}", change.field, change.to);
+            assert!(change.to.contains("••••••"));
+        }
+        assert!(changes[0].to.contains("gpt-4o"));
+        assert!(changes[1].to.contains("X-Gateway-Key"));
+    }
+
+    // ─── Storage Metadata Tests ──────────────────────────────
+
+    use crate::storage::{KeyMeta, content_type_for, detect_content_type, sniff_content_type};
+
+    #[test]
+    fn test_content_type_by_extension() {
+        assert_eq!(content_type_for("vfs:/workspace/notes.md"), Some("text/markdown"));
+        assert_eq!(content_type_for("vfs:/img/Photo.JPG"), Some("image/jpeg"));
+        assert_eq!(content_type_for("vfs:/dir.d/Makefile"), None);
+        assert_eq!(content_type_for("session:abc"), None);
+    }
+
+    #[test]
+    fn test_key_meta_for_value() {
+        let meta = KeyMeta::for_value("vfs:/a.json", b"{}");
+        assert_eq!(meta.size, 2);
+        assert!(meta.modified.is_some());
+        assert_eq!(meta.content_type.as_deref(), Some("application/json"));
+
+        let untimed = KeyMeta::untimed("vfs:/a.json", 2);
+        assert_eq!(untimed.modified, None);
+        assert_eq!(untimed.content_type, meta.content_type);
+    }
+
+    #[test]
+    fn test_sniff_content_type() {
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n\0\0"), Some("image/png"));
+        assert_eq!(sniff_content_type(b"%PDF-1.7"), Some("application/pdf"));
+        assert_eq!(sniff_content_type(b"plain text"), None);
+        // The extension is preferred, the signature fills in without one
+        assert_eq!(detect_content_type("/a.txt", b"%PDF-1.7"), Some("text/plain"));
+        assert_eq!(detect_content_type("/plot", b"\xff\xd8\xff\xe0"), Some("image/jpeg"));
+    }
+
+    // ─── Share Link Tests ────────────────────────────────────
+
+    use crate::share::{SHARE_VERSION, SharedTranscript, decode_base64url, encode_base64url};
+
+    #[test]
+    fn test_shared_transcript_leaves_out_system_prompt_and_usage() {
+        let mut session = Session::new("s1".to_string());
+        let mut answer = Message::assistant("Hi there");
+        answer.meta = Some(MessageMeta {
+            model: Some("deepseek-chat".to_string()),
+            usage: Some(TokenUsage { prompt_tokens: 10, completion_tokens: 2, total_tokens: 12 }),
+            cost_usd: Some(0.01),
+            ..MessageMeta::new()
+        });
+        session.update_messages(vec![Message::system("secret instructions"), Message::user("Hello"), answer]);
+
+        let transcript = SharedTranscript::from_session(&session);
+        assert_eq!(transcript.version, SHARE_VERSION);
+        assert_eq!(transcript.title, "Hello");
+        assert_eq!(transcript.messages.len(), 2);
+        let meta = transcript.messages[1].meta.as_ref().unwrap();
+        assert_eq!(meta.model.as_deref(), Some("deepseek-chat"));
+        assert!(meta.usage.is_none() && meta.cost_usd.is_none() && meta.id.is_empty());
+
+        let json = serde_json::to_vec(&transcript).unwrap();
+        assert!(!String::from_utf8_lossy(&json).contains("secret"));
+        let back = SharedTranscript::from_json(&json).unwrap();
+        assert_eq!(back.messages[0].content.as_text(), "Hello");
+
+        let newer = String::from_utf8(json).unwrap().replace("\"version\":1", "\"version\":99");
+        assert!(SharedTranscript::from_json(newer.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_base64url_round_trip() {
+        assert_eq!(encode_base64url(b""), "");
+        assert_eq!(encode_base64url(b"f"), "Zg");
+        assert_eq!(encode_base64url(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode_base64url(&[0xfb, 0xff]), "-_8");
+        for len in 0..8 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 37 + 200) as u8).collect();
+            assert_eq!(decode_base64url(&encode_base64url(&bytes)).unwrap(), bytes);
+        }
+        assert_eq!(decode_base64url("Zg==").unwrap(), b"f");
+        assert!(decode_base64url("Zm9v+").is_err());
+        assert!(decode_base64url("Zm9vY").is_err());
+    }
+
+    // ─── Crash Recovery Tests ────────────────────────────────
+
+    #[test]
+    fn test_recovered_session_only_replaces_an_older_copy() {
+        let mut stored = Session::new("s1".to_string());
+        stored.update_messages(vec![Message::user("Hi"), Message::assistant("Hello")]);
+        let mut session = stored.clone();
+        session.messages.push(Message::user("Run the tests"));
+        let recovery = SessionRecovery { workspace_id: "default".to_string(), session };
+
+        assert!(recovery.is_newer_than(None));
+        assert!(recovery.is_newer_than(Some(&stored)));
+        stored.messages.push(Message::user("Run the tests"));
+        assert!(!recovery.is_newer_than(Some(&stored)));
+
+        let empty = SessionRecovery { workspace_id: "default".to_string(), session: Session::new("s2".to_string()) };
+        assert!(!empty.is_newer_than(None));
+
+        let json = serde_json::to_string(&recovery).unwrap();
+        let back: SessionRecovery = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.session.messages.len(), 3);
+    }
 }
@@ -8,6 +8,10 @@ pub struct ToolDefinition {
     pub name: String,
     pub description: String,
     pub parameters: ToolParameters,
+    /// Ask providers that support it (OpenAI `strict: true`) to guarantee
+    /// arguments match the schema exactly
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,20 +23,153 @@ pub struct ToolParameters {
     pub required: Vec<String>,
 }
 
+impl ToolParameters {
+    /// An empty object schema
+    pub fn object() -> Self {
+        Self {
+            schema_type: "object".to_string(),
+            properties: serde_json::Map::new(),
+            required: Vec::new(),
+        }
+    }
+
+    /// Add a property, marking it required if asked
+    pub fn property(mut self, name: &str, schema: ParamSchema, required: bool) -> Self {
+        self.properties.insert(
+            name.to_string(),
+            serde_json::to_value(schema).unwrap_or(Value::Null),
+        );
+        if required {
+            self.required.push(name.to_string());
+        }
+        self
+    }
+}
+
+/// JSON Schema for a single tool parameter
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParamSchema {
+    #[serde(rename = "type")]
+    pub schema_type: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description: Option<String>,
+    /// Allowed values
+    #[serde(rename = "enum", skip_serializing_if = "Vec::is_empty", default)]
+    pub enum_values: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub minimum: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub maximum: Option<f64>,
+    /// Element schema for arrays
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub items: Option<Box<ParamSchema>>,
+}
+
+impl ParamSchema {
+    fn typed(schema_type: &str, description: &str) -> Self {
+        Self {
+            schema_type: schema_type.to_string(),
+            description: (!description.is_empty()).then(|| description.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn string(description: &str) -> Self {
+        Self::typed("string", description)
+    }
+
+    pub fn integer(description: &str) -> Self {
+        Self::typed("integer", description)
+    }
+
+    pub fn number(description: &str) -> Self {
+        Self::typed("number", description)
+    }
+
+    pub fn boolean(description: &str) -> Self {
+        Self::typed("boolean", description)
+    }
+
+    pub fn array(items: ParamSchema, description: &str) -> Self {
+        Self {
+            items: Some(Box::new(items)),
+            ..Self::typed("array", description)
+        }
+    }
+
+    /// Restrict to a fixed set of string values
+    pub fn one_of(mut self, values: &[&str]) -> Self {
+        self.enum_values = values.iter().map(|v| Value::from(*v)).collect();
+        self
+    }
+
+    /// Inclusive numeric bounds
+    pub fn range(mut self, min: f64, max: f64) -> Self {
+        self.minimum = Some(min);
+        self.maximum = Some(max);
+        self
+    }
+}
+
 /// Result of executing a tool
 #[derive(Debug, Clone)]
 pub struct ToolResult {
     pub call_id: String,
     pub output: String,
     pub success: bool,
+    /// What the tool reports besides its text, for the UI and for code
+    /// that uses the result
+    pub data: Option<ToolResultData>,
+}
+
+/// Structured details of a tool result. The model sees only the text
+/// output; these travel with it in the tool message's metadata and the
+/// `ToolExecEnd` event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolResultData {
+    /// Media type of the text output, e.g. `text/markdown`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mime_type: Option<String>,
+    /// VFS paths of files the tool wrote or that hold its full output
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub artifacts: Vec<String>,
+    /// VFS paths of images to show with the result, e.g. a saved plot
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub images: Vec<String>,
+    /// Exit code of a shell command
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub exit_code: Option<i32>,
+    /// Machine-readable form of the result
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub json: Option<Value>,
+}
+
+/// How to run a shell command
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecOptions {
+    /// Working directory, or the shell's own when unset
+    pub cwd: Option<String>,
+    /// Variables added to the command's environment
+    pub env: Vec<(String, String)>,
+    pub timeout_ms: Option<u64>,
+}
+
+impl ExecOptions {
+    /// Run in `cwd`
+    pub fn in_dir(cwd: &str) -> Self {
+        Self { cwd: Some(cwd.to_string()), ..Self::default() }
+    }
 }
 
 /// Shell execution result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ExecResult {
     pub stdout: String,
     pub stderr: String,
     pub exit_code: i32,
+    /// Wall-clock time the command took (0 = not measured)
+    #[serde(default)]
+    pub duration_ms: u64,
 }
 
 /// An entry in a virtual directory listing
@@ -41,6 +178,9 @@ pub struct DirEntry {
     pub name: String,
     pub is_dir: bool,
     pub size: u64,
+    /// MIME type of a file, when known
+    #[serde(default)]
+    pub content_type: Option<String>,
 }
 
 /// File metadata
@@ -49,8 +189,19 @@ pub struct FileStat {
     pub size: u64,
     pub is_dir: bool,
     pub modified: Option<String>,
+    /// MIME type of a file, when known
+    #[serde(default)]
+    pub content_type: Option<String>,
 }
 
 /// Handle to a running process, used for cancellation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ExecHandle(pub u64);
+
+/// A shell command still running
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunningExec {
+    pub handle: ExecHandle,
+    pub command: String,
+    pub elapsed_ms: u64,
+}
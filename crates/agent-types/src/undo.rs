@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// A file's contents from before an agent tool changed it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoEntry {
+    /// Position in the journal; later changes have larger numbers
+    pub seq: u64,
+    /// Id of the user message whose turn made the change
+    pub turn_id: String,
+    /// Tool that made the change
+    pub tool: String,
+    pub path: String,
+    /// Contents before the change; `None` if the file did not exist
+    pub previous: Option<Vec<u8>>,
+    pub created_at: String,
+}
+
+impl UndoEntry {
+    pub fn new(
+        seq: u64,
+        turn_id: impl Into<String>,
+        tool: impl Into<String>,
+        path: impl Into<String>,
+        previous: Option<Vec<u8>>,
+    ) -> Self {
+        Self {
+            seq,
+            turn_id: turn_id.into(),
+            tool: tool.into(),
+            path: path.into(),
+            previous,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
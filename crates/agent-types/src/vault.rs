@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::LlmProvider;
+
+/// PBKDF2-SHA256 rounds for new vaults
+pub const VAULT_KDF_ITERATIONS: u32 = 310_000;
+
+/// The key vault as stored: KDF parameters and the AES-256-GCM encrypted
+/// [`VaultKeys`]. Nothing in here reveals the keys without the passphrase.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SealedVault {
+    pub version: u32,
+    pub iterations: u32,
+    pub salt: Vec<u8>,
+    pub iv: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+/// The secrets a vault protects: API keys by provider
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VaultKeys {
+    /// Keyed by [`LlmProvider::label`]
+    pub api_keys: BTreeMap<String, String>,
+}
+
+impl VaultKeys {
+    pub fn get(&self, provider: &LlmProvider) -> Option<&str> {
+        self.api_keys.get(provider.label()).map(String::as_str)
+    }
+
+    /// Store `key` for `provider`; an empty key removes it.
+    /// Returns whether anything changed.
+    pub fn set(&mut self, provider: &LlmProvider, key: &str) -> bool {
+        let label = provider.label().to_string();
+        if key.is_empty() {
+            return self.api_keys.remove(&label).is_some();
+        }
+        self.api_keys.insert(label, key.to_string()).as_deref() != Some(key)
+    }
+}
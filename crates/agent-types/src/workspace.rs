@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Id of the workspace that uses the un-prefixed storage keys
+pub const DEFAULT_WORKSPACE_ID: &str = "default";
+
+/// A named project with its own `/workspace` tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    /// Keep this workspace's sessions apart from the shared session list
+    #[serde(default)]
+    pub separate_sessions: bool,
+}
+
+impl Workspace {
+    pub fn new(name: impl Into<String>, separate_sessions: bool) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.into(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            separate_sessions,
+        }
+    }
+
+    /// The built-in workspace, backed by the original storage layout
+    pub fn default_workspace() -> Self {
+        Self {
+            id: DEFAULT_WORKSPACE_ID.to_string(),
+            name: "Default".to_string(),
+            created_at: String::new(),
+            separate_sessions: false,
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.id == DEFAULT_WORKSPACE_ID
+    }
+}
@@ -67,6 +67,8 @@ fn message_with_tool_calls_serialization() {
                 arguments: r#"{"command":"ls"}"#.to_string(),
             },
         }],
+        reasoning: None,
+        meta: None,
     };
     let json = serde_json::to_string(&msg).unwrap();
     assert!(json.contains("bash"));
@@ -147,6 +149,9 @@ fn worker_command_serialization() {
         id: 42,
         cmd: "echo hello".to_string(),
         timeout_ms: Some(5000),
+        cwd: None,
+        env: Vec::new(),
+        stdin: false,
     };
     let json = serde_json::to_string(&cmd).unwrap();
     assert!(json.contains("ExecBash"));
@@ -182,6 +187,7 @@ fn tool_definition_serialization() {
             properties: serde_json::Map::new(),
             required: vec!["command".to_string()],
         },
+        strict: false,
     };
     let json = serde_json::to_string(&tool).unwrap();
     let deserialized: ToolDefinition = serde_json::from_str(&json).unwrap();
@@ -195,6 +201,7 @@ fn exec_result_fields() {
         stdout: "hello\n".to_string(),
         stderr: String::new(),
         exit_code: 0,
+        duration_ms: 0,
     };
     assert_eq!(result.exit_code, 0);
     assert!(result.stderr.is_empty());
@@ -206,6 +213,7 @@ fn dir_entry_serialization() {
         name: "file.txt".to_string(),
         is_dir: false,
         size: 1024,
+        content_type: None,
     };
     let json = serde_json::to_string(&entry).unwrap();
     let deserialized: DirEntry = serde_json::from_str(&json).unwrap();
@@ -220,6 +228,7 @@ fn file_stat_serialization() {
         size: 2048,
         is_dir: true,
         modified: Some("2026-01-01T00:00:00Z".to_string()),
+        content_type: None,
     };
     let json = serde_json::to_string(&stat).unwrap();
     let deserialized: FileStat = serde_json::from_str(&json).unwrap();
@@ -279,7 +288,7 @@ fn llm_provider_labels() {
 #[wasm_bindgen_test]
 fn llm_provider_all() {
     let all = LlmProvider::all();
-    assert_eq!(all.len(), 5);
+    assert_eq!(all.len(), 7);
     assert!(all.contains(&LlmProvider::DeepSeek));
     assert!(all.contains(&LlmProvider::OpenAI));
 }
@@ -318,6 +327,7 @@ fn session_summary_serialization() {
         title: "Chat about Rust".to_string(),
         updated_at: "2026-01-01T00:00:00Z".to_string(),
         message_count: 5,
+        cost_usd: 0.25,
     };
     let json = serde_json::to_string(&summary).unwrap();
     let deserialized: SessionSummary = serde_json::from_str(&json).unwrap();
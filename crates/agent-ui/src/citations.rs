@@ -0,0 +1,98 @@
+//! Citations in assistant messages.
+//!
+//! Answers can cite their sources with numbered markers (`[1]`) and
+//! reference lines, as in Markdown:
+//!
+//! ```text
+//! The parser lives in the lexer module [1], see also the docs [2].
+//!
+//! [1]: /workspace/src/lexer.rs:42
+//! [2]: https://docs.rs/logos "logos docs"
+//! ```
+//!
+//! The reference lines are taken out of the text and shown as footnotes
+//! linking to the file and line, or the URL. Provider annotations (web
+//! search results) arrive as the same kind of lines, appended by the LLM
+//! adapter.
+
+use crate::panels::chat::FileRef;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CitationTarget {
+    File(FileRef),
+    Url(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub number: usize,
+    pub target: CitationTarget,
+    /// Quoted title after the target, if any
+    pub title: Option<String>,
+}
+
+impl Citation {
+    /// The title, or else the file and line or URL
+    pub fn label(&self) -> String {
+        if let Some(title) = &self.title {
+            return title.clone();
+        }
+        match &self.target {
+            CitationTarget::File(file) => file.label(),
+            CitationTarget::Url(url) => url.clone(),
+        }
+    }
+}
+
+/// Split `text` into its body and the citations defined in it. Lines that
+/// don't parse as a citation stay in the body.
+pub fn split_citations(text: &str) -> (String, Vec<Citation>) {
+    let mut body = Vec::new();
+    let mut citations: Vec<Citation> = Vec::new();
+    for line in text.lines() {
+        match parse_citation(line) {
+            Some(citation) if !citations.iter().any(|c| c.number == citation.number) => {
+                citations.push(citation);
+            }
+            Some(_) => {}
+            None => body.push(line),
+        }
+    }
+    if citations.is_empty() {
+        return (text.to_string(), citations);
+    }
+    citations.sort_by_key(|c| c.number);
+    (body.join("\n").trim_end().to_string(), citations)
+}
+
+/// `[n]: target "title"`, the target a URL or an absolute path with an
+/// optional `:line`
+pub fn parse_citation(line: &str) -> Option<Citation> {
+    let rest = line.trim().strip_prefix('[')?;
+    let (number, rest) = rest.split_once("]:")?;
+    let number = number.parse().ok()?;
+    let rest = rest.trim();
+    let (target, title) = match rest.split_once(char::is_whitespace) {
+        Some((target, title)) => (target, Some(title.trim())),
+        None => (rest, None),
+    };
+    let title = title
+        .map(|t| t.trim_matches(|c| matches!(c, '"' | '\'' | '(' | ')')).trim())
+        .filter(|t| !t.is_empty())
+        .map(String::from);
+    let target = if target.starts_with("https://") || target.starts_with("http://") {
+        CitationTarget::Url(target.to_string())
+    } else if target.starts_with('/') && target.len() > 1 {
+        let file = match target.rsplit_once(':') {
+            Some((path, line)) if !line.is_empty() && line.bytes().all(|b| b.is_ascii_digit()) => FileRef {
+                path: path.to_string(),
+                line: line.parse().ok().filter(|&n| n > 0),
+            },
+            _ => FileRef { path: target.to_string(), line: None },
+        };
+        CitationTarget::File(file)
+    } else {
+        return None;
+    };
+    Some(Citation { number, target, title })
+}
@@ -0,0 +1,111 @@
+//! Mermaid diagrams and display math in assistant messages.
+//!
+//! A ```` ```mermaid ```` fenced block is drawn as a picture instead of
+//! shown as code, and so is a `$$…$$` formula (see [`crate::math`]). The
+//! UI can't draw them itself: one that isn't cached queues its source, the
+//! app has the browser render it and hands back the pixels with
+//! [`Diagrams::finish`], and they become a texture the first time it is
+//! drawn. Until then, or if the source doesn't parse, it is shown as text.
+
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DiagramKind {
+    Mermaid,
+    /// A TeX formula, set as display math
+    Math,
+}
+
+/// A diagram to draw: its kind and source, and whether for a dark theme
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DiagramRequest {
+    pub kind: DiagramKind,
+    pub source: String,
+    pub dark: bool,
+}
+
+pub enum Diagram {
+    /// Queued for the app, or being rendered
+    Loading,
+    /// Rendered as RGBA pixels, not yet a texture
+    Pixels { size: [usize; 2], rgba: Vec<u8> },
+    Shown(egui::TextureHandle),
+    Failed(String),
+}
+
+#[derive(Default)]
+pub struct Diagrams {
+    diagrams: HashMap<DiagramRequest, Diagram>,
+    /// Diagrams queued for the app to render
+    queued: BTreeSet<DiagramRequest>,
+}
+
+impl Diagrams {
+    /// The diagram for `source`, queueing it if it isn't cached
+    pub fn get(&mut self, kind: DiagramKind, source: &str, dark: bool) -> &mut Diagram {
+        let request = DiagramRequest { kind, source: source.to_string(), dark };
+        if !self.diagrams.contains_key(&request) {
+            self.queued.insert(request.clone());
+        }
+        self.diagrams.entry(request).or_insert(Diagram::Loading)
+    }
+
+    /// Diagrams to render. Called by the app each frame.
+    pub fn take_requests(&mut self) -> Vec<DiagramRequest> {
+        std::mem::take(&mut self.queued).into_iter().collect()
+    }
+
+    /// Apply a finished render: the pixels' size and RGBA, or why it failed
+    pub fn finish(&mut self, request: &DiagramRequest, result: Result<([usize; 2], Vec<u8>), String>) {
+        if let Some(diagram) = self.diagrams.get_mut(request) {
+            *diagram = match result {
+                Ok((size, rgba)) => Diagram::Pixels { size, rgba },
+                Err(e) => Diagram::Failed(e),
+            };
+        }
+    }
+}
+
+/// A piece of a message: text, or the source of a mermaid diagram
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Text(String),
+    Mermaid(String),
+}
+
+/// `text` split around its ```` ```mermaid ```` blocks. A block left open
+/// (say, while the answer is still streaming) stays text.
+pub fn split_diagrams(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut prose: Vec<&str> = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() != "```mermaid" {
+            prose.push(line);
+            continue;
+        }
+        let mut source = Vec::new();
+        let mut closed = false;
+        for inner in lines.by_ref() {
+            if inner.trim() == "```" {
+                closed = true;
+                break;
+            }
+            source.push(inner);
+        }
+        if !closed {
+            prose.push(line);
+            prose.extend(source);
+            break;
+        }
+        if !prose.is_empty() {
+            blocks.push(Block::Text(prose.join("\n")));
+            prose.clear();
+        }
+        blocks.push(Block::Mermaid(source.join("\n")));
+    }
+    if !prose.is_empty() {
+        blocks.push(Block::Text(prose.join("\n")));
+    }
+    blocks
+}
@@ -0,0 +1,148 @@
+//! Cached directory listings of the VFS, shared by the panels.
+//!
+//! Like the viewer, the tree never touches the VFS itself. Asking for a
+//! directory that isn't cached (or has gone stale) queues it; the app
+//! lists queued directories and hands the results back with
+//! [`FileTree::finish_list`]. Tool events mark the directories a write may
+//! have changed as stale, so listings are refreshed only where needed.
+
+use std::collections::{BTreeSet, HashMap};
+
+use agent_types::event::AgentEvent;
+use agent_types::tool::DirEntry;
+
+#[derive(Default)]
+struct Listing {
+    entries: Vec<DirEntry>,
+    /// Why the directory couldn't be listed
+    error: Option<String>,
+    stale: bool,
+}
+
+#[derive(Default)]
+pub struct FileTree {
+    listings: HashMap<String, Listing>,
+    /// Directories queued for the app to list
+    queued: BTreeSet<String>,
+    /// Directories the app is listing now
+    loading: BTreeSet<String>,
+    /// Running tool calls that may change files: the path written, or
+    /// `None` when any file may change (shell commands)
+    writes: HashMap<String, Option<String>>,
+}
+
+impl FileTree {
+    /// Cached entries of `dir`, possibly stale. Queues a listing if the
+    /// directory isn't cached or is stale.
+    pub fn entries(&mut self, dir: &str) -> Option<&[DirEntry]> {
+        let dir = normalize(dir);
+        let fresh = self.listings.get(&dir).is_some_and(|l| !l.stale);
+        if !fresh && !self.loading.contains(&dir) {
+            self.queued.insert(dir.clone());
+        }
+        self.listings
+            .get(&dir)
+            .filter(|l| l.error.is_none())
+            .map(|l| l.entries.as_slice())
+    }
+
+    /// Why `dir` couldn't be listed, if its last listing failed
+    pub fn error(&self, dir: &str) -> Option<&str> {
+        self.listings.get(&normalize(dir))?.error.as_deref()
+    }
+
+    /// Directories to list. Called by the app each frame.
+    pub fn take_requests(&mut self) -> Vec<String> {
+        let requests: Vec<String> = std::mem::take(&mut self.queued).into_iter().collect();
+        self.loading.extend(requests.iter().cloned());
+        requests
+    }
+
+    /// Store a finished listing. A failed listing is kept as an error, and
+    /// only retried once the directory is invalidated.
+    pub fn finish_list(&mut self, dir: &str, result: Result<Vec<DirEntry>, String>) {
+        let dir = normalize(dir);
+        self.loading.remove(&dir);
+        let listing = match result {
+            Ok(entries) => Listing { entries, error: None, stale: false },
+            Err(e) => {
+                log::debug!("Listing {} failed: {}", dir, e);
+                Listing { entries: Vec::new(), error: Some(e), stale: false }
+            }
+        };
+        self.listings.insert(dir, listing);
+    }
+
+    /// `path` was written or deleted: its directory (and its own listing,
+    /// if it is one) is refreshed next time it is asked for.
+    pub fn invalidate(&mut self, path: &str) {
+        let path = normalize(path);
+        let parent = match path.rfind('/') {
+            Some(0) | None => "/".to_string(),
+            Some(i) => path[..i].to_string(),
+        };
+        for dir in [parent, path] {
+            if let Some(listing) = self.listings.get_mut(&dir) {
+                listing.stale = true;
+            }
+        }
+    }
+
+    /// Anything may have changed, e.g. after a shell command or switching
+    /// workspace.
+    pub fn invalidate_all(&mut self) {
+        for listing in self.listings.values_mut() {
+            listing.stale = true;
+        }
+    }
+
+    /// Follow tool calls and invalidate what they changed once they finish
+    pub fn observe(&mut self, event: &AgentEvent) {
+        match event {
+            AgentEvent::ToolExecStart { call_id, tool_name, arguments } => {
+                let write = match tool_name.as_str() {
+                    "write_file" => serde_json::from_str::<serde_json::Value>(arguments)
+                        .ok()
+                        .and_then(|args| args["path"].as_str().map(String::from)),
+                    "bash" | "run_script" | "replace_in_files" => None,
+                    _ => return,
+                };
+                self.writes.insert(call_id.clone(), write);
+            }
+            AgentEvent::ToolExecEnd { call_id, .. } => match self.writes.remove(call_id) {
+                Some(Some(path)) => self.invalidate(&path),
+                Some(None) => self.invalidate_all(),
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Paths completing `partial` from cached listings, directories with a
+    /// trailing `/`. Queues the directory if it isn't cached yet.
+    pub fn complete(&mut self, partial: &str) -> Vec<String> {
+        let (dir, prefix) = match partial.rfind('/') {
+            Some(i) => (&partial[..=i], &partial[i + 1..]),
+            None => ("/", partial),
+        };
+        let Some(entries) = self.entries(dir) else {
+            return Vec::new();
+        };
+        let base = if dir.ends_with('/') { dir.to_string() } else { format!("{}/", dir) };
+        entries
+            .iter()
+            .filter(|e| e.name.starts_with(prefix))
+            .map(|e| format!("{}{}{}", base, e.name, if e.is_dir { "/" } else { "" }))
+            .collect()
+    }
+}
+
+/// `/a/b/` → `/a/b`, `` → `/`
+pub(crate) fn normalize(path: &str) -> String {
+    let trimmed = path.trim().trim_end_matches('/');
+    match trimmed {
+        "" => "/".to_string(),
+        p if p.starts_with('/') => p.to_string(),
+        p => format!("/{}", p),
+    }
+}
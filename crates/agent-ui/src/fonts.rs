@@ -0,0 +1,99 @@
+//! Extra fonts for scripts egui's built-in fonts don't cover.
+//!
+//! The CJK font is several megabytes, so by default it is only fetched once
+//! text that needs it shows up: in agent events, chat input, restored
+//! sessions or files. The app fetches the configured URLs when
+//! [`ExtraFonts::take_request`] says so and reports back with
+//! [`ExtraFonts::finish`]; until then panels can show that glyphs are
+//! still loading.
+
+use agent_types::config::{FontConfig, FontLoading};
+use agent_types::event::AgentEvent;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum FontStatus {
+    /// No text has needed the fonts yet
+    #[default]
+    Unneeded,
+    /// Text needs the fonts; the app hasn't picked up the request
+    Needed,
+    Loading,
+    Loaded,
+    Failed(String),
+}
+
+#[derive(Debug, Default)]
+pub struct ExtraFonts {
+    pub status: FontStatus,
+    loading: FontLoading,
+}
+
+impl ExtraFonts {
+    /// Follow the configured loading mode; `Always` asks for the fonts now
+    pub fn configure(&mut self, config: &FontConfig) {
+        self.loading = config.loading;
+        if self.loading == FontLoading::Always && self.status == FontStatus::Unneeded {
+            self.status = FontStatus::Needed;
+        }
+    }
+
+    /// Ask for the fonts if `text` needs them
+    pub fn observe_text(&mut self, text: &str) {
+        if self.loading == FontLoading::Auto && self.status == FontStatus::Unneeded && needs_cjk_font(text) {
+            self.status = FontStatus::Needed;
+        }
+    }
+
+    /// Look at the text an agent event carries
+    pub fn observe(&mut self, event: &AgentEvent) {
+        match event {
+            AgentEvent::LlmDelta { token: text }
+            | AgentEvent::LlmComplete { text }
+            | AgentEvent::LlmReasoning { text }
+            | AgentEvent::ToolOutput { chunk: text, .. }
+            | AgentEvent::ToolExecEnd { result: text, .. }
+            | AgentEvent::Error { message: text } => self.observe_text(text),
+            AgentEvent::ToolExecStart { arguments, .. } => self.observe_text(arguments),
+            _ => {}
+        }
+    }
+
+    /// Whether the app should fetch the fonts now. Called each frame.
+    pub fn take_request(&mut self) -> bool {
+        if self.status != FontStatus::Needed {
+            return false;
+        }
+        self.status = FontStatus::Loading;
+        true
+    }
+
+    /// The fetch finished. A failure is kept, and not retried.
+    pub fn finish(&mut self, result: Result<(), String>) {
+        self.status = match result {
+            Ok(()) => FontStatus::Loaded,
+            Err(e) => FontStatus::Failed(e),
+        };
+    }
+
+    /// Glyphs are on their way: text may show as boxes until they arrive
+    pub fn is_loading(&self) -> bool {
+        matches!(self.status, FontStatus::Needed | FontStatus::Loading)
+    }
+}
+
+/// Whether `text` has Chinese, Japanese or Korean characters (including
+/// their punctuation and full-width forms)
+pub fn needs_cjk_font(text: &str) -> bool {
+    text.chars().any(|c| {
+        matches!(
+            c as u32,
+            0x1100..=0x11FF
+                | 0x2E80..=0x9FFF
+                | 0xAC00..=0xD7AF
+                | 0xF900..=0xFAFF
+                | 0xFE30..=0xFE4F
+                | 0xFF00..=0xFFEF
+                | 0x20000..=0x3FFFF
+        )
+    })
+}
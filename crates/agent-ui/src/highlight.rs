@@ -0,0 +1,238 @@
+//! Lightweight syntax highlighting for the file viewer.
+//!
+//! A single hand-written tokenizer driven by per-language tables —
+//! comments, strings, numbers and keywords only. Good enough for reading
+//! code without pulling in a grammar engine.
+
+use egui::text::{LayoutJob, TextFormat};
+use egui::{Color32, FontId};
+
+use crate::theme::*;
+
+const KEYWORD: Color32 = Color32::from_rgb(198, 120, 221);
+const STRING: Color32 = Color32::from_rgb(152, 195, 121);
+const NUMBER: Color32 = Color32::from_rgb(209, 154, 102);
+const COMMENT: Color32 = Color32::from_rgb(106, 115, 125);
+
+/// Tokenizer settings for one language
+pub struct Syntax {
+    pub name: &'static str,
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    keywords: &'static [&'static str],
+}
+
+const PLAIN: Syntax = Syntax {
+    name: "text",
+    line_comment: None,
+    block_comment: None,
+    quotes: &[],
+    keywords: &[],
+};
+
+const SYNTAXES: &[(&[&str], Syntax)] = &[
+    (&["rs"], Syntax {
+        name: "rust",
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+    }),
+    (&["py"], Syntax {
+        name: "python",
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+            "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return",
+            "True", "try", "while", "with", "yield",
+        ],
+    }),
+    (&["js", "mjs", "cjs", "ts", "tsx", "jsx"], Syntax {
+        name: "javascript",
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default",
+            "delete", "do", "else", "export", "extends", "false", "finally", "for", "from",
+            "function", "if", "import", "in", "instanceof", "interface", "let", "new", "null",
+            "of", "return", "switch", "this", "throw", "true", "try", "type", "typeof",
+            "undefined", "var", "void", "while", "yield",
+        ],
+    }),
+    (&["json"], Syntax {
+        name: "json",
+        line_comment: None,
+        block_comment: None,
+        quotes: &['"'],
+        keywords: &["true", "false", "null"],
+    }),
+    (&["toml", "ini", "cfg"], Syntax {
+        name: "toml",
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        keywords: &["true", "false"],
+    }),
+    (&["yaml", "yml"], Syntax {
+        name: "yaml",
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        keywords: &["true", "false", "null"],
+    }),
+    (&["sh", "bash"], Syntax {
+        name: "shell",
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        keywords: &[
+            "case", "do", "done", "echo", "elif", "else", "esac", "export", "fi", "for",
+            "function", "if", "in", "local", "return", "then", "while",
+        ],
+    }),
+    (&["html", "htm", "xml", "svg"], Syntax {
+        name: "html",
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+        quotes: &['"', '\''],
+        keywords: &[],
+    }),
+    (&["css"], Syntax {
+        name: "css",
+        line_comment: None,
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        keywords: &["important"],
+    }),
+    (&["c", "h", "cpp", "hpp", "cc", "go", "java"], Syntax {
+        name: "c-like",
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        keywords: &[
+            "break", "case", "class", "const", "continue", "default", "else", "enum", "for",
+            "func", "if", "import", "int", "package", "return", "static", "struct", "switch",
+            "void", "while",
+        ],
+    }),
+];
+
+/// Pick a syntax from the file extension; unknown types are plain text
+pub fn syntax_for(path: &str) -> &'static Syntax {
+    let ext = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    SYNTAXES
+        .iter()
+        .find(|(exts, _)| exts.contains(&ext.as_str()))
+        .map(|(_, syntax)| syntax)
+        .unwrap_or(&PLAIN)
+}
+
+/// Token classes produced by [`tokenize`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+}
+
+/// Split `text` into (class, byte range) spans covering the whole input
+pub fn tokenize(text: &str, syntax: &Syntax) -> Vec<(Token, std::ops::Range<usize>)> {
+    let mut spans: Vec<(Token, std::ops::Range<usize>)> = Vec::new();
+    let mut push = |token: Token, range: std::ops::Range<usize>| {
+        // Merge neighbouring spans of the same class to keep jobs small
+        if let Some((last, prev)) = spans.last_mut() {
+            if *last == token && prev.end == range.start {
+                prev.end = range.end;
+                return;
+            }
+        }
+        spans.push((token, range));
+    };
+
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap_or(' ');
+
+        if syntax.line_comment.is_some_and(|m| rest.starts_with(m)) {
+            let end = rest.find('\n').map_or(text.len(), |n| i + n);
+            push(Token::Comment, i..end);
+            i = end;
+        } else if let Some((open, close)) = syntax.block_comment.filter(|(o, _)| rest.starts_with(o)) {
+            let end = rest[open.len()..]
+                .find(close)
+                .map_or(text.len(), |n| i + open.len() + n + close.len());
+            push(Token::Comment, i..end);
+            i = end;
+        } else if syntax.quotes.contains(&c) {
+            let mut end = i + c.len_utf8();
+            let mut escaped = false;
+            for ch in text[end..].chars() {
+                end += ch.len_utf8();
+                if escaped {
+                    escaped = false;
+                } else if ch == '\\' {
+                    escaped = true;
+                } else if ch == c || (ch == '\n' && c != '`') {
+                    break;
+                }
+            }
+            push(Token::String, i..end);
+            i = end;
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '.' || ch == '_'))
+                .unwrap_or(rest.len());
+            push(Token::Number, i..i + len);
+            i += len;
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            let token = if syntax.keywords.contains(&word) {
+                Token::Keyword
+            } else {
+                Token::Plain
+            };
+            push(token, i..i + len);
+            i += len;
+        } else {
+            push(Token::Plain, i..i + c.len_utf8());
+            i += c.len_utf8();
+        }
+    }
+    spans
+}
+
+/// Highlighted, non-wrapping layout of `text`
+pub fn highlight(text: &str, syntax: &Syntax, font: FontId) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    for (token, range) in tokenize(text, syntax) {
+        let color = match token {
+            Token::Plain => TEXT_PRIMARY.color(),
+            Token::Keyword => KEYWORD,
+            Token::String => STRING,
+            Token::Number => NUMBER,
+            Token::Comment => COMMENT,
+        };
+        job.append(&text[range], 0.0, TextFormat::simple(font.clone(), color));
+    }
+    job.wrap.max_width = f32::INFINITY;
+    job
+}
@@ -0,0 +1,75 @@
+//! Images tools produced, shown under their results in the chat.
+//!
+//! Like the viewer, the chat never touches the VFS itself. Drawing an image
+//! that isn't cached queues its path; the app reads queued images and
+//! hands the bytes back with [`ChatImages::finish`]. Each is decoded to a
+//! texture the first time it is drawn. A `ToolArtifact` event for a cached
+//! path means the file was written again, so it is read afresh.
+
+use std::collections::{BTreeSet, HashMap};
+
+use agent_types::event::AgentEvent;
+
+/// Images larger than this are not shown inline
+pub const MAX_CHAT_IMAGE_BYTES: u64 = 8 * 1024 * 1024;
+
+pub enum ChatImage {
+    /// Queued for the app, or being read
+    Loading,
+    /// Read, not yet decoded
+    Bytes(Vec<u8>),
+    Shown(egui::TextureHandle),
+    Failed(String),
+}
+
+#[derive(Default)]
+pub struct ChatImages {
+    images: HashMap<String, ChatImage>,
+    /// Paths queued for the app to read
+    queued: BTreeSet<String>,
+}
+
+impl ChatImages {
+    /// The image at `path`, queueing it if it isn't cached
+    pub fn get(&mut self, path: &str) -> &mut ChatImage {
+        if !self.images.contains_key(path) {
+            self.queued.insert(path.to_string());
+        }
+        self.images.entry(path.to_string()).or_insert(ChatImage::Loading)
+    }
+
+    /// Paths to read, in order. Called by the app each frame.
+    pub fn take_requests(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.queued).into_iter().collect()
+    }
+
+    /// Apply a finished read. Results for images no longer cached are dropped.
+    pub fn finish(&mut self, path: &str, result: Result<Vec<u8>, String>) {
+        if let Some(image) = self.images.get_mut(path) {
+            *image = match result {
+                Ok(bytes) => ChatImage::Bytes(bytes),
+                Err(e) => ChatImage::Failed(e),
+            };
+        }
+    }
+
+    /// Forget cached images that were written again
+    pub fn observe(&mut self, event: &AgentEvent) {
+        if let AgentEvent::ToolArtifact { path, .. } = event {
+            self.images.remove(path);
+        }
+    }
+}
+
+/// Decode `bytes` into a texture named after `path`
+pub fn decode(ctx: &egui::Context, path: &str, bytes: &[u8]) -> ChatImage {
+    match image::load_from_memory(bytes) {
+        Ok(img) => {
+            let rgba = img.to_rgba8();
+            let size = [rgba.width() as usize, rgba.height() as usize];
+            let color = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw());
+            ChatImage::Shown(ctx.load_texture(path, color, Default::default()))
+        }
+        Err(e) => ChatImage::Failed(format!("Couldn't decode image: {}", e)),
+    }
+}
@@ -1,6 +1,15 @@
+pub mod citations;
+pub mod diagrams;
+pub mod file_tree;
+pub mod fonts;
+pub mod highlight;
+pub mod images;
+pub mod math;
 pub mod panels;
 pub mod state;
+pub mod tables;
 pub mod theme;
+pub mod widgets;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,365 @@
+//! TeX math in assistant messages.
+//!
+//! Display math (`$$…$$` or `\[…\]` starting a line, possibly over several
+//! lines) is set by MathJax in the browser and shown as a picture, like a
+//! mermaid diagram (see [`crate::diagrams`]). Inline math (`$…$` or
+//! `\(…\)`) stays in its line of text, converted to Unicode: Greek letters
+//! and symbols, super- and subscripts where Unicode has them, `\frac{a}{b}`
+//! as `a/b`, `\sqrt{x}` as `√x`.
+//!
+//! A `$` only opens math when followed by a non-space, and only closes it
+//! when preceded by one and not followed by a digit, so prices like
+//! "$5 and $10" stay text. Code spans and fenced code are left alone.
+
+/// A piece of a message: text, or a display formula's TeX
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathBlock {
+    Text(String),
+    Display(String),
+}
+
+/// `text` split around its display formulas. A formula left open (say,
+/// while the answer is still streaming) stays text.
+pub fn split_display_math(text: &str) -> Vec<MathBlock> {
+    let mut blocks = Vec::new();
+    let mut prose: Vec<String> = Vec::new();
+    let mut lines = text.lines();
+    let mut in_fence = false;
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        }
+        let close = match () {
+            _ if in_fence => None,
+            _ if trimmed.starts_with("$$") => Some("$$"),
+            _ if trimmed.starts_with("\\[") => Some("\\]"),
+            _ => None,
+        };
+        let Some(close) = close else {
+            prose.push(line.to_string());
+            continue;
+        };
+        // The formula may close on its first line or a later one
+        let mut tex = trimmed[2..].to_string();
+        let mut taken = vec![line];
+        let mut rest = None;
+        loop {
+            if let Some(end) = tex.find(close) {
+                rest = Some(tex[end + 2..].trim().to_string());
+                tex.truncate(end);
+                break;
+            }
+            match lines.next() {
+                Some(next) => {
+                    taken.push(next);
+                    tex.push('\n');
+                    tex.push_str(next);
+                }
+                None => break,
+            }
+        }
+        let Some(rest) = rest else {
+            prose.extend(taken.into_iter().map(String::from));
+            break;
+        };
+        if !prose.is_empty() {
+            blocks.push(MathBlock::Text(prose.join("\n")));
+            prose.clear();
+        }
+        blocks.push(MathBlock::Display(tex.trim().to_string()));
+        if !rest.is_empty() {
+            prose.push(rest);
+        }
+    }
+    if !prose.is_empty() {
+        blocks.push(MathBlock::Text(prose.join("\n")));
+    }
+    blocks
+}
+
+/// `text` with its inline formulas converted to Unicode
+pub fn inline_math(text: &str) -> String {
+    if !text.contains('$') && !text.contains("\\(") {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut in_fence = false;
+    for (n, line) in text.split('\n').enumerate() {
+        if n > 0 {
+            out.push('\n');
+        }
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if in_fence || line.trim_start().starts_with("```") {
+            out.push_str(line);
+        } else {
+            inline_line(line, &mut out);
+        }
+    }
+    out
+}
+
+fn inline_line(line: &str, out: &mut String) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            // A code span, copied as it is
+            '`' => {
+                let end = chars[i + 1..].iter().position(|&c| c == '`').map(|p| i + 1 + p);
+                let end = end.unwrap_or(chars.len() - 1);
+                out.extend(&chars[i..=end]);
+                i = end + 1;
+            }
+            '\\' if chars.get(i + 1) == Some(&'$') => {
+                out.push('$');
+                i += 2;
+            }
+            '\\' if chars.get(i + 1) == Some(&'(') => {
+                let close = (i + 2..chars.len().saturating_sub(1)).find(|&j| chars[j] == '\\' && chars[j + 1] == ')');
+                match close {
+                    Some(j) => {
+                        out.push_str(&tex_to_unicode(&chars[i + 2..j].iter().collect::<String>()));
+                        i = j + 2;
+                    }
+                    None => {
+                        out.push('\\');
+                        i += 1;
+                    }
+                }
+            }
+            '$' if opens_math(&chars, i) => match closing_dollar(&chars, i + 1) {
+                Some(j) => {
+                    out.push_str(&tex_to_unicode(&chars[i + 1..j].iter().collect::<String>()));
+                    i = j + 1;
+                }
+                None => {
+                    out.push('$');
+                    i += 1;
+                }
+            },
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn opens_math(chars: &[char], i: usize) -> bool {
+    chars.get(i + 1).is_some_and(|c| !c.is_whitespace() && *c != '$') && (i == 0 || chars[i - 1] != '$')
+}
+
+fn closing_dollar(chars: &[char], from: usize) -> Option<usize> {
+    (from..chars.len()).find(|&j| {
+        chars[j] == '$'
+            && chars[j - 1] != '\\'
+            && !chars[j - 1].is_whitespace()
+            && !chars.get(j + 1).is_some_and(|c| c.is_ascii_digit())
+    })
+}
+
+/// TeX as plain Unicode text, e.g. `\alpha^2 + \frac{1}{n}` → `α² + 1/n`
+pub fn tex_to_unicode(tex: &str) -> String {
+    let chars: Vec<char> = tex.chars().collect();
+    let mut i = 0;
+    let out = convert(&chars, &mut i, false);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Convert from `i` to the end, or to the `}` closing a group
+fn convert(chars: &[char], i: &mut usize, in_group: bool) -> String {
+    let mut out = String::new();
+    while *i < chars.len() {
+        let c = chars[*i];
+        *i += 1;
+        match c {
+            '}' if in_group => return out,
+            '{' => out.push_str(&convert(chars, i, true)),
+            '^' => out.push_str(&script(&argument(chars, i), SUPERSCRIPTS, '^')),
+            '_' => out.push_str(&script(&argument(chars, i), SUBSCRIPTS, '_')),
+            '~' => out.push(' '),
+            '\\' => out.push_str(&command(chars, i)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The next argument: a `{…}` group, a command, or a single character
+fn argument(chars: &[char], i: &mut usize) -> String {
+    while chars.get(*i).is_some_and(|c| c.is_whitespace()) {
+        *i += 1;
+    }
+    match chars.get(*i) {
+        Some('{') => {
+            *i += 1;
+            convert(chars, i, true)
+        }
+        Some('\\') => {
+            *i += 1;
+            command(chars, i)
+        }
+        Some(&c) => {
+            *i += 1;
+            c.to_string()
+        }
+        None => String::new(),
+    }
+}
+
+/// A command after its backslash, with its arguments
+fn command(chars: &[char], i: &mut usize) -> String {
+    let start = *i;
+    while chars.get(*i).is_some_and(|c| c.is_ascii_alphabetic()) {
+        *i += 1;
+    }
+    if *i == start {
+        // A one-character command: escaped symbols and spacing
+        let Some(&c) = chars.get(*i) else {
+            return "\\".to_string();
+        };
+        *i += 1;
+        return match c {
+            ',' | ':' | ';' | ' ' => " ".to_string(),
+            '!' => String::new(),
+            '\\' => "; ".to_string(),
+            c => c.to_string(),
+        };
+    }
+    let name: String = chars[start..*i].iter().collect();
+    match name.as_str() {
+        "frac" | "dfrac" | "tfrac" => {
+            let numerator = argument(chars, i);
+            let denominator = argument(chars, i);
+            format!("{}/{}", wrap(&numerator), wrap(&denominator))
+        }
+        "sqrt" => {
+            let mut root = "√";
+            if chars.get(*i) == Some(&'[') {
+                let end = chars[*i..].iter().position(|&c| c == ']').map_or(chars.len(), |p| *i + p);
+                root = match chars[*i + 1..end].iter().collect::<String>().trim() {
+                    "3" => "∛",
+                    "4" => "∜",
+                    _ => "√",
+                };
+                *i = (end + 1).min(chars.len());
+            }
+            format!("{}{}", root, wrap(&argument(chars, i)))
+        }
+        "text" | "textrm" | "textbf" | "textit" | "mathrm" | "mathbf" | "mathit" | "mathsf" | "mathtt"
+        | "mathcal" | "mathbb" | "boldsymbol" | "operatorname" | "mbox" => {
+            let text = argument(chars, i);
+            if name == "mathbb" { double_struck(&text) } else { text }
+        }
+        "left" | "right" | "bigl" | "bigr" | "Bigl" | "Bigr" | "big" | "Big" | "bigg" | "Bigg" => {
+            // `\left.` is an invisible delimiter
+            if chars.get(*i) == Some(&'.') {
+                *i += 1;
+            }
+            String::new()
+        }
+        "displaystyle" | "textstyle" | "limits" | "nolimits" => String::new(),
+        "quad" | "qquad" => " ".to_string(),
+        name => match SYMBOLS.iter().find(|(n, _)| *n == name) {
+            Some((_, symbol)) => symbol.to_string(),
+            // Upright, with a space before an argument like `x` or `\theta`
+            None if FUNCTIONS.contains(&name) => match chars.get(*i) {
+                Some(c) if c.is_alphanumeric() || *c == '\\' => format!("{} ", name),
+                _ => name.to_string(),
+            },
+            // Unknown commands are kept as written
+            None => match chars.get(*i) {
+                Some(c) if c.is_alphanumeric() || *c == '{' => format!("\\{} ", name),
+                _ => format!("\\{}", name),
+            },
+        },
+    }
+}
+
+/// `text` as it reads after `/` or `√`: alone if a single term, else in
+/// parentheses
+fn wrap(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= 1 || text.chars().all(|c| c.is_alphanumeric() || c == '.') {
+        text.to_string()
+    } else {
+        format!("({})", text)
+    }
+}
+
+/// `text` as super- or subscript characters if Unicode has them all, else
+/// after `mark`, in parentheses if longer than a character
+fn script(text: &str, table: &[(char, char)], mark: char) -> String {
+    let mapped: Option<String> = text.chars().map(|c| table.iter().find(|(from, _)| *from == c).map(|(_, to)| *to)).collect();
+    match mapped {
+        Some(mapped) if !mapped.is_empty() => mapped,
+        _ if text.chars().count() == 1 => format!("{}{}", mark, text),
+        _ => format!("{}({})", mark, text),
+    }
+}
+
+fn double_struck(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'R' => 'ℝ',
+            'N' => 'ℕ',
+            'Z' => 'ℤ',
+            'Q' => 'ℚ',
+            'C' => 'ℂ',
+            c => c,
+        })
+        .collect()
+}
+
+const SUPERSCRIPTS: &[(char, char)] = &[
+    ('0', '⁰'), ('1', '¹'), ('2', '²'), ('3', '³'), ('4', '⁴'), ('5', '⁵'), ('6', '⁶'), ('7', '⁷'),
+    ('8', '⁸'), ('9', '⁹'), ('+', '⁺'), ('-', '⁻'), ('−', '⁻'), ('=', '⁼'), ('(', '⁽'), (')', '⁾'),
+    ('a', 'ᵃ'), ('b', 'ᵇ'), ('c', 'ᶜ'), ('d', 'ᵈ'), ('e', 'ᵉ'), ('f', 'ᶠ'), ('g', 'ᵍ'), ('h', 'ʰ'),
+    ('i', 'ⁱ'), ('j', 'ʲ'), ('k', 'ᵏ'), ('l', 'ˡ'), ('m', 'ᵐ'), ('n', 'ⁿ'), ('o', 'ᵒ'), ('p', 'ᵖ'),
+    ('r', 'ʳ'), ('s', 'ˢ'), ('t', 'ᵗ'), ('u', 'ᵘ'), ('v', 'ᵛ'), ('w', 'ʷ'), ('x', 'ˣ'), ('y', 'ʸ'),
+    ('z', 'ᶻ'), ('T', 'ᵀ'), ('′', '′'), ('*', '*'),
+];
+
+const SUBSCRIPTS: &[(char, char)] = &[
+    ('0', '₀'), ('1', '₁'), ('2', '₂'), ('3', '₃'), ('4', '₄'), ('5', '₅'), ('6', '₆'), ('7', '₇'),
+    ('8', '₈'), ('9', '₉'), ('+', '₊'), ('-', '₋'), ('−', '₋'), ('=', '₌'), ('(', '₍'), (')', '₎'),
+    ('a', 'ₐ'), ('e', 'ₑ'), ('h', 'ₕ'), ('i', 'ᵢ'), ('j', 'ⱼ'), ('k', 'ₖ'), ('l', 'ₗ'), ('m', 'ₘ'),
+    ('n', 'ₙ'), ('o', 'ₒ'), ('p', 'ₚ'), ('r', 'ᵣ'), ('s', 'ₛ'), ('t', 'ₜ'), ('u', 'ᵤ'), ('v', 'ᵥ'),
+    ('x', 'ₓ'),
+];
+
+/// Functions set upright
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh", "log",
+    "ln", "lg", "exp", "lim", "max", "min", "sup", "inf", "det", "gcd", "arg", "deg", "dim", "ker", "Pr",
+];
+
+const SYMBOLS: &[(&str, &str)] = &[
+    ("alpha", "α"), ("beta", "β"), ("gamma", "γ"), ("delta", "δ"), ("epsilon", "ε"), ("varepsilon", "ε"),
+    ("zeta", "ζ"), ("eta", "η"), ("theta", "θ"), ("vartheta", "ϑ"), ("iota", "ι"), ("kappa", "κ"),
+    ("lambda", "λ"), ("mu", "μ"), ("nu", "ν"), ("xi", "ξ"), ("pi", "π"), ("varpi", "ϖ"), ("rho", "ρ"),
+    ("varrho", "ϱ"), ("sigma", "σ"), ("varsigma", "ς"), ("tau", "τ"), ("upsilon", "υ"), ("phi", "φ"),
+    ("varphi", "φ"), ("chi", "χ"), ("psi", "ψ"), ("omega", "ω"), ("Gamma", "Γ"), ("Delta", "Δ"),
+    ("Theta", "Θ"), ("Lambda", "Λ"), ("Xi", "Ξ"), ("Pi", "Π"), ("Sigma", "Σ"), ("Upsilon", "Υ"),
+    ("Phi", "Φ"), ("Psi", "Ψ"), ("Omega", "Ω"),
+    ("times", "×"), ("cdot", "·"), ("div", "÷"), ("pm", "±"), ("mp", "∓"), ("ast", "∗"), ("star", "⋆"),
+    ("circ", "∘"), ("bullet", "•"), ("leq", "≤"), ("le", "≤"), ("geq", "≥"), ("ge", "≥"), ("neq", "≠"),
+    ("ne", "≠"), ("approx", "≈"), ("sim", "∼"), ("simeq", "≃"), ("cong", "≅"), ("equiv", "≡"),
+    ("propto", "∝"), ("ll", "≪"), ("gg", "≫"), ("infty", "∞"), ("partial", "∂"), ("nabla", "∇"),
+    ("sum", "∑"), ("prod", "∏"), ("coprod", "∐"), ("int", "∫"), ("iint", "∬"), ("oint", "∮"),
+    ("to", "→"), ("rightarrow", "→"), ("leftarrow", "←"), ("gets", "←"), ("leftrightarrow", "↔"),
+    ("Rightarrow", "⇒"), ("implies", "⇒"), ("Leftarrow", "⇐"), ("Leftrightarrow", "⇔"), ("iff", "⇔"),
+    ("mapsto", "↦"), ("uparrow", "↑"), ("downarrow", "↓"), ("in", "∈"), ("notin", "∉"), ("ni", "∋"),
+    ("subset", "⊂"), ("subseteq", "⊆"), ("supset", "⊃"), ("supseteq", "⊇"), ("cup", "∪"), ("cap", "∩"),
+    ("setminus", "∖"), ("emptyset", "∅"), ("varnothing", "∅"), ("forall", "∀"), ("exists", "∃"),
+    ("neg", "¬"), ("lnot", "¬"), ("land", "∧"), ("wedge", "∧"), ("lor", "∨"), ("vee", "∨"),
+    ("oplus", "⊕"), ("otimes", "⊗"), ("perp", "⊥"), ("parallel", "∥"), ("angle", "∠"), ("degree", "°"),
+    ("prime", "′"), ("ldots", "…"), ("dots", "…"), ("cdots", "⋯"), ("vdots", "⋮"), ("ddots", "⋱"),
+    ("hbar", "ℏ"), ("ell", "ℓ"), ("Re", "ℜ"), ("Im", "ℑ"), ("aleph", "ℵ"), ("langle", "⟨"),
+    ("rangle", "⟩"), ("lfloor", "⌊"), ("rfloor", "⌋"), ("lceil", "⌈"), ("rceil", "⌉"), ("mid", "|"),
+    ("vert", "|"), ("Vert", "‖"), ("lvert", "|"), ("rvert", "|"), ("lVert", "‖"), ("rVert", "‖"),
+    ("colon", ":"), ("cdotp", "·"),
+];
@@ -0,0 +1,261 @@
+//! Batch window — prompts lined up to run one after another as separate
+//! turns, e.g. the same refactor for each of a dozen files.
+//!
+//! Prompts are typed in (one per line, or separated by `---` lines) or
+//! loaded from a VFS file. Running is the app's job: it asks [`BatchPanel::next`]
+//! for a prompt whenever the agent is idle, and reports each turn back with
+//! [`BatchPanel::finish`]. Pausing lets the running turn finish and holds
+//! the rest; a failed turn is recorded and the batch moves on.
+
+use egui::{self, RichText};
+use agent_core::batch::parse_batch;
+
+use crate::theme::*;
+use crate::widgets::name_field;
+
+/// Prompts file used unless the user picks another
+pub const DEFAULT_BATCH_PATH: &str = "/workspace/prompts.txt";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemStatus {
+    Pending,
+    Running,
+    Done,
+    Failed(String),
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchItem {
+    pub prompt: String,
+    pub status: ItemStatus,
+}
+
+pub struct BatchPanel {
+    pub open: bool,
+    pub items: Vec<BatchItem>,
+    /// Started and not paused
+    pub running: bool,
+    /// Prompts being typed in, not yet added
+    pub draft: String,
+    /// VFS path of a prompts file
+    pub file_path: String,
+    pub load_requested: bool,
+    pub loading: bool,
+    /// Why the prompts file couldn't be loaded
+    pub error: Option<String>,
+    /// Outcome of the last finished batch
+    pub summary: Option<String>,
+}
+
+impl Default for BatchPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            items: Vec::new(),
+            running: false,
+            draft: String::new(),
+            file_path: DEFAULT_BATCH_PATH.to_string(),
+            load_requested: false,
+            loading: false,
+            error: None,
+            summary: None,
+        }
+    }
+}
+
+impl BatchPanel {
+    pub fn add(&mut self, prompts: Vec<String>) {
+        self.items.extend(prompts.into_iter().map(|prompt| BatchItem { prompt, status: ItemStatus::Pending }));
+        self.summary = None;
+    }
+
+    /// Add the prompts typed into the draft
+    pub fn add_draft(&mut self) {
+        let prompts = parse_batch(&self.draft);
+        if !prompts.is_empty() {
+            self.add(prompts);
+            self.draft.clear();
+        }
+    }
+
+    fn pending(&self) -> usize {
+        self.items.iter().filter(|item| item.status == ItemStatus::Pending).count()
+    }
+
+    /// A prompt's turn is in flight
+    pub fn in_flight(&self) -> bool {
+        self.items.iter().any(|item| item.status == ItemStatus::Running)
+    }
+
+    /// The next prompt to run, marked as running. Nothing while paused or
+    /// while the previous prompt's turn is in flight.
+    pub fn take_next(&mut self) -> Option<String> {
+        if !self.running || self.in_flight() {
+            return None;
+        }
+        let item = self.items.iter_mut().find(|item| item.status == ItemStatus::Pending)?;
+        item.status = ItemStatus::Running;
+        Some(item.prompt.clone())
+    }
+
+    /// The running prompt's turn ended. Returns the summary once the last
+    /// prompt is done.
+    pub fn finish(&mut self, result: Result<(), String>) -> Option<String> {
+        let item = self.items.iter_mut().find(|item| item.status == ItemStatus::Running)?;
+        item.status = match result {
+            Ok(()) => ItemStatus::Done,
+            Err(e) => ItemStatus::Failed(e),
+        };
+        self.complete()
+    }
+
+    /// Leave out a prompt that hasn't run yet. Returns the summary if that
+    /// was the last one.
+    pub fn skip(&mut self, index: usize) -> Option<String> {
+        let item = self.items.get_mut(index).filter(|item| item.status == ItemStatus::Pending)?;
+        item.status = ItemStatus::Skipped;
+        self.complete()
+    }
+
+    /// Stop and sum up a started batch with nothing left to run
+    fn complete(&mut self) -> Option<String> {
+        if !self.running || self.in_flight() || self.pending() > 0 {
+            return None;
+        }
+        self.running = false;
+        let summary = self.summarize();
+        self.summary = Some(summary.clone());
+        Some(summary)
+    }
+
+    /// Drop every prompt except one still running
+    pub fn clear(&mut self) {
+        self.items.retain(|item| item.status == ItemStatus::Running);
+        self.running = false;
+        self.summary = None;
+    }
+
+    /// e.g. "Batch of 12 prompts: 10 done, 1 failed, 1 skipped"
+    pub fn summarize(&self) -> String {
+        let count = |wanted: fn(&ItemStatus) -> bool| self.items.iter().filter(|item| wanted(&item.status)).count();
+        let mut parts = vec![format!("{} done", count(|s| *s == ItemStatus::Done))];
+        let failed = count(|s| matches!(s, ItemStatus::Failed(_)));
+        if failed > 0 {
+            parts.push(format!("{} failed", failed));
+        }
+        let skipped = count(|s| *s == ItemStatus::Skipped);
+        if skipped > 0 {
+            parts.push(format!("{} skipped", skipped));
+        }
+        format!("Batch of {} prompts: {}", self.items.len(), parts.join(", "))
+    }
+}
+
+fn status_label(status: &ItemStatus) -> RichText {
+    match status {
+        ItemStatus::Pending => RichText::new("○").color(TEXT_SECONDARY),
+        ItemStatus::Running => RichText::new("▶").color(ACCENT),
+        ItemStatus::Done => RichText::new("✔").color(SUCCESS),
+        ItemStatus::Failed(_) => RichText::new("✖").color(ERROR),
+        ItemStatus::Skipped => RichText::new("–").color(TEXT_SECONDARY),
+    }
+}
+
+/// The batch window, while open. Returns the summary when skipping a
+/// prompt ends the batch.
+pub fn batch_window(ctx: &egui::Context, panel: &mut BatchPanel) -> Option<String> {
+    let mut open = panel.open;
+    let mut finished = None;
+    egui::Window::new("Batch")
+        .open(&mut open)
+        .default_width(420.0)
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.label(
+                RichText::new("Prompts run one after another as separate turns. One per line, or separated by --- lines.")
+                    .color(TEXT_SECONDARY)
+                    .small(),
+            );
+            let field = ui.add(
+                egui::TextEdit::multiline(&mut panel.draft)
+                    .desired_rows(3)
+                    .desired_width(f32::INFINITY)
+                    .hint_text("Apply the refactor to src/a.rs\nApply the refactor to src/b.rs"),
+            );
+            name_field(&field, "Batch prompts");
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!panel.draft.trim().is_empty(), egui::Button::new("Add")).clicked() {
+                    panel.add_draft();
+                }
+                ui.separator();
+                let field = ui.add(
+                    egui::TextEdit::singleline(&mut panel.file_path)
+                        .desired_width(180.0)
+                        .hint_text(DEFAULT_BATCH_PATH),
+                );
+                name_field(&field, "Prompts file path");
+                let ready = !panel.loading && !panel.file_path.trim().is_empty();
+                if ui.add_enabled(ready, egui::Button::new("Load file")).clicked() {
+                    panel.load_requested = true;
+                }
+                if panel.loading {
+                    ui.spinner();
+                }
+            });
+            if let Some(error) = &panel.error {
+                ui.label(RichText::new(error).color(ERROR).small());
+            }
+            ui.separator();
+
+            if panel.items.is_empty() {
+                ui.label(RichText::new("No prompts queued.").color(TEXT_SECONDARY));
+                return;
+            }
+            ui.horizontal(|ui| {
+                let pending = panel.pending() > 0;
+                if panel.running {
+                    if ui.button("⏸ Pause").on_hover_text("Hold the rest once the running prompt is done").clicked() {
+                        panel.running = false;
+                    }
+                } else if ui.add_enabled(pending, egui::Button::new("▶ Run")).clicked() {
+                    panel.running = true;
+                    panel.summary = None;
+                }
+                if ui.button("Clear").clicked() {
+                    panel.clear();
+                }
+                ui.label(
+                    RichText::new(format!("{} of {} left", panel.pending(), panel.items.len()))
+                        .color(TEXT_SECONDARY)
+                        .small(),
+                );
+            });
+            if let Some(summary) = &panel.summary {
+                ui.label(RichText::new(summary).color(TEXT_PRIMARY).small());
+            }
+
+            let mut skipped = None;
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for (i, item) in panel.items.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let icon = ui.label(status_label(&item.status));
+                        if let ItemStatus::Failed(error) = &item.status {
+                            icon.on_hover_text(error);
+                        }
+                        let first_line = item.prompt.lines().next().unwrap_or_default();
+                        ui.label(RichText::new(first_line).color(TEXT_PRIMARY).small())
+                            .on_hover_text(&item.prompt);
+                        if item.status == ItemStatus::Pending && ui.small_button("Skip").clicked() {
+                            skipped = Some(i);
+                        }
+                    });
+                }
+            });
+            if let Some(i) = skipped {
+                finished = panel.skip(i);
+            }
+        });
+    panel.open = open;
+    finished
+}
@@ -1,32 +1,112 @@
 //! Chat panel — displays conversation messages and input field.
+//!
+//! Messages are laid out by the chat display settings: full-width rows in a
+//! centered column or bubbles, comfortable or compact spacing, and the
+//! assistant's answers optionally in monospace.
+//!
+//! An empty session offers suggestion chips picked from what is in the
+//! workspace; clicking one sends its prompt, or puts it in the input to
+//! edit first.
+//!
+//! Citations in answers (`[1]: /workspace/src/lib.rs:42` reference lines)
+//! are shown as numbered footnotes linking to the file or URL.
+//!
+//! VFS files can be attached to a message with the 📎 button or by typing
+//! `@/path` in the input, which completes against the shared file tree.
+//! The runtime shows their contents to the LLM for that turn only.
+//!
+//! A paste longer than the configured limit doesn't go into the input:
+//! the user is offered to save it under `/workspace/pasted/` and send a
+//! short reference to the file instead.
+//!
+//! Messages sent while offline wait in the [`Outbox`], shown as queued,
+//! and go out in order once the provider can be reached.
+//!
+//! The 🔗 button copies a link holding the whole conversation (see
+//! [`Sharing`]); opening one shows it here read-only, without the input.
+//!
+//! Markdown tables in answers and CSV/TSV files read by tools are drawn as
+//! sortable grids (see [`crate::tables`]), mermaid blocks as diagrams
+//! (see [`crate::diagrams`]) and TeX formulas as math (see [`crate::math`]).
+
+use std::collections::VecDeque;
 
 use egui::{self, Align, Color32, Layout, RichText, ScrollArea, Vec2};
-use crate::state::UiState;
+use agent_types::activity::format_duration;
+use agent_types::config::{ChatDensity, ChatDisplayConfig};
+use agent_types::tool::DirEntry;
+use crate::citations::{CitationTarget, split_citations};
+use crate::panels::prompts::{self, path_suggestions};
+use crate::panels::viewer::format_size;
+use crate::highlight::{highlight, syntax_for};
+use crate::diagrams::{Block, Diagram, DiagramKind, Diagrams, split_diagrams};
+use crate::images::{ChatImage, ChatImages, decode};
+use crate::math::{MathBlock, inline_math, split_display_math, tex_to_unicode};
+use crate::tables::{MAX_TABLE_ROWS, Segment, Table, TableExports, delimiter_for, parse_delimited, split_tables};
+use crate::state::{RunningTool, UiState, pretty_arguments, tool_summary};
 use crate::theme::*;
+use crate::widgets::{icon_button, name_field};
+
+/// Directory the suggestion chips look at
+const WORKSPACE_DIR: &str = "/workspace";
+/// Where large pastes are saved
+const PASTE_DIR: &str = "/workspace/pasted";
+
+/// A message the user sent, with the files attached to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outgoing {
+    pub text: String,
+    pub attachments: Vec<String>,
+}
 
 /// Render the chat panel. Returns Some(message) when user submits input.
-pub fn chat_panel(ui: &mut egui::Ui, state: &mut UiState) -> Option<String> {
+pub fn chat_panel(ui: &mut egui::Ui, state: &mut UiState, display: &ChatDisplayConfig) -> Option<Outgoing> {
     let mut submitted = None;
 
     egui::Frame::default()
-        .fill(BG_PRIMARY)
+        .fill(BG_PRIMARY.color())
         .inner_margin(PANEL_PADDING)
         .show(ui, |ui| {
             ui.vertical(|ui| {
                 // Header
                 ui.horizontal(|ui| {
                     ui.heading(
-                        RichText::new("Agent Chat")
+                        RichText::new(state.share.viewing.as_deref().unwrap_or("Agent Chat"))
                             .color(TEXT_PRIMARY)
                             .strong(),
                     );
                     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                        let status_color = if state.is_busy() { WARNING } else { SUCCESS };
-                        ui.label(
-                            RichText::new(&state.status_text)
-                                .color(status_color)
-                                .small(),
-                        );
+                        ui.toggle_value(&mut state.hide_tool_messages, RichText::new("Hide tools").small())
+                            .on_hover_text("Show only the conversation, without tool results");
+                        if state.share.viewing.is_none() {
+                            let can_share = !state.messages.is_empty() && !state.is_busy() && !state.share.packing;
+                            if icon_button(ui, can_share, "🔗", "Copy share link").clicked() {
+                                state.share.requested = true;
+                            }
+                        }
+                        if state.fonts.is_loading() {
+                            ui.label(RichText::new("Loading fonts…").color(TEXT_SECONDARY).small())
+                                .on_hover_text("Some characters show as boxes until the CJK font arrives");
+                            ui.spinner();
+                        }
+                        let now = ui.input(|i| i.time);
+                        match state.busy_elapsed(now) {
+                            // The spinner repaints every frame, which keeps
+                            // the counter moving
+                            Some(elapsed) => {
+                                ui.label(
+                                    RichText::new(format_elapsed(elapsed))
+                                        .color(TEXT_SECONDARY)
+                                        .monospace()
+                                        .small(),
+                                );
+                                ui.label(RichText::new(&state.status_text).color(WARNING).small());
+                                ui.spinner();
+                            }
+                            None => {
+                                ui.label(RichText::new(&state.status_text).color(SUCCESS).small());
+                            }
+                        }
                     });
                 });
 
@@ -39,41 +119,106 @@ pub fn chat_panel(ui: &mut egui::Ui, state: &mut UiState) -> Option<String> {
                     .auto_shrink([false, false])
                     .stick_to_bottom(true)
                     .show(ui, |ui| {
-                        for entry in &state.messages {
-                            render_message(ui, entry);
-                            ui.add_space(4.0);
+                        let spacing = message_spacing(display.density);
+                        if state.messages.is_empty()
+                            && state.running_tools.is_empty()
+                            && state.streaming_text.is_empty()
+                            && state.share.viewing.is_none()
+                        {
+                            let entries = state.files.entries(WORKSPACE_DIR);
+                            match suggestion_chips(ui, &suggestions(entries)) {
+                                Some(SuggestionClick::Send(prompt)) if !state.is_busy() => {
+                                    if let Submitted::Send(message) = submit(state, prompt) {
+                                        submitted = Some(message);
+                                    }
+                                }
+                                Some(SuggestionClick::Edit(prompt)) => state.input_text = prompt,
+                                _ => {}
+                            }
+                        }
+                        let mut clicked_file = None;
+                        let mut images = std::mem::take(&mut state.images);
+                        let mut exports = std::mem::take(&mut state.table_exports);
+                        let mut diagrams = std::mem::take(&mut state.diagrams);
+                        for (idx, entry) in state.visible_messages() {
+                            let from_user = entry.role == "user";
+                            let clicked = message_column(ui, display, from_user, |ui| {
+                                render_message(ui, idx, entry, &mut images, &mut exports, &mut diagrams, display)
+                            });
+                            if let Some(file) = clicked {
+                                clicked_file = Some(file);
+                            }
+                            ui.add_space(spacing);
+                        }
+                        state.images = images;
+                        state.table_exports = exports;
+                        state.diagrams = diagrams;
+                        if let Some(file) = clicked_file {
+                            state.viewer.open_at(&file.path, file.line);
+                        }
+                        if !state.hide_tool_messages {
+                            for tool in &state.running_tools {
+                                message_column(ui, display, false, |ui| render_running_tool(ui, tool, display));
+                                ui.add_space(spacing);
+                            }
                         }
 
                         // Show streaming text if any
                         if !state.streaming_text.is_empty() {
-                            egui::Frame::default()
-                                .fill(BG_SECONDARY)
-                                .corner_radius(PANEL_ROUNDING)
-                                .inner_margin(8.0)
-                                .show(ui, |ui| {
-                                    ui.label(
-                                        RichText::new(&state.streaming_text)
-                                            .color(TEXT_PRIMARY),
-                                    );
-                                    ui.label(
-                                        RichText::new("▌")
-                                            .color(ACCENT)
-                                            .strong(),
-                                    );
-                                });
+                            message_column(ui, display, false, |ui| {
+                                egui::Frame::default()
+                                    .fill(BG_SECONDARY.color())
+                                    .corner_radius(PANEL_ROUNDING)
+                                    .inner_margin(message_padding(display.density))
+                                    .show(ui, |ui| {
+                                        ui.label(assistant_text(&state.streaming_text, display));
+                                        ui.label(
+                                            RichText::new("▌")
+                                                .color(ACCENT)
+                                                .strong(),
+                                        );
+                                    });
+                            });
                         }
                     });
 
                 ui.add_space(8.0);
 
+                if state.share.viewing.is_some() {
+                    shared_notice(ui, state);
+                    return;
+                }
+
                 // Input area
+                outbox_notice(ui, state);
+                paste_offer(ui, state);
+                attachment_row(ui, state);
+                mention_suggestions(ui, state);
                 ui.horizontal(|ui| {
+                    prompts::prompt_menu(ui, &mut state.prompts, &mut state.input_text);
+                    if icon_button(ui, true, "📎", "Attach files").clicked() {
+                        state.attach_input = match state.attach_input {
+                            Some(_) => None,
+                            None => Some(String::new()),
+                        };
+                    }
+
+                    let input_id = egui::Id::new("chat_input");
+                    let focused = ui.memory(|m| m.has_focus(input_id));
+                    if focused && display.paste_file_chars > 0 {
+                        if let Some(text) = take_large_paste(ui, display.paste_file_chars) {
+                            state.paste.pending = Some(text);
+                            state.paste.error = None;
+                        }
+                    }
                     let input = egui::TextEdit::singleline(&mut state.input_text)
+                        .id(input_id)
                         .hint_text("Type a message...")
                         .desired_width(ui.available_width() - 70.0)
                         .font(egui::FontId::proportional(14.0));
 
                     let response = ui.add(input);
+                    name_field(&response, "Message");
 
                     let send_enabled = !state.input_text.trim().is_empty() && !state.is_busy();
                     let send_btn = ui.add_enabled(
@@ -94,34 +239,1073 @@ pub fn chat_panel(ui: &mut egui::Ui, state: &mut UiState) -> Option<String> {
                         || send_btn.clicked()
                     {
                         let text = state.input_text.trim().to_string();
-                        state.push_user_message(&text);
-                        submitted = Some(text);
-                        state.input_text.clear();
-                        response.request_focus();
+                        match submit(state, text) {
+                            Submitted::Send(message) => {
+                                submitted = Some(message);
+                                state.input_text.clear();
+                                response.request_focus();
+                            }
+                            Submitted::Queued => {
+                                state.input_text.clear();
+                                response.request_focus();
+                            }
+                            Submitted::Kept => {}
+                        }
                     }
                 });
             });
         });
 
+    prompts::fill_dialog(ui.ctx(), &mut state.prompts, &mut state.input_text, &mut state.files);
+
     submitted
 }
 
-fn render_message(ui: &mut egui::Ui, entry: &crate::state::ChatEntry) {
+/// What became of a submitted message
+enum Submitted {
+    Send(Outgoing),
+    /// Held in the outbox until the provider can be reached
+    Queued,
+    /// Not sent; the input stays as it is
+    Kept,
+}
+
+/// Send `text` as the user's message with the pending attachments and the
+/// files it mentions, unless the vault must be unlocked first. While
+/// offline, or with messages already waiting, it joins the outbox.
+fn submit(state: &mut UiState, text: String) -> Submitted {
+    if state.vault.is_locked() {
+        // No API key until the vault is unlocked; keep the input
+        state.vault.show_unlock = true;
+        return Submitted::Kept;
+    }
+    let mut attachments = std::mem::take(&mut state.attachments);
+    for path in mentioned_files(&text) {
+        if !attachments.contains(&path) {
+            attachments.push(path);
+        }
+    }
+    state.attach_input = None;
+    let message = Outgoing { text, attachments };
+    if !state.online || !state.outbox.is_empty() {
+        state.queue_message(message);
+        return Submitted::Queued;
+    }
+    state.push_user_message(&message.text, message.attachments.clone());
+    Submitted::Send(message)
+}
+
+/// First wait before sending a queued message again
+const RETRY_BASE_SECS: f64 = 5.0;
+/// Longest wait between attempts
+const RETRY_MAX_SECS: f64 = 60.0;
+
+/// Messages waiting for the provider to be reachable, oldest first. They
+/// are sent one turn at a time; one whose turn still can't reach the
+/// provider goes back to the front and is tried again after a growing
+/// wait, or as soon as the browser is back online.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    queued: VecDeque<Outgoing>,
+    /// UI time before which nothing is sent
+    retry_at: f64,
+    /// Attempts in a row that couldn't reach the provider
+    failures: u32,
+}
+
+impl Outbox {
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    pub fn push(&mut self, message: Outgoing) {
+        self.queued.push_back(message);
+    }
+
+    /// The next message, if one is due at UI time `now`
+    pub fn take_due(&mut self, now: f64) -> Option<Outgoing> {
+        if now < self.retry_at {
+            return None;
+        }
+        self.queued.pop_front()
+    }
+
+    /// Put back a message whose turn couldn't reach the provider
+    pub fn put_back(&mut self, message: Outgoing, now: f64) {
+        self.queued.push_front(message);
+        let wait = RETRY_BASE_SECS * 2f64.powi(self.failures.min(8) as i32);
+        self.retry_at = now + wait.min(RETRY_MAX_SECS);
+        self.failures += 1;
+    }
+
+    /// The provider looks reachable again (a turn went through, or the
+    /// browser came back online): send without waiting
+    pub fn reachable(&mut self) {
+        self.failures = 0;
+        self.retry_at = 0.0;
+    }
+
+    /// Seconds until the next attempt, as of UI time `now`
+    pub fn wait(&self, now: f64) -> f64 {
+        (self.retry_at - now).max(0.0)
+    }
+
+    pub fn clear(&mut self) {
+        self.queued.clear();
+    }
+}
+
+/// A paste too large for the input, waiting for the user to choose what
+/// to do with it, and the pastes to save as files
+#[derive(Default)]
+pub struct LargePaste {
+    /// The paste on offer
+    pub pending: Option<String>,
+    /// Why the last paste couldn't be saved
+    pub error: Option<String>,
+    /// Pastes to write to the workspace: path and text
+    saves: Vec<(String, String)>,
+}
+
+impl LargePaste {
+    /// Queue the pending paste to be written to `path`. Returns the
+    /// reference that goes into the input instead.
+    pub fn save_to(&mut self, path: &str) -> Option<String> {
+        let text = self.pending.take()?;
+        let reference = paste_reference(path, &text);
+        self.saves.push((path.to_string(), text));
+        Some(reference)
+    }
+
+    /// Pastes to write. Called by the app each frame.
+    pub fn take_saves(&mut self) -> Vec<(String, String)> {
+        std::mem::take(&mut self.saves)
+    }
+
+    /// A save finished. A failed one is offered again, to paste after all.
+    pub fn finish_save(&mut self, path: &str, text: String, result: Result<(), String>) {
+        if let Err(e) = result {
+            self.error = Some(format!("Couldn't save {}: {}", path, e));
+            self.pending = Some(text);
+        }
+    }
+}
+
+/// Where a paste made at `now` is saved
+pub fn pasted_path(now: chrono::DateTime<chrono::Local>) -> String {
+    format!("{}/{}.txt", PASTE_DIR, now.format("%Y%m%d-%H%M%S"))
+}
+
+/// What the input says about a paste saved to `path`
+pub fn paste_reference(path: &str, text: &str) -> String {
+    format!("[Pasted text saved to {} ({} lines, {} characters)]", path, text.lines().count(), text.chars().count())
+}
+
+/// Share links: the user asking for one, and a shared conversation being
+/// viewed
+#[derive(Default)]
+pub struct Sharing {
+    /// "Copy share link" was clicked
+    requested: bool,
+    /// The app is packing the conversation into a link
+    pub packing: bool,
+    /// Title of the shared conversation shown; the chat is read-only
+    /// while it is
+    pub viewing: Option<String>,
+    /// "Start your own" was clicked while viewing
+    leave: bool,
+}
+
+impl Sharing {
+    /// Whether a link was asked for. Called by the app each frame.
+    pub fn take_request(&mut self) -> bool {
+        std::mem::take(&mut self.requested)
+    }
+
+    /// Whether the user wants to leave the shared conversation. Called by
+    /// the app each frame.
+    pub fn take_leave(&mut self) -> bool {
+        std::mem::take(&mut self.leave)
+    }
+}
+
+/// Remove a paste longer than `limit` characters from this frame's
+/// input, before the text field sees it
+fn take_large_paste(ui: &mut egui::Ui, limit: usize) -> Option<String> {
+    ui.input_mut(|i| {
+        let index = i
+            .events
+            .iter()
+            .position(|e| matches!(e, egui::Event::Paste(text) if text.chars().count() > limit))?;
+        match i.events.remove(index) {
+            egui::Event::Paste(text) => Some(text),
+            _ => None,
+        }
+    })
+}
+
+/// In place of the input while viewing a shared conversation
+fn shared_notice(ui: &mut egui::Ui, state: &mut UiState) {
+    ui.horizontal(|ui| {
+        ui.label(
+            RichText::new("A shared copy of a conversation — read-only")
+                .color(TEXT_SECONDARY)
+                .small(),
+        );
+        if ui.button(RichText::new("Start your own").small()).clicked() {
+            state.share.leave = true;
+        }
+    });
+}
+
+/// How many messages are queued and when they go out
+fn outbox_notice(ui: &mut egui::Ui, state: &mut UiState) {
+    if state.outbox.is_empty() {
+        return;
+    }
+    let now = ui.input(|i| i.time);
+    let status = if !state.online {
+        "offline — sent once the connection is back".to_string()
+    } else if state.outbox.wait(now) > 0.0 {
+        format!("provider unreachable — retrying in {:.0}s", state.outbox.wait(now).ceil())
+    } else {
+        "sending…".to_string()
+    };
+    ui.horizontal_wrapped(|ui| {
+        ui.label(RichText::new(format!("⏳ {} queued · {}", state.outbox.len(), status)).color(WARNING).small());
+        if state.online && state.outbox.wait(now) > 0.0 && ui.small_button("Retry now").clicked() {
+            state.outbox.reachable();
+        }
+    });
+}
+
+/// The choice offered for a large paste
+fn paste_offer(ui: &mut egui::Ui, state: &mut UiState) {
+    if let Some(error) = &state.paste.error {
+        ui.label(RichText::new(error).color(ERROR).small());
+    }
+    let Some(text) = &state.paste.pending else {
+        return;
+    };
+    let summary = format!(
+        "📋 Pasted {} characters ({} lines)",
+        text.chars().count(),
+        text.lines().count()
+    );
+    ui.horizontal_wrapped(|ui| {
+        ui.label(RichText::new(summary).color(TEXT_PRIMARY).small());
+        if ui.button("Save as file").on_hover_text("Saved under /workspace/pasted/; the message refers to the file").clicked() {
+            let path = pasted_path(chrono::Local::now());
+            if let Some(reference) = state.paste.save_to(&path) {
+                if !state.input_text.trim().is_empty() && !state.input_text.ends_with(char::is_whitespace) {
+                    state.input_text.push(' ');
+                }
+                state.input_text.push_str(&reference);
+                state.paste.error = None;
+            }
+        }
+        if ui.button("Paste anyway").clicked() {
+            if let Some(text) = state.paste.pending.take() {
+                state.input_text.push_str(&text);
+                state.paste.error = None;
+            }
+        }
+        if ui.button("Discard").clicked() {
+            state.paste.pending = None;
+            state.paste.error = None;
+        }
+    });
+}
+
+/// Pending attachments as removable chips, and the attach popup while it
+/// is open
+fn attachment_row(ui: &mut egui::Ui, state: &mut UiState) {
+    if !state.attachments.is_empty() {
+        ui.horizontal_wrapped(|ui| {
+            let mut removed = None;
+            for (i, path) in state.attachments.iter().enumerate() {
+                ui.label(RichText::new(format!("📄 {}", file_name(path))).color(TEXT_PRIMARY).small())
+                    .on_hover_text(path);
+                if icon_button(ui, true, "✕", &format!("Remove {}", path)).clicked() {
+                    removed = Some(i);
+                }
+            }
+            if let Some(i) = removed {
+                state.attachments.remove(i);
+            }
+        });
+    }
+    let Some(input) = state.attach_input.as_mut() else {
+        return;
+    };
+    let mut picked = None;
+    ui.horizontal(|ui| {
+        let response = ui.add(
+            egui::TextEdit::singleline(input)
+                .hint_text("/workspace/…")
+                .font(egui::TextStyle::Monospace)
+                .desired_width(ui.available_width() - 30.0),
+        );
+        name_field(&response, "File to attach");
+        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            picked = Some(input.trim().to_string());
+        }
+        if icon_button(ui, true, "✕", "Close").clicked() {
+            picked = Some(String::new());
+        }
+    });
+    for suggestion in path_suggestions(input, &mut state.files) {
+        if ui.selectable_label(false, RichText::new(&suggestion).monospace().small()).clicked() {
+            if suggestion.ends_with('/') {
+                *input = suggestion;
+            } else {
+                picked = Some(suggestion);
+            }
+        }
+    }
+    if let Some(path) = picked {
+        state.attach_input = None;
+        if !path.is_empty() && !path.ends_with('/') && !state.attachments.contains(&path) {
+            state.attachments.push(path);
+        }
+    }
+}
+
+/// Completions of an `@path` being typed at the end of the input
+fn mention_suggestions(ui: &mut egui::Ui, state: &mut UiState) {
+    let Some(partial) = mention_at_end(&state.input_text) else {
+        return;
+    };
+    let partial = if partial.is_empty() { "/workspace/".to_string() } else { partial.to_string() };
+    let suggestions = path_suggestions(&partial, &mut state.files);
+    ui.horizontal_wrapped(|ui| {
+        for suggestion in suggestions {
+            if ui.selectable_label(false, RichText::new(format!("@{}", suggestion)).monospace().small()).clicked() {
+                complete_mention(&mut state.input_text, &suggestion);
+            }
+        }
+    });
+}
+
+/// The path after an `@` that starts the last word of `input`, while it
+/// is being typed
+pub fn mention_at_end(input: &str) -> Option<&str> {
+    let word = input.rsplit(char::is_whitespace).next()?;
+    let partial = word.strip_prefix('@')?;
+    (partial.is_empty() || partial.starts_with('/')).then_some(partial)
+}
+
+/// Replace the `@path` being typed with `completion`. A file is finished
+/// with a space; a directory stays open for the next segment.
+pub fn complete_mention(input: &mut String, completion: &str) {
+    let Some(partial) = mention_at_end(input) else {
+        return;
+    };
+    input.truncate(input.len() - partial.len());
+    input.push_str(completion);
+    if !completion.ends_with('/') {
+        input.push(' ');
+    }
+}
+
+/// Files mentioned as `@/path` in `text`, in order
+pub fn mentioned_files(text: &str) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let Some(path) = word.strip_prefix('@') else {
+            continue;
+        };
+        let path = path.trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+        if path.starts_with('/') && !path.ends_with('/') && !files.iter().any(|f| f == path) {
+            files.push(path.to_string());
+        }
+    }
+    files
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// A quick action offered in an empty session
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub label: String,
+    pub prompt: String,
+}
+
+impl Suggestion {
+    fn new(label: impl Into<String>, prompt: impl Into<String>) -> Self {
+        Self { label: label.into(), prompt: prompt.into() }
+    }
+}
+
+enum SuggestionClick {
+    Send(String),
+    Edit(String),
+}
+
+/// Suggestions for a workspace with `entries` at its top level, or an
+/// unlisted one while `None`
+pub fn suggestions(entries: Option<&[DirEntry]>) -> Vec<Suggestion> {
+    let entries = entries.unwrap_or_default();
+    let file = |pred: &dyn Fn(&str) -> bool| entries.iter().find(|e| !e.is_dir && pred(&e.name));
+    let mut list = vec![Suggestion::new(
+        "Explore the workspace",
+        format!("Explore {}: list what is there and give me an overview of the project.", WORKSPACE_DIR),
+    )];
+    if let Some(readme) = file(&|name| name.to_lowercase().starts_with("readme")) {
+        list.push(Suggestion::new(
+            format!("Summarize {}", readme.name),
+            format!("Summarize {}/{}.", WORKSPACE_DIR, readme.name),
+        ));
+    }
+    if file(&|name| name == "Cargo.toml").is_some() {
+        list.push(Suggestion::new(
+            "Explain the Rust project",
+            format!("Read {}/Cargo.toml and the sources, and explain how the Rust project is organized.", WORKSPACE_DIR),
+        ));
+    } else {
+        list.push(Suggestion::new(
+            "Create a Rust hello world",
+            format!("Create a Rust hello world project in {} with a Cargo.toml and src/main.rs.", WORKSPACE_DIR),
+        ));
+    }
+    if let Some(script) = file(&|name| name.ends_with(".py") || name.ends_with(".sh")) {
+        list.push(Suggestion::new(
+            format!("Explain {}", script.name),
+            format!("Read {}/{} and explain what it does.", WORKSPACE_DIR, script.name),
+        ));
+    }
+    list
+}
+
+/// The chips of an empty session. Returns the one clicked, to send or to
+/// edit first.
+fn suggestion_chips(ui: &mut egui::Ui, suggestions: &[Suggestion]) -> Option<SuggestionClick> {
+    let mut clicked = None;
+    ui.add_space(24.0);
+    ui.vertical_centered(|ui| {
+        ui.label(RichText::new("What should we work on?").color(TEXT_SECONDARY).size(16.0));
+        ui.add_space(8.0);
+    });
+    ui.horizontal_wrapped(|ui| {
+        for suggestion in suggestions {
+            let chip = egui::Button::new(RichText::new(&suggestion.label).color(TEXT_PRIMARY))
+                .fill(BG_SURFACE.color())
+                .corner_radius(12.0);
+            if ui.add(chip).on_hover_text(&suggestion.prompt).clicked() {
+                clicked = Some(SuggestionClick::Send(suggestion.prompt.clone()));
+            }
+            if icon_button(ui, true, "✎", &format!("Edit \"{}\" before sending", suggestion.label)).clicked() {
+                clicked = Some(SuggestionClick::Edit(suggestion.prompt.clone()));
+            }
+            ui.add_space(6.0);
+        }
+    });
+    clicked
+}
+
+/// Render one entry. Returns a referenced file if its link was clicked.
+fn render_message(
+    ui: &mut egui::Ui,
+    idx: usize,
+    entry: &crate::state::ChatEntry,
+    images: &mut ChatImages,
+    exports: &mut TableExports,
+    diagrams: &mut Diagrams,
+    display: &ChatDisplayConfig,
+) -> Option<FileRef> {
+    // Reasoning is long and secondary — collapsed until asked for
+    if entry.role == "reasoning" {
+        egui::CollapsingHeader::new(RichText::new("Thinking").color(TEXT_SECONDARY).small())
+            .id_salt(("reasoning", idx))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.label(RichText::new(&entry.content).color(TEXT_SECONDARY).italics());
+            });
+        return None;
+    }
+
+    // Provider switches and similar asides: one quiet line
+    if entry.role == "notice" {
+        ui.label(RichText::new(format!("⚠ {}", entry.content)).color(WARNING).small());
+        return None;
+    }
+
     let error_bg = Color32::from_rgb(50, 20, 20);
     let (label, label_color, bg) = match entry.role.as_str() {
-        "user" => ("You", ACCENT, BG_SECONDARY),
-        "assistant" => ("Agent", SUCCESS, BG_SECONDARY),
-        "tool" => ("[tool]", WARNING, BG_SURFACE),
+        "user" => ("You", ACCENT, BG_SECONDARY.color()),
+        "assistant" => ("Agent", SUCCESS, BG_SECONDARY.color()),
+        "tool" => ("[tool]", WARNING, BG_SURFACE.color()),
         "error" => ("Error", ERROR, error_bg),
-        _ => ("???", TEXT_SECONDARY, BG_SECONDARY),
+        "pending" => ("You · queued", TEXT_SECONDARY, BG_SURFACE.color()),
+        _ => ("???", TEXT_SECONDARY, BG_SECONDARY.color()),
     };
 
+    let mut clicked = None;
     egui::Frame::default()
         .fill(bg)
         .corner_radius(PANEL_ROUNDING)
-        .inner_margin(8.0)
+        .inner_margin(message_padding(display.density))
         .show(ui, |ui| {
-            ui.label(RichText::new(label).color(label_color).strong().small());
-            ui.label(RichText::new(&entry.content).color(TEXT_PRIMARY));
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(label).color(label_color).strong().small());
+                match entry.success {
+                    Some(true) => {
+                        ui.label(RichText::new("✓").color(SUCCESS).strong());
+                    }
+                    Some(false) => {
+                        ui.label(RichText::new("✗").color(ERROR).strong());
+                    }
+                    None => {}
+                }
+                if let Some(ms) = entry.duration_ms {
+                    ui.label(RichText::new(format_duration(ms)).color(TEXT_SECONDARY).small());
+                }
+                if let Some(code) = entry.data.as_ref().and_then(|d| d.exit_code) {
+                    let color = if code == 0 { TEXT_SECONDARY } else { ERROR };
+                    ui.label(RichText::new(format!("exit {}", code)).color(color).monospace().small());
+                }
+            });
+            // Tool results: what was called, with the raw arguments on request
+            if let (Some(tool_name), Some(arguments)) = (&entry.tool_name, &entry.arguments) {
+                ui.label(RichText::new(tool_summary(tool_name, arguments)).color(TEXT_SECONDARY).monospace().small());
+                egui::CollapsingHeader::new(RichText::new("Arguments").color(TEXT_SECONDARY).small())
+                    .id_salt(("arguments", idx))
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let pretty = pretty_arguments(arguments);
+                        let job = highlight(&pretty, syntax_for("arguments.json"), egui::FontId::monospace(12.0));
+                        ui.add(egui::Label::new(job).extend());
+                    });
+            }
+            let (body, citations) = if entry.role == "assistant" {
+                split_citations(&entry.content)
+            } else {
+                (entry.content.clone(), Vec::new())
+            };
+            let markdown = entry.data.as_ref().and_then(|d| d.mime_type.as_deref()) == Some("text/markdown");
+            if let Some((source, table)) = tool_table(entry) {
+                let id = ui.id().with(("table", idx));
+                if let Some(file) = table_view(ui, id, &table, source.as_deref(), exports) {
+                    clicked = Some(file);
+                }
+            } else if entry.role == "assistant" || markdown {
+                if let Some(file) = render_answer(ui, idx, &body, exports, diagrams, display) {
+                    clicked = Some(file);
+                }
+            } else {
+                ui.label(RichText::new(&body).color(TEXT_PRIMARY));
+            }
+            if !citations.is_empty() {
+                ui.add_space(4.0);
+                for citation in &citations {
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing.x = 4.0;
+                        ui.label(RichText::new(format!("[{}]", citation.number)).color(TEXT_SECONDARY).small());
+                        let label = RichText::new(citation.label()).small();
+                        match &citation.target {
+                            CitationTarget::File(file) => {
+                                let link = ui.link(label.monospace()).on_hover_text(file.label());
+                                if link.clicked() {
+                                    clicked = Some(file.clone());
+                                }
+                            }
+                            CitationTarget::Url(url) => {
+                                // A new tab, so the session isn't navigated away from
+                                ui.add(egui::Hyperlink::from_label_and_url(label, url).open_in_new_tab(true))
+                                    .on_hover_text(url);
+                            }
+                        }
+                    });
+                }
+            }
+
+            if let Some(data) = &entry.data {
+                for path in &data.images {
+                    if let Some(file) = tool_image(ui, images, path) {
+                        clicked = Some(file);
+                    }
+                }
+                if !data.artifacts.is_empty() {
+                    ui.horizontal_wrapped(|ui| {
+                        for path in &data.artifacts {
+                            let chip = egui::Button::new(
+                                RichText::new(format!("📄 {}", file_name(path))).color(TEXT_PRIMARY).small(),
+                            )
+                            .fill(BG_SECONDARY.color())
+                            .corner_radius(12.0);
+                            if ui.add(chip).on_hover_text(path).clicked() {
+                                clicked = Some(FileRef { path: path.clone(), line: None });
+                            }
+                        }
+                    });
+                }
+                if let Some(json) = &data.json {
+                    egui::CollapsingHeader::new(RichText::new("Data").color(TEXT_SECONDARY).small())
+                        .id_salt(("data", idx))
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            let pretty = serde_json::to_string_pretty(json).unwrap_or_default();
+                            let job = highlight(&pretty, syntax_for("data.json"), egui::FontId::monospace(12.0));
+                            ui.add(egui::Label::new(job).extend());
+                        });
+                }
+            }
+
+            if !entry.attachments.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    for path in &entry.attachments {
+                        let chip = egui::Button::new(
+                            RichText::new(format!("📎 {}", file_name(path))).color(TEXT_PRIMARY).small(),
+                        )
+                        .fill(BG_SURFACE.color())
+                        .corner_radius(12.0);
+                        if ui.add(chip).on_hover_text(path).clicked() {
+                            clicked = Some(FileRef { path: path.clone(), line: None });
+                        }
+                    }
+                });
+            }
+
+            // Files the text mentions, less those already cited
+            let files: Vec<FileRef> = file_references(&body)
+                .into_iter()
+                .filter(|f| !citations.iter().any(|c| c.target == CitationTarget::File(f.clone())))
+                .collect();
+            if !files.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    for file in files {
+                        if ui.link(RichText::new(file.label()).monospace().small()).clicked() {
+                            clicked = Some(file);
+                        }
+                    }
+                });
+            }
         });
+    clicked
+}
+
+/// Lay out one message: a bubble on the right (the user's) or left, or a
+/// row in a centered column
+fn message_column<R>(
+    ui: &mut egui::Ui,
+    display: &ChatDisplayConfig,
+    from_user: bool,
+    add_contents: impl FnOnce(&mut egui::Ui) -> R,
+) -> R {
+    let available = ui.available_width();
+    let width = message_width(available, display);
+    if display.bubbles {
+        let align = if from_user { Align::Max } else { Align::Min };
+        ui.with_layout(Layout::top_down(align), |ui| {
+            ui.set_max_width(width);
+            add_contents(ui)
+        })
+        .inner
+    } else {
+        ui.horizontal(|ui| {
+            ui.add_space((available - width) / 2.0);
+            ui.vertical(|ui| {
+                ui.set_width(width);
+                add_contents(ui)
+            })
+            .inner
+        })
+        .inner
+    }
+}
+
+/// Width of a message in a panel `available` points wide. Bubbles leave a
+/// fifth of the panel free so whose message it is stays visible.
+pub fn message_width(available: f32, display: &ChatDisplayConfig) -> f32 {
+    let mut width = available;
+    if display.bubbles {
+        width *= 0.8;
+    }
+    if display.max_width > 0.0 {
+        width = width.min(display.max_width);
+    }
+    width.max(0.0)
+}
+
+/// Space inside a message's frame
+pub fn message_padding(density: ChatDensity) -> f32 {
+    match density {
+        ChatDensity::Comfortable => 8.0,
+        ChatDensity::Compact => 4.0,
+    }
+}
+
+/// Space between messages
+pub fn message_spacing(density: ChatDensity) -> f32 {
+    match density {
+        ChatDensity::Comfortable => 4.0,
+        ChatDensity::Compact => 1.0,
+    }
+}
+
+fn assistant_text(text: &str, display: &ChatDisplayConfig) -> RichText {
+    let text = RichText::new(text).color(TEXT_PRIMARY);
+    if display.monospace_assistant { text.monospace() } else { text }
+}
+
+/// Markdown text with its diagrams, display formulas and tables drawn as
+/// such, and inline math as Unicode. Returns a file to open, if any.
+fn render_answer(
+    ui: &mut egui::Ui,
+    idx: usize,
+    body: &str,
+    exports: &mut TableExports,
+    diagrams: &mut Diagrams,
+    display: &ChatDisplayConfig,
+) -> Option<FileRef> {
+    let mut clicked = None;
+    for (b, block) in split_diagrams(body).into_iter().enumerate() {
+        let text = match block {
+            Block::Mermaid(source) => {
+                diagram_view(ui, ui.id().with(("diagram", idx, b)), &source, diagrams);
+                continue;
+            }
+            Block::Text(text) => text,
+        };
+        for (m, block) in split_display_math(&text).into_iter().enumerate() {
+            let text = match block {
+                MathBlock::Display(tex) => {
+                    formula_view(ui, &tex, diagrams);
+                    continue;
+                }
+                MathBlock::Text(text) => text,
+            };
+            for (n, segment) in split_tables(&text).into_iter().enumerate() {
+                match segment {
+                    Segment::Text(text) => {
+                        ui.label(assistant_text(&inline_math(&text), display));
+                    }
+                    Segment::Table(table) => {
+                        let id = ui.id().with(("table", idx, b, m, n));
+                        if let Some(file) = table_view(ui, id, &table, None, exports) {
+                            clicked = Some(file);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    clicked
+}
+
+/// A display formula set by MathJax, centered. Until it is, or if it can't
+/// be, the formula is shown converted to Unicode.
+fn formula_view(ui: &mut egui::Ui, tex: &str, diagrams: &mut Diagrams) {
+    let formula = diagrams.get(DiagramKind::Math, tex, ui.visuals().dark_mode);
+    if let Diagram::Pixels { size, rgba } = formula {
+        let color = egui::ColorImage::from_rgba_unmultiplied(*size, rgba);
+        *formula = Diagram::Shown(ui.ctx().load_texture("formula", color, Default::default()));
+    }
+    ui.vertical_centered(|ui| match formula {
+        Diagram::Shown(texture) => {
+            let size = texture.size_vec2() / ui.ctx().pixels_per_point();
+            let scale = (ui.available_width() / size.x).min(1.0);
+            ui.add(egui::Image::new(&*texture).fit_to_exact_size(size * scale)).on_hover_text(tex);
+        }
+        Diagram::Failed(error) => {
+            ui.label(RichText::new(tex_to_unicode(tex)).color(TEXT_PRIMARY).italics())
+                .on_hover_text(format!("Couldn't typeset the formula: {}", error));
+        }
+        Diagram::Loading | Diagram::Pixels { .. } => {
+            ui.label(RichText::new(tex_to_unicode(tex)).color(TEXT_PRIMARY).italics());
+        }
+    });
+}
+
+/// Tallest a table is drawn before it scrolls
+const TABLE_HEIGHT: f32 = 360.0;
+
+/// The table in a tool result, and the file it came from: a CSV/TSV file
+/// read whole, or a result whose data says it is CSV/TSV
+pub fn tool_table(entry: &crate::state::ChatEntry) -> Option<(Option<String>, Table)> {
+    if entry.success != Some(true) {
+        return None;
+    }
+    let mime = entry.data.as_ref().and_then(|d| d.mime_type.as_deref());
+    let delimiter = match mime {
+        Some("text/csv") => Some(','),
+        Some("text/tab-separated-values") => Some('\t'),
+        _ => None,
+    };
+    if let Some(delimiter) = delimiter {
+        return parse_delimited(&entry.content, delimiter).map(|table| (None, table));
+    }
+    if entry.tool_name.as_deref() != Some("read_file") {
+        return None;
+    }
+    let args: serde_json::Value = serde_json::from_str(entry.arguments.as_deref()?).ok()?;
+    // Numbered lines of part of a file aren't CSV
+    if args["offset"].is_u64() || args["limit"].is_u64() {
+        return None;
+    }
+    let path = args["path"].as_str()?;
+    let table = parse_delimited(&entry.content, delimiter_for(path)?)?;
+    Some((Some(path.to_string()), table))
+}
+
+/// A table as a grid, sorted by the column whose header was clicked
+/// (ascending, descending, then as given). Rows past [`MAX_TABLE_ROWS`]
+/// are left to the viewer: "Open as file" opens `source`, or saves a copy
+/// through `exports` when the table didn't come from a file. Returns the
+/// file to open, if any.
+fn table_view(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    table: &Table,
+    source: Option<&str>,
+    exports: &mut TableExports,
+) -> Option<FileRef> {
+    let mut sort: Option<(usize, bool)> = ui.data(|d| d.get_temp(id)).flatten();
+    let order: Vec<usize> = match sort {
+        Some((column, descending)) => table.sorted(column, descending),
+        None => (0..table.rows.len()).collect(),
+    };
+    let numeric: Vec<bool> = (0..table.headers.len()).map(|c| table.is_numeric(c)).collect();
+    ScrollArea::both().id_salt(id).max_height(TABLE_HEIGHT).show(ui, |ui| {
+        egui::Grid::new(id.with("grid")).striped(true).spacing([12.0, 4.0]).show(ui, |ui| {
+            for (column, header) in table.headers.iter().enumerate() {
+                let arrow = match sort {
+                    Some((c, false)) if c == column => " ▲",
+                    Some((c, true)) if c == column => " ▼",
+                    _ => "",
+                };
+                let label = RichText::new(format!("{}{}", header, arrow)).color(TEXT_PRIMARY).strong().small();
+                if ui.add(egui::Button::new(label).frame(false)).on_hover_text("Sort by this column").clicked() {
+                    sort = match sort {
+                        Some((c, false)) if c == column => Some((column, true)),
+                        Some((c, true)) if c == column => None,
+                        _ => Some((column, false)),
+                    };
+                }
+            }
+            ui.end_row();
+            for &row in order.iter().take(MAX_TABLE_ROWS) {
+                for (column, cell) in table.rows[row].iter().enumerate() {
+                    let text = RichText::new(cell).color(TEXT_PRIMARY).small();
+                    if numeric[column] {
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| ui.label(text.monospace()));
+                    } else {
+                        ui.label(text);
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    });
+    ui.data_mut(|d| d.insert_temp(id, sort));
+
+    let mut clicked = None;
+    let total = table.rows.len();
+    ui.horizontal(|ui| {
+        let shown = if total > MAX_TABLE_ROWS {
+            format!("Showing {} of {} rows", MAX_TABLE_ROWS, total)
+        } else {
+            format!("{} row{}", total, if total == 1 { "" } else { "s" })
+        };
+        ui.label(RichText::new(shown).color(TEXT_SECONDARY).small());
+        if total > MAX_TABLE_ROWS && ui.small_button("Open as file").clicked() {
+            match source {
+                Some(path) => clicked = Some(FileRef { path: path.to_string(), line: None }),
+                None => {
+                    exports.export(table, chrono::Local::now());
+                }
+            }
+        }
+    });
+    clicked
+}
+
+/// Tallest a diagram is drawn
+const DIAGRAM_HEIGHT: f32 = 480.0;
+
+/// A mermaid diagram fit to the message, with its source on request. The
+/// source is shown instead if the diagram couldn't be drawn.
+fn diagram_view(ui: &mut egui::Ui, id: egui::Id, source: &str, diagrams: &mut Diagrams) {
+    let diagram = diagrams.get(DiagramKind::Mermaid, source, ui.visuals().dark_mode);
+    if let Diagram::Pixels { size, rgba } = diagram {
+        let color = egui::ColorImage::from_rgba_unmultiplied(*size, rgba);
+        *diagram = Diagram::Shown(ui.ctx().load_texture("mermaid", color, Default::default()));
+    }
+    let code = |ui: &mut egui::Ui| {
+        let job = highlight(source, syntax_for("diagram.mmd"), egui::FontId::monospace(12.0));
+        ui.add(egui::Label::new(job).extend());
+    };
+    match diagram {
+        Diagram::Loading | Diagram::Pixels { .. } => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(RichText::new("Drawing diagram…").color(TEXT_SECONDARY).small());
+            });
+        }
+        Diagram::Shown(texture) => {
+            // Rendered at the screen's pixel density
+            let size = texture.size_vec2() / ui.ctx().pixels_per_point();
+            let scale = (ui.available_width() / size.x).min(DIAGRAM_HEIGHT / size.y).min(1.0);
+            ui.add(egui::Image::new(&*texture).fit_to_exact_size(size * scale));
+            egui::CollapsingHeader::new(RichText::new("Diagram source").color(TEXT_SECONDARY).small())
+                .id_salt(id)
+                .default_open(false)
+                .show(ui, code);
+        }
+        Diagram::Failed(error) => {
+            ui.label(RichText::new(format!("Couldn't draw the diagram: {}", error)).color(WARNING).small());
+            code(ui);
+        }
+    }
+}
+
+/// Tallest an image under a tool result is drawn
+const TOOL_IMAGE_HEIGHT: f32 = 320.0;
+
+/// An image a tool produced, fit to the card. Returns the file if the image
+/// was clicked, to open it in the viewer.
+fn tool_image(ui: &mut egui::Ui, images: &mut ChatImages, path: &str) -> Option<FileRef> {
+    let image = images.get(path);
+    if let ChatImage::Bytes(bytes) = image {
+        *image = decode(ui.ctx(), path, bytes);
+    }
+    match image {
+        ChatImage::Loading | ChatImage::Bytes(_) => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(RichText::new(format!("Loading {}", file_name(path))).color(TEXT_SECONDARY).small());
+            });
+            None
+        }
+        ChatImage::Shown(texture) => {
+            let size = texture.size_vec2();
+            let scale = (ui.available_width() / size.x).min(TOOL_IMAGE_HEIGHT / size.y).min(1.0);
+            let response = ui
+                .add(egui::Image::new(&*texture).fit_to_exact_size(size * scale).sense(egui::Sense::click()))
+                .on_hover_text(format!("{} · {} × {} px", path, size.x, size.y));
+            response.clicked().then(|| FileRef { path: path.to_string(), line: None })
+        }
+        ChatImage::Failed(error) => {
+            ui.label(RichText::new(format!("🖼 {}: {}", file_name(path), error)).color(ERROR).small());
+            None
+        }
+    }
+}
+
+/// A tool call in progress: its name and a progress bar, or a spinner until
+/// it reports progress
+fn render_running_tool(ui: &mut egui::Ui, tool: &RunningTool, display: &ChatDisplayConfig) {
+    egui::Frame::default()
+        .fill(BG_SURFACE.color())
+        .corner_radius(PANEL_ROUNDING)
+        .inner_margin(message_padding(display.density))
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("[tool]").color(WARNING).strong().small());
+                ui.label(RichText::new(&tool.tool_name).color(TEXT_PRIMARY).monospace().small());
+                if tool.progress.is_none() {
+                    ui.spinner();
+                }
+            });
+            if let Some((done, total, unit)) = &tool.progress {
+                let text = progress_text(*done, *total, unit);
+                let bar = match total {
+                    Some(total) if *total > 0 => egui::ProgressBar::new(*done as f32 / *total as f32),
+                    _ => egui::ProgressBar::new(0.0).animate(true),
+                };
+                ui.add(bar.text(text).desired_width(ui.available_width()));
+            }
+        });
+}
+
+/// Time a turn has been running: "7s", "1m 05s"
+pub fn format_elapsed(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// "3.0 MB of 8.0 MB", "12 of 40 files", "7 files"
+pub fn progress_text(done: u64, total: Option<u64>, unit: &str) -> String {
+    let amount = |n: u64| if unit == "bytes" { format_size(n) } else { n.to_string() };
+    match total {
+        Some(total) if unit == "bytes" => format!("{} of {}", amount(done), amount(total)),
+        Some(total) => format!("{} of {} {}", done, total, unit),
+        None if unit == "bytes" => amount(done),
+        None => format!("{} {}", done, unit),
+    }
+}
+
+/// A workspace file mentioned in text, with the line for `path:line`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRef {
+    pub path: String,
+    /// Line number, starting at 1
+    pub line: Option<usize>,
+}
+
+impl FileRef {
+    /// `path` or `path:line`, as written
+    pub fn label(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}:{}", self.path, line),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// Workspace file references in `text` with the byte range each covers,
+/// in order. `/workspace/a.rs:12:5` covers the line and column too.
+pub fn file_spans(text: &str) -> Vec<(std::ops::Range<usize>, FileRef)> {
+    let mut spans = Vec::new();
+    for (start, _) in text.match_indices("/workspace/") {
+        // Only whole paths, not the tail of e.g. "/home/workspace/"
+        let preceded = text[..start].chars().next_back();
+        if preceded.is_some_and(|c| !c.is_whitespace() && !"\"'`([<:".contains(c)) {
+            continue;
+        }
+        let rest = &text[start..];
+        let end = rest
+            .find(|c: char| c.is_whitespace() || "\"'`()<>[],;".contains(c))
+            .unwrap_or(rest.len());
+        let word = rest[..end].trim_end_matches(['.', ':', '!', '?']);
+        let (path, line, len) = match word.split_once(':') {
+            Some((path, suffix)) => {
+                let digits = suffix.find(|c: char| !c.is_ascii_digit()).unwrap_or(suffix.len());
+                // Keep a column (`:5`) inside the link, not other suffixes
+                let len = match suffix[digits..].strip_prefix(':') {
+                    Some(col) if !col.is_empty() && col.bytes().all(|b| b.is_ascii_digit()) => word.len(),
+                    _ => path.len() + 1 + digits,
+                };
+                let line = suffix[..digits].parse().ok().filter(|&n| n > 0);
+                (path, line, if line.is_some() { len } else { path.len() })
+            }
+            None => (word, None, word.len()),
+        };
+        if path.len() > "/workspace/".len() && !path.ends_with('/') {
+            spans.push((start..start + len, FileRef { path: path.to_string(), line }));
+        }
+    }
+    spans
+}
+
+/// Workspace files mentioned in a message, in order, without duplicates
+pub fn file_references(text: &str) -> Vec<FileRef> {
+    let mut refs: Vec<FileRef> = Vec::new();
+    for (_, file) in file_spans(text) {
+        if !refs.contains(&file) {
+            refs.push(file);
+        }
+    }
+    refs
 }
@@ -0,0 +1,152 @@
+//! Memory and storage use in the settings panel, with cleanups.
+//!
+//! Measuring and cleaning up are the app's job: the panel raises
+//! `refresh_requested` or `cleanup` and shows `storage`, `running` and
+//! `status` as set by the app.
+
+use egui::{self, RichText};
+use agent_types::storage::StorageBreakdown;
+
+use crate::panels::viewer::format_size;
+use crate::theme::*;
+
+/// A cleanup the user asked for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cleanup {
+    ClearTerminal,
+    /// Remove what deleted workspaces left in storage
+    VacuumVfs,
+    /// Delete sessions last updated before `cutoff` (RFC 3339), except the
+    /// open one
+    DeleteOldSessions { cutoff: String },
+}
+
+/// What the UI itself holds, counted by the app every frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Retained {
+    /// Size of the WASM linear memory, if known
+    pub heap_bytes: Option<u64>,
+    pub messages: usize,
+    pub terminal_lines: usize,
+}
+
+pub struct DiagnosticsPanel {
+    /// Bytes in storage as last measured by the app
+    pub storage: Option<StorageBreakdown>,
+    pub loading: bool,
+    pub refresh_requested: bool,
+    /// "Delete old sessions" takes sessions untouched for this many days
+    pub session_age_days: u32,
+    /// Asking to confirm deleting old sessions
+    pub confirm_delete: bool,
+    pub cleanup: Option<Cleanup>,
+    /// A cleanup is running
+    pub running: bool,
+    /// Outcome of the last cleanup
+    pub status: Option<Result<String, String>>,
+}
+
+impl Default for DiagnosticsPanel {
+    fn default() -> Self {
+        Self {
+            storage: None,
+            loading: false,
+            refresh_requested: false,
+            session_age_days: 30,
+            confirm_delete: false,
+            cleanup: None,
+            running: false,
+            status: None,
+        }
+    }
+}
+
+/// The "Memory & Storage" section of the settings panel.
+pub fn diagnostics_section(ui: &mut egui::Ui, panel: &mut DiagnosticsPanel, retained: &Retained) {
+    if panel.storage.is_none() && !panel.loading {
+        panel.refresh_requested = true;
+    }
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("Memory & Storage").color(TEXT_SECONDARY).small());
+        if ui.add_enabled(!panel.loading, egui::Button::new("⟳").small()).on_hover_text("Measure again").clicked() {
+            panel.refresh_requested = true;
+        }
+        if panel.loading || panel.running {
+            ui.spinner();
+        }
+    });
+
+    let bytes = |n: Option<u64>| n.map_or("–".to_string(), format_size);
+    let storage = panel.storage.as_ref();
+    let rows = [
+        ("WASM heap", bytes(retained.heap_bytes)),
+        ("Settings", bytes(storage.map(|s| s.config))),
+        ("Sessions", bytes(storage.map(|s| s.sessions))),
+        ("Files", bytes(storage.map(|s| s.vfs))),
+        ("Other", bytes(storage.map(|s| s.other()))),
+        ("Messages shown", retained.messages.to_string()),
+        ("Terminal lines", retained.terminal_lines.to_string()),
+    ];
+    egui::Grid::new("diagnostics_usage").num_columns(2).show(ui, |ui| {
+        for (label, value) in rows {
+            ui.label(RichText::new(label).color(TEXT_SECONDARY).small());
+            ui.label(RichText::new(value).small().color(TEXT_PRIMARY));
+            ui.end_row();
+        }
+    });
+
+    ui.add_enabled_ui(!panel.running, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            if ui
+                .add_enabled(retained.terminal_lines > 0, egui::Button::new("Clear terminal").small())
+                .on_hover_text("Forget the terminal's scrollback")
+                .clicked()
+            {
+                panel.cleanup = Some(Cleanup::ClearTerminal);
+            }
+            if ui
+                .add(egui::Button::new("Vacuum files").small())
+                .on_hover_text("Remove files and sessions left in storage by deleted workspaces")
+                .clicked()
+            {
+                panel.cleanup = Some(Cleanup::VacuumVfs);
+            }
+        });
+        ui.horizontal(|ui| {
+            if panel.confirm_delete {
+                ui.label(
+                    RichText::new(format!("Delete sessions older than {} days?", panel.session_age_days))
+                        .color(WARNING)
+                        .small(),
+                );
+                if ui.small_button("Delete").clicked() {
+                    let cutoff = chrono::Utc::now() - chrono::Duration::days(i64::from(panel.session_age_days));
+                    panel.cleanup = Some(Cleanup::DeleteOldSessions { cutoff: cutoff.to_rfc3339() });
+                    panel.confirm_delete = false;
+                }
+                if ui.small_button("Cancel").clicked() {
+                    panel.confirm_delete = false;
+                }
+            } else {
+                if ui
+                    .add(egui::Button::new("Delete old sessions").small())
+                    .on_hover_text("Delete sessions not updated for this long; the open one is kept")
+                    .clicked()
+                {
+                    panel.confirm_delete = true;
+                }
+                ui.add(egui::DragValue::new(&mut panel.session_age_days).range(1..=3650).suffix(" days"));
+            }
+        });
+    });
+
+    match &panel.status {
+        Some(Ok(summary)) => {
+            ui.label(RichText::new(summary).color(TEXT_SECONDARY).small());
+        }
+        Some(Err(error)) => {
+            ui.label(RichText::new(error).color(ERROR).small());
+        }
+        None => {}
+    }
+}
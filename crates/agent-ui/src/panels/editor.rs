@@ -0,0 +1,282 @@
+//! Code editor — hand-edit a VFS file.
+//!
+//! Like the viewer, the panel only records what should happen: Ctrl+S (or
+//! the Save button) queues a [`SaveRequest`] for the app, which writes it
+//! through the VFS and reports back with [`CodeEditor::finish_save`].
+
+use std::ops::Range;
+
+use egui::{self, RichText, ScrollArea};
+
+use crate::highlight::{highlight, syntax_for};
+use crate::theme::*;
+use crate::widgets::{icon_button, name_field};
+
+/// Contents the app should write to the VFS
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveRequest {
+    pub path: String,
+    pub contents: String,
+}
+
+/// Byte ranges of non-overlapping occurrences of `needle`
+pub fn find_matches(text: &str, needle: &str) -> Vec<Range<usize>> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    text.match_indices(needle)
+        .map(|(start, m)| start..start + m.len())
+        .collect()
+}
+
+#[derive(Default)]
+pub struct CodeEditor {
+    pub path: Option<String>,
+    pub text: String,
+    /// Contents as last opened or saved, for the dirty check
+    saved: String,
+    pub saving: bool,
+    pub error: Option<String>,
+    pub find: String,
+    pub replace: String,
+    pub show_find: bool,
+    /// Match selected by the last find
+    current_match: Option<Range<usize>>,
+    save_request: Option<SaveRequest>,
+    confirm_close: bool,
+}
+
+impl CodeEditor {
+    pub fn is_open(&self) -> bool {
+        self.path.is_some()
+    }
+
+    pub fn open(&mut self, path: &str, text: String) {
+        *self = Self {
+            path: Some(path.to_string()),
+            saved: text.clone(),
+            text,
+            ..Default::default()
+        };
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Unsaved changes?
+    pub fn is_dirty(&self) -> bool {
+        self.text != self.saved
+    }
+
+    /// Queue the current contents for saving
+    pub fn request_save(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        self.saving = true;
+        self.error = None;
+        self.save_request = Some(SaveRequest {
+            path: path.clone(),
+            contents: self.text.clone(),
+        });
+    }
+
+    /// The pending save, if any. Called by the app each frame.
+    pub fn take_save_request(&mut self) -> Option<SaveRequest> {
+        self.save_request.take()
+    }
+
+    /// Record the outcome of a save. `contents` is what was written, so
+    /// edits made while the save was in flight stay dirty.
+    pub fn finish_save(&mut self, request: SaveRequest, result: Result<(), String>) {
+        if self.path.as_ref() != Some(&request.path) {
+            return;
+        }
+        self.saving = false;
+        match result {
+            Ok(()) => self.saved = request.contents,
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Select the next occurrence of the find text after the current
+    /// match, wrapping around at the end.
+    pub fn find_next(&mut self) -> Option<Range<usize>> {
+        let from = self.current_match.as_ref().map_or(0, |m| m.end);
+        let matches = find_matches(&self.text, &self.find);
+        let next = matches
+            .iter()
+            .find(|m| m.start >= from)
+            .or_else(|| matches.first())
+            .cloned();
+        self.current_match = next.clone();
+        next
+    }
+
+    /// Replace the selected match (if it still matches) and move to the next.
+    pub fn replace_next(&mut self) -> Option<Range<usize>> {
+        if let Some(m) = self.current_match.clone() {
+            if self.text.get(m.clone()) == Some(self.find.as_str()) {
+                self.text.replace_range(m.clone(), &self.replace);
+                self.current_match = Some(m.start..m.start + self.replace.len());
+            }
+        }
+        self.find_next()
+    }
+
+    /// Replace every occurrence. Returns how many were replaced.
+    pub fn replace_all(&mut self) -> usize {
+        let count = find_matches(&self.text, &self.find).len();
+        if count > 0 {
+            self.text = self.text.replace(&self.find, &self.replace);
+        }
+        self.current_match = None;
+        count
+    }
+}
+
+/// Render the editor.
+pub fn editor_panel(ui: &mut egui::Ui, editor: &mut CodeEditor) {
+    let Some(path) = editor.path.clone() else {
+        return;
+    };
+    let editor_id = egui::Id::new("code_editor");
+
+    if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::S)) && !editor.saving {
+        editor.request_save();
+    }
+    if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::F)) {
+        editor.show_find = true;
+    }
+
+    let mut select = None;
+    egui::Frame::default()
+        .fill(BG_SECONDARY.color())
+        .inner_margin(PANEL_PADDING)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(&path).color(TEXT_PRIMARY).monospace());
+                if editor.is_dirty() {
+                    ui.label(RichText::new("●").color(WARNING)).on_hover_text("Unsaved changes");
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if icon_button(ui, true, "✕", "Close").clicked() {
+                        if editor.is_dirty() {
+                            editor.confirm_close = true;
+                        } else {
+                            editor.close();
+                        }
+                    }
+                    if ui.small_button("Find").on_hover_text("Ctrl+F").clicked() {
+                        editor.show_find = !editor.show_find;
+                    }
+                    if editor.saving {
+                        ui.spinner();
+                    } else if ui
+                        .add_enabled(editor.is_dirty(), egui::Button::new("Save").small())
+                        .on_hover_text("Ctrl+S")
+                        .clicked()
+                    {
+                        editor.request_save();
+                    }
+                });
+            });
+
+            if editor.confirm_close {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Discard unsaved changes?").color(WARNING));
+                    if ui.button("Discard").clicked() {
+                        editor.close();
+                    }
+                    if ui.button("Keep editing").clicked() {
+                        editor.confirm_close = false;
+                    }
+                });
+                if !editor.is_open() {
+                    return;
+                }
+            }
+            if let Some(error) = &editor.error {
+                ui.label(RichText::new(format!("Save failed: {}", error)).color(ERROR).small());
+            }
+
+            if editor.show_find {
+                ui.horizontal(|ui| {
+                    let find = ui.add(
+                        egui::TextEdit::singleline(&mut editor.find)
+                            .hint_text("Find")
+                            .desired_width(120.0),
+                    );
+                    if find.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        select = editor.find_next();
+                    }
+                    if ui.small_button("Next").clicked() {
+                        select = editor.find_next();
+                    }
+                    let count = find_matches(&editor.text, &editor.find).len();
+                    ui.label(RichText::new(format!("{} found", count)).color(TEXT_SECONDARY).small());
+                });
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut editor.replace)
+                            .hint_text("Replace")
+                            .desired_width(120.0),
+                    );
+                    if ui.small_button("Replace").clicked() {
+                        select = editor.replace_next();
+                    }
+                    if ui.small_button("All").clicked() {
+                        editor.replace_all();
+                    }
+                });
+            }
+            ui.separator();
+
+            let font = egui::FontId::monospace(12.0);
+            let syntax = syntax_for(&path);
+            let line_count = editor.text.split('\n').count();
+            let gutter: String = (1..=line_count).map(|n| format!("{}\n", n)).collect();
+            let mut layouter = |ui: &egui::Ui, buf: &dyn egui::TextBuffer, _wrap_width: f32| {
+                let job = highlight(buf.as_str(), syntax, egui::FontId::monospace(12.0));
+                ui.fonts_mut(|f| f.layout_job(job))
+            };
+
+            ScrollArea::both()
+                .id_salt("editor_scroll")
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    ui.horizontal_top(|ui| {
+                        ui.label(
+                            RichText::new(gutter.trim_end()).font(font).color(TEXT_SECONDARY),
+                        );
+                        let response = ui.add(
+                            egui::TextEdit::multiline(&mut editor.text)
+                                .id(editor_id)
+                                .code_editor()
+                                .frame(false)
+                                .margin(egui::vec2(4.0, 0.0))
+                                .desired_width(f32::INFINITY)
+                                .desired_rows(line_count)
+                                .layouter(&mut layouter),
+                        );
+                        name_field(&response, &format!("Contents of {}", path));
+                    });
+                });
+        });
+
+    // Highlight the match by selecting it in the text edit
+    if let Some(range) = select {
+        let text = &editor.text;
+        let start = text[..range.start].chars().count();
+        let end = start + text[range].chars().count();
+        if let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), editor_id) {
+            state.cursor.set_char_range(Some(egui::text::CCursorRange::two(
+                egui::text::CCursor::new(start),
+                egui::text::CCursor::new(end),
+            )));
+            egui::TextEdit::store_state(ui.ctx(), editor_id, state);
+        }
+        ui.memory_mut(|m| m.request_focus(editor_id));
+    }
+}
@@ -0,0 +1,67 @@
+//! Eval controls in the settings panel — pick a suite file and run it.
+//!
+//! Running is the app's job: the panel raises `run_requested` and shows
+//! `running` and `result` as set by the app.
+
+use egui::{self, RichText};
+
+use crate::theme::*;
+use crate::widgets::name_field;
+
+/// Suite file used unless the user picks another
+pub const DEFAULT_SUITE_PATH: &str = "/workspace/eval.json";
+
+pub struct EvalPanel {
+    /// VFS path of the suite JSON
+    pub suite_path: String,
+    pub running: bool,
+    pub run_requested: bool,
+    /// Outcome of the last run, e.g. "4/5 passed"
+    pub result: Option<Result<String, String>>,
+}
+
+impl Default for EvalPanel {
+    fn default() -> Self {
+        Self {
+            suite_path: DEFAULT_SUITE_PATH.to_string(),
+            running: false,
+            run_requested: false,
+            result: None,
+        }
+    }
+}
+
+/// The "Evaluation" section of the settings panel. `busy` disables running
+/// while an agent turn is in flight.
+pub fn eval_section(ui: &mut egui::Ui, panel: &mut EvalPanel, busy: bool) {
+    ui.label(RichText::new("Evaluation").color(TEXT_SECONDARY).small());
+    ui.label(
+        RichText::new("Run the prompts in a suite file and compare with the previous run.")
+            .color(TEXT_SECONDARY)
+            .small(),
+    );
+    ui.horizontal(|ui| {
+        let field = ui.add(
+            egui::TextEdit::singleline(&mut panel.suite_path)
+                .desired_width(180.0)
+                .hint_text(DEFAULT_SUITE_PATH),
+        );
+        name_field(&field, "Eval suite path");
+        let ready = !panel.running && !busy && !panel.suite_path.trim().is_empty();
+        if ui.add_enabled(ready, egui::Button::new("▶ Run")).clicked() {
+            panel.run_requested = true;
+        }
+        if panel.running {
+            ui.spinner();
+        }
+    });
+    match &panel.result {
+        Some(Ok(summary)) => {
+            ui.label(RichText::new(summary).color(TEXT_SECONDARY).small());
+        }
+        Some(Err(error)) => {
+            ui.label(RichText::new(error).color(ERROR).small());
+        }
+        None => {}
+    }
+}
@@ -0,0 +1,299 @@
+//! File explorer — browse the VFS through the shared [`FileTree`].
+//!
+//! Directories are entered by clicking them, a segment of the breadcrumb
+//! trail, or typing a path into the path bar, which completes against the
+//! cached listings. Back and forward walk the navigation history. Files
+//! are handed to the caller to open in the viewer.
+
+use egui::{self, RichText, ScrollArea};
+
+use crate::file_tree::{FileTree, normalize};
+use crate::panels::viewer::format_size;
+use crate::theme::*;
+use crate::widgets::{icon_button, name_field};
+
+/// Completions shown under the path bar
+const MAX_SUGGESTIONS: usize = 8;
+
+pub struct FileExplorer {
+    pub open: bool,
+    /// Directory shown
+    pub cwd: String,
+    /// Text of the path bar while it is being edited
+    pub path_input: Option<String>,
+    back: Vec<String>,
+    forward: Vec<String>,
+}
+
+impl Default for FileExplorer {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl FileExplorer {
+    /// An explorer showing `root`, with no history
+    pub fn new(root: &str) -> Self {
+        Self {
+            open: false,
+            cwd: normalize(root),
+            path_input: None,
+            back: Vec::new(),
+            forward: Vec::new(),
+        }
+    }
+
+    /// Start over at `root`, forgetting the history
+    pub fn reset(&mut self, root: &str) {
+        *self = Self { open: self.open, ..Self::new(root) };
+    }
+
+    /// Show `dir`, relative to the current directory unless absolute
+    pub fn go_to(&mut self, dir: &str) {
+        let dir = resolve(&self.cwd, dir);
+        if dir == self.cwd {
+            return;
+        }
+        self.back.push(std::mem::replace(&mut self.cwd, dir));
+        self.forward.clear();
+    }
+
+    pub fn go_back(&mut self) {
+        if let Some(dir) = self.back.pop() {
+            self.forward.push(std::mem::replace(&mut self.cwd, dir));
+        }
+    }
+
+    pub fn go_forward(&mut self) {
+        if let Some(dir) = self.forward.pop() {
+            self.back.push(std::mem::replace(&mut self.cwd, dir));
+        }
+    }
+
+    pub fn go_up(&mut self) {
+        self.go_to("..");
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+
+    /// Start editing the path bar, filled with the current directory
+    pub fn edit_path(&mut self) {
+        let cwd = self.cwd.trim_end_matches('/');
+        self.path_input = Some(format!("{}/", cwd));
+    }
+
+    /// Completions of the path bar text from cached listings
+    pub fn suggestions(&self, files: &mut FileTree) -> Vec<String> {
+        let Some(input) = &self.path_input else {
+            return Vec::new();
+        };
+        let absolute = if input.starts_with('/') {
+            input.clone()
+        } else {
+            format!("{}/{}", self.cwd.trim_end_matches('/'), input)
+        };
+        files.complete(&absolute)
+    }
+
+    /// Go to the directory typed in the path bar, or return the path of
+    /// the file it names
+    pub fn submit_path(&mut self, files: &mut FileTree) -> Option<String> {
+        let input = self.path_input.take()?;
+        let path = resolve(&self.cwd, &input);
+        let (parent, name) = match path.rfind('/') {
+            Some(0) => ("/", &path[1..]),
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => return None,
+        };
+        let is_file = files
+            .entries(parent)
+            .is_some_and(|entries| entries.iter().any(|e| e.name == name && !e.is_dir));
+        if is_file {
+            return Some(path);
+        }
+        self.go_to(&path);
+        None
+    }
+}
+
+/// `(label, path)` for each segment of `dir`, starting at the root
+pub fn breadcrumbs(dir: &str) -> Vec<(String, String)> {
+    let mut crumbs = vec![("/".to_string(), "/".to_string())];
+    let mut path = String::new();
+    for segment in dir.split('/').filter(|s| !s.is_empty()) {
+        path.push('/');
+        path.push_str(segment);
+        crumbs.push((segment.to_string(), path.clone()));
+    }
+    crumbs
+}
+
+/// Join `path` onto `cwd` and fold `.` and `..` segments
+pub fn resolve(cwd: &str, path: &str) -> String {
+    let joined = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", cwd, path)
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+/// The explorer window, while `explorer.open`. Returns a file the user
+/// opened.
+pub fn explorer_window(
+    ctx: &egui::Context,
+    explorer: &mut FileExplorer,
+    files: &mut FileTree,
+) -> Option<String> {
+    let mut open = explorer.open;
+    let mut opened = None;
+    egui::Window::new("Files")
+        .open(&mut open)
+        .default_width(360.0)
+        .default_height(420.0)
+        .resizable(true)
+        .show(ctx, |ui| {
+            if let Some(path) = navigation_bar(ui, explorer, files) {
+                opened = Some(path);
+            }
+            ui.separator();
+            if let Some(path) = entry_list(ui, explorer, files) {
+                opened = Some(path);
+            }
+        });
+    explorer.open = open;
+    opened
+}
+
+/// History buttons, then the breadcrumb trail or the path bar
+fn navigation_bar(ui: &mut egui::Ui, explorer: &mut FileExplorer, files: &mut FileTree) -> Option<String> {
+    let mut opened = None;
+    ui.horizontal(|ui| {
+        if icon_button(ui, explorer.can_go_back(), "◀", "Back").clicked() {
+            explorer.go_back();
+        }
+        if icon_button(ui, explorer.can_go_forward(), "▶", "Forward").clicked() {
+            explorer.go_forward();
+        }
+        if icon_button(ui, explorer.cwd != "/", "⬆", "Up").clicked() {
+            explorer.go_up();
+        }
+        if icon_button(ui, true, "⟳", "Refresh").clicked() {
+            files.invalidate(&format!("{}/", explorer.cwd));
+        }
+        ui.separator();
+
+        if explorer.path_input.is_some() {
+            opened = path_bar(ui, explorer, files);
+            return;
+        }
+        ui.spacing_mut().item_spacing.x = 2.0;
+        let crumbs = breadcrumbs(&explorer.cwd);
+        let last = crumbs.len() - 1;
+        for (i, (label, path)) in crumbs.into_iter().enumerate() {
+            if i > 1 {
+                ui.label(RichText::new("›").color(TEXT_SECONDARY));
+            }
+            let text = RichText::new(label).monospace();
+            let text = if i == last { text.color(TEXT_PRIMARY) } else { text.color(TEXT_SECONDARY) };
+            if ui.selectable_label(false, text).clicked() {
+                explorer.go_to(&path);
+            }
+        }
+        if icon_button(ui, true, "✎", "Type a path").clicked() {
+            explorer.edit_path();
+        }
+    });
+
+    if explorer.path_input.is_some() {
+        let suggestions = explorer.suggestions(files);
+        for suggestion in suggestions.into_iter().take(MAX_SUGGESTIONS) {
+            if ui.selectable_label(false, RichText::new(&suggestion).monospace().small()).clicked() {
+                explorer.path_input = Some(suggestion);
+            }
+        }
+    }
+    opened
+}
+
+/// The editable path: Enter goes there, Escape cancels
+fn path_bar(ui: &mut egui::Ui, explorer: &mut FileExplorer, files: &mut FileTree) -> Option<String> {
+    let input = explorer.path_input.get_or_insert_with(String::new);
+    let response = ui.add(
+        egui::TextEdit::singleline(input)
+            .font(egui::TextStyle::Monospace)
+            .desired_width(f32::INFINITY),
+    );
+    name_field(&response, "Path");
+    if !response.has_focus() && !response.lost_focus() {
+        response.request_focus();
+    }
+    if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+        explorer.path_input = None;
+        return None;
+    }
+    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+        return explorer.submit_path(files);
+    }
+    None
+}
+
+/// Entries of the current directory, directories first
+fn entry_list(ui: &mut egui::Ui, explorer: &mut FileExplorer, files: &mut FileTree) -> Option<String> {
+    if let Some(error) = files.error(&explorer.cwd) {
+        ui.label(RichText::new(error).color(ERROR));
+        return None;
+    }
+    let Some(entries) = files.entries(&explorer.cwd) else {
+        ui.spinner();
+        return None;
+    };
+    if entries.is_empty() {
+        ui.label(RichText::new("Empty directory").color(TEXT_SECONDARY));
+        return None;
+    }
+    let mut entries = entries.to_vec();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+    let mut opened = None;
+    ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+        for entry in &entries {
+            ui.horizontal(|ui| {
+                let icon = if entry.is_dir { "📁" } else { "📄" };
+                let label = RichText::new(format!("{} {}", icon, entry.name)).color(TEXT_PRIMARY);
+                let clicked = ui.selectable_label(false, label).clicked();
+                if !entry.is_dir {
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label(RichText::new(format_size(entry.size)).color(TEXT_SECONDARY).small());
+                    });
+                }
+                if !clicked {
+                    return;
+                }
+                let path = resolve(&explorer.cwd, &entry.name);
+                if entry.is_dir {
+                    explorer.go_to(&path);
+                } else {
+                    opened = Some(path);
+                }
+            });
+        }
+    });
+    opened
+}
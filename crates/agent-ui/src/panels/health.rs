@@ -0,0 +1,104 @@
+//! Health indicator — a dot each for the network, the LLM provider and the
+//! shell worker in the top bar, with details on hover.
+//!
+//! The state is kept up to date from agent events: the runtime reports
+//! every LLM request with its latency, the app reports when the shell
+//! worker is ready or gives up on it, and the browser's online state is
+//! tracked with the outbox.
+
+use egui::{self, RichText};
+
+use crate::theme::*;
+
+/// Slower replies are shown as degraded
+pub const SLOW_LLM_MS: u64 = 15_000;
+
+/// The last LLM request, as reported by the runtime
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlmCall {
+    pub model: String,
+    pub latency_ms: u64,
+    /// Why it failed, if it did
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ShellHealth {
+    #[default]
+    Starting,
+    Ready,
+    Unavailable(String),
+}
+
+#[derive(Debug, Default)]
+pub struct Health {
+    pub last_llm: Option<LlmCall>,
+    pub shell: ShellHealth,
+}
+
+/// How a dot is colored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Good,
+    Degraded,
+    Down,
+    Unknown,
+}
+
+impl Level {
+    fn color(self) -> ThemeColor {
+        match self {
+            Level::Good => SUCCESS,
+            Level::Degraded => WARNING,
+            Level::Down => ERROR,
+            Level::Unknown => TEXT_SECONDARY,
+        }
+    }
+}
+
+impl Health {
+    /// The provider's dot and its hover text
+    pub fn llm_status(&self) -> (Level, String) {
+        match &self.last_llm {
+            None => (Level::Unknown, "LLM: no requests yet".to_string()),
+            Some(LlmCall { model, latency_ms, error: Some(error) }) => (
+                Level::Down,
+                format!("LLM: last request to {} failed after {} ms\n{}", model, latency_ms, error),
+            ),
+            Some(LlmCall { model, latency_ms, error: None }) => {
+                let level = if *latency_ms > SLOW_LLM_MS { Level::Degraded } else { Level::Good };
+                (level, format!("LLM: last reply from {} took {} ms", model, latency_ms))
+            }
+        }
+    }
+
+    /// The shell worker's dot and its hover text
+    pub fn shell_status(&self) -> (Level, String) {
+        match &self.shell {
+            ShellHealth::Starting => (Level::Degraded, "Shell: worker starting…".to_string()),
+            ShellHealth::Ready => (Level::Good, "Shell: worker ready".to_string()),
+            ShellHealth::Unavailable(reason) => (Level::Down, format!("Shell: unavailable\n{}", reason)),
+        }
+    }
+}
+
+/// The network's dot and its hover text
+pub fn network_status(online: bool) -> (Level, String) {
+    if online {
+        (Level::Good, "Network: online".to_string())
+    } else {
+        (Level::Down, "Network: offline — messages are queued until the connection is back".to_string())
+    }
+}
+
+fn dot(ui: &mut egui::Ui, name: &str, (level, detail): (Level, String)) {
+    ui.label(RichText::new(format!("● {}", name)).color(level.color()).small())
+        .on_hover_text(detail);
+}
+
+/// The three dots, for the top bar
+pub fn health_indicator(ui: &mut egui::Ui, health: &Health, online: bool) {
+    dot(ui, "Shell", health.shell_status());
+    dot(ui, "LLM", health.llm_status());
+    dot(ui, "Net", network_status(online));
+}
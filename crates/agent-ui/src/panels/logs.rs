@@ -0,0 +1,89 @@
+//! Log viewer — the in-app log (`agent_core::logs`), filtered by level
+//! and text.
+//!
+//! Saving is the app's job: "Download" raises `download_requested`.
+
+use egui::{self, RichText};
+use log::Level;
+use agent_core::logs::{LogBuffer, LogFilter};
+
+use crate::theme::*;
+
+const LEVELS: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+#[derive(Default)]
+pub struct LogPanel {
+    pub open: bool,
+    pub filter: LogFilter,
+    pub download_requested: bool,
+}
+
+fn level_color(level: Level) -> ThemeColor {
+    match level {
+        Level::Error => ERROR,
+        Level::Warn => WARNING,
+        Level::Info => TEXT_PRIMARY,
+        Level::Debug | Level::Trace => TEXT_SECONDARY,
+    }
+}
+
+/// The log window, while `panel.open`.
+pub fn log_window(ctx: &egui::Context, panel: &mut LogPanel, buffer: &mut LogBuffer) {
+    let mut open = panel.open;
+    egui::Window::new("Logs")
+        .open(&mut open)
+        .default_width(640.0)
+        .default_height(360.0)
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("log_level")
+                    .selected_text(format!("{} and up", panel.filter.level))
+                    .show_ui(ui, |ui| {
+                        for level in LEVELS {
+                            ui.selectable_value(&mut panel.filter.level, level, level.as_str());
+                        }
+                    });
+                ui.add(
+                    egui::TextEdit::singleline(&mut panel.filter.text)
+                        .hint_text("Filter by target or message")
+                        .desired_width(200.0),
+                );
+                if ui.button("Clear").on_hover_text("Forget the records so far").clicked() {
+                    buffer.clear();
+                }
+                if ui.button("⬇ Download").on_hover_text("Save every record as a text file").clicked() {
+                    panel.download_requested = true;
+                }
+            });
+
+            let shown: Vec<_> = buffer.records().iter().filter(|r| panel.filter.matches(r)).collect();
+            let mut count = format!("{} of {} records", shown.len(), buffer.records().len());
+            if buffer.dropped() > 0 {
+                count.push_str(&format!(", {} older dropped", buffer.dropped()));
+            }
+            ui.label(RichText::new(count).color(TEXT_SECONDARY).small());
+            ui.separator();
+
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::both()
+                .auto_shrink([false, false])
+                .stick_to_bottom(true)
+                .show_rows(ui, row_height, shown.len(), |ui, rows| {
+                    for record in &shown[rows] {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(record.time()).monospace().color(TEXT_SECONDARY));
+                            ui.label(
+                                RichText::new(format!("{:<5}", record.level))
+                                    .monospace()
+                                    .color(level_color(record.level)),
+                            );
+                            ui.label(RichText::new(&record.target).monospace().color(ACCENT))
+                                .on_hover_text(&record.timestamp);
+                            ui.label(RichText::new(&record.message).monospace().color(TEXT_PRIMARY));
+                        });
+                    }
+                });
+        });
+    panel.open = open;
+}
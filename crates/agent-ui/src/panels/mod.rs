@@ -1,3 +1,16 @@
 pub mod chat;
 pub mod terminal;
 pub mod settings;
+pub mod viewer;
+pub mod editor;
+pub mod prompts;
+pub mod vault;
+pub mod stats;
+pub mod eval;
+pub mod explorer;
+pub mod health;
+pub mod batch;
+pub mod plan;
+pub mod logs;
+pub mod perf;
+pub mod diagnostics;
@@ -0,0 +1,79 @@
+//! Performance overlay — a small corner panel with a frame time graph and
+//! the counts that grow when rendering or the event flow goes wrong.
+//!
+//! The app collects the numbers (see `agent_core::perf`); this only draws
+//! them.
+
+use egui::{self, RichText, Sense, Stroke, Vec2};
+use agent_core::perf::{FRAME_BUDGET_MS, FRAME_HISTORY, FrameStats};
+
+use crate::theme::*;
+
+/// Frame time at the top of the graph, in milliseconds
+const GRAPH_MAX_MS: f32 = 50.0;
+
+/// Counts shown below the graph
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PerfCounts {
+    pub pending_storage: usize,
+    pub storage_ops: u64,
+    pub messages: usize,
+    pub terminal_lines: usize,
+}
+
+/// The overlay, in the bottom right corner
+pub fn perf_overlay(ctx: &egui::Context, frames: &FrameStats, counts: PerfCounts) {
+    egui::Area::new(egui::Id::new("perf_overlay"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, Vec2::new(-8.0, -8.0))
+        .order(egui::Order::Foreground)
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_width(260.0);
+                frame_graph(ui, frames);
+
+                let summary = frames.summary();
+                let last = frames.last().copied().unwrap_or_default();
+                let cpu = |ms: Option<f32>| ms.map_or("–".to_string(), |ms| format!("{:.1} ms", ms));
+                let rows = [
+                    (
+                        "Frame",
+                        format!("{:.1} ms (avg {:.1}, max {:.1})", last.interval_ms, summary.mean_interval_ms, summary.max_interval_ms),
+                    ),
+                    ("Build", format!("{} (avg {:.1} ms, {} slow)", cpu(last.cpu_ms), summary.mean_cpu_ms, summary.slow_frames)),
+                    ("Events", format!("{} this frame (max {})", last.events, summary.max_events)),
+                    ("Storage", format!("{} pending ({} total)", counts.pending_storage, counts.storage_ops)),
+                    ("Messages", counts.messages.to_string()),
+                    ("Terminal", format!("{} lines", counts.terminal_lines)),
+                ];
+                egui::Grid::new("perf_counts").num_columns(2).show(ui, |ui| {
+                    for (label, value) in rows {
+                        ui.label(RichText::new(label).color(TEXT_SECONDARY).small());
+                        ui.label(RichText::new(value).monospace().small().color(TEXT_PRIMARY));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+}
+
+/// One bar per frame: build time if measured, else the frame interval;
+/// the line marks 60 fps
+fn frame_graph(ui: &mut egui::Ui, frames: &FrameStats) {
+    let size = Vec2::new(ui.available_width(), 60.0);
+    let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, PANEL_ROUNDING, BG_SURFACE);
+
+    let y = |ms: f32| rect.bottom() - rect.height() * (ms / GRAPH_MAX_MS).min(1.0);
+    let slot = rect.width() / FRAME_HISTORY as f32;
+    let offset = FRAME_HISTORY - frames.samples().len();
+    for (i, sample) in frames.samples().iter().enumerate() {
+        let ms = sample.cpu_ms.unwrap_or(sample.interval_ms);
+        let color = if ms > FRAME_BUDGET_MS { WARNING.color() } else { ACCENT.color() };
+        let x = rect.left() + (offset + i) as f32 * slot;
+        let bar = egui::Rect::from_min_max(egui::pos2(x, y(ms)), egui::pos2(x + slot.max(1.0), rect.bottom()));
+        painter.rect_filled(bar, 0.0, color);
+    }
+    painter.hline(rect.x_range(), y(FRAME_BUDGET_MS), Stroke::new(1.0, TEXT_SECONDARY));
+}
@@ -0,0 +1,103 @@
+//! Plan window — the steps of a planned turn and how far each has got.
+//!
+//! Filled in from the orchestrator's events: the window opens when a plan
+//! arrives and shows each step's status, with the executor's summary (or
+//! why the step failed) under it.
+
+use egui::{self, RichText};
+
+use crate::theme::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanStepView {
+    pub title: String,
+    pub status: StepStatus,
+    /// The executor's reply, or why the step failed
+    pub summary: Option<String>,
+}
+
+#[derive(Default)]
+pub struct PlanView {
+    pub open: bool,
+    /// Steps of the latest plan
+    pub steps: Vec<PlanStepView>,
+}
+
+impl PlanView {
+    /// A new plan arrived
+    pub fn show_plan(&mut self, titles: Vec<String>) {
+        self.steps = titles
+            .into_iter()
+            .map(|title| PlanStepView { title, status: StepStatus::Pending, summary: None })
+            .collect();
+        self.open = true;
+    }
+
+    pub fn start_step(&mut self, index: usize) {
+        if let Some(step) = self.steps.get_mut(index) {
+            step.status = StepStatus::Running;
+        }
+    }
+
+    pub fn finish_step(&mut self, index: usize, success: bool, summary: String) {
+        if let Some(step) = self.steps.get_mut(index) {
+            step.status = if success { StepStatus::Done } else { StepStatus::Failed };
+            step.summary = Some(summary).filter(|s| !s.trim().is_empty());
+        }
+    }
+
+    /// e.g. "Step 2/5: Update the callers"
+    pub fn progress(&self, index: usize) -> String {
+        let title = self.steps.get(index).map(|step| step.title.as_str()).unwrap_or_default();
+        format!("Step {}/{}: {}", index + 1, self.steps.len(), title)
+    }
+}
+
+fn status_label(status: StepStatus) -> RichText {
+    match status {
+        StepStatus::Pending => RichText::new("○").color(TEXT_SECONDARY),
+        StepStatus::Running => RichText::new("▶").color(ACCENT),
+        StepStatus::Done => RichText::new("✔").color(SUCCESS),
+        StepStatus::Failed => RichText::new("✖").color(ERROR),
+    }
+}
+
+/// The plan window, while open
+pub fn plan_window(ctx: &egui::Context, view: &mut PlanView) {
+    let mut open = view.open;
+    egui::Window::new("Plan")
+        .open(&mut open)
+        .default_width(420.0)
+        .resizable(true)
+        .show(ctx, |ui| {
+            if view.steps.is_empty() {
+                ui.label(
+                    RichText::new("No plan yet. Turn on planning in Settings to have requests split into steps.")
+                        .color(TEXT_SECONDARY),
+                );
+                return;
+            }
+            egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                for (i, step) in view.steps.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(status_label(step.status));
+                        ui.label(RichText::new(format!("{}. {}", i + 1, step.title)).color(TEXT_PRIMARY).strong());
+                    });
+                    if let Some(summary) = &step.summary {
+                        let color = if step.status == StepStatus::Failed { ERROR } else { TEXT_SECONDARY };
+                        ui.label(RichText::new(summary).color(color).small());
+                    }
+                    ui.add_space(4.0);
+                }
+            });
+        });
+    view.open = open;
+}
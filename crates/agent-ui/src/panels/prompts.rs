@@ -0,0 +1,222 @@
+//! Prompt library picker — save the chat input as a snippet, insert saved
+//! snippets, and fill in their `{{placeholders}}`.
+//!
+//! Placeholders are filled in a small form with a field per variable kind:
+//! a line or a box of text, a choice, or a VFS path completed from the
+//! shared [`FileTree`].
+//!
+//! Storage is the app's job: the picker shows `snippets` as loaded by the
+//! app and queues [`PromptAction`]s for it to persist.
+
+use egui::{self, RichText};
+use agent_types::prompt::{PromptSnippet, PromptVariable, VariableKind};
+
+use crate::file_tree::FileTree;
+use crate::theme::*;
+use crate::widgets::{icon_button, name_field};
+
+/// Path completions shown under a file field
+const MAX_PATH_SUGGESTIONS: usize = 6;
+
+/// A library change for the app to persist
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptAction {
+    Save(PromptSnippet),
+    Delete(String),
+}
+
+#[derive(Default)]
+pub struct PromptPicker {
+    /// Snippets as last loaded by the app, sorted by name
+    pub snippets: Vec<PromptSnippet>,
+    /// Name for saving the current input
+    pub save_name: String,
+    /// Snippet whose placeholders are being filled, with the values so far
+    pub filling: Option<(PromptSnippet, Vec<(String, String)>)>,
+    actions: Vec<PromptAction>,
+}
+
+impl PromptPicker {
+    /// Pending library changes. Called by the app each frame.
+    pub fn take_actions(&mut self) -> Vec<PromptAction> {
+        std::mem::take(&mut self.actions)
+    }
+
+    /// Save `body` under `name`, showing it in the list right away
+    pub fn save(&mut self, name: &str, body: &str) {
+        let snippet = PromptSnippet::new(name.trim(), body);
+        self.snippets.push(snippet.clone());
+        self.snippets.sort_by_key(|s| s.name.to_lowercase());
+        self.actions.push(PromptAction::Save(snippet));
+    }
+
+    pub fn delete(&mut self, id: &str) {
+        self.snippets.retain(|s| s.id != id);
+        self.actions.push(PromptAction::Delete(id.to_string()));
+    }
+
+    /// Insert a snippet into `input`, or start asking for its placeholders
+    pub fn insert(&mut self, snippet: &PromptSnippet, input: &mut String) {
+        let variables = snippet.variables();
+        if variables.is_empty() {
+            append(input, &snippet.body);
+        } else {
+            let values = variables.iter().map(|v| (v.name.clone(), v.initial_value())).collect();
+            self.filling = Some((snippet.clone(), values));
+        }
+    }
+
+    /// Insert the snippet being filled, with the values entered
+    pub fn finish_fill(&mut self, input: &mut String) {
+        if let Some((snippet, values)) = self.filling.take() {
+            append(input, &snippet.fill(&values));
+        }
+    }
+}
+
+fn append(input: &mut String, text: &str) {
+    if !input.trim().is_empty() && !input.ends_with(char::is_whitespace) {
+        input.push(' ');
+    }
+    input.push_str(text);
+}
+
+/// The "Prompts" menu button for the chat input row.
+pub fn prompt_menu(ui: &mut egui::Ui, picker: &mut PromptPicker, input: &mut String) {
+    ui.menu_button("Prompts", |ui| {
+        ui.set_min_width(220.0);
+        ui.label(RichText::new("Save input as prompt").color(TEXT_SECONDARY).small());
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut picker.save_name)
+                    .hint_text("Name")
+                    .desired_width(140.0),
+            );
+            let can_save = !picker.save_name.trim().is_empty() && !input.trim().is_empty();
+            if ui.add_enabled(can_save, egui::Button::new("Save")).clicked() {
+                let name = std::mem::take(&mut picker.save_name);
+                picker.save(&name, input.trim());
+            }
+        });
+        ui.separator();
+
+        if picker.snippets.is_empty() {
+            ui.label(
+                RichText::new(
+                    "No saved prompts. Use {{name}} in a prompt for a value to fill in, \
+                     or {{name:file}}, {{name:multiline}}, {{name:a|b}} for a path, longer text or a choice.",
+                )
+                    .color(TEXT_SECONDARY)
+                    .small(),
+            );
+            return;
+        }
+        let mut insert = None;
+        let mut delete = None;
+        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            for snippet in &picker.snippets {
+                ui.horizontal(|ui| {
+                    if ui.button(&snippet.name).on_hover_text(&snippet.body).clicked() {
+                        insert = Some(snippet.clone());
+                    }
+                    if icon_button(ui, true, "🗑", &format!("Delete {}", snippet.name)).clicked() {
+                        delete = Some(snippet.id.clone());
+                    }
+                });
+            }
+        });
+        if let Some(snippet) = insert {
+            picker.insert(&snippet, input);
+            ui.close();
+        }
+        if let Some(id) = delete {
+            picker.delete(&id);
+        }
+    });
+}
+
+/// Ask for placeholder values while a snippet is being inserted.
+pub fn fill_dialog(ctx: &egui::Context, picker: &mut PromptPicker, input: &mut String, files: &mut FileTree) {
+    let Some((snippet, values)) = picker.filling.as_mut() else {
+        return;
+    };
+    let variables = snippet.variables();
+    let mut done = false;
+    let mut cancelled = false;
+    let modal = egui::Modal::new(egui::Id::new("prompt_fill_dialog")).show(ctx, |ui| {
+        ui.set_max_width(360.0);
+        ui.heading(&snippet.name);
+        egui::Grid::new("prompt_fill_fields").num_columns(2).show(ui, |ui| {
+            for (variable, (_, value)) in variables.iter().zip(values.iter_mut()) {
+                ui.label(RichText::new(variable.name.as_str()).color(TEXT_SECONDARY));
+                variable_field(ui, variable, value, files);
+                ui.end_row();
+            }
+        });
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            if ui.button("Insert").clicked() {
+                done = true;
+            }
+            if ui.button("Cancel").clicked() {
+                cancelled = true;
+            }
+        });
+    });
+    if done {
+        picker.finish_fill(input);
+    } else if cancelled || modal.should_close() {
+        picker.filling = None;
+    }
+}
+
+/// The input for one variable, by its kind
+fn variable_field(ui: &mut egui::Ui, variable: &PromptVariable, value: &mut String, files: &mut FileTree) {
+    match &variable.kind {
+        VariableKind::Text => {
+            let response = ui.text_edit_singleline(value);
+            name_field(&response, &variable.name);
+        }
+        VariableKind::Multiline => {
+            let response = ui.add(egui::TextEdit::multiline(value).desired_rows(3));
+            name_field(&response, &variable.name);
+        }
+        VariableKind::Choice(options) => {
+            egui::ComboBox::from_id_salt(("prompt_choice", &variable.name))
+                .selected_text(value.as_str())
+                .show_ui(ui, |ui| {
+                    for option in options {
+                        ui.selectable_value(value, option.clone(), option);
+                    }
+                });
+        }
+        VariableKind::File => {
+            ui.vertical(|ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(value)
+                        .hint_text("/workspace/…")
+                        .font(egui::TextStyle::Monospace),
+                );
+                name_field(&response, &variable.name);
+                for suggestion in path_suggestions(value, files) {
+                    if ui.selectable_label(false, RichText::new(&suggestion).monospace().small()).clicked() {
+                        *value = suggestion;
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Completions of a file field from cached listings, starting in the
+/// workspace while it is empty. A path already naming a file has none.
+pub fn path_suggestions(value: &str, files: &mut FileTree) -> Vec<String> {
+    let partial = if value.trim().is_empty() { "/workspace/" } else { value.trim() };
+    let mut suggestions = files.complete(partial);
+    if suggestions.iter().any(|s| s == partial) {
+        return Vec::new();
+    }
+    suggestions.sort();
+    suggestions.truncate(MAX_PATH_SUGGESTIONS);
+    suggestions
+}
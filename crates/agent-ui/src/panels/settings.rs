@@ -1,15 +1,38 @@
 //! Settings panel — LLM provider config, model selection, API key input.
 
+use std::collections::BTreeMap;
+
 use egui::{self, RichText};
-use agent_types::config::{AgentConfig, LlmProvider};
+use agent_core::models::MODEL_LIST_TTL_SECS;
+use agent_core::ports::StorageUsage;
+use agent_core::tools::TOOL_NAMES;
+use agent_types::config::{
+    AgentConfig, ChatDensity, ContextStrategyKind, FallbackTarget, FontLoading, HarmCategory, HarmThreshold,
+    LlmProvider, MAX_CHAT_WIDTH, Persona, ReasoningEffort, ShellConfig, ThemePreset, ToolOutputConfig, UI_SCALE_RANGE,
+};
+use agent_types::migration::ConfigChange;
+use agent_types::models::ModelList;
+use agent_types::pricing::{ModelPrice, price_for};
+use crate::panels::viewer::format_size;
 use crate::theme::*;
+use crate::widgets::{icon_button, name_field};
+
+/// The provider's model list as shown in the settings panel.
+/// Filled in by the app; the panel only sets `refresh_requested`.
+#[derive(Debug, Default)]
+pub struct ModelOptions {
+    pub list: Option<ModelList>,
+    pub loading: bool,
+    pub error: Option<String>,
+    pub refresh_requested: bool,
+}
 
 /// Render the settings panel. Returns true if settings were modified.
-pub fn settings_panel(ui: &mut egui::Ui, config: &mut AgentConfig) -> bool {
+pub fn settings_panel(ui: &mut egui::Ui, config: &mut AgentConfig, models: &mut ModelOptions) -> bool {
     let mut changed = false;
 
     egui::Frame::default()
-        .fill(BG_SECONDARY)
+        .fill(BG_SECONDARY.color())
         .inner_margin(PANEL_PADDING)
         .corner_radius(PANEL_ROUNDING)
         .show(ui, |ui| {
@@ -26,6 +49,10 @@ pub fn settings_panel(ui: &mut egui::Ui, config: &mut AgentConfig) -> bool {
                             .selectable_value(&mut config.llm.provider, p.clone(), p.label())
                             .changed()
                         {
+                            let model = config.llm.provider.default_model();
+                            if !model.is_empty() {
+                                config.llm.model = model.to_string();
+                            }
                             changed = true;
                         }
                     }
@@ -41,38 +68,91 @@ pub fn settings_panel(ui: &mut egui::Ui, config: &mut AgentConfig) -> bool {
             {
                 changed = true;
             }
+            changed |= model_picker(ui, &mut config.llm.model, models);
 
             ui.add_space(4.0);
 
             // API Key (masked)
-            ui.label(RichText::new("API Key").color(TEXT_SECONDARY).small());
-            let api_key_edit = egui::TextEdit::singleline(&mut config.llm.api_key)
-                .password(true)
-                .hint_text("sk-...");
-            if ui.add(api_key_edit).changed() {
-                changed = true;
+            if config.llm.provider.requires_api_key() {
+                ui.label(RichText::new("API Key").color(TEXT_SECONDARY).small());
+                let api_key_edit = egui::TextEdit::singleline(&mut config.llm.api_key)
+                    .password(true)
+                    .hint_text("sk-...");
+                let response = ui.add(api_key_edit);
+                name_field(&response, "API key");
+                if response.changed() {
+                    changed = true;
+                }
+            } else if config.llm.provider == LlmProvider::Demo {
+                ui.label(
+                    RichText::new("Scripted answers that show off the shell and files — \
+                                   nothing is sent anywhere. Try \"run a command\" or \"write a file\".")
+                        .color(TEXT_SECONDARY)
+                        .small(),
+                );
+            } else {
+                ui.label(
+                    RichText::new("Runs in your browser via WebGPU — no API key needed. \
+                                   The model is downloaded on first use.")
+                        .color(TEXT_SECONDARY)
+                        .small(),
+                );
             }
 
             ui.add_space(4.0);
 
             // Custom base URL
-            ui.label(RichText::new("API Base URL (optional)").color(TEXT_SECONDARY).small());
-            let mut base_url = config.llm.api_base.clone().unwrap_or_default();
-            if ui
-                .add(
-                    egui::TextEdit::singleline(&mut base_url)
-                        .hint_text(config.llm.provider.default_base_url()),
-                )
-                .changed()
-            {
-                config.llm.api_base = if base_url.is_empty() {
-                    None
-                } else {
-                    Some(base_url)
-                };
-                changed = true;
+            if config.llm.provider != LlmProvider::Demo {
+                ui.label(RichText::new("API Base URL (optional)").color(TEXT_SECONDARY).small());
+                let mut base_url = config.llm.api_base.clone().unwrap_or_default();
+                if ui
+                    .add(
+                        egui::TextEdit::singleline(&mut base_url)
+                            .hint_text(config.llm.provider.default_base_url()),
+                    )
+                    .changed()
+                {
+                    config.llm.api_base = if base_url.is_empty() {
+                        None
+                    } else {
+                        Some(base_url)
+                    };
+                    changed = true;
+                }
+            }
+
+            if !matches!(config.llm.provider, LlmProvider::Local | LlmProvider::Demo) {
+                ui.add_space(4.0);
+                ui.label(RichText::new("CORS Proxy (optional)").color(TEXT_SECONDARY).small());
+                let mut proxy = config.llm.proxy_url.clone().unwrap_or_default();
+                if ui
+                    .add(
+                        egui::TextEdit::singleline(&mut proxy)
+                            .hint_text("https://proxy.example/ or …?url={url}"),
+                    )
+                    .on_hover_text(
+                        "For providers that block requests from a browser. \
+                         Requests go to {proxy}/{original URL}, or {url} in the proxy is replaced.",
+                    )
+                    .changed()
+                {
+                    config.llm.proxy_url = if proxy.trim().is_empty() { None } else { Some(proxy) };
+                    changed = true;
+                }
             }
 
+            egui::CollapsingHeader::new(RichText::new("Fallback Providers").color(TEXT_SECONDARY).small())
+                .id_salt("llm_fallbacks")
+                .show(ui, |ui| {
+                    ui.label(
+                        RichText::new("Tried in order when the provider above fails with a \
+                                       rate limit, server or network error.")
+                            .color(TEXT_SECONDARY)
+                            .small(),
+                    );
+                    changed |= fallback_rows(ui, &mut config.llm.fallbacks, &config.llm.provider);
+                });
+
             ui.add_space(4.0);
 
             // Temperature
@@ -92,7 +172,1006 @@ pub fn settings_panel(ui: &mut egui::Ui, config: &mut AgentConfig) -> bool {
             {
                 changed = true;
             }
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Request Timeout").color(TEXT_SECONDARY).small());
+                changed |= ui
+                    .add(egui::DragValue::new(&mut config.llm.timeout_secs).range(0..=3600).suffix(" s"))
+                    .on_hover_text("Give up on a reply after this long; 0 waits forever")
+                    .changed();
+            });
+
+            egui::CollapsingHeader::new(RichText::new("Pricing").color(TEXT_SECONDARY).small())
+                .id_salt("llm_pricing")
+                .show(ui, |ui| {
+                    changed |= price_rows(ui, &mut config.prices, &config.llm.model);
+                });
+
+            // Advanced sampling
+            egui::CollapsingHeader::new(RichText::new("Advanced").color(TEXT_SECONDARY).small())
+                .id_salt("llm_advanced")
+                .show(ui, |ui| {
+                    changed |= optional_slider(ui, "Top P", &mut config.llm.top_p, 0.0..=1.0, 1.0);
+                    changed |= optional_slider(
+                        ui,
+                        "Frequency Penalty",
+                        &mut config.llm.frequency_penalty,
+                        -2.0..=2.0,
+                        0.0,
+                    );
+                    changed |= optional_slider(
+                        ui,
+                        "Presence Penalty",
+                        &mut config.llm.presence_penalty,
+                        -2.0..=2.0,
+                        0.0,
+                    );
+
+                    ui.label(RichText::new("Stop Sequences").color(TEXT_SECONDARY).small());
+                    let mut stop = config.llm.stop.join(", ");
+                    if ui
+                        .add(egui::TextEdit::singleline(&mut stop).hint_text("comma-separated"))
+                        .changed()
+                    {
+                        config.llm.stop = stop
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|s| !s.is_empty())
+                            .map(String::from)
+                            .collect();
+                        changed = true;
+                    }
+
+                    ui.horizontal(|ui| {
+                        let mut fixed = config.llm.seed.is_some();
+                        if ui.checkbox(&mut fixed, "Seed").changed() {
+                            config.llm.seed = fixed.then_some(0);
+                            changed = true;
+                        }
+                        if let Some(seed) = config.llm.seed.as_mut() {
+                            if ui.add(egui::DragValue::new(seed)).changed() {
+                                changed = true;
+                            }
+                        }
+                    });
+
+                    ui.label(RichText::new("Custom Headers").color(TEXT_SECONDARY).small());
+                    changed |= pair_rows(ui, &mut config.llm.headers, ("Name", "Value"), "header");
+
+                    // Reasoning: Anthropic takes a thinking budget, the
+                    // OpenAI-style APIs an effort level
+                    let reasoning = &mut config.llm.reasoning;
+                    match config.llm.provider {
+                        LlmProvider::Anthropic => {
+                            ui.horizontal(|ui| {
+                                let mut thinking = reasoning.budget_tokens.is_some();
+                                if ui.checkbox(&mut thinking, "Extended thinking").changed() {
+                                    reasoning.budget_tokens = thinking.then_some(4096);
+                                    changed = true;
+                                }
+                                if let Some(budget) = reasoning.budget_tokens.as_mut() {
+                                    if ui
+                                        .add(egui::DragValue::new(budget).range(1024..=65536).suffix(" tokens"))
+                                        .changed()
+                                    {
+                                        changed = true;
+                                    }
+                                }
+                            });
+                        }
+                        LlmProvider::OpenAI | LlmProvider::Google | LlmProvider::Custom => {
+                            ui.label(RichText::new("Reasoning Effort").color(TEXT_SECONDARY).small());
+                            egui::ComboBox::from_id_salt("reasoning_effort")
+                                .selected_text(reasoning.effort.map_or("Default", |e| e.as_str()))
+                                .show_ui(ui, |ui| {
+                                    changed |= ui
+                                        .selectable_value(&mut reasoning.effort, None, "Default")
+                                        .changed();
+                                    for effort in ReasoningEffort::all() {
+                                        changed |= ui
+                                            .selectable_value(&mut reasoning.effort, Some(*effort), effort.as_str())
+                                            .changed();
+                                    }
+                                });
+                        }
+                        LlmProvider::DeepSeek | LlmProvider::Local | LlmProvider::Demo => {}
+                    }
+
+                    if config.llm.provider == LlmProvider::Google {
+                        ui.label(RichText::new("Safety Filters").color(TEXT_SECONDARY).small());
+                        egui::Grid::new("safety_settings").num_columns(2).show(ui, |ui| {
+                            for category in HarmCategory::all() {
+                                ui.label(category.label());
+                                let mut threshold = config.llm.safety_threshold(*category);
+                                egui::ComboBox::from_id_salt(("safety", category.as_str()))
+                                    .selected_text(threshold.map_or("Default", |t| t.label()))
+                                    .show_ui(ui, |ui| {
+                                        let mut picked = ui.selectable_value(&mut threshold, None, "Default").changed();
+                                        for option in HarmThreshold::all() {
+                                            picked |= ui
+                                                .selectable_value(&mut threshold, Some(*option), option.label())
+                                                .changed();
+                                        }
+                                        if picked {
+                                            config.llm.set_safety_threshold(*category, threshold);
+                                            changed = true;
+                                        }
+                                    });
+                                ui.end_row();
+                            }
+                        });
+                    }
+                });
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Notifications
+            ui.label(RichText::new("Notifications").color(TEXT_SECONDARY).small());
+            if ui
+                .checkbox(
+                    &mut config.ui.notify_on_turn_end,
+                    "Notify when a turn finishes in a background tab",
+                )
+                .changed()
+            {
+                changed = true;
+            }
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Accessibility
+            ui.label(RichText::new("Accessibility").color(TEXT_SECONDARY).small());
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Theme").color(TEXT_SECONDARY).small());
+                for preset in ThemePreset::all() {
+                    changed |= ui.selectable_value(&mut config.ui.theme, *preset, preset.label()).changed();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("UI scale").color(TEXT_SECONDARY).small());
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut config.ui.scale, UI_SCALE_RANGE)
+                            .step_by(0.05)
+                            .custom_formatter(|s, _| format!("{:.0}%", s * 100.0)),
+                    )
+                    .changed();
+            });
+            changed |= ui
+                .checkbox(&mut config.ui.screen_reader, "Screen reader")
+                .on_hover_text("Read focused and clicked controls aloud with the browser's speech synthesis")
+                .changed();
+
+            ui.label(RichText::new("CJK fonts").color(TEXT_SECONDARY).small());
+            let fonts = &mut config.ui.fonts;
+            ui.horizontal(|ui| {
+                for loading in FontLoading::all() {
+                    changed |= ui.selectable_value(&mut fonts.loading, *loading, loading.label()).changed();
+                }
+            });
+            let mut urls = fonts.urls.join("\n");
+            let response = ui.add(
+                egui::TextEdit::multiline(&mut urls)
+                    .desired_rows(2)
+                    .hint_text("Font URLs, one per line")
+                    .font(egui::TextStyle::Monospace),
+            );
+            name_field(&response, "Font URLs");
+            if response.changed() {
+                // Blank lines are kept while editing and skipped when fetching
+                fonts.urls = urls.split('\n').map(String::from).collect();
+                changed = true;
+            }
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Chat layout
+            ui.label(RichText::new("Chat").color(TEXT_SECONDARY).small());
+            let chat = &mut config.ui.chat;
+            ui.horizontal(|ui| {
+                for density in ChatDensity::all() {
+                    changed |= ui.selectable_value(&mut chat.density, *density, density.label()).changed();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Message width").color(TEXT_SECONDARY).small());
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut chat.max_width)
+                            .range(0.0..=MAX_CHAT_WIDTH)
+                            .speed(10.0)
+                            .custom_formatter(|w, _| if w == 0.0 { "full".to_string() } else { format!("{:.0} pt", w) }),
+                    )
+                    .changed();
+            });
+            changed |= ui.checkbox(&mut chat.bubbles, "Show messages as bubbles").changed();
+            changed |= ui.checkbox(&mut chat.monospace_assistant, "Monospace font for answers").changed();
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Save pastes longer than").color(TEXT_SECONDARY).small());
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut chat.paste_file_chars)
+                            .speed(100.0)
+                            .custom_formatter(|n, _| if n == 0.0 { "never".to_string() } else { format!("{:.0} chars", n) }),
+                    )
+                    .on_hover_text("Offer to save a large paste as a workspace file and send a reference to it instead")
+                    .changed();
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Session recording
+            ui.label(RichText::new("Recording").color(TEXT_SECONDARY).small());
+            changed |= ui
+                .checkbox(&mut config.record_sessions, "Record LLM and shell responses")
+                .on_hover_text("Sessions recorded this way can be replayed offline, without an API key")
+                .changed();
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Local OpenAI-compatible endpoint
+            ui.label(RichText::new("Local Endpoint").color(TEXT_SECONDARY).small());
+            changed |= ui
+                .checkbox(&mut config.local_endpoint, "Serve /v1/chat/completions to this site")
+                .on_hover_text(
+                    "Other pages on this origin, such as notebooks in the workspace, can call the configured \
+                     provider through a service worker without an API key of their own",
+                )
+                .changed();
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Diagnostics
+            ui.label(RichText::new("Diagnostics").color(TEXT_SECONDARY).small());
+            changed |= ui
+                .checkbox(&mut config.ui.perf_overlay, "Performance overlay")
+                .on_hover_text("Frame times, events per frame, storage operations in flight and message counts")
+                .changed();
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Response cache
+            ui.label(RichText::new("Response Cache").color(TEXT_SECONDARY).small());
+            changed |= ui
+                .checkbox(&mut config.cache.enabled, "Reuse replies to identical requests")
+                .on_hover_text(
+                    "Re-running the same prompts (workflows, evals, replays) is answered from \
+                     browser storage instead of billed again",
+                )
+                .changed();
+            ui.add_enabled_ui(config.cache.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Keep replies for").color(TEXT_SECONDARY).small());
+                    let mut hours = config.cache.ttl_secs / 3600;
+                    if ui.add(egui::DragValue::new(&mut hours).range(1..=24 * 30).suffix(" h")).changed() {
+                        config.cache.ttl_secs = hours * 3600;
+                        changed = true;
+                    }
+                });
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Shell output limits
+            ui.label(RichText::new("Shell").color(TEXT_SECONDARY).small());
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Output kept per command").color(TEXT_SECONDARY).small());
+                changed |= kib_value(ui, &mut config.shell.max_output_bytes, 1..=1024)
+                    .on_hover_text("The start and end are kept; the middle is elided")
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                let mut kill = config.shell.kill_after_bytes > 0;
+                if ui.checkbox(&mut kill, "Kill after").changed() {
+                    config.shell.kill_after_bytes = if kill { ShellConfig::default().kill_after_bytes } else { 0 };
+                    changed = true;
+                }
+                if kill {
+                    changed |= kib_value(ui, &mut config.shell.kill_after_bytes, 64..=65536).changed();
+                    ui.label(RichText::new("of output").color(TEXT_SECONDARY).small());
+                }
+            });
+            ui.horizontal(|ui| {
+                let output = &mut config.tool_output;
+                let mut limit = output.turn_budget_bytes > 0;
+                if ui
+                    .checkbox(&mut limit, "Tool output per turn")
+                    .on_hover_text(format!(
+                        "Later results are saved under {} and the agent gets a pointer instead",
+                        output.outputs_dir
+                    ))
+                    .changed()
+                {
+                    output.turn_budget_bytes = if limit { ToolOutputConfig::default().turn_budget_bytes } else { 0 };
+                    changed = true;
+                }
+                if limit {
+                    changed |= kib_value(ui, &mut output.turn_budget_bytes, 16..=4096).changed();
+                }
+            });
+            ui.label(RichText::new("Terminal aliases").color(TEXT_SECONDARY).small())
+                .on_hover_text("Expanded in commands typed into the terminal, e.g. ll → ls -la");
+            changed |= pair_rows(ui, &mut config.aliases, ("Alias", "Command"), "alias");
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Context window
+            ui.label(RichText::new("Context").color(TEXT_SECONDARY).small());
+            let context = &mut config.context;
+            egui::ComboBox::from_id_salt("context_strategy")
+                .selected_text(context.strategy.label())
+                .show_ui(ui, |ui| {
+                    for strategy in ContextStrategyKind::all() {
+                        changed |= ui
+                            .selectable_value(&mut context.strategy, *strategy, strategy.label())
+                            .changed();
+                    }
+                });
+            match context.strategy {
+                ContextStrategyKind::FullHistory => {}
+                ContextStrategyKind::SlidingWindow => {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("Earlier turns sent").color(TEXT_SECONDARY).small());
+                        changed |= ui
+                            .add(egui::DragValue::new(&mut context.window_turns).range(0..=100))
+                            .changed();
+                    });
+                }
+                ContextStrategyKind::RecentBudget | ContextStrategyKind::ImportanceWeighted => {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("Earlier messages up to").color(TEXT_SECONDARY).small());
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut context.max_tokens)
+                                    .range(1000..=1_000_000)
+                                    .speed(100)
+                                    .suffix(" tokens"),
+                            )
+                            .on_hover_text("Estimated at four characters per token")
+                            .changed();
+                    });
+                }
+            }
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Self-review
+            ui.label(RichText::new("Review").color(TEXT_SECONDARY).small());
+            changed |= ui
+                .checkbox(&mut config.review.enabled, "Check multi-step answers before finishing")
+                .on_hover_text(
+                    "After a turn that used several tools, the model verifies its work against \
+                     the request once and fixes anything it missed",
+                )
+                .changed();
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Planner/executor orchestration
+            ui.label(RichText::new("Planning").color(TEXT_SECONDARY).small());
+            changed |= ui
+                .checkbox(&mut config.plan.enabled, "Plan requests in steps")
+                .on_hover_text(
+                    "A planner splits each request into steps, each step runs in a fresh \
+                     executor, and the planner answers from their results",
+                )
+                .changed();
+            ui.add_enabled_ui(config.plan.enabled, |ui| {
+                egui::Grid::new("plan_models").num_columns(2).show(ui, |ui| {
+                    ui.label("Planner model");
+                    let field = ui.add(
+                        egui::TextEdit::singleline(&mut config.plan.planner_model)
+                            .hint_text(config.llm.model.as_str())
+                            .desired_width(160.0),
+                    );
+                    name_field(&field, "Planner model");
+                    changed |= field.changed();
+                    ui.end_row();
+                    ui.label("Executor model");
+                    let field = ui.add(
+                        egui::TextEdit::singleline(&mut config.plan.executor_model)
+                            .hint_text(config.llm.model.as_str())
+                            .desired_width(160.0),
+                    );
+                    name_field(&field, "Executor model");
+                    changed |= field.changed();
+                    ui.end_row();
+                    ui.label("Max steps");
+                    changed |= ui.add(egui::DragValue::new(&mut config.plan.max_steps).range(1..=20)).changed();
+                    ui.end_row();
+                });
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Personas, picked per session in the top bar
+            egui::CollapsingHeader::new(RichText::new("Personas").color(TEXT_SECONDARY).small())
+                .id_salt("personas")
+                .show(ui, |ui| {
+                    ui.label(
+                        RichText::new("A persona replaces the system prompt and can limit the tools \
+                                       and change the model of the sessions that use it.")
+                            .color(TEXT_SECONDARY)
+                            .small(),
+                    );
+                    changed |= persona_rows(ui, &mut config.personas, &config.llm.model);
+                });
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Long-term memory
+            ui.label(RichText::new("Memory").color(TEXT_SECONDARY).small());
+            if ui
+                .checkbox(&mut config.memory.enabled, "Let the agent remember facts across sessions")
+                .changed()
+            {
+                changed = true;
+            }
+            ui.add_enabled_ui(config.memory.enabled, |ui| {
+                if ui
+                    .checkbox(
+                        &mut config.memory.inject_into_prompt,
+                        "Include memories in the system prompt",
+                    )
+                    .changed()
+                {
+                    changed = true;
+                }
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Web pages
+            ui.label(RichText::new("Web Pages").color(TEXT_SECONDARY).small());
+            changed |= ui
+                .checkbox(&mut config.web.enabled, "Let the agent read web pages")
+                .on_hover_text("Offers the read_url tool; pages go through the CORS proxy above when one is set")
+                .changed();
+            ui.add_enabled_ui(config.web.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Longest page").color(TEXT_SECONDARY).small());
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut config.web.max_chars).range(0..=200_000).suffix(" chars"))
+                        .on_hover_text("Text past this is cut off; 0 keeps whole pages")
+                        .changed();
+                });
+                changed |= ui
+                    .checkbox(&mut config.web.cache, "Keep read pages in the workspace")
+                    .on_hover_text("Reading a page again uses the saved copy unless the agent asks to refresh it")
+                    .changed();
+                if config.web.cache {
+                    let field = ui.add(
+                        egui::TextEdit::singleline(&mut config.web.cache_dir)
+                            .hint_text("/workspace/.agent/web")
+                            .desired_width(200.0),
+                    );
+                    name_field(&field, "Web page folder");
+                    changed |= field.changed();
+                }
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // JavaScript
+            ui.label(RichText::new("JavaScript").color(TEXT_SECONDARY).small());
+            changed |= ui
+                .checkbox(&mut config.js.enabled, "Let the agent run JavaScript")
+                .on_hover_text("Offers the run_js tool; scripts run in a worker without DOM. Not isolated: \
+                 they share the app's origin")
+                .changed();
+            ui.add_enabled_ui(config.js.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Time limit").color(TEXT_SECONDARY).small());
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut config.js.timeout_ms).range(100..=60_000).suffix(" ms"))
+                        .on_hover_text("A script still running after this is stopped")
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Longest output").color(TEXT_SECONDARY).small());
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut config.js.max_output_chars).range(0..=200_000).suffix(" chars"))
+                        .on_hover_text("Console output and result past this are cut off; 0 keeps all of it")
+                        .changed();
+                });
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // SQL
+            ui.label(RichText::new("SQL").color(TEXT_SECONDARY).small());
+            changed |= ui
+                .checkbox(&mut config.sql.enabled, "Let the agent query SQLite databases")
+                .on_hover_text(
+                    "Offers the sql tool, which can load CSV files into tables; databases are kept per workspace. \
+                     Downloads sql.js on first use",
+                )
+                .changed();
+            ui.add_enabled_ui(config.sql.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Rows shown").color(TEXT_SECONDARY).small());
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut config.sql.max_rows).range(1..=10_000))
+                        .on_hover_text("Rows of a result past this are counted but not shown to the agent")
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Time limit").color(TEXT_SECONDARY).small());
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut config.sql.timeout_ms).range(1_000..=600_000).suffix(" ms"))
+                        .on_hover_text("Statements still running after this are stopped and change nothing")
+                        .changed();
+                });
+            });
+
+            ui.add_space(8.0);
+            ui.separator();
+
+            // Workspace search (RAG)
+            ui.label(RichText::new("Workspace Search").color(TEXT_SECONDARY).small());
+            if ui
+                .checkbox(&mut config.retrieval.enabled, "Index workspace files for semantic search")
+                .on_hover_text("Embeds files locally; downloads a small model on first use")
+                .changed()
+            {
+                changed = true;
+            }
+            ui.add_enabled_ui(config.retrieval.enabled, |ui| {
+                if ui
+                    .checkbox(
+                        &mut config.retrieval.auto_inject,
+                        "Add relevant excerpts to each message",
+                    )
+                    .changed()
+                {
+                    changed = true;
+                }
+                ui.label(RichText::new("Excerpts per message").color(TEXT_SECONDARY).small());
+                if ui
+                    .add(egui::Slider::new(&mut config.retrieval.top_k, 1..=10))
+                    .changed()
+                {
+                    changed = true;
+                }
+            });
         });
 
     changed
 }
+
+/// Problems found restoring the saved settings, shown above the panel
+/// until dismissed.
+pub fn config_warnings(ui: &mut egui::Ui, warnings: &mut Vec<String>) {
+    if warnings.is_empty() {
+        return;
+    }
+    egui::Frame::default()
+        .fill(BG_SURFACE.color())
+        .inner_margin(PANEL_PADDING)
+        .corner_radius(PANEL_ROUNDING)
+        .show(ui, |ui| {
+            ui.label(RichText::new("⚠ Saved settings were adjusted").color(WARNING).strong());
+            for warning in warnings.iter() {
+                ui.label(RichText::new(format!("• {}", warning)).color(TEXT_SECONDARY).small());
+            }
+            if ui.small_button("Dismiss").clicked() {
+                warnings.clear();
+            }
+        });
+    ui.add_space(4.0);
+}
+
+/// Cap and usage of the in-memory storage fallback; the app shows this only
+/// when storage is held in memory. Returns true if the cap changed.
+pub fn storage_section(ui: &mut egui::Ui, config: &mut AgentConfig, usage: &StorageUsage) -> bool {
+    ui.label(RichText::new("Storage (in memory)").color(TEXT_SECONDARY).small());
+    ui.label(
+        RichText::new("Browser storage is unavailable, so nothing is kept after a reload")
+            .color(WARNING)
+            .small(),
+    );
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("Keep at most").color(TEXT_SECONDARY).small());
+        let mut mib = config.storage.memory_cap_bytes / (1024 * 1024);
+        let response = ui
+            .add(egui::DragValue::new(&mut mib).range(0..=2048).suffix(" MiB"))
+            .on_hover_text("The least recently used entries are dropped beyond this (0 = no limit)");
+        if response.changed() {
+            config.storage.memory_cap_bytes = mib * 1024 * 1024;
+            changed = true;
+        }
+    });
+    let held = match usage.cap {
+        0 => format!("{} in {} entries", format_size(usage.bytes as u64), usage.entries),
+        cap => format!(
+            "{} of {} in {} entries",
+            format_size(usage.bytes as u64),
+            format_size(cap as u64),
+            usage.entries
+        ),
+    };
+    ui.label(RichText::new(held).color(TEXT_SECONDARY).small());
+    if usage.evictions > 0 {
+        ui.label(
+            RichText::new(format!(
+                "{} entries ({}) evicted to stay under the cap",
+                usage.evictions,
+                format_size(usage.evicted_bytes)
+            ))
+            .color(WARNING)
+            .small(),
+        );
+    }
+    changed
+}
+
+/// Export/import controls. The file work is the app's: it handles the
+/// `*_requested` flags, fills `preview` with what an imported file would
+/// change, and applies it once `apply_requested` is set.
+#[derive(Default)]
+pub struct ConfigTransfer {
+    /// Put API keys and header values in the exported file
+    pub include_secrets: bool,
+    pub export_requested: bool,
+    pub import_requested: bool,
+    /// Imported settings waiting for confirmation
+    pub preview: Option<ImportPreview>,
+    pub apply_requested: bool,
+    /// Outcome of the last export or import
+    pub status: Option<Result<String, String>>,
+}
+
+pub struct ImportPreview {
+    pub config: AgentConfig,
+    pub changes: Vec<ConfigChange>,
+    /// Settings in the file that were reset or repaired
+    pub warnings: Vec<String>,
+}
+
+/// The "Export / import" section: move settings to another browser.
+pub fn transfer_section(ui: &mut egui::Ui, transfer: &mut ConfigTransfer) {
+    ui.label(RichText::new("Export / import").color(TEXT_SECONDARY).small());
+    ui.horizontal(|ui| {
+        if ui.button("Export settings").clicked() {
+            transfer.export_requested = true;
+        }
+        if ui.button("Import settings…").clicked() {
+            transfer.import_requested = true;
+        }
+    });
+    ui.checkbox(&mut transfer.include_secrets, "Include API keys and header values")
+        .on_hover_text("Leave off to share the file; importing it keeps the keys already set here");
+    match &transfer.status {
+        Some(Ok(message)) => {
+            ui.label(RichText::new(message).color(TEXT_SECONDARY).small());
+        }
+        Some(Err(error)) => {
+            ui.label(RichText::new(error).color(ERROR).small());
+        }
+        None => {}
+    }
+}
+
+/// Confirm an import, listing each setting it changes.
+pub fn import_preview_dialog(ctx: &egui::Context, transfer: &mut ConfigTransfer) {
+    let Some(preview) = &transfer.preview else {
+        return;
+    };
+    let mut close = false;
+    let modal = egui::Modal::new(egui::Id::new("import_settings")).show(ctx, |ui| {
+        ui.set_max_width(480.0);
+        ui.heading("Import settings");
+        ui.label(format!("{} setting(s) will change:", preview.changes.len()));
+        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            egui::Grid::new("import_changes").striped(true).num_columns(3).show(ui, |ui| {
+                for change in &preview.changes {
+                    ui.label(RichText::new(&change.field).monospace().small());
+                    ui.label(RichText::new(short(&change.from)).color(TEXT_SECONDARY).small());
+                    ui.label(RichText::new(format!("→ {}", short(&change.to))).small());
+                    ui.end_row();
+                }
+            });
+        });
+        for warning in &preview.warnings {
+            ui.label(RichText::new(format!("⚠ {}", warning)).color(WARNING).small());
+        }
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            if ui.button("Apply").clicked() {
+                transfer.apply_requested = true;
+            }
+            if ui.button("Cancel").clicked() {
+                close = true;
+            }
+        });
+    });
+    if close || modal.should_close() {
+        transfer.preview = None;
+    }
+}
+
+/// A value cut to fit a table cell
+fn short(value: &str) -> String {
+    const MAX_CHARS: usize = 40;
+    let line = value.lines().next().unwrap_or("");
+    if value.chars().count() > MAX_CHARS || line.len() < value.len() {
+        format!("{}…", line.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        value.to_string()
+    }
+}
+
+/// Drag value editing a byte count in KiB
+fn kib_value(ui: &mut egui::Ui, bytes: &mut usize, range: std::ops::RangeInclusive<usize>) -> egui::Response {
+    let mut kib = *bytes / 1024;
+    let response = ui.add(egui::DragValue::new(&mut kib).range(range).suffix(" KiB"));
+    if response.changed() {
+        *bytes = kib * 1024;
+    }
+    response
+}
+
+/// Price of the current model, and editable per-model overrides.
+fn price_rows(ui: &mut egui::Ui, prices: &mut BTreeMap<String, ModelPrice>, model: &str) -> bool {
+    let mut changed = false;
+    let current = match price_for(model, prices) {
+        Some(p) => format!("{}: ${} in / ${} out per 1M tokens", model, p.input_per_mtok, p.output_per_mtok),
+        None => format!("{}: no known price — add one below", model),
+    };
+    ui.label(RichText::new(current).color(TEXT_SECONDARY).small());
+
+    let mut remove = None;
+    for (name, price) in prices.iter_mut() {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(name).small());
+            changed |= ui
+                .add(egui::DragValue::new(&mut price.input_per_mtok).speed(0.01).range(0.0..=1000.0).prefix("in $"))
+                .changed();
+            changed |= ui
+                .add(egui::DragValue::new(&mut price.output_per_mtok).speed(0.01).range(0.0..=1000.0).prefix("out $"))
+                .changed();
+            if icon_button(ui, true, "🗑", "Use the built-in price").clicked() {
+                remove = Some(name.clone());
+            }
+        });
+    }
+    if let Some(name) = remove {
+        prices.remove(&name);
+        changed = true;
+    }
+    if !model.is_empty() && !prices.contains_key(model) && ui.small_button("+ Override price for this model").clicked() {
+        let price = price_for(model, prices).unwrap_or(ModelPrice::new(0.0, 0.0));
+        prices.insert(model.to_string(), price);
+        changed = true;
+    }
+    changed
+}
+
+/// Editable provider/model rows for the fallback chain.
+fn fallback_rows(ui: &mut egui::Ui, fallbacks: &mut Vec<FallbackTarget>, primary: &LlmProvider) -> bool {
+    let mut changed = false;
+    let mut remove = None;
+    for (i, target) in fallbacks.iter_mut().enumerate() {
+        ui.push_id(("fallback", i), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(format!("{}.", i + 1)).color(TEXT_SECONDARY).small());
+                egui::ComboBox::from_id_salt("fallback_provider")
+                    .selected_text(target.provider.label())
+                    .show_ui(ui, |ui| {
+                        for p in LlmProvider::all() {
+                            if ui.selectable_value(&mut target.provider, p.clone(), p.label()).changed() {
+                                target.model = target.provider.default_model().to_string();
+                                changed = true;
+                            }
+                        }
+                    });
+                if icon_button(ui, true, "🗑", "Remove fallback").clicked() {
+                    remove = Some(i);
+                }
+            });
+            changed |= ui
+                .add(egui::TextEdit::singleline(&mut target.model).hint_text("Model"))
+                .changed();
+            if target.provider.requires_api_key() {
+                let hint = if target.provider == *primary { "Same key as above" } else { "API key" };
+                changed |= ui
+                    .add(egui::TextEdit::singleline(&mut target.api_key).password(true).hint_text(hint))
+                    .changed();
+            }
+        });
+    }
+    if let Some(i) = remove {
+        fallbacks.remove(i);
+        changed = true;
+    }
+    if ui.small_button("+ Add fallback").clicked() {
+        fallbacks.push(FallbackTarget {
+            provider: LlmProvider::OpenAI,
+            model: LlmProvider::OpenAI.default_model().to_string(),
+            api_key: String::new(),
+            api_base: None,
+        });
+        changed = true;
+    }
+    changed
+}
+
+fn persona_rows(ui: &mut egui::Ui, personas: &mut Vec<Persona>, main_model: &str) -> bool {
+    let mut changed = false;
+    let mut remove = None;
+    for (i, persona) in personas.iter_mut().enumerate() {
+        ui.push_id(("persona", i), |ui| {
+            ui.horizontal(|ui| {
+                let field = ui.add(
+                    egui::TextEdit::singleline(&mut persona.name).hint_text("Name").desired_width(140.0),
+                );
+                name_field(&field, "Persona name");
+                changed |= field.changed();
+                if icon_button(ui, true, "🗑", "Remove persona").clicked() {
+                    remove = Some(i);
+                }
+            });
+            let mut model = persona.model.clone().unwrap_or_default();
+            let field = ui.add(egui::TextEdit::singleline(&mut model).hint_text(main_model).desired_width(180.0));
+            name_field(&field, "Persona model");
+            if field.changed() {
+                persona.model = Some(model.trim().to_string()).filter(|m| !m.is_empty());
+                changed = true;
+            }
+            let field = ui.add(
+                egui::TextEdit::multiline(&mut persona.system_prompt)
+                    .desired_rows(3)
+                    .desired_width(f32::INFINITY)
+                    .hint_text("System prompt (empty keeps the global one)"),
+            );
+            name_field(&field, "Persona system prompt");
+            changed |= field.changed();
+
+            let mut all = persona.allowed_tools.is_none();
+            if ui.checkbox(&mut all, "All tools").changed() {
+                persona.allowed_tools = if all { None } else { Some(Vec::new()) };
+                changed = true;
+            }
+            if let Some(tools) = &mut persona.allowed_tools {
+                ui.horizontal_wrapped(|ui| {
+                    for name in TOOL_NAMES {
+                        let mut on = tools.iter().any(|t| t == name);
+                        if ui.checkbox(&mut on, RichText::new(*name).small()).changed() {
+                            if on {
+                                tools.push(name.to_string());
+                            } else {
+                                tools.retain(|t| t != name);
+                            }
+                            changed = true;
+                        }
+                    }
+                });
+            }
+            ui.add_space(4.0);
+        });
+    }
+    if let Some(i) = remove {
+        personas.remove(i);
+        changed = true;
+    }
+    if ui.small_button("+ Add persona").clicked() {
+        personas.push(Persona { name: format!("Persona {}", personas.len() + 1), ..Default::default() });
+        changed = true;
+    }
+    changed
+}
+
+/// Editable name/value rows, e.g. extra request headers or aliases.
+/// `noun` names a row in the buttons.
+fn pair_rows(ui: &mut egui::Ui, rows: &mut Vec<(String, String)>, hints: (&str, &str), noun: &str) -> bool {
+    let mut changed = false;
+    let mut remove = None;
+    for (i, (name, value)) in rows.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(egui::TextEdit::singleline(name).hint_text(hints.0).desired_width(110.0))
+                .changed();
+            changed |= ui
+                .add(egui::TextEdit::singleline(value).hint_text(hints.1).desired_width(140.0))
+                .changed();
+            if icon_button(ui, true, "🗑", &format!("Remove {}", noun)).clicked() {
+                remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = remove {
+        rows.remove(i);
+        changed = true;
+    }
+    if ui.small_button(format!("+ Add {}", noun)).clicked() {
+        rows.push((String::new(), String::new()));
+        changed = true;
+    }
+    changed
+}
+
+/// Dropdown of the provider's models with a refresh button, the list's
+/// age, and a warning when the configured model is not offered.
+fn model_picker(ui: &mut egui::Ui, model: &mut String, models: &mut ModelOptions) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        let available = models.list.as_ref().map_or(&[][..], |l| &l.models[..]);
+        ui.add_enabled_ui(!available.is_empty(), |ui| {
+            egui::ComboBox::from_id_salt("llm_model_list")
+                .selected_text(format!("{} available", available.len()))
+                .height(300.0)
+                .show_ui(ui, |ui| {
+                    for name in available {
+                        if ui.selectable_label(model == name, name).clicked() && model != name {
+                            *model = name.clone();
+                            changed = true;
+                        }
+                    }
+                });
+        });
+        if models.loading {
+            ui.spinner();
+        } else if icon_button(ui, true, "⟳", "Refresh model list").clicked() {
+            models.refresh_requested = true;
+        }
+    });
+
+    if let Some(error) = &models.error {
+        ui.label(RichText::new(format!("Couldn't list models: {}", error)).color(ERROR).small());
+    }
+    if let Some(list) = &models.list {
+        if list.is_stale(MODEL_LIST_TTL_SECS) {
+            ui.label(RichText::new("Model list may be out of date").color(WARNING).small());
+        }
+        if !model.is_empty() && !list.offers(model) {
+            ui.label(
+                RichText::new(format!("\"{}\" is not in this provider's model list", model))
+                    .color(WARNING)
+                    .small(),
+            );
+        }
+    }
+    changed
+}
+
+/// A checkbox enabling an optional parameter, plus a slider while enabled.
+/// Unchecked means "use the provider default".
+fn optional_slider(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut Option<f32>,
+    range: std::ops::RangeInclusive<f32>,
+    default: f32,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+        if ui.checkbox(&mut enabled, label).changed() {
+            *value = enabled.then_some(default);
+            changed = true;
+        }
+        if let Some(v) = value.as_mut() {
+            if ui.add(egui::Slider::new(v, range)).changed() {
+                changed = true;
+            }
+        }
+    });
+    changed
+}
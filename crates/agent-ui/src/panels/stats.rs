@@ -0,0 +1,165 @@
+//! Usage dashboard — turns, tool use, tokens, cost and latency, computed
+//! from the activity log.
+//!
+//! Loading is the app's job: the panel shows `stats` as set by the app and
+//! raises `refresh_requested` when it is opened or the user asks.
+
+use egui::{self, RichText, Sense, Vec2};
+use agent_types::activity::{ActivityStats, DayStats, format_duration};
+use agent_types::pricing::format_usd;
+
+use crate::theme::*;
+
+/// Days shown in the charts
+const CHART_DAYS: usize = 30;
+
+#[derive(Default)]
+pub struct StatsPanel {
+    pub open: bool,
+    /// Stats as last loaded by the app
+    pub stats: Option<ActivityStats>,
+    pub loading: bool,
+    pub refresh_requested: bool,
+}
+
+impl StatsPanel {
+    /// Show or hide the window, asking for fresh stats when it opens
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        if self.open {
+            self.refresh_requested = true;
+        }
+    }
+}
+
+fn percent(part: u64, whole: u64) -> String {
+    if whole == 0 {
+        return "–".to_string();
+    }
+    format!("{:.0}%", part as f64 * 100.0 / whole as f64)
+}
+
+/// The stats window, while `panel.open`.
+pub fn stats_window(ctx: &egui::Context, panel: &mut StatsPanel) {
+    let mut open = panel.open;
+    egui::Window::new("Usage")
+        .open(&mut open)
+        .default_width(420.0)
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!panel.loading, egui::Button::new("⟳ Refresh")).clicked() {
+                    panel.refresh_requested = true;
+                }
+                if panel.loading {
+                    ui.spinner();
+                }
+            });
+            ui.separator();
+
+            let Some(stats) = &panel.stats else {
+                return;
+            };
+            if stats.turns == 0 {
+                ui.label(RichText::new("No activity yet.").color(TEXT_SECONDARY));
+                return;
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                summary_grid(ui, stats);
+                ui.add_space(8.0);
+                tool_table(ui, stats);
+                ui.add_space(8.0);
+
+                let days = &stats.days[stats.days.len().saturating_sub(CHART_DAYS)..];
+                ui.label(RichText::new("Tokens per day").color(TEXT_SECONDARY).small());
+                bar_chart(ui, days, |d| d.tokens as f64, |d| format!("{}: {} tokens, {} turns", d.day, d.tokens, d.turns));
+                ui.add_space(4.0);
+                ui.label(RichText::new("Estimated cost per day").color(TEXT_SECONDARY).small());
+                bar_chart(ui, days, |d| d.cost_usd, |d| format!("{}: {}", d.day, format_usd(d.cost_usd)));
+            });
+        });
+    panel.open = open;
+}
+
+fn summary_grid(ui: &mut egui::Ui, stats: &ActivityStats) {
+    let calls = stats.tool_calls();
+    let rows = [
+        ("Turns", stats.turns.to_string()),
+        (
+            "Failed turns",
+            format!("{} ({})", stats.failed_turns, percent(stats.failed_turns, stats.turns)),
+        ),
+        (
+            "Tool calls",
+            format!("{} ({} succeeded)", calls, percent(calls - stats.tool_failures(), calls)),
+        ),
+        (
+            "Tokens",
+            format!("{} in / {} out", stats.usage.prompt_tokens, stats.usage.completion_tokens),
+        ),
+        ("Estimated cost", format_usd(stats.usage.cost_usd)),
+        ("Average turn", format_duration(stats.average_turn_ms)),
+    ];
+    egui::Grid::new("stats_summary").num_columns(2).striped(true).show(ui, |ui| {
+        for (label, value) in rows {
+            ui.label(RichText::new(label).color(TEXT_SECONDARY));
+            ui.label(RichText::new(value).color(TEXT_PRIMARY));
+            ui.end_row();
+        }
+    });
+}
+
+fn tool_table(ui: &mut egui::Ui, stats: &ActivityStats) {
+    if stats.tools.is_empty() {
+        return;
+    }
+    ui.label(RichText::new("Tools").color(TEXT_SECONDARY).small());
+    let mut tools: Vec<_> = stats.tools.iter().collect();
+    tools.sort_by_key(|(_, tool)| std::cmp::Reverse(tool.calls));
+    egui::Grid::new("stats_tools").num_columns(3).show(ui, |ui| {
+        for (name, tool) in tools {
+            ui.label(RichText::new(name).monospace().small());
+            ui.label(RichText::new(tool.calls.to_string()).small());
+            ui.add(
+                egui::ProgressBar::new(tool.success_ratio())
+                    .desired_width(140.0)
+                    .text(format!("{} ok", percent(tool.calls - tool.failures, tool.calls))),
+            );
+            ui.end_row();
+        }
+    });
+}
+
+/// One bar per day, scaled to the largest value; hover shows `label`.
+fn bar_chart(
+    ui: &mut egui::Ui,
+    days: &[DayStats],
+    value: impl Fn(&DayStats) -> f64,
+    label: impl Fn(&DayStats) -> String,
+) {
+    let size = Vec2::new(ui.available_width().max(120.0), 80.0);
+    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, PANEL_ROUNDING, BG_SURFACE);
+
+    let max = days.iter().map(&value).fold(0.0, f64::max);
+    if days.is_empty() || max <= 0.0 {
+        return;
+    }
+    let slot = rect.width() / days.len() as f32;
+    let hover = response.hover_pos();
+    for (i, day) in days.iter().enumerate() {
+        let height = (value(day) / max) as f32 * (rect.height() - 4.0);
+        let left = rect.left() + i as f32 * slot;
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(left + slot * 0.15, rect.bottom() - height),
+            egui::pos2(left + slot * 0.85, rect.bottom()),
+        );
+        let hovered = hover.is_some_and(|p| p.x >= left && p.x < left + slot);
+        let color = if hovered { ACCENT.color() } else { ACCENT.color().gamma_multiply(0.7) };
+        painter.rect_filled(bar, 1.0, color);
+        if hovered {
+            response.clone().on_hover_text(label(day));
+        }
+    }
+}
@@ -1,13 +1,309 @@
-//! Terminal panel — displays bash output from tool executions.
+//! Terminal panel — displays bash output from tool executions, with a
+//! command line for running commands directly.
+//!
+//! Tab completes the word before the cursor: command names in command
+//! position, VFS paths (through the shared [`FileTree`]) everywhere else.
+//! When several candidates match, the common prefix is filled in and the
+//! candidates are listed under the command line. A command starting with
+//! a path (`./build.sh`) runs that script from the VFS.
+//!
+//! Commands still running, the agent's included, are listed as jobs that
+//! can be killed. The app refreshes the list from the shell each frame and
+//! cancels the jobs the user asks it to.
+//!
+//! Workspace paths in the output are links that open the file in the
+//! viewer, at the line for `path:line`.
 
 use egui::{self, RichText, ScrollArea};
+use agent_types::activity::format_duration;
+use agent_types::tool::{ExecHandle, RunningExec};
+use crate::file_tree::FileTree;
+use crate::panels::chat::{FileRef, file_spans};
+use crate::panels::explorer::resolve;
 use crate::state::UiState;
 use crate::theme::*;
+use crate::widgets::{icon_button, name_field};
+
+/// Bash builtins offered for completion
+pub const SHELL_BUILTINS: &[&str] = &[
+    "alias", "cd", "echo", "exit", "export", "false", "history", "printf", "pwd", "read", "set",
+    "source", "test", "true", "type", "unset",
+];
+
+/// Commands from the WASIX packages the shell worker provides
+pub const PACKAGE_COMMANDS: &[&str] = &[
+    "awk", "base64", "basename", "cat", "cp", "cut", "date", "diff", "dirname", "du", "env", "find",
+    "grep", "gzip", "head", "ls", "mkdir", "mv", "rm", "rmdir", "sed", "seq", "sleep", "sort", "tail",
+    "tar", "tee", "touch", "tr", "uniq", "wc", "xargs",
+];
+
+/// Candidates listed under the command line
+const MAX_CANDIDATES: usize = 24;
+
+/// The terminal's command line
+pub struct TerminalPrompt {
+    pub input: String,
+    /// Directory relative paths are completed against
+    pub cwd: String,
+    /// Matches of the last Tab, when there was more than one
+    pub candidates: Vec<String>,
+    /// Set by the app while a submitted command runs
+    pub running: bool,
+    /// Tab was pressed before the directory was listed; complete again
+    /// once it is
+    retry: bool,
+}
+
+impl Default for TerminalPrompt {
+    fn default() -> Self {
+        Self {
+            input: String::new(),
+            cwd: "/".to_string(),
+            candidates: Vec::new(),
+            running: false,
+            retry: false,
+        }
+    }
+}
+
+impl TerminalPrompt {
+    /// Complete the last word of the input
+    pub fn complete(&mut self, files: &mut FileTree) {
+        let start = self.word_start();
+        let word = &self.input[start..];
+        let before = self.input[..start].trim_end();
+        let command_position =
+            (before.is_empty() || before.ends_with(['|', ';', '&', '('])) && !word.contains('/');
+
+        let candidates = if command_position {
+            self.retry = false;
+            command_candidates(word)
+        } else {
+            let (candidates, loading) = path_candidates(&self.cwd, word, files);
+            self.retry = loading;
+            candidates
+        };
+
+        self.candidates.clear();
+        match candidates.as_slice() {
+            [] => {}
+            [only] => self.pick(only),
+            many => {
+                let prefix = common_prefix(many);
+                if prefix.len() > word.len() {
+                    self.input.replace_range(start.., &prefix);
+                }
+                self.candidates = candidates;
+            }
+        }
+    }
+
+    /// Replace the last word with `candidate`
+    pub fn pick(&mut self, candidate: &str) {
+        let start = self.word_start();
+        self.input.replace_range(start.., candidate);
+        if !candidate.ends_with('/') {
+            self.input.push(' ');
+        }
+        self.candidates.clear();
+    }
+
+    /// The command to run, clearing the line. Nothing while one is running.
+    pub fn submit(&mut self) -> Option<String> {
+        let command = self.input.trim().to_string();
+        if command.is_empty() || self.running {
+            return None;
+        }
+        self.input.clear();
+        self.candidates.clear();
+        self.retry = false;
+        Some(command)
+    }
+
+    fn word_start(&self) -> usize {
+        self.input
+            .rfind(|c: char| c.is_whitespace() || "|;&<>()".contains(c))
+            .map_or(0, |i| i + 1)
+    }
+}
+
+/// Shell commands in flight
+#[derive(Default)]
+pub struct Jobs {
+    /// As last listed by the shell
+    pub running: Vec<RunningExec>,
+    /// Jobs the user asked to kill, for the app to cancel
+    kills: Vec<ExecHandle>,
+    /// Kills sent for jobs that are still listed
+    killing: Vec<ExecHandle>,
+}
+
+impl Jobs {
+    /// Ask the app to cancel `handle`
+    pub fn kill(&mut self, handle: ExecHandle) {
+        if !self.is_killing(handle) {
+            self.kills.push(handle);
+        }
+    }
+
+    /// Whether `handle` has been asked to stop
+    pub fn is_killing(&self, handle: ExecHandle) -> bool {
+        self.kills.contains(&handle) || self.killing.contains(&handle)
+    }
+
+    /// Kills to send. Called by the app each frame.
+    pub fn take_kills(&mut self) -> Vec<ExecHandle> {
+        let kills = std::mem::take(&mut self.kills);
+        self.killing.extend(&kills);
+        kills
+    }
+
+    /// Replace the list with the shell's
+    pub fn set_running(&mut self, running: Vec<RunningExec>) {
+        self.killing.retain(|handle| running.iter().any(|job| job.handle == *handle));
+        self.running = running;
+    }
+}
+
+/// Known commands starting with `word`, sorted
+pub fn command_candidates(word: &str) -> Vec<String> {
+    let mut commands: Vec<String> = SHELL_BUILTINS
+        .iter()
+        .chain(PACKAGE_COMMANDS)
+        .filter(|c| c.starts_with(word))
+        .map(|c| c.to_string())
+        .collect();
+    commands.sort();
+    commands.dedup();
+    commands
+}
+
+/// Paths completing `word` from cached listings, relative when `word` is,
+/// and whether its directory is still being listed
+fn path_candidates(cwd: &str, word: &str, files: &mut FileTree) -> (Vec<String>, bool) {
+    let base = format!("{}/", cwd.trim_end_matches('/'));
+    let absolute = if word.starts_with('/') { word.to_string() } else { format!("{}{}", base, word) };
+    let dir = match absolute.rfind('/') {
+        Some(i) => &absolute[..=i],
+        None => "/",
+    };
+    let mut candidates = files.complete(&absolute);
+    let loading = candidates.is_empty() && files.entries(dir).is_none() && files.error(dir).is_none();
+    if !word.starts_with('/') {
+        for candidate in &mut candidates {
+            if let Some(relative) = candidate.strip_prefix(&base) {
+                *candidate = relative.to_string();
+            }
+        }
+    }
+    candidates.sort();
+    (candidates, loading)
+}
+
+/// Longest prefix shared by all of `words`
+pub fn common_prefix(words: &[String]) -> String {
+    let Some(first) = words.first() else {
+        return String::new();
+    };
+    let mut len = first.len();
+    for word in &words[1..] {
+        len = first
+            .char_indices()
+            .zip(word.chars())
+            .take_while(|((_, a), b)| a == b)
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+/// Expand aliases in command position, as bash does for interactive
+/// shells. An alias isn't expanded again inside its own expansion, and
+/// nothing inside quotes is touched.
+pub fn expand_aliases(command: &str, aliases: &[(String, String)]) -> String {
+    let mut out = String::with_capacity(command.len());
+    let mut quote = None;
+    let mut command_position = true;
+    let mut chars = command.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                } else if c == '\\' && q == '"' {
+                    out.push(c);
+                    if let Some((_, next)) = chars.next() {
+                        out.push(next);
+                    }
+                    continue;
+                }
+                out.push(c);
+            }
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                command_position = false;
+                out.push(c);
+            }
+            None if "|;&(\n".contains(c) => {
+                command_position = true;
+                out.push(c);
+            }
+            None if c.is_whitespace() => out.push(c),
+            None if command_position => {
+                let end = command[i..]
+                    .find(|c: char| c.is_whitespace() || "|;&()<>'\"".contains(c))
+                    .map_or(command.len(), |n| i + n);
+                out.push_str(&expand_word(&command[i..end], aliases));
+                while chars.peek().is_some_and(|&(j, _)| j < end) {
+                    chars.next();
+                }
+                command_position = false;
+            }
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// Expand `word` while its first word is an alias not expanded yet
+fn expand_word(word: &str, aliases: &[(String, String)]) -> String {
+    let mut expanded = word.to_string();
+    let mut seen: Vec<&str> = Vec::new();
+    loop {
+        let split = expanded.find(char::is_whitespace).unwrap_or(expanded.len());
+        let (first, rest) = expanded.split_at(split);
+        let alias = aliases
+            .iter()
+            .find(|(name, _)| name == first && !seen.contains(&name.as_str()));
+        let Some((name, replacement)) = alias else {
+            return expanded;
+        };
+        seen.push(name);
+        expanded = format!("{}{}", replacement, rest);
+    }
+}
+
+/// The VFS script a command starts with, if its first word is a path like
+/// `./build.sh` or `/workspace/run.sh`: the script's absolute path and the
+/// rest of the command, its arguments
+pub fn script_invocation<'a>(command: &'a str, cwd: &str) -> Option<(String, &'a str)> {
+    let command = command.trim_start();
+    let end = command.find(char::is_whitespace).unwrap_or(command.len());
+    let first = &command[..end];
+    if !(first.starts_with("./") || first.starts_with("../") || first.starts_with('/')) {
+        return None;
+    }
+    Some((resolve(cwd, first), command[end..].trim()))
+}
+
+/// Render the terminal panel. Returns Some(command) when the user runs one.
+pub fn terminal_panel(ui: &mut egui::Ui, state: &mut UiState) -> Option<String> {
+    let mut submitted = None;
 
-/// Render the terminal output panel.
-pub fn terminal_panel(ui: &mut egui::Ui, state: &UiState) {
     egui::Frame::default()
-        .fill(TERMINAL_BG)
+        .fill(TERMINAL_BG.color())
         .inner_margin(PANEL_PADDING)
         .corner_radius(PANEL_ROUNDING)
         .show(ui, |ui| {
@@ -28,7 +324,14 @@ pub fn terminal_panel(ui: &mut egui::Ui, state: &UiState) {
 
             ui.separator();
 
+            if !state.jobs.running.is_empty() {
+                jobs_section(ui, &mut state.jobs);
+                ui.separator();
+            }
+
+            let prompt_height = if state.terminal.candidates.is_empty() { 28.0 } else { 64.0 };
             ScrollArea::vertical()
+                .max_height((ui.available_height() - prompt_height).max(0.0))
                 .auto_shrink([false, false])
                 .stick_to_bottom(true)
                 .show(ui, |ui| {
@@ -40,19 +343,146 @@ pub fn terminal_panel(ui: &mut egui::Ui, state: &UiState) {
                                 .monospace(),
                         );
                     } else {
+                        let mut clicked_file = None;
                         for line in &state.terminal_lines {
                             let color = if line.is_stderr {
                                 TERMINAL_ERR
                             } else {
                                 TERMINAL_FG
                             };
-                            ui.label(
-                                RichText::new(&line.text)
-                                    .color(color)
-                                    .monospace(),
-                            );
+                            if let Some(file) = output_line(ui, &line.text, color) {
+                                clicked_file = Some(file);
+                            }
+                        }
+                        if let Some(file) = clicked_file {
+                            state.viewer.open_at(&file.path, file.line);
                         }
                     }
                 });
+
+            submitted = command_line(ui, &mut state.terminal, &mut state.files);
+        });
+
+    submitted
+}
+
+/// A line of output, with workspace paths as links. Returns the file whose
+/// link was clicked.
+fn output_line(ui: &mut egui::Ui, text: &str, color: ThemeColor) -> Option<FileRef> {
+    let spans = file_spans(text);
+    if spans.is_empty() {
+        ui.label(RichText::new(text).color(color).monospace());
+        return None;
+    }
+    let mut clicked = None;
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.x = 0.0;
+        let mut last = 0;
+        for (range, file) in spans {
+            if range.start > last {
+                ui.label(RichText::new(&text[last..range.start]).color(color).monospace());
+            }
+            if ui.link(RichText::new(&text[range.clone()]).monospace()).clicked() {
+                clicked = Some(file);
+            }
+            last = range.end;
+        }
+        if last < text.len() {
+            ui.label(RichText::new(&text[last..]).color(color).monospace());
+        }
+    });
+    clicked
+}
+
+/// One row per running command: id, command, elapsed time and a kill
+/// button
+fn jobs_section(ui: &mut egui::Ui, jobs: &mut Jobs) {
+    let mut kill = None;
+    for job in &jobs.running {
+        ui.horizontal(|ui| {
+            let killing = jobs.is_killing(job.handle);
+            if icon_button(ui, !killing, "✕", &format!("Kill job {}", job.handle.0)).clicked() {
+                kill = Some(job.handle);
+            }
+            ui.label(
+                RichText::new(format!("[{}]", job.handle.0))
+                    .color(TEXT_SECONDARY)
+                    .small()
+                    .monospace(),
+            );
+            ui.label(
+                RichText::new(format_duration(job.elapsed_ms))
+                    .color(WARNING)
+                    .small()
+                    .monospace(),
+            );
+            let command = job.command.lines().next().unwrap_or_default();
+            ui.add(egui::Label::new(RichText::new(command).color(TERMINAL_FG).small().monospace()).truncate())
+                .on_hover_text(&job.command);
+        });
+    }
+    if let Some(handle) = kill {
+        jobs.kill(handle);
+    }
+}
+
+/// The `$` input with Tab completion and the candidate list
+fn command_line(ui: &mut egui::Ui, prompt: &mut TerminalPrompt, files: &mut FileTree) -> Option<String> {
+    let mut submitted = None;
+    let id = ui.make_persistent_id("terminal_input");
+    let tab = ui.memory(|m| m.has_focus(id))
+        && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab));
+    let completed = tab || prompt.retry;
+    if completed {
+        prompt.complete(files);
+    }
+
+    ui.horizontal(|ui| {
+        let marker = if prompt.running { "…" } else { "$" };
+        ui.label(RichText::new(marker).color(TERMINAL_FG).monospace());
+        let mut output = egui::TextEdit::singleline(&mut prompt.input)
+            .id(id)
+            .lock_focus(true)
+            .frame(false)
+            .font(egui::TextStyle::Monospace)
+            .text_color(TERMINAL_FG.color())
+            .hint_text("Run a command (Tab completes)")
+            .desired_width(f32::INFINITY)
+            .show(ui);
+        name_field(&output.response, "Terminal command");
+        if completed {
+            let end = prompt.input.chars().count();
+            let range = egui::text::CCursorRange::one(egui::text::CCursor::new(end));
+            output.state.cursor.set_char_range(Some(range));
+            output.state.store(ui.ctx(), id);
+        }
+        if output.response.changed() {
+            prompt.candidates.clear();
+        }
+        if output.response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            submitted = prompt.submit();
+            output.response.request_focus();
+        }
+    });
+
+    if !prompt.candidates.is_empty() {
+        let mut picked = None;
+        ui.horizontal_wrapped(|ui| {
+            for candidate in prompt.candidates.iter().take(MAX_CANDIDATES) {
+                let label = RichText::new(candidate).color(TERMINAL_FG).monospace().small();
+                if ui.selectable_label(false, label).clicked() {
+                    picked = Some(candidate.clone());
+                }
+            }
+            if prompt.candidates.len() > MAX_CANDIDATES {
+                let more = format!("+{} more", prompt.candidates.len() - MAX_CANDIDATES);
+                ui.label(RichText::new(more).color(TEXT_SECONDARY).small());
+            }
         });
+        if let Some(candidate) = picked {
+            prompt.pick(&candidate);
+            ui.memory_mut(|m| m.request_focus(id));
+        }
+    }
+    submitted
 }
@@ -0,0 +1,219 @@
+//! Key vault controls — the settings section, the top bar lock indicator
+//! and the unlock dialog.
+//!
+//! Encryption and storage are the app's job: the panel shows `status` as
+//! set by the app and queues [`VaultAction`]s for it to carry out.
+
+use egui::{self, RichText};
+
+use crate::theme::*;
+
+/// Shortest passphrase accepted when creating a vault
+pub const MIN_PASSPHRASE_CHARS: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VaultStatus {
+    /// No vault; API keys are only kept in memory
+    #[default]
+    Off,
+    /// A vault exists but has not been unlocked this session
+    Locked,
+    Unlocked,
+}
+
+/// A vault operation for the app to perform
+#[derive(Debug, Clone, PartialEq)]
+pub enum VaultAction {
+    /// Encrypt the current API keys with a new passphrase
+    Create(String),
+    Unlock(String),
+    /// Forget the keys until the next unlock
+    Lock,
+    /// Delete the vault and the keys in it
+    Remove,
+}
+
+#[derive(Default)]
+pub struct VaultPanel {
+    pub status: VaultStatus,
+    pub passphrase: String,
+    pub confirm: String,
+    /// Last failure, e.g. a wrong passphrase
+    pub error: Option<String>,
+    /// An action is being carried out
+    pub busy: bool,
+    /// The unlock dialog is open
+    pub show_unlock: bool,
+    actions: Vec<VaultAction>,
+}
+
+impl VaultPanel {
+    /// Pending vault operations. Called by the app each frame.
+    pub fn take_actions(&mut self) -> Vec<VaultAction> {
+        std::mem::take(&mut self.actions)
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.status == VaultStatus::Locked
+    }
+
+    /// Queue `action`, clearing the passphrase fields
+    pub fn request(&mut self, action: VaultAction) {
+        self.passphrase.clear();
+        self.confirm.clear();
+        self.error = None;
+        self.busy = true;
+        self.actions.push(action);
+    }
+
+    /// Why the entered passphrase can't be used for a new vault, if it can't
+    pub fn create_problem(&self) -> Option<&'static str> {
+        if self.passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+            Some("Passphrase must be at least 8 characters")
+        } else if self.passphrase != self.confirm {
+            Some("Passphrases do not match")
+        } else {
+            None
+        }
+    }
+}
+
+/// The "Key Vault" section of the settings panel.
+pub fn vault_section(ui: &mut egui::Ui, panel: &mut VaultPanel) {
+    ui.label(RichText::new("Key Vault").color(TEXT_SECONDARY).small());
+    match panel.status {
+        VaultStatus::Off => {
+            ui.label(
+                RichText::new("Encrypt API keys with a passphrase and keep them across reloads.")
+                    .color(TEXT_SECONDARY)
+                    .small(),
+            );
+            ui.add(
+                egui::TextEdit::singleline(&mut panel.passphrase)
+                    .password(true)
+                    .hint_text("Passphrase"),
+            );
+            ui.add(
+                egui::TextEdit::singleline(&mut panel.confirm)
+                    .password(true)
+                    .hint_text("Confirm passphrase"),
+            );
+            let problem = panel.create_problem();
+            let button = ui.add_enabled(
+                problem.is_none() && !panel.busy,
+                egui::Button::new("Create vault"),
+            );
+            if let Some(problem) = problem.filter(|_| !panel.passphrase.is_empty()) {
+                button.on_disabled_hover_text(problem);
+            } else if button.clicked() {
+                let passphrase = panel.passphrase.clone();
+                panel.request(VaultAction::Create(passphrase));
+            }
+        }
+        VaultStatus::Locked => {
+            if ui.button("🔒 Unlock…").clicked() {
+                panel.show_unlock = true;
+            }
+        }
+        VaultStatus::Unlocked => {
+            ui.label(
+                RichText::new("Unlocked — API key changes are saved to the vault.")
+                    .color(TEXT_SECONDARY)
+                    .small(),
+            );
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!panel.busy, egui::Button::new("Lock")).clicked() {
+                    panel.request(VaultAction::Lock);
+                }
+                if ui
+                    .add_enabled(!panel.busy, egui::Button::new("Remove vault"))
+                    .on_hover_text("Delete the stored keys; the current key stays until reload")
+                    .clicked()
+                {
+                    panel.request(VaultAction::Remove);
+                }
+            });
+        }
+    }
+    if let Some(error) = &panel.error {
+        ui.label(RichText::new(error).color(ERROR).small());
+    }
+}
+
+/// Lock state for the top bar; clicking opens the unlock dialog or locks.
+pub fn vault_indicator(ui: &mut egui::Ui, panel: &mut VaultPanel) {
+    match panel.status {
+        VaultStatus::Off => {}
+        VaultStatus::Locked => {
+            if ui
+                .button(RichText::new("🔒 Locked").color(WARNING))
+                .on_hover_text("Unlock the key vault to use the LLM")
+                .clicked()
+            {
+                panel.show_unlock = true;
+            }
+        }
+        VaultStatus::Unlocked => {
+            if ui
+                .button(RichText::new("🔓").color(TEXT_SECONDARY))
+                .on_hover_text("Key vault unlocked — click to lock")
+                .clicked()
+                && !panel.busy
+            {
+                panel.request(VaultAction::Lock);
+            }
+        }
+    }
+}
+
+/// Ask for the passphrase while the unlock dialog is open.
+pub fn unlock_dialog(ctx: &egui::Context, panel: &mut VaultPanel) {
+    if !panel.show_unlock {
+        return;
+    }
+    if panel.status != VaultStatus::Locked {
+        panel.show_unlock = false;
+        return;
+    }
+    let mut submit = false;
+    let mut cancelled = false;
+    let modal = egui::Modal::new(egui::Id::new("vault_unlock_dialog")).show(ctx, |ui| {
+        ui.set_max_width(320.0);
+        ui.heading("Unlock key vault");
+        let field = ui.add(
+            egui::TextEdit::singleline(&mut panel.passphrase)
+                .password(true)
+                .hint_text("Passphrase"),
+        );
+        if !panel.busy {
+            field.request_focus();
+        }
+        if field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            submit = true;
+        }
+        if let Some(error) = &panel.error {
+            ui.label(RichText::new(error).color(ERROR).small());
+        }
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            let ready = !panel.passphrase.is_empty() && !panel.busy;
+            if ui.add_enabled(ready, egui::Button::new("Unlock")).clicked() {
+                submit = true;
+            }
+            if ui.button("Cancel").clicked() {
+                cancelled = true;
+            }
+            if panel.busy {
+                ui.spinner();
+            }
+        });
+    });
+    if submit && !panel.passphrase.is_empty() && !panel.busy {
+        let passphrase = panel.passphrase.clone();
+        panel.request(VaultAction::Unlock(passphrase));
+    } else if cancelled || modal.should_close() {
+        panel.show_unlock = false;
+        panel.passphrase.clear();
+        panel.error = None;
+    }
+}
@@ -0,0 +1,351 @@
+//! File viewer — read-only preview of a VFS file.
+//!
+//! The panel never touches the VFS itself: it records an open request, the
+//! app loads the file and hands back a [`FileLoad`] with the content type
+//! the VFS recorded. Text is highlighted with line numbers, images become
+//! textures, anything else is shown as a hex dump. Large files are paged
+//! so only part of them is laid out. A file opened at a line (from a
+//! `path:line` reference) scrolls to it and marks it in the gutter.
+
+use egui::{self, RichText, ScrollArea};
+
+use crate::highlight::{highlight, syntax_for};
+use crate::theme::*;
+use crate::widgets::icon_button;
+
+/// Files larger than this are only loaded after confirmation
+pub const MAX_AUTO_LOAD_BYTES: u64 = 2 * 1024 * 1024;
+/// Lines of text laid out per page
+pub const TEXT_PAGE_LINES: usize = 500;
+/// Bytes of hex dump shown per page
+pub const HEX_PAGE_BYTES: usize = 4096;
+
+/// A file the app should load for the viewer
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRequest {
+    pub path: String,
+    /// Load even if larger than [`MAX_AUTO_LOAD_BYTES`]
+    pub force: bool,
+}
+
+/// Outcome of loading a requested file
+#[derive(Debug, Clone)]
+pub enum FileLoad {
+    Loaded {
+        path: String,
+        data: Vec<u8>,
+        /// MIME type from the file's metadata, if known
+        content_type: Option<String>,
+    },
+    /// Not loaded because of its size; the user may ask again with `force`
+    TooLarge { path: String, size: u64 },
+    Failed { path: String, error: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Text,
+    Image,
+    Binary,
+}
+
+/// Decide how to show a file from its content type (or, for files saved
+/// without one, its name) and first bytes
+pub fn classify(path: &str, content_type: Option<&str>, data: &[u8]) -> FileKind {
+    let image = match content_type {
+        Some(mime) => mime.starts_with("image/"),
+        None => {
+            let lower = path.to_ascii_lowercase();
+            [".png", ".jpg", ".jpeg", ".bmp"].iter().any(|e| lower.ends_with(e))
+        }
+    };
+    if image && image::guess_format(data).is_ok() {
+        return FileKind::Image;
+    }
+    let head = &data[..data.len().min(8192)];
+    if head.contains(&0) {
+        return FileKind::Binary;
+    }
+    match std::str::from_utf8(data) {
+        Ok(_) => FileKind::Text,
+        // A multi-byte character may be cut at the end of a valid prefix
+        Err(e) if e.error_len().is_none() => FileKind::Text,
+        Err(_) => FileKind::Binary,
+    }
+}
+
+/// Classic 16-bytes-per-row hex dump with an ASCII column
+pub fn hex_dump(data: &[u8], offset: usize) -> String {
+    data.chunks(16)
+        .enumerate()
+        .map(|(row, bytes)| {
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = bytes
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            format!("{:08x}  {:<47}  {}", offset + row * 16, hex.join(" "), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Default)]
+pub struct FileViewer {
+    /// File being shown (or loading)
+    pub path: Option<String>,
+    pub data: Option<Vec<u8>>,
+    pub content_type: Option<String>,
+    pub loading: bool,
+    pub error: Option<String>,
+    /// Size of a file held back by [`MAX_AUTO_LOAD_BYTES`]
+    pub too_large: Option<u64>,
+    /// Set when the user asks to download the file; the app clears it
+    pub download_requested: bool,
+    /// Line the file was opened at, starting at 1
+    pub line: Option<usize>,
+    /// Scroll to `line` when the text is next laid out
+    scroll_to_line: bool,
+    /// Pending load for the app to pick up
+    request: Option<FileRequest>,
+    /// Lines (text) or bytes (hex) currently shown
+    shown: usize,
+    texture: Option<egui::TextureHandle>,
+}
+
+impl FileViewer {
+    pub fn is_open(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Show `path`, asking the app to load it
+    pub fn open(&mut self, path: &str) {
+        self.request_load(path, None, false);
+    }
+
+    /// Show `path` scrolled to `line` (starting at 1), if given
+    pub fn open_at(&mut self, path: &str, line: Option<usize>) {
+        self.request_load(path, line, false);
+    }
+
+    fn request_load(&mut self, path: &str, line: Option<usize>, force: bool) {
+        *self = Self {
+            path: Some(path.to_string()),
+            loading: true,
+            line,
+            scroll_to_line: line.is_some(),
+            request: Some(FileRequest {
+                path: path.to_string(),
+                force,
+            }),
+            ..Default::default()
+        };
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The pending load, if any. Called by the app each frame.
+    pub fn take_request(&mut self) -> Option<FileRequest> {
+        self.request.take()
+    }
+
+    /// Apply a finished load. Results for a file no longer shown are dropped.
+    pub fn finish_load(&mut self, load: FileLoad) {
+        let path = match &load {
+            FileLoad::Loaded { path, .. }
+            | FileLoad::TooLarge { path, .. }
+            | FileLoad::Failed { path, .. } => path,
+        };
+        if self.path.as_ref() != Some(path) {
+            return;
+        }
+        self.loading = false;
+        match load {
+            FileLoad::Loaded { data, content_type, .. } => {
+                self.shown = 0;
+                self.data = Some(data);
+                self.content_type = content_type;
+            }
+            FileLoad::TooLarge { size, .. } => self.too_large = Some(size),
+            FileLoad::Failed { error, .. } => self.error = Some(error),
+        }
+    }
+
+    /// Bytes of the loaded file
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.as_deref()
+    }
+}
+
+/// Render the viewer. Returns true when the user asks to edit the file.
+pub fn viewer_panel(ui: &mut egui::Ui, viewer: &mut FileViewer) -> bool {
+    let Some(path) = viewer.path.clone() else {
+        return false;
+    };
+    let mut edit = false;
+
+    egui::Frame::default()
+        .fill(BG_SECONDARY.color())
+        .inner_margin(PANEL_PADDING)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(&path).color(TEXT_PRIMARY).monospace());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if icon_button(ui, true, "✕", "Close").clicked() {
+                        viewer.close();
+                    }
+                    if let Some(data) = viewer.data() {
+                        let kind = classify(&path, viewer.content_type.as_deref(), data);
+                        let size = format_size(data.len() as u64);
+                        if icon_button(ui, true, "⬇", "Download").clicked() {
+                            viewer.download_requested = true;
+                        }
+                        if kind == FileKind::Text && ui.small_button("Edit").clicked() {
+                            edit = true;
+                        }
+                        let info = match &viewer.content_type {
+                            Some(mime) => format!("{} · {}", mime, size),
+                            None => size,
+                        };
+                        ui.label(RichText::new(info).color(TEXT_SECONDARY).small());
+                    }
+                });
+            });
+            ui.separator();
+
+            if viewer.loading {
+                ui.spinner();
+                return;
+            }
+            if let Some(error) = &viewer.error {
+                ui.label(RichText::new(error).color(ERROR));
+                return;
+            }
+            if let Some(size) = viewer.too_large {
+                ui.label(
+                    RichText::new(format!("This file is {} — larger than previews load by default.", format_size(size)))
+                        .color(TEXT_SECONDARY),
+                );
+                if ui.button("Load anyway").clicked() {
+                    viewer.request_load(&path, viewer.line, true);
+                }
+                return;
+            }
+            let Some(data) = viewer.data.take() else {
+                return;
+            };
+            match classify(&path, viewer.content_type.as_deref(), &data) {
+                FileKind::Text => text_view(ui, viewer, &path, &data),
+                FileKind::Image => image_view(ui, viewer, &path, &data),
+                FileKind::Binary => hex_view(ui, viewer, &data),
+            }
+            viewer.data = Some(data);
+        });
+    edit
+}
+
+fn text_view(ui: &mut egui::Ui, viewer: &mut FileViewer, path: &str, data: &[u8]) {
+    let text = String::from_utf8_lossy(data);
+    let total_lines = text.lines().count().max(1);
+    if viewer.shown == 0 {
+        // Enough pages to reach the line opened at
+        let pages = viewer.line.map_or(1, |line| line.div_ceil(TEXT_PAGE_LINES).max(1));
+        viewer.shown = pages * TEXT_PAGE_LINES;
+    }
+    let shown = viewer.shown.min(total_lines);
+    // Byte offset just past the last shown line
+    let end = text
+        .match_indices('\n')
+        .nth(shown - 1)
+        .map_or(text.len(), |(i, _)| i);
+    let visible = &text[..end];
+
+    let font = egui::FontId::monospace(12.0);
+    let mut gutter = egui::text::LayoutJob::default();
+    for n in 1..=shown {
+        let color = if viewer.line == Some(n) { ACCENT } else { TEXT_SECONDARY }.color();
+        let number = if n == shown { n.to_string() } else { format!("{}\n", n) };
+        gutter.append(&number, 0.0, egui::TextFormat::simple(font.clone(), color));
+    }
+    let job = highlight(visible, syntax_for(path), font.clone());
+
+    let mut area = ScrollArea::both().id_salt("viewer_text").auto_shrink([false, false]);
+    if std::mem::take(&mut viewer.scroll_to_line) {
+        // A few lines of context above the target
+        let row_height = ui.fonts_mut(|f| f.row_height(&font));
+        let line = viewer.line.unwrap_or(1).min(shown);
+        area = area.vertical_scroll_offset(row_height * line.saturating_sub(4) as f32);
+    }
+    area.show(ui, |ui| {
+        ui.horizontal_top(|ui| {
+            ui.label(gutter);
+            ui.add(egui::Label::new(job).extend());
+        });
+        if shown < total_lines {
+            ui.add_space(4.0);
+            if ui
+                .button(format!("Show more ({} of {} lines)", shown, total_lines))
+                .clicked()
+            {
+                viewer.shown += TEXT_PAGE_LINES;
+            }
+        }
+    });
+}
+
+fn image_view(ui: &mut egui::Ui, viewer: &mut FileViewer, path: &str, data: &[u8]) {
+    if viewer.texture.is_none() {
+        match image::load_from_memory(data) {
+            Ok(img) => {
+                let rgba = img.to_rgba8();
+                let size = [rgba.width() as usize, rgba.height() as usize];
+                let color = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw());
+                viewer.texture = Some(ui.ctx().load_texture(path, color, Default::default()));
+            }
+            Err(e) => {
+                ui.label(RichText::new(format!("Couldn't decode image: {}", e)).color(ERROR));
+                hex_view(ui, viewer, data);
+                return;
+            }
+        }
+    }
+    if let Some(texture) = &viewer.texture {
+        let [w, h] = texture.size();
+        ui.label(RichText::new(format!("{} × {} px", w, h)).color(TEXT_SECONDARY).small());
+        ScrollArea::both().id_salt("viewer_image").show(ui, |ui| {
+            ui.add(egui::Image::new(texture).shrink_to_fit());
+        });
+    }
+}
+
+fn hex_view(ui: &mut egui::Ui, viewer: &mut FileViewer, data: &[u8]) {
+    if viewer.shown == 0 {
+        viewer.shown = HEX_PAGE_BYTES;
+    }
+    let shown = viewer.shown.min(data.len());
+    ScrollArea::both()
+        .id_salt("viewer_hex")
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            ui.label(RichText::new(hex_dump(&data[..shown], 0)).monospace().color(TEXT_PRIMARY));
+            if shown < data.len() {
+                ui.add_space(4.0);
+                if ui
+                    .button(format!("Show more ({} of {})", format_size(shown as u64), format_size(data.len() as u64)))
+                    .clicked()
+                {
+                    viewer.shown += HEX_PAGE_BYTES;
+                }
+            }
+        });
+}
+
+pub(crate) fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{} B", b),
+        b if b < 1024 * 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
@@ -2,9 +2,37 @@
 //! This is a read-only projection of the agent runtime state,
 //! updated each frame by draining the EventBus.
 
+use std::collections::HashMap;
+
+use agent_types::activity::format_duration;
 use agent_types::event::AgentEvent;
+use agent_types::message::{Message, Role};
+use agent_types::share::SharedTranscript;
+use agent_types::tool::ToolResultData;
 use agent_core::runtime::AgentState;
 
+use crate::diagrams::Diagrams;
+use crate::file_tree::FileTree;
+use crate::fonts::ExtraFonts;
+use crate::images::ChatImages;
+use crate::tables::TableExports;
+use crate::panels::viewer::format_size;
+use crate::panels::editor::CodeEditor;
+use crate::panels::batch::BatchPanel;
+use crate::panels::eval::EvalPanel;
+use crate::panels::explorer::FileExplorer;
+use crate::panels::diagnostics::DiagnosticsPanel;
+use crate::panels::health::{Health, LlmCall, ShellHealth};
+use crate::panels::logs::LogPanel;
+use crate::panels::plan::PlanView;
+use crate::panels::prompts::PromptPicker;
+use crate::panels::settings::ConfigTransfer;
+use crate::panels::chat::{LargePaste, Outbox, Outgoing, Sharing};
+use crate::panels::stats::StatsPanel;
+use crate::panels::terminal::{Jobs, TerminalPrompt};
+use crate::panels::vault::VaultPanel;
+use crate::panels::viewer::FileViewer;
+
 /// State visible to UI panels
 pub struct UiState {
     /// Displayed messages (user + assistant + tool results)
@@ -13,14 +41,75 @@ pub struct UiState {
     pub agent_status: AgentState,
     /// Terminal output buffer (from bash executions)
     pub terminal_lines: Vec<TerminalLine>,
+    /// Index in `terminal_lines` of the `$ …` line of each running tool
+    /// call, by call id
+    pub tool_headers: HashMap<String, usize>,
+    /// Tool calls in progress, oldest first
+    pub running_tools: Vec<RunningTool>,
+    /// Terminal command line
+    pub terminal: TerminalPrompt,
+    /// Shell commands still running
+    pub jobs: Jobs,
     /// Streaming LLM text being assembled
     pub streaming_text: String,
     /// Input field content
     pub input_text: String,
+    /// VFS files to attach to the next message
+    pub attachments: Vec<String>,
+    /// Whether the attach popup is open, and the path typed into it
+    pub attach_input: Option<String>,
+    /// A large paste on offer to save as a file
+    pub paste: LargePaste,
+    /// Whether the browser reports a network connection
+    pub online: bool,
+    /// Messages waiting for the provider to be reachable
+    pub outbox: Outbox,
+    /// Provider latency and shell worker state for the top bar
+    pub health: Health,
     /// Whether settings panel is open
     pub show_settings: bool,
-    /// Status line text
+    /// Hide tool results and running tool cards, leaving the conversation
+    pub hide_tool_messages: bool,
+    /// Status line text; while busy, the phase of the turn
     pub status_text: String,
+    /// UI time (seconds) the current turn was first seen busy
+    pub busy_since: Option<f64>,
+    /// File preview panel
+    pub viewer: FileViewer,
+    /// File editor panel
+    pub editor: CodeEditor,
+    /// Saved prompt snippets for the chat input
+    pub prompts: PromptPicker,
+    /// API key vault controls
+    pub vault: VaultPanel,
+    /// Usage dashboard window
+    pub stats: StatsPanel,
+    /// In-app log window
+    pub logs: LogPanel,
+    /// Memory and storage use in the settings panel
+    pub diagnostics: DiagnosticsPanel,
+    /// Prompts lined up to run as separate turns
+    pub batch: BatchPanel,
+    /// Steps of the latest planned turn
+    pub plan: PlanView,
+    /// Scripted eval runs
+    pub eval: EvalPanel,
+    /// Settings export/import
+    pub config_transfer: ConfigTransfer,
+    /// Cached VFS listings shared by the panels
+    pub files: FileTree,
+    /// CJK fonts, fetched once text needs them
+    pub fonts: ExtraFonts,
+    /// Images shown under tool results in the chat
+    pub images: ChatImages,
+    /// Mermaid diagrams in answers, drawn by the app
+    pub diagrams: Diagrams,
+    /// Tables from the chat to save as files and open
+    pub table_exports: TableExports,
+    /// Share link requests and the shared conversation being viewed
+    pub share: Sharing,
+    /// File explorer window
+    pub explorer: FileExplorer,
 }
 
 /// A chat entry for display
@@ -29,7 +118,28 @@ pub struct ChatEntry {
     pub role: String,
     pub content: String,
     pub is_tool_call: bool,
+    /// Tool that produced a tool result
     pub tool_name: Option<String>,
+    /// Arguments of that tool call, as JSON
+    pub arguments: Option<String>,
+    /// How long the tool call took
+    pub duration_ms: Option<u64>,
+    /// Whether the tool call succeeded, when known
+    pub success: Option<bool>,
+    /// VFS files attached to a user message
+    pub attachments: Vec<String>,
+    /// Structured details of a tool result
+    pub data: Option<ToolResultData>,
+}
+
+/// A tool call still running, shown as a card with its progress
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunningTool {
+    pub call_id: String,
+    pub tool_name: String,
+    pub arguments: String,
+    /// Last reported progress: done, total (if known) and unit
+    pub progress: Option<(u64, Option<u64>, String)>,
 }
 
 /// A line in the terminal output
@@ -45,21 +155,55 @@ impl UiState {
             messages: Vec::new(),
             agent_status: AgentState::Idle,
             terminal_lines: Vec::new(),
+            tool_headers: HashMap::new(),
+            running_tools: Vec::new(),
+            terminal: TerminalPrompt::default(),
+            jobs: Jobs::default(),
             streaming_text: String::new(),
             input_text: String::new(),
+            attachments: Vec::new(),
+            attach_input: None,
+            paste: LargePaste::default(),
+            online: true,
+            outbox: Outbox::default(),
+            health: Health::default(),
             show_settings: false,
+            hide_tool_messages: false,
             status_text: "Ready".to_string(),
+            busy_since: None,
+            viewer: FileViewer::default(),
+            editor: CodeEditor::default(),
+            prompts: PromptPicker::default(),
+            vault: VaultPanel::default(),
+            stats: StatsPanel::default(),
+            logs: LogPanel::default(),
+            diagnostics: DiagnosticsPanel::default(),
+            batch: BatchPanel::default(),
+            plan: PlanView::default(),
+            eval: EvalPanel::default(),
+            config_transfer: ConfigTransfer::default(),
+            files: FileTree::default(),
+            fonts: ExtraFonts::default(),
+            images: ChatImages::default(),
+            table_exports: TableExports::default(),
+            diagrams: Diagrams::default(),
+            share: Sharing::default(),
+            explorer: FileExplorer::default(),
         }
     }
 
     /// Process events from the EventBus and update UI state
     pub fn process_events(&mut self, events: Vec<AgentEvent>) {
         for event in events {
+            self.files.observe(&event);
+            self.fonts.observe(&event);
+            self.images.observe(&event);
             match event {
                 AgentEvent::TurnStart { .. } => {
                     self.agent_status = AgentState::Thinking;
                     self.streaming_text.clear();
                     self.status_text = "Thinking...".to_string();
+                    self.busy_since = None;
                 }
                 AgentEvent::LlmDelta { token } => {
                     self.streaming_text.push_str(&token);
@@ -70,19 +214,45 @@ impl UiState {
                         content: text,
                         is_tool_call: false,
                         tool_name: None,
+                        arguments: None,
+                        duration_ms: None,
+                        success: None,
+                        attachments: Vec::new(),
+                        data: None,
                     });
                     self.streaming_text.clear();
                 }
+                AgentEvent::LlmReasoning { text } => {
+                    self.messages.push(ChatEntry {
+                        role: "reasoning".to_string(),
+                        content: text,
+                        is_tool_call: false,
+                        tool_name: None,
+                        arguments: None,
+                        duration_ms: None,
+                        success: None,
+                        attachments: Vec::new(),
+                        data: None,
+                    });
+                }
                 AgentEvent::ToolExecStart {
+                    call_id,
                     tool_name,
                     arguments,
-                    ..
                 } => {
-                    self.status_text = format!("Running: {}", tool_name);
-                    self.terminal_lines.push(TerminalLine {
-                        text: format!("$ {} {}", tool_name, arguments),
-                        is_stderr: false,
-                    });
+                    self.agent_status = AgentState::ExecutingTool {
+                        name: tool_name.clone(),
+                        call_id: call_id.clone(),
+                    };
+                    self.status_text = tool_phase(&tool_name, &arguments);
+                    let header = self.echo_command(&tool_summary(&tool_name, &arguments));
+                    self.tool_headers.insert(call_id.clone(), header);
+                    self.running_tools.push(RunningTool { call_id, tool_name, arguments, progress: None });
+                }
+                AgentEvent::ToolProgress { call_id, done, total, unit } => {
+                    if let Some(tool) = self.running_tools.iter_mut().find(|t| t.call_id == call_id) {
+                        tool.progress = Some((done, total, unit));
+                    }
                 }
                 AgentEvent::ToolOutput { chunk, .. } => {
                     self.terminal_lines.push(TerminalLine {
@@ -90,21 +260,87 @@ impl UiState {
                         is_stderr: false,
                     });
                 }
+                AgentEvent::ToolArtifact { path, .. } => {
+                    // Read it now, so it's ready when the result is drawn
+                    self.images.get(&path);
+                }
                 AgentEvent::ToolExecEnd {
                     call_id,
                     result,
-                    ..
+                    success,
+                    duration_ms,
+                    data,
                 } => {
+                    if let Some(header) = self.tool_headers.remove(&call_id) {
+                        self.time_command(header, duration_ms);
+                    }
+                    let call = self
+                        .running_tools
+                        .iter()
+                        .position(|t| t.call_id == call_id)
+                        .map(|i| self.running_tools.remove(i));
+                    if self.running_tools.is_empty() && self.is_busy() {
+                        self.agent_status = AgentState::Thinking;
+                        self.status_text = "Thinking...".to_string();
+                    }
                     self.messages.push(ChatEntry {
                         role: "tool".to_string(),
                         content: result,
                         is_tool_call: true,
-                        tool_name: Some(call_id),
+                        tool_name: call.as_ref().map(|c| c.tool_name.clone()),
+                        arguments: call.map(|c| c.arguments),
+                        duration_ms: Some(duration_ms),
+                        success: Some(success),
+                        attachments: Vec::new(),
+                        data,
                     });
                 }
                 AgentEvent::TurnEnd { .. } => {
+                    self.outbox.reachable();
                     self.agent_status = AgentState::Idle;
                     self.status_text = "Ready".to_string();
+                    // Calls cut short by a cancelled turn never end
+                    self.running_tools.clear();
+                    self.tool_headers.clear();
+                }
+                AgentEvent::ReviewStart => {
+                    self.status_text = "Reviewing the answer...".to_string();
+                }
+                AgentEvent::PlanReady { steps } => {
+                    self.status_text = format!("Planned {} step(s)", steps.len());
+                    self.plan.show_plan(steps);
+                }
+                AgentEvent::PlanStepStart { index } => {
+                    self.plan.start_step(index);
+                    self.status_text = self.plan.progress(index);
+                }
+                AgentEvent::PlanStepEnd { index, success, summary } => {
+                    self.plan.finish_step(index, success, summary);
+                    if !success {
+                        self.status_text = format!("{} failed", self.plan.progress(index));
+                    }
+                }
+                AgentEvent::ModelLoadProgress { progress, .. } => {
+                    self.status_text = format!("Loading model… {:.0}%", progress * 100.0);
+                }
+                AgentEvent::LlmFallback { provider, model, reason } => {
+                    self.status_text = format!("Falling back to {} / {}", provider, model);
+                    self.push_notice(format!("{} Retrying with {} / {}.", reason, provider, model));
+                }
+                AgentEvent::LlmCallEnd { model, latency_ms, error } => {
+                    self.health.last_llm = Some(LlmCall { model, latency_ms, error });
+                }
+                AgentEvent::ShellReady => {
+                    self.health.shell = ShellHealth::Ready;
+                }
+                AgentEvent::ShellUnavailable { reason } => {
+                    self.health.shell = ShellHealth::Unavailable(reason.clone());
+                    self.status_text = "Shell unavailable".to_string();
+                    self.terminal_lines.push(TerminalLine {
+                        text: reason.clone(),
+                        is_stderr: true,
+                    });
+                    self.push_notice(reason);
                 }
                 AgentEvent::Error { message } => {
                     self.agent_status = AgentState::Error(message.clone());
@@ -114,25 +350,332 @@ impl UiState {
                         content: message,
                         is_tool_call: false,
                         tool_name: None,
+                        arguments: None,
+                        duration_ms: None,
+                        success: None,
+                        attachments: Vec::new(),
+                        data: None,
                     });
                 }
             }
         }
     }
 
+    /// Replace the displayed conversation with a restored message history
+    /// (e.g. when switching sessions).
+    pub fn load_messages(&mut self, messages: &[Message]) {
+        self.messages.clear();
+        self.share.viewing = None;
+        // Queued messages belong to the conversation being left
+        self.outbox.clear();
+        self.streaming_text.clear();
+        // Tool results name their call; the call itself is on the
+        // assistant message before them
+        let mut calls: HashMap<&str, (&str, &str)> = HashMap::new();
+        for msg in messages {
+            for call in &msg.tool_calls {
+                calls.insert(&call.id, (&call.function.name, &call.function.arguments));
+            }
+            let text = msg.content.as_text();
+            self.fonts.observe_text(text);
+            if let Some(reasoning) = &msg.reasoning {
+                self.messages.push(ChatEntry {
+                    role: "reasoning".to_string(),
+                    content: reasoning.clone(),
+                    is_tool_call: false,
+                    tool_name: None,
+                    arguments: None,
+                    duration_ms: None,
+                    success: None,
+                    attachments: Vec::new(),
+                    data: None,
+                });
+            }
+            let entry = match msg.role {
+                Role::System => continue,
+                Role::User => ChatEntry {
+                    role: "user".to_string(),
+                    content: text.to_string(),
+                    is_tool_call: false,
+                    tool_name: None,
+                    arguments: None,
+                    duration_ms: None,
+                    success: None,
+                    attachments: msg.meta.as_ref().map(|m| m.attachments.clone()).unwrap_or_default(),
+                    data: None,
+                },
+                // Intermediate assistant text before tool calls was shown as
+                // streaming text live; only final answers get an entry.
+                Role::Assistant if !msg.tool_calls.is_empty() || text.is_empty() => continue,
+                Role::Assistant => ChatEntry {
+                    role: "assistant".to_string(),
+                    content: text.to_string(),
+                    is_tool_call: false,
+                    tool_name: None,
+                    arguments: None,
+                    duration_ms: None,
+                    success: None,
+                    attachments: Vec::new(),
+                    data: None,
+                },
+                Role::Tool => {
+                    let call = msg.tool_call_id.as_deref().and_then(|id| calls.get(id));
+                    ChatEntry {
+                        role: "tool".to_string(),
+                        content: text.to_string(),
+                        is_tool_call: true,
+                        tool_name: call.map(|(name, _)| name.to_string()),
+                        arguments: call.map(|(_, arguments)| arguments.to_string()),
+                        duration_ms: None,
+                        success: None,
+                        attachments: Vec::new(),
+                        data: msg.meta.as_ref().and_then(|m| m.tool_data.clone()),
+                    }
+                }
+            };
+            self.messages.push(entry);
+        }
+        self.agent_status = AgentState::Idle;
+        self.status_text = "Ready".to_string();
+    }
+
+    /// Show a shared conversation, read-only, in place of the session's
+    pub fn load_shared(&mut self, transcript: &SharedTranscript) {
+        self.load_messages(&transcript.messages);
+        self.share.viewing = Some(transcript.title.clone());
+    }
+
+    /// Echo a command to the terminal as a `$ …` line. Returns the line's
+    /// index, to time it with [`UiState::time_command`] once it finishes.
+    pub fn echo_command(&mut self, command: &str) -> usize {
+        self.terminal_lines.push(TerminalLine {
+            text: format!("$ {}", command),
+            is_stderr: false,
+        });
+        self.terminal_lines.len() - 1
+    }
+
+    /// Note on a command's `$ …` line how long it took
+    pub fn time_command(&mut self, header: usize, duration_ms: u64) {
+        if let Some(line) = self.terminal_lines.get_mut(header) {
+            line.text.push_str(&format!("  ({})", format_duration(duration_ms)));
+        }
+    }
+
     /// Add a user message to the display
-    pub fn push_user_message(&mut self, text: &str) {
+    pub fn push_user_message(&mut self, text: &str, attachments: Vec<String>) {
+        self.fonts.observe_text(text);
         self.messages.push(ChatEntry {
             role: "user".to_string(),
             content: text.to_string(),
             is_tool_call: false,
             tool_name: None,
+            arguments: None,
+            duration_ms: None,
+            success: None,
+            attachments,
+            data: None,
         });
     }
 
+    /// A note in the chat from the app rather than the conversation
+    pub fn push_notice(&mut self, text: impl Into<String>) {
+        self.messages.push(ChatEntry {
+            role: "notice".to_string(),
+            content: text.into(),
+            is_tool_call: false,
+            tool_name: None,
+            arguments: None,
+            duration_ms: None,
+            success: None,
+            attachments: Vec::new(),
+            data: None,
+        });
+    }
+
+    /// Show `message` as queued and hold it until the provider can be
+    /// reached
+    pub fn queue_message(&mut self, message: Outgoing) {
+        self.push_user_message(&message.text, message.attachments.clone());
+        if let Some(entry) = self.messages.last_mut() {
+            entry.role = "pending".to_string();
+        }
+        self.outbox.push(message);
+    }
+
+    /// Track the browser's connection; coming back online sends queued
+    /// messages without waiting out the retry delay
+    pub fn set_online(&mut self, online: bool) {
+        if online && !self.online {
+            self.outbox.reachable();
+        }
+        self.online = online;
+    }
+
+    /// The next queued message to send now, shown as sent from here on.
+    /// Nothing while offline or busy.
+    pub fn take_queued(&mut self, now: f64) -> Option<Outgoing> {
+        if !self.online || self.is_busy() {
+            return None;
+        }
+        let message = self.outbox.take_due(now)?;
+        if let Some(entry) = self.messages.iter_mut().find(|e| e.role == "pending") {
+            entry.role = "user".to_string();
+        }
+        Some(message)
+    }
+
+    /// `message` was sent but its turn couldn't reach the provider: show
+    /// it as queued again and retry later
+    pub fn requeue(&mut self, message: Outgoing, now: f64) {
+        if let Some(entry) = self.messages.iter_mut().rev().find(|e| e.role == "user" && e.content == message.text) {
+            entry.role = "pending".to_string();
+        }
+        self.outbox.put_back(message, now);
+    }
+
+    /// Move the previewed text file into the editor
+    pub fn edit_viewed_file(&mut self) {
+        let (Some(path), Some(data)) = (&self.viewer.path, self.viewer.data()) else {
+            return;
+        };
+        let text = String::from_utf8_lossy(data).into_owned();
+        self.editor.open(&path.clone(), text);
+        self.viewer.close();
+    }
+
+    /// Messages to show with their index, skipping tool results while
+    /// `hide_tool_messages` is on
+    pub fn visible_messages(&self) -> impl Iterator<Item = (usize, &ChatEntry)> {
+        self.messages
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !(self.hide_tool_messages && entry.is_tool_call))
+    }
+
     pub fn is_busy(&self) -> bool {
         !matches!(self.agent_status, AgentState::Idle | AgentState::Error(_))
     }
+
+    /// Seconds the current turn has been busy, as of UI time `now`. None
+    /// when idle.
+    pub fn busy_elapsed(&mut self, now: f64) -> Option<f64> {
+        if !self.is_busy() {
+            self.busy_since = None;
+            return None;
+        }
+        Some(now - *self.busy_since.get_or_insert(now))
+    }
+}
+
+/// One line describing a tool call, for the terminal and tool cards:
+/// "write_file → /workspace/src/main.rs, 1.2 KB". Unknown tools show
+/// their arguments as compact JSON.
+pub fn tool_summary(tool_name: &str, arguments: &str) -> String {
+    let Ok(args) = serde_json::from_str::<serde_json::Value>(arguments) else {
+        return format!("{} {}", tool_name, arguments.trim());
+    };
+    let str_arg = |key: &str| args[key].as_str().unwrap_or("").to_string();
+    let detail = match tool_name {
+        "bash" => match args["cwd"].as_str() {
+            Some(cwd) => format!("{} (in {})", str_arg("command"), cwd),
+            None => str_arg("command"),
+        },
+        "write_file" => {
+            let size = format_size(args["content"].as_str().map_or(0, str::len) as u64);
+            match args["mode"].as_str() {
+                Some(mode) if mode != "overwrite" => format!("{}, {} ({})", str_arg("path"), size, mode),
+                _ => format!("{}, {}", str_arg("path"), size),
+            }
+        }
+        "read_file" => match (args["offset"].as_u64(), args["limit"].as_u64()) {
+            (None, None) => str_arg("path"),
+            (offset, limit) => {
+                let first = offset.unwrap_or(1);
+                let last = limit.map_or(String::new(), |n| (first + n.saturating_sub(1)).to_string());
+                format!("{} (lines {}–{})", str_arg("path"), first, last)
+            }
+        },
+        "list_dir" => str_arg("path"),
+        "run_script" => {
+            let script_args: Vec<&str> =
+                args["args"].as_array().map(|a| a.iter().filter_map(|v| v.as_str()).collect()).unwrap_or_default();
+            std::iter::once(str_arg("path").as_str()).chain(script_args).collect::<Vec<_>>().join(" ")
+        }
+        "search_workspace" => format!("\"{}\"", str_arg("query")),
+        "replace_in_files" => {
+            let glob = args["glob"].as_str().filter(|g| !g.trim().is_empty()).unwrap_or("**");
+            let dry_run = if args["dry_run"].as_bool() == Some(true) { " (dry run)" } else { "" };
+            format!("\"{}\" → \"{}\" in {}{}", str_arg("pattern"), str_arg("replacement"), glob, dry_run)
+        }
+        "sql" => {
+            let database = args["database"].as_str().filter(|d| !d.trim().is_empty());
+            let on = database.map_or(String::new(), |d| format!(" on {}", d));
+            let query = str_arg("query");
+            let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+            match args["csv_path"].as_str() {
+                Some(path) if query.is_empty() => format!("load {}{}", path, on),
+                Some(path) => format!("load {}, then {}{}", path, query, on),
+                None => format!("{}{}", query, on),
+            }
+        }
+        "run_js" => {
+            let code = str_arg("code");
+            let lines: Vec<&str> = code.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+            match lines.len() {
+                0 | 1 => lines.first().copied().unwrap_or_default().to_string(),
+                n => format!("{} (+{} line{})", lines[0], n - 1, if n == 2 { "" } else { "s" }),
+            }
+        }
+        _ => return format!("{} {}", tool_name, args),
+    };
+    format!("{} → {}", tool_name, detail)
+}
+
+/// `arguments` indented for reading, or as given if it isn't JSON
+pub fn pretty_arguments(arguments: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(arguments)
+        .ok()
+        .and_then(|args| serde_json::to_string_pretty(&args).ok())
+        .unwrap_or_else(|| arguments.to_string())
+}
+
+/// Longest command quoted in the busy indicator
+const PHASE_COMMAND_CHARS: usize = 40;
+
+/// What a tool call is doing, for the busy indicator: "Running bash: ls",
+/// "Writing notes.md"
+pub fn tool_phase(tool_name: &str, arguments: &str) -> String {
+    let args: serde_json::Value = serde_json::from_str(arguments).unwrap_or_default();
+    let file = || {
+        let path = args["path"].as_str().unwrap_or("");
+        path.rsplit('/').next().filter(|name| !name.is_empty()).unwrap_or(path).to_string()
+    };
+    match tool_name {
+        "bash" => {
+            let command = args["command"].as_str().unwrap_or("").trim();
+            if command.is_empty() {
+                return "Running bash".to_string();
+            }
+            let mut quoted: String = command.chars().take(PHASE_COMMAND_CHARS).collect();
+            if quoted.len() < command.len() {
+                quoted.push('…');
+            }
+            format!("Running bash: {}", quoted)
+        }
+        "write_file" => format!("Writing {}", file()),
+        "read_file" => format!("Reading {}", file()),
+        "run_script" => format!("Running {}", file()),
+        "list_dir" => format!("Listing {}", args["path"].as_str().unwrap_or("/")),
+        "search_workspace" => "Searching the workspace".to_string(),
+        "replace_in_files" => "Replacing in files".to_string(),
+        "run_js" => "Running JavaScript".to_string(),
+        "sql" => match args["csv_path"].as_str() {
+            Some(path) => format!("Loading {}", path.rsplit('/').next().unwrap_or(path)),
+            None => "Querying the database".to_string(),
+        },
+        other => format!("Running {}", other),
+    }
 }
 
 impl Default for UiState {
@@ -0,0 +1,223 @@
+//! Tables in chat messages.
+//!
+//! Markdown tables in answers (and in markdown tool results, such as SQL
+//! query output) and CSV/TSV files read by tools are shown as grids that
+//! can be sorted by clicking a column header, rather than as misaligned
+//! text. Only the first [`MAX_TABLE_ROWS`] rows are drawn; a longer table
+//! can be opened in the viewer, after saving it under [`TABLE_DIR`] when it
+//! didn't come from a file.
+//!
+//! ```text
+//! | Crate      | Lines |
+//! |------------|------:|
+//! | agent-core | 9120  |
+//! ```
+
+use std::cmp::Ordering;
+
+use agent_core::sql::parse_csv;
+
+/// Rows drawn before the rest is left to the viewer
+pub const MAX_TABLE_ROWS: usize = 100;
+
+/// Where tables that didn't come from a file are saved to open them
+pub const TABLE_DIR: &str = "/workspace/tables";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub headers: Vec<String>,
+    /// Cells of each row, as many as there are headers
+    pub rows: Vec<Vec<String>>,
+}
+
+/// A piece of a message: text, or a table found in it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Table(Table),
+}
+
+impl Table {
+    /// Whether every non-empty cell of `column` is a number
+    pub fn is_numeric(&self, column: usize) -> bool {
+        let mut cells = self.rows.iter().map(|row| row[column].trim()).filter(|c| !c.is_empty()).peekable();
+        cells.peek().is_some() && cells.all(|c| parse_number(c).is_some())
+    }
+
+    /// Row indices ordered by `column`: numbers by value, text ignoring
+    /// case. Equal rows keep their order.
+    pub fn sorted(&self, column: usize, descending: bool) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.rows.len()).collect();
+        order.sort_by(|&a, &b| {
+            let ordering = compare_cells(&self.rows[a][column], &self.rows[b][column]);
+            if descending { ordering.reverse() } else { ordering }
+        });
+        order
+    }
+
+    /// The table as CSV, quoting cells where needed
+    pub fn to_csv(&self) -> String {
+        let line = |cells: &[String]| {
+            cells
+                .iter()
+                .map(|cell| {
+                    if cell.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", cell.replace('"', "\"\""))
+                    } else {
+                        cell.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mut csv = line(&self.headers);
+        for row in &self.rows {
+            csv.push('\n');
+            csv.push_str(&line(row));
+        }
+        csv.push('\n');
+        csv
+    }
+}
+
+/// Numbers (with `,` thousands separators allowed) by value, anything else
+/// as text ignoring case; numbers come before text
+pub fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (parse_number(a.trim()), parse_number(b.trim())) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.to_lowercase().cmp(&b.to_lowercase()),
+    }
+}
+
+fn parse_number(cell: &str) -> Option<f64> {
+    cell.replace(',', "").parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// The delimiter of a CSV or TSV file, by its name
+pub fn delimiter_for(path: &str) -> Option<char> {
+    let lower = path.to_ascii_lowercase();
+    if lower.ends_with(".csv") {
+        Some(',')
+    } else if lower.ends_with(".tsv") || lower.ends_with(".tab") {
+        Some('\t')
+    } else {
+        None
+    }
+}
+
+/// A CSV/TSV text as a table, the first record naming the columns. `None`
+/// unless it has a header and a row, at least two columns, and every record
+/// has as many fields as the header — text that was cut off or isn't really
+/// delimited stays text.
+pub fn parse_delimited(text: &str, delimiter: char) -> Option<Table> {
+    let mut records = parse_csv(text, delimiter).into_iter();
+    let headers = records.next()?;
+    let rows: Vec<Vec<String>> = records.collect();
+    if headers.len() < 2 || rows.is_empty() || rows.iter().any(|row| row.len() != headers.len()) {
+        return None;
+    }
+    Some(Table { headers, rows })
+}
+
+/// `text` split around its markdown tables: a header row, a delimiter row
+/// (`|---|:--:|`) and the rows up to the first line without a `|`. Tables
+/// inside fenced code blocks are left alone.
+pub fn split_tables(text: &str) -> Vec<Segment> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut segments = Vec::new();
+    let mut prose: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        let headers = split_row(line);
+        let is_table = !in_fence
+            && headers.len() >= 2
+            && lines.get(i + 1).is_some_and(|next| is_delimiter_row(next, headers.len()));
+        if !is_table {
+            prose.push(line);
+            i += 1;
+            continue;
+        }
+        if !prose.is_empty() {
+            segments.push(Segment::Text(prose.join("\n")));
+            prose.clear();
+        }
+        i += 2;
+        let mut rows = Vec::new();
+        while let Some(line) = lines.get(i).filter(|l| l.contains('|') && !l.trim().is_empty()) {
+            let mut row = split_row(line);
+            row.resize(headers.len(), String::new());
+            rows.push(row);
+            i += 1;
+        }
+        segments.push(Segment::Table(Table { headers, rows }));
+    }
+    if !prose.is_empty() {
+        segments.push(Segment::Text(prose.join("\n")));
+    }
+    segments
+}
+
+/// The cells of a table row, split at unescaped `|` and trimmed
+fn split_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    if !line.contains('|') {
+        return Vec::new();
+    }
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => cell.push(chars.next().unwrap_or('|')),
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    // Leading and trailing pipes are optional
+    if line.starts_with('|') {
+        cells.remove(0);
+    }
+    if line.ends_with('|') && !line.ends_with("\\|") {
+        cells.pop();
+    }
+    cells
+}
+
+fn is_delimiter_row(line: &str, columns: usize) -> bool {
+    let cells = split_row(line);
+    cells.len() == columns
+        && cells.iter().all(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+        })
+}
+
+/// Tables waiting to be saved as files, to open in the viewer
+#[derive(Default)]
+pub struct TableExports {
+    /// Tables to write: path and CSV
+    saves: Vec<(String, String)>,
+}
+
+impl TableExports {
+    /// Queue `table` to be saved as a file named after `now`. Returns its
+    /// path.
+    pub fn export(&mut self, table: &Table, now: chrono::DateTime<chrono::Local>) -> String {
+        let path = format!("{}/{}.csv", TABLE_DIR, now.format("%Y%m%d-%H%M%S%.3f"));
+        self.saves.push((path.clone(), table.to_csv()));
+        path
+    }
+
+    /// Tables to write. Called by the app each frame.
+    pub fn take_saves(&mut self) -> Vec<(String, String)> {
+        std::mem::take(&mut self.saves)
+    }
+}
@@ -1,7 +1,9 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::state::*;
     use agent_types::event::AgentEvent;
+    use agent_types::message::Message;
     use agent_core::runtime::AgentState;
 
     // ─── UiState Tests ───────────────────────────────────────
@@ -22,7 +24,7 @@ mod tests {
     #[test]
     fn test_ui_state_push_user_message() {
         let mut state = UiState::new();
-        state.push_user_message("hello");
+        state.push_user_message("hello", Vec::new());
         assert_eq!(state.messages.len(), 1);
         assert_eq!(state.messages[0].role, "user");
         assert_eq!(state.messages[0].content, "hello");
@@ -41,6 +43,16 @@ mod tests {
         assert!(state.is_busy());
     }
 
+    #[test]
+    fn test_ui_state_process_shell_unavailable() {
+        let mut state = UiState::new();
+        state.process_events(vec![AgentEvent::ShellUnavailable { reason: "no worker".to_string() }]);
+        assert_eq!(state.status_text, "Shell unavailable");
+        assert_eq!(state.terminal_lines.len(), 1);
+        assert!(state.terminal_lines[0].is_stderr);
+        assert_eq!(state.messages.last().unwrap().role, "notice");
+    }
+
     #[test]
     fn test_ui_state_process_llm_delta() {
         let mut state = UiState::new();
@@ -65,6 +77,35 @@ mod tests {
         assert!(state.streaming_text.is_empty());
     }
 
+    #[test]
+    fn test_ui_state_process_llm_fallback() {
+        let mut state = UiState::new();
+        state.process_events(vec![AgentEvent::LlmFallback {
+            provider: "OpenAI".to_string(),
+            model: "gpt-4o".to_string(),
+            reason: "The provider had a server error".to_string(),
+        }]);
+
+        assert_eq!(state.messages.len(), 1);
+        assert_eq!(state.messages[0].role, "notice");
+        assert!(state.messages[0].content.contains("Retrying with OpenAI / gpt-4o"));
+        assert_eq!(state.status_text, "Falling back to OpenAI / gpt-4o");
+    }
+
+    #[test]
+    fn test_ui_state_process_llm_reasoning() {
+        let mut state = UiState::new();
+        state.process_events(vec![
+            AgentEvent::LlmReasoning { text: "Let me think".to_string() },
+            AgentEvent::LlmComplete { text: "Answer".to_string() },
+        ]);
+
+        assert_eq!(state.messages.len(), 2);
+        assert_eq!(state.messages[0].role, "reasoning");
+        assert_eq!(state.messages[0].content, "Let me think");
+        assert_eq!(state.messages[1].role, "assistant");
+    }
+
     #[test]
     fn test_ui_state_process_tool_exec() {
         let mut state = UiState::new();
@@ -75,9 +116,75 @@ mod tests {
             arguments: r#"{"command":"ls"}"#.to_string(),
         }]);
 
-        assert_eq!(state.status_text, "Running: bash");
+        assert_eq!(state.status_text, "Running bash: ls");
+        assert!(matches!(state.agent_status, AgentState::ExecutingTool { ref name, .. } if name == "bash"));
         assert_eq!(state.terminal_lines.len(), 1);
         assert!(state.terminal_lines[0].text.contains("bash"));
+
+        state.process_events(vec![AgentEvent::ToolExecEnd {
+            call_id: "c1".to_string(),
+            result: String::new(),
+            success: true,
+            duration_ms: 3,
+            data: None,
+        }]);
+        assert_eq!(state.agent_status, AgentState::Thinking);
+        assert_eq!(state.status_text, "Thinking...");
+    }
+
+    #[test]
+    fn test_tool_phase() {
+        assert_eq!(tool_phase("write_file", r#"{"path":"/workspace/notes/file.txt"}"#), "Writing file.txt");
+        assert_eq!(tool_phase("read_file", r#"{"path":"main.rs"}"#), "Reading main.rs");
+        assert_eq!(tool_phase("list_dir", r#"{"path":"/workspace"}"#), "Listing /workspace");
+        assert_eq!(tool_phase("bash", "not json"), "Running bash");
+        let long = format!(r#"{{"command":"{}"}}"#, "x".repeat(60));
+        assert_eq!(tool_phase("bash", &long), format!("Running bash: {}…", "x".repeat(40)));
+        assert_eq!(tool_phase("sql", r#"{"csv_path":"/workspace/data/sales.csv"}"#), "Loading sales.csv");
+        assert_eq!(tool_phase("recall", "{}"), "Running recall");
+    }
+
+    #[test]
+    fn test_tool_summary() {
+        let content = "x".repeat(1_229);
+        let write = format!(r#"{{"path":"/workspace/src/main.rs","content":"{}"}}"#, content);
+        assert_eq!(tool_summary("write_file", &write), "write_file → /workspace/src/main.rs, 1.2 KB");
+        let append = r#"{"path":"log.txt","content":"hi","mode":"append"}"#;
+        assert_eq!(tool_summary("write_file", append), "write_file → log.txt, 2 B (append)");
+        assert_eq!(tool_summary("bash", r#"{"command":"make","cwd":"/src"}"#), "bash → make (in /src)");
+        assert_eq!(tool_summary("read_file", r#"{"path":"a.rs","offset":10,"limit":5}"#), "read_file → a.rs (lines 10–14)");
+        assert_eq!(tool_summary("run_script", r#"{"path":"s.py","args":["-v","x"]}"#), "run_script → s.py -v x");
+        assert_eq!(tool_summary("search_workspace", r#"{"query":"retry"}"#), r#"search_workspace → "retry""#);
+        let replace = r#"{"pattern":"foo\\(","replacement":"bar(","glob":"src/**/*.rs","dry_run":true}"#;
+        assert_eq!(tool_summary("replace_in_files", replace), r#"replace_in_files → "foo\(" → "bar(" in src/**/*.rs (dry run)"#);
+        let script = r#"{"code":"const xs = [3, 1, 2];\n\nxs.sort()"}"#;
+        assert_eq!(tool_summary("run_js", script), "run_js → const xs = [3, 1, 2]; (+1 line)");
+        let sql = r#"{"csv_path":"/workspace/sales.csv","query":"SELECT region,\n  sum(amount) FROM sales"}"#;
+        assert_eq!(tool_summary("sql", sql), "sql → load /workspace/sales.csv, then SELECT region, sum(amount) FROM sales");
+        assert_eq!(tool_summary("sql", r#"{"query":"SELECT 1","database":"crm"}"#), "sql → SELECT 1 on crm");
+        assert_eq!(tool_summary("recall", r#"{"key":"k"}"#), r#"recall {"key":"k"}"#);
+        assert_eq!(tool_summary("bash", "not json"), "bash not json");
+        assert_eq!(pretty_arguments(r#"{"a":1}"#), "{\n  \"a\": 1\n}");
+        assert_eq!(pretty_arguments("not json"), "not json");
+    }
+
+    #[test]
+    fn test_busy_elapsed_counts_per_turn() {
+        use crate::panels::chat::format_elapsed;
+
+        let mut state = UiState::new();
+        assert_eq!(state.busy_elapsed(1.0), None);
+        state.process_events(vec![AgentEvent::TurnStart { turn_id: 1 }]);
+        assert_eq!(state.busy_elapsed(10.0), Some(0.0));
+        assert_eq!(state.busy_elapsed(17.5), Some(7.5));
+        // A new turn starts the clock over
+        state.process_events(vec![AgentEvent::TurnEnd { turn_id: 1 }, AgentEvent::TurnStart { turn_id: 2 }]);
+        assert_eq!(state.busy_elapsed(30.0), Some(0.0));
+        state.process_events(vec![AgentEvent::TurnEnd { turn_id: 2 }]);
+        assert_eq!(state.busy_elapsed(31.0), None);
+
+        assert_eq!(format_elapsed(7.9), "7s");
+        assert_eq!(format_elapsed(65.0), "1m 05s");
     }
 
     #[test]
@@ -109,6 +216,8 @@ mod tests {
             call_id: "c1".to_string(),
             result: "output here".to_string(),
             success: true,
+            duration_ms: 0,
+            data: None,
         }]);
 
         assert_eq!(state.messages.len(), 1);
@@ -117,6 +226,137 @@ mod tests {
         assert!(state.messages[0].is_tool_call);
     }
 
+    #[test]
+    fn test_tool_result_data_reaches_chat() {
+        use agent_types::tool::ToolResultData;
+
+        let data = ToolResultData { exit_code: Some(1), artifacts: vec!["/workspace/out.txt".to_string()], ..Default::default() };
+        let mut state = UiState::new();
+        state.process_events(vec![AgentEvent::ToolExecEnd {
+            call_id: "c1".to_string(),
+            result: "failed".to_string(),
+            success: false,
+            duration_ms: 0,
+            data: Some(data.clone()),
+        }]);
+        assert_eq!(state.messages[0].data, Some(data.clone()));
+
+        // and back from a saved session
+        let mut result = Message::tool_result("c1", "failed");
+        result.stamp().tool_data = Some(data.clone());
+        state.load_messages(&[Message::system("prompt"), Message::user("run it"), result]);
+        assert_eq!(state.messages[1].data, Some(data));
+    }
+
+    #[test]
+    fn test_ui_state_tracks_running_tool_progress() {
+        let mut state = UiState::new();
+        state.process_events(vec![
+            AgentEvent::ToolExecStart {
+                call_id: "c1".to_string(),
+                tool_name: "write_file".to_string(),
+                arguments: "{}".to_string(),
+            },
+            AgentEvent::ToolProgress {
+                call_id: "c1".to_string(),
+                done: 1024,
+                total: Some(4096),
+                unit: "bytes".to_string(),
+            },
+        ]);
+        assert_eq!(state.running_tools.len(), 1);
+        assert_eq!(state.running_tools[0].tool_name, "write_file");
+        assert_eq!(state.running_tools[0].progress, Some((1024, Some(4096), "bytes".to_string())));
+
+        state.process_events(vec![AgentEvent::ToolExecEnd {
+            call_id: "c1".to_string(),
+            result: "Written".to_string(),
+            success: true,
+            duration_ms: 5,
+            data: None,
+        }]);
+        assert!(state.running_tools.is_empty());
+
+        // A cancelled turn leaves no stale cards
+        state.process_events(vec![
+            AgentEvent::ToolExecStart {
+                call_id: "c2".to_string(),
+                tool_name: "bash".to_string(),
+                arguments: "{}".to_string(),
+            },
+            AgentEvent::TurnEnd { turn_id: 1 },
+        ]);
+        assert!(state.running_tools.is_empty());
+    }
+
+    #[test]
+    fn test_progress_text() {
+        use crate::panels::chat::progress_text;
+        assert_eq!(progress_text(3 << 20, Some(8 << 20), "bytes"), "3.0 MB of 8.0 MB");
+        assert_eq!(progress_text(12, Some(40), "files"), "12 of 40 files");
+        assert_eq!(progress_text(7, None, "files"), "7 files");
+    }
+
+    #[test]
+    fn test_ui_state_times_tool_calls() {
+        let mut state = UiState::new();
+        state.process_events(vec![
+            AgentEvent::ToolExecStart {
+                call_id: "c1".to_string(),
+                tool_name: "bash".to_string(),
+                arguments: r#"{"command":"make"}"#.to_string(),
+            },
+            AgentEvent::ToolOutput { call_id: "c1".to_string(), chunk: "built".to_string() },
+            AgentEvent::ToolExecEnd {
+                call_id: "c1".to_string(),
+                result: "built".to_string(),
+                success: true,
+                duration_ms: 2_150,
+                data: None,
+            },
+        ]);
+        assert_eq!(state.terminal_lines[0].text, "$ bash → make  (2.1 s)");
+        assert_eq!(state.terminal_lines[1].text, "built");
+        assert_eq!(state.messages[0].duration_ms, Some(2_150));
+        assert_eq!(state.messages[0].tool_name.as_deref(), Some("bash"));
+        assert_eq!(state.messages[0].success, Some(true));
+        assert_eq!(state.messages[0].arguments.as_deref(), Some(r#"{"command":"make"}"#));
+        assert!(state.tool_headers.is_empty());
+
+        let header = state.echo_command("ls");
+        state.time_command(header, 40);
+        assert_eq!(state.terminal_lines[header].text, "$ ls  (40 ms)");
+    }
+
+    #[test]
+    fn test_ui_state_hides_tool_messages() {
+        let mut state = UiState::new();
+        state.push_user_message("build it", Vec::new());
+        state.process_events(vec![
+            AgentEvent::ToolExecStart {
+                call_id: "c1".to_string(),
+                tool_name: "bash".to_string(),
+                arguments: r#"{"command":"make"}"#.to_string(),
+            },
+            AgentEvent::ToolExecEnd {
+                call_id: "c1".to_string(),
+                result: "make: *** No targets".to_string(),
+                success: false,
+                duration_ms: 10,
+                data: None,
+            },
+            AgentEvent::LlmComplete { text: "There is no Makefile".to_string() },
+            AgentEvent::TurnEnd { turn_id: 1 },
+        ]);
+        assert_eq!(state.messages[1].success, Some(false));
+        assert_eq!(state.messages[2].success, None);
+        assert_eq!(state.visible_messages().count(), 3);
+
+        state.hide_tool_messages = true;
+        let shown: Vec<usize> = state.visible_messages().map(|(idx, _)| idx).collect();
+        assert_eq!(shown, vec![0, 2]);
+    }
+
     #[test]
     fn test_ui_state_process_turn_end() {
         let mut state = UiState::new();
@@ -145,11 +385,22 @@ mod tests {
     }
 
     #[test]
+    fn test_ui_state_model_load_progress() {
+        let mut state = UiState::new();
+        state.process_events(vec![AgentEvent::ModelLoadProgress {
+            progress: 0.42,
+            text: "Fetching params".to_string(),
+        }]);
+        assert_eq!(state.status_text, "Loading model… 42%");
+    }
+
+    #[test]
+    #[allow(clippy::len_zero)]
     fn test_ui_state_full_turn_lifecycle() {
         let mut state = UiState::new();
 
         // Simulate a complete turn
-        state.push_user_message("run ls");
+        state.push_user_message("run ls", Vec::new());
 
         state.process_events(vec![
             AgentEvent::TurnStart { turn_id: 1 },
@@ -176,6 +427,8 @@ mod tests {
                 call_id: "c1".to_string(),
                 result: "file1.txt\nfile2.txt".to_string(),
                 success: true,
+                duration_ms: 0,
+                data: None,
             },
         ]);
 
@@ -196,6 +449,48 @@ mod tests {
         assert!(state.terminal_lines.len() >= 1);
     }
 
+    #[test]
+    fn test_ui_state_load_messages() {
+        let mut state = UiState::new();
+        state.push_user_message("stale", Vec::new());
+        state.streaming_text = "partial".to_string();
+
+        let mut with_calls = Message::assistant("Let me check");
+        with_calls.tool_calls.push(agent_types::message::ToolCallRequest {
+            id: "c1".to_string(),
+            function: agent_types::message::FunctionCall {
+                name: "bash".to_string(),
+                arguments: r#"{"command":"ls"}"#.to_string(),
+            },
+        });
+        state.load_messages(&[
+            Message::system("prompt"),
+            Message::user("run ls"),
+            with_calls,
+            Message::tool_result("c1", "a.txt"),
+            Message::assistant("Found a.txt"),
+        ]);
+
+        let roles: Vec<&str> = state.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "tool", "assistant"]);
+        assert_eq!(state.messages[1].tool_name.as_deref(), Some("bash"));
+        assert_eq!(state.messages[1].arguments.as_deref(), Some(r#"{"command":"ls"}"#));
+        assert!(state.streaming_text.is_empty());
+        assert!(!state.is_busy());
+    }
+
+    #[test]
+    fn test_ui_state_load_messages_with_reasoning() {
+        let mut state = UiState::new();
+        let mut answer = Message::assistant("42");
+        answer.reasoning = Some("6 times 7".to_string());
+        state.load_messages(&[Message::user("question"), answer]);
+
+        let roles: Vec<&str> = state.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "reasoning", "assistant"]);
+        assert_eq!(state.messages[1].content, "6 times 7");
+    }
+
     #[test]
     fn test_ui_state_is_busy_states() {
         let mut state = UiState::new();
@@ -222,4 +517,1191 @@ mod tests {
         assert!(state.messages.is_empty());
         assert!(!state.is_busy());
     }
+
+    // ─── File Viewer Tests ───────────────────────────────────
+
+    use crate::highlight::{Token, syntax_for, tokenize};
+    use crate::panels::chat::{FileRef, file_references, file_spans};
+    use crate::panels::viewer::{FileKind, FileLoad, classify, hex_dump};
+
+    #[test]
+    fn test_viewer_open_and_finish_load() {
+        let mut state = UiState::new();
+        assert!(!state.viewer.is_open());
+
+        state.viewer.open("/workspace/a.txt");
+        assert!(state.viewer.is_open());
+        assert!(state.viewer.loading);
+        let request = state.viewer.take_request().unwrap();
+        assert_eq!(request.path, "/workspace/a.txt");
+        assert!(!request.force);
+        assert!(state.viewer.take_request().is_none());
+
+        // A load for a file that is no longer shown is ignored
+        state.viewer.finish_load(FileLoad::Loaded { path: "/workspace/old".to_string(), data: vec![1], content_type: None });
+        assert!(state.viewer.loading);
+
+        state.viewer.finish_load(FileLoad::Loaded {
+            path: "/workspace/a.txt".to_string(),
+            data: b"hi".to_vec(),
+            content_type: Some("text/plain".to_string()),
+        });
+        assert!(!state.viewer.loading);
+        assert_eq!(state.viewer.data(), Some(&b"hi"[..]));
+
+        state.viewer.close();
+        assert!(!state.viewer.is_open());
+    }
+
+    #[test]
+    fn test_viewer_too_large_and_failed() {
+        let mut state = UiState::new();
+        state.viewer.open("/workspace/big.bin");
+        state.viewer.finish_load(FileLoad::TooLarge { path: "/workspace/big.bin".to_string(), size: 10 << 20 });
+        assert_eq!(state.viewer.too_large, Some(10 << 20));
+        assert!(state.viewer.data().is_none());
+
+        state.viewer.open("/workspace/gone");
+        state.viewer.finish_load(FileLoad::Failed { path: "/workspace/gone".to_string(), error: "File not found".to_string() });
+        assert_eq!(state.viewer.error.as_deref(), Some("File not found"));
+        assert!(state.viewer.too_large.is_none());
+    }
+
+    #[test]
+    fn test_classify_files() {
+        assert_eq!(classify("/a.rs", None, b"fn main() {}"), FileKind::Text);
+        assert_eq!(classify("/a.bin", None, &[0, 1, 2, 3]), FileKind::Binary);
+        assert_eq!(classify("/a.dat", None, &[0xff, 0xfe, 0x41]), FileKind::Binary);
+        let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0];
+        assert_eq!(classify("/logo.PNG", None, &png), FileKind::Image);
+        // The extension alone is not enough
+        assert_eq!(classify("/fake.png", None, b"not an image"), FileKind::Text);
+    }
+
+    #[test]
+    fn test_classify_by_content_type() {
+        let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0];
+        // An image saved without an extension is still shown as one
+        assert_eq!(classify("/plot", Some("image/png"), &png), FileKind::Image);
+        // The content type wins over a misleading extension
+        assert_eq!(classify("/data.png", Some("application/octet-stream"), &png), FileKind::Binary);
+    }
+
+    #[test]
+    fn test_hex_dump_format() {
+        let dump = hex_dump(b"ABC\x00", 16);
+        assert_eq!(dump, format!("00000010  41 42 43 00{}  ABC.", " ".repeat(36)));
+        assert_eq!(hex_dump(&[0u8; 20], 0).lines().count(), 2);
+    }
+
+    #[test]
+    fn test_tokenize_rust() {
+        let text = "fn x() { let s = \"a // b\"; 42 } // done";
+        let syntax = syntax_for("/src/main.rs");
+        assert_eq!(syntax.name, "rust");
+        let spans = tokenize(text, syntax);
+        let find = |tok: Token| -> Vec<&str> {
+            spans.iter().filter(|(t, _)| *t == tok).map(|(_, r)| &text[r.clone()]).collect()
+        };
+        assert_eq!(find(Token::Keyword), vec!["fn", "let"]);
+        assert_eq!(find(Token::String), vec!["\"a // b\""]);
+        assert_eq!(find(Token::Number), vec!["42"]);
+        assert_eq!(find(Token::Comment), vec!["// done"]);
+        // Spans cover the whole input
+        assert_eq!(spans.iter().map(|(_, r)| r.len()).sum::<usize>(), text.len());
+    }
+
+    #[test]
+    fn test_tokenize_block_comment_and_unknown_language() {
+        let css = "/* hi */ a { color: red; }";
+        let spans = tokenize(css, syntax_for("style.css"));
+        assert_eq!(spans[0], (Token::Comment, 0..8));
+
+        let plain = syntax_for("notes");
+        assert_eq!(plain.name, "text");
+        assert!(tokenize("# not a comment", plain).iter().all(|(t, _)| *t != Token::Comment));
+    }
+
+    #[test]
+    fn test_file_references_in_chat() {
+        let text = "Created /workspace/todo.html and updated `/workspace/src/app.js`. \
+                    See /workspace/todo.html again, or /home/workspace/x and /workspace/dir/.";
+        let paths: Vec<String> = file_references(text).into_iter().map(|f| f.path).collect();
+        assert_eq!(paths, vec!["/workspace/todo.html", "/workspace/src/app.js"]);
+        assert!(file_references("no paths here").is_empty());
+    }
+
+    #[test]
+    fn test_file_spans_with_lines() {
+        let text = "error at /workspace/src/main.rs:12:5: expected `;`";
+        let spans = file_spans(text);
+        assert_eq!(spans.len(), 1);
+        let (range, file) = &spans[0];
+        assert_eq!(&text[range.clone()], "/workspace/src/main.rs:12:5");
+        assert_eq!(file, &FileRef { path: "/workspace/src/main.rs".to_string(), line: Some(12) });
+        assert_eq!(file.label(), "/workspace/src/main.rs:12");
+
+        // A trailing colon or a non-numeric suffix is not a line
+        let text = "see /workspace/a.txt: done, /workspace/b.txt:x";
+        let files: Vec<(&str, FileRef)> =
+            file_spans(text).into_iter().map(|(range, file)| (&text[range], file)).collect();
+        assert_eq!(files[0], ("/workspace/a.txt", FileRef { path: "/workspace/a.txt".to_string(), line: None }));
+        assert_eq!(files[1], ("/workspace/b.txt", FileRef { path: "/workspace/b.txt".to_string(), line: None }));
+
+        // The same file at different lines is two references
+        let refs = file_references("/workspace/a.rs:1 and /workspace/a.rs:9 and /workspace/a.rs:1");
+        assert_eq!(refs.iter().map(FileRef::label).collect::<Vec<_>>(), vec!["/workspace/a.rs:1", "/workspace/a.rs:9"]);
+    }
+
+    #[test]
+    fn test_citations_split_from_answer() {
+        use crate::citations::{CitationTarget, split_citations};
+        let text = "Lexing is in [1], see [2].\n\n[2]: https://docs.rs/logos \"logos docs\"\n[1]: /workspace/src/lexer.rs:42\n[x]: not a citation";
+        let (body, citations) = split_citations(text);
+        assert_eq!(body, "Lexing is in [1], see [2].\n\n[x]: not a citation");
+        assert_eq!(
+            citations[0].target,
+            CitationTarget::File(FileRef { path: "/workspace/src/lexer.rs".to_string(), line: Some(42) })
+        );
+        assert_eq!(citations[0].label(), "/workspace/src/lexer.rs:42");
+        assert_eq!(citations[1].target, CitationTarget::Url("https://docs.rs/logos".to_string()));
+        assert_eq!(citations[1].label(), "logos docs");
+
+        // Without reference lines the text is left alone, whitespace and all
+        assert_eq!(split_citations("Plain [1] answer\n").0, "Plain [1] answer\n");
+    }
+
+    #[test]
+    fn test_chat_message_width() {
+        use crate::panels::chat::{message_padding, message_width};
+        use agent_types::config::{ChatDensity, ChatDisplayConfig};
+
+        let mut display = ChatDisplayConfig::default();
+        assert_eq!(message_width(1600.0, &display), 1600.0);
+        display.max_width = 900.0;
+        assert_eq!(message_width(1600.0, &display), 900.0);
+        assert_eq!(message_width(600.0, &display), 600.0);
+        display.bubbles = true;
+        assert_eq!(message_width(600.0, &display), 480.0);
+        assert!(message_padding(ChatDensity::Compact) < message_padding(ChatDensity::Comfortable));
+    }
+
+    #[test]
+    fn test_chat_mentions_complete_and_attach() {
+        use crate::panels::chat::{complete_mention, mention_at_end, mentioned_files};
+        assert_eq!(mention_at_end("look at @"), Some(""));
+        assert_eq!(mention_at_end("look at @/workspace/sr"), Some("/workspace/sr"));
+        assert_eq!(mention_at_end("mail me@example.com"), None);
+        assert_eq!(mention_at_end("@/a.rs then"), None);
+
+        let mut input = "look at @/workspace/sr".to_string();
+        complete_mention(&mut input, "/workspace/src/");
+        assert_eq!(input, "look at @/workspace/src/");
+        complete_mention(&mut input, "/workspace/src/main.rs");
+        assert_eq!(input, "look at @/workspace/src/main.rs ");
+
+        assert_eq!(
+            mentioned_files("Compare @/a.rs, @/b.rs and @/a.rs. Not @/dir/ or me@x"),
+            vec!["/a.rs", "/b.rs"]
+        );
+    }
+
+    #[test]
+    fn test_chat_large_paste_saved_as_file() {
+        use crate::panels::chat::{LargePaste, pasted_path};
+        use chrono::TimeZone;
+        let now = chrono::Local.with_ymd_and_hms(2026, 3, 4, 5, 6, 7).unwrap();
+        assert_eq!(pasted_path(now), "/workspace/pasted/20260304-050607.txt");
+
+        let mut paste = LargePaste::default();
+        paste.pending = Some("one\ntwo\n".to_string());
+        let reference = paste.save_to("/workspace/pasted/a.txt").unwrap();
+        assert_eq!(reference, "[Pasted text saved to /workspace/pasted/a.txt (2 lines, 8 characters)]");
+        assert!(paste.pending.is_none());
+        assert_eq!(paste.save_to("/workspace/pasted/b.txt"), None);
+
+        let (path, text) = paste.take_saves().pop().unwrap();
+        assert!(paste.take_saves().is_empty());
+        paste.finish_save(&path, text, Err("quota exceeded".to_string()));
+        assert_eq!(paste.pending.as_deref(), Some("one\ntwo\n"));
+        assert_eq!(paste.error.as_deref(), Some("Couldn't save /workspace/pasted/a.txt: quota exceeded"));
+    }
+
+    #[test]
+    fn test_chat_user_message_keeps_attachments() {
+        let mut state = UiState::new();
+        let mut msg = Message::user("Explain");
+        msg.stamp().attachments = vec!["/workspace/a.rs".to_string()];
+        state.load_messages(&[msg]);
+        assert_eq!(state.messages[0].attachments, vec!["/workspace/a.rs"]);
+    }
+
+    #[test]
+    fn test_chat_suggestions_follow_workspace() {
+        use crate::panels::chat::suggestions;
+        use agent_types::tool::DirEntry;
+        let labels = |entries: Option<&[DirEntry]>| {
+            suggestions(entries).into_iter().map(|s| s.label).collect::<Vec<_>>()
+        };
+        assert_eq!(labels(None), vec!["Explore the workspace", "Create a Rust hello world"]);
+
+        let file = |name: &str| DirEntry { name: name.to_string(), is_dir: false, size: 0, content_type: None };
+        let entries = vec![file("README.md"), file("Cargo.toml"), file("build.sh")];
+        assert_eq!(
+            labels(Some(&entries)),
+            vec!["Explore the workspace", "Summarize README.md", "Explain the Rust project", "Explain build.sh"]
+        );
+        assert_eq!(suggestions(Some(&entries))[1].prompt, "Summarize /workspace/README.md.");
+    }
+
+    #[test]
+    fn test_viewer_open_at_line() {
+        let mut state = UiState::new();
+        state.viewer.open_at("/workspace/a.rs", Some(42));
+        assert_eq!(state.viewer.line, Some(42));
+        assert_eq!(state.viewer.take_request().unwrap().path, "/workspace/a.rs");
+        state.viewer.open("/workspace/b.rs");
+        assert_eq!(state.viewer.line, None);
+    }
+
+    // ─── Code Editor Tests ───────────────────────────────────
+
+    use crate::panels::editor::{CodeEditor, find_matches};
+
+    #[test]
+    fn test_editor_dirty_and_save_flow() {
+        let mut editor = CodeEditor::default();
+        editor.open("/workspace/a.py", "print(1)\n".to_string());
+        assert!(!editor.is_dirty());
+
+        editor.text.push_str("print(2)\n");
+        assert!(editor.is_dirty());
+        editor.request_save();
+        assert!(editor.saving);
+        let request = editor.take_save_request().unwrap();
+        assert_eq!(request.contents, "print(1)\nprint(2)\n");
+        assert!(editor.take_save_request().is_none());
+
+        // Typing during the save keeps the buffer dirty afterwards
+        editor.text.push('#');
+        editor.finish_save(request, Ok(()));
+        assert!(!editor.saving);
+        assert!(editor.is_dirty());
+
+        editor.request_save();
+        let request = editor.take_save_request().unwrap();
+        editor.finish_save(request, Err("Storage full".to_string()));
+        assert_eq!(editor.error.as_deref(), Some("Storage full"));
+        assert!(editor.is_dirty());
+    }
+
+    #[test]
+    fn test_edit_viewed_file() {
+        let mut state = UiState::new();
+        state.viewer.open("/workspace/notes.md");
+        state.viewer.finish_load(FileLoad::Loaded {
+            path: "/workspace/notes.md".to_string(),
+            data: b"# Notes".to_vec(),
+            content_type: None,
+        });
+        state.edit_viewed_file();
+        assert!(!state.viewer.is_open());
+        assert_eq!(state.editor.path.as_deref(), Some("/workspace/notes.md"));
+        assert_eq!(state.editor.text, "# Notes");
+    }
+
+    #[test]
+    fn test_find_matches() {
+        assert_eq!(find_matches("abcabc", "bc"), vec![1..3, 4..6]);
+        assert!(find_matches("abc", "").is_empty());
+        assert_eq!(find_matches("ééé", "é"), vec![0..2, 2..4, 4..6]);
+    }
+
+    #[test]
+    fn test_editor_find_and_replace() {
+        let mut editor = CodeEditor::default();
+        editor.open("/workspace/a.txt", "foo bar foo".to_string());
+        editor.find = "foo".to_string();
+        editor.replace = "baz".to_string();
+
+        assert_eq!(editor.find_next(), Some(0..3));
+        assert_eq!(editor.find_next(), Some(8..11));
+        // Wraps around
+        assert_eq!(editor.find_next(), Some(0..3));
+
+        assert_eq!(editor.replace_next(), Some(8..11));
+        assert_eq!(editor.text, "baz bar foo");
+
+        editor.text = "foo foo foo".to_string();
+        assert_eq!(editor.replace_all(), 3);
+        assert_eq!(editor.text, "baz baz baz");
+        assert_eq!(editor.find_next(), None);
+    }
+
+    // ─── Prompt Picker Tests ─────────────────────────────────
+
+    use crate::panels::prompts::{PromptAction, PromptPicker};
+
+    #[test]
+    fn test_prompt_picker_save_and_delete_queue_actions() {
+        let mut picker = PromptPicker::default();
+        picker.save(" Fix ", "Fix the tests");
+        assert_eq!(picker.snippets[0].name, "Fix");
+        let id = picker.snippets[0].id.clone();
+        picker.delete(&id);
+        assert!(picker.snippets.is_empty());
+
+        let actions = picker.take_actions();
+        assert!(matches!(&actions[0], PromptAction::Save(s) if s.body == "Fix the tests"));
+        assert_eq!(actions[1], PromptAction::Delete(id));
+        assert!(picker.take_actions().is_empty());
+    }
+
+    #[test]
+    fn test_prompt_picker_insert_fills_placeholders() {
+        use agent_types::prompt::PromptSnippet;
+        let mut picker = PromptPicker::default();
+        let mut input = "Please".to_string();
+        picker.insert(&PromptSnippet::new("Plain", "help"), &mut input);
+        assert_eq!(input, "Please help");
+
+        let mut input = String::new();
+        picker.insert(&PromptSnippet::new("Review", "Review {{file}}"), &mut input);
+        assert!(input.is_empty());
+        picker.filling.as_mut().unwrap().1[0].1 = "lib.rs".to_string();
+        picker.finish_fill(&mut input);
+        assert_eq!(input, "Review lib.rs");
+        assert!(picker.filling.is_none());
+    }
+
+    #[test]
+    fn test_prompt_picker_choice_starts_at_first_option() {
+        use agent_types::prompt::PromptSnippet;
+        let mut picker = PromptPicker::default();
+        let mut input = String::new();
+        picker.insert(&PromptSnippet::new("Tone", "Be {{tone:brief|thorough}} about {{topic}}"), &mut input);
+        let (_, values) = picker.filling.as_ref().unwrap();
+        assert_eq!(values[0], ("tone".to_string(), "brief".to_string()));
+        assert_eq!(values[1], ("topic".to_string(), String::new()));
+        picker.finish_fill(&mut input);
+        assert_eq!(input, "Be brief about ");
+    }
+
+    // ─── Key Vault Tests ─────────────────────────────────────
+
+    use crate::panels::vault::{VaultAction, VaultPanel, VaultStatus};
+
+    #[test]
+    fn test_vault_create_needs_matching_passphrase() {
+        let mut panel = VaultPanel::default();
+        panel.passphrase = "short".to_string();
+        panel.confirm = "short".to_string();
+        assert!(panel.create_problem().unwrap().contains("at least 8"));
+
+        panel.passphrase = "correct horse".to_string();
+        assert_eq!(panel.create_problem(), Some("Passphrases do not match"));
+
+        panel.confirm = "correct horse".to_string();
+        assert!(panel.create_problem().is_none());
+    }
+
+    #[test]
+    fn test_vault_request_clears_passphrase() {
+        let mut panel = VaultPanel::default();
+        panel.status = VaultStatus::Locked;
+        panel.passphrase = "secret passphrase".to_string();
+        panel.error = Some("Wrong passphrase".to_string());
+        assert!(panel.is_locked());
+        panel.request(VaultAction::Unlock("secret passphrase".to_string()));
+
+        assert!(panel.passphrase.is_empty());
+        assert!(panel.error.is_none());
+        assert!(panel.busy);
+        assert_eq!(panel.take_actions(), vec![VaultAction::Unlock("secret passphrase".to_string())]);
+        assert!(panel.take_actions().is_empty());
+    }
+
+    // ─── File Tree Tests ─────────────────────────────────────
+
+    use crate::file_tree::FileTree;
+    use agent_types::tool::DirEntry;
+
+    fn entry(name: &str, is_dir: bool) -> DirEntry {
+        DirEntry { name: name.to_string(), is_dir, size: 0, content_type: None }
+    }
+
+    fn tool_call(tree: &mut FileTree, call_id: &str, tool_name: &str, arguments: &str) {
+        tree.observe(&AgentEvent::ToolExecStart {
+            call_id: call_id.to_string(),
+            tool_name: tool_name.to_string(),
+            arguments: arguments.to_string(),
+        });
+        tree.observe(&AgentEvent::ToolExecEnd {
+            call_id: call_id.to_string(),
+            result: String::new(),
+            success: true,
+            duration_ms: 0,
+            data: None,
+        });
+    }
+
+    #[test]
+    fn test_file_tree_queues_each_directory_once() {
+        let mut tree = FileTree::default();
+        assert!(tree.entries("/src").is_none());
+        assert!(tree.entries("/src/").is_none());
+        assert_eq!(tree.take_requests(), vec!["/src".to_string()]);
+
+        // Already being listed
+        assert!(tree.entries("/src").is_none());
+        assert!(tree.take_requests().is_empty());
+
+        tree.finish_list("/src", Ok(vec![entry("main.rs", false)]));
+        assert_eq!(tree.entries("/src").unwrap().len(), 1);
+        assert!(tree.take_requests().is_empty());
+    }
+
+    #[test]
+    fn test_file_tree_failed_listing_waits_for_invalidation() {
+        let mut tree = FileTree::default();
+        tree.entries("/missing");
+        tree.take_requests();
+        tree.finish_list("/missing", Err("not found".to_string()));
+        assert!(tree.entries("/missing").is_none());
+        assert_eq!(tree.error("/missing"), Some("not found"));
+        assert!(tree.take_requests().is_empty());
+
+        tree.invalidate("/missing");
+        tree.entries("/missing");
+        assert_eq!(tree.take_requests(), vec!["/missing".to_string()]);
+    }
+
+    #[test]
+    fn test_file_tree_write_invalidates_parent_only() {
+        let mut tree = FileTree::default();
+        tree.finish_list("/", Ok(vec![entry("src", true)]));
+        tree.finish_list("/src", Ok(vec![entry("main.rs", false)]));
+
+        tool_call(&mut tree, "c1", "write_file", r#"{"path":"/src/lib.rs","content":""}"#);
+        // Stale entries are still served while the refresh is queued
+        assert_eq!(tree.entries("/src").unwrap().len(), 1);
+        assert!(tree.entries("/").is_some());
+        assert_eq!(tree.take_requests(), vec!["/src".to_string()]);
+
+        tool_call(&mut tree, "c2", "read_file", r#"{"path":"/src/main.rs"}"#);
+        tree.finish_list("/src", Ok(vec![entry("lib.rs", false), entry("main.rs", false)]));
+        tree.entries("/src");
+        assert!(tree.take_requests().is_empty());
+    }
+
+    #[test]
+    fn test_file_tree_bash_invalidates_everything() {
+        let mut tree = FileTree::default();
+        tree.finish_list("/", Ok(vec![entry("src", true)]));
+        tree.finish_list("/src", Ok(vec![]));
+
+        tool_call(&mut tree, "c1", "bash", r#"{"command":"rm -r /src"}"#);
+        tree.entries("/");
+        tree.entries("/src");
+        assert_eq!(tree.take_requests(), vec!["/".to_string(), "/src".to_string()]);
+    }
+
+    #[test]
+    fn test_file_tree_complete() {
+        let mut tree = FileTree::default();
+        assert!(tree.complete("/src/ma").is_empty());
+        assert_eq!(tree.take_requests(), vec!["/src".to_string()]);
+
+        tree.finish_list(
+            "/src",
+            Ok(vec![entry("main.rs", false), entry("macros", true), entry("lib.rs", false)]),
+        );
+        assert_eq!(tree.complete("/src/ma"), vec!["/src/main.rs", "/src/macros/"]);
+        tree.finish_list("/", Ok(vec![entry("src", true)]));
+        assert_eq!(tree.complete("s"), vec!["/src/"]);
+    }
+
+    // ─── File Explorer Tests ─────────────────────────────────
+
+    use crate::panels::explorer::{FileExplorer, breadcrumbs, resolve};
+
+    #[test]
+    fn test_explorer_history() {
+        let mut explorer = FileExplorer::new("/workspace");
+        explorer.go_to("src");
+        explorer.go_to("/workspace/home");
+        assert_eq!(explorer.cwd, "/workspace/home");
+
+        explorer.go_back();
+        assert_eq!(explorer.cwd, "/workspace/src");
+        explorer.go_back();
+        assert_eq!(explorer.cwd, "/workspace");
+        assert!(!explorer.can_go_back());
+        explorer.go_forward();
+        assert_eq!(explorer.cwd, "/workspace/src");
+
+        // Going somewhere new drops the forward history
+        explorer.go_up();
+        assert_eq!(explorer.cwd, "/workspace");
+        assert!(!explorer.can_go_forward());
+
+        // Staying put isn't recorded
+        explorer.go_to(".");
+        explorer.go_back();
+        assert_eq!(explorer.cwd, "/workspace/src");
+    }
+
+    #[test]
+    fn test_explorer_breadcrumbs_and_resolve() {
+        let crumbs = breadcrumbs("/workspace/src");
+        let paths: Vec<&str> = crumbs.iter().map(|(_, p)| p.as_str()).collect();
+        assert_eq!(paths, vec!["/", "/workspace", "/workspace/src"]);
+        assert_eq!(crumbs[2].0, "src");
+        assert_eq!(breadcrumbs("/").len(), 1);
+
+        assert_eq!(resolve("/workspace/src", "../home/"), "/workspace/home");
+        assert_eq!(resolve("/workspace", "/tmp/./x"), "/tmp/x");
+        assert_eq!(resolve("/", ".."), "/");
+    }
+
+    #[test]
+    fn test_explorer_path_bar() {
+        let mut files = FileTree::default();
+        files.finish_list(
+            "/workspace",
+            Ok(vec![entry("src", true), entry("README.md", false)]),
+        );
+        let mut explorer = FileExplorer::new("/");
+
+        explorer.path_input = Some("/workspace/s".to_string());
+        assert_eq!(explorer.suggestions(&mut files), vec!["/workspace/src/"]);
+
+        explorer.path_input = Some("/workspace/src/".to_string());
+        assert_eq!(explorer.submit_path(&mut files), None);
+        assert_eq!(explorer.cwd, "/workspace/src");
+        assert!(explorer.path_input.is_none());
+
+        explorer.path_input = Some("../README.md".to_string());
+        assert_eq!(explorer.submit_path(&mut files), Some("/workspace/README.md".to_string()));
+        assert_eq!(explorer.cwd, "/workspace/src");
+    }
+
+    // ─── Terminal Completion Tests ───────────────────────────
+
+    use crate::panels::terminal::{
+        Jobs, TerminalPrompt, command_candidates, common_prefix, expand_aliases, script_invocation,
+    };
+    use agent_types::tool::{ExecHandle, RunningExec};
+
+    fn prompt(input: &str) -> TerminalPrompt {
+        let mut prompt = TerminalPrompt::default();
+        prompt.input = input.to_string();
+        prompt.cwd = "/workspace".to_string();
+        prompt
+    }
+
+    #[test]
+    fn test_terminal_completes_commands() {
+        let mut files = FileTree::default();
+        let mut p = prompt("gr");
+        p.complete(&mut files);
+        assert_eq!(p.input, "grep ");
+        assert!(p.candidates.is_empty());
+
+        // Several matches fill in the shared prefix and list them
+        let mut p = prompt("ls | t");
+        p.complete(&mut files);
+        assert_eq!(p.input, "ls | t");
+        assert!(p.candidates.contains(&"tail".to_string()));
+        assert!(p.candidates.contains(&"touch".to_string()));
+
+        p.pick("tail");
+        assert_eq!(p.input, "ls | tail ");
+        assert!(files.take_requests().is_empty());
+        assert_eq!(command_candidates("unz"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_terminal_completes_paths() {
+        let mut files = FileTree::default();
+        let mut p = prompt("cat src/ma");
+        p.complete(&mut files);
+        assert_eq!(p.input, "cat src/ma");
+        assert_eq!(files.take_requests(), vec!["/workspace/src".to_string()]);
+
+        files.finish_list(
+            "/workspace/src",
+            Ok(vec![entry("main.rs", false), entry("macros.rs", false), entry("lib.rs", false)]),
+        );
+        p.complete(&mut files);
+        assert_eq!(p.input, "cat src/ma");
+        assert_eq!(p.candidates, vec!["src/macros.rs", "src/main.rs"]);
+
+        files.finish_list("/workspace", Ok(vec![entry("src", true)]));
+        let mut p = prompt("ls /workspace/s");
+        p.complete(&mut files);
+        assert_eq!(p.input, "ls /workspace/src/");
+    }
+
+    #[test]
+    fn test_terminal_submit_and_common_prefix() {
+        let mut p = prompt("  ls -la ");
+        assert_eq!(p.submit(), Some("ls -la".to_string()));
+        assert!(p.input.is_empty());
+        assert_eq!(p.submit(), None);
+
+        p.input = "pwd".to_string();
+        p.running = true;
+        assert_eq!(p.submit(), None);
+
+        let words = vec!["src/main.rs".to_string(), "src/macros.rs".to_string()];
+        assert_eq!(common_prefix(&words), "src/ma");
+        assert_eq!(common_prefix(&["日本".to_string(), "日光".to_string()]), "日");
+        assert_eq!(common_prefix(&[]), "");
+    }
+
+    #[test]
+    fn test_terminal_expands_aliases_in_command_position() {
+        let aliases = vec![
+            ("ll".to_string(), "ls -la".to_string()),
+            ("la".to_string(), "ll -h".to_string()),
+            ("ls".to_string(), "ls --color".to_string()),
+        ];
+        assert_eq!(expand_aliases("ll src", &aliases), "ls --color -la src");
+        assert_eq!(expand_aliases("la", &aliases), "ls --color -la -h");
+        // Only the first word of each command
+        assert_eq!(
+            expand_aliases("echo ll | ll; cd /x && ll", &aliases),
+            "echo ll | ls --color -la; cd /x && ls --color -la"
+        );
+        // Nothing inside quotes
+        assert_eq!(expand_aliases(r#"echo "a; ll" 'b | ll'"#, &aliases), r#"echo "a; ll" 'b | ll'"#);
+        assert_eq!(expand_aliases("  pwd  ", &aliases), "  pwd  ");
+    }
+
+    #[test]
+    fn test_terminal_script_invocation() {
+        assert_eq!(
+            script_invocation("./build.sh --release | tail", "/workspace"),
+            Some(("/workspace/build.sh".to_string(), "--release | tail"))
+        );
+        assert_eq!(
+            script_invocation("../tmp/x.sh", "/workspace/src"),
+            Some(("/workspace/tmp/x.sh".to_string(), ""))
+        );
+        assert_eq!(script_invocation("/workspace/run.sh a", "/"), Some(("/workspace/run.sh".to_string(), "a")));
+        assert_eq!(script_invocation("bash build.sh", "/workspace"), None);
+    }
+
+    #[test]
+    fn test_terminal_jobs_kill_once() {
+        let job = |id| RunningExec { handle: ExecHandle(id), command: "sleep 100".to_string(), elapsed_ms: 0 };
+        let mut jobs = Jobs::default();
+        jobs.set_running(vec![job(1), job(2)]);
+
+        jobs.kill(ExecHandle(2));
+        jobs.kill(ExecHandle(2));
+        assert!(jobs.is_killing(ExecHandle(2)));
+        assert_eq!(jobs.take_kills(), vec![ExecHandle(2)]);
+        assert!(jobs.take_kills().is_empty());
+
+        // Still listed: asking again sends nothing
+        jobs.set_running(vec![job(1), job(2)]);
+        jobs.kill(ExecHandle(2));
+        assert!(jobs.take_kills().is_empty());
+
+        jobs.set_running(vec![job(1)]);
+        assert!(!jobs.is_killing(ExecHandle(2)));
+        assert!(!jobs.is_killing(ExecHandle(1)));
+    }
+
+    // ─── Font Loading Tests ──────────────────────────────────
+
+    use crate::fonts::{ExtraFonts, FontStatus, needs_cjk_font};
+    use agent_types::config::{FontConfig, FontLoading};
+
+    #[test]
+    fn test_needs_cjk_font() {
+        assert!(!needs_cjk_font("Hello, world — café naïve"));
+        assert!(needs_cjk_font("技術要點"));
+        assert!(needs_cjk_font("こんにちは"));
+        assert!(needs_cjk_font("한국어"));
+        assert!(needs_cjk_font("ok，"));
+    }
+
+    #[test]
+    fn test_fonts_requested_when_text_needs_them() {
+        let mut state = UiState::new();
+        state.fonts.configure(&FontConfig::default());
+        state.push_user_message("hello", Vec::new());
+        state.process_events(vec![AgentEvent::LlmDelta { token: "plain".to_string() }]);
+        assert!(!state.fonts.take_request());
+
+        state.process_events(vec![AgentEvent::LlmDelta { token: "你好".to_string() }]);
+        assert!(state.fonts.is_loading());
+        assert!(state.fonts.take_request());
+        assert!(!state.fonts.take_request());
+        state.fonts.finish(Ok(()));
+        assert_eq!(state.fonts.status, FontStatus::Loaded);
+        assert!(!state.fonts.is_loading());
+    }
+
+    #[test]
+    fn test_font_loading_modes() {
+        let mut fonts = ExtraFonts::default();
+        fonts.configure(&FontConfig { loading: FontLoading::Never, ..FontConfig::default() });
+        fonts.observe_text("中文");
+        assert!(!fonts.take_request());
+
+        fonts.configure(&FontConfig { loading: FontLoading::Always, ..FontConfig::default() });
+        assert!(fonts.take_request());
+        fonts.finish(Err("HTTP 404".to_string()));
+        // A failed fetch isn't retried
+        fonts.observe_text("中文");
+        assert!(!fonts.take_request());
+        assert_eq!(fonts.status, FontStatus::Failed("HTTP 404".to_string()));
+    }
+
+    // ─── Theme Tests ─────────────────────────────────────────
+
+    use crate::theme::{self, ThemeColor, contrast_ratio};
+    use agent_types::config::ThemePreset;
+
+    #[test]
+    fn test_high_contrast_theme_meets_wcag_aa() {
+        let preset = ThemePreset::HighContrast;
+        let text = [theme::TEXT_PRIMARY, theme::TEXT_SECONDARY, theme::SUCCESS, theme::ERROR, theme::WARNING];
+        for background in [theme::BG_PRIMARY, theme::BG_SECONDARY, theme::BG_SURFACE] {
+            for color in text {
+                let ratio = contrast_ratio(color.in_preset(preset), background.in_preset(preset));
+                assert!(ratio >= 4.5, "{:?} on {:?}: {:.2}", color, background, ratio);
+            }
+            // The accent marks controls and selections: 3:1 for UI components
+            assert!(contrast_ratio(theme::ACCENT.in_preset(preset), background.in_preset(preset)) >= 3.0);
+        }
+        for color in [theme::TERMINAL_FG, theme::TERMINAL_ERR] {
+            assert!(contrast_ratio(color.in_preset(preset), theme::TERMINAL_BG.in_preset(preset)) >= 4.5);
+        }
+        // Button labels on the accent fill
+        assert!(contrast_ratio(theme::TEXT_PRIMARY.in_preset(preset), theme::ACCENT.in_preset(preset)) >= 4.5);
+
+        // Stronger than the dark preset's secondary text
+        let secondary = |preset| contrast_ratio(theme::TEXT_SECONDARY.in_preset(preset), theme::BG_SURFACE.in_preset(preset));
+        assert!(secondary(ThemePreset::HighContrast) > secondary(ThemePreset::Dark));
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        assert!((contrast_ratio(egui::Color32::BLACK, egui::Color32::WHITE) - 21.0).abs() < 0.01);
+        assert_eq!(contrast_ratio(egui::Color32::GRAY, egui::Color32::GRAY), 1.0);
+        assert_eq!(ThemeColor::Accent.in_preset(ThemePreset::Dark), egui::Color32::from_rgb(99, 102, 241));
+    }
+
+    // ─── Outbox Tests ────────────────────────────────────────
+
+    use crate::panels::chat::{Outbox, Outgoing};
+
+    fn outgoing(text: &str) -> Outgoing {
+        Outgoing { text: text.to_string(), attachments: Vec::new() }
+    }
+
+    #[test]
+    fn test_outbox_backs_off_until_reachable() {
+        let mut outbox = Outbox::default();
+        outbox.push(outgoing("a"));
+        outbox.push(outgoing("b"));
+        let first = outbox.take_due(0.0).unwrap();
+        outbox.put_back(first, 10.0);
+        assert_eq!(outbox.wait(10.0), 5.0);
+        assert!(outbox.take_due(14.0).is_none());
+        let first = outbox.take_due(15.0).unwrap();
+        assert_eq!(first.text, "a");
+
+        // Each failure in a row doubles the wait, up to a minute
+        outbox.put_back(first, 15.0);
+        assert_eq!(outbox.wait(15.0), 10.0);
+        for i in 1..=6 {
+            let now = 1000.0 * i as f64;
+            let message = outbox.take_due(now).unwrap();
+            outbox.put_back(message, now);
+        }
+        assert_eq!(outbox.wait(6000.0), 60.0);
+
+        outbox.reachable();
+        assert_eq!(outbox.take_due(6000.0).unwrap().text, "a");
+        assert_eq!(outbox.take_due(6000.0).unwrap().text, "b");
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_queued_messages_wait_for_connection() {
+        let mut state = UiState::new();
+        state.set_online(false);
+        state.queue_message(outgoing("first"));
+        state.queue_message(outgoing("second"));
+        assert_eq!(state.messages.iter().filter(|e| e.role == "pending").count(), 2);
+        assert!(state.take_queued(0.0).is_none());
+
+        state.set_online(true);
+        let message = state.take_queued(0.0).unwrap();
+        assert_eq!(message.text, "first");
+        assert_eq!(state.messages[0].role, "user");
+        assert_eq!(state.messages[1].role, "pending");
+
+        // The provider couldn't be reached: shown as queued again, ahead
+        // of the rest, and retried later
+        state.requeue(message, 1.0);
+        assert_eq!(state.messages[0].role, "pending");
+        assert_eq!(state.outbox.len(), 2);
+        assert!(state.take_queued(2.0).is_none());
+        assert_eq!(state.take_queued(6.0).unwrap().text, "first");
+
+        // Nothing more goes out while a turn is running
+        state.process_events(vec![AgentEvent::TurnStart { turn_id: 1 }]);
+        assert!(state.take_queued(100.0).is_none());
+        state.process_events(vec![AgentEvent::TurnEnd { turn_id: 1 }]);
+        assert_eq!(state.take_queued(6.0).unwrap().text, "second");
+        assert!(state.messages.iter().all(|e| e.role == "user"));
+    }
+
+    // ─── Health Indicator Tests ──────────────────────────────
+
+    use crate::panels::health::{Level, ShellHealth, network_status};
+
+    #[test]
+    fn test_health_follows_events() {
+        let mut state = UiState::new();
+        assert_eq!(state.health.llm_status().0, Level::Unknown);
+        assert_eq!(state.health.shell_status().0, Level::Degraded);
+
+        state.process_events(vec![
+            AgentEvent::ShellReady,
+            AgentEvent::LlmCallEnd { model: "gpt-4o".to_string(), latency_ms: 850, error: None },
+        ]);
+        assert_eq!(state.health.shell, ShellHealth::Ready);
+        let (level, detail) = state.health.llm_status();
+        assert_eq!(level, Level::Good);
+        assert!(detail.contains("gpt-4o") && detail.contains("850 ms"));
+
+        state.process_events(vec![AgentEvent::LlmCallEnd {
+            model: "gpt-4o".to_string(),
+            latency_ms: 30_000,
+            error: None,
+        }]);
+        assert_eq!(state.health.llm_status().0, Level::Degraded);
+
+        state.process_events(vec![
+            AgentEvent::LlmCallEnd {
+                model: "gpt-4o".to_string(),
+                latency_ms: 12,
+                error: Some("Could not reach the provider".to_string()),
+            },
+            AgentEvent::ShellUnavailable { reason: "no worker".to_string() },
+        ]);
+        let (level, detail) = state.health.llm_status();
+        assert_eq!(level, Level::Down);
+        assert!(detail.contains("Could not reach the provider"));
+        assert_eq!(state.health.shell_status().0, Level::Down);
+
+        assert_eq!(network_status(true).0, Level::Good);
+        assert_eq!(network_status(false).0, Level::Down);
+    }
+
+    // ─── Batch Queue Tests ───────────────────────────────────
+
+    use crate::panels::batch::{BatchPanel, ItemStatus};
+
+    #[test]
+    fn test_batch_runs_prompts_in_order() {
+        let mut batch = BatchPanel { draft: "Fix a\nFix b\nFix c".to_string(), ..Default::default() };
+        batch.add_draft();
+        assert!(batch.draft.is_empty());
+        assert_eq!(batch.items.len(), 3);
+        assert!(batch.take_next().is_none(), "not started");
+
+        batch.running = true;
+        assert_eq!(batch.take_next().as_deref(), Some("Fix a"));
+        assert!(batch.take_next().is_none(), "one turn at a time");
+        assert!(batch.finish(Ok(())).is_none());
+
+        // Paused: the rest waits
+        batch.running = false;
+        assert!(batch.take_next().is_none());
+        batch.running = true;
+        assert_eq!(batch.take_next().as_deref(), Some("Fix b"));
+        assert!(batch.finish(Err("rate limited".to_string())).is_none());
+        assert_eq!(batch.items[1].status, ItemStatus::Failed("rate limited".to_string()));
+
+        // A failure doesn't stop the batch
+        assert_eq!(batch.take_next().as_deref(), Some("Fix c"));
+        let summary = batch.finish(Ok(())).unwrap();
+        assert_eq!(summary, "Batch of 3 prompts: 2 done, 1 failed");
+        assert!(!batch.running);
+        assert!(batch.take_next().is_none());
+    }
+
+    #[test]
+    fn test_batch_skip_last_prompt_finishes() {
+        let mut batch = BatchPanel::default();
+        batch.add(vec!["one".to_string(), "two".to_string()]);
+        batch.running = true;
+        assert_eq!(batch.take_next().as_deref(), Some("one"));
+        assert!(batch.skip(1).is_none(), "one is still running");
+        assert_eq!(batch.finish(Ok(())).unwrap(), "Batch of 2 prompts: 1 done, 1 skipped");
+
+        batch.add(vec!["three".to_string()]);
+        batch.running = true;
+        assert_eq!(batch.skip(2).unwrap(), "Batch of 3 prompts: 1 done, 2 skipped");
+        batch.clear();
+        assert!(batch.items.is_empty());
+    }
+
+    // ─── Plan View Tests ─────────────────────────────────────
+
+    use crate::panels::plan::StepStatus;
+
+    #[test]
+    fn test_plan_view_follows_events() {
+        let mut state = UiState::new();
+        state.process_events(vec![
+            AgentEvent::TurnStart { turn_id: 1 },
+            AgentEvent::PlanReady { steps: vec!["Find".to_string(), "Fix".to_string()] },
+        ]);
+        assert!(state.plan.open);
+        assert_eq!(state.plan.steps.len(), 2);
+
+        state.process_events(vec![
+            AgentEvent::PlanStepStart { index: 0 },
+            AgentEvent::PlanStepEnd { index: 0, success: true, summary: "Found 3 callers".to_string() },
+            AgentEvent::PlanStepStart { index: 1 },
+        ]);
+        assert_eq!(state.plan.steps[0].status, StepStatus::Done);
+        assert_eq!(state.plan.steps[0].summary.as_deref(), Some("Found 3 callers"));
+        assert_eq!(state.plan.steps[1].status, StepStatus::Running);
+        assert_eq!(state.status_text, "Step 2/2: Fix");
+
+        state.process_events(vec![AgentEvent::PlanStepEnd {
+            index: 1,
+            success: false,
+            summary: "Rate limited".to_string(),
+        }]);
+        assert_eq!(state.plan.steps[1].status, StepStatus::Failed);
+        assert_eq!(state.status_text, "Step 2/2: Fix failed");
+    }
+
+    // ─── Chat Image Tests ────────────────────────────────────
+
+    use crate::images::ChatImage;
+
+    #[test]
+    fn test_chat_images_load_once_and_reload_when_rewritten() {
+        let mut state = UiState::new();
+        let artifact = AgentEvent::ToolArtifact {
+            call_id: "c1".to_string(),
+            path: "/workspace/plot.png".to_string(),
+            mime_type: "image/png".to_string(),
+        };
+        state.process_events(vec![artifact.clone()]);
+        assert_eq!(state.images.take_requests(), vec!["/workspace/plot.png".to_string()]);
+        // Drawing while the read is in flight doesn't queue it again
+        assert!(matches!(state.images.get("/workspace/plot.png"), ChatImage::Loading));
+        assert!(state.images.take_requests().is_empty());
+
+        state.images.finish("/workspace/plot.png", Ok(vec![1, 2, 3]));
+        assert!(matches!(state.images.get("/workspace/plot.png"), ChatImage::Bytes(b) if b == &vec![1, 2, 3]));
+        state.images.finish("/workspace/other.png", Err("not found".to_string()));
+        assert!(state.images.take_requests().is_empty());
+
+        // Written again by a later call: read afresh
+        state.process_events(vec![artifact]);
+        assert!(matches!(state.images.get("/workspace/plot.png"), ChatImage::Loading));
+        assert_eq!(state.images.take_requests(), vec!["/workspace/plot.png".to_string()]);
+    }
+
+    // ─── Table Tests ─────────────────────────────────────────
+
+    use crate::panels::chat::tool_table;
+    use crate::tables::{Segment, Table, TableExports, compare_cells, parse_delimited, split_tables};
+
+    fn strings(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_split_tables_finds_markdown_tables() {
+        let text = "Sizes:\n\n| Crate | Lines |\n|:------|------:|\n| core | 9,120 |\n| a \\| b |\n\nDone.\n\
+                    ```\n| not | a table |\n|---|---|\n```";
+        let segments = split_tables(text);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], Segment::Text("Sizes:\n".to_string()));
+        let Segment::Table(table) = &segments[1] else {
+            panic!("expected a table, got {:?}", segments[1]);
+        };
+        assert_eq!(table.headers, strings(&["Crate", "Lines"]));
+        assert_eq!(table.rows, vec![strings(&["core", "9,120"]), strings(&["a | b", ""])]);
+        let Segment::Text(rest) = &segments[2] else {
+            panic!("expected text, got {:?}", segments[2]);
+        };
+        assert!(rest.starts_with("\nDone.") && rest.contains("| not | a table |"));
+
+        // Without a delimiter row it's just text with pipes
+        assert_eq!(split_tables("a | b\nc | d"), vec![Segment::Text("a | b\nc | d".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_delimited_needs_consistent_records() {
+        let table = parse_delimited("name,note\nada,\"likes, commas\"\nbob,\"said \"\"hi\"\"\"\n", ',').unwrap();
+        assert_eq!(table.rows[0], strings(&["ada", "likes, commas"]));
+        assert_eq!(table.to_csv(), "name,note\nada,\"likes, commas\"\nbob,\"said \"\"hi\"\"\"\n");
+        assert_eq!(parse_delimited("a\tb\n1\t2", '\t').unwrap().rows, vec![strings(&["1", "2"])]);
+
+        // Cut off, a single column, or only a header: left as text
+        assert_eq!(parse_delimited("a,b\n1,2\n3", ','), None);
+        assert_eq!(parse_delimited("just\nlines", ','), None);
+        assert_eq!(parse_delimited("a,b\n", ','), None);
+    }
+
+    #[test]
+    fn test_table_sorting() {
+        let table = Table {
+            headers: strings(&["name", "size"]),
+            rows: vec![strings(&["b", "1,200"]), strings(&["A", "90"]), strings(&["c", ""])],
+        };
+        assert!(table.is_numeric(1));
+        assert!(!table.is_numeric(0));
+        assert_eq!(table.sorted(1, false), vec![1, 0, 2]);
+        assert_eq!(table.sorted(1, true), vec![2, 0, 1]);
+        assert_eq!(table.sorted(0, false), vec![1, 0, 2]);
+        assert_eq!(compare_cells("10", "9"), std::cmp::Ordering::Greater);
+        assert_eq!(compare_cells("10", "9a"), std::cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn test_tool_table_from_csv_read() {
+        let entry = |arguments: &str, content: &str| ChatEntry {
+            role: "tool".to_string(),
+            content: content.to_string(),
+            is_tool_call: true,
+            tool_name: Some("read_file".to_string()),
+            arguments: Some(arguments.to_string()),
+            duration_ms: Some(3),
+            success: Some(true),
+            attachments: Vec::new(),
+            data: None,
+        };
+        let (source, table) = tool_table(&entry(r#"{"path":"/workspace/a.csv"}"#, "x,y\n1,2\n")).unwrap();
+        assert_eq!(source.as_deref(), Some("/workspace/a.csv"));
+        assert_eq!(table.headers, strings(&["x", "y"]));
+        assert_eq!(tool_table(&entry(r#"{"path":"/workspace/a.csv","offset":2}"#, "2\t1,2")), None);
+        assert_eq!(tool_table(&entry(r#"{"path":"/workspace/a.txt"}"#, "x,y\n1,2\n")), None);
+
+        let mut exports = TableExports::default();
+        let now = chrono::Local::now();
+        let path = exports.export(&table, now);
+        assert!(path.starts_with("/workspace/tables/") && path.ends_with(".csv"));
+        assert_eq!(exports.take_saves(), vec![(path, "x,y\n1,2\n".to_string())]);
+        assert!(exports.take_saves().is_empty());
+    }
+
+    // ─── Diagram Tests ───────────────────────────────────────
+
+    use crate::diagrams::{Block, Diagram, DiagramKind, DiagramRequest, Diagrams, split_diagrams};
+
+    #[test]
+    fn test_split_diagrams() {
+        let text = "The flow:\n```mermaid\ngraph TD\n  A --> B\n```\nThen:\n```rust\nfn main() {}\n```";
+        assert_eq!(
+            split_diagrams(text),
+            vec![
+                Block::Text("The flow:".to_string()),
+                Block::Mermaid("graph TD\n  A --> B".to_string()),
+                Block::Text("Then:\n```rust\nfn main() {}\n```".to_string()),
+            ]
+        );
+        // Still streaming: the open block stays text
+        let partial = "See:\n```mermaid\ngraph TD";
+        assert_eq!(split_diagrams(partial), vec![Block::Text(partial.to_string())]);
+    }
+
+    #[test]
+    fn test_diagrams_render_once_per_theme() {
+        let mut diagrams = Diagrams::default();
+        assert!(matches!(diagrams.get(DiagramKind::Mermaid, "graph TD; A-->B", true), Diagram::Loading));
+        assert!(matches!(diagrams.get(DiagramKind::Mermaid, "graph TD; A-->B", true), Diagram::Loading));
+        let dark = DiagramRequest { kind: DiagramKind::Mermaid, source: "graph TD; A-->B".to_string(), dark: true };
+        assert_eq!(diagrams.take_requests(), vec![dark.clone()]);
+
+        diagrams.finish(&dark, Ok(([1, 1], vec![0, 0, 0, 255])));
+        assert!(matches!(diagrams.get(DiagramKind::Mermaid, "graph TD; A-->B", true), Diagram::Pixels { size: [1, 1], .. }));
+        diagrams.get(DiagramKind::Mermaid, "graph TD; A-->B", false);
+        assert_eq!(diagrams.take_requests().len(), 1);
+
+        diagrams.finish(&dark, Err("Parse error on line 1".to_string()));
+        assert!(matches!(diagrams.get(DiagramKind::Mermaid, "graph TD; A-->B", true), Diagram::Failed(e) if e.starts_with("Parse error")));
+        assert!(diagrams.take_requests().is_empty());
+    }
+
+    // ─── Math Tests ──────────────────────────────────────────
+
+    use crate::math::{MathBlock, inline_math, split_display_math, tex_to_unicode};
+
+    #[test]
+    fn test_tex_to_unicode() {
+        assert_eq!(tex_to_unicode(r"\alpha^2 + \beta_{i} \leq \frac{1}{n}"), "α² + βᵢ ≤ 1/n");
+        assert_eq!(tex_to_unicode(r"\frac{a+b}{2} \times \sqrt{x+1}"), "(a+b)/2 × √(x+1)");
+        assert_eq!(tex_to_unicode(r"\sum_{k=1}^{n} k = \frac{n(n+1)}{2}"), "∑ₖ₌₁ⁿ k = (n(n+1))/2");
+        assert_eq!(tex_to_unicode(r"e^{i\pi} + 1 = 0"), "e^(iπ) + 1 = 0");
+        assert_eq!(tex_to_unicode(r"\sin\theta \cdot \log(x) \in \mathbb{R}"), "sin θ · log(x) ∈ ℝ");
+        assert_eq!(tex_to_unicode(r"\left( x \right) \unknown{y}"), r"( x ) \unknown y");
+    }
+
+    #[test]
+    fn test_inline_math_leaves_prices_and_code_alone() {
+        assert_eq!(inline_math("Area is $\\pi r^2$, cost $5 and $10."), "Area is π r², cost $5 and $10.");
+        assert_eq!(inline_math("Run `echo $x$y` or \\(x_1\\)"), "Run `echo $x$y` or x₁");
+        assert_eq!(inline_math("```sh\necho $HOME$PATH\n```"), "```sh\necho $HOME$PATH\n```");
+        assert_eq!(inline_math("a \\$ sign"), "a $ sign");
+    }
+
+    #[test]
+    fn test_split_display_math() {
+        let text = "Energy:\n$$E = mc^2$$\nand\n\\[\n  a^2 + b^2\n  = c^2\n\\] done";
+        assert_eq!(
+            split_display_math(text),
+            vec![
+                MathBlock::Text("Energy:".to_string()),
+                MathBlock::Display("E = mc^2".to_string()),
+                MathBlock::Text("and".to_string()),
+                MathBlock::Display("a^2 + b^2\n  = c^2".to_string()),
+                MathBlock::Text("done".to_string()),
+            ]
+        );
+        // Still streaming, or in code: stays text
+        assert_eq!(split_display_math("$$x"), vec![MathBlock::Text("$$x".to_string())]);
+        let code = "```\n$$x$$\n```";
+        assert_eq!(split_display_math(code), vec![MathBlock::Text(code.to_string())]);
+    }
+
+    // ─── Share Link Tests ────────────────────────────────────
+
+    use agent_types::share::SharedTranscript;
+
+    #[test]
+    fn test_shared_conversation_is_read_only_until_a_session_loads() {
+        let transcript = SharedTranscript {
+            version: 1,
+            title: "Plotting sales".to_string(),
+            created_at: String::new(),
+            messages: vec![Message::user("Plot it"), Message::assistant("Done")],
+        };
+        let mut state = UiState::new();
+        state.load_shared(&transcript);
+        assert_eq!(state.share.viewing.as_deref(), Some("Plotting sales"));
+        assert_eq!(state.messages.len(), 2);
+        assert_eq!(state.messages[1].content, "Done");
+
+        state.load_messages(&[]);
+        assert!(state.share.viewing.is_none());
+        assert!(!state.share.take_request() && !state.share.take_leave());
+    }
 }
@@ -1,43 +1,175 @@
-//! UI theme constants
+//! UI theme: colors, spacing and the egui style.
+//!
+//! Colors are named by role. Each role resolves against the preset last
+//! applied with [`apply_theme`], so panels keep using the same names
+//! whichever preset is chosen; `RichText::color` and strokes take them
+//! as is, APIs that want a plain [`Color32`] get [`ThemeColor::color`].
 
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use agent_types::config::{ThemePreset, UiConfig};
 use egui::{Color32, CornerRadius, Stroke, Vec2};
 
-pub const BG_PRIMARY: Color32 = Color32::from_rgb(24, 24, 27);
-pub const BG_SECONDARY: Color32 = Color32::from_rgb(39, 39, 42);
-pub const BG_SURFACE: Color32 = Color32::from_rgb(52, 52, 56);
-pub const TEXT_PRIMARY: Color32 = Color32::from_rgb(228, 228, 231);
-pub const TEXT_SECONDARY: Color32 = Color32::from_rgb(161, 161, 170);
-pub const ACCENT: Color32 = Color32::from_rgb(99, 102, 241);
-pub const SUCCESS: Color32 = Color32::from_rgb(34, 197, 94);
-pub const ERROR: Color32 = Color32::from_rgb(239, 68, 68);
-pub const WARNING: Color32 = Color32::from_rgb(234, 179, 8);
-pub const TERMINAL_BG: Color32 = Color32::from_rgb(15, 15, 18);
-pub const TERMINAL_FG: Color32 = Color32::from_rgb(180, 230, 180);
-pub const TERMINAL_ERR: Color32 = Color32::from_rgb(255, 120, 120);
+/// A color role of the palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeColor {
+    BgPrimary,
+    BgSecondary,
+    BgSurface,
+    TextPrimary,
+    TextSecondary,
+    Accent,
+    Success,
+    Error,
+    Warning,
+    TerminalBg,
+    TerminalFg,
+    TerminalErr,
+}
+
+pub const BG_PRIMARY: ThemeColor = ThemeColor::BgPrimary;
+pub const BG_SECONDARY: ThemeColor = ThemeColor::BgSecondary;
+pub const BG_SURFACE: ThemeColor = ThemeColor::BgSurface;
+pub const TEXT_PRIMARY: ThemeColor = ThemeColor::TextPrimary;
+pub const TEXT_SECONDARY: ThemeColor = ThemeColor::TextSecondary;
+pub const ACCENT: ThemeColor = ThemeColor::Accent;
+pub const SUCCESS: ThemeColor = ThemeColor::Success;
+pub const ERROR: ThemeColor = ThemeColor::Error;
+pub const WARNING: ThemeColor = ThemeColor::Warning;
+pub const TERMINAL_BG: ThemeColor = ThemeColor::TerminalBg;
+pub const TERMINAL_FG: ThemeColor = ThemeColor::TerminalFg;
+pub const TERMINAL_ERR: ThemeColor = ThemeColor::TerminalErr;
+
+/// The colors of one preset, indexed by [`ThemeColor`]
+pub type Palette = [Color32; 12];
+
+const DARK: Palette = [
+    Color32::from_rgb(24, 24, 27),
+    Color32::from_rgb(39, 39, 42),
+    Color32::from_rgb(52, 52, 56),
+    Color32::from_rgb(228, 228, 231),
+    Color32::from_rgb(161, 161, 170),
+    Color32::from_rgb(99, 102, 241),
+    Color32::from_rgb(34, 197, 94),
+    Color32::from_rgb(239, 68, 68),
+    Color32::from_rgb(234, 179, 8),
+    Color32::from_rgb(15, 15, 18),
+    Color32::from_rgb(180, 230, 180),
+    Color32::from_rgb(255, 120, 120),
+];
+
+/// Text meets WCAG AA (4.5:1) on every background; the accent is a fill
+/// for white text and stands out 3:1 from the backgrounds
+const HIGH_CONTRAST: Palette = [
+    Color32::from_rgb(0, 0, 0),
+    Color32::from_rgb(18, 18, 20),
+    Color32::from_rgb(38, 38, 42),
+    Color32::from_rgb(255, 255, 255),
+    Color32::from_rgb(212, 212, 216),
+    Color32::from_rgb(45, 108, 245),
+    Color32::from_rgb(74, 222, 128),
+    Color32::from_rgb(248, 113, 113),
+    Color32::from_rgb(250, 204, 21),
+    Color32::from_rgb(0, 0, 0),
+    Color32::from_rgb(200, 255, 200),
+    Color32::from_rgb(255, 150, 150),
+];
+
+/// Preset the roles resolve against, as `ThemePreset as u8`
+static PRESET: AtomicU8 = AtomicU8::new(ThemePreset::Dark as u8);
+
+/// Colors of `preset`
+pub fn palette(preset: ThemePreset) -> &'static Palette {
+    match preset {
+        ThemePreset::Dark => &DARK,
+        ThemePreset::HighContrast => &HIGH_CONTRAST,
+    }
+}
+
+fn current_preset() -> ThemePreset {
+    if PRESET.load(Ordering::Relaxed) == ThemePreset::HighContrast as u8 {
+        ThemePreset::HighContrast
+    } else {
+        ThemePreset::Dark
+    }
+}
+
+impl ThemeColor {
+    /// This role in the current preset
+    pub fn color(self) -> Color32 {
+        self.in_preset(current_preset())
+    }
+
+    pub fn in_preset(self, preset: ThemePreset) -> Color32 {
+        palette(preset)[self as usize]
+    }
+}
+
+impl From<ThemeColor> for Color32 {
+    fn from(color: ThemeColor) -> Self {
+        color.color()
+    }
+}
+
+/// WCAG contrast ratio of two colors, from 1 (same) to 21 (black on white)
+pub fn contrast_ratio(a: Color32, b: Color32) -> f32 {
+    fn luminance(c: Color32) -> f32 {
+        let channel = |v: u8| {
+            let v = v as f32 / 255.0;
+            if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+        };
+        0.2126 * channel(c.r()) + 0.7152 * channel(c.g()) + 0.0722 * channel(c.b())
+    }
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
 
 pub const PANEL_ROUNDING: CornerRadius = CornerRadius::same(6);
 pub const PANEL_PADDING: Vec2 = Vec2::new(12.0, 8.0);
 
-/// Apply the dark theme to an egui context
-pub fn apply_theme(ctx: &egui::Context) {
+/// Apply `preset` to an egui context and to the color roles
+pub fn apply_theme(ctx: &egui::Context, preset: ThemePreset) {
+    PRESET.store(preset as u8, Ordering::Relaxed);
+    let high_contrast = preset == ThemePreset::HighContrast;
+    let stroke_width = if high_contrast { 2.0 } else { 1.0 };
     let mut style = (*ctx.style()).clone();
 
     style.visuals.dark_mode = true;
-    style.visuals.panel_fill = BG_PRIMARY;
-    style.visuals.window_fill = BG_SECONDARY;
-    style.visuals.extreme_bg_color = TERMINAL_BG;
+    style.visuals.panel_fill = BG_PRIMARY.color();
+    style.visuals.window_fill = BG_SECONDARY.color();
+    style.visuals.extreme_bg_color = TERMINAL_BG.color();
+    style.visuals.override_text_color = high_contrast.then(|| TEXT_PRIMARY.color());
+
+    style.visuals.widgets.inactive.bg_fill = BG_SURFACE.color();
+    style.visuals.widgets.inactive.fg_stroke = Stroke::new(stroke_width, TEXT_SECONDARY);
+    style.visuals.widgets.hovered.bg_fill = BG_SURFACE.color();
+    style.visuals.widgets.hovered.fg_stroke = Stroke::new(stroke_width, TEXT_PRIMARY);
+    style.visuals.widgets.active.bg_fill = ACCENT.color();
+    style.visuals.widgets.active.fg_stroke = Stroke::new(stroke_width, TEXT_PRIMARY);
 
-    style.visuals.widgets.inactive.bg_fill = BG_SURFACE;
-    style.visuals.widgets.inactive.fg_stroke = Stroke::new(1.0, TEXT_SECONDARY);
-    style.visuals.widgets.hovered.bg_fill = BG_SURFACE;
-    style.visuals.widgets.hovered.fg_stroke = Stroke::new(1.0, TEXT_PRIMARY);
-    style.visuals.widgets.active.bg_fill = ACCENT;
-    style.visuals.widgets.active.fg_stroke = Stroke::new(1.0, TEXT_PRIMARY);
+    style.visuals.selection.bg_fill = ACCENT.color().linear_multiply(0.4);
+    style.visuals.selection.stroke = Stroke::new(stroke_width, ACCENT);
 
-    style.visuals.selection.bg_fill = ACCENT.linear_multiply(0.4);
-    style.visuals.selection.stroke = Stroke::new(1.0, ACCENT);
+    if high_contrast {
+        // Outlined controls, and a bright outline on the hovered or
+        // focused one (egui draws focused widgets with the hovered style)
+        style.visuals.widgets.noninteractive.bg_stroke = Stroke::new(1.0, TEXT_SECONDARY);
+        style.visuals.widgets.noninteractive.fg_stroke = Stroke::new(1.0, TEXT_PRIMARY);
+        style.visuals.widgets.inactive.bg_stroke = Stroke::new(1.0, TEXT_SECONDARY);
+        style.visuals.widgets.hovered.bg_stroke = Stroke::new(2.0, WARNING);
+        style.visuals.widgets.active.bg_stroke = Stroke::new(2.0, WARNING);
+        style.visuals.selection.bg_fill = ACCENT.color();
+        style.visuals.selection.stroke = Stroke::new(stroke_width, TEXT_PRIMARY);
+    }
 
     style.spacing.item_spacing = Vec2::new(8.0, 6.0);
 
     ctx.set_style(style);
 }
+
+/// Apply the UI scale and screen reader settings. The scale is a zoom
+/// factor, so text and layout grow together.
+pub fn apply_accessibility(ctx: &egui::Context, ui: &UiConfig) {
+    ctx.set_zoom_factor(ui.scale);
+    ctx.options_mut(|options| options.screen_reader = ui.screen_reader);
+}
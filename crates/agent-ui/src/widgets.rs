@@ -0,0 +1,26 @@
+//! Small widgets shared by the panels.
+//!
+//! Icon buttons and unlabeled text fields mean nothing to a screen reader
+//! on their own: these helpers give them a name, announced when they are
+//! focused or clicked.
+
+use egui::{Response, Ui, WidgetInfo, WidgetType};
+
+/// A small button showing `icon`, named `label` on hover and for screen
+/// readers
+pub fn icon_button(ui: &mut Ui, enabled: bool, icon: &str, label: &str) -> Response {
+    let response = ui.add_enabled(enabled, egui::Button::new(icon).small()).on_hover_text(label);
+    response.widget_info(|| WidgetInfo::labeled(WidgetType::Button, enabled, label));
+    response
+}
+
+/// Name a text field for screen readers, which otherwise only hear its
+/// hint text
+pub fn name_field(response: &Response, label: &str) {
+    response.widget_info(|| {
+        let mut info = WidgetInfo::new(WidgetType::TextEdit);
+        info.enabled = response.enabled();
+        info.label = Some(label.to_string());
+        info
+    });
+}
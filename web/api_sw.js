@@ -0,0 +1,125 @@
+/**
+ * Service Worker — the local OpenAI-compatible endpoint
+ *
+ * Same-origin fetches to `v1/chat/completions` and `v1/models` under the
+ * worker's scope are handed to an app page that turned the endpoint on; it
+ * answers them with the configured provider and its stored key, which
+ * never come here. Requests made by a serving page itself, and any other
+ * request, go to the network as usual. A page that stops answering for
+ * FORWARD_TIMEOUT_MS fails the request with 504.
+ *
+ * Protocol:
+ *   Page → SW: { type: "serve", on }              (this page answers requests, or stops)
+ *   SW → Page: { type: "who_serves" }             (after a restart, pages that serve say so again)
+ *   SW → Page: { type: "api_request", method, path, body } with a MessagePort
+ *   Page → SW, on the port: { type: "reply", status, body }
+ *                         or { type: "stream" }, then { type: "chunk", text }…, { type: "end" }
+ */
+
+const ROUTES = ['v1/chat/completions', 'v1/models'];
+
+/** Longest wait for the page's reply, or between two chunks of a stream */
+const FORWARD_TIMEOUT_MS = 120000;
+
+/** Ids of the window clients answering requests */
+const serving = new Set();
+
+self.addEventListener('install', () => self.skipWaiting());
+
+// Take over pages already open, so they can use the endpoint without a reload
+self.addEventListener('activate', (event) => event.waitUntil(self.clients.claim()));
+
+self.addEventListener('message', (event) => {
+    const data = event.data || {};
+    if (data.type !== 'serve' || !event.source) return;
+    if (data.on) serving.add(event.source.id);
+    else serving.delete(event.source.id);
+});
+
+self.addEventListener('fetch', (event) => {
+    const url = new URL(event.request.url);
+    if (url.origin !== self.location.origin) return;
+    // The page answering must reach its own provider, even one at this origin
+    if (serving.has(event.clientId)) return;
+    const scope = new URL(self.registration.scope).pathname;
+    const route = ROUTES.find((r) => url.pathname === scope + r);
+    if (!route) return;
+    event.respondWith(forward(event.request, '/' + route));
+});
+
+function jsonError(status, message) {
+    return new Response(JSON.stringify({ error: { message, type: 'api_error' } }), {
+        status,
+        headers: { 'Content-Type': 'application/json' },
+    });
+}
+
+/**
+ * A page answering requests. The worker may have been stopped and started
+ * since pages said so, losing the list; then they are asked again.
+ */
+async function server() {
+    if (serving.size === 0) {
+        const windows = await self.clients.matchAll({ type: 'window' });
+        windows.forEach((w) => w.postMessage({ type: 'who_serves' }));
+        await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+    for (const id of serving) {
+        const client = await self.clients.get(id);
+        if (client) return client;
+        serving.delete(id);
+    }
+    return null;
+}
+
+async function forward(request, path) {
+    const client = await server();
+    if (!client) {
+        return jsonError(503, 'No agent page with the local endpoint turned on is open');
+    }
+    const body = request.method === 'GET' || request.method === 'HEAD' ? '' : await request.text();
+    const channel = new MessageChannel();
+    const encoder = new TextEncoder();
+    return new Promise((resolve) => {
+        let controller = null;
+        let timer = null;
+        const finish = () => {
+            clearTimeout(timer);
+            channel.port1.close();
+        };
+        const wait = () => {
+            clearTimeout(timer);
+            timer = setTimeout(() => {
+                finish();
+                if (controller) controller.error(new Error('The agent page stopped answering'));
+                else resolve(jsonError(504, 'The agent page did not answer in time'));
+            }, FORWARD_TIMEOUT_MS);
+        };
+        channel.port1.onmessage = ({ data }) => {
+            wait();
+            switch (data.type) {
+                case 'reply':
+                    resolve(new Response(data.body, {
+                        status: data.status,
+                        headers: { 'Content-Type': 'application/json' },
+                    }));
+                    finish();
+                    break;
+                case 'stream':
+                    resolve(new Response(new ReadableStream({ start: (c) => { controller = c; } }), {
+                        headers: { 'Content-Type': 'text/event-stream', 'Cache-Control': 'no-cache' },
+                    }));
+                    break;
+                case 'chunk':
+                    controller.enqueue(encoder.encode(data.text));
+                    break;
+                case 'end':
+                    controller.close();
+                    finish();
+                    break;
+            }
+        };
+        wait();
+        client.postMessage({ type: 'api_request', method: request.method, path, body }, [channel.port2]);
+    });
+}
@@ -0,0 +1,81 @@
+/**
+ * Module Worker — local sentence embeddings via transformers.js
+ *
+ * Runs a small sentence-transformer fully in the browser so semantic features
+ * never send workspace content to a remote API.
+ *
+ * Protocol:
+ *   Main thread → Worker: EmbeddingsCommand (JSON via postMessage)
+ *   Worker → Main thread: EmbeddingsEvent (JSON via postMessage)
+ */
+
+import { pipeline } from 'https://cdn.jsdelivr.net/npm/@huggingface/transformers@3';
+
+let extractor = null;
+let loading = null;
+
+function sendEvent(event) {
+    self.postMessage(event);
+}
+
+/**
+ * Load the feature-extraction pipeline, reporting download progress.
+ */
+async function init(model) {
+    if (loading) return loading;
+    loading = pipeline('feature-extraction', model, {
+        progress_callback: (p) => {
+            if (p.status === 'progress') {
+                sendEvent({
+                    type: 'Progress',
+                    progress: (p.progress || 0) / 100,
+                    text: `Downloading ${p.file || model}`,
+                });
+            }
+        },
+    }).then((pipe) => {
+        extractor = pipe;
+        sendEvent({ type: 'Ready' });
+        console.log('[Embeddings] Model loaded:', model);
+        return pipe;
+    }).catch((error) => {
+        loading = null;
+        sendEvent({ type: 'Error', id: 0, message: `Model load failed: ${error.message}` });
+        throw error;
+    });
+    return loading;
+}
+
+async function embed(id, texts) {
+    try {
+        if (!extractor) {
+            if (!loading) throw new Error('Embeddings model not initialised');
+            await loading;
+        }
+        const output = await extractor(texts, { pooling: 'mean', normalize: true });
+        sendEvent({ type: 'Vectors', id: id, vectors: output.tolist() });
+    } catch (error) {
+        sendEvent({ type: 'Error', id: id, message: `Embedding failed: ${error.message}` });
+    }
+}
+
+// ─── Message handler ─────────────────────────────────────────
+
+self.onmessage = async function(event) {
+    const msg = event.data;
+
+    switch (msg.type) {
+        case 'Init':
+            init(msg.model).catch(() => {});
+            break;
+
+        case 'Embed':
+            await embed(msg.id, msg.texts);
+            break;
+
+        default:
+            console.warn('[Embeddings] Unknown command:', msg.type);
+    }
+};
+
+console.log('[Embeddings] Worker loaded');
@@ -0,0 +1,88 @@
+/**
+ * Web Worker — sandboxed JavaScript for the run_js tool
+ *
+ * Each script gets a worker of its own, which the main thread terminates
+ * when the script finishes or runs out of time. Workers have no DOM, and the
+ * common network and storage APIs are removed before the script runs. This
+ * is not isolation: the worker shares the app's origin, so dynamic import()
+ * and origin storage (OPFS through navigator.storage) remain reachable.
+ *
+ * Protocol:
+ *   Main thread → Worker: the script's source (a string)
+ *   Worker → Main thread: JsRun { console, result, error }
+ */
+
+const post = self.postMessage.bind(self);
+
+const BLOCKED = [
+    'fetch', 'XMLHttpRequest', 'WebSocket', 'EventSource', 'WebTransport', 'BroadcastChannel',
+    'importScripts', 'indexedDB', 'caches', 'Worker', 'SharedWorker', 'postMessage', 'close',
+];
+
+function block() {
+    for (const name of BLOCKED) {
+        try {
+            Object.defineProperty(self, name, { value: undefined, writable: false, configurable: false });
+        } catch (_) {
+            // Not present in this browser
+        }
+    }
+}
+
+/**
+ * A value as text: strings as they are, everything else as (pretty) JSON
+ * where possible.
+ */
+function show(value) {
+    if (typeof value === 'string') return value;
+    if (value instanceof Error) return `${value.name}: ${value.message}`;
+    if (typeof value === 'function' || typeof value === 'symbol' || typeof value === 'bigint') {
+        return String(value);
+    }
+    try {
+        const json = JSON.stringify(value, null, 2);
+        return json === undefined ? String(value) : json;
+    } catch (_) {
+        return String(value);
+    }
+}
+
+function captureConsole(lines) {
+    const levels = { log: '', info: '', debug: '', warn: '[warn] ', error: '[error] ' };
+    for (const [level, prefix] of Object.entries(levels)) {
+        console[level] = (...args) => lines.push(prefix + args.map(show).join(' '));
+    }
+    console.table = (data) => lines.push(show(data));
+}
+
+/**
+ * Evaluate `code` as a script, so the value of its last expression is the
+ * result. Code using top-level await is run as the body of an async
+ * function instead, where `return` gives the result.
+ */
+async function evaluate(code) {
+    try {
+        return await (0, eval)(code);
+    } catch (error) {
+        if (error instanceof SyntaxError && /\bawait\b/.test(code)) {
+            const AsyncFunction = (async () => {}).constructor;
+            return await new AsyncFunction(code)();
+        }
+        throw error;
+    }
+}
+
+self.onmessage = async (event) => {
+    self.onmessage = null;
+    const lines = [];
+    captureConsole(lines);
+    block();
+    const run = { console: lines, result: null, error: null };
+    try {
+        const value = await evaluate(String(event.data));
+        run.result = value === undefined ? null : show(value);
+    } catch (error) {
+        run.error = error instanceof Error ? `${error.name}: ${error.message}` : show(error);
+    }
+    post(run);
+};
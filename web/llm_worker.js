@@ -0,0 +1,175 @@
+/**
+ * Web Worker — LLM network I/O
+ *
+ * Performs provider HTTP requests off the main thread. Streaming responses
+ * (server-sent chat completion chunks) are parsed here and only compact,
+ * batched events are posted back, so long streams don't compete with egui
+ * rendering.
+ *
+ * Protocol:
+ *   Main thread → Worker: LlmWorkerCommand (JSON via postMessage)
+ *   Worker → Main thread: LlmWorkerEvent (JSON via postMessage)
+ */
+
+// Batch streamed text until this much time has passed or this much arrived
+const FLUSH_MS = 50;
+const FLUSH_CHARS = 2048;
+
+const controllers = new Map();
+
+function sendEvent(event) {
+    self.postMessage(event);
+}
+
+/**
+ * Retry-After header in milliseconds (seconds form only).
+ */
+function retryAfterMs(response) {
+    const value = response.headers.get('retry-after');
+    const secs = value ? parseInt(value.trim(), 10) : NaN;
+    return Number.isFinite(secs) ? secs * 1000 : null;
+}
+
+async function fetchRequest(msg) {
+    const controller = new AbortController();
+    controllers.set(msg.id, controller);
+    try {
+        const response = await fetch(msg.url, {
+            method: msg.method,
+            headers: msg.headers,
+            body: msg.body,
+            signal: controller.signal,
+        });
+        if (!msg.stream || !response.ok) {
+            const body = await response.text();
+            sendEvent({
+                type: 'Response',
+                id: msg.id,
+                status: response.status,
+                retry_after: retryAfterMs(response),
+                body: body,
+            });
+            return;
+        }
+        await readStream(msg.id, response);
+    } catch (error) {
+        if (error.name !== 'AbortError') {
+            sendEvent({ type: 'Error', id: msg.id, message: error.message || String(error) });
+        }
+    } finally {
+        controllers.delete(msg.id);
+    }
+}
+
+/**
+ * Parse an SSE chat completions stream into Delta / ToolCallDelta /
+ * Reasoning / Usage events.
+ */
+async function readStream(id, response) {
+    const reader = response.body.getReader();
+    const decoder = new TextDecoder();
+    let buffer = '';
+    let text = '';
+    let reasoning = '';
+    let lastFlush = Date.now();
+    // A chunk carried a finish reason
+    let finished = false;
+
+    const flush = () => {
+        if (reasoning) {
+            sendEvent({ type: 'Reasoning', id: id, text: reasoning });
+            reasoning = '';
+        }
+        if (text) {
+            sendEvent({ type: 'Delta', id: id, text: text });
+            text = '';
+        }
+        lastFlush = Date.now();
+    };
+
+    const handleLine = (line) => {
+        if (!line.startsWith('data:')) return false;
+        const data = line.slice(5).trim();
+        if (data === '[DONE]') return true;
+        let chunk;
+        try {
+            chunk = JSON.parse(data);
+        } catch (_) {
+            return false;
+        }
+        // Sent with stream_options.include_usage, often in a chunk of its own
+        if (chunk.usage) {
+            flush();
+            sendEvent({ type: 'Usage', id: id, usage: chunk.usage });
+        }
+        const choice = chunk.choices && chunk.choices[0];
+        if (choice && choice.finish_reason) finished = true;
+        const delta = choice && choice.delta;
+        if (!delta) return false;
+        const thought = delta.reasoning_content || delta.reasoning;
+        if (thought) reasoning += thought;
+        if (delta.content) text += delta.content;
+        for (const call of delta.tool_calls || []) {
+            // Keep text and tool call parts in order
+            flush();
+            sendEvent({
+                type: 'ToolCallDelta',
+                id: id,
+                index: call.index || 0,
+                call_id: call.id || null,
+                name: (call.function && call.function.name) || null,
+                arguments: (call.function && call.function.arguments) || '',
+            });
+        }
+        return false;
+    };
+
+    let done = false;
+    while (!done) {
+        const { value, done: ended } = await reader.read();
+        if (ended) break;
+        buffer += decoder.decode(value, { stream: true });
+        const lines = buffer.split('\n');
+        buffer = lines.pop();
+        for (const line of lines) {
+            if (handleLine(line.trim())) {
+                done = true;
+                break;
+            }
+        }
+        if (Date.now() - lastFlush >= FLUSH_MS || text.length + reasoning.length >= FLUSH_CHARS) {
+            flush();
+        }
+    }
+    if (!done && buffer) done = handleLine(buffer.trim());
+    flush();
+    if (!done && !finished) {
+        // Closed without [DONE] or a finish reason: the reply broke off
+        sendEvent({ type: 'Error', id: id, message: 'The stream ended before the reply was complete' });
+        return;
+    }
+    sendEvent({ type: 'Done', id: id });
+}
+
+// ─── Message handler ─────────────────────────────────────────
+
+self.onmessage = function(event) {
+    const msg = event.data;
+
+    switch (msg.type) {
+        case 'Fetch':
+            fetchRequest(msg);
+            break;
+
+        case 'Abort': {
+            const controller = controllers.get(msg.id);
+            if (controller) controller.abort();
+            break;
+        }
+
+        default:
+            console.warn('[LLM Worker] Unknown command:', msg.type);
+    }
+};
+
+console.log('[LLM Worker] Network worker loaded');
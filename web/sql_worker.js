@@ -0,0 +1,45 @@
+/**
+ * Web Worker — SQLite (sql.js) for the sql tool
+ *
+ * Each call gets a worker of its own, which the main thread terminates when
+ * the statements finish or run out of time. The database file comes with
+ * the call and goes back with the reply; nothing is kept here.
+ *
+ * Protocol:
+ *   Main thread → Worker: { sql, image: Uint8Array | null }
+ *   Worker → Main thread: { results: [{ columns, values }], changes, image }
+ *                         or { error }
+ */
+
+const SQL_JS = 'https://cdn.jsdelivr.net/npm/sql.js@1.10.3/dist/';
+
+importScripts(SQL_JS + 'sql-wasm.js');
+
+const ready = initSqlJs({ locateFile: (file) => SQL_JS + file });
+
+/**
+ * A cell as JSON: blobs are described rather than sent.
+ */
+function cell(value) {
+    if (value instanceof Uint8Array) return `<blob, ${value.length} bytes>`;
+    return value;
+}
+
+self.onmessage = async (event) => {
+    let db = null;
+    try {
+        const SQL = await ready;
+        db = event.data.image ? new SQL.Database(event.data.image) : new SQL.Database();
+        const results = db.exec(event.data.sql).map((result) => ({
+            columns: result.columns,
+            values: result.values.map((row) => row.map(cell)),
+        }));
+        const changes = db.getRowsModified();
+        const image = db.export();
+        self.postMessage({ results, changes, image }, [image.buffer]);
+    } catch (error) {
+        self.postMessage({ error: error && error.message ? error.message : String(error) });
+    } finally {
+        if (db) db.close();
+    }
+};
@@ -48,33 +48,76 @@ async function initWasmer() {
     }
 }
 
+/**
+ * Standard input of a process started with `stdin: true`. Input that
+ * arrives before the process has spawned is buffered, and `closed`
+ * resolves once `CloseStdin` is received.
+ */
+function createStdinPipe() {
+    const encoder = new TextEncoder();
+    const buffered = [];
+    let writer = null;
+    let isClosed = false;
+    let resolveClosed;
+    const closed = new Promise(resolve => { resolveClosed = resolve; });
+
+    return {
+        closed,
+        write(data) {
+            if (isClosed) return;
+            const bytes = encoder.encode(data);
+            if (writer) writer.write(bytes);
+            else buffered.push(bytes);
+        },
+        close() {
+            if (isClosed) return;
+            isClosed = true;
+            if (writer) writer.close();
+            resolveClosed();
+        },
+        attach(stream) {
+            writer = stream.getWriter();
+            for (const bytes of buffered) writer.write(bytes);
+            buffered.length = 0;
+            if (isClosed) writer.close();
+        },
+    };
+}
+
 /**
  * Execute a bash command via WASIX.
  * Falls back to a simple command parser if Wasmer-JS is not available.
  */
-async function execBash(id, cmd, timeoutMs) {
+async function execBash(id, cmd, opts) {
+    // Registered before spawning so input sent right after the command
+    // isn't lost
+    const process = { instance: null, stdin: opts.stdin ? createStdinPipe() : null };
+    runningProcesses.set(id, process);
     try {
         // If Wasmer-JS with full WASIX bash is available, use it
         if (typeof Wasmer !== 'undefined' && Wasmer.init) {
-            await execBashWasmer(id, cmd, timeoutMs);
+            await execBashWasmer(id, cmd, opts, process);
             return;
         }
 
         // Fallback: simple command simulation
-        await execBashFallback(id, cmd);
+        await execBashFallback(id, cmd, opts);
     } catch (error) {
         sendEvent({
             type: 'Error',
             id: id,
             message: `Execution error: ${error.message}`,
         });
+    } finally {
+        runningProcesses.delete(id);
     }
 }
 
 /**
  * Execute via the Wasmer-JS WASIX runtime.
  */
-async function execBashWasmer(id, cmd, timeoutMs) {
+async function execBashWasmer(id, cmd, opts, process) {
+    const timeoutMs = opts.timeoutMs;
     try {
         // Initialize Wasmer if needed
         await Wasmer.init();
@@ -83,13 +126,17 @@ async function execBashWasmer(id, cmd, timeoutMs) {
         const bash = await Wasmer.spawn('sharrattj/bash', {
             args: ['-c', cmd],
             stdin: { mode: 'pipe' },
+            ...(opts.cwd ? { cwd: opts.cwd } : {}),
+            env: opts.env,
         });
+        process.instance = bash;
 
         // Set up timeout if specified
         let timeoutHandle = null;
         if (timeoutMs) {
             timeoutHandle = setTimeout(() => {
                 bash.kill();
+                if (process.stdin) process.stdin.close();
                 sendEvent({
                     type: 'Error',
                     id: id,
@@ -98,6 +145,12 @@ async function execBashWasmer(id, cmd, timeoutMs) {
             }, timeoutMs);
         }
 
+        // Feed stdin until it is closed; waiting closes it
+        if (process.stdin) {
+            process.stdin.attach(bash.stdin);
+            await process.stdin.closed;
+        }
+
         // Read stdout
         const output = await bash.wait();
         if (timeoutHandle) clearTimeout(timeoutHandle);
@@ -126,7 +179,7 @@ async function execBashWasmer(id, cmd, timeoutMs) {
  * Fallback command execution — simulates basic shell behavior
  * when the full Wasmer-JS WASIX runtime is not available.
  */
-async function execBashFallback(id, cmd) {
+async function execBashFallback(id, cmd, opts) {
     const parts = cmd.trim().split(/\s+/);
     const command = parts[0];
     const args = parts.slice(1);
@@ -152,7 +205,7 @@ async function execBashFallback(id, cmd) {
             break;
 
         case 'pwd':
-            stdout = '/home/agent\n';
+            stdout = (opts.cwd || '/home/agent') + '\n';
             break;
 
         case 'ls':
@@ -204,12 +257,26 @@ async function execBashFallback(id, cmd) {
  */
 function cancelExec(id) {
     const process = runningProcesses.get(id);
-    if (process && process.kill) {
-        process.kill();
+    if (process) {
+        if (process.instance) process.instance.kill();
+        // Unblock a command still waiting for its input
+        if (process.stdin) process.stdin.close();
         runningProcesses.delete(id);
     }
 }
 
+/**
+ * Write to, or close, the standard input of a running process.
+ */
+function stdinOf(id) {
+    const process = runningProcesses.get(id);
+    if (!process || !process.stdin) {
+        console.warn('[Worker] No open stdin for process', id);
+        return null;
+    }
+    return process.stdin;
+}
+
 // ─── Message handler ─────────────────────────────────────────
 
 self.onmessage = async function(event) {
@@ -221,17 +288,33 @@ self.onmessage = async function(event) {
             break;
 
         case 'ExecBash':
-            await execBash(msg.id, msg.cmd, msg.timeout_ms || null);
+            await execBash(msg.id, msg.cmd, {
+                timeoutMs: msg.timeout_ms || null,
+                cwd: msg.cwd || null,
+                env: Object.fromEntries(msg.env || []),
+                stdin: msg.stdin || false,
+            });
             break;
 
         case 'CancelExec':
             cancelExec(msg.id);
             break;
 
-        case 'WriteStdin':
-            // Will be implemented with streaming support
-            console.warn('[Worker] WriteStdin not yet implemented');
+        case 'Ping':
+            sendEvent({ type: 'Pong', seq: msg.seq });
+            break;
+
+        case 'WriteStdin': {
+            const stdin = stdinOf(msg.id);
+            if (stdin) stdin.write(msg.data);
             break;
+        }
+
+        case 'CloseStdin': {
+            const stdin = stdinOf(msg.id);
+            if (stdin) stdin.close();
+            break;
+        }
 
         default:
             console.warn('[Worker] Unknown command:', msg.type);